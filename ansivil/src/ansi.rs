@@ -1,10 +1,9 @@
 use image::{ImageBuffer, Rgba};
 use std::os::fd::FromRawFd;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{fs::File, io::Write, path::Path};
 
-use crate::wgpu_renderer::WgpuRenderer;
 use smithay::{
     backend::{
         allocator::{
@@ -13,37 +12,122 @@ use smithay::{
         },
         egl::{EGLContext, EGLDisplay},
         input::InputEvent,
-        ratatui::{self, RatatuiEvent, RatatuiInputBackend, RatatuiMouseEvent},
+        ratatui::{
+            self, toplevel_title, Clipboard, RatatuiCursor, RatatuiCursorRenderElement, RatatuiEvent,
+            RatatuiInputBackend, RatatuiMouseEvent,
+        },
         renderer::{
-            damage::OutputDamageTracker, element::surface::WaylandSurfaceRenderElement, gles::GlesRenderer,
-            Bind, ImportDma,
+            damage::OutputDamageTracker, gles::GlesRenderer, wgpu::WgpuRenderer, Bind, ImportAll, ImportDma,
+            Renderer,
         },
     },
     output::{Mode, Output, PhysicalProperties, Subpixel},
     reexports::{calloop::EventLoop, wayland_server::DisplayHandle},
-    utils::Transform,
+    utils::{Physical, Point, Size, Transform},
+    wayland::selection::SelectionTarget,
 };
-use tracing::{debug, error};
+use tracing::{debug, error, info, warn};
 
+use crate::software::SoftwarePipeline;
 use crate::{CalloopData, Smallvil};
 use gpu_ansi_encoder::GpuAnsiEncoder;
 
 use tokio::sync::mpsc;
 
+/// Background colour of the output
+pub const CLEAR_COLOR: [f32; 4] = [0.1, 0.1, 0.4, 1.0];
+/// Path debug screenshots are saved to
+const SCREENSHOT_PATH: &str = "/tmp/screenshot.png";
+/// Title of the terminal while no window is focused
+const DEFAULT_TITLE: &str = "ansivil";
+
 struct AnsiHandler {
-    renderer: GlesRenderer,
-    wgpu_renderer: WgpuRenderer,
-    allocator: GbmAllocator<Arc<File>>,
+    pipeline: Pipeline,
     output: Output,
     damage_tracker: OutputDamageTracker,
     backend: ratatui::RatatuiBackend,
-    tx: mpsc::Sender<Option<wgpu::Texture>>,
     debug_frame: Option<u32>,
     frame_count: u32,
+    /// Show the cursor using the mouse pointer of the terminal instead of drawing it
+    terminal_cursor: bool,
+}
+
+/// Pointer cursor drawn into a frame
+#[derive(Clone, Copy)]
+pub struct CursorOverlay<'a> {
+    pub cursor: &'a RatatuiCursor,
+    /// Position of the hotspot on the output
+    pub location: Point<f64, Physical>,
+    /// Size of a terminal cell in pixels of the output
+    pub cell_size: Size<i32, Physical>,
+}
+
+impl CursorOverlay<'_> {
+    pub fn render_elements<R>(&self, renderer: &mut R) -> Vec<RatatuiCursorRenderElement<R>>
+    where
+        R: Renderer + ImportAll,
+        R::TextureId: Clone + 'static,
+    {
+        self.cursor
+            .render_elements(renderer, self.location, self.cell_size, 1.0, 1.0)
+    }
+}
+
+/// Renders frames and encodes them for the terminal
+enum Pipeline {
+    /// Renders with GLES and encodes with wgpu
+    Gpu(Box<GpuPipeline>),
+    /// Renders with pixman and encodes on the CPU
+    Software(SoftwarePipeline),
+}
+
+impl Pipeline {
+    fn render(
+        &mut self,
+        output: &Output,
+        state: &Smallvil,
+        damage_tracker: &mut OutputDamageTracker,
+        cursor: Option<CursorOverlay<'_>>,
+        screenshot: Option<&Path>,
+    ) {
+        match self {
+            Pipeline::Gpu(pipeline) => pipeline.render(output, state, damage_tracker, cursor, screenshot),
+            Pipeline::Software(pipeline) => {
+                pipeline.render(output, state, damage_tracker, cursor, screenshot)
+            }
+        }
+    }
+
+    /// Make the next frame redraw every cell of the terminal
+    fn reset(&mut self) {
+        match self {
+            Pipeline::Gpu(pipeline) => {
+                let _ = pipeline.tx.try_send(None);
+            }
+            Pipeline::Software(pipeline) => pipeline.reset(),
+        }
+    }
+}
+
+struct GpuPipeline {
+    renderer: GlesRenderer,
+    wgpu_renderer: WgpuRenderer,
+    allocator: GbmAllocator<Arc<File>>,
+    tx: mpsc::Sender<Option<wgpu::Texture>>,
 }
 
 impl AnsiHandler {
     fn handle_event(&mut self, event: RatatuiEvent, state: &mut Smallvil, display: &mut DisplayHandle) {
+        for (target, data) in state.terminal_selections.drain(..) {
+            let clipboard = match target {
+                SelectionTarget::Clipboard => Clipboard::Clipboard,
+                SelectionTarget::Primary => Clipboard::Primary,
+            };
+            if let Err(err) = self.backend.set_clipboard(clipboard, &data) {
+                error!("Failed to copy selection to the terminal: {}", err);
+            }
+        }
+
         match event {
             RatatuiEvent::Redraw => {
                 self.redraw(state, display);
@@ -59,36 +143,198 @@ impl AnsiHandler {
                     None,
                     None,
                 );
-                let _ = self.tx.try_send(None); // Reset diffing on resize
+                self.pipeline.reset(); // Reset diffing on resize
             }
             event @ RatatuiEvent::Key { .. } => {
                 debug!("Ratatui Key Event: {:?}", event);
-                state
-                    .process_input_event::<RatatuiInputBackend>(InputEvent::Keyboard { event: event.into() });
+                state.process_input_event::<RatatuiInputBackend>(
+                    InputEvent::Keyboard { event: event.into() },
+                    &self.output,
+                );
+            }
+            // typed in one go on press, the keymap only holds the key while typing
+            RatatuiEvent::Keysym { keysym, kind } => {
+                debug!("Ratatui Keysym Event: {:?} {:?}", keysym, kind);
+                if kind != crossterm::event::KeyEventKind::Release {
+                    state.type_keysym(keysym);
+                }
             }
             RatatuiEvent::Mouse(event) => {
                 debug!("Ratatui Mouse Event: {:?}", event);
                 let e = RatatuiMouseEvent::new(event, self.backend.window_size());
-                let event = match event.kind {
-                    crossterm::event::MouseEventKind::Down(_) | crossterm::event::MouseEventKind::Up(_) => {
-                        InputEvent::PointerButton { event: e }
-                    }
-                    crossterm::event::MouseEventKind::Drag(_) | crossterm::event::MouseEventKind::Moved => {
-                        InputEvent::PointerMotionAbsolute { event: e }
-                    }
-                    crossterm::event::MouseEventKind::ScrollDown
-                    | crossterm::event::MouseEventKind::ScrollUp
-                    | crossterm::event::MouseEventKind::ScrollLeft
-                    | crossterm::event::MouseEventKind::ScrollRight => InputEvent::PointerAxis { event: e },
-                };
-                state.process_input_event::<RatatuiInputBackend>(event);
+                state.process_input_event(pointer_event(event.kind, e), &self.output);
             }
+            RatatuiEvent::Paste(text) => {
+                debug!("Ratatui Paste Event: {} bytes", text.len());
+                state.paste(text);
+            }
+            // only emitted by remote terminals, see `crate::remote`
+            RatatuiEvent::Detached => {}
         }
     }
 
     fn redraw(&mut self, state: &mut Smallvil, display: &mut DisplayHandle) {
-        let size = self.output.current_mode().unwrap().size;
-        //eprintln!("redraw, size = {size:?}");
+        self.frame_count += 1;
+        let screenshot = (self.debug_frame == Some(self.frame_count)).then_some(Path::new(SCREENSHOT_PATH));
+
+        if std::mem::take(&mut state.bell_pending) {
+            if let Err(err) = self.backend.bell() {
+                error!("Failed to ring the terminal bell: {}", err);
+            }
+        }
+        self.update_title(state);
+
+        let cursor = if self.terminal_cursor {
+            if let Err(err) = self.backend.set_mouse_shape(state.cursor.terminal_shape()) {
+                error!("Failed to set the mouse pointer shape: {}", err);
+            }
+            None
+        } else {
+            state.seat.get_pointer().map(|pointer| CursorOverlay {
+                cursor: &state.cursor,
+                location: pointer.current_location().to_physical(1.0),
+                cell_size: self.backend.cell_size(),
+            })
+        };
+
+        self.pipeline
+            .render(&self.output, state, &mut self.damage_tracker, cursor, screenshot);
+        if screenshot.is_some() {
+            std::process::exit(0);
+        }
+
+        // Frame callbacks
+        state.space.elements().for_each(|window| {
+            window.send_frame(
+                &self.output,
+                state.start_time.elapsed(),
+                Some(Duration::ZERO),
+                |_, _| Some(self.output.clone()),
+            )
+        });
+
+        state.space.refresh();
+        state.popups.cleanup();
+        let _ = display.flush_clients();
+    }
+
+    /// Show the title of the focused window in the terminal
+    fn update_title(&mut self, state: &Smallvil) {
+        let focus = state
+            .seat
+            .get_keyboard()
+            .and_then(|keyboard| keyboard.current_focus());
+        let title = focus.and_then(|surface| {
+            state
+                .space
+                .elements()
+                .filter_map(|window| window.toplevel())
+                .find(|toplevel| *toplevel.wl_surface() == surface)
+                .and_then(toplevel_title)
+        });
+        if let Err(err) = self.backend.set_title(title.as_deref().unwrap_or(DEFAULT_TITLE)) {
+            error!("Failed to set the terminal title: {}", err);
+        }
+    }
+}
+
+/// Input event for a mouse event of a terminal, `kind` decides whether it is a button, motion or axis event
+pub fn pointer_event(
+    kind: crossterm::event::MouseEventKind,
+    event: RatatuiMouseEvent,
+) -> InputEvent<RatatuiInputBackend> {
+    match kind {
+        crossterm::event::MouseEventKind::Down(_) | crossterm::event::MouseEventKind::Up(_) => {
+            InputEvent::PointerButton { event }
+        }
+        crossterm::event::MouseEventKind::Drag(_) | crossterm::event::MouseEventKind::Moved => {
+            InputEvent::PointerMotionAbsolute { event }
+        }
+        crossterm::event::MouseEventKind::ScrollDown
+        | crossterm::event::MouseEventKind::ScrollUp
+        | crossterm::event::MouseEventKind::ScrollLeft
+        | crossterm::event::MouseEventKind::ScrollRight => InputEvent::PointerAxis { event },
+    }
+}
+
+impl GpuPipeline {
+    fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        // DRM / GBM / EGL / GLES Setup
+        // Manual scan for render node since DrmNode::ty() is acting up
+        let drm_node = (128..136)
+            .map(|i| format!("/dev/dri/renderD{}", i))
+            .filter_map(|path| smithay::backend::drm::DrmNode::from_path(path).ok())
+            .next()
+            .ok_or_else(|| Box::<dyn std::error::Error>::from("No render node found"))?;
+
+        let fd = Arc::new(
+            std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(drm_node.dev_path().unwrap())?,
+        );
+        let gbm_egl = GbmDevice::new(fd.clone())?;
+        let gbm_alloc = GbmDevice::new(fd)?;
+
+        let egl_display = unsafe { EGLDisplay::new(gbm_egl) }?;
+        let egl_context = EGLContext::new(&egl_display)?;
+        let renderer = unsafe { GlesRenderer::new(egl_context) }?;
+        let allocator = GbmAllocator::new(gbm_alloc, GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
+
+        // WGPU Initialization (for encoding)
+        let wgpu_instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::VULKAN,
+            ..Default::default()
+        });
+        let adapter =
+            pollster::block_on(wgpu_instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let (wgpu_device, wgpu_queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default()))?;
+        let wgpu_device = Arc::new(wgpu_device);
+        let wgpu_queue = Arc::new(wgpu_queue);
+
+        let ansi_encoder =
+            pollster::block_on(GpuAnsiEncoder::new(wgpu_device.clone(), wgpu_queue.clone())).unwrap();
+        let wgpu_renderer = WgpuRenderer::new(&wgpu_instance, wgpu_device.clone(), wgpu_queue.clone());
+
+        let (tx, mut rx) = mpsc::channel::<Option<wgpu::Texture>>(2);
+        let ansi_encoder = Arc::new(ansi_encoder);
+
+        tokio::spawn(async move {
+            let mut previous_texture: Option<wgpu::Texture> = None;
+            let mut presenter = Presenter::new();
+
+            while let Some(msg) = rx.recv().await {
+                if let Some(current_texture) = msg {
+                    let ansi_string = ansi_encoder
+                        .ansi_from_texture(previous_texture.as_ref(), &current_texture)
+                        .await
+                        .unwrap();
+                    presenter.present(&ansi_string);
+                    previous_texture = Some(current_texture);
+                } else {
+                    previous_texture = None;
+                }
+            }
+        });
+
+        Ok(GpuPipeline {
+            renderer,
+            wgpu_renderer,
+            allocator,
+            tx,
+        })
+    }
+
+    fn render(
+        &mut self,
+        output: &Output,
+        state: &Smallvil,
+        damage_tracker: &mut OutputDamageTracker,
+        cursor: Option<CursorOverlay<'_>>,
+        screenshot: Option<&Path>,
+    ) {
+        let size = output.current_mode().unwrap().size;
 
         // Allocate a Dmabuf
         let mut dmabuf = match self.allocator.create_buffer(
@@ -109,18 +355,21 @@ impl AnsiHandler {
 
         // Bind and render
         {
+            let cursor_elements = cursor
+                .map(|cursor| cursor.render_elements(&mut self.renderer))
+                .unwrap_or_default();
             let mut target = self.renderer.bind(&mut dmabuf).expect("Failed to bind dmabuf");
 
             smithay::desktop::space::render_output(
-                &self.output,
+                output,
                 &mut self.renderer,
                 &mut target,
                 1.0,
                 0,
                 [&state.space],
-                &[] as &[WaylandSurfaceRenderElement<GlesRenderer>],
-                &mut self.damage_tracker,
-                [0.1f32, 0.1, 0.4, 1.0],
+                &cursor_elements,
+                damage_tracker,
+                CLEAR_COLOR,
             )
             .expect("Failed to render output");
         }
@@ -134,36 +383,52 @@ impl AnsiHandler {
         // Send to encoding task
         let _ = self.tx.try_send(Some(wgpu_texture.wgpu_texture().clone()));
 
-        // Frame callbacks
-        state.space.elements().for_each(|window| {
-            window.send_frame(
-                &self.output,
-                state.start_time.elapsed(),
-                Some(Duration::ZERO),
-                |_, _| Some(self.output.clone()),
-            )
-        });
+        if let Some(path) = screenshot {
+            eprintln!("Saving debug screenshot to {}", path.display());
+            pollster::block_on(save_texture_to_file(
+                self.wgpu_renderer.device(),
+                self.wgpu_renderer.queue(),
+                wgpu_texture.wgpu_texture(),
+                path,
+            ))
+            .expect("Failed to save debug screenshot");
+        }
+    }
+}
 
-        state.space.refresh();
-        state.popups.cleanup();
-        let _ = display.flush_clients();
+/// Writes encoded frames to the terminal
+pub struct Presenter {
+    stdout: File,
+    frames: u32,
+    start: Instant,
+}
 
-        self.frame_count += 1;
-        if let Some(debug_frame) = self.debug_frame {
-            if self.frame_count == debug_frame {
-                eprintln!(
-                    "Saving debug screenshot for frame {} to /tmp/screenshot.png",
-                    debug_frame
-                );
-                pollster::block_on(save_texture_to_file(
-                    self.wgpu_renderer.device(),
-                    self.wgpu_renderer.queue(),
-                    wgpu_texture.wgpu_texture(),
-                    "/tmp/screenshot.png",
-                ))
-                .expect("Failed to save debug screenshot");
-                std::process::exit(0);
-            }
+impl Presenter {
+    pub fn new() -> Self {
+        Presenter {
+            stdout: unsafe { File::from_raw_fd(1) },
+            frames: 0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Write `ansi` as a single synchronized update
+    pub fn present(&mut self, ansi: &str) {
+        const START_BUFFERING: &str = "\x1b[?2026h";
+        const STOP_BUFFERING: &str = "\x1b[?2026l";
+        self.stdout.write_all(START_BUFFERING.as_bytes()).unwrap();
+        self.stdout.write_all(ansi.as_bytes()).unwrap();
+        self.stdout.write_all(STOP_BUFFERING.as_bytes()).unwrap();
+        self.stdout.flush().unwrap();
+
+        self.frames += 1;
+        if self.frames >= 60 {
+            eprintln!(
+                "FPS = {}",
+                self.frames as f64 / self.start.elapsed().as_secs_f64()
+            );
+            self.frames = 0;
+            self.start = Instant::now();
         }
     }
 }
@@ -256,29 +521,25 @@ pub fn init_ansi(
     let display_handle = &mut data.display_handle;
     let state = &mut data.state;
 
-    // DRM / GBM / EGL / GLES Setup
-    // Manual scan for render node since DrmNode::ty() is acting up
-    let drm_node = (128..136)
-        .map(|i| format!("/dev/dri/renderD{}", i))
-        .filter_map(|path| smithay::backend::drm::DrmNode::from_path(path).ok())
-        .next()
-        .ok_or_else(|| Box::<dyn std::error::Error>::from("No render node found"))?;
-
-    let fd = Arc::new(
-        std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(drm_node.dev_path().unwrap())?,
-    );
-    let gbm_egl = GbmDevice::new(fd.clone())?;
-    let gbm_alloc = GbmDevice::new(fd)?;
-
-    let egl_display = unsafe { EGLDisplay::new(gbm_egl) }.expect("Failed to create EGLDisplay");
-    let egl_context = EGLContext::new(&egl_display).expect("Failed to create EGLContext");
-    let renderer = unsafe { GlesRenderer::new(egl_context).expect("Failed to create GlesRenderer") };
-    let allocator = GbmAllocator::new(gbm_alloc, GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
-
     let backend = ratatui::RatatuiBackend::new()?;
+    let color_mode = backend.capabilities().color_mode;
+
+    // Rendering without a GPU can be forced, e.g. to compare the output of both pipelines
+    let pipeline = if std::env::var_os("ANSIVIL_SOFTWARE").is_some() {
+        info!("Using the software pipeline");
+        Pipeline::Software(SoftwarePipeline::new(color_mode)?)
+    } else {
+        match GpuPipeline::new() {
+            Ok(pipeline) => Pipeline::Gpu(Box::new(pipeline)),
+            Err(err) => {
+                warn!(
+                    "GPU pipeline unavailable ({}), falling back to software rendering",
+                    err
+                );
+                Pipeline::Software(SoftwarePipeline::new(color_mode)?)
+            }
+        }
+    };
 
     let mode = Mode {
         size: backend.window_size(),
@@ -300,79 +561,22 @@ pub fn init_ansi(
 
     state.space.map_output(&output, (0, 0));
 
-    // WGPU Initialization (for encoding)
-    let wgpu_instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::VULKAN,
-        ..Default::default()
-    });
-    let adapter =
-        pollster::block_on(wgpu_instance.request_adapter(&wgpu::RequestAdapterOptions::default())).unwrap();
-    let (wgpu_device, wgpu_queue) =
-        pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).unwrap();
-    let wgpu_device = Arc::new(wgpu_device);
-    let wgpu_queue = Arc::new(wgpu_queue);
-
-    let ansi_encoder =
-        pollster::block_on(GpuAnsiEncoder::new(wgpu_device.clone(), wgpu_queue.clone())).unwrap();
-    let wgpu_renderer = WgpuRenderer::new(&wgpu_instance, wgpu_device.clone(), wgpu_queue.clone());
-
     std::env::set_var("WAYLAND_DISPLAY", &state.socket_name);
 
-    let (tx, mut rx) = mpsc::channel::<Option<wgpu::Texture>>(2);
-    let ansi_encoder = Arc::new(ansi_encoder);
-
-    tokio::spawn(async move {
-        let mut previous_texture: Option<wgpu::Texture> = None;
-        let mut frames = 0;
-        let mut render_start = std::time::Instant::now();
-        let mut stdout = unsafe { File::from_raw_fd(1) };
-
-        while let Some(msg) = rx.recv().await {
-            if let Some(current_texture) = msg {
-                let ansi_string = ansi_encoder
-                    .ansi_from_texture(previous_texture.as_ref(), &current_texture)
-                    .await
-                    .unwrap();
-                {
-                    const START_BUFFERING: &str = "\x1b[?2026h";
-                    const STOP_BUFFERING: &str = "\x1b[?2026l";
-                    stdout.write_all(START_BUFFERING.as_bytes()).unwrap();
-                    stdout.write_all(ansi_string.as_bytes()).unwrap();
-                    stdout.write_all(STOP_BUFFERING.as_bytes()).unwrap();
-                    let _ = stdout.flush().unwrap();
-                }
-                previous_texture = Some(current_texture);
-
-                frames += 1;
-                if frames >= 60 {
-                    let render_end = std::time::Instant::now();
-                    eprintln!(
-                        "FPS = {}",
-                        frames as f64 / render_end.duration_since(render_start).as_secs_f64()
-                    );
-                    frames = 0;
-                    render_start = std::time::Instant::now();
-                }
-            } else {
-                previous_texture = None;
-            }
-        }
-    });
-
     let damage_tracker = OutputDamageTracker::from_output(&output);
 
     let debug_frame = std::env::var("DEBUG").ok().and_then(|s| s.parse::<u32>().ok());
+    // Cursors are coarse at the resolution of a terminal, the pointer of the terminal can be used instead
+    let terminal_cursor = std::env::var_os("ANSIVIL_TERMINAL_CURSOR").is_some();
 
     let mut handler = AnsiHandler {
-        renderer,
-        wgpu_renderer,
-        allocator,
+        pipeline,
         output,
         damage_tracker,
         backend,
-        tx,
         debug_frame,
         frame_count: 0,
+        terminal_cursor,
     };
 
     event_loop
@@ -382,7 +586,12 @@ pub fn init_ansi(
                 1_000_000_000 / u64::try_from(mode.refresh).unwrap(),
             )),
             move |event, _, data| {
+                // remote terminals are drawn at the pace of the terminal of the process
+                let redraw = matches!(event, RatatuiEvent::Redraw);
                 handler.handle_event(event, &mut data.state, &mut data.display_handle);
+                if redraw {
+                    data.remote.render(&data.state);
+                }
             },
         )
         .unwrap();
@@ -0,0 +1,19 @@
+//! Attach the terminal to a session of a running ansivil
+//!
+//! Usage: `ansivil-attach [SESSION]`, the session defaults to `default`. The socket is looked up
+//! like ansivil does, at `ANSIVIL_SOCKET` or `$XDG_RUNTIME_DIR/ansivil.sock`. Press Ctrl+Alt+D
+//! to detach.
+
+use std::path::PathBuf;
+
+fn main() -> std::io::Result<()> {
+    let session = std::env::args().nth(1).unwrap_or_else(|| "default".to_owned());
+    let path = std::env::var_os("ANSIVIL_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_else(|| "/tmp".into());
+            PathBuf::from(runtime_dir).join("ansivil.sock")
+        });
+
+    smithay::backend::ratatui::remote::attach(path, &session)
+}
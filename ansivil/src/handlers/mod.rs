@@ -1,5 +1,6 @@
 mod compositor;
 mod dmabuf;
+mod selection;
 mod xdg_shell;
 
 use crate::Smallvil;
@@ -15,8 +16,9 @@ use smithay::wayland::output::OutputHandler;
 use smithay::wayland::selection::data_device::{
     set_data_device_focus, ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler,
 };
-use smithay::wayland::selection::SelectionHandler;
-use smithay::{delegate_data_device, delegate_output, delegate_seat};
+use smithay::wayland::selection::primary_selection::set_primary_focus;
+use smithay::wayland::xdg_system_bell::XdgSystemBellHandler;
+use smithay::{delegate_data_device, delegate_output, delegate_seat, delegate_xdg_system_bell};
 
 impl SeatHandler for Smallvil {
     type KeyboardFocus = WlSurface;
@@ -27,12 +29,15 @@ impl SeatHandler for Smallvil {
         &mut self.seat_state
     }
 
-    fn cursor_image(&mut self, _seat: &Seat<Self>, _image: smithay::input::pointer::CursorImageStatus) {}
+    fn cursor_image(&mut self, _seat: &Seat<Self>, image: smithay::input::pointer::CursorImageStatus) {
+        self.cursor.set_status(image);
+    }
 
     fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
         let dh = &self.display_handle;
         let client = focused.and_then(|s| dh.get_client(s.id()).ok());
-        set_data_device_focus(dh, seat, client);
+        set_data_device_focus(dh, seat, client.clone());
+        set_primary_focus(dh, seat, client);
     }
 }
 
@@ -42,10 +47,6 @@ delegate_seat!(Smallvil);
 // Wl Data Device
 //
 
-impl SelectionHandler for Smallvil {
-    type SelectionUserData = ();
-}
-
 impl DataDeviceHandler for Smallvil {
     fn data_device_state(&self) -> &DataDeviceState {
         &self.data_device_state
@@ -63,3 +64,15 @@ delegate_data_device!(Smallvil);
 
 impl OutputHandler for Smallvil {}
 delegate_output!(Smallvil);
+
+//
+// Xdg System Bell
+//
+
+impl XdgSystemBellHandler for Smallvil {
+    fn ring(&mut self, _surface: Option<WlSurface>) {
+        // rung on the next frame, so the bell is not written in the middle of a frame
+        self.bell_pending = true;
+    }
+}
+delegate_xdg_system_bell!(Smallvil);
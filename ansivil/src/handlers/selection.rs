@@ -0,0 +1,148 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    os::unix::io::OwnedFd,
+    sync::Arc,
+};
+
+use rustix::pipe::{pipe_with, PipeFlags};
+use smithay::{
+    backend::ratatui::MAX_CLIPBOARD_SIZE,
+    delegate_primary_selection,
+    input::Seat,
+    reexports::calloop::{generic::Generic, Interest, Mode, PostAction},
+    wayland::selection::{
+        data_device::{request_data_device_client_selection, set_data_device_selection},
+        primary_selection::{
+            request_primary_client_selection, PrimarySelectionHandler, PrimarySelectionState,
+        },
+        SelectionHandler, SelectionSource, SelectionTarget,
+    },
+};
+use tracing::{debug, warn};
+
+use crate::Smallvil;
+
+/// Mime types used to transfer text, in order of preference
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+impl SelectionHandler for Smallvil {
+    /// Text pasted into the terminal
+    type SelectionUserData = Arc<str>;
+
+    fn new_selection(&mut self, ty: SelectionTarget, source: Option<SelectionSource>, _seat: Seat<Self>) {
+        // Selections of clients are forwarded to the terminal, so they can be pasted outside of it
+        let Some(source) = source else {
+            return;
+        };
+        let mime_types = source.mime_types();
+        let Some(mime_type) = TEXT_MIME_TYPES
+            .into_iter()
+            .find(|mime_type| mime_types.iter().any(|offered| offered == mime_type))
+        else {
+            return;
+        };
+
+        // the seat only holds the new selection after this callback, so it is requested afterwards
+        self.loop_handle
+            .insert_idle(move |data| data.state.read_selection(ty, mime_type));
+    }
+
+    fn send_selection(
+        &mut self,
+        _ty: SelectionTarget,
+        _mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        user_data: &Self::SelectionUserData,
+    ) {
+        let text = user_data.clone();
+        // writing blocks until the client reads the pipe
+        std::thread::spawn(move || {
+            if let Err(err) = File::from(fd).write_all(text.as_bytes()) {
+                warn!(?err, "Failed to send selection");
+            }
+        });
+    }
+}
+
+impl Smallvil {
+    /// Read the client selection `ty` in `mime_type` and queue it for the terminal clipboard
+    fn read_selection(&mut self, ty: SelectionTarget, mime_type: &'static str) {
+        let (read, write) = match pipe_with(PipeFlags::CLOEXEC) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                warn!(?err, "Failed to create pipe for selection");
+                return;
+            }
+        };
+        let result = match ty {
+            SelectionTarget::Clipboard => {
+                request_data_device_client_selection(&self.seat, mime_type.to_owned(), write)
+                    .map_err(anyhow::Error::from)
+            }
+            SelectionTarget::Primary => {
+                request_primary_client_selection(&self.seat, mime_type.to_owned(), write)
+                    .map_err(anyhow::Error::from)
+            }
+        };
+        if let Err(err) = result {
+            // the selection was replaced or cleared in the meantime
+            debug!(%err, ?ty, "Failed to request selection");
+            return;
+        }
+
+        let mut contents = Vec::new();
+        let source = Generic::new(File::from(read), Interest::READ, Mode::Level);
+        let result = self.loop_handle.insert_source(source, move |_, file, data| {
+            let mut buf = [0u8; 4096];
+            // a single read does not block, as the pipe is readable
+            let n = match (&**file).read(&mut buf) {
+                Ok(n) => n,
+                Err(err) => {
+                    warn!(?err, "Failed to read selection");
+                    return Ok(PostAction::Remove);
+                }
+            };
+            if n == 0 {
+                data.state
+                    .terminal_selections
+                    .push((ty, std::mem::take(&mut contents)));
+                return Ok(PostAction::Remove);
+            }
+            contents.extend_from_slice(&buf[..n]);
+            if contents.len() > MAX_CLIPBOARD_SIZE {
+                warn!("Selection too large for the terminal clipboard");
+                return Ok(PostAction::Remove);
+            }
+            Ok(PostAction::Continue)
+        });
+        if let Err(err) = result {
+            warn!(?err, "Failed to read selection");
+        }
+    }
+
+    /// Offer text pasted into the terminal as clipboard selection
+    pub fn paste(&mut self, text: String) {
+        set_data_device_selection(
+            &self.display_handle,
+            &self.seat,
+            TEXT_MIME_TYPES.map(String::from).to_vec(),
+            Arc::from(text),
+        );
+    }
+}
+
+impl PrimarySelectionHandler for Smallvil {
+    fn primary_selection_state(&self) -> &PrimarySelectionState {
+        &self.primary_selection_state
+    }
+}
+
+delegate_primary_selection!(Smallvil);
@@ -1,21 +1,23 @@
 use smithay::{
     backend::input::{
-        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent,
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, InputBackend, InputEvent, KeyState,
         KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
     },
     input::{
-        keyboard::FilterResult,
+        keyboard::{xkb, FilterResult, KeyboardTarget, Keycode, Keysym},
         pointer::{AxisFrame, ButtonEvent, MotionEvent},
     },
+    output::Output,
     reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
     utils::SERIAL_COUNTER,
 };
-use tracing::debug;
+use tracing::{debug, error};
 
 use crate::state::Smallvil;
 
 impl Smallvil {
-    pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
+    /// Handle `event` of a terminal showing `output`, absolute pointer positions are relative to it
+    pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>, output: &Output) {
         match event {
             InputEvent::Keyboard { event, .. } => {
                 let serial = SERIAL_COUNTER.next_serial();
@@ -38,8 +40,9 @@ impl Smallvil {
             }
             InputEvent::PointerMotion { .. } => {}
             InputEvent::PointerMotionAbsolute { event, .. } => {
-                let output = self.space.outputs().next().unwrap();
-                let output_geo = self.space.output_geometry(output).unwrap();
+                let Some(output_geo) = self.space.output_geometry(output) else {
+                    return;
+                };
                 let pos = event.position_transformed(output_geo.size) + output_geo.loc.to_f64();
                 let serial = SERIAL_COUNTER.next_serial();
                 let pointer = self.seat.get_pointer().unwrap();
@@ -155,4 +158,163 @@ impl Smallvil {
             _ => {}
         }
     }
+
+    /// Type `keysym` using a spare key of the seat keymap
+    ///
+    /// Used for characters the terminal reports without a key on the keymap of the seat.
+    /// The keymap is only changed if no spare key produces `keysym` yet, keeping the
+    /// state of the modifiers.
+    pub fn type_keysym(&mut self, keysym: Keysym) {
+        let keyboard = self.seat.get_keyboard().unwrap();
+        if self.typed_keysyms.is_none() {
+            let typed = keyboard.with_xkb_state(self, |context| {
+                // SAFETY: the keymap is not kept beyond serializing it and looking up its keys
+                TypedKeysyms::new(unsafe { context.xkb().lock().unwrap().keymap() })
+            });
+            self.typed_keysyms = Some(typed);
+        }
+        let typed = self.typed_keysyms.as_mut().unwrap();
+
+        let keycode = match typed.keycode(keysym) {
+            Some(keycode) => keycode,
+            None => {
+                let Some((keycode, keymap)) = typed.add(keysym) else {
+                    error!("Failed to type keysym {:?}: no spare keys", keysym);
+                    return;
+                };
+
+                let mods = keyboard.modifier_state();
+                if let Err(err) = keyboard.set_keymap_from_string(self, keymap) {
+                    error!("Failed to type keysym {:?}: {}", keysym, err);
+                    return;
+                }
+                if keyboard.set_modifier_state(mods) != 0 {
+                    if let Some(focus) = keyboard.current_focus() {
+                        focus.modifiers(&self.seat.clone(), self, mods, SERIAL_COUNTER.next_serial());
+                    }
+                }
+                keycode
+            }
+        };
+
+        let time = self.start_time.elapsed().as_millis() as u32;
+        for state in [KeyState::Pressed, KeyState::Released] {
+            keyboard.input::<(), _>(
+                self,
+                keycode,
+                state,
+                SERIAL_COUNTER.next_serial(),
+                time,
+                |_, _, _| FilterResult::Forward,
+            );
+        }
+    }
+}
+
+/// Keysyms typed by [`Smallvil::type_keysym`] on the keys without symbols of the seat keymap
+#[derive(Debug)]
+pub struct TypedKeysyms {
+    /// Keymap of the seat the keysyms are added to
+    keymap: String,
+    /// Names and keycodes of the keys without symbols in `keymap`
+    spare: Vec<(String, Keycode)>,
+    /// Keysyms of the spare keys in use, in the order of `spare`
+    keysyms: Vec<Keysym>,
+    /// Spare key replaced next once all of them are in use
+    next: usize,
+}
+
+impl TypedKeysyms {
+    fn new(keymap: &xkb::Keymap) -> Self {
+        let mut spare = Vec::new();
+        keymap.key_for_each(|keymap, keycode| {
+            if keymap.num_layouts_for_key(keycode) == 0 {
+                if let Some(name) = keymap.key_get_name(keycode) {
+                    spare.push((name.to_owned(), keycode));
+                }
+            }
+        });
+
+        TypedKeysyms {
+            keymap: keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1),
+            spare,
+            keysyms: Vec::new(),
+            next: 0,
+        }
+    }
+
+    /// Keycode of the spare key producing `keysym`
+    fn keycode(&self, keysym: Keysym) -> Option<Keycode> {
+        let index = self.keysyms.iter().position(|typed| *typed == keysym)?;
+        Some(self.spare[index].1)
+    }
+
+    /// Put `keysym` on a spare key and return its keycode with the new keymap
+    fn add(&mut self, keysym: Keysym) -> Option<(Keycode, String)> {
+        if self.spare.is_empty() {
+            return None;
+        }
+
+        let index = if self.keysyms.len() < self.spare.len() {
+            self.keysyms.push(keysym);
+            self.keysyms.len() - 1
+        } else {
+            let index = self.next;
+            self.keysyms[index] = keysym;
+            self.next = (index + 1) % self.spare.len();
+            index
+        };
+
+        let keys = self
+            .spare
+            .iter()
+            .zip(&self.keysyms)
+            .map(|((name, _), keysym)| {
+                format!("    key <{name}> {{ [ {} ] }};\n", xkb::keysym_get_name(*keysym))
+            })
+            .collect::<String>();
+        // the symbols section is the last one of the serialized keymap
+        let end = self.keymap.trim_end().trim_end_matches("};").trim_end().len() - "};".len();
+        let mut keymap = self.keymap.clone();
+        keymap.insert_str(end, &keys);
+        Some((self.spare[index].1, keymap))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay::input::keyboard::{keysyms, xkb, Keysym};
+
+    use super::TypedKeysyms;
+
+    #[test]
+    fn typed_keysyms() {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(&context, "", "", "us", "", None, xkb::COMPILE_NO_FLAGS)
+            .expect("no us keymap");
+        let mut typed = TypedKeysyms::new(&keymap);
+        assert!(typed.spare.len() >= 2);
+
+        let euro = Keysym::from(keysyms::KEY_EuroSign);
+        let cyrillic_a = Keysym::from(keysyms::KEY_Cyrillic_a);
+        let (keycode, _) = typed.add(euro).unwrap();
+        assert_eq!(typed.keycode(euro), Some(keycode));
+        let (other, extended) = typed.add(cyrillic_a).unwrap();
+        assert_ne!(keycode, other);
+
+        let extended = xkb::Keymap::new_from_string(
+            &context,
+            extended,
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::COMPILE_NO_FLAGS,
+        )
+        .expect("invalid extended keymap");
+        assert_eq!(extended.key_get_syms_by_level(keycode, 0, 0), [euro]);
+        assert_eq!(extended.key_get_syms_by_level(other, 0, 0), [cyrillic_a]);
+        let a = keymap.key_by_name("AC01").unwrap();
+        assert_eq!(
+            extended.key_get_syms_by_level(a, 0, 0),
+            keymap.key_get_syms_by_level(a, 0, 0)
+        );
+    }
 }
@@ -5,8 +5,9 @@ mod handlers;
 mod ansi;
 mod grabs;
 mod input;
+mod remote;
+mod software;
 mod state;
-mod wgpu_renderer;
 
 use smithay::reexports::{
     calloop::EventLoop,
@@ -17,6 +18,8 @@ pub use state::Smallvil;
 pub struct CalloopData {
     state: Smallvil,
     display_handle: DisplayHandle,
+    /// Terminals attached over the socket of [`remote`]
+    remote: remote::RemoteTerminals,
 }
 
 #[tokio::main]
@@ -39,9 +42,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut data = CalloopData {
         state,
         display_handle,
+        remote: Default::default(),
     };
 
     crate::ansi::init_ansi(&mut event_loop, &mut data)?;
+    crate::remote::init_remote(&mut event_loop);
 
     let mut args = std::env::args().skip(1);
     let flag = args.next();
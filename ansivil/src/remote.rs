@@ -0,0 +1,286 @@
+//! Terminals attached over a Unix socket
+//!
+//! Every attached terminal shows a session with its own output, placed right of the outputs
+//! already in the space, and drives the seat with its own input device. Sessions are named by the
+//! terminal attaching. When a terminal detaches, the output of its session is removed, but the
+//! area of the space it showed is kept for the session, so the windows left there are shown again
+//! when a terminal attaches to the same session. A terminal attaching to a session that is shown
+//! by another terminal takes it over.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use smithay::{
+    backend::{
+        input::InputEvent,
+        ratatui::{
+            remote::{RatatuiListener, RemoteTerminal},
+            RatatuiCursorRenderElement, RatatuiEvent, RatatuiInputBackend, RatatuiInputDevice,
+            RatatuiKeyEvent, RatatuiMouseEvent,
+        },
+        renderer::{
+            damage::OutputDamageTracker,
+            ratatui::{RatatuiFramebuffer, RatatuiRenderer},
+        },
+    },
+    output::{Mode, Output, PhysicalProperties, Subpixel},
+    reexports::{
+        calloop::{EventLoop, RegistrationToken},
+        wayland_server::backend::GlobalId,
+    },
+    utils::{Logical, Rectangle, Transform},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::ansi::{pointer_event, CLEAR_COLOR};
+use crate::{CalloopData, Smallvil};
+
+/// Path of the socket, if `ANSIVIL_SOCKET` is not set
+pub fn default_socket_path() -> PathBuf {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").unwrap_or_else(|| "/tmp".into());
+    PathBuf::from(runtime_dir).join("ansivil.sock")
+}
+
+/// Listen for terminals on the socket at `ANSIVIL_SOCKET`, or the [`default_socket_path`]
+///
+/// Terminals are attached using `ansivil-attach`. If the socket cannot be created, ansivil runs
+/// without remote terminals.
+pub fn init_remote(event_loop: &mut EventLoop<CalloopData>) {
+    let path = std::env::var_os("ANSIVIL_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(default_socket_path);
+    let listener = match RatatuiListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            warn!("Not accepting remote terminals on {}: {}", path.display(), err);
+            return;
+        }
+    };
+
+    event_loop
+        .handle()
+        .insert_source(listener, |terminal, _, data| {
+            data.remote.attach(terminal, &mut data.state);
+        })
+        .unwrap();
+}
+
+/// A session shown by an attached terminal
+struct Session {
+    output: Output,
+    global: GlobalId,
+    renderer: RatatuiRenderer,
+    /// Kept between frames, so only the damaged cells are rendered
+    framebuffer: Option<RatatuiFramebuffer>,
+    age: usize,
+    damage_tracker: OutputDamageTracker,
+    device: RatatuiInputDevice,
+    /// Event source of the input of the terminal
+    token: RegistrationToken,
+}
+
+/// Sessions of the terminals attached over the socket
+#[derive(Default)]
+pub struct RemoteTerminals {
+    sessions: HashMap<String, Session>,
+    /// Area of the space of every session that was attached once
+    areas: HashMap<String, Rectangle<i32, Logical>>,
+}
+
+impl RemoteTerminals {
+    fn attach(&mut self, terminal: RemoteTerminal, state: &mut Smallvil) {
+        let name = terminal.session().to_owned();
+        let (renderer, events) = terminal.into_parts();
+        let device = events.device();
+        let mode = Mode {
+            size: renderer.window_size(),
+            refresh: 60_000,
+        };
+
+        let token = state
+            .loop_handle
+            .insert_source(events, {
+                let name = name.clone();
+                move |event, _, data| data.remote.handle_event(&name, event, &mut data.state)
+            })
+            .unwrap();
+
+        if let Some(session) = self.sessions.get_mut(&name) {
+            info!(session = name, "Terminal took over session");
+            // dropping the event source and the renderer closes the connection of the previous terminal
+            state.loop_handle.remove(session.token);
+            session.renderer = renderer;
+            session.device = device;
+            session.token = token;
+            self.resize(&name, state);
+            return;
+        }
+
+        info!(session = name, "Terminal attached");
+        let output = Output::new(
+            format!("ansi-{name}"),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "RemoteRatatui".into(),
+            },
+        );
+        let global = output.create_global::<Smallvil>(&state.display_handle);
+        let location = match self.areas.get(&name) {
+            Some(area) => area.loc,
+            None => {
+                // right of every output and every area kept for a detached session
+                let right = state
+                    .space
+                    .outputs()
+                    .filter_map(|output| state.space.output_geometry(output))
+                    .chain(self.areas.values().copied())
+                    .map(|area| area.loc.x + area.size.w)
+                    .max()
+                    .unwrap_or(0);
+                (right, 0).into()
+            }
+        };
+        output.change_current_state(Some(mode), Some(Transform::Normal), None, Some(location));
+        output.set_preferred(mode);
+        state.space.map_output(&output, location);
+        self.areas
+            .insert(name.clone(), Rectangle::new(location, mode.size.to_logical(1)));
+
+        let damage_tracker = OutputDamageTracker::from_output(&output);
+        self.sessions.insert(
+            name,
+            Session {
+                output,
+                global,
+                renderer,
+                framebuffer: None,
+                age: 0,
+                damage_tracker,
+                device,
+                token,
+            },
+        );
+    }
+
+    /// Remove the output of the session `name`
+    fn detach(&mut self, name: &str, state: &mut Smallvil) {
+        let Some(session) = self.sessions.remove(name) else {
+            return;
+        };
+        info!(session = name, "Terminal detached");
+        state.space.unmap_output(&session.output);
+        state.display_handle.remove_global::<Smallvil>(session.global);
+    }
+
+    fn handle_event(&mut self, name: &str, event: RatatuiEvent, state: &mut Smallvil) {
+        let Some(session) = self.sessions.get_mut(name) else {
+            return;
+        };
+        match event {
+            RatatuiEvent::Resize(_, _) => self.resize(name, state),
+            event @ RatatuiEvent::Key { .. } => {
+                let event = RatatuiKeyEvent::from(event).with_device(session.device.clone());
+                state.process_input_event(
+                    InputEvent::<RatatuiInputBackend>::Keyboard { event },
+                    &session.output,
+                );
+            }
+            RatatuiEvent::Keysym { keysym, kind } => {
+                if kind != crossterm::event::KeyEventKind::Release {
+                    state.type_keysym(keysym);
+                }
+            }
+            RatatuiEvent::Mouse(event) => {
+                let kind = event.kind;
+                let event = RatatuiMouseEvent::with_terminal_size(
+                    event,
+                    session.renderer.window_size(),
+                    session.renderer.terminal_size(),
+                )
+                .with_device(session.device.clone());
+                state.process_input_event(pointer_event(kind, event), &session.output);
+            }
+            RatatuiEvent::Paste(text) => state.paste(text),
+            RatatuiEvent::Detached => self.detach(name, state),
+            // drawn together with the terminal of the process
+            RatatuiEvent::Redraw => {}
+        }
+    }
+
+    /// Adapt the output of the session `name` to the size of its terminal
+    fn resize(&mut self, name: &str, state: &Smallvil) {
+        let Some(session) = self.sessions.get_mut(name) else {
+            return;
+        };
+        let mode = Mode {
+            size: session.renderer.window_size(),
+            refresh: 60_000,
+        };
+        debug!(session = name, ?mode, "Remote terminal resized");
+        session.output.change_current_state(Some(mode), None, None, None);
+        session.output.set_preferred(mode);
+        session.framebuffer = None;
+        session.age = 0;
+        if let Some(area) = state.space.output_geometry(&session.output) {
+            self.areas.insert(name.to_owned(), area);
+        }
+    }
+
+    /// Draw the damaged parts of the outputs to their terminals
+    pub fn render(&mut self, state: &Smallvil) {
+        for session in self.sessions.values_mut() {
+            session.render(state);
+        }
+    }
+}
+
+impl Session {
+    fn render(&mut self, state: &Smallvil) {
+        let Some(geometry) = state.space.output_geometry(&self.output) else {
+            return;
+        };
+        let cursor_elements: Vec<RatatuiCursorRenderElement<RatatuiRenderer>> = state
+            .seat
+            .get_pointer()
+            .map(|pointer| {
+                let location = (pointer.current_location() - geometry.loc.to_f64()).to_physical(1.0);
+                let cell_size = self.renderer.cell_size();
+                state
+                    .cursor
+                    .render_elements(&mut self.renderer, location, cell_size, 1.0, 1.0)
+            })
+            .unwrap_or_default();
+
+        let mut framebuffer = self
+            .framebuffer
+            .take()
+            .unwrap_or_else(|| self.renderer.new_framebuffer());
+        let damage = match smithay::desktop::space::render_output(
+            &self.output,
+            &mut self.renderer,
+            &mut framebuffer,
+            1.0,
+            self.age,
+            [&state.space],
+            &cursor_elements,
+            &mut self.damage_tracker,
+            CLEAR_COLOR,
+        ) {
+            Ok(result) => result.damage.cloned().unwrap_or_default(),
+            Err(err) => {
+                error!("Failed to render {}: {}", self.output.name(), err);
+                return;
+            }
+        };
+
+        match self.renderer.swap_buffers_with_damage(framebuffer, &damage) {
+            Ok(framebuffer) => {
+                self.framebuffer = Some(framebuffer);
+                self.age = 1;
+            }
+            // the terminal is gone, its event source reports it as detached
+            Err(err) => debug!("Failed to draw {}: {}", self.output.name(), err),
+        }
+    }
+}
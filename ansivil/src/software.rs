@@ -0,0 +1,440 @@
+//! Rendering pipeline for machines without a GPU
+//!
+//! Frames are rendered with the [`PixmanRenderer`] into a memory buffer and converted to
+//! half-block cells on the CPU. Large damaged regions are composited on all available threads.
+//! Only the cells covered by the damage of a frame are converted, and of those only the ones that
+//! actually changed are written to the terminal, with their colours reduced to the [`ColorMode`]
+//! of the terminal.
+
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use image::{ImageBuffer, Rgba};
+use ratatui::{buffer::Cell as TerminalCell, layout::Rect, style::Color};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        ratatui::{damage_to_cells, quantize_cells, ColorMode},
+        renderer::{damage::OutputDamageTracker, pixman::PixmanRenderer, Bind, ExportMem, Offscreen},
+    },
+    output::Output,
+    reexports::pixman::Image,
+    utils::{Buffer, Physical, Rectangle, Transform},
+};
+use tokio::sync::mpsc;
+use tracing::error;
+
+use crate::ansi::{CursorOverlay, Presenter, CLEAR_COLOR};
+use crate::Smallvil;
+
+/// Symbol of the cells, the background colour covers the upper half of the cell and the
+/// foreground colour the lower half
+const LOWER_HALF_BLOCK: char = '\u{2584}';
+/// Damage rectangles kept for frames the encoder did not accept, before the whole output is redrawn
+const MAX_PENDING_DAMAGE: usize = 256;
+
+pub struct SoftwarePipeline {
+    renderer: PixmanRenderer,
+    /// Memory buffer frames are rendered into, kept alive to only redraw damaged regions
+    image: Option<Image<'static, 'static>>,
+    age: usize,
+    /// Damage of frames the encoder did not accept yet
+    pending: Vec<Rectangle<i32, Physical>>,
+    tx: mpsc::Sender<CellUpdate>,
+    /// Set to make the encoder redraw every cell with the next update it receives
+    redraw: Arc<AtomicBool>,
+}
+
+impl SoftwarePipeline {
+    pub fn new(color_mode: ColorMode) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut renderer = PixmanRenderer::new()?;
+        renderer.set_threads(std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+
+        let (tx, mut rx) = mpsc::channel::<CellUpdate>(2);
+        let redraw = Arc::new(AtomicBool::new(false));
+        let encoder_redraw = redraw.clone();
+        tokio::spawn(async move {
+            let mut encoder = AnsiEncoder::new(color_mode);
+            let mut presenter = Presenter::new();
+            let mut ansi = String::new();
+
+            while let Some(update) = rx.recv().await {
+                if encoder_redraw.swap(false, Ordering::AcqRel) {
+                    encoder.reset();
+                }
+                ansi.clear();
+                encoder.encode(&update, &mut ansi);
+                if !ansi.is_empty() {
+                    presenter.present(&ansi);
+                }
+            }
+        });
+
+        Ok(SoftwarePipeline {
+            renderer,
+            image: None,
+            age: 0,
+            pending: Vec::new(),
+            tx,
+            redraw,
+        })
+    }
+
+    /// Make the next frame redraw every cell of the terminal
+    pub fn reset(&mut self) {
+        self.age = 0;
+        // a flag instead of a message, so the reset is not lost if the channel is full
+        self.redraw.store(true, Ordering::Release);
+    }
+
+    pub fn render(
+        &mut self,
+        output: &Output,
+        state: &Smallvil,
+        damage_tracker: &mut OutputDamageTracker,
+        cursor: Option<CursorOverlay<'_>>,
+        screenshot: Option<&Path>,
+    ) {
+        let size = output.current_mode().unwrap().size;
+        let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+
+        let outdated = self.image.as_ref().map_or(true, |image| {
+            (image.width(), image.height()) != (size.w as usize, size.h as usize)
+        });
+        if outdated {
+            self.image = match self.renderer.create_buffer(Fourcc::Xrgb8888, buffer_size) {
+                Ok(image) => Some(image),
+                Err(err) => {
+                    error!("Failed to allocate the render buffer: {}", err);
+                    return;
+                }
+            };
+            self.age = 0;
+        }
+        let cursor_elements = cursor
+            .map(|cursor| cursor.render_elements(&mut self.renderer))
+            .unwrap_or_default();
+        let image = self.image.as_mut().unwrap();
+
+        let mut target = self
+            .renderer
+            .bind(image)
+            .expect("Failed to bind the render buffer");
+        let result = smithay::desktop::space::render_output(
+            output,
+            &mut self.renderer,
+            &mut target,
+            1.0,
+            self.age,
+            [&state.space],
+            &cursor_elements,
+            damage_tracker,
+            CLEAR_COLOR,
+        )
+        .expect("Failed to render output");
+        self.age = 1;
+
+        let mut damage = std::mem::take(&mut self.pending);
+        damage.extend(result.damage.into_iter().flatten().copied());
+        if damage.is_empty() && screenshot.is_none() {
+            return;
+        }
+
+        let bounds = Rectangle::from_size(buffer_size);
+        let mut copy = |region: Rectangle<i32, Buffer>| {
+            let mapping = self
+                .renderer
+                .copy_framebuffer(&target, region, Fourcc::Xrgb8888)
+                .expect("Failed to copy the render buffer");
+            let data = self
+                .renderer
+                .map_texture(&mapping)
+                .expect("Failed to map the render buffer");
+            Pixels {
+                width: region.size.w as usize,
+                height: region.size.h as usize,
+                stride: data.len() / (region.size.h as usize).max(1),
+                data: data.to_vec(),
+            }
+        };
+
+        if let Some(path) = screenshot {
+            eprintln!("Saving debug screenshot to {}", path.display());
+            copy(bounds).save(path).expect("Failed to save debug screenshot");
+        }
+
+        // only convert the cells covering the damage
+        let regions = damage_to_cells(&damage, size, CELL_SIZE.into())
+            .into_iter()
+            .filter_map(|area| {
+                let pixels = Rectangle::new(
+                    (i32::from(area.x), i32::from(area.y) * CELL_SIZE.1).into(),
+                    (i32::from(area.width), i32::from(area.height) * CELL_SIZE.1).into(),
+                )
+                .intersection(bounds)?;
+                let pixels = copy(pixels);
+                let cells = (0..area.height as usize)
+                    .flat_map(|row| (0..area.width as usize).map(move |column| (column, row)))
+                    .map(|(column, row)| (pixels.rgb(column, row * 2), pixels.rgb(column, row * 2 + 1)))
+                    .collect();
+                Some((area, cells))
+            })
+            .collect();
+        let update = CellUpdate {
+            width: size.w as usize,
+            rows: (size.h as usize).div_ceil(2),
+            regions,
+        };
+
+        // Send to encoding task, the damage is sent again with the next frame if the encoder is busy
+        if self.tx.try_send(update).is_err() {
+            if damage.len() > MAX_PENDING_DAMAGE {
+                damage = vec![Rectangle::from_size(size)];
+            }
+            self.pending = damage;
+        }
+    }
+}
+
+/// A frame in `Xrgb8888`
+struct Pixels {
+    width: usize,
+    height: usize,
+    /// Bytes per row
+    stride: usize,
+    data: Vec<u8>,
+}
+
+impl Pixels {
+    /// Colour of the pixel at `(x, y)`, pixels outside of the frame are black
+    fn rgb(&self, x: usize, y: usize) -> [u8; 3] {
+        if y >= self.height {
+            return [0, 0, 0];
+        }
+        let offset = y * self.stride + x * 4;
+        // little endian, stored as b, g, r, x
+        let pixel = &self.data[offset..offset + 4];
+        [pixel[2], pixel[1], pixel[0]]
+    }
+
+    fn save(&self, path: &Path) -> Result<(), image::ImageError> {
+        let image = ImageBuffer::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let [r, g, b] = self.rgb(x as usize, y as usize);
+            Rgba([r, g, b, 255])
+        });
+        image.save(path)
+    }
+}
+
+/// Colours of the upper and lower half of a cell
+type Cell = ([u8; 3], [u8; 3]);
+
+/// Size of a cell in pixels
+const CELL_SIZE: (i32, i32) = (1, 2);
+
+/// Cells of a frame that were damaged
+struct CellUpdate {
+    /// Size of the frame in cells
+    width: usize,
+    rows: usize,
+    /// Damaged areas with their cells, row by row
+    regions: Vec<(Rect, Vec<Cell>)>,
+}
+
+/// Converts frames to ANSI escape sequences, only updating cells that changed
+#[derive(Debug)]
+struct AnsiEncoder {
+    /// Cells of the previous frame, empty if the terminal has to be redrawn entirely
+    cells: Vec<Cell>,
+    width: usize,
+    color_mode: ColorMode,
+}
+
+impl AnsiEncoder {
+    fn new(color_mode: ColorMode) -> Self {
+        AnsiEncoder {
+            cells: Vec::new(),
+            width: 0,
+            color_mode,
+        }
+    }
+
+    /// Redraw every cell on the next frame
+    fn reset(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Append the sequences updating the changed cells of `update` to `out`
+    fn encode(&mut self, update: &CellUpdate, out: &mut String) {
+        let width = update.width;
+        if self.width != width || self.cells.len() != width * update.rows {
+            self.cells.clear();
+            self.width = width;
+        }
+        let redraw = self.cells.is_empty();
+        if redraw {
+            self.cells.resize(width * update.rows, ([0; 3], [0; 3]));
+        }
+
+        // position of the cursor and colours set by the last written cell
+        let mut cursor = None;
+        let mut colors: Option<(Color, Color)> = None;
+        for (area, cells) in &update.regions {
+            let mut positions = Vec::new();
+            let mut changed = Vec::new();
+            for (i, &cell) in cells.iter().enumerate() {
+                let column = usize::from(area.x) + i % usize::from(area.width);
+                let row = usize::from(area.y) + i / usize::from(area.width);
+                let Some(previous) = self.cells.get_mut(row * width + column) else {
+                    continue;
+                };
+                if !redraw && *previous == cell {
+                    continue;
+                }
+                *previous = cell;
+                positions.push((column, row));
+                changed.push(terminal_cell(cell));
+            }
+            quantize_cells(&mut changed, area.width, self.color_mode, false);
+
+            for ((column, row), cell) in positions.into_iter().zip(changed) {
+                if cursor != Some((column, row)) {
+                    let _ = write!(out, "\x1b[{};{}H", row + 1, column + 1);
+                }
+                if colors.map_or(true, |(fg, _)| fg != cell.fg) {
+                    write_color(out, cell.fg, true);
+                }
+                if colors.map_or(true, |(_, bg)| bg != cell.bg) {
+                    write_color(out, cell.bg, false);
+                }
+                out.push_str(cell.symbol());
+
+                colors = Some((cell.fg, cell.bg));
+                cursor = Some((column + 1, row));
+            }
+        }
+
+        if colors.is_some() {
+            out.push_str("\x1b[0m");
+        }
+    }
+}
+
+/// A half-block cell in the layout expected by [`quantize_cells`]
+fn terminal_cell((upper, lower): Cell) -> TerminalCell {
+    let mut cell = TerminalCell::default();
+    cell.set_char(LOWER_HALF_BLOCK)
+        .set_fg(Color::Rgb(lower[0], lower[1], lower[2]))
+        .set_bg(Color::Rgb(upper[0], upper[1], upper[2]));
+    cell
+}
+
+/// Append the SGR sequence setting the foreground or background to `color`
+fn write_color(out: &mut String, color: Color, foreground: bool) {
+    let (base, bright, extended, default) = if foreground {
+        (30, 90, 38, 39)
+    } else {
+        (40, 100, 48, 49)
+    };
+    let _ = match color {
+        Color::Reset => write!(out, "\x1b[{}m", default),
+        Color::Rgb(r, g, b) => write!(out, "\x1b[{};2;{};{};{}m", extended, r, g, b),
+        Color::Indexed(index) => write!(out, "\x1b[{};5;{}m", extended, index),
+        Color::Black => write!(out, "\x1b[{}m", base),
+        Color::Red => write!(out, "\x1b[{}m", base + 1),
+        Color::Green => write!(out, "\x1b[{}m", base + 2),
+        Color::Yellow => write!(out, "\x1b[{}m", base + 3),
+        Color::Blue => write!(out, "\x1b[{}m", base + 4),
+        Color::Magenta => write!(out, "\x1b[{}m", base + 5),
+        Color::Cyan => write!(out, "\x1b[{}m", base + 6),
+        Color::Gray => write!(out, "\x1b[{}m", base + 7),
+        Color::DarkGray => write!(out, "\x1b[{}m", bright),
+        Color::LightRed => write!(out, "\x1b[{}m", bright + 1),
+        Color::LightGreen => write!(out, "\x1b[{}m", bright + 2),
+        Color::LightYellow => write!(out, "\x1b[{}m", bright + 3),
+        Color::LightBlue => write!(out, "\x1b[{}m", bright + 4),
+        Color::LightMagenta => write!(out, "\x1b[{}m", bright + 5),
+        Color::LightCyan => write!(out, "\x1b[{}m", bright + 6),
+        Color::White => write!(out, "\x1b[{}m", bright + 7),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+    const WHITE: [u8; 3] = [255, 255, 255];
+    const BLACK: [u8; 3] = [0, 0, 0];
+
+    /// An update of a frame two cells wide and one row high
+    fn update(cells: [Cell; 2]) -> CellUpdate {
+        CellUpdate {
+            width: 2,
+            rows: 1,
+            regions: vec![(Rect::new(0, 0, 2, 1), cells.to_vec())],
+        }
+    }
+
+    fn encode(encoder: &mut AnsiEncoder, update: &CellUpdate) -> String {
+        let mut out = String::new();
+        encoder.encode(update, &mut out);
+        out
+    }
+
+    #[test]
+    fn true_color() {
+        let mut encoder = AnsiEncoder::new(ColorMode::TrueColor);
+        assert_eq!(
+            encode(&mut encoder, &update([(RED, BLUE), (RED, BLUE)])),
+            "\x1b[1;1H\x1b[38;2;0;0;255m\x1b[48;2;255;0;0m\u{2584}\u{2584}\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn indexed_256() {
+        let mut encoder = AnsiEncoder::new(ColorMode::Indexed256);
+        assert_eq!(
+            encode(&mut encoder, &update([(RED, BLUE), (BLUE, RED)])),
+            "\x1b[1;1H\x1b[38;5;21m\x1b[48;5;196m\u{2584}\x1b[38;5;196m\x1b[48;5;21m\u{2584}\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn indexed_16() {
+        let mut encoder = AnsiEncoder::new(ColorMode::Indexed16);
+        assert_eq!(
+            encode(&mut encoder, &update([(RED, BLUE), (BLACK, WHITE)])),
+            "\x1b[1;1H\x1b[34m\x1b[101m\u{2584}\x1b[97m\x1b[40m\u{2584}\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn monochrome() {
+        let mut encoder = AnsiEncoder::new(ColorMode::Monochrome);
+        assert_eq!(
+            encode(&mut encoder, &update([(WHITE, BLACK), (WHITE, WHITE)])),
+            "\x1b[1;1H\x1b[39m\x1b[49m\u{2580}\u{2588}\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn only_changed_cells_until_reset() {
+        let mut encoder = AnsiEncoder::new(ColorMode::TrueColor);
+        let full = encode(&mut encoder, &update([(RED, RED), (BLUE, BLUE)]));
+
+        assert_eq!(encode(&mut encoder, &update([(RED, RED), (BLUE, BLUE)])), "");
+        assert_eq!(
+            encode(&mut encoder, &update([(RED, RED), (WHITE, WHITE)])),
+            "\x1b[1;2H\x1b[38;2;255;255;255m\x1b[48;2;255;255;255m\u{2584}\x1b[0m"
+        );
+
+        encoder.reset();
+        assert_eq!(encode(&mut encoder, &update([(RED, RED), (BLUE, BLUE)])), full);
+    }
+}
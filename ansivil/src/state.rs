@@ -1,11 +1,14 @@
 use std::{ffi::OsString, sync::Arc};
 
 use smithay::{
-    backend::allocator::{Format, Fourcc, Modifier},
+    backend::{
+        allocator::{Format, Fourcc, Modifier},
+        ratatui::RatatuiCursor,
+    },
     desktop::{PopupManager, Space, Window, WindowSurfaceType},
     input::{Seat, SeatState},
     reexports::{
-        calloop::{generic::Generic, EventLoop, Interest, LoopSignal, Mode, PostAction},
+        calloop::{generic::Generic, EventLoop, Interest, LoopHandle, LoopSignal, Mode, PostAction},
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
             protocol::wl_surface::WlSurface,
@@ -17,14 +20,17 @@ use smithay::{
         compositor::{CompositorClientState, CompositorState},
         dmabuf::{DmabufGlobal, DmabufState},
         output::OutputManagerState,
-        selection::data_device::DataDeviceState,
+        selection::{
+            data_device::DataDeviceState, primary_selection::PrimarySelectionState, SelectionTarget,
+        },
         shell::xdg::XdgShellState,
         shm::ShmState,
         socket::ListeningSocketSource,
+        xdg_system_bell::XdgSystemBellState,
     },
 };
 
-use crate::CalloopData;
+use crate::{input::TypedKeysyms, CalloopData};
 
 pub struct Smallvil {
     pub start_time: std::time::Instant,
@@ -33,6 +39,7 @@ pub struct Smallvil {
 
     pub space: Space<Window>,
     pub loop_signal: LoopSignal,
+    pub loop_handle: LoopHandle<'static, CalloopData>,
 
     // Smithay State
     pub compositor_state: CompositorState,
@@ -43,13 +50,24 @@ pub struct Smallvil {
     pub output_manager_state: OutputManagerState,
     pub seat_state: SeatState<Smallvil>,
     pub data_device_state: DataDeviceState,
+    pub primary_selection_state: PrimarySelectionState,
+    pub xdg_system_bell_state: XdgSystemBellState,
     pub popups: PopupManager,
 
     pub seat: Seat<Self>,
+
+    /// Selections of clients waiting to be copied to the terminal clipboard
+    pub terminal_selections: Vec<(SelectionTarget, Vec<u8>)>,
+    /// Cursor image requested by clients
+    pub cursor: RatatuiCursor,
+    /// Whether a client rang the bell since the last frame
+    pub bell_pending: bool,
+    /// Keysyms typed on spare keys of the seat keymap, created on first use
+    pub typed_keysyms: Option<TypedKeysyms>,
 }
 
 impl Smallvil {
-    pub fn new(event_loop: &mut EventLoop<CalloopData>, display: Display<Self>) -> Self {
+    pub fn new(event_loop: &mut EventLoop<'static, CalloopData>, display: Display<Self>) -> Self {
         let start_time = std::time::Instant::now();
 
         let dh = display.handle();
@@ -80,6 +98,8 @@ impl Smallvil {
         let output_manager_state = OutputManagerState::new_with_xdg_output::<Self>(&dh);
         let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<Self>(&dh);
+        let primary_selection_state = PrimarySelectionState::new::<Self>(&dh);
+        let xdg_system_bell_state = XdgSystemBellState::new::<Self>(&dh);
         let popups = PopupManager::default();
 
         // A seat is a group of keyboards, pointer and touch devices.
@@ -104,6 +124,7 @@ impl Smallvil {
 
         // Get the loop signal, used to stop the event loop
         let loop_signal = event_loop.get_signal();
+        let loop_handle = event_loop.handle();
 
         Self {
             start_time,
@@ -111,6 +132,7 @@ impl Smallvil {
 
             space,
             loop_signal,
+            loop_handle,
             socket_name,
 
             compositor_state,
@@ -121,8 +143,14 @@ impl Smallvil {
             output_manager_state,
             seat_state,
             data_device_state,
+            primary_selection_state,
+            xdg_system_bell_state,
             popups,
             seat,
+            terminal_selections: Vec::new(),
+            cursor: RatatuiCursor::new(),
+            bell_pending: false,
+            typed_keysyms: None,
         }
     }
 
@@ -0,0 +1,110 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            pixman::{PixmanRenderer, PixmanTexture},
+            Bind, Color32F, Frame, ImportMem, Offscreen, Renderer,
+        },
+    },
+    reexports::pixman::Image,
+    utils::{Buffer, Physical, Rectangle, Size, Transform},
+};
+
+/// A 4K output
+const OUTPUT_SIZE: (i32, i32) = (3840, 2160);
+/// Size of the window contents, upscaled to most of the output
+const TEXTURE_SIZE: (i32, i32) = (1920, 1080);
+
+/// Renders a frame with a background, a translucent panel and a scaled window into `target`
+fn render_frame(
+    renderer: &mut PixmanRenderer,
+    target: &mut Image<'static, 'static>,
+    texture: &PixmanTexture,
+    damage: Rectangle<i32, Physical>,
+) {
+    let size = Size::from(OUTPUT_SIZE);
+    let mut framebuffer = renderer.bind(target).unwrap();
+    let mut frame = renderer
+        .render(&mut framebuffer, size, Transform::Normal)
+        .unwrap();
+
+    frame.clear(Color32F::new(0.1, 0.1, 0.1, 1.0), &[damage]).unwrap();
+    let panel = Rectangle::new((0, 0).into(), (size.w, 2 * size.h / 3).into());
+    if let Some(mut damage) = damage.intersection(panel) {
+        damage.loc -= panel.loc;
+        frame
+            .draw_solid(panel, &[damage], Color32F::new(0.2, 0.0, 0.3, 0.6))
+            .unwrap();
+    }
+    let window = Rectangle::new(
+        (size.w / 8, size.h / 8).into(),
+        (3 * size.w / 4, 3 * size.h / 4).into(),
+    );
+    if let Some(mut damage) = damage.intersection(window) {
+        damage.loc -= window.loc;
+        frame
+            .render_texture_from_to(
+                texture,
+                Rectangle::from_size(Size::<i32, Buffer>::from(TEXTURE_SIZE).to_f64()),
+                window,
+                &[damage],
+                &[],
+                Transform::Normal,
+                0.9,
+            )
+            .unwrap();
+    }
+    let sync = frame.finish().unwrap();
+    renderer.wait(&sync).unwrap();
+}
+
+fn setup(threads: usize) -> (PixmanRenderer, Image<'static, 'static>, PixmanTexture) {
+    let mut renderer = PixmanRenderer::new().unwrap();
+    renderer.set_threads(threads);
+
+    let size = Size::from(TEXTURE_SIZE);
+    let pixels = (0..size.w * size.h)
+        .flat_map(|i| [(i % 256) as u8, (i / size.w % 256) as u8, 128, 255])
+        .collect::<Vec<_>>();
+    let texture = renderer
+        .import_memory(&pixels, Fourcc::Argb8888, size, false)
+        .unwrap();
+    let target = renderer
+        .create_buffer(Fourcc::Xrgb8888, Size::from(OUTPUT_SIZE))
+        .unwrap();
+
+    (renderer, target, texture)
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let available = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let mut threads = vec![1, 2, 4, available];
+    threads.sort_unstable();
+    threads.dedup();
+
+    let mut group = c.benchmark_group("PixmanRenderer full damage");
+    group.sample_size(20);
+    for &threads in &threads {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+            let (mut renderer, mut target, texture) = setup(threads);
+            let damage = Rectangle::from_size(Size::from(OUTPUT_SIZE));
+            b.iter(|| render_frame(&mut renderer, &mut target, &texture, damage))
+        });
+    }
+    group.finish();
+
+    // small damage is composited on a single thread, this should not regress with more threads
+    let mut group = c.benchmark_group("PixmanRenderer small damage");
+    for &threads in &threads {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &threads| {
+            let (mut renderer, mut target, texture) = setup(threads);
+            let damage = Rectangle::new((1000, 500).into(), (64, 64).into());
+            b.iter(|| render_frame(&mut renderer, &mut target, &texture, damage))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
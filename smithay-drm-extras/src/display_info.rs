@@ -35,3 +35,58 @@ pub fn for_connector(device: &impl ControlDevice, connector: connector::Handle)
 
     Info::parse_edid(&data).ok()
 }
+
+/// HDR capabilities of a display, as advertised in its EDID
+///
+/// ```no_run
+/// # mod helpers { include!("./docs/doctest_helpers.rs"); };
+/// # let drm_device: helpers::FakeDevice = todo!();
+/// # let connector = todo!();
+/// use smithay_drm_extras::display_info;
+///
+/// let info = display_info::for_connector(&drm_device, connector).unwrap();
+/// let caps = display_info::HdrCapabilities::from_info(&info);
+///
+/// if caps.supports_hdr() {
+///     println!("HDR display, peak luminance: {:?} cd/m²", caps.max_luminance);
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HdrCapabilities {
+    /// The display supports the SMPTE ST 2084 (perceptual quantizer) transfer function
+    pub pq: bool,
+    /// The display supports the hybrid log-gamma transfer function
+    pub hlg: bool,
+    /// The display supports BT.2020 RGB signals
+    pub bt2020_rgb: bool,
+    /// Desired content maximum luminance in cd/m², if known
+    pub max_luminance: Option<f32>,
+    /// Desired content maximum frame-average luminance in cd/m², if known
+    pub max_frame_average_luminance: Option<f32>,
+    /// Desired content minimum luminance in cd/m², if known
+    pub min_luminance: Option<f32>,
+}
+
+impl HdrCapabilities {
+    /// Read the HDR capabilities from the parsed EDID
+    pub fn from_info(info: &Info) -> Self {
+        let metadata = info.hdr_static_metadata();
+        let colorimetry = info.supported_signal_colorimetry();
+        // zero values are used to signal missing data
+        let known = |value: f32| (value > 0.0).then_some(value);
+
+        HdrCapabilities {
+            pq: metadata.pq,
+            hlg: metadata.hlg,
+            bt2020_rgb: colorimetry.bt2020_rgb,
+            max_luminance: known(metadata.desired_content_max_luminance),
+            max_frame_average_luminance: known(metadata.desired_content_max_frame_avg_luminance),
+            min_luminance: known(metadata.desired_content_min_luminance),
+        }
+    }
+
+    /// Returns `true` if the display supports any HDR transfer function
+    pub fn supports_hdr(&self) -> bool {
+        self.pq || self.hlg
+    }
+}
@@ -43,6 +43,20 @@
 //! provided buffer. Additionally the element has to be either fully opaque or the clear color has to match the CRTC
 //! background color and no overlap with an underlay is found.
 //!
+//! ### Color description
+//!
+//! Elements are only directly scanned out, if their [`Element::color_description`] matches the
+//! primaries and transfer function the output is driven with, see [`DrmCompositor::use_hdr`].
+//! Elements without a color description are assumed to be sRGB.
+//!
+//! # HDR
+//!
+//! To drive an HDR display the swapchain should use a 10-bit format, e.g. by passing
+//! [`TEN_BIT_COLOR_FORMATS`] as `color_formats` to [`DrmCompositor::new`], and
+//! [`DrmCompositor::use_hdr`] has to be called with the description of the rendered content.
+//! The compositor does not convert the rendered content, the used [`Renderer`] has to output
+//! content matching the description.
+//!
 //! # How to use it
 //!
 //! ```no_run
@@ -158,6 +172,7 @@ use crate::{
         drm::{plane_has_property, DrmError, PlaneDamageClips},
         renderer::{
             buffer_y_inverted,
+            color::{ColorDescription, NamedPrimaries, Primaries},
             damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
             element::{
                 Element, Id, Kind, RenderElement, RenderElementPresentationState, RenderElementState,
@@ -177,13 +192,21 @@ use crate::{
 use super::{
     error::AccessError,
     exporter::{gbm::GbmFramebufferExporter, gbm::NodeFilter, ExportBuffer, ExportFramebuffer},
-    surface::VrrSupport,
+    surface::{Colorspace, HdrMetadata, VrrSupport},
     DrmSurface, Framebuffer, PlaneClaim, PlaneInfo, Planes,
 };
 
 mod elements;
 mod frame_result;
 
+/// Formats with 10 bits per color channel, usable for the swapchain of HDR outputs
+pub const TEN_BIT_COLOR_FORMATS: [DrmFourcc; 4] = [
+    DrmFourcc::Abgr2101010,
+    DrmFourcc::Argb2101010,
+    DrmFourcc::Xbgr2101010,
+    DrmFourcc::Xrgb2101010,
+];
+
 use elements::*;
 pub use frame_result::*;
 
@@ -756,6 +779,21 @@ impl<B: Buffer, F: Framebuffer> FrameState<B, F> {
         )
     }
 
+    #[profiling::function]
+    fn page_flip_async(
+        &mut self,
+        surface: &DrmSurface,
+        supports_fencing: bool,
+        allow_partial_update: bool,
+        event: bool,
+    ) -> Result<(), crate::backend::drm::error::Error> {
+        debug_assert!(!self.planes.iter().any(|(_, state)| state.needs_test));
+        surface.page_flip_async(
+            self.build_planes(surface, supports_fencing, allow_partial_update),
+            event,
+        )
+    }
+
     #[profiling::function]
     fn build_planes<'a>(
         &'a mut self,
@@ -990,6 +1028,7 @@ enum PreparedFrameKind {
 struct PreparedFrame<A: Allocator, F: ExportFramebuffer<<A as Allocator>::Buffer>> {
     frame: CompositorFrameState<A, F>,
     kind: PreparedFrameKind,
+    async_flip: bool,
 }
 
 impl<A: Allocator, F: ExportFramebuffer<<A as Allocator>::Buffer>> PreparedFrame<A, F> {
@@ -1032,6 +1071,10 @@ bitflags::bitflags! {
         const ALLOW_CURSOR_PLANE_SCANOUT = 8;
         /// Return `EmptyFrame`, if only the cursor plane would have been updated
         const SKIP_CURSOR_ONLY_UPDATES = 16;
+        /// Allow to present the frame without waiting for the next vblank, if only the
+        /// buffer of an element directly scanned out on the primary plane and spanning the
+        /// complete output changed and the element allows tearing (see [`Element::allows_tearing`])
+        const ALLOW_TEARING = 32;
         /// Allow to realize the frame by assigning elements on any plane
         const ALLOW_SCANOUT = Self::ALLOW_PRIMARY_PLANE_SCANOUT.bits() | Self::ALLOW_OVERLAY_PLANE_SCANOUT.bits() | Self::ALLOW_CURSOR_PLANE_SCANOUT.bits();
         /// Safe default set of flags
@@ -1059,6 +1102,7 @@ where
     primary_plane_element_id: Id,
     primary_plane_damage_bag: DamageBag<i32, BufferCoords>,
     supports_fencing: bool,
+    supports_async_page_flip: bool,
     reset_pending: bool,
     signaled_fence: Option<Arc<OwnedFd>>,
 
@@ -1080,6 +1124,7 @@ where
     element_opaque_regions_workhouse: Vec<Rectangle<i32, Physical>>,
 
     debug_flags: DebugFlags,
+    color_description: ColorDescription,
     span: tracing::Span,
 }
 
@@ -1202,6 +1247,7 @@ where
                 })?
             && plane_has_property(&*surface, surface.plane(), "IN_FENCE_FD")?
             && !(is_nvidia && nvidia_drm_version().unwrap_or((0, 0, 0)) < (560, 35, 3));
+        let supports_async_page_flip = surface.async_page_flip_supported();
 
         for format in color_formats {
             debug!("Testing color format: {}", format);
@@ -1265,7 +1311,9 @@ where
                         opaque_regions: Vec::new(),
                         element_opaque_regions_workhouse: Vec::new(),
                         supports_fencing,
+                        supports_async_page_flip,
                         debug_flags: DebugFlags::empty(),
+                        color_description: ColorDescription::srgb(),
                         span,
                     };
 
@@ -1385,6 +1433,7 @@ where
                 })?
             && plane_has_property(&*surface, surface.plane(), "IN_FENCE_FD")?
             && !(is_nvidia && nvidia_drm_version().unwrap_or((0, 0, 0)) < (560, 35, 3));
+        let supports_async_page_flip = surface.async_page_flip_supported();
 
         let (swapchain, is_opaque) = Self::test_format(
             &surface,
@@ -1447,7 +1496,9 @@ where
             opaque_regions: Vec::new(),
             element_opaque_regions_workhouse: Vec::new(),
             supports_fencing,
+            supports_async_page_flip,
             debug_flags: DebugFlags::empty(),
+            color_description: ColorDescription::srgb(),
             span,
         };
 
@@ -1965,6 +2016,7 @@ where
         // This will hold the element that has been selected for direct scan-out on
         // the primary plane if any
         let mut primary_plane_scanout_element: Option<&'a E> = None;
+        let mut primary_plane_scanout_spans_output = false;
         // This will hold all elements that have been assigned to the primary plane
         // for rendering
         let mut primary_plane_elements: Vec<&'a E> = Vec::with_capacity(elements.len());
@@ -2030,7 +2082,11 @@ where
                         drm::control::PlaneType::Overlay => {
                             overlay_plane_elements.insert(direct_scan_out_plane.handle, element);
                         }
-                        drm::control::PlaneType::Primary => primary_plane_scanout_element = Some(element),
+                        drm::control::PlaneType::Primary => {
+                            primary_plane_scanout_element = Some(element);
+                            primary_plane_scanout_spans_output =
+                                element_geometry.contains_rect(output_geometry);
+                        }
                         drm::control::PlaneType::Cursor => cursor_plane_element = Some(element),
                     }
 
@@ -2385,6 +2441,24 @@ where
             }
         }
 
+        // Async page flips can only replace the framebuffer of the primary plane,
+        // everything else has to stay the same
+        let async_flip = frame_flags.contains(FrameFlags::ALLOW_TEARING)
+            && self.supports_async_page_flip
+            && allow_partial_update
+            && primary_plane_scanout_spans_output
+            && primary_plane_scanout_element.is_some_and(|element| element.allows_tearing())
+            && next_frame_state.planes.iter().all(|(plane, state)| {
+                if *plane != self.surface.plane() {
+                    return state.skip;
+                }
+                let previous_config = previous_state.plane_state(*plane).and_then(|s| s.config.as_ref());
+                match (state.config.as_ref(), previous_config) {
+                    (Some(config), Some(previous_config)) => config.is_compatible(previous_config),
+                    _ => false,
+                }
+            });
+
         let next_frame = PreparedFrame {
             kind: if allow_partial_update {
                 PreparedFrameKind::Partial
@@ -2392,6 +2466,7 @@ where
                 PreparedFrameKind::Full
             },
             frame: next_frame_state,
+            async_flip,
         };
         let frame_reference: RenderFrameResult<'a, A::Buffer, F::Framebuffer, E> = RenderFrameResult {
             is_empty: next_frame.is_empty(),
@@ -2538,6 +2613,22 @@ where
             prepared_frame
                 .frame
                 .commit(&self.surface, self.supports_fencing, allow_partial_update, true)
+        } else if prepared_frame.async_flip {
+            prepared_frame
+                .frame
+                .page_flip_async(&self.surface, self.supports_fencing, allow_partial_update, true)
+                .or_else(|err| {
+                    debug!(
+                        "async page flip failed, falling back to a regular page flip: {}",
+                        err
+                    );
+                    prepared_frame.frame.page_flip(
+                        &self.surface,
+                        self.supports_fencing,
+                        allow_partial_update,
+                        true,
+                    )
+                })
         } else {
             prepared_frame
                 .frame
@@ -2731,6 +2822,121 @@ where
         self.surface.use_vrr(vrr).map_err(FrameError::DrmError)
     }
 
+    /// Returns the number of entries per color channel of the gamma ramp.
+    ///
+    /// See [`DrmSurface::gamma_size`] for more details.
+    pub fn gamma_size(&self) -> FrameResult<u32, A, F> {
+        self.surface.gamma_size().map_err(FrameError::DrmError)
+    }
+
+    /// Sets a new gamma ramp to be used starting with the next frame.
+    ///
+    /// See [`DrmSurface::set_gamma`] for more details.
+    pub fn set_gamma(&mut self, red: &[u16], green: &[u16], blue: &[u16]) -> FrameResult<(), A, F> {
+        self.surface
+            .set_gamma(red, green, blue)
+            .map_err(FrameError::DrmError)
+    }
+
+    /// Restores the original gamma ramp starting with the next frame.
+    ///
+    /// See [`DrmSurface::reset_gamma`] for more details.
+    pub fn reset_gamma(&mut self) -> FrameResult<(), A, F> {
+        self.surface.reset_gamma().map_err(FrameError::DrmError)
+    }
+
+    /// Returns the [`Colorspace`]s supported by the given connector.
+    ///
+    /// See [`DrmSurface::supported_colorspaces`] for more details.
+    pub fn supported_colorspaces(&self, conn: connector::Handle) -> FrameResult<Vec<Colorspace>, A, F> {
+        self.surface
+            .supported_colorspaces(conn)
+            .map_err(FrameError::DrmError)
+    }
+
+    /// Tries to set the [`Colorspace`] signaled to the connectors starting with the next frame.
+    ///
+    /// See [`DrmSurface::set_colorspace`] for more details.
+    pub fn set_colorspace(&mut self, colorspace: Colorspace) -> FrameResult<(), A, F> {
+        self.surface
+            .set_colorspace(colorspace)
+            .map_err(FrameError::DrmError)
+    }
+
+    /// Returns if the given connector supports sending [`HdrMetadata`].
+    ///
+    /// See [`DrmSurface::hdr_metadata_supported`] for more details.
+    pub fn hdr_metadata_supported(&self, conn: connector::Handle) -> FrameResult<bool, A, F> {
+        self.surface
+            .hdr_metadata_supported(conn)
+            .map_err(FrameError::DrmError)
+    }
+
+    /// Tries to set the [`HdrMetadata`] sent to the connectors starting with the next frame.
+    ///
+    /// See [`DrmSurface::set_hdr_metadata`] for more details.
+    pub fn set_hdr_metadata(&mut self, metadata: Option<HdrMetadata>) -> FrameResult<(), A, F> {
+        self.surface
+            .set_hdr_metadata(metadata)
+            .map_err(FrameError::DrmError)
+    }
+
+    /// Returns the range of values supported by the `max bpc` property of the given connector.
+    ///
+    /// See [`DrmSurface::max_bpc_range`] for more details.
+    pub fn max_bpc_range(&self, conn: connector::Handle) -> FrameResult<(u32, u32), A, F> {
+        self.surface.max_bpc_range(conn).map_err(FrameError::DrmError)
+    }
+
+    /// Tries to set the maximum bits per color channel starting with the next frame.
+    ///
+    /// See [`DrmSurface::set_max_bpc`] for more details.
+    pub fn set_max_bpc(&mut self, bpc: u32) -> FrameResult<(), A, F> {
+        self.surface.set_max_bpc(bpc).map_err(FrameError::DrmError)
+    }
+
+    /// Returns the [`ColorDescription`] of the content sent to the output
+    pub fn color_description(&self) -> &ColorDescription {
+        &self.color_description
+    }
+
+    /// Switches the output to HDR or back to SDR starting with the next frame.
+    ///
+    /// With `Some` description, the `Colorspace` matching the primaries is selected, [`HdrMetadata`]
+    /// derived from the description is sent and at least 10 bits per color channel are requested,
+    /// if supported by the connectors. `None` restores the defaults for sRGB content.
+    ///
+    /// The description is also used to decide, which elements can be directly scanned out.
+    /// Doing so will likely cause the next frame to trigger a modeset.
+    pub fn use_hdr(&mut self, description: Option<ColorDescription>) -> FrameResult<(), A, F> {
+        let colorspace = match description.map(|description| description.primaries) {
+            Some(Primaries::Named(NamedPrimaries::Bt2020)) => Colorspace::Bt2020Rgb,
+            Some(Primaries::Named(NamedPrimaries::DciP3)) => Colorspace::DciP3RgbTheater,
+            Some(Primaries::Named(NamedPrimaries::DisplayP3)) => Colorspace::DciP3RgbD65,
+            _ => Colorspace::Default,
+        };
+        self.set_colorspace(colorspace)?;
+        self.set_hdr_metadata(description.as_ref().map(HdrMetadata::from_color_description))?;
+
+        if description.is_some() {
+            let max = self
+                .surface
+                .pending_connectors()
+                .into_iter()
+                .filter_map(|conn| self.surface.max_bpc_range(conn).ok())
+                .map(|(_, max)| max)
+                .min();
+            if let Some(max) = max.filter(|max| *max >= 10) {
+                self.set_max_bpc(max.min(12))?;
+            }
+        } else if self.surface.max_bpc().is_some_and(|bpc| bpc > 8) {
+            self.set_max_bpc(8)?;
+        }
+
+        self.color_description = description.unwrap_or_else(ColorDescription::srgb);
+        Ok(())
+    }
+
     /// Set the [`DebugFlags`] to use
     ///
     /// Note: This will reset the primary plane swapchain if
@@ -2829,6 +3035,18 @@ where
             return Err(None);
         };
 
+        // The element has to match the colorimetry the output is driven with
+        let element_description = element.color_description().unwrap_or_default();
+        if element_description.primaries != self.color_description.primaries
+            || element_description.transfer_function != self.color_description.transfer_function
+        {
+            trace!(
+                "skipping direct scan-out for element {:?}, color description does not match the output",
+                element.id()
+            );
+            return Err(None);
+        }
+
         let mut rendering_reason: Option<RenderingReason> = None;
 
         if try_assign_primary_plane {
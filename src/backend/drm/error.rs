@@ -86,6 +86,22 @@ pub enum Error {
     /// Atomic Test failed for new properties
     #[error("Atomic Test failed for new properties on crtc ({0:?})")]
     TestFailed(crtc::Handle),
+    /// The provided gamma ramp does not match the gamma size of the crtc
+    #[error("The gamma ramp does not match the gamma size ({expected}) of crtc ({crtc:?})")]
+    InvalidGammaSize {
+        /// CRTC
+        crtc: crtc::Handle,
+        /// Expected number of elements per color channel
+        expected: usize,
+    },
+    /// The requested max bpc value is out of the range supported by the connector
+    #[error("The connector ({connector:?}) does not support a max bpc value of {bpc}")]
+    UnsupportedMaxBpc {
+        /// Connector
+        connector: connector::Handle,
+        /// Requested max bpc value
+        bpc: u32,
+    },
 }
 
 impl From<Error> for SwapBuffersError {
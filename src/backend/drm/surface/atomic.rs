@@ -11,7 +11,7 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 #[cfg(debug_assertions)]
 use std::fmt;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsFd, AsRawFd};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex, RwLock,
@@ -34,7 +34,17 @@ use crate::{
 
 use tracing::{debug, info, info_span, instrument, trace, warn};
 
-use super::{PlaneConfig, PlaneState, VrrSupport};
+use super::{Colorspace, HdrMetadata, PlaneConfig, PlaneState, VrrSupport};
+
+// Properties applied to all connectors of the surface.
+// `None` values are left untouched, as the property is unsupported or its value unknown.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConnectorState {
+    pub hdr_metadata: Option<HdrMetadata>,
+    pub hdr_metadata_blob: u64,
+    pub colorspace: Option<(Colorspace, u64)>,
+    pub max_bpc: Option<u64>,
+}
 
 #[derive(Debug, Clone)]
 pub struct State {
@@ -42,6 +52,8 @@ pub struct State {
     pub mode: Mode,
     pub blob: property::Value<'static>,
     pub vrr: bool,
+    pub gamma_lut: u64,
+    pub connector_state: ConnectorState,
     pub connectors: HashSet<connector::Handle>,
 }
 
@@ -51,6 +63,10 @@ impl PartialEq for State {
         self.active == other.active
             && self.mode == other.mode
             && self.vrr == other.vrr
+            && self.gamma_lut == other.gamma_lut
+            && self.connector_state.hdr_metadata_blob == other.connector_state.hdr_metadata_blob
+            && self.connector_state.colorspace == other.connector_state.colorspace
+            && self.connector_state.max_bpc == other.connector_state.max_bpc
             && self.connectors == other.connectors
     }
 }
@@ -119,23 +135,55 @@ impl State {
             }
         }
 
-        // Get the current active (dpms) state and vrr state of the CRTC
+        // Get the current hdr metadata, colorspace and max bpc of the connectors
+        let mut connector_state = ConnectorState::default();
+        for conn in &current_connectors {
+            let Ok(props) = fd.get_properties(*conn) else {
+                continue;
+            };
+            let mapping = prop_mapping.connectors.get(conn);
+            let hdr_prop = mapping.and_then(|m| m.get("HDR_OUTPUT_METADATA"));
+            let colorspace_prop = mapping.and_then(|m| m.get("Colorspace"));
+            let bpc_prop = mapping.and_then(|m| m.get("max bpc"));
+            let (ids, vals) = props.as_props_and_values();
+            for (&id, &val) in ids.iter().zip(vals.iter()) {
+                if Some(&id) == hdr_prop {
+                    connector_state.hdr_metadata_blob = val;
+                } else if Some(&id) == colorspace_prop {
+                    if let Ok(info) = fd.get_property(id) {
+                        if let property::Value::Enum(Some(value)) = info.value_type().convert_value(val) {
+                            connector_state.colorspace = value
+                                .name()
+                                .to_str()
+                                .ok()
+                                .and_then(Colorspace::from_property_name)
+                                .map(|colorspace| (colorspace, val));
+                        }
+                    }
+                } else if Some(&id) == bpc_prop {
+                    connector_state.max_bpc = Some(val);
+                }
+            }
+        }
+
+        // Get the current active (dpms) state, vrr state and gamma lut of the CRTC
         //
         // Changing a CRTC to active might require a modeset
         let mut active = None;
         let mut vrr = None;
+        let mut gamma_lut = None;
         if let Ok(props) = fd.get_properties(crtc) {
             let active_prop = prop_mapping.crtcs.get(&crtc).and_then(|m| m.get("ACTIVE"));
             let vrr_prop = prop_mapping.crtcs.get(&crtc).and_then(|m| m.get("VRR_ENABLED"));
+            let gamma_prop = prop_mapping.crtcs.get(&crtc).and_then(|m| m.get("GAMMA_LUT"));
             let (ids, vals) = props.as_props_and_values();
             for (&id, &val) in ids.iter().zip(vals.iter()) {
                 if Some(&id) == active_prop {
                     active = property::ValueType::Boolean.convert_value(val).as_boolean();
-                    break;
-                }
-                if Some(&id) == vrr_prop {
+                } else if Some(&id) == vrr_prop {
                     vrr = property::ValueType::Boolean.convert_value(val).as_boolean();
-                    break;
+                } else if Some(&id) == gamma_prop {
+                    gamma_lut = Some(val);
                 }
             }
         }
@@ -148,6 +196,9 @@ impl State {
             blob: current_blob,
             // If we don't know the VRR state, the driver doesn't support the property
             vrr: vrr.unwrap_or(false),
+            // No gamma lut set or not supported by the driver
+            gamma_lut: gamma_lut.unwrap_or(0),
+            connector_state,
             connectors: current_connectors,
         })
     }
@@ -171,6 +222,14 @@ pub struct AtomicDrmSurface {
     prop_mapping: Arc<RwLock<PropMapping>>,
     state: RwLock<State>,
     pending: RwLock<State>,
+    // gamma lut set before we took over the crtc, not owned by us and never committed
+    foreign_gamma_lut: u64,
+    // copy of the gamma lut of the crtc before `set_gamma` changed it, restored by `reset_gamma`
+    original_gamma_lut: Mutex<Option<u64>>,
+    // the gamma lut was changed without a modeset and is committed with the next page flip
+    gamma_lut_changed: AtomicBool,
+    // hdr metadata set before we took over the crtc, not owned by us
+    original_hdr_metadata: u64,
     pub(super) span: tracing::Span,
 }
 
@@ -200,11 +259,15 @@ impl AtomicDrmSurface {
                 source,
             })
         })?;
+        let foreign_gamma_lut = state.gamma_lut;
+        let original_hdr_metadata = state.connector_state.hdr_metadata_blob;
         let pending = State {
             active: true,
             mode,
             blob,
             vrr: false,
+            gamma_lut: foreign_gamma_lut,
+            connector_state: state.connector_state,
             connectors: connectors.iter().copied().collect(),
         };
 
@@ -218,6 +281,10 @@ impl AtomicDrmSurface {
             prop_mapping,
             state: RwLock::new(state),
             pending: RwLock::new(pending),
+            foreign_gamma_lut,
+            original_gamma_lut: Mutex::new(None),
+            gamma_lut_changed: AtomicBool::new(false),
+            original_hdr_metadata,
             span,
         };
 
@@ -361,6 +428,8 @@ impl AtomicDrmSurface {
                 self.crtc,
                 Some(pending.blob),
                 pending.vrr,
+                None,
+                &pending.connector_state,
                 &connectors,
                 [],
                 [&plane_state],
@@ -420,6 +489,8 @@ impl AtomicDrmSurface {
             self.crtc,
             Some(pending.blob),
             pending.vrr,
+            None,
+            &pending.connector_state,
             &connectors,
             [&conn],
             [&plane_state],
@@ -477,6 +548,8 @@ impl AtomicDrmSurface {
             self.crtc,
             Some(pending.blob),
             pending.vrr,
+            None,
+            &pending.connector_state,
             &conns,
             removed,
             [&plane_state],
@@ -531,6 +604,8 @@ impl AtomicDrmSurface {
             self.crtc,
             Some(new_blob),
             pending.vrr,
+            None,
+            &pending.connector_state,
             pending.connectors.iter(),
             [],
             [&plane_state],
@@ -673,6 +748,415 @@ impl AtomicDrmSurface {
         Ok(())
     }
 
+    pub fn gamma_size(&self) -> Result<u32, Error> {
+        let prop = self
+            .prop_mapping
+            .read()
+            .unwrap()
+            .crtc_prop_handle(self.crtc, "GAMMA_LUT_SIZE")?;
+        let props = self.fd.get_properties(self.crtc).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Error loading crtc properties",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })?;
+
+        let (ids, vals) = props.as_props_and_values();
+        ids.iter()
+            .zip(vals.iter())
+            .find(|(id, _)| **id == prop)
+            .map(|(_, val)| *val as u32)
+            .ok_or(Error::UnknownProperty {
+                handle: self.crtc.into(),
+                name: "GAMMA_LUT_SIZE",
+            })
+    }
+
+    pub fn set_gamma(&self, red: &[u16], green: &[u16], blue: &[u16]) -> Result<(), Error> {
+        let size = self.gamma_size()? as usize;
+        if red.len() != size || green.len() != size || blue.len() != size {
+            return Err(Error::InvalidGammaSize {
+                crtc: self.crtc,
+                expected: size,
+            });
+        }
+
+        let mut lut = red
+            .iter()
+            .zip(green)
+            .zip(blue)
+            .map(|((&red, &green), &blue)| drm_ffi::drm_color_lut {
+                red,
+                green,
+                blue,
+                reserved: 0,
+            })
+            .collect::<Vec<_>>();
+        // SAFETY: `drm_color_lut` is a plain `repr(C)` struct without padding
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(
+                lut.as_mut_ptr() as *mut u8,
+                lut.len() * std::mem::size_of::<drm_ffi::drm_color_lut>(),
+            )
+        };
+        let mut original = self.original_gamma_lut.lock().unwrap();
+        if original.is_none() {
+            // the lut of the crtc may not be ours, so it is restored from a copy
+            let current = self.pending.read().unwrap().gamma_lut;
+            *original = Some(if current != 0 {
+                let mut data = self.fd.get_property_blob(current).map_err(|source| {
+                    Error::Access(AccessError {
+                        errmsg: "Failed to read gamma lut property blob",
+                        dev: self.fd.dev_path(),
+                        source,
+                    })
+                })?;
+                self.create_gamma_lut(&mut data)?
+            } else {
+                0
+            });
+        }
+
+        let blob = self.create_gamma_lut(data)?;
+        let res = self.use_gamma_lut(blob);
+        if res.is_err() {
+            self.destroy_gamma_lut(blob);
+        }
+        res
+    }
+
+    pub fn reset_gamma(&self) -> Result<(), Error> {
+        let mut original = self.original_gamma_lut.lock().unwrap();
+        let Some(blob) = *original else {
+            return Ok(());
+        };
+        self.use_gamma_lut(blob)?;
+        *original = None;
+        Ok(())
+    }
+
+    fn create_gamma_lut(&self, data: &mut [u8]) -> Result<u64, Error> {
+        let blob = drm_ffi::mode::create_property_blob(self.fd.as_fd(), data).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Failed to create Property Blob for gamma lut",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })?;
+        Ok(blob.blob_id as u64)
+    }
+
+    fn use_gamma_lut(&self, blob: u64) -> Result<(), Error> {
+        let mut current = self.state.write().unwrap();
+        let mut pending = self.pending.write().unwrap();
+        if pending.gamma_lut == blob {
+            return Ok(());
+        }
+
+        let test_buffer = self.create_test_buffer(pending.mode.size(), self.plane)?;
+        let plane_config = PlaneState {
+            handle: self.plane,
+            config: Some(PlaneConfig {
+                src: Rectangle::from_size(pending.mode.size().into()).to_f64(),
+                dst: Rectangle::from_size(
+                    (pending.mode.size().0 as i32, pending.mode.size().1 as i32).into(),
+                ),
+                transform: Transform::Normal,
+                alpha: 1.0,
+                damage_clips: None,
+                fb: test_buffer.fb,
+                fence: None,
+            }),
+        };
+
+        let mut new_state = pending.clone();
+        new_state.gamma_lut = blob;
+        if *current == *pending {
+            // Try a non modesetting commit, the lut will be applied with the next page flip
+            if self
+                .test_state_internal([plane_config.clone()], false, &current, &new_state)
+                .is_ok()
+            {
+                let old = std::mem::replace(&mut current.gamma_lut, blob);
+                pending.gamma_lut = blob;
+                self.gamma_lut_changed.store(true, Ordering::SeqCst);
+                self.destroy_gamma_lut(old);
+                return Ok(());
+            }
+        }
+
+        // Try a modeset commit
+        self.test_state_internal([plane_config], true, &current, &new_state)?;
+        let old = std::mem::replace(&mut pending.gamma_lut, blob);
+        if old != current.gamma_lut {
+            self.destroy_gamma_lut(old);
+        }
+        Ok(())
+    }
+
+    fn destroy_gamma_lut(&self, blob: u64) {
+        // the lut of the crtc before we took it over is not owned by us
+        if blob == 0 || blob == self.foreign_gamma_lut {
+            return;
+        }
+        if let Err(err) = self.fd.destroy_property_blob(blob) {
+            warn!("Failed to destroy old gamma lut property blob: {}", err);
+        }
+    }
+
+    fn connector_property(
+        &self,
+        conn: connector::Handle,
+        name: &'static str,
+    ) -> Result<Option<(property::Info, property::RawValue)>, Error> {
+        self.ensure_props_known(&[conn])?;
+        let Ok(prop) = self.prop_mapping.read().unwrap().conn_prop_handle(conn, name) else {
+            return Ok(None);
+        };
+
+        let info = self.fd.get_property(prop).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Error querying property",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })?;
+        let props = self.fd.get_properties(conn).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Error querying properties",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })?;
+        let (ids, vals) = props.as_props_and_values();
+        let value = ids
+            .iter()
+            .zip(vals.iter())
+            .find(|(id, _)| **id == prop)
+            .map(|(_, val)| *val)
+            .unwrap_or(0);
+
+        Ok(Some((info, value)))
+    }
+
+    pub fn supported_colorspaces(&self, conn: connector::Handle) -> Result<Vec<Colorspace>, Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let Some((info, _)) = self.connector_property(conn, "Colorspace")? else {
+            return Ok(Vec::new());
+        };
+        let ValueType::Enum(values) = info.value_type() else {
+            return Ok(Vec::new());
+        };
+
+        Ok(values
+            .values()
+            .1
+            .iter()
+            .filter_map(|value| {
+                value
+                    .name()
+                    .to_str()
+                    .ok()
+                    .and_then(Colorspace::from_property_name)
+            })
+            .collect())
+    }
+
+    pub fn colorspace(&self) -> Colorspace {
+        self.pending
+            .read()
+            .unwrap()
+            .connector_state
+            .colorspace
+            .map(|(colorspace, _)| colorspace)
+            .unwrap_or_default()
+    }
+
+    pub fn set_colorspace(&self, colorspace: Colorspace) -> Result<(), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let mut value = None;
+        for conn in self.pending_connectors() {
+            let enum_value = self
+                .connector_property(conn, "Colorspace")?
+                .and_then(|(info, _)| match info.value_type() {
+                    ValueType::Enum(values) => values
+                        .values()
+                        .1
+                        .iter()
+                        .find(|value| value.name().to_str() == Ok(colorspace.property_name()))
+                        .map(|value| value.value()),
+                    _ => None,
+                });
+
+            match enum_value {
+                Some(enum_value) => value = Some((colorspace, enum_value)),
+                // every connector implicitly supports the default colorimetry
+                None if colorspace == Colorspace::Default => {}
+                None => {
+                    return Err(Error::UnknownProperty {
+                        handle: conn.into(),
+                        name: "Colorspace",
+                    })
+                }
+            }
+        }
+
+        let mut state = self.pending.read().unwrap().connector_state;
+        state.colorspace = value;
+        self.use_connector_state(state)
+    }
+
+    pub fn hdr_metadata_supported(&self, conn: connector::Handle) -> Result<bool, Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        Ok(self.connector_property(conn, "HDR_OUTPUT_METADATA")?.is_some())
+    }
+
+    pub fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        self.pending.read().unwrap().connector_state.hdr_metadata
+    }
+
+    pub fn set_hdr_metadata(&self, metadata: Option<HdrMetadata>) -> Result<(), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let mut state = self.pending.read().unwrap().connector_state;
+        if state.hdr_metadata == metadata && (metadata.is_some() || state.hdr_metadata_blob == 0) {
+            return Ok(());
+        }
+
+        let blob = match metadata {
+            Some(metadata) => {
+                let mut raw = metadata.to_raw();
+                // SAFETY: `hdr_output_metadata` is a plain `repr(C)` struct
+                let data = unsafe {
+                    std::slice::from_raw_parts_mut(
+                        &mut raw as *mut drm_ffi::hdr_output_metadata as *mut u8,
+                        std::mem::size_of::<drm_ffi::hdr_output_metadata>(),
+                    )
+                };
+                drm_ffi::mode::create_property_blob(self.fd.as_fd(), data)
+                    .map_err(|source| {
+                        Error::Access(AccessError {
+                            errmsg: "Failed to create Property Blob for hdr metadata",
+                            dev: self.fd.dev_path(),
+                            source,
+                        })
+                    })?
+                    .blob_id as u64
+            }
+            None => 0,
+        };
+
+        state.hdr_metadata = metadata;
+        state.hdr_metadata_blob = blob;
+        let res = self.use_connector_state(state);
+        if res.is_err() {
+            self.destroy_hdr_metadata(blob);
+        }
+        res
+    }
+
+    pub fn max_bpc_range(&self, conn: connector::Handle) -> Result<(u32, u32), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        match self.connector_property(conn, "max bpc")? {
+            Some((info, _)) => match info.value_type() {
+                ValueType::UnsignedRange(min, max) => Ok((min as u32, max as u32)),
+                _ => Err(Error::UnknownProperty {
+                    handle: conn.into(),
+                    name: "max bpc",
+                }),
+            },
+            None => Err(Error::UnknownProperty {
+                handle: conn.into(),
+                name: "max bpc",
+            }),
+        }
+    }
+
+    pub fn max_bpc(&self) -> Option<u32> {
+        self.pending
+            .read()
+            .unwrap()
+            .connector_state
+            .max_bpc
+            .map(|bpc| bpc as u32)
+    }
+
+    pub fn set_max_bpc(&self, bpc: u32) -> Result<(), Error> {
+        for conn in self.pending_connectors() {
+            let (min, max) = self.max_bpc_range(conn)?;
+            if !(min..=max).contains(&bpc) {
+                return Err(Error::UnsupportedMaxBpc { connector: conn, bpc });
+            }
+        }
+
+        let mut state = self.pending.read().unwrap().connector_state;
+        state.max_bpc = Some(bpc as u64);
+        self.use_connector_state(state)
+    }
+
+    // Connector properties are only set on commits, so unlike the crtc properties
+    // they can't be applied with the next page flip.
+    fn use_connector_state(&self, state: ConnectorState) -> Result<(), Error> {
+        let current = self.state.read().unwrap();
+        let mut pending = self.pending.write().unwrap();
+        if pending.connector_state == state {
+            return Ok(());
+        }
+
+        let test_buffer = self.create_test_buffer(pending.mode.size(), self.plane)?;
+        let plane_config = PlaneState {
+            handle: self.plane,
+            config: Some(PlaneConfig {
+                src: Rectangle::from_size(pending.mode.size().into()).to_f64(),
+                dst: Rectangle::from_size(
+                    (pending.mode.size().0 as i32, pending.mode.size().1 as i32).into(),
+                ),
+                transform: Transform::Normal,
+                alpha: 1.0,
+                damage_clips: None,
+                fb: test_buffer.fb,
+                fence: None,
+            }),
+        };
+
+        let mut new_state = pending.clone();
+        new_state.connector_state = state;
+        self.test_state_internal([plane_config], true, &current, &new_state)?;
+
+        let old = std::mem::replace(&mut pending.connector_state, state);
+        if old.hdr_metadata_blob != current.connector_state.hdr_metadata_blob
+            && old.hdr_metadata_blob != state.hdr_metadata_blob
+        {
+            self.destroy_hdr_metadata(old.hdr_metadata_blob);
+        }
+        Ok(())
+    }
+
+    fn destroy_hdr_metadata(&self, blob: u64) {
+        // the original metadata is not owned by us
+        if blob == 0 || blob == self.original_hdr_metadata {
+            return;
+        }
+        if let Err(err) = self.fd.destroy_property_blob(blob) {
+            warn!("Failed to destroy old hdr metadata property blob: {}", err);
+        }
+    }
+
     pub fn commit_pending(&self) -> bool {
         *self.pending.read().unwrap() != *self.state.read().unwrap()
     }
@@ -708,11 +1192,14 @@ impl AtomicDrmSurface {
         let removed = current_conns.difference(&pending_conns);
         let prop_mapping = self.prop_mapping.read().unwrap();
 
+        let gamma_lut = (pending.gamma_lut != current.gamma_lut).then_some(pending.gamma_lut);
         let req = AtomicRequest::build_request(
             &prop_mapping,
             self.crtc,
             Some(pending.blob),
             pending.vrr,
+            gamma_lut,
+            &pending.connector_state,
             &pending_conns,
             removed,
             &*planes,
@@ -777,6 +1264,11 @@ impl AtomicDrmSurface {
 
         trace!("Testing screen config");
 
+        // the gamma lut is only set, if it changed since the last commit
+        let gamma_lut = (self.gamma_lut_changed.load(Ordering::SeqCst)
+            || current.gamma_lut != pending.gamma_lut)
+            .then_some(pending.gamma_lut);
+
         // test the new config and return the request if it would be accepted by the driver.
         let prop_mapping = self.prop_mapping.read().unwrap();
         let req = {
@@ -785,6 +1277,8 @@ impl AtomicDrmSurface {
                 self.crtc,
                 Some(pending.blob),
                 pending.vrr,
+                gamma_lut,
+                &pending.connector_state,
                 &pending_conns,
                 removed,
                 &*planes,
@@ -838,6 +1332,13 @@ impl AtomicDrmSurface {
             });
 
         if result.is_ok() {
+            if current.gamma_lut != pending.gamma_lut {
+                self.destroy_gamma_lut(current.gamma_lut);
+            }
+            self.gamma_lut_changed.store(false, Ordering::SeqCst);
+            if current.connector_state.hdr_metadata_blob != pending.connector_state.hdr_metadata_blob {
+                self.destroy_hdr_metadata(current.connector_state.hdr_metadata_blob);
+            }
             *current = pending.clone();
             for plane in planes.iter() {
                 if plane.config.is_some() {
@@ -857,6 +1358,25 @@ impl AtomicDrmSurface {
         &self,
         planes: impl IntoIterator<Item = PlaneState<'a>>,
         event: bool,
+    ) -> Result<(), Error> {
+        self.page_flip_internal(planes, event, false)
+    }
+
+    #[instrument(level = "trace", parent = &self.span, skip(self, planes))]
+    #[profiling::function]
+    pub fn page_flip_async<'a>(
+        &self,
+        planes: impl IntoIterator<Item = PlaneState<'a>>,
+        event: bool,
+    ) -> Result<(), Error> {
+        self.page_flip_internal(planes, event, true)
+    }
+
+    fn page_flip_internal<'a>(
+        &self,
+        planes: impl IntoIterator<Item = PlaneState<'a>>,
+        event: bool,
+        async_flip: bool,
     ) -> Result<(), Error> {
         if !self.active.load(Ordering::SeqCst) {
             return Err(Error::DeviceInactive);
@@ -867,11 +1387,16 @@ impl AtomicDrmSurface {
 
         // page flips work just like commits with fewer parameters..
         let prop_mapping = self.prop_mapping.read().unwrap();
+        let current = self.state.read().unwrap();
+        // async commits may not change the gamma lut, it is left for the next page flip
+        let gamma_changed = !async_flip && self.gamma_lut_changed.load(Ordering::SeqCst);
         let req = AtomicRequest::build_request(
             &prop_mapping,
             self.crtc,
             None,
-            self.state.read().unwrap().vrr,
+            current.vrr,
+            gamma_changed.then_some(current.gamma_lut),
+            &current.connector_state,
             [],
             [],
             &*planes,
@@ -880,26 +1405,28 @@ impl AtomicDrmSurface {
         // .. and without `AtomicCommitFlags::AllowModeset`.
         // If we would set anything here, that would require a modeset, this would fail,
         // indicating a problem in our assumptions.
-        trace!(?planes, "Queueing page flip: {:?}", req);
-        let res = self
-            .fd
-            .atomic_commit(
-                if event {
-                    AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::NONBLOCK
-                } else {
-                    AtomicCommitFlags::NONBLOCK
-                },
-                req.build()?,
-            )
-            .map_err(|source| {
-                Error::Access(AccessError {
-                    errmsg: "Page flip commit failed",
-                    dev: self.fd.dev_path(),
-                    source,
-                })
-            });
+        trace!(?planes, async_flip, "Queueing page flip: {:?}", req);
+        let mut flags = AtomicCommitFlags::NONBLOCK;
+        if event {
+            flags |= AtomicCommitFlags::PAGE_FLIP_EVENT;
+        }
+        // The kernel rejects async commits changing anything besides the framebuffer,
+        // properties set to their current value are fine.
+        if async_flip {
+            flags |= AtomicCommitFlags::PAGE_FLIP_ASYNC;
+        }
+        let res = self.fd.atomic_commit(flags, req.build()?).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Page flip commit failed",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        });
 
         if res.is_ok() {
+            if gamma_changed {
+                self.gamma_lut_changed.store(false, Ordering::SeqCst);
+            }
             for plane in planes.iter() {
                 if plane.config.is_some() {
                     used_planes.insert(plane.handle);
@@ -1080,7 +1607,10 @@ impl From<Transform> for DrmRotation {
 #[cfg(test)]
 mod test {
     use crate::{
-        backend::drm::surface::atomic::to_fixed,
+        backend::{
+            drm::surface::{atomic::to_fixed, HdrMetadata},
+            renderer::color::ColorDescription,
+        },
         utils::{Physical, Rectangle},
     };
 
@@ -1106,6 +1636,25 @@ mod test {
         let fixed = to_fixed(geometry.size.w) as u64;
         assert_eq!(125835674, fixed);
     }
+
+    #[test]
+    fn test_hdr_metadata_encoding() {
+        let metadata = HdrMetadata::from_color_description(&ColorDescription::bt2100_pq());
+        let raw = metadata.to_raw();
+        // SAFETY: type 1 is the only variant of the union
+        let infoframe = unsafe { raw.__bindgen_anon_1.hdmi_metadata_type1 };
+
+        assert_eq!(raw.metadata_type, 0);
+        assert_eq!(infoframe.eotf, 2);
+        assert_eq!(
+            (infoframe.display_primaries[0].x, infoframe.display_primaries[0].y),
+            (35400, 14600)
+        );
+        assert_eq!((infoframe.white_point.x, infoframe.white_point.y), (15635, 16450));
+        assert_eq!(infoframe.max_display_mastering_luminance, 10000);
+        assert_eq!(infoframe.min_display_mastering_luminance, 50);
+        assert_eq!(infoframe.max_cll, 0);
+    }
 }
 
 #[cfg(debug_assertions)]
@@ -1145,9 +1694,42 @@ impl<'a> AtomicRequest<'a> {
         }
     }
 
-    fn set_connector(&mut self, conn: connector::Handle, crtc: crtc::Handle) -> Result<(), Error> {
+    fn set_connector(
+        &mut self,
+        conn: connector::Handle,
+        crtc: crtc::Handle,
+        state: &ConnectorState,
+    ) -> Result<(), Error> {
         let connector_props = self.connector_props.entry(conn).or_default();
         connector_props.insert("CRTC_ID", property::Value::CRTC(Some(crtc)));
+
+        if self.mapping.conn_prop_handle(conn, "HDR_OUTPUT_METADATA").is_ok() {
+            connector_props.insert(
+                "HDR_OUTPUT_METADATA",
+                property::Value::Blob(state.hdr_metadata_blob),
+            );
+        } else if state.hdr_metadata_blob != 0 {
+            return Err(Error::UnknownProperty {
+                handle: conn.into(),
+                name: "HDR_OUTPUT_METADATA",
+            });
+        }
+        if let Some((colorspace, value)) = state.colorspace {
+            if self.mapping.conn_prop_handle(conn, "Colorspace").is_ok() {
+                connector_props.insert("Colorspace", property::Value::Unknown(value));
+            } else if colorspace != Colorspace::Default {
+                return Err(Error::UnknownProperty {
+                    handle: conn.into(),
+                    name: "Colorspace",
+                });
+            }
+        }
+        if let Some(bpc) = state.max_bpc {
+            if self.mapping.conn_prop_handle(conn, "max bpc").is_ok() {
+                connector_props.insert("max bpc", property::Value::UnsignedRange(bpc));
+            }
+        }
+
         Ok(())
     }
 
@@ -1162,6 +1744,7 @@ impl<'a> AtomicRequest<'a> {
         crtc: crtc::Handle,
         mode: Option<property::Value<'static>>,
         vrr: bool,
+        gamma_lut: Option<u64>,
     ) -> Result<(), Error> {
         let crtc_props = self.crtc_props.entry(crtc).or_default();
 
@@ -1177,6 +1760,16 @@ impl<'a> AtomicRequest<'a> {
                 name: "VRR_ENABLED",
             });
         }
+        if let Some(gamma_lut) = gamma_lut {
+            if self.mapping.crtc_prop_handle(crtc, "GAMMA_LUT").is_ok() {
+                crtc_props.insert("GAMMA_LUT", property::Value::Blob(gamma_lut));
+            } else if gamma_lut != 0 {
+                return Err(Error::UnknownProperty {
+                    handle: crtc.into(),
+                    name: "GAMMA_LUT",
+                });
+            }
+        }
 
         Ok(())
     }
@@ -1341,12 +1934,45 @@ impl<'a> AtomicRequest<'a> {
         }
     }
 
-    fn set_connector(&mut self, conn: connector::Handle, crtc: crtc::Handle) -> Result<(), Error> {
+    fn set_connector(
+        &mut self,
+        conn: connector::Handle,
+        crtc: crtc::Handle,
+        state: &ConnectorState,
+    ) -> Result<(), Error> {
         self.request.add_property(
             conn,
             self.mapping.conn_prop_handle(conn, "CRTC_ID")?,
             property::Value::CRTC(Some(crtc)),
         );
+
+        if let Ok(prop) = self.mapping.conn_prop_handle(conn, "HDR_OUTPUT_METADATA") {
+            self.request
+                .add_property(conn, prop, property::Value::Blob(state.hdr_metadata_blob));
+        } else if state.hdr_metadata_blob != 0 {
+            return Err(Error::UnknownProperty {
+                handle: conn.into(),
+                name: "HDR_OUTPUT_METADATA",
+            });
+        }
+        if let Some((colorspace, value)) = state.colorspace {
+            if let Ok(prop) = self.mapping.conn_prop_handle(conn, "Colorspace") {
+                self.request
+                    .add_property(conn, prop, property::Value::Unknown(value));
+            } else if colorspace != Colorspace::Default {
+                return Err(Error::UnknownProperty {
+                    handle: conn.into(),
+                    name: "Colorspace",
+                });
+            }
+        }
+        if let Some(bpc) = state.max_bpc {
+            if let Ok(prop) = self.mapping.conn_prop_handle(conn, "max bpc") {
+                self.request
+                    .add_property(conn, prop, property::Value::UnsignedRange(bpc));
+            }
+        }
+
         Ok(())
     }
 
@@ -1364,6 +1990,7 @@ impl<'a> AtomicRequest<'a> {
         crtc: crtc::Handle,
         mode: Option<property::Value<'static>>,
         vrr: bool,
+        gamma_lut: Option<u64>,
     ) -> Result<(), Error> {
         if let Some(blob) = mode {
             self.request
@@ -1386,6 +2013,18 @@ impl<'a> AtomicRequest<'a> {
             });
         }
 
+        if let Some(gamma_lut) = gamma_lut {
+            if let Ok(gamma_prop) = self.mapping.crtc_prop_handle(crtc, "GAMMA_LUT") {
+                self.request
+                    .add_property(crtc, gamma_prop, property::Value::Blob(gamma_lut));
+            } else if gamma_lut != 0 {
+                return Err(Error::UnknownProperty {
+                    handle: crtc.into(),
+                    name: "GAMMA_LUT",
+                });
+            }
+        }
+
         Ok(())
     }
 
@@ -1612,11 +2251,14 @@ impl<'a> AtomicRequest<'a> {
 }
 
 impl<'a> AtomicRequest<'a> {
+    #[allow(clippy::too_many_arguments)]
     fn build_request(
         mapping: &'a PropMapping,
         crtc: crtc::Handle,
         blob: Option<property::Value<'static>>,
         vrr: bool,
+        gamma_lut: Option<u64>,
+        connector_state: &ConnectorState,
         connectors: impl IntoIterator<Item = &'a connector::Handle>,
         removed_connectors: impl IntoIterator<Item = &'a connector::Handle>,
         planes: impl IntoIterator<Item = &'a PlaneState<'a>>,
@@ -1627,8 +2269,9 @@ impl<'a> AtomicRequest<'a> {
         // for different drm objects (crtc, plane, connector, ...).
 
         // for every connector that is new, we need to set our crtc_id
+        // and the connector properties (hdr metadata, colorspace, max bpc)
         for conn in connectors {
-            req.set_connector(*conn, crtc)?;
+            req.set_connector(*conn, crtc, connector_state)?;
         }
 
         // for every connector that got removed, we need to set no crtc_id.
@@ -1639,8 +2282,8 @@ impl<'a> AtomicRequest<'a> {
             req.reset_connector(*conn)?;
         }
 
-        // Set the crtc properties (active, mode_id, vrr_enabled).
-        req.set_crtc(crtc, blob, vrr)?;
+        // Set the crtc properties (active, mode_id, vrr_enabled and gamma_lut, if it changed).
+        req.set_crtc(crtc, blob, vrr, gamma_lut)?;
 
         for plane_state in planes.into_iter() {
             req.set_plane(crtc, plane_state)?;
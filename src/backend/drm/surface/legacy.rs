@@ -87,6 +87,8 @@ pub struct LegacyDrmSurface {
     state: RwLock<State>,
     pending: RwLock<State>,
     dpms: Mutex<bool>,
+    // gamma ramp set before we changed it for the first time, restored by `reset_gamma`
+    original_gamma: Mutex<Option<[Vec<u16>; 3]>>,
     pub(super) span: tracing::Span,
 }
 
@@ -116,6 +118,7 @@ impl LegacyDrmSurface {
             state: RwLock::new(state),
             pending: RwLock::new(pending),
             dpms: Mutex::new(true),
+            original_gamma: Mutex::new(None),
             span,
         };
 
@@ -334,7 +337,22 @@ impl LegacyDrmSurface {
     #[instrument(level = "trace", parent = &self.span, skip(self))]
     #[profiling::function]
     pub fn page_flip(&self, framebuffer: framebuffer::Handle, event: bool) -> Result<(), Error> {
-        trace!("Queueing Page flip");
+        self.page_flip_internal(framebuffer, event, false)
+    }
+
+    #[instrument(level = "trace", parent = &self.span, skip(self))]
+    #[profiling::function]
+    pub fn page_flip_async(&self, framebuffer: framebuffer::Handle, event: bool) -> Result<(), Error> {
+        self.page_flip_internal(framebuffer, event, true)
+    }
+
+    fn page_flip_internal(
+        &self,
+        framebuffer: framebuffer::Handle,
+        event: bool,
+        async_flip: bool,
+    ) -> Result<(), Error> {
+        trace!(async_flip, "Queueing Page flip");
 
         if !self.active.load(Ordering::SeqCst) {
             return Err(Error::DeviceInactive);
@@ -351,10 +369,11 @@ impl LegacyDrmSurface {
             &*self.fd,
             self.crtc,
             framebuffer,
-            if event {
-                PageFlipFlags::EVENT
-            } else {
-                PageFlipFlags::empty()
+            match (event, async_flip) {
+                (true, true) => PageFlipFlags::EVENT | PageFlipFlags::ASYNC,
+                (true, false) => PageFlipFlags::EVENT,
+                (false, true) => PageFlipFlags::ASYNC,
+                (false, false) => PageFlipFlags::empty(),
             },
             None,
         )
@@ -453,6 +472,73 @@ impl LegacyDrmSurface {
         }
     }
 
+    pub fn gamma_size(&self) -> Result<u32, Error> {
+        let info = self.fd.get_crtc(self.crtc).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Error loading crtc info",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })?;
+        Ok(info.gamma_length())
+    }
+
+    pub fn set_gamma(&self, red: &[u16], green: &[u16], blue: &[u16]) -> Result<(), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let size = self.gamma_size()? as usize;
+        if red.len() != size || green.len() != size || blue.len() != size {
+            return Err(Error::InvalidGammaSize {
+                crtc: self.crtc,
+                expected: size,
+            });
+        }
+
+        let mut original = self.original_gamma.lock().unwrap();
+        if original.is_none() {
+            let mut ramp = [vec![0u16; size], vec![0u16; size], vec![0u16; size]];
+            let [r, g, b] = &mut ramp;
+            self.fd.get_gamma(self.crtc, r, g, b).map_err(|source| {
+                Error::Access(AccessError {
+                    errmsg: "Failed to get gamma ramp",
+                    dev: self.fd.dev_path(),
+                    source,
+                })
+            })?;
+            *original = Some(ramp);
+        }
+
+        self.fd.set_gamma(self.crtc, red, green, blue).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Failed to set gamma ramp",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })
+    }
+
+    pub fn reset_gamma(&self) -> Result<(), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let mut original = self.original_gamma.lock().unwrap();
+        let Some([red, green, blue]) = original.as_ref() else {
+            return Ok(());
+        };
+        self.fd.set_gamma(self.crtc, red, green, blue).map_err(|source| {
+            Error::Access(AccessError {
+                errmsg: "Failed to set gamma ramp",
+                dev: self.fd.dev_path(),
+                source,
+            })
+        })?;
+        *original = None;
+        Ok(())
+    }
+
     pub(crate) fn reset_state<B: DevPath + ControlDevice + 'static>(
         &self,
         fd: Option<&B>,
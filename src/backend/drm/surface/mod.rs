@@ -4,7 +4,7 @@ use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use drm::control::{connector, crtc, framebuffer, plane, Device as ControlDevice, Mode};
-use drm::Device as BasicDevice;
+use drm::{Device as BasicDevice, DriverCapability};
 
 use libc::dev_t;
 
@@ -16,6 +16,7 @@ use super::{
     device::PlaneClaimStorage, error::Error, plane_type, DrmDeviceFd, PlaneClaim, PlaneInfo, PlaneType,
     Planes,
 };
+use crate::backend::renderer::color::{Chromaticities, ColorDescription, TransferFunction};
 use crate::utils::DevPath;
 use crate::utils::{Buffer, Physical, Point, Rectangle, Transform};
 use atomic::AtomicDrmSurface;
@@ -168,6 +169,176 @@ pub enum VrrSupport {
     Supported,
 }
 
+/// Colorimetry of the signal sent to a connector
+///
+/// Set through the `Colorspace` connector property, see [`DrmSurface::set_colorspace`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Colorspace {
+    /// Colorimetry chosen by the driver, usually BT.709 / sRGB
+    #[default]
+    Default,
+    /// ITU-R BT.709 YCbCr
+    Bt709Ycc,
+    /// xvYCC 601
+    Xvycc601,
+    /// xvYCC 709
+    Xvycc709,
+    /// sYCC 601
+    Sycc601,
+    /// opYCC 601
+    OpYcc601,
+    /// opRGB (Adobe RGB)
+    OpRgb,
+    /// ITU-R BT.2020 constant luminance YCbCr
+    Bt2020Cycc,
+    /// ITU-R BT.2020 RGB, commonly used for HDR10 output
+    Bt2020Rgb,
+    /// ITU-R BT.2020 YCbCr
+    Bt2020Ycc,
+    /// DCI-P3 RGB using the D65 white point
+    DciP3RgbD65,
+    /// DCI-P3 RGB using the theater white point
+    DciP3RgbTheater,
+}
+
+impl Colorspace {
+    pub(super) fn property_name(&self) -> &'static str {
+        match self {
+            Colorspace::Default => "Default",
+            Colorspace::Bt709Ycc => "BT709_YCC",
+            Colorspace::Xvycc601 => "XVYCC_601",
+            Colorspace::Xvycc709 => "XVYCC_709",
+            Colorspace::Sycc601 => "SYCC_601",
+            Colorspace::OpYcc601 => "opYCC_601",
+            Colorspace::OpRgb => "opRGB",
+            Colorspace::Bt2020Cycc => "BT2020_CYCC",
+            Colorspace::Bt2020Rgb => "BT2020_RGB",
+            Colorspace::Bt2020Ycc => "BT2020_YCC",
+            Colorspace::DciP3RgbD65 => "DCI-P3_RGB_D65",
+            Colorspace::DciP3RgbTheater => "DCI-P3_RGB_Theater",
+        }
+    }
+
+    pub(super) fn from_property_name(name: &str) -> Option<Self> {
+        [
+            Colorspace::Default,
+            Colorspace::Bt709Ycc,
+            Colorspace::Xvycc601,
+            Colorspace::Xvycc709,
+            Colorspace::Sycc601,
+            Colorspace::OpYcc601,
+            Colorspace::OpRgb,
+            Colorspace::Bt2020Cycc,
+            Colorspace::Bt2020Rgb,
+            Colorspace::Bt2020Ycc,
+            Colorspace::DciP3RgbD65,
+            Colorspace::DciP3RgbTheater,
+        ]
+        .into_iter()
+        .find(|colorspace| colorspace.property_name() == name)
+    }
+}
+
+/// Electro-optical transfer function signaled to a display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HdrEotf {
+    /// Traditional gamma, SDR luminance range
+    TraditionalSdr,
+    /// Traditional gamma, HDR luminance range
+    TraditionalHdr,
+    /// SMPTE ST 2084 (perceptual quantizer)
+    SmpteSt2084,
+    /// Hybrid log-gamma (ARIB STD-B67)
+    Hlg,
+}
+
+/// Static HDR metadata sent to a display
+///
+/// This corresponds to the static metadata descriptor type 1 of CTA-861-G and is set
+/// through the `HDR_OUTPUT_METADATA` connector property, see [`DrmSurface::set_hdr_metadata`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HdrMetadata {
+    /// Transfer function of the signal
+    pub eotf: HdrEotf,
+    /// Primaries and white point of the mastering display
+    pub mastering_display_primaries: Chromaticities,
+    /// Minimum and maximum luminance of the mastering display in cd/m²
+    pub mastering_display_luminance: (f64, f64),
+    /// Maximum content light level in cd/m², if known
+    pub max_cll: Option<f64>,
+    /// Maximum frame-average light level in cd/m², if known
+    pub max_fall: Option<f64>,
+}
+
+impl HdrMetadata {
+    /// Metadata for a signal using the given [`ColorDescription`]
+    ///
+    /// The mastering display defaults to the primaries and luminances of the description.
+    pub fn from_color_description(description: &ColorDescription) -> Self {
+        let eotf = match description.transfer_function {
+            TransferFunction::Pq => HdrEotf::SmpteSt2084,
+            TransferFunction::Hlg => HdrEotf::Hlg,
+            _ => HdrEotf::TraditionalSdr,
+        };
+
+        HdrMetadata {
+            eotf,
+            mastering_display_primaries: description
+                .target_primaries
+                .unwrap_or_else(|| description.primaries.chromaticities()),
+            mastering_display_luminance: description
+                .target_luminance
+                .unwrap_or((description.luminances.min, description.luminances.max)),
+            max_cll: description.max_cll,
+            max_fall: description.max_fall,
+        }
+    }
+
+    pub(super) fn to_raw(self) -> drm_ffi::hdr_output_metadata {
+        // chromaticities are encoded in units of 0.00002
+        let coord = |v: f64| (v / 0.00002).round().clamp(0., 50000.) as u16;
+        let point = |(x, y): (f64, f64)| drm_ffi::hdr_metadata_infoframe__bindgen_ty_1 {
+            x: coord(x),
+            y: coord(y),
+        };
+        let luminance = |v: f64| v.round().clamp(0., u16::MAX as f64) as u16;
+
+        let primaries = self.mastering_display_primaries;
+        let infoframe = drm_ffi::hdr_metadata_infoframe {
+            eotf: match self.eotf {
+                HdrEotf::TraditionalSdr => 0,
+                HdrEotf::TraditionalHdr => 1,
+                HdrEotf::SmpteSt2084 => 2,
+                HdrEotf::Hlg => 3,
+            },
+            // static metadata type 1
+            metadata_type: 0,
+            display_primaries: [
+                point(primaries.red),
+                point(primaries.green),
+                point(primaries.blue),
+            ],
+            white_point: drm_ffi::hdr_metadata_infoframe__bindgen_ty_2 {
+                x: coord(primaries.white.0),
+                y: coord(primaries.white.1),
+            },
+            max_display_mastering_luminance: luminance(self.mastering_display_luminance.1),
+            // the minimum luminance is encoded in units of 0.0001 cd/m²
+            min_display_mastering_luminance: luminance(self.mastering_display_luminance.0 * 10000.),
+            // zero signals an unknown value
+            max_cll: self.max_cll.map(luminance).unwrap_or(0),
+            max_fall: self.max_fall.map(luminance).unwrap_or(0),
+        };
+
+        drm_ffi::hdr_output_metadata {
+            metadata_type: 0,
+            __bindgen_anon_1: drm_ffi::hdr_output_metadata__bindgen_ty_1 {
+                hdmi_metadata_type1: infoframe,
+            },
+        }
+    }
+}
+
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum DrmSurfaceInternal {
@@ -341,6 +512,148 @@ impl DrmSurface {
         }
     }
 
+    /// Returns the number of entries per color channel of the gamma ramp of the crtc
+    ///
+    /// On atomic devices this is the size of the `GAMMA_LUT` property and errors
+    /// if the property is not supported.
+    pub fn gamma_size(&self) -> Result<u32, Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.gamma_size(),
+            DrmSurfaceInternal::Legacy(surf) => surf.gamma_size(),
+        }
+    }
+
+    /// Sets a new gamma ramp for the crtc
+    ///
+    /// Each channel needs to contain exactly [`DrmSurface::gamma_size`] entries.
+    ///
+    /// On atomic devices the `GAMMA_LUT` property is set and applied with the next
+    /// [`page_flip`](DrmSurface::page_flip), or with the next [`commit`](DrmSurface::commit)
+    /// if [`DrmSurface::commit_pending`] returns `true`.
+    /// Async page flips may not change the gamma lut and leave it for the next regular one.
+    /// On legacy devices the gamma ramp is set immediately.
+    pub fn set_gamma(&self, red: &[u16], green: &[u16], blue: &[u16]) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.set_gamma(red, green, blue),
+            DrmSurfaceInternal::Legacy(surf) => surf.set_gamma(red, green, blue),
+        }
+    }
+
+    /// Restores the gamma ramp the crtc had before it was changed by [`DrmSurface::set_gamma`]
+    ///
+    /// Follows the same rules as [`DrmSurface::set_gamma`] regarding when the change is applied.
+    pub fn reset_gamma(&self) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.reset_gamma(),
+            DrmSurfaceInternal::Legacy(surf) => surf.reset_gamma(),
+        }
+    }
+
+    /// Returns the [`Colorspace`]s supported by the given connector.
+    ///
+    /// Returns an empty list, if the connector does not support the `Colorspace` property
+    /// or the underlying implementation is using the legacy DRM api.
+    pub fn supported_colorspaces(&self, conn: connector::Handle) -> Result<Vec<Colorspace>, Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.supported_colorspaces(conn),
+            DrmSurfaceInternal::Legacy(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the [`Colorspace`] signaled to the connectors after the next commit.
+    pub fn colorspace(&self) -> Colorspace {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.colorspace(),
+            DrmSurfaceInternal::Legacy(_) => Colorspace::Default,
+        }
+    }
+
+    /// Tries to set the [`Colorspace`] signaled to the connectors.
+    ///
+    /// Doing so will cause [`DrmSurface::commit_pending`] to return `true` and might require a modeset.
+    /// Fails if any of the pending connectors does not support the colorspace,
+    /// see [`DrmSurface::supported_colorspaces`].
+    pub fn set_colorspace(&self, colorspace: Colorspace) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.set_colorspace(colorspace),
+            DrmSurfaceInternal::Legacy(_) if colorspace == Colorspace::Default => Ok(()),
+            DrmSurfaceInternal::Legacy(_) => Err(Error::UnknownProperty {
+                handle: self.crtc.into(),
+                name: "Colorspace",
+            }),
+        }
+    }
+
+    /// Returns if the given connector supports sending [`HdrMetadata`].
+    ///
+    /// Note: This will always return `false` if the underlying implementation is using the
+    /// legacy DRM api. The connected display might still not support HDR, which can be checked
+    /// by parsing its EDID, e.g. with `smithay-drm-extras`.
+    pub fn hdr_metadata_supported(&self, conn: connector::Handle) -> Result<bool, Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.hdr_metadata_supported(conn),
+            DrmSurfaceInternal::Legacy(_) => Ok(false),
+        }
+    }
+
+    /// Returns the [`HdrMetadata`] sent to the connectors after the next commit,
+    /// if set through [`DrmSurface::set_hdr_metadata`].
+    pub fn hdr_metadata(&self) -> Option<HdrMetadata> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.hdr_metadata(),
+            DrmSurfaceInternal::Legacy(_) => None,
+        }
+    }
+
+    /// Tries to set the [`HdrMetadata`] sent to the connectors via the `HDR_OUTPUT_METADATA` property.
+    ///
+    /// `None` disables sending HDR metadata, which usually switches the display back to SDR.
+    /// Doing so will cause [`DrmSurface::commit_pending`] to return `true` and might require a modeset.
+    pub fn set_hdr_metadata(&self, metadata: Option<HdrMetadata>) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.set_hdr_metadata(metadata),
+            DrmSurfaceInternal::Legacy(_) if metadata.is_none() => Ok(()),
+            DrmSurfaceInternal::Legacy(_) => Err(Error::UnknownProperty {
+                handle: self.crtc.into(),
+                name: "HDR_OUTPUT_METADATA",
+            }),
+        }
+    }
+
+    /// Returns the range of values supported by the `max bpc` property of the given connector.
+    pub fn max_bpc_range(&self, conn: connector::Handle) -> Result<(u32, u32), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.max_bpc_range(conn),
+            DrmSurfaceInternal::Legacy(_) => Err(Error::UnknownProperty {
+                handle: conn.into(),
+                name: "max bpc",
+            }),
+        }
+    }
+
+    /// Returns the maximum bits per color channel used for the signal after the next commit, if known.
+    pub fn max_bpc(&self) -> Option<u32> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.max_bpc(),
+            DrmSurfaceInternal::Legacy(_) => None,
+        }
+    }
+
+    /// Tries to set the maximum bits per color channel used for the signal.
+    ///
+    /// The driver may choose a lower value, e.g. due to bandwidth limitations.
+    /// To drive an HDR display at least `10` should be used together with a 10-bit framebuffer format.
+    /// Doing so will cause [`DrmSurface::commit_pending`] to return `true` and might require a modeset.
+    pub fn set_max_bpc(&self, bpc: u32) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.set_max_bpc(bpc),
+            DrmSurfaceInternal::Legacy(_) => Err(Error::UnknownProperty {
+                handle: self.crtc.into(),
+                name: "max bpc",
+            }),
+        }
+    }
+
     /// Disables the given plane.
     ///
     /// Errors if the plane is not supported by this crtc or if the underlying
@@ -358,6 +671,9 @@ impl DrmSurface {
     /// - [`add_connector`](DrmSurface::add_connector)
     /// - [`remove_connector`](DrmSurface::remove_connector)
     /// - [`use_mode`](DrmSurface::use_mode)
+    /// - [`set_colorspace`](DrmSurface::set_colorspace)
+    /// - [`set_hdr_metadata`](DrmSurface::set_hdr_metadata)
+    /// - [`set_max_bpc`](DrmSurface::set_max_bpc)
     pub fn commit_pending(&self) -> bool {
         match &*self.internal {
             DrmSurfaceInternal::Atomic(surf) => surf.commit_pending(),
@@ -439,6 +755,40 @@ impl DrmSurface {
         }
     }
 
+    /// Returns `true` if the device supports asynchronous page flips for this surface.
+    ///
+    /// See [`DrmSurface::page_flip_async`].
+    pub fn async_page_flip_supported(&self) -> bool {
+        let cap = match &*self.internal {
+            DrmSurfaceInternal::Atomic(_) => DriverCapability::AtomicASyncPageFlip,
+            DrmSurfaceInternal::Legacy(_) => DriverCapability::ASyncPageFlip,
+        };
+        self.device_fd().get_driver_capability(cap).unwrap_or(0) == 1
+    }
+
+    /// Page-flip the underlying [`crtc`](drm::control::crtc) to a new given set of [`framebuffer`]s
+    /// without waiting for the next vblank.
+    ///
+    /// This allows tearing, but reduces latency. Drivers usually only allow to change the framebuffer
+    /// of the primary plane this way, any other change compared to the current state
+    /// will make this call fail, in which case a regular [`DrmSurface::page_flip`] should be used instead.
+    ///
+    /// Check [`DrmSurface::async_page_flip_supported`] before calling this function.
+    #[profiling::function]
+    pub fn page_flip_async<'a>(
+        &self,
+        planes: impl IntoIterator<Item = PlaneState<'a>>,
+        event: bool,
+    ) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.page_flip_async(planes, event),
+            DrmSurfaceInternal::Legacy(surf) => {
+                let fb = ensure_legacy_planes(self, planes)?;
+                surf.page_flip_async(fb, event)
+            }
+        }
+    }
+
     /// Returns a set of available planes for this surface
     pub fn planes(&self) -> &Planes {
         &self.planes
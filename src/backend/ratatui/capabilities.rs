@@ -0,0 +1,340 @@
+//! Detection of the features supported by the host terminal
+//!
+//! Capabilities are derived from the environment (`NO_COLOR`, `COLORTERM`, `TERM`) and refined
+//! by querying the terminal for its primary and secondary device attributes (DA1/DA2),
+//! its termcap entries (XTGETTCAP) and support for the kitty graphics and keyboard protocols.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use rustix::event::{PollFd, PollFlags, Timespec};
+use tracing::{debug, warn};
+
+use super::{ColorMode, GraphicsProtocol};
+
+/// How long to wait for the terminal to answer queries
+const QUERY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Kitty graphics query for a 1x1 rgb image, answered with `OK` by supporting terminals
+const KITTY_QUERY: &[u8] = b"\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\";
+const KITTY_QUERY_OK: &[u8] = b"\x1b_Gi=31;OK";
+/// Termcap entries queried via XTGETTCAP: `RGB`, `Tc` and `colors`, hex encoded
+const XTGETTCAP_QUERY: &[u8] = b"\x1bP+q524742;5463;636f6c6f7273\x1b\\";
+const XTGETTCAP_REPLY: &[u8] = b"\x1bP1+r";
+/// Progressive enhancement flags of the kitty keyboard protocol, answered with `CSI ? flags u`
+const KEYBOARD_QUERY: &[u8] = b"\x1b[?u";
+/// Secondary device attributes
+const DA2_QUERY: &[u8] = b"\x1b[>c";
+/// Primary device attributes, answered by every terminal, so it is sent last to end the detection
+const DA1_QUERY: &[u8] = b"\x1b[c";
+/// Device attribute advertised by terminals supporting sixel graphics
+const DA1_SIXEL: &str = "4";
+
+/// Terminal types reported via DA2 by terminal multiplexers, `T` for tmux and `S` for screen
+const DA2_TMUX: u32 = 84;
+const DA2_SCREEN: u32 = 83;
+
+/// Features supported by the host terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalCapabilities {
+    /// Colour depth the terminal is able to display
+    pub color_mode: ColorMode,
+    /// Graphics protocol supported by the terminal, the kitty protocol is preferred over sixel
+    pub graphics_protocol: Option<GraphicsProtocol>,
+    /// Terminal type and firmware version as reported via DA2
+    pub terminal_id: Option<(u32, u32)>,
+    /// Whether the terminal reports key releases and modifier keys via the kitty keyboard protocol
+    pub keyboard_enhancement: bool,
+}
+
+impl Default for TerminalCapabilities {
+    fn default() -> Self {
+        TerminalCapabilities {
+            color_mode: ColorMode::Indexed256,
+            graphics_protocol: None,
+            terminal_id: None,
+            keyboard_enhancement: false,
+        }
+    }
+}
+
+impl TerminalCapabilities {
+    /// Probe the terminal attached to stdin and stdout
+    ///
+    /// The terminal is expected to be in raw mode and no other reader of stdin may
+    /// be active while the queries are running. If the terminal does not answer,
+    /// only the environment is taken into account.
+    pub fn detect() -> TerminalCapabilities {
+        let env = Environment::from_env();
+
+        let mut query = Vec::new();
+        query.extend_from_slice(KITTY_QUERY);
+        query.extend_from_slice(XTGETTCAP_QUERY);
+        query.extend_from_slice(KEYBOARD_QUERY);
+        query.extend_from_slice(DA2_QUERY);
+        query.extend_from_slice(DA1_QUERY);
+        let reply = query_terminal(&query).unwrap_or_else(|err| {
+            warn!(?err, "Failed to query terminal capabilities");
+            Vec::new()
+        });
+
+        let capabilities = TerminalCapabilities::from_reply(&env, &reply);
+        debug!(?capabilities, "Detected terminal capabilities");
+        capabilities
+    }
+
+    fn from_reply(env: &Environment, reply: &[u8]) -> TerminalCapabilities {
+        let terminal_id = da2_attributes(reply).and_then(|attrs| {
+            let mut attrs = attrs.split(';').map(|attr| attr.parse::<u32>().ok());
+            Some((attrs.next()??, attrs.next().flatten().unwrap_or(0)))
+        });
+        let multiplexer = matches!(terminal_id, Some((DA2_TMUX | DA2_SCREEN, _)));
+
+        let termcap = xtgettcap_entries(reply);
+        let termcap_truecolor = termcap.iter().any(|(name, _)| name == "RGB" || name == "Tc");
+        let termcap_colors = termcap
+            .iter()
+            .find(|(name, _)| name == "colors")
+            .and_then(|(_, value)| value.parse::<u32>().ok());
+
+        let color_mode = if env.no_color {
+            ColorMode::Monochrome
+        } else if termcap_truecolor || (env.truecolor && !multiplexer) {
+            // multiplexers only pass through 24-bit colour, if they advertise it themselves
+            ColorMode::TrueColor
+        } else if let Some(colors) = termcap_colors {
+            ColorMode::from_color_count(colors)
+        } else if let Some(color_mode) = env.color_mode {
+            color_mode
+        } else {
+            ColorMode::Indexed256
+        };
+
+        TerminalCapabilities {
+            color_mode,
+            graphics_protocol: graphics_protocol(reply),
+            terminal_id,
+            keyboard_enhancement: keyboard_flags(reply).is_some_and(|flags| flags != 0),
+        }
+    }
+}
+
+/// Colour related environment variables
+#[derive(Debug, Default)]
+struct Environment {
+    no_color: bool,
+    truecolor: bool,
+    /// Colour depth implied by `TERM`
+    color_mode: Option<ColorMode>,
+}
+
+impl Environment {
+    fn from_env() -> Environment {
+        let var = |name| std::env::var(name).ok();
+        Environment::new(
+            var("NO_COLOR").as_deref(),
+            var("COLORTERM").as_deref(),
+            var("TERM").as_deref(),
+        )
+    }
+
+    fn new(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Environment {
+        let term = term.unwrap_or_default();
+        let color_mode = if term == "dumb" {
+            Some(ColorMode::Monochrome)
+        } else if term == "linux" || term.starts_with("vt") {
+            Some(ColorMode::Indexed16)
+        } else if term.contains("256color") {
+            Some(ColorMode::Indexed256)
+        } else {
+            None
+        };
+
+        Environment {
+            no_color: no_color.is_some_and(|value| !value.is_empty()),
+            truecolor: matches!(colorterm, Some("truecolor" | "24bit")) || term.ends_with("-direct"),
+            color_mode,
+        }
+    }
+}
+
+fn graphics_protocol(reply: &[u8]) -> Option<GraphicsProtocol> {
+    if reply.windows(KITTY_QUERY_OK.len()).any(|w| w == KITTY_QUERY_OK) {
+        return Some(GraphicsProtocol::Kitty);
+    }
+
+    da1_attributes(reply)?
+        .split(';')
+        .any(|attr| attr == DA1_SIXEL)
+        .then_some(GraphicsProtocol::Sixel)
+}
+
+/// Returns the parameters of the first control sequence of the form `CSI <prefix> Ps ; ... <final_byte>`
+fn csi_parameters<'a>(reply: &'a [u8], prefix: &[u8], final_byte: u8) -> Option<&'a str> {
+    let mut rest = reply;
+    while let Some(start) = rest.windows(prefix.len()).position(|w| w == prefix) {
+        rest = &rest[start + prefix.len()..];
+        let Some(len) = rest.iter().position(|c| !(c.is_ascii_digit() || *c == b';')) else {
+            break;
+        };
+        if rest[len] == final_byte {
+            return std::str::from_utf8(&rest[..len]).ok();
+        }
+    }
+    None
+}
+
+fn da1_attributes(reply: &[u8]) -> Option<&str> {
+    csi_parameters(reply, b"\x1b[?", b'c')
+}
+
+fn da2_attributes(reply: &[u8]) -> Option<&str> {
+    csi_parameters(reply, b"\x1b[>", b'c')
+}
+
+fn keyboard_flags(reply: &[u8]) -> Option<u32> {
+    csi_parameters(reply, b"\x1b[?", b'u')?.parse().ok()
+}
+
+/// Returns all termcap entries successfully queried via XTGETTCAP (`DCS 1 + r name=value ST`)
+fn xtgettcap_entries(reply: &[u8]) -> Vec<(String, String)> {
+    let decode = |hex: &str| -> Option<String> {
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        String::from_utf8(bytes).ok()
+    };
+
+    let mut entries = Vec::new();
+    let mut rest = reply;
+    while let Some(start) = rest
+        .windows(XTGETTCAP_REPLY.len())
+        .position(|w| w == XTGETTCAP_REPLY)
+    {
+        rest = &rest[start + XTGETTCAP_REPLY.len()..];
+        let Some(end) = rest.iter().position(|c| *c == 0x1b) else {
+            break;
+        };
+        let Ok(payload) = std::str::from_utf8(&rest[..end]) else {
+            continue;
+        };
+        // boolean capabilities are reported without a value
+        for entry in payload.split(';') {
+            let (name, value) = entry.split_once('=').unwrap_or((entry, ""));
+            if let (Some(name), Some(value)) = (decode(name), decode(value)) {
+                entries.push((name, value));
+            }
+        }
+        rest = &rest[end..];
+    }
+    entries
+}
+
+/// Write `query` to stdout and collect the reply of the terminal from stdin
+///
+/// `query` has to end with a primary device attributes request, its reply marks the end
+/// of the answer. Reading stops early, if the terminal does not reply in time.
+fn query_terminal(query: &[u8]) -> io::Result<Vec<u8>> {
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(query)?;
+    stdout.flush()?;
+
+    let stdin = io::stdin();
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 256];
+    while da1_attributes(&reply).is_none() {
+        let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+            debug!("Terminal did not answer in time");
+            break;
+        };
+        let timeout = Timespec::try_from(remaining).map_err(io::Error::other)?;
+        let mut fds = [PollFd::new(&stdin, PollFlags::IN)];
+        if rustix::event::poll(&mut fds, Some(&timeout))? == 0 {
+            continue;
+        }
+        let n = rustix::io::read(&stdin, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buf[..n]);
+    }
+
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graphics_from_reply() {
+        assert_eq!(
+            graphics_protocol(b"\x1b_Gi=31;OK\x1b\\\x1b[?62;22c"),
+            Some(GraphicsProtocol::Kitty)
+        );
+        assert_eq!(
+            graphics_protocol(b"\x1b[?62;4;22c"),
+            Some(GraphicsProtocol::Sixel)
+        );
+        assert_eq!(graphics_protocol(b"\x1b[?62;22;42c"), None);
+        assert_eq!(graphics_protocol(b""), None);
+    }
+
+    #[test]
+    fn color_mode_from_env() {
+        let no_reply = |env| TerminalCapabilities::from_reply(&env, b"").color_mode;
+        assert_eq!(
+            no_reply(Environment::new(None, Some("truecolor"), Some("xterm-256color"))),
+            ColorMode::TrueColor
+        );
+        assert_eq!(
+            no_reply(Environment::new(None, None, Some("xterm-256color"))),
+            ColorMode::Indexed256
+        );
+        assert_eq!(
+            no_reply(Environment::new(None, None, Some("linux"))),
+            ColorMode::Indexed16
+        );
+        assert_eq!(
+            no_reply(Environment::new(Some("1"), Some("truecolor"), None)),
+            ColorMode::Monochrome
+        );
+    }
+
+    #[test]
+    fn color_mode_from_reply() {
+        let env = Environment::new(None, Some("truecolor"), Some("tmux-256color"));
+        // tmux without RGB support
+        let capabilities = TerminalCapabilities::from_reply(&env, b"\x1bP0+r\x1b\\\x1b[>84;0;0c\x1b[?1;2c");
+        assert_eq!(capabilities.color_mode, ColorMode::Indexed256);
+        assert_eq!(capabilities.terminal_id, Some((84, 0)));
+
+        // tmux with RGB support
+        let capabilities =
+            TerminalCapabilities::from_reply(&env, b"\x1bP1+r524742\x1b\\\x1b[>84;0;0c\x1b[?1;2c");
+        assert_eq!(capabilities.color_mode, ColorMode::TrueColor);
+
+        // colors=16
+        let env = Environment::new(None, None, None);
+        let capabilities =
+            TerminalCapabilities::from_reply(&env, b"\x1bP1+r636f6c6f7273=3136\x1b\\\x1b[?1;2c");
+        assert_eq!(capabilities.color_mode, ColorMode::Indexed16);
+    }
+
+    #[test]
+    fn keyboard_from_reply() {
+        let env = Environment::new(None, None, None);
+        let capabilities = TerminalCapabilities::from_reply(&env, b"\x1b[?3u\x1b[?62;4c");
+        assert!(capabilities.keyboard_enhancement);
+        assert_eq!(capabilities.graphics_protocol, Some(GraphicsProtocol::Sixel));
+
+        let capabilities = TerminalCapabilities::from_reply(&env, b"\x1b[?0u\x1b[?62c");
+        assert!(!capabilities.keyboard_enhancement);
+        let capabilities = TerminalCapabilities::from_reply(&env, b"\x1b[?62c");
+        assert!(!capabilities.keyboard_enhancement);
+    }
+}
@@ -0,0 +1,50 @@
+//! Access to the clipboard of the host terminal via OSC 52
+//!
+//! Terminals supporting OSC 52 forward its payload to the clipboard of the system they are
+//! running on, which also works over SSH. Pasted text is received as bracketed paste instead,
+//! see [`RatatuiEvent::Paste`](super::RatatuiEvent::Paste).
+
+use super::graphics::base64;
+
+/// Largest payload forwarded to the terminal, many terminals silently drop bigger sequences
+pub const MAX_CLIPBOARD_SIZE: usize = 1 << 20;
+
+/// Selection of the host terminal to write to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Clipboard {
+    /// The regular clipboard
+    Clipboard,
+    /// The primary selection, pasted with the middle mouse button
+    Primary,
+}
+
+impl Clipboard {
+    fn parameter(self) -> u8 {
+        match self {
+            Clipboard::Clipboard => b'c',
+            Clipboard::Primary => b'p',
+        }
+    }
+}
+
+/// Encode `data` as an OSC 52 sequence setting `clipboard` (`OSC 52 ; Pc ; base64 ST`)
+pub(crate) fn osc52(clipboard: Clipboard, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len().div_ceil(3) * 4 + 10);
+    out.extend_from_slice(b"\x1b]52;");
+    out.push(clipboard.parameter());
+    out.push(b';');
+    out.extend_from_slice(&base64(data));
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_sequence() {
+        assert_eq!(osc52(Clipboard::Clipboard, b"foo"), b"\x1b]52;c;Zm9v\x1b\\");
+        assert_eq!(osc52(Clipboard::Primary, b""), b"\x1b]52;p;\x1b\\");
+    }
+}
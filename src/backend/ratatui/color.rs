@@ -0,0 +1,274 @@
+//! Reduction of cell colours to the palette supported by the terminal
+//!
+//! Cells are rendered using 24-bit colours. Before they are presented, the colours are mapped
+//! to the nearest entry of the xterm 256 colour palette, the 16 standard colours, or replaced by
+//! block characters on monochrome terminals. Optionally a 4x4 ordered dither is applied to
+//! reduce banding, treating the upper and lower half of each cell as separate pixels.
+
+use ratatui::{
+    buffer::{Buffer, Cell},
+    layout::Rect,
+    style::Color,
+};
+
+/// Colour depth used to present cells
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorMode {
+    /// 24-bit colours
+    #[default]
+    TrueColor,
+    /// The xterm 256 colour palette
+    Indexed256,
+    /// The 16 standard terminal colours
+    Indexed16,
+    /// No colours, cells are drawn using block characters
+    Monochrome,
+}
+
+impl ColorMode {
+    /// Colour mode able to display `colors` distinct colours
+    pub fn from_color_count(colors: u32) -> ColorMode {
+        match colors {
+            0x100_0000.. => ColorMode::TrueColor,
+            256.. => ColorMode::Indexed256,
+            8.. => ColorMode::Indexed16,
+            _ => ColorMode::Monochrome,
+        }
+    }
+}
+
+/// Threshold map of the ordered dither
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Channel levels of the 6x6x6 colour cube of the xterm palette, starting at index 16
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+/// Index of the first entry of the grayscale ramp (`8 + 10 * i`) of the xterm palette
+const GRAYSCALE_START: u8 = 232;
+
+/// Default values of the 16 standard colours used by xterm
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Map the colours of `cells`, a buffer `width` cells wide, to `mode`
+///
+/// The background of a cell is expected to cover its upper half and the foreground its lower
+/// half, drawn with a lower half block. On monochrome terminals the symbol is replaced and both
+/// colours are reset.
+pub fn quantize_cells(cells: &mut [Cell], width: u16, mode: ColorMode, dithering: bool) {
+    if mode == ColorMode::TrueColor || width == 0 {
+        return;
+    }
+
+    for (i, cell) in cells.iter_mut().enumerate() {
+        quantize_cell(cell, i % width as usize, i / width as usize, mode, dithering);
+    }
+}
+
+/// Map the colours of the cells of `buffer` within `area` to `mode`
+pub(crate) fn quantize_area(buffer: &mut Buffer, area: Rect, mode: ColorMode, dithering: bool) {
+    if mode == ColorMode::TrueColor {
+        return;
+    }
+
+    for row in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            if let Some(cell) = buffer.cell_mut((x, row)) {
+                quantize_cell(cell, x as usize, row as usize, mode, dithering);
+            }
+        }
+    }
+}
+
+/// Map the colours of the cell at column `x` and `row` to `mode`
+fn quantize_cell(cell: &mut Cell, x: usize, row: usize, mode: ColorMode, dithering: bool) {
+    // the background covers the upper half of a cell, the foreground the lower half
+    let threshold = |y: usize| {
+        if dithering {
+            (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0
+        } else {
+            0.5
+        }
+    };
+    let (top, bottom) = (threshold(row * 2), threshold(row * 2 + 1));
+
+    if mode == ColorMode::Monochrome {
+        quantize_monochrome(cell, top, bottom);
+    } else {
+        cell.bg = quantize_color(cell.bg, mode, top);
+        cell.fg = quantize_color(cell.fg, mode, bottom);
+    }
+}
+
+fn quantize_color(color: Color, mode: ColorMode, threshold: f32) -> Color {
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+
+    // offset the colour by up to half the distance between palette entries
+    let spread = match mode {
+        ColorMode::Indexed256 => 40.0,
+        _ => 128.0,
+    };
+    let offset = (threshold - 0.5) * spread;
+    let rgb = [r, g, b].map(|c| (c as f32 + offset).round().clamp(0.0, 255.0) as u8);
+
+    match mode {
+        ColorMode::Indexed256 => Color::Indexed(nearest_indexed(rgb)),
+        ColorMode::Indexed16 => nearest_ansi(rgb),
+        ColorMode::TrueColor | ColorMode::Monochrome => color,
+    }
+}
+
+fn quantize_monochrome(cell: &mut Cell, top: f32, bottom: f32) {
+    let luminance = |color: Color| match color {
+        Color::Rgb(r, g, b) => (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32) / 255.0,
+        Color::Reset | Color::Black => 0.0,
+        _ => 1.0,
+    };
+
+    let top_lit = luminance(cell.bg) > top;
+    let bottom_lit = if cell.symbol() == "\u{2584}" {
+        luminance(cell.fg) > bottom
+    } else {
+        luminance(cell.bg) > bottom
+    };
+
+    let symbol = match (top_lit, bottom_lit) {
+        (false, false) => ' ',
+        (true, false) => '\u{2580}',
+        (false, true) => '\u{2584}',
+        (true, true) => '\u{2588}',
+    };
+    cell.set_char(symbol);
+    cell.fg = Color::Reset;
+    cell.bg = Color::Reset;
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Index of the nearest colour in the colour cube or grayscale ramp of the xterm palette
+fn nearest_indexed(rgb: [u8; 3]) -> u8 {
+    let level = |c: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap()
+    };
+    let [r, g, b] = rgb.map(level);
+    let cube = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+    let cube_index = 16 + 36 * r + 6 * g + b;
+
+    let average = rgb.iter().map(|c| *c as i32).sum::<i32>() / 3;
+    let gray_step = ((average - 3) / 10).clamp(0, 23);
+    let gray_value = (8 + 10 * gray_step) as u8;
+
+    if distance(rgb, [gray_value; 3]) < distance(rgb, cube) {
+        GRAYSCALE_START + gray_step as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+/// Nearest of the 16 standard colours
+fn nearest_ansi(rgb: [u8; 3]) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, (r, g, b))| distance(rgb, [*r, *g, *b]))
+        .map(|(color, _)| *color)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_palette() {
+        assert_eq!(nearest_indexed([255, 0, 0]), 196);
+        assert_eq!(nearest_indexed([0, 0, 0]), 16);
+        assert_eq!(nearest_indexed([255, 255, 255]), 231);
+        assert_eq!(nearest_indexed([128, 128, 128]), 244);
+        assert_eq!(nearest_indexed([95, 135, 175]), 67);
+    }
+
+    #[test]
+    fn ansi_palette() {
+        assert_eq!(nearest_ansi([250, 10, 10]), Color::LightRed);
+        assert_eq!(nearest_ansi([120, 130, 125]), Color::DarkGray);
+        assert_eq!(nearest_ansi([10, 10, 10]), Color::Black);
+    }
+
+    #[test]
+    fn monochrome_blocks() {
+        let mut cells = vec![Cell::default(); 2];
+        cells[0]
+            .set_char('\u{2584}')
+            .set_bg(Color::Rgb(255, 255, 255))
+            .set_fg(Color::Rgb(0, 0, 0));
+        cells[1].set_bg(Color::Rgb(200, 200, 200));
+        quantize_cells(&mut cells, 2, ColorMode::Monochrome, false);
+        assert_eq!(cells[0].symbol(), "\u{2580}");
+        assert_eq!(cells[1].symbol(), "\u{2588}");
+        assert_eq!(cells[1].bg, Color::Reset);
+    }
+
+    #[test]
+    fn dithering_mixes_colors() {
+        let mut cells = vec![Cell::default(); 16];
+        for cell in &mut cells {
+            cell.set_bg(Color::Rgb(128, 128, 128));
+        }
+        quantize_cells(&mut cells, 4, ColorMode::Monochrome, true);
+        assert!(cells.iter().any(|cell| cell.symbol() != cells[0].symbol()));
+    }
+
+    #[test]
+    fn color_count() {
+        assert_eq!(ColorMode::from_color_count(16_777_216), ColorMode::TrueColor);
+        assert_eq!(ColorMode::from_color_count(256), ColorMode::Indexed256);
+        assert_eq!(ColorMode::from_color_count(16), ColorMode::Indexed16);
+        assert_eq!(ColorMode::from_color_count(2), ColorMode::Monochrome);
+    }
+
+    #[test]
+    fn area_matches_whole_buffer() {
+        let area = Rect::new(0, 0, 6, 4);
+        let mut buffer = Buffer::empty(area);
+        for (i, cell) in buffer.content.iter_mut().enumerate() {
+            let value = (i * 16) as u8;
+            cell.set_bg(Color::Rgb(value, 255 - value, value / 2));
+        }
+        let mut expected = buffer.clone();
+        quantize_cells(&mut expected.content, area.width, ColorMode::Indexed16, true);
+
+        let damage = Rect::new(2, 1, 3, 2);
+        quantize_area(&mut buffer, damage, ColorMode::Indexed16, true);
+        for (x, y) in area.positions().map(|position| (position.x, position.y)) {
+            if damage.contains((x, y).into()) {
+                assert_eq!(buffer[(x, y)], expected[(x, y)]);
+            } else {
+                assert!(matches!(buffer[(x, y)].bg, Color::Rgb(..)));
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Presentation of the pointer cursor in the terminal
+//!
+//! The cursor requested by clients can either be drawn into the frames, using
+//! [`RatatuiCursor::render_elements`], or be approximated by the mouse pointer of the host terminal,
+//! which is shaped using OSC 22 (see [`RatatuiRenderer::set_mouse_shape`]). Not every terminal
+//! supports OSC 22, those ignore the sequence.
+//!
+//! [`RatatuiRenderer::set_mouse_shape`]: crate::backend::renderer::ratatui::RatatuiRenderer::set_mouse_shape
+
+use std::fmt;
+
+use crate::{
+    backend::renderer::{
+        element::{
+            solid::SolidColorRenderElement,
+            surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+            Id, Kind,
+        },
+        utils::CommitCounter,
+        Color32F, ImportAll, Renderer,
+    },
+    input::pointer::{CursorIcon, CursorImageStatus, CursorImageSurfaceData},
+    utils::{Physical, Point, Rectangle, Scale, Size},
+    wayland::compositor,
+};
+
+/// Colour of cells covered by a named cursor
+const NAMED_CURSOR_COLOR: Color32F = Color32F::new(1.0, 1.0, 1.0, 1.0);
+
+/// The pointer cursor of a terminal
+///
+/// Named cursors cannot be drawn in a meaningful way at the resolution of a terminal,
+/// so they are drawn as a single highlighted cell. Cursor surfaces are drawn as they are.
+#[derive(Debug)]
+pub struct RatatuiCursor {
+    status: CursorImageStatus,
+    id: Id,
+    commit: CommitCounter,
+}
+
+impl Default for RatatuiCursor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RatatuiCursor {
+    /// Create a new cursor showing the default named cursor
+    pub fn new() -> Self {
+        RatatuiCursor {
+            status: CursorImageStatus::default_named(),
+            id: Id::new(),
+            commit: CommitCounter::default(),
+        }
+    }
+
+    /// Returns the cursor image requested by clients
+    pub fn status(&self) -> &CursorImageStatus {
+        &self.status
+    }
+
+    /// Set the cursor image requested by clients, see [`SeatHandler::cursor_image`](crate::input::SeatHandler::cursor_image)
+    pub fn set_status(&mut self, status: CursorImageStatus) {
+        if status != self.status {
+            self.status = status;
+            self.commit.increment();
+        }
+    }
+
+    /// Returns the shape of the mouse pointer of the terminal closest to the cursor image
+    ///
+    /// Terminals can neither hide their pointer nor show a cursor surface, in these cases the
+    /// default shape is returned.
+    pub fn terminal_shape(&self) -> CursorIcon {
+        match &self.status {
+            CursorImageStatus::Named(icon) => *icon,
+            CursorImageStatus::Hidden | CursorImageStatus::Surface(_) => CursorIcon::Default,
+        }
+    }
+
+    /// Returns the elements drawing the cursor with its hotspot at `location`
+    ///
+    /// `cell_size` is the size of a terminal cell in pixels of the output,
+    /// see [`RatatuiRenderer::cell_size`](crate::backend::renderer::ratatui::RatatuiRenderer::cell_size).
+    pub fn render_elements<R, E>(
+        &self,
+        renderer: &mut R,
+        location: Point<f64, Physical>,
+        cell_size: Size<i32, Physical>,
+        scale: impl Into<Scale<f64>>,
+        alpha: f32,
+    ) -> Vec<E>
+    where
+        R: Renderer + ImportAll,
+        R::TextureId: Clone + 'static,
+        E: From<RatatuiCursorRenderElement<R>>,
+    {
+        let scale = scale.into();
+        match &self.status {
+            CursorImageStatus::Hidden => Vec::new(),
+            CursorImageStatus::Named(_) => {
+                if cell_size.w <= 0 || cell_size.h <= 0 {
+                    return Vec::new();
+                }
+                // highlight the cell containing the hotspot
+                let cell = Point::new(
+                    (location.x / cell_size.w as f64).floor() as i32 * cell_size.w,
+                    (location.y / cell_size.h as f64).floor() as i32 * cell_size.h,
+                );
+                let element = SolidColorRenderElement::new(
+                    self.id.clone(),
+                    Rectangle::new(cell, cell_size),
+                    self.commit,
+                    NAMED_CURSOR_COLOR * alpha,
+                    Kind::Cursor,
+                );
+                vec![E::from(RatatuiCursorRenderElement::Named(element))]
+            }
+            CursorImageStatus::Surface(surface) => {
+                let hotspot = compositor::with_states(surface, |states| {
+                    states
+                        .data_map
+                        .get::<CursorImageSurfaceData>()
+                        .map(|attributes| attributes.lock().unwrap().hotspot)
+                        .unwrap_or_default()
+                });
+                let location = (location - hotspot.to_f64().to_physical(scale)).to_i32_round();
+                render_elements_from_surface_tree::<R, RatatuiCursorRenderElement<R>>(
+                    renderer,
+                    surface,
+                    location,
+                    scale,
+                    alpha,
+                    Kind::Cursor,
+                )
+                .into_iter()
+                .map(E::from)
+                .collect()
+            }
+        }
+    }
+}
+
+crate::backend::renderer::element::render_elements! {
+    /// Render elements of a [`RatatuiCursor`]
+    pub RatatuiCursorRenderElement<R> where
+        R: ImportAll;
+    /// A named cursor, drawn as a single cell
+    Named=SolidColorRenderElement,
+    /// A surface of the cursor
+    Surface=WaylandSurfaceRenderElement<R>,
+}
+
+impl<R: Renderer + ImportAll> fmt::Debug for RatatuiCursorRenderElement<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named(arg0) => f.debug_tuple("Named").field(arg0).finish(),
+            Self::Surface(arg0) => f.debug_tuple("Surface").field(arg0).finish(),
+            Self::_GenericCatcher(_) => unreachable!(),
+        }
+    }
+}
+
+/// Encode an OSC 22 sequence setting the shape of the mouse pointer (`OSC 22 ; name ST`)
+pub(crate) fn osc22(shape: CursorIcon) -> Vec<u8> {
+    format!("\x1b]22;{}\x1b\\", shape.name()).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc22_sequence() {
+        assert_eq!(osc22(CursorIcon::Pointer), b"\x1b]22;pointer\x1b\\");
+        assert_eq!(osc22(CursorIcon::EwResize), b"\x1b]22;ew-resize\x1b\\");
+    }
+}
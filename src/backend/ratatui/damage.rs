@@ -0,0 +1,118 @@
+//! Mapping of output damage onto the cell grid of the terminal
+//!
+//! The damage reported by [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker)
+//! is given in pixels. Terminals are updated cell by cell, so every partially damaged cell has to be
+//! sent again as a whole.
+
+use ratatui::layout::Rect;
+
+use crate::utils::{Physical, Rectangle, Size};
+
+/// Returns the cells of the terminal covered by `damage`
+///
+/// `damage` is given in pixels of an output of `size`, whose top left corner is placed on the top
+/// left cell of the terminal. Every cell covers `cell_size` pixels, see
+/// [`RatatuiRenderer::cell_size`](crate::backend::renderer::ratatui::RatatuiRenderer::cell_size).
+/// Damage outside of the output is ignored and overlapping regions are merged.
+pub fn damage_to_cells(
+    damage: &[Rectangle<i32, Physical>],
+    size: Size<i32, Physical>,
+    cell_size: Size<i32, Physical>,
+) -> Vec<Rect> {
+    if cell_size.w <= 0 || cell_size.h <= 0 {
+        return Vec::new();
+    }
+    // cells partially covered by the output are still part of it
+    let bounds = Rectangle::from_size(Size::new(
+        (size.w + cell_size.w - 1) / cell_size.w * cell_size.w,
+        (size.h + cell_size.h - 1) / cell_size.h * cell_size.h,
+    ));
+    let damage = damage
+        .iter()
+        .filter_map(|rect| rect.intersection(Rectangle::from_size(size)))
+        .collect::<Vec<_>>();
+
+    cell_aligned_regions(&damage, bounds, cell_size)
+        .into_iter()
+        .map(|region| {
+            let cell = |value: i32, cell: i32| u16::try_from(value / cell).unwrap_or(u16::MAX);
+            Rect::new(
+                cell(region.loc.x, cell_size.w),
+                cell(region.loc.y, cell_size.h),
+                cell(region.size.w, cell_size.w),
+                cell(region.size.h, cell_size.h),
+            )
+        })
+        .collect()
+}
+
+/// Expand `damage` to full cells and merge overlapping regions
+pub(crate) fn cell_aligned_regions(
+    damage: &[Rectangle<i32, Physical>],
+    bounds: Rectangle<i32, Physical>,
+    cell_size: Size<i32, Physical>,
+) -> Vec<Rectangle<i32, Physical>> {
+    let mut regions: Vec<Rectangle<i32, Physical>> = Vec::with_capacity(damage.len());
+    for rect in damage {
+        let Some(rect) = rect.intersection(bounds) else {
+            continue;
+        };
+        let x1 = (rect.loc.x / cell_size.w) * cell_size.w;
+        let y1 = (rect.loc.y / cell_size.h) * cell_size.h;
+        let x2 = (rect.loc.x + rect.size.w + cell_size.w - 1) / cell_size.w * cell_size.w;
+        let y2 = (rect.loc.y + rect.size.h + cell_size.h - 1) / cell_size.h * cell_size.h;
+        let Some(mut rect) = Rectangle::from_extremities((x1, y1), (x2, y2)).intersection(bounds) else {
+            continue;
+        };
+
+        // merge with all overlapping regions, until nothing overlaps anymore
+        while let Some(idx) = regions.iter().position(|r| r.overlaps(rect)) {
+            rect = rect.merge(regions.swap_remove(idx));
+        }
+        regions.push(rect);
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_cell_aligned() {
+        let bounds = Rectangle::from_size((100, 100).into());
+        let cell = Size::from((10, 20));
+        let regions = cell_aligned_regions(
+            &[
+                Rectangle::new((5, 5).into(), (10, 10).into()),
+                Rectangle::new((12, 15).into(), (2, 10).into()),
+                Rectangle::new((95, 95).into(), (10, 10).into()),
+            ],
+            bounds,
+            cell,
+        );
+        assert_eq!(
+            regions,
+            vec![
+                Rectangle::new((0, 0).into(), (20, 40).into()),
+                Rectangle::new((90, 80).into(), (10, 20).into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn half_block_cells() {
+        let size = Size::from((80, 47));
+        let cells = damage_to_cells(
+            &[
+                Rectangle::new((3, 1).into(), (2, 2).into()),
+                Rectangle::new((70, 40).into(), (20, 20).into()),
+                Rectangle::new((-10, -10).into(), (5, 5).into()),
+            ],
+            size,
+            Size::from((1, 2)),
+        );
+        // the last row of cells is only half covered by the output
+        assert_eq!(cells, vec![Rect::new(3, 0, 2, 2), Rect::new(70, 20, 10, 4)]);
+    }
+}
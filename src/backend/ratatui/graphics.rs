@@ -0,0 +1,289 @@
+//! Image based presentation for terminals supporting a graphics protocol
+//!
+//! Instead of approximating the output with half-block cells, frames are sent to the terminal
+//! as images using either DEC sixel graphics or the kitty graphics protocol. Only the damaged
+//! parts of a frame are transmitted, aligned to the cell grid of the terminal.
+
+use std::io::Write;
+
+use super::{damage::cell_aligned_regions, TerminalCapabilities};
+use crate::utils::{Physical, Rectangle, Size};
+
+/// Maximum payload size of a single kitty graphics escape sequence
+const KITTY_CHUNK_SIZE: usize = 4096;
+/// Number of partial kitty images kept on screen before the frame is sent again as a whole
+const KITTY_MAX_IMAGES: usize = 64;
+
+/// Levels per channel of the sixel palette
+const SIXEL_LEVELS: u32 = 6;
+const SIXEL_PALETTE_SIZE: usize = (SIXEL_LEVELS * SIXEL_LEVELS * SIXEL_LEVELS) as usize;
+
+/// Graphics protocol used to present frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GraphicsProtocol {
+    /// DEC sixel graphics
+    Sixel,
+    /// The kitty terminal graphics protocol
+    Kitty,
+}
+
+impl GraphicsProtocol {
+    /// Query the terminal attached to stdin and stdout for a supported graphics protocol
+    ///
+    /// The kitty graphics protocol is preferred over sixel, if both are available.
+    /// See [`TerminalCapabilities::detect`] for the requirements on the terminal.
+    pub fn detect() -> Option<GraphicsProtocol> {
+        TerminalCapabilities::detect().graphics_protocol
+    }
+}
+
+/// A frame in `Xrgb8888`, rows are tightly packed
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Image<'a> {
+    pub pixels: &'a [u32],
+    pub size: Size<i32, Physical>,
+}
+
+impl Image<'_> {
+    #[inline]
+    fn rgb(&self, x: i32, y: i32) -> (u8, u8, u8) {
+        let pixel = self.pixels[(y * self.size.w + x) as usize];
+        ((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+    }
+}
+
+/// Encodes damaged regions of frames using a [`GraphicsProtocol`]
+#[derive(Debug)]
+pub(crate) struct GraphicsEncoder {
+    protocol: GraphicsProtocol,
+    next_image_id: u32,
+    /// Kitty images currently placed on the screen, oldest first
+    images: Vec<u32>,
+}
+
+impl GraphicsEncoder {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        GraphicsEncoder {
+            protocol,
+            next_image_id: 1,
+            images: Vec::new(),
+        }
+    }
+
+    pub fn protocol(&self) -> GraphicsProtocol {
+        self.protocol
+    }
+
+    /// Encode the `damage` of `image` for a terminal with cells of `cell_size` pixels
+    ///
+    /// The image is expected to start at the top left cell of the terminal.
+    pub fn encode(
+        &mut self,
+        out: &mut Vec<u8>,
+        image: Image<'_>,
+        cell_size: Size<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+    ) {
+        let bounds = Rectangle::from_size(image.size);
+        let mut regions = cell_aligned_regions(damage, bounds, cell_size);
+
+        if self.protocol == GraphicsProtocol::Kitty && self.images.len() + regions.len() > KITTY_MAX_IMAGES {
+            regions = vec![bounds];
+        }
+
+        for region in regions {
+            // move the cursor to the top left cell of the region
+            let _ = write!(
+                out,
+                "\x1b[{};{}H",
+                region.loc.y / cell_size.h + 1,
+                region.loc.x / cell_size.w + 1
+            );
+            match self.protocol {
+                GraphicsProtocol::Sixel => encode_sixel(out, image, region),
+                GraphicsProtocol::Kitty => {
+                    let id = self.next_image_id;
+                    self.next_image_id = self.next_image_id.checked_add(1).unwrap_or(1);
+                    encode_kitty(out, image, region, id);
+
+                    // a complete frame covers all previous images
+                    if region == bounds {
+                        self.delete_images(out);
+                    }
+                    self.images.push(id);
+                }
+            }
+        }
+    }
+
+    /// Remove all images previously placed by this encoder
+    pub fn clear(&mut self, out: &mut Vec<u8>) {
+        if self.protocol == GraphicsProtocol::Kitty {
+            self.delete_images(out);
+        }
+    }
+
+    fn delete_images(&mut self, out: &mut Vec<u8>) {
+        for id in self.images.drain(..) {
+            let _ = write!(out, "\x1b_Ga=d,d=I,i={id},q=2\x1b\\");
+        }
+    }
+}
+
+/// Index into the sixel palette
+#[inline]
+fn sixel_color(rgb: (u8, u8, u8)) -> usize {
+    let level = |c: u8| (c as u32 * (SIXEL_LEVELS - 1) + 127) / 255;
+    (level(rgb.0) * SIXEL_LEVELS * SIXEL_LEVELS + level(rgb.1) * SIXEL_LEVELS + level(rgb.2)) as usize
+}
+
+fn encode_sixel(out: &mut Vec<u8>, image: Image<'_>, region: Rectangle<i32, Physical>) {
+    let width = region.size.w as usize;
+
+    // P2 = 1: pixels without a color stay transparent
+    let _ = write!(out, "\x1bP0;1;0q\"1;1;{};{}", region.size.w, region.size.h);
+
+    let mut defined = [false; SIXEL_PALETTE_SIZE];
+    // color of every pixel of the current band
+    let mut band = vec![0u16; width * 6];
+    // sixel data per color used in the current band
+    let mut colors: Vec<(usize, Vec<u8>)> = Vec::new();
+    let mut color_slots = [usize::MAX; SIXEL_PALETTE_SIZE];
+
+    for band_y in (0..region.size.h).step_by(6) {
+        let rows = (region.size.h - band_y).min(6) as usize;
+        colors.clear();
+        color_slots.fill(usize::MAX);
+
+        for row in 0..rows {
+            for x in 0..width {
+                let color =
+                    sixel_color(image.rgb(region.loc.x + x as i32, region.loc.y + band_y + row as i32));
+                band[row * width + x] = color as u16;
+                if color_slots[color] == usize::MAX {
+                    color_slots[color] = colors.len();
+                    colors.push((color, vec![0u8; width]));
+                }
+                colors[color_slots[color]].1[x] |= 1 << row;
+            }
+        }
+
+        for (idx, (color, sixels)) in colors.iter().enumerate() {
+            if !defined[*color] {
+                defined[*color] = true;
+                let percent = |level: usize| level * 100 / (SIXEL_LEVELS as usize - 1);
+                let levels = SIXEL_LEVELS as usize;
+                let _ = write!(
+                    out,
+                    "#{};2;{};{};{}",
+                    color,
+                    percent(color / (levels * levels)),
+                    percent(color / levels % levels),
+                    percent(color % levels)
+                );
+            }
+            let _ = write!(out, "#{color}");
+            sixel_rle(out, sixels);
+            if idx + 1 < colors.len() {
+                // carriage return to draw the next color over the same band
+                out.push(b'$');
+            }
+        }
+        if band_y + 6 < region.size.h {
+            out.push(b'-');
+        }
+    }
+
+    out.extend_from_slice(b"\x1b\\");
+}
+
+/// Append run-length encoded sixels
+fn sixel_rle(out: &mut Vec<u8>, sixels: &[u8]) {
+    let mut iter = sixels.iter().peekable();
+    while let Some(&bits) = iter.next() {
+        let mut count = 1;
+        while iter.next_if_eq(&&bits).is_some() {
+            count += 1;
+        }
+        let sixel = b'?' + bits;
+        if count > 3 {
+            let _ = write!(out, "!{count}");
+            out.push(sixel);
+        } else {
+            out.extend(std::iter::repeat(sixel).take(count));
+        }
+    }
+}
+
+fn encode_kitty(out: &mut Vec<u8>, image: Image<'_>, region: Rectangle<i32, Physical>, id: u32) {
+    let mut rgb = Vec::with_capacity((region.size.w * region.size.h * 3) as usize);
+    for y in region.loc.y..region.loc.y + region.size.h {
+        for x in region.loc.x..region.loc.x + region.size.w {
+            let (r, g, b) = image.rgb(x, y);
+            rgb.extend_from_slice(&[r, g, b]);
+        }
+    }
+    let payload = base64(&rgb);
+
+    let mut chunks = payload.chunks(KITTY_CHUNK_SIZE).peekable();
+    let mut first = true;
+    while let Some(chunk) = chunks.next() {
+        let more = u8::from(chunks.peek().is_some());
+        if first {
+            // C=1: do not move the cursor, q=2: suppress replies
+            let _ = write!(
+                out,
+                "\x1b_Ga=T,f=24,s={},v={},i={id},C=1,q=2,m={more};",
+                region.size.w, region.size.h
+            );
+            first = false;
+        } else {
+            let _ = write!(out, "\x1b_Gm={more};");
+        }
+        out.extend_from_slice(chunk);
+        out.extend_from_slice(b"\x1b\\");
+    }
+}
+
+pub(super) fn base64(data: &[u8]) -> Vec<u8> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = Vec::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        out.push(ALPHABET[(n >> 18) as usize & 63]);
+        out.push(ALPHABET[(n >> 12) as usize & 63]);
+        out.push(if chunk.len() > 1 {
+            ALPHABET[(n >> 6) as usize & 63]
+        } else {
+            b'='
+        });
+        out.push(if chunk.len() > 2 {
+            ALPHABET[n as usize & 63]
+        } else {
+            b'='
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b"f"), b"Zg==");
+        assert_eq!(base64(b"fo"), b"Zm8=");
+        assert_eq!(base64(b"foo"), b"Zm9v");
+        assert_eq!(base64(b"foobar"), b"Zm9vYmFy");
+    }
+
+    #[test]
+    fn sixel_run_length() {
+        let mut out = Vec::new();
+        sixel_rle(&mut out, &[0, 0, 0, 0, 0, 1, 1, 63]);
+        assert_eq!(out, b"!5?@@~");
+    }
+}
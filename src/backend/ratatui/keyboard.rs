@@ -0,0 +1,483 @@
+//! Translation of crossterm key events into evdev key codes
+//!
+//! Terminals without support for the kitty keyboard protocol only report key presses, with the
+//! state of the modifiers attached to them. In that case presses and releases of the modifiers
+//! are synthesized around every key, and every key press is immediately followed by its release,
+//! so the compositor sees a consistent stream of keys.
+//!
+//! Characters are mapped to the keys producing them on a US layout. Characters without such a key
+//! are passed on as keysyms, which the compositor has to type on its own, see
+//! [`RatatuiEvent::Keysym`](super::RatatuiEvent::Keysym).
+
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers, MediaKeyCode, ModifierKeyCode,
+};
+use input_event_codes::*;
+use xkbcommon::xkb;
+
+/// Offset between evdev scancodes and xkb keycodes, see `MIN_KEYCODE` in evdev
+const EVDEV_OFFSET: u32 = 8;
+
+/// Evdev code of the virtual `<META>` key of the xkb evdev keycodes
+///
+/// Evdev has no meta key, `KEY_LEFTMETA` and `KEY_RIGHTMETA` are the super keys.
+const KEY_META: u32 = 205 - EVDEV_OFFSET;
+
+/// Modifiers that are synthesized, with the key used to do so
+const SYNTHESIZED_MODIFIERS: [(KeyModifiers, u32); 5] = [
+    (KeyModifiers::SHIFT, KEY_LEFTSHIFT!()),
+    (KeyModifiers::CONTROL, KEY_LEFTCTRL!()),
+    (KeyModifiers::ALT, KEY_LEFTALT!()),
+    (KeyModifiers::SUPER, KEY_LEFTMETA!()),
+    (KeyModifiers::META, KEY_META),
+];
+
+/// Key resulting from the translation of a crossterm key event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Key {
+    /// Key with an xkb keycode
+    Code(u32, KeyEventKind),
+    /// Character without a key on the US layout, as xkb keysym
+    Keysym(u32, KeyEventKind),
+}
+
+impl From<Key> for super::RatatuiEvent {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Code(code, kind) => super::RatatuiEvent::Key { code, kind },
+            Key::Keysym(keysym, kind) => super::RatatuiEvent::Keysym {
+                keysym: keysym.into(),
+                kind,
+            },
+        }
+    }
+}
+
+impl Key {
+    fn with_kind(self, kind: KeyEventKind) -> Key {
+        match self {
+            Key::Code(code, _) => Key::Code(code, kind),
+            Key::Keysym(keysym, _) => Key::Keysym(keysym, kind),
+        }
+    }
+}
+
+/// Tracks modifier state to translate crossterm key events into a consistent stream of keys
+#[derive(Debug)]
+pub(super) struct Keyboard {
+    /// Whether the terminal reports key releases and modifier keys
+    enhanced: bool,
+    /// Currently pressed modifiers and the evdev code of the key holding them
+    pressed_modifiers: Vec<(KeyModifiers, u32)>,
+}
+
+impl Keyboard {
+    pub(super) fn new(enhanced: bool) -> Self {
+        Keyboard {
+            enhanced,
+            pressed_modifiers: Vec::new(),
+        }
+    }
+
+    /// Translate `event` into xkb keycodes or keysyms and their state
+    pub(super) fn translate(&mut self, event: KeyEvent) -> Vec<Key> {
+        let mut keys = Vec::new();
+
+        if let KeyCode::Modifier(modifier) = event.code {
+            let Some((flag, code)) = modifier_key(modifier) else {
+                tracing::trace!(?modifier, "Unsupported modifier key");
+                return keys;
+            };
+            match event.kind {
+                KeyEventKind::Press | KeyEventKind::Repeat => {
+                    if !self.pressed_modifiers.contains(&(flag, code)) {
+                        self.pressed_modifiers.push((flag, code));
+                    }
+                }
+                KeyEventKind::Release => self.pressed_modifiers.retain(|(_, pressed)| *pressed != code),
+            }
+            keys.push(Key::Code(code + EVDEV_OFFSET, event.kind));
+            return keys;
+        }
+
+        let (key, modifiers) = match key_code(&event) {
+            Some((code, implied)) => (
+                Key::Code(code + EVDEV_OFFSET, event.kind),
+                event.modifiers | implied,
+            ),
+            None => {
+                let keysym = match event.code {
+                    KeyCode::Char(c) => xkb::utf32_to_keysym(c as u32),
+                    _ => xkb::Keysym::NoSymbol,
+                };
+                if keysym == xkb::Keysym::NoSymbol {
+                    tracing::trace!(?event, "Unsupported key");
+                    return keys;
+                }
+                // the keysym already is the shifted character
+                (
+                    Key::Keysym(keysym.raw(), event.kind),
+                    event.modifiers - KeyModifiers::SHIFT,
+                )
+            }
+        };
+
+        if event.kind != KeyEventKind::Release {
+            self.sync_modifiers(modifiers, &mut keys);
+        }
+        if self.enhanced {
+            keys.push(key);
+        } else {
+            keys.push(key.with_kind(KeyEventKind::Press));
+            keys.push(key.with_kind(KeyEventKind::Release));
+            self.sync_modifiers(KeyModifiers::NONE, &mut keys);
+        }
+        keys
+    }
+
+    /// Press and release modifiers until the pressed modifiers match `modifiers`
+    fn sync_modifiers(&mut self, modifiers: KeyModifiers, keys: &mut Vec<Key>) {
+        for (flag, code) in SYNTHESIZED_MODIFIERS {
+            let pressed = self.pressed_modifiers.iter().any(|(pressed, _)| *pressed == flag);
+            if modifiers.contains(flag) && !pressed {
+                self.pressed_modifiers.push((flag, code));
+                keys.push(Key::Code(code + EVDEV_OFFSET, KeyEventKind::Press));
+            } else if !modifiers.contains(flag) && pressed {
+                self.pressed_modifiers.retain(|(pressed, code)| {
+                    if *pressed == flag {
+                        keys.push(Key::Code(code + EVDEV_OFFSET, KeyEventKind::Release));
+                    }
+                    *pressed != flag
+                });
+            }
+        }
+    }
+}
+
+fn modifier_key(modifier: ModifierKeyCode) -> Option<(KeyModifiers, u32)> {
+    Some(match modifier {
+        ModifierKeyCode::LeftShift => (KeyModifiers::SHIFT, KEY_LEFTSHIFT!()),
+        ModifierKeyCode::RightShift => (KeyModifiers::SHIFT, KEY_RIGHTSHIFT!()),
+        ModifierKeyCode::LeftControl => (KeyModifiers::CONTROL, KEY_LEFTCTRL!()),
+        ModifierKeyCode::RightControl => (KeyModifiers::CONTROL, KEY_RIGHTCTRL!()),
+        ModifierKeyCode::LeftAlt => (KeyModifiers::ALT, KEY_LEFTALT!()),
+        ModifierKeyCode::RightAlt => (KeyModifiers::ALT, KEY_RIGHTALT!()),
+        ModifierKeyCode::LeftSuper => (KeyModifiers::SUPER, KEY_LEFTMETA!()),
+        ModifierKeyCode::RightSuper => (KeyModifiers::SUPER, KEY_RIGHTMETA!()),
+        ModifierKeyCode::LeftMeta | ModifierKeyCode::RightMeta => (KeyModifiers::META, KEY_META),
+        // AltGr on most layouts
+        ModifierKeyCode::IsoLevel3Shift => (KeyModifiers::NONE, KEY_RIGHTALT!()),
+        ModifierKeyCode::LeftHyper | ModifierKeyCode::RightHyper | ModifierKeyCode::IsoLevel5Shift => {
+            return None
+        }
+    })
+}
+
+/// Evdev code of the key of `event` and the modifiers implied by it on a US layout
+fn key_code(event: &KeyEvent) -> Option<(u32, KeyModifiers)> {
+    let keypad = event.state.contains(KeyEventState::KEYPAD);
+    let code = match event.code {
+        KeyCode::Char(c) if keypad => keypad_char(c)?,
+        KeyCode::Char(c) => return char_key(c),
+        KeyCode::Enter if keypad => KEY_KPENTER!(),
+        KeyCode::Insert if keypad => KEY_KP0!(),
+        KeyCode::Delete if keypad => KEY_KPDOT!(),
+        KeyCode::End if keypad => KEY_KP1!(),
+        KeyCode::Down if keypad => KEY_KP2!(),
+        KeyCode::PageDown if keypad => KEY_KP3!(),
+        KeyCode::Left if keypad => KEY_KP4!(),
+        KeyCode::Right if keypad => KEY_KP6!(),
+        KeyCode::Home if keypad => KEY_KP7!(),
+        KeyCode::Up if keypad => KEY_KP8!(),
+        KeyCode::PageUp if keypad => KEY_KP9!(),
+        KeyCode::KeypadBegin => KEY_KP5!(),
+
+        KeyCode::Backspace => KEY_BACKSPACE!(),
+        KeyCode::Enter => KEY_ENTER!(),
+        KeyCode::Left => KEY_LEFT!(),
+        KeyCode::Right => KEY_RIGHT!(),
+        KeyCode::Up => KEY_UP!(),
+        KeyCode::Down => KEY_DOWN!(),
+        KeyCode::Home => KEY_HOME!(),
+        KeyCode::End => KEY_END!(),
+        KeyCode::PageUp => KEY_PAGEUP!(),
+        KeyCode::PageDown => KEY_PAGEDOWN!(),
+        KeyCode::Tab => KEY_TAB!(),
+        KeyCode::BackTab => return Some((KEY_TAB!(), KeyModifiers::SHIFT)),
+        KeyCode::Delete => KEY_DELETE!(),
+        KeyCode::Insert => KEY_INSERT!(),
+        KeyCode::F(n) => function_key(n)?,
+        KeyCode::Null => return Some((KEY_SPACE!(), KeyModifiers::CONTROL)),
+        KeyCode::Esc => KEY_ESC!(),
+        KeyCode::CapsLock => KEY_CAPSLOCK!(),
+        KeyCode::ScrollLock => KEY_SCROLLLOCK!(),
+        KeyCode::NumLock => KEY_NUMLOCK!(),
+        KeyCode::PrintScreen => KEY_SYSRQ!(),
+        KeyCode::Pause => KEY_PAUSE!(),
+        KeyCode::Menu => KEY_COMPOSE!(),
+        KeyCode::Media(media) => media_key(media)?,
+        KeyCode::Modifier(modifier) => modifier_key(modifier)?.1,
+    };
+    Some((code, KeyModifiers::NONE))
+}
+
+fn function_key(n: u8) -> Option<u32> {
+    const FUNCTION_KEYS: [u32; 24] = [
+        KEY_F1!(),
+        KEY_F2!(),
+        KEY_F3!(),
+        KEY_F4!(),
+        KEY_F5!(),
+        KEY_F6!(),
+        KEY_F7!(),
+        KEY_F8!(),
+        KEY_F9!(),
+        KEY_F10!(),
+        KEY_F11!(),
+        KEY_F12!(),
+        KEY_F13!(),
+        KEY_F14!(),
+        KEY_F15!(),
+        KEY_F16!(),
+        KEY_F17!(),
+        KEY_F18!(),
+        KEY_F19!(),
+        KEY_F20!(),
+        KEY_F21!(),
+        KEY_F22!(),
+        KEY_F23!(),
+        KEY_F24!(),
+    ];
+    FUNCTION_KEYS.get(usize::from(n).checked_sub(1)?).copied()
+}
+
+fn media_key(media: MediaKeyCode) -> Option<u32> {
+    Some(match media {
+        MediaKeyCode::Play => KEY_PLAY!(),
+        MediaKeyCode::Pause => KEY_PAUSECD!(),
+        MediaKeyCode::PlayPause => KEY_PLAYPAUSE!(),
+        MediaKeyCode::Stop => KEY_STOPCD!(),
+        MediaKeyCode::FastForward => KEY_FASTFORWARD!(),
+        MediaKeyCode::Rewind => KEY_REWIND!(),
+        MediaKeyCode::TrackNext => KEY_NEXTSONG!(),
+        MediaKeyCode::TrackPrevious => KEY_PREVIOUSSONG!(),
+        MediaKeyCode::Record => KEY_RECORD!(),
+        MediaKeyCode::LowerVolume => KEY_VOLUMEDOWN!(),
+        MediaKeyCode::RaiseVolume => KEY_VOLUMEUP!(),
+        MediaKeyCode::MuteVolume => KEY_MUTE!(),
+        MediaKeyCode::Reverse => return None,
+    })
+}
+
+fn keypad_char(c: char) -> Option<u32> {
+    Some(match c {
+        '0' => KEY_KP0!(),
+        '1' => KEY_KP1!(),
+        '2' => KEY_KP2!(),
+        '3' => KEY_KP3!(),
+        '4' => KEY_KP4!(),
+        '5' => KEY_KP5!(),
+        '6' => KEY_KP6!(),
+        '7' => KEY_KP7!(),
+        '8' => KEY_KP8!(),
+        '9' => KEY_KP9!(),
+        '.' => KEY_KPDOT!(),
+        ',' => KEY_KPCOMMA!(),
+        '+' => KEY_KPPLUS!(),
+        '-' => KEY_KPMINUS!(),
+        '*' => KEY_KPASTERISK!(),
+        '/' => KEY_KPSLASH!(),
+        '=' => KEY_KPEQUAL!(),
+        _ => return None,
+    })
+}
+
+/// Evdev code of the key producing `c` on a US layout and whether it requires shift
+fn char_key(c: char) -> Option<(u32, KeyModifiers)> {
+    let shifted = c.is_ascii_uppercase() || "~!@#$%^&*()_+{}|:\"<>?".contains(c);
+    let code = match c.to_ascii_lowercase() {
+        '1' | '!' => KEY_1!(),
+        '2' | '@' => KEY_2!(),
+        '3' | '#' => KEY_3!(),
+        '4' | '$' => KEY_4!(),
+        '5' | '%' => KEY_5!(),
+        '6' | '^' => KEY_6!(),
+        '7' | '&' => KEY_7!(),
+        '8' | '*' => KEY_8!(),
+        '9' | '(' => KEY_9!(),
+        '0' | ')' => KEY_0!(),
+        '-' | '_' => KEY_MINUS!(),
+        '=' | '+' => KEY_EQUAL!(),
+        'q' => KEY_Q!(),
+        'w' => KEY_W!(),
+        'e' => KEY_E!(),
+        'r' => KEY_R!(),
+        't' => KEY_T!(),
+        'y' => KEY_Y!(),
+        'u' => KEY_U!(),
+        'i' => KEY_I!(),
+        'o' => KEY_O!(),
+        'p' => KEY_P!(),
+        '[' | '{' => KEY_LEFTBRACE!(),
+        ']' | '}' => KEY_RIGHTBRACE!(),
+        'a' => KEY_A!(),
+        's' => KEY_S!(),
+        'd' => KEY_D!(),
+        'f' => KEY_F!(),
+        'g' => KEY_G!(),
+        'h' => KEY_H!(),
+        'j' => KEY_J!(),
+        'k' => KEY_K!(),
+        'l' => KEY_L!(),
+        ';' | ':' => KEY_SEMICOLON!(),
+        '\'' | '"' => KEY_APOSTROPHE!(),
+        '`' | '~' => KEY_GRAVE!(),
+        '\\' | '|' => KEY_BACKSLASH!(),
+        'z' => KEY_Z!(),
+        'x' => KEY_X!(),
+        'c' => KEY_C!(),
+        'v' => KEY_V!(),
+        'b' => KEY_B!(),
+        'n' => KEY_N!(),
+        'm' => KEY_M!(),
+        ',' | '<' => KEY_COMMA!(),
+        '.' | '>' => KEY_DOT!(),
+        '/' | '?' => KEY_SLASH!(),
+        ' ' => KEY_SPACE!(),
+        '\t' => KEY_TAB!(),
+        '\n' | '\r' => KEY_ENTER!(),
+        _ => return None,
+    };
+    let implied = if shifted {
+        KeyModifiers::SHIFT
+    } else {
+        KeyModifiers::NONE
+    };
+    Some((code, implied))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: u32, kind: KeyEventKind) -> Key {
+        Key::Code(code + EVDEV_OFFSET, kind)
+    }
+
+    #[test]
+    fn legacy_synthesizes_modifiers() {
+        let mut keyboard = Keyboard::new(false);
+        let keys = keyboard.translate(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+        assert_eq!(
+            keys,
+            vec![
+                key(KEY_LEFTCTRL!(), KeyEventKind::Press),
+                key(KEY_C!(), KeyEventKind::Press),
+                key(KEY_C!(), KeyEventKind::Release),
+                key(KEY_LEFTCTRL!(), KeyEventKind::Release),
+            ]
+        );
+
+        let keys = keyboard.translate(KeyEvent::new(KeyCode::Char('!'), KeyModifiers::NONE));
+        assert_eq!(keys[0], key(KEY_LEFTSHIFT!(), KeyEventKind::Press));
+        assert_eq!(keys[1], key(KEY_1!(), KeyEventKind::Press));
+        assert_eq!(keys.len(), 4);
+    }
+
+    #[test]
+    fn enhanced_tracks_modifier_keys() {
+        let mut keyboard = Keyboard::new(true);
+        let keys = keyboard.translate(KeyEvent::new(
+            KeyCode::Modifier(ModifierKeyCode::RightShift),
+            KeyModifiers::SHIFT,
+        ));
+        assert_eq!(keys, vec![key(KEY_RIGHTSHIFT!(), KeyEventKind::Press)]);
+
+        // shift is already held, ctrl is missing
+        let keys = keyboard.translate(KeyEvent::new(
+            KeyCode::Char('a'),
+            KeyModifiers::SHIFT | KeyModifiers::CONTROL,
+        ));
+        assert_eq!(
+            keys,
+            vec![
+                key(KEY_LEFTCTRL!(), KeyEventKind::Press),
+                key(KEY_A!(), KeyEventKind::Press),
+            ]
+        );
+
+        // the release of shift was missed
+        let keys = keyboard.translate(KeyEvent::new(KeyCode::Char('b'), KeyModifiers::CONTROL));
+        assert_eq!(
+            keys,
+            vec![
+                key(KEY_RIGHTSHIFT!(), KeyEventKind::Release),
+                key(KEY_B!(), KeyEventKind::Press),
+            ]
+        );
+    }
+
+    #[test]
+    fn keypad_and_media_keys() {
+        let mut keyboard = Keyboard::new(true);
+        let mut event = KeyEvent::new(KeyCode::Char('5'), KeyModifiers::NONE);
+        event.state = KeyEventState::KEYPAD;
+        assert_eq!(
+            keyboard.translate(event),
+            vec![key(KEY_KP5!(), KeyEventKind::Press)]
+        );
+
+        let event = KeyEvent::new(KeyCode::Media(MediaKeyCode::PlayPause), KeyModifiers::NONE);
+        assert_eq!(
+            keyboard.translate(event),
+            vec![key(KEY_PLAYPAUSE!(), KeyEventKind::Press)]
+        );
+
+        let event = KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE);
+        assert_eq!(
+            keyboard.translate(event),
+            vec![key(KEY_PAGEUP!(), KeyEventKind::Press)]
+        );
+    }
+
+    #[test]
+    fn non_ascii_characters_as_keysyms() {
+        let mut keyboard = Keyboard::new(false);
+        // shift is part of the character, other modifiers still apply
+        let keys = keyboard.translate(KeyEvent::new(
+            KeyCode::Char('É'),
+            KeyModifiers::SHIFT | KeyModifiers::CONTROL,
+        ));
+        assert_eq!(
+            keys,
+            vec![
+                key(KEY_LEFTCTRL!(), KeyEventKind::Press),
+                Key::Keysym(0xc9, KeyEventKind::Press),
+                Key::Keysym(0xc9, KeyEventKind::Release),
+                key(KEY_LEFTCTRL!(), KeyEventKind::Release),
+            ]
+        );
+
+        let mut keyboard = Keyboard::new(true);
+        let event = KeyEvent::new_with_kind(KeyCode::Char('€'), KeyModifiers::NONE, KeyEventKind::Release);
+        assert_eq!(
+            keyboard.translate(event),
+            vec![Key::Keysym(xkb::Keysym::EuroSign.raw(), KeyEventKind::Release)]
+        );
+    }
+
+    #[test]
+    fn meta_is_not_super() {
+        let mut keyboard = Keyboard::new(true);
+        let meta = keyboard.translate(KeyEvent::new(
+            KeyCode::Modifier(ModifierKeyCode::LeftMeta),
+            KeyModifiers::META,
+        ));
+        let super_ = keyboard.translate(KeyEvent::new(
+            KeyCode::Modifier(ModifierKeyCode::LeftSuper),
+            KeyModifiers::META | KeyModifiers::SUPER,
+        ));
+        assert_eq!(meta, vec![Key::Code(205, KeyEventKind::Press)]);
+        assert_eq!(super_, vec![key(KEY_LEFTMETA!(), KeyEventKind::Press)]);
+    }
+}
@@ -6,11 +6,33 @@ use calloop::{EventSource, Interest, Mode, PostAction};
 use timerfd::{SetTimeFlags, TimerFd, TimerState};
 
 use crate::{backend::renderer::ratatui::RatatuiRenderer, utils::Size};
+
+mod capabilities;
+mod clipboard;
+mod color;
+mod cursor;
+mod damage;
+mod graphics;
+mod keyboard;
+pub mod remote;
+mod title;
+pub use capabilities::TerminalCapabilities;
+pub(crate) use clipboard::osc52;
+pub use clipboard::{Clipboard, MAX_CLIPBOARD_SIZE};
+pub(crate) use color::quantize_area;
+pub use color::{quantize_cells, ColorMode};
+pub(crate) use cursor::osc22;
+pub use cursor::{RatatuiCursor, RatatuiCursorRenderElement};
+pub use damage::damage_to_cells;
+pub use graphics::GraphicsProtocol;
+pub(crate) use graphics::{GraphicsEncoder, Image};
 use std::{
     io,
     os::{fd::AsFd, unix::prelude::BorrowedFd},
     time::{Duration, Instant},
 };
+pub(crate) use title::{osc0, BEL, POP_TITLE, PUSH_TITLE};
+pub use title::{toplevel_title, MAX_TITLE_LENGTH};
 
 #[derive(Debug)]
 struct Timer {
@@ -49,13 +71,50 @@ impl AsFd for Timer {
 #[derive(Debug)]
 pub struct RatatuiBackend {
     renderer: RatatuiRenderer,
+    capabilities: TerminalCapabilities,
 }
 
 impl RatatuiBackend {
     /// Create a new ratatui backend.
+    ///
+    /// The [`TerminalCapabilities`] are detected on creation and the [`ColorMode`]
+    /// of the renderer is set to the colour depth supported by the terminal.
     pub fn new() -> Result<Self, io::Error> {
-        let renderer = RatatuiRenderer::new();
-        Ok(RatatuiBackend { renderer })
+        let mut renderer = RatatuiRenderer::new();
+        let capabilities = TerminalCapabilities::detect();
+        renderer.set_color_mode(capabilities.color_mode);
+        Ok(RatatuiBackend {
+            renderer,
+            capabilities,
+        })
+    }
+
+    /// Create a new ratatui backend presenting frames as images.
+    ///
+    /// Uses the sixel or kitty graphics protocol, if the terminal advertises support
+    /// for one of them, and falls back to half-block cells otherwise.
+    pub fn with_graphics() -> Result<Self, io::Error> {
+        let mut backend = Self::new()?;
+        if let Some(protocol) = backend.capabilities.graphics_protocol {
+            if let Err(err) = backend.renderer.set_graphics_protocol(Some(protocol)) {
+                tracing::warn!(
+                    ?protocol,
+                    ?err,
+                    "Failed to enable graphics, falling back to cells"
+                );
+            }
+        }
+        Ok(backend)
+    }
+
+    /// Returns the capabilities of the terminal detected on creation
+    pub fn capabilities(&self) -> &TerminalCapabilities {
+        &self.capabilities
+    }
+
+    /// Returns the [`GraphicsProtocol`] used to present frames, if any
+    pub fn graphics_protocol(&self) -> Option<GraphicsProtocol> {
+        self.renderer.graphics_protocol()
     }
 
     /// Get a mutable reference to the renderer.
@@ -63,16 +122,53 @@ impl RatatuiBackend {
         &mut self.renderer
     }
 
-    /// Return window size, in cells
+    /// Return window size, in cells or in pixels if a [`GraphicsProtocol`] is used
     pub fn window_size(&self) -> Size<i32, crate::utils::Physical> {
         self.renderer.window_size()
     }
 
+    /// Copy `data` to the clipboard of the terminal using OSC 52
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`], if `data` is larger than [`MAX_CLIPBOARD_SIZE`].
+    /// Terminals without OSC 52 support ignore the sequence.
+    pub fn set_clipboard(&mut self, clipboard: Clipboard, data: &[u8]) -> io::Result<()> {
+        self.renderer.set_clipboard(clipboard, data)
+    }
+
+    /// Set the title of the terminal window using OSC 0
+    ///
+    /// See [`RatatuiRenderer::set_title`], use [`toplevel_title`] to get the title of a window.
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        self.renderer.set_title(title)
+    }
+
+    /// Set the shape of the mouse pointer of the terminal using OSC 22
+    ///
+    /// See [`RatatuiRenderer::set_mouse_shape`] and [`RatatuiCursor::terminal_shape`].
+    pub fn set_mouse_shape(&mut self, shape: crate::input::pointer::CursorIcon) -> io::Result<()> {
+        self.renderer.set_mouse_shape(shape)
+    }
+
+    /// Ring the bell of the terminal, e.g. for a [system bell](crate::wayland::xdg_system_bell) request
+    pub fn bell(&mut self) -> io::Result<()> {
+        self.renderer.bell()
+    }
+
+    /// Return the size of a terminal cell in pixels of the [`window_size`](RatatuiBackend::window_size)
+    pub fn cell_size(&self) -> Size<i32, crate::utils::Physical> {
+        self.renderer.cell_size()
+    }
+
+    /// Create an event source for input from the terminal and redraws every `refresh_interval`
+    ///
+    /// If the terminal does not support the kitty keyboard protocol, key releases and
+    /// modifier keys are synthesized from the key presses reported by the terminal.
     pub fn event_source(&self, refresh_interval: Duration) -> RatatuiEventSource {
         RatatuiEventSource {
             event_token: None,
             timer: None,
             refresh_interval,
+            keyboard: keyboard::Keyboard::new(self.capabilities.keyboard_enhancement),
         }
     }
 }
@@ -82,6 +178,7 @@ pub struct RatatuiEventSource {
     event_token: Option<calloop::Token>,
     timer: Option<Timer>,
     refresh_interval: Duration,
+    keyboard: keyboard::Keyboard,
 }
 
 #[derive(Debug)]
@@ -92,111 +189,19 @@ pub enum RatatuiEvent {
         code: u32,
         kind: crossterm::event::KeyEventKind,
     },
+    /// A character typed without a key on the US layout, which [`Key`](RatatuiEvent::Key) events use
+    ///
+    /// The compositor has to type `keysym` on its own, e.g. by pressing a key of a temporary
+    /// keymap entry or by committing the character through text input.
+    Keysym {
+        keysym: xkbcommon::xkb::Keysym,
+        kind: crossterm::event::KeyEventKind,
+    },
     Mouse(crossterm::event::MouseEvent),
-}
-
-fn to_input_code(code: crossterm::event::KeyCode) -> Option<u32> {
-    use crossterm::event::KeyCode;
-    use crossterm::event::ModifierKeyCode;
-    use input_event_codes::*;
-
-    Some(
-        match code {
-            KeyCode::Esc => KEY_ESC!(),
-            KeyCode::Char('1') => KEY_1!(),
-            KeyCode::Char('2') => KEY_2!(),
-            KeyCode::Char('3') => KEY_3!(),
-            KeyCode::Char('4') => KEY_4!(),
-            KeyCode::Char('5') => KEY_5!(),
-            KeyCode::Char('6') => KEY_6!(),
-            KeyCode::Char('7') => KEY_7!(),
-            KeyCode::Char('8') => KEY_8!(),
-            KeyCode::Char('9') => KEY_9!(),
-            KeyCode::Char('0') => KEY_0!(),
-            KeyCode::Char('-') => KEY_MINUS!(),
-            KeyCode::Char('=') => KEY_EQUAL!(),
-            KeyCode::Backspace => KEY_BACKSPACE!(),
-            KeyCode::Tab => KEY_TAB!(),
-            KeyCode::Char('Q') | KeyCode::Char('q') => KEY_Q!(),
-            KeyCode::Char('W') | KeyCode::Char('w') => KEY_W!(),
-            KeyCode::Char('E') | KeyCode::Char('e') => KEY_E!(),
-            KeyCode::Char('R') | KeyCode::Char('r') => KEY_R!(),
-            KeyCode::Char('T') | KeyCode::Char('t') => KEY_T!(),
-            KeyCode::Char('Y') | KeyCode::Char('y') => KEY_Y!(),
-            KeyCode::Char('U') | KeyCode::Char('u') => KEY_U!(),
-            KeyCode::Char('I') | KeyCode::Char('i') => KEY_I!(),
-            KeyCode::Char('O') | KeyCode::Char('o') => KEY_O!(),
-            KeyCode::Char('P') | KeyCode::Char('p') => KEY_P!(),
-            KeyCode::Char('[') | KeyCode::Char('{') => KEY_LEFTBRACE!(),
-            KeyCode::Char(']') | KeyCode::Char('}') => KEY_RIGHTBRACE!(),
-            KeyCode::Enter => KEY_ENTER!(),
-            KeyCode::Char('A') | KeyCode::Char('a') => KEY_A!(),
-            KeyCode::Char('S') | KeyCode::Char('s') => KEY_S!(),
-            KeyCode::Char('D') | KeyCode::Char('d') => KEY_D!(),
-            KeyCode::Char('F') | KeyCode::Char('f') => KEY_F!(),
-            KeyCode::Char('G') | KeyCode::Char('g') => KEY_G!(),
-            KeyCode::Char('H') | KeyCode::Char('h') => KEY_H!(),
-            KeyCode::Char('J') | KeyCode::Char('j') => KEY_J!(),
-            KeyCode::Char('K') | KeyCode::Char('k') => KEY_K!(),
-            KeyCode::Char('L') | KeyCode::Char('l') => KEY_L!(),
-            KeyCode::Char(';') | KeyCode::Char(':') => KEY_SEMICOLON!(),
-            KeyCode::Char('\'') | KeyCode::Char('"') => KEY_APOSTROPHE!(),
-            KeyCode::Char('`') | KeyCode::Char('~') => KEY_GRAVE!(),
-            KeyCode::Char('\\') | KeyCode::Char('|') => KEY_BACKSLASH!(),
-            KeyCode::Char('Z') | KeyCode::Char('z') => KEY_Z!(),
-            KeyCode::Char('X') | KeyCode::Char('x') => KEY_X!(),
-            KeyCode::Char('C') | KeyCode::Char('c') => KEY_C!(),
-            KeyCode::Char('V') | KeyCode::Char('v') => KEY_V!(),
-            KeyCode::Char('B') | KeyCode::Char('b') => KEY_B!(),
-            KeyCode::Char('N') | KeyCode::Char('n') => KEY_N!(),
-            KeyCode::Char('M') | KeyCode::Char('m') => KEY_M!(),
-            KeyCode::Char(',') | KeyCode::Char('<') => KEY_COMMA!(),
-            KeyCode::Char('.') | KeyCode::Char('>') => KEY_DOT!(),
-            KeyCode::Char('/') | KeyCode::Char('?') => KEY_SLASH!(),
-            KeyCode::Char(' ') => KEY_SPACE!(),
-            KeyCode::F(1) => KEY_F1!(),
-            KeyCode::F(2) => KEY_F2!(),
-            KeyCode::F(3) => KEY_F3!(),
-            KeyCode::F(4) => KEY_F4!(),
-            KeyCode::F(5) => KEY_F5!(),
-            KeyCode::F(6) => KEY_F6!(),
-            KeyCode::F(7) => KEY_F7!(),
-            KeyCode::F(8) => KEY_F8!(),
-            KeyCode::F(9) => KEY_F9!(),
-            KeyCode::F(10) => KEY_F10!(),
-            KeyCode::F(11) => KEY_F11!(),
-            KeyCode::F(12) => KEY_F12!(),
-            KeyCode::F(13) => KEY_F13!(),
-            KeyCode::F(14) => KEY_F14!(),
-            KeyCode::F(15) => KEY_F15!(),
-            KeyCode::F(16) => KEY_F16!(),
-            KeyCode::F(17) => KEY_F17!(),
-            KeyCode::F(18) => KEY_F18!(),
-            KeyCode::F(19) => KEY_F19!(),
-            KeyCode::F(20) => KEY_F20!(),
-            KeyCode::F(21) => KEY_F21!(),
-            KeyCode::F(22) => KEY_F22!(),
-            KeyCode::F(23) => KEY_F23!(),
-            KeyCode::F(24) => KEY_F24!(),
-            KeyCode::NumLock => KEY_NUMLOCK!(),
-            KeyCode::CapsLock => KEY_CAPSLOCK!(),
-            KeyCode::Left => KEY_LEFT!(),
-            KeyCode::Right => KEY_RIGHT!(),
-            KeyCode::Up => KEY_UP!(),
-            KeyCode::Down => KEY_DOWN!(),
-            KeyCode::Modifier(ModifierKeyCode::LeftShift) => KEY_LEFTSHIFT!(),
-            KeyCode::Modifier(ModifierKeyCode::RightShift) => KEY_RIGHTSHIFT!(),
-            KeyCode::Modifier(ModifierKeyCode::LeftControl) => KEY_LEFTCTRL!(),
-            KeyCode::Modifier(ModifierKeyCode::RightControl) => KEY_RIGHTCTRL!(),
-            KeyCode::Modifier(ModifierKeyCode::LeftAlt) => KEY_LEFTALT!(),
-            KeyCode::Modifier(ModifierKeyCode::RightAlt) => KEY_RIGHTALT!(),
-            c => {
-                eprintln!("unsupported key code: {c:?}");
-                return None;
-            }
-        } + 8, /* +8 maps scancode to x11 keycode, see MIN_KEYCODE in evdev */
-               // TODO: type-based scancode -> keycode map
-    )
+    /// Text pasted into the terminal
+    Paste(String),
+    /// The terminal detached, only emitted for [`remote`] terminals
+    Detached,
 }
 
 impl EventSource for RatatuiEventSource {
@@ -236,20 +241,19 @@ impl EventSource for RatatuiEventSource {
 
         while crossterm::event::poll(Duration::from_millis(0))? {
             let event = match crossterm::event::read()? {
-                crossterm::event::Event::Resize(width, height) => Some(RatatuiEvent::Resize(width, height)),
+                crossterm::event::Event::Resize(width, height) => RatatuiEvent::Resize(width, height),
                 crossterm::event::Event::Key(event) => {
-                    to_input_code(event.code).map(|code| RatatuiEvent::Key {
-                        code,
-                        kind: event.kind,
-                    })
+                    for key in self.keyboard.translate(event) {
+                        callback(key.into(), data);
+                    }
+                    continue;
                 }
-                crossterm::event::Event::Mouse(event) => Some(RatatuiEvent::Mouse(event)),
+                crossterm::event::Event::Mouse(event) => RatatuiEvent::Mouse(event),
+                crossterm::event::Event::Paste(text) => RatatuiEvent::Paste(text),
                 _ => continue,
             };
 
-            if let Some(event) = event {
-                callback(event, data);
-            }
+            callback(event, data);
         }
         Ok(PostAction::Continue)
     }
@@ -308,7 +312,7 @@ mod input {
 
     use crate::{
         backend::input::{self, KeyboardKeyEvent},
-        utils::Size,
+        utils::{Point, Size},
     };
 
     #[derive(Debug)]
@@ -345,16 +349,35 @@ mod input {
         type SpecialEvent = input::UnusedEvent;
     }
 
-    #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-    pub struct Device;
+    /// Input device of a terminal
+    ///
+    /// The default device belongs to the terminal of the process,
+    /// every [`RemoteTerminal`](super::remote::RemoteTerminal) has a device of its own.
+    #[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
+    pub struct Device {
+        /// `0` for the terminal of the process
+        terminal: u64,
+    }
+
+    impl Device {
+        pub(crate) fn remote(terminal: u64) -> Self {
+            Device { terminal }
+        }
+    }
 
     impl input::Device for Device {
         fn id(&self) -> String {
-            "ratatui-input-device-id".to_owned()
+            match self.terminal {
+                0 => "ratatui-input-device-id".to_owned(),
+                terminal => format!("ratatui-input-device-id-{terminal}"),
+            }
         }
 
         fn name(&self) -> String {
-            "ratatui-input-device".to_owned()
+            match self.terminal {
+                0 => "ratatui-input-device".to_owned(),
+                terminal => format!("ratatui-input-device-{terminal}"),
+            }
         }
 
         fn has_capability(&self, capability: input::DeviceCapability) -> bool {
@@ -379,6 +402,15 @@ mod input {
         time: Instant,
         code: u32,
         kind: crossterm::event::KeyEventKind,
+        device: Device,
+    }
+
+    impl KeyEvent {
+        /// Attribute the event to `device` instead of the terminal of the process
+        pub fn with_device(mut self, device: Device) -> Self {
+            self.device = device;
+            self
+        }
     }
 
     impl crate::backend::input::Event<Backend> for KeyEvent {
@@ -387,7 +419,7 @@ mod input {
         }
 
         fn device(&self) -> <Backend as input::InputBackend>::Device {
-            Device
+            self.device.clone()
         }
     }
 
@@ -401,6 +433,7 @@ mod input {
                 time: Instant::now(),
                 code,
                 kind,
+                device: Device::default(),
             };
             tracing::trace!(
                 "key event: code {:?}, state {:?}, count {:?}",
@@ -435,22 +468,57 @@ mod input {
     pub struct MouseEvent {
         time: Instant,
         event: crossterm::event::MouseEvent,
+        position: Point<f64, crate::utils::Physical>,
         window_size: Size<i32, crate::utils::Physical>,
+        device: Device,
     }
 
     impl MouseEvent {
-        /// TODO: doc
+        /// Create a mouse event for an output of `window_size`
+        ///
+        /// The event is positioned at the center of the cell it was reported for.
         pub fn new(
-            mut event: crossterm::event::MouseEvent,
+            event: crossterm::event::MouseEvent,
+            window_size: Size<i32, crate::utils::Physical>,
+        ) -> Self {
+            let terminal_size = crossterm::terminal::size()
+                .map(|(columns, rows)| ratatui::layout::Size::new(columns, rows))
+                .unwrap_or_default();
+            Self::with_terminal_size(event, window_size, terminal_size)
+        }
+
+        /// Create a mouse event for an output of `window_size` on a terminal of `terminal_size` cells
+        ///
+        /// Used for terminals other than the one of the process,
+        /// see [`RatatuiRenderer::terminal_size`](crate::backend::renderer::ratatui::RatatuiRenderer::terminal_size).
+        pub fn with_terminal_size(
+            event: crossterm::event::MouseEvent,
             window_size: Size<i32, crate::utils::Physical>,
+            terminal_size: ratatui::layout::Size,
         ) -> Self {
-            event.row *= 2;
+            let (columns, rows) = if terminal_size.width > 0 && terminal_size.height > 0 {
+                (terminal_size.width, terminal_size.height)
+            } else {
+                (window_size.w as u16, (window_size.h / 2) as u16)
+            };
+            let position = Point::new(
+                (event.column as f64 + 0.5) * window_size.w as f64 / columns as f64,
+                (event.row as f64 + 0.5) * window_size.h as f64 / rows as f64,
+            );
             Self {
                 time: Instant::now(),
                 event,
+                position,
                 window_size,
+                device: Device::default(),
             }
         }
+
+        /// Attribute the event to `device` instead of the terminal of the process
+        pub fn with_device(mut self, device: Device) -> Self {
+            self.device = device;
+            self
+        }
     }
 
     impl input::Event<Backend> for MouseEvent {
@@ -459,7 +527,7 @@ mod input {
         }
 
         fn device(&self) -> <Backend as crate::backend::input::InputBackend>::Device {
-            Device
+            self.device.clone()
         }
     }
 
@@ -483,11 +551,11 @@ mod input {
 
     impl input::AbsolutePositionEvent<Backend> for MouseEvent {
         fn x(&self) -> f64 {
-            self.event.column as _
+            self.position.x
         }
 
         fn y(&self) -> f64 {
-            self.event.row as _
+            self.position.y
         }
 
         fn x_transformed(&self, width: i32) -> f64 {
@@ -533,5 +601,6 @@ mod input {
 }
 
 pub use input::Backend as RatatuiInputBackend;
+pub use input::Device as RatatuiInputDevice;
 pub use input::KeyEvent as RatatuiKeyEvent;
 pub use input::MouseEvent as RatatuiMouseEvent;
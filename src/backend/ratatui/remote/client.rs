@@ -0,0 +1,156 @@
+//! The terminal side of a remote session
+
+use std::{
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crossterm::{
+    event::{
+        DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode,
+        KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+        PushKeyboardEnhancementFlags,
+    },
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use ratatui::{backend::WindowSize, layout::Size};
+
+use super::protocol::{ClientMessage, PROTOCOL_VERSION};
+use crate::backend::ratatui::{
+    keyboard::{Key, Keyboard},
+    TerminalCapabilities,
+};
+
+/// How often to check whether the compositor closed the connection
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Attach the terminal of the process to `session` of the compositor listening on `path`
+///
+/// Blocks until the terminal detaches, by pressing Ctrl+Alt+D, or the compositor closes
+/// the connection. The terminal is put into raw mode and the alternate screen while attached.
+pub fn attach(path: impl AsRef<Path>, session: &str) -> io::Result<()> {
+    let mut stream = UnixStream::connect(path)?;
+    let _modes = TerminalModes::enter()?;
+
+    let capabilities = TerminalCapabilities::detect();
+    stream.write_all(
+        &ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            session: session.to_owned(),
+            size: window_size()?,
+            capabilities: capabilities.clone(),
+        }
+        .encode(),
+    )?;
+
+    let closed = Arc::new(AtomicBool::new(false));
+    let output = std::thread::spawn({
+        let mut stream = stream.try_clone()?;
+        let closed = closed.clone();
+        move || {
+            let mut stdout = io::stdout();
+            let mut buf = vec![0u8; 64 * 1024];
+            while let Ok(n @ 1..) = stream.read(&mut buf) {
+                if stdout.write_all(&buf[..n]).and_then(|_| stdout.flush()).is_err() {
+                    break;
+                }
+            }
+            closed.store(true, Ordering::Release);
+        }
+    });
+
+    let result = forward_input(
+        &mut stream,
+        &closed,
+        Keyboard::new(capabilities.keyboard_enhancement),
+    );
+    let _ = stream.shutdown(Shutdown::Both);
+    let _ = output.join();
+    result
+}
+
+fn forward_input(stream: &mut UnixStream, closed: &AtomicBool, mut keyboard: Keyboard) -> io::Result<()> {
+    while !closed.load(Ordering::Acquire) {
+        if !crossterm::event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+        let message = match crossterm::event::read()? {
+            Event::Key(event) if is_detach_key(&event) => break,
+            Event::Key(event) => {
+                for key in keyboard.translate(event) {
+                    let message = match key {
+                        Key::Code(code, kind) => ClientMessage::Key { code, kind },
+                        Key::Keysym(keysym, kind) => ClientMessage::Keysym { keysym, kind },
+                    };
+                    stream.write_all(&message.encode())?;
+                }
+                continue;
+            }
+            Event::Mouse(event) => ClientMessage::Mouse(event),
+            Event::Paste(text) => ClientMessage::Paste(text),
+            Event::Resize(..) => ClientMessage::Resize(window_size()?),
+            _ => continue,
+        };
+        stream.write_all(&message.encode())?;
+    }
+    Ok(())
+}
+
+fn is_detach_key(event: &KeyEvent) -> bool {
+    event.kind == KeyEventKind::Press
+        && event
+            .modifiers
+            .contains(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        && matches!(event.code, KeyCode::Char('d' | 'D'))
+}
+
+fn window_size() -> io::Result<WindowSize> {
+    let (columns, rows) = crossterm::terminal::size()?;
+    // not every terminal reports its size in pixels
+    let pixels = crossterm::terminal::window_size()
+        .map(|size| Size::new(size.width, size.height))
+        .unwrap_or_default();
+    Ok(WindowSize {
+        columns_rows: Size::new(columns, rows),
+        pixels,
+    })
+}
+
+/// Terminal modes required while attached, restored on drop
+struct TerminalModes;
+
+impl TerminalModes {
+    fn enter() -> io::Result<TerminalModes> {
+        crossterm::terminal::enable_raw_mode()?;
+        let modes = TerminalModes;
+        io::stdout()
+            .execute(EnterAlternateScreen)?
+            .execute(EnableMouseCapture)?
+            .execute(EnableBracketedPaste)?
+            .execute(PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                    | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES,
+            ))?;
+        Ok(modes)
+    }
+}
+
+impl Drop for TerminalModes {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.execute(PopKeyboardEnhancementFlags);
+        let _ = stdout.execute(DisableBracketedPaste);
+        let _ = stdout.execute(DisableMouseCapture);
+        let _ = stdout.execute(crossterm::cursor::Show);
+        let _ = stdout.execute(LeaveAlternateScreen);
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,762 @@
+//! Terminals attached to the compositor through a Unix socket
+//!
+//! A [`RatatuiListener`] accepts connections of terminals running [`attach`], for example in
+//! a small command line tool started over SSH. Every attached terminal is reported as a
+//! [`RemoteTerminal`], providing its own [`RatatuiRenderer`], an event source for its input and
+//! an input device, so each terminal can be presented as a separate output.
+//!
+//! Terminals name the session they attach to. Detaching, either explicitly by pressing
+//! Ctrl+Alt+D or by closing the connection, emits [`RatatuiEvent::Detached`]. Compositors
+//! wanting tmux-like behaviour keep the output of a session around and reuse it, when a
+//! terminal attaches to the same session again.
+//!
+//! ```no_run
+//! use smithay::backend::ratatui::remote::RatatuiListener;
+//!
+//! # let event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+//! let listener = RatatuiListener::bind("/tmp/compositor-terminal").unwrap();
+//! event_loop.handle().insert_source(listener, |terminal, _, _| {
+//!     let session = terminal.session().to_owned();
+//!     let (renderer, events) = terminal.into_parts();
+//!     // create or look up the output of `session` and render to it using `renderer`
+//! #   let _ = (session, renderer, events);
+//! }).unwrap();
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    generic::Generic,
+    timer::{TimeoutAction, Timer},
+    EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use ratatui::backend::WindowSize;
+use tracing::{debug, info, warn};
+
+use super::{RatatuiEvent, RatatuiInputDevice, TerminalCapabilities};
+use crate::backend::renderer::ratatui::RatatuiRenderer;
+
+mod client;
+mod protocol;
+
+pub use client::attach;
+use protocol::{message_len, ClientMessage, PROTOCOL_VERSION};
+
+/// How long a connecting terminal may take to introduce itself
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Largest accepted hello message, the session name is its only variable part
+const MAX_HELLO_SIZE: usize = 64 * 1024;
+/// How much output may queue up for a terminal, before it is considered unresponsive
+const MAX_PENDING_OUTPUT: usize = 16 << 20;
+
+/// Identifiers of attached terminals, `0` is used by the terminal of the process
+static NEXT_TERMINAL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// An event source accepting terminals on a Unix socket
+///
+/// Connecting terminals are introduced without blocking the event loop, terminals not
+/// completing the handshake within a short timeout are dropped. The socket file is removed,
+/// when the listener is dropped.
+#[derive(Debug)]
+pub struct RatatuiListener {
+    socket: Generic<UnixListener>,
+    path: PathBuf,
+    handshakes: Vec<Handshake>,
+    /// Fires at the deadline of the oldest handshake, while there are any
+    timer: Timer,
+    timer_armed: bool,
+}
+
+impl RatatuiListener {
+    /// Listen for terminals on a new socket at `path`
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<RatatuiListener> {
+        let path = path.as_ref().to_owned();
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        info!(?path, "Listening for terminals");
+        Ok(RatatuiListener {
+            socket: Generic::new(listener, Interest::READ, Mode::Level),
+            path,
+            handshakes: Vec::new(),
+            timer: Timer::immediate(),
+            timer_armed: false,
+        })
+    }
+
+    /// Path of the socket
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.handshakes.iter().map(|handshake| handshake.deadline).min()
+    }
+}
+
+impl Drop for RatatuiListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl EventSource for RatatuiListener {
+    /// A newly attached terminal
+    type Event = RemoteTerminal;
+    type Metadata = ();
+    type Ret = ();
+    type Error = io::Error;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let RatatuiListener {
+            socket,
+            handshakes,
+            timer,
+            timer_armed,
+            ..
+        } = self;
+
+        // new connections are registered, once the event loop reregisters the listener
+        let mut reregister = false;
+        socket.process_events(readiness, token, |_, listener| {
+            loop {
+                let stream = match listener.accept() {
+                    Ok((stream, _)) => stream,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                };
+                match stream.set_nonblocking(true) {
+                    Ok(()) => {
+                        handshakes.push(Handshake {
+                            stream: Generic::new(stream, Interest::READ, Mode::Level),
+                            buffer: Vec::new(),
+                            deadline: Instant::now() + HANDSHAKE_TIMEOUT,
+                            registered: false,
+                        });
+                        reregister = true;
+                    }
+                    Err(err) => warn!(?err, "Failed to attach terminal"),
+                }
+            }
+            Ok(PostAction::Continue)
+        })?;
+
+        timer.process_events(readiness, token, |now, _| {
+            handshakes.retain(|handshake| {
+                let expired = handshake.deadline <= now;
+                if expired {
+                    warn!("Terminal did not complete the handshake in time");
+                }
+                !expired
+            });
+            match handshakes.iter().map(|handshake| handshake.deadline).min() {
+                Some(deadline) => TimeoutAction::ToInstant(deadline),
+                None => {
+                    *timer_armed = false;
+                    TimeoutAction::Drop
+                }
+            }
+        })?;
+
+        let mut index = 0;
+        while index < handshakes.len() {
+            let hello = match handshakes[index].process(readiness, token) {
+                Ok(None) => {
+                    index += 1;
+                    continue;
+                }
+                Ok(Some(hello)) => hello,
+                Err(err) => {
+                    handshakes.swap_remove(index);
+                    warn!(?err, "Failed to attach terminal");
+                    continue;
+                }
+            };
+            let stream = handshakes.swap_remove(index).stream.unwrap();
+            match RemoteTerminal::new(stream, hello) {
+                Ok(terminal) => {
+                    debug!(session = terminal.session(), device = ?terminal.device(), "Terminal attached");
+                    callback(terminal, &mut ());
+                }
+                Err(err) => warn!(?err, "Failed to attach terminal"),
+            }
+        }
+
+        if !handshakes.is_empty() && !*timer_armed {
+            reregister = true;
+        }
+        Ok(if reregister {
+            PostAction::Reregister
+        } else {
+            PostAction::Continue
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.socket.register(poll, token_factory)?;
+        for handshake in &mut self.handshakes {
+            handshake.stream.register(poll, token_factory)?;
+            handshake.registered = true;
+        }
+        if let Some(deadline) = self.next_deadline() {
+            self.timer.set_deadline(deadline);
+            self.timer.register(poll, token_factory)?;
+            self.timer_armed = true;
+        }
+        Ok(())
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.socket.reregister(poll, token_factory)?;
+        for handshake in &mut self.handshakes {
+            if handshake.registered {
+                handshake.stream.reregister(poll, token_factory)?;
+            } else {
+                handshake.stream.register(poll, token_factory)?;
+                handshake.registered = true;
+            }
+        }
+        self.timer.unregister(poll)?;
+        self.timer_armed = false;
+        if let Some(deadline) = self.next_deadline() {
+            self.timer.set_deadline(deadline);
+            self.timer.register(poll, token_factory)?;
+            self.timer_armed = true;
+        }
+        Ok(())
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.socket.unregister(poll)?;
+        for handshake in &mut self.handshakes {
+            if handshake.registered {
+                handshake.stream.unregister(poll)?;
+                handshake.registered = false;
+            }
+        }
+        self.timer.unregister(poll)?;
+        self.timer_armed = false;
+        Ok(())
+    }
+}
+
+/// A connected terminal, which did not introduce itself yet
+#[derive(Debug)]
+struct Handshake {
+    stream: Generic<UnixStream>,
+    buffer: Vec<u8>,
+    deadline: Instant,
+    registered: bool,
+}
+
+impl Handshake {
+    /// Reads what the terminal sent so far, returning its hello once complete
+    fn process(&mut self, readiness: Readiness, token: Token) -> io::Result<Option<Hello>> {
+        let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidData, err);
+
+        let Handshake { stream, buffer, .. } = self;
+        stream.process_events(readiness, token, |_, stream| {
+            // only the hello is read, anything sent after it is left to the terminal's event source
+            let mut buf = [0u8; 4096];
+            loop {
+                let missing = match message_len(buffer).map_err(|err| invalid(err.to_string()))? {
+                    None => 4 - buffer.len(),
+                    Some(len) if len > MAX_HELLO_SIZE => {
+                        return Err(invalid(format!("hello of {len} bytes exceeds the size limit")));
+                    }
+                    Some(len) => 4 + len - buffer.len(),
+                };
+                if missing == 0 {
+                    break;
+                }
+                match (&**stream).read(&mut buf[..missing.min(4096)]) {
+                    Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+                    Ok(n) => buffer.extend_from_slice(&buf[..n]),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(PostAction::Continue)
+        })?;
+
+        let Some((message, _)) = ClientMessage::decode(buffer).map_err(|err| invalid(err.to_string()))?
+        else {
+            return Ok(None);
+        };
+        let ClientMessage::Hello {
+            version,
+            session,
+            size,
+            capabilities,
+        } = message
+        else {
+            return Err(invalid("expected hello message".into()));
+        };
+        if version != PROTOCOL_VERSION {
+            return Err(invalid(format!("unsupported protocol version {version}")));
+        }
+        Ok(Some(Hello {
+            session,
+            size,
+            capabilities,
+        }))
+    }
+}
+
+/// Contents of the hello of a terminal
+#[derive(Debug)]
+struct Hello {
+    session: String,
+    size: WindowSize,
+    capabilities: TerminalCapabilities,
+}
+
+/// A terminal attached through a [`RatatuiListener`]
+#[derive(Debug)]
+pub struct RemoteTerminal {
+    session: String,
+    capabilities: TerminalCapabilities,
+    renderer: RatatuiRenderer,
+    events: RemoteTerminalSource,
+}
+
+impl RemoteTerminal {
+    fn new(stream: UnixStream, hello: Hello) -> io::Result<RemoteTerminal> {
+        let Hello {
+            session,
+            size,
+            capabilities,
+        } = hello;
+        let size = Arc::new(Mutex::new(size));
+        let output = RemoteOutput(Arc::new(Mutex::new(OutputState {
+            stream: stream.try_clone()?,
+            pending: Vec::new(),
+            disconnected: false,
+        })));
+        let mut renderer = RatatuiRenderer::remote(Box::new(output.clone()), size.clone())?;
+        renderer.set_color_mode(capabilities.color_mode);
+
+        let id = NEXT_TERMINAL_ID.fetch_add(1, Ordering::Relaxed);
+        Ok(RemoteTerminal {
+            session,
+            capabilities,
+            renderer,
+            events: RemoteTerminalSource {
+                writable: Generic::new(stream.try_clone()?, Interest::WRITE, Mode::Edge),
+                stream: Generic::new(stream, Interest::READ, Mode::Level),
+                buffer: Vec::new(),
+                output,
+                size,
+                device: RatatuiInputDevice::remote(id),
+            },
+        })
+    }
+
+    /// Name of the session the terminal attached to
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// Capabilities detected by the terminal
+    pub fn capabilities(&self) -> &TerminalCapabilities {
+        &self.capabilities
+    }
+
+    /// Input device of the terminal
+    pub fn device(&self) -> RatatuiInputDevice {
+        self.events.device.clone()
+    }
+
+    /// Get a mutable reference to the renderer drawing to the terminal
+    pub fn renderer(&mut self) -> &mut RatatuiRenderer {
+        &mut self.renderer
+    }
+
+    /// Split the terminal into its renderer and the event source for its input
+    pub fn into_parts(self) -> (RatatuiRenderer, RemoteTerminalSource) {
+        (self.renderer, self.events)
+    }
+}
+
+#[derive(Debug)]
+struct OutputState {
+    stream: UnixStream,
+    /// Output the socket did not accept yet
+    pending: Vec<u8>,
+    disconnected: bool,
+}
+
+impl OutputState {
+    /// Writes as much of the pending output as the socket accepts without blocking
+    fn drain(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.pending.len() {
+                break Ok(());
+            }
+            match (&self.stream).write(&self.pending[written..]) {
+                Ok(0) => break Err(io::Error::from(io::ErrorKind::WriteZero)),
+                Ok(n) => written += n,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => break Err(err),
+            }
+        };
+        self.pending.drain(..written);
+        if result.is_err() {
+            self.disconnect();
+        }
+        result
+    }
+
+    /// Closes the connection, the event source of the terminal reports it as detached
+    fn disconnect(&mut self) {
+        self.disconnected = true;
+        self.pending = Vec::new();
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Output of a remote terminal, written without blocking
+///
+/// Output not accepted by the socket right away is queued and written, once the terminal reads
+/// again. Terminals falling behind by more than [`MAX_PENDING_OUTPUT`] are disconnected.
+#[derive(Debug, Clone)]
+struct RemoteOutput(Arc<Mutex<OutputState>>);
+
+impl Write for RemoteOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.0.lock().unwrap();
+        if state.disconnected {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        if state.pending.len() + buf.len() > MAX_PENDING_OUTPUT {
+            warn!("Terminal does not read its output, disconnecting");
+            state.disconnect();
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "terminal does not read its output",
+            ));
+        }
+        state.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.0.lock().unwrap();
+        if state.disconnected {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        state.drain()
+    }
+}
+
+/// Event source for the input of a [`RemoteTerminal`]
+///
+/// Also writes output of the terminal, that could not be written without blocking before.
+/// Emits [`RatatuiEvent::Detached`] and removes itself, once the terminal detached.
+#[derive(Debug)]
+pub struct RemoteTerminalSource {
+    stream: Generic<UnixStream>,
+    /// Notifies once the socket accepts output again
+    writable: Generic<UnixStream>,
+    buffer: Vec<u8>,
+    /// Shared with the renderer of the terminal
+    output: RemoteOutput,
+    /// Shared with the renderer of the terminal
+    size: Arc<Mutex<WindowSize>>,
+    device: RatatuiInputDevice,
+}
+
+impl RemoteTerminalSource {
+    /// Input device of the terminal
+    pub fn device(&self) -> RatatuiInputDevice {
+        self.device.clone()
+    }
+}
+
+impl EventSource for RemoteTerminalSource {
+    type Event = RatatuiEvent;
+    type Metadata = ();
+    type Ret = ();
+    type Error = io::Error;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let RemoteTerminalSource {
+            stream,
+            writable,
+            buffer,
+            output,
+            size,
+            ..
+        } = self;
+
+        writable.process_events(readiness, token, |_, _| {
+            // a failure disconnects the terminal, which is noticed when reading
+            if let Err(err) = output.0.lock().unwrap().drain() {
+                debug!(?err, "Failed to write to terminal");
+            }
+            Ok(PostAction::Continue)
+        })?;
+
+        stream.process_events(readiness, token, |readiness, stream| {
+            let mut buf = [0u8; 4096];
+            let n = if readiness.error {
+                0
+            } else {
+                match (&**stream).read(&mut buf) {
+                    Ok(n) => n,
+                    Err(err)
+                        if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) =>
+                    {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) => {
+                        debug!(?err, "Failed to read from terminal");
+                        0
+                    }
+                }
+            };
+            if n == 0 {
+                callback(RatatuiEvent::Detached, &mut ());
+                return Ok(PostAction::Remove);
+            }
+            buffer.extend_from_slice(&buf[..n]);
+
+            let mut consumed = 0;
+            loop {
+                let message = match ClientMessage::decode(&buffer[consumed..]) {
+                    Ok(Some((message, len))) => {
+                        consumed += len;
+                        message
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        warn!(?err, "Invalid message from terminal");
+                        callback(RatatuiEvent::Detached, &mut ());
+                        return Ok(PostAction::Remove);
+                    }
+                };
+                let event = match message {
+                    ClientMessage::Resize(new_size) => {
+                        *size.lock().unwrap() = new_size;
+                        let columns_rows = new_size.columns_rows;
+                        RatatuiEvent::Resize(columns_rows.width, columns_rows.height)
+                    }
+                    ClientMessage::Key { code, kind } => RatatuiEvent::Key { code, kind },
+                    ClientMessage::Keysym { keysym, kind } => RatatuiEvent::Keysym {
+                        keysym: keysym.into(),
+                        kind,
+                    },
+                    ClientMessage::Mouse(event) => RatatuiEvent::Mouse(event),
+                    ClientMessage::Paste(text) => RatatuiEvent::Paste(text),
+                    ClientMessage::Hello { .. } => continue,
+                };
+                callback(event, &mut ());
+            }
+            buffer.drain(..consumed);
+            Ok(PostAction::Continue)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.stream.register(poll, token_factory)?;
+        self.writable.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.stream.reregister(poll, token_factory)?;
+        self.writable.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.stream.unregister(poll)?;
+        self.writable.unregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crossterm::event::KeyEventKind;
+    use ratatui::layout::Size;
+
+    use super::*;
+
+    fn hello(session: &str) -> Vec<u8> {
+        ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            session: session.into(),
+            size: WindowSize {
+                columns_rows: Size::new(80, 24),
+                pixels: Size::new(0, 0),
+            },
+            capabilities: TerminalCapabilities::default(),
+        }
+        .encode()
+    }
+
+    /// Event loop with a listener on a new socket, collecting the attached terminals
+    fn listen(name: &str) -> (calloop::EventLoop<'static, Vec<RemoteTerminal>>, PathBuf) {
+        let path = std::env::temp_dir().join(format!("smithay-ratatui-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let listener = RatatuiListener::bind(&path).unwrap();
+        let event_loop = calloop::EventLoop::<Vec<RemoteTerminal>>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(listener, |terminal, _, terminals| terminals.push(terminal))
+            .unwrap();
+        (event_loop, path)
+    }
+
+    fn dispatch_until<T>(
+        event_loop: &mut calloop::EventLoop<'static, T>,
+        data: &mut T,
+        mut done: impl FnMut(&mut T) -> bool,
+    ) {
+        let start = Instant::now();
+        while !done(data) {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+            event_loop
+                .dispatch(Some(Duration::from_millis(100)), data)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn attach_and_detach() {
+        let (mut event_loop, path) = listen("attach");
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(&hello("main")).unwrap();
+        let resized = WindowSize {
+            columns_rows: Size::new(100, 30),
+            pixels: Size::new(0, 0),
+        };
+        // sent right after the hello, before the terminal is attached
+        client
+            .write_all(&ClientMessage::Resize(resized).encode())
+            .unwrap();
+
+        let mut terminals = Vec::new();
+        dispatch_until(&mut event_loop, &mut terminals, |terminals| !terminals.is_empty());
+        let terminal = terminals.pop().unwrap();
+        assert_eq!(terminal.session(), "main");
+        let (renderer, events) = terminal.into_parts();
+        assert_eq!(renderer.terminal_size(), Size::new(80, 24));
+
+        client
+            .write_all(
+                &ClientMessage::Key {
+                    code: 38,
+                    kind: KeyEventKind::Press,
+                }
+                .encode(),
+            )
+            .unwrap();
+        client.shutdown(std::net::Shutdown::Write).unwrap();
+
+        let mut event_loop = calloop::EventLoop::<Vec<RatatuiEvent>>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(events, |event, _, received| received.push(event))
+            .unwrap();
+        let mut received = Vec::new();
+        dispatch_until(&mut event_loop, &mut received, |received| {
+            matches!(received.last(), Some(RatatuiEvent::Detached))
+        });
+
+        assert!(matches!(received[0], RatatuiEvent::Resize(100, 30)));
+        assert!(matches!(received[1], RatatuiEvent::Key { code: 38, .. }));
+        assert_eq!(received.len(), 3);
+        assert_eq!(renderer.terminal_size(), Size::new(100, 30));
+    }
+
+    #[test]
+    fn slow_handshake() {
+        let (mut event_loop, path) = listen("slow");
+        let mut stalled = UnixStream::connect(&path).unwrap();
+        let hello_stalled = hello("stalled");
+        stalled.write_all(&hello_stalled[..6]).unwrap();
+
+        // the stalled terminal does not hold up others
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(&hello("main")).unwrap();
+        let mut terminals = Vec::new();
+        dispatch_until(&mut event_loop, &mut terminals, |terminals| !terminals.is_empty());
+        assert_eq!(terminals[0].session(), "main");
+
+        // and is dropped after the timeout
+        stalled.set_nonblocking(true).unwrap();
+        let mut buf = [0u8; 1];
+        let start = Instant::now();
+        while !matches!(stalled.read(&mut buf), Ok(0)) {
+            assert!(
+                start.elapsed() < HANDSHAKE_TIMEOUT * 2,
+                "handshake did not time out"
+            );
+            event_loop
+                .dispatch(Some(Duration::from_millis(100)), &mut terminals)
+                .unwrap();
+        }
+        assert_eq!(terminals.len(), 1);
+    }
+
+    #[test]
+    fn unresponsive_terminal() {
+        let (mut event_loop, path) = listen("unresponsive");
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(&hello("main")).unwrap();
+        let mut terminals = Vec::new();
+        dispatch_until(&mut event_loop, &mut terminals, |terminals| !terminals.is_empty());
+        let (_renderer, events) = terminals.pop().unwrap().into_parts();
+
+        // the client never reads, writes must not block and eventually give up
+        let mut output = events.output.clone();
+        let chunk = vec![b'x'; 64 * 1024];
+        let start = Instant::now();
+        let err = loop {
+            if let Err(err) = output.write_all(&chunk).and_then(|_| output.flush()) {
+                break err;
+            }
+        };
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        let mut event_loop = calloop::EventLoop::<Vec<RatatuiEvent>>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(events, |event, _, received| received.push(event))
+            .unwrap();
+        let mut received = Vec::new();
+        dispatch_until(&mut event_loop, &mut received, |received| {
+            matches!(received.last(), Some(RatatuiEvent::Detached))
+        });
+    }
+}
@@ -0,0 +1,378 @@
+//! Wire format of the connection between an attached terminal and the compositor
+//!
+//! The attached terminal sends messages prefixed with their length as `u32`, all integers are
+//! little endian. The compositor sends the output for the terminal unframed.
+
+use crossterm::event::{KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::{backend::WindowSize, layout::Size};
+
+use crate::backend::ratatui::{ColorMode, GraphicsProtocol, TerminalCapabilities};
+
+/// Version of the protocol, a mismatch is rejected on attach
+pub(super) const PROTOCOL_VERSION: u8 = 2;
+/// Largest accepted message, bounds the size of pasted text
+pub(super) const MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+const HELLO: u8 = 0;
+const RESIZE: u8 = 1;
+const KEY: u8 = 2;
+const MOUSE: u8 = 3;
+const PASTE: u8 = 4;
+const KEYSYM: u8 = 5;
+
+/// Messages sent by an attached terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum ClientMessage {
+    /// First message on every connection
+    Hello {
+        version: u8,
+        session: String,
+        size: WindowSize,
+        capabilities: TerminalCapabilities,
+    },
+    Resize(WindowSize),
+    /// Already translated to an xkb keycode
+    Key {
+        code: u32,
+        kind: KeyEventKind,
+    },
+    /// Character without a key on the US layout, as xkb keysym
+    Keysym {
+        keysym: u32,
+        kind: KeyEventKind,
+    },
+    Mouse(MouseEvent),
+    Paste(String),
+}
+
+/// Errors decoding a [`ClientMessage`]
+#[derive(Debug, thiserror::Error)]
+pub(super) enum ProtocolError {
+    #[error("Unknown message type {0}")]
+    UnknownMessage(u8),
+    #[error("Malformed message")]
+    Malformed,
+    #[error("Message of {0} bytes exceeds the size limit")]
+    TooLarge(usize),
+}
+
+impl ClientMessage {
+    /// Encode the message including its length prefix
+    pub(super) fn encode(&self) -> Vec<u8> {
+        let mut out = vec![0; 4];
+        match self {
+            ClientMessage::Hello {
+                version,
+                session,
+                size,
+                capabilities,
+            } => {
+                out.push(HELLO);
+                out.push(*version);
+                put_bytes(&mut out, session.as_bytes());
+                put_window_size(&mut out, size);
+                out.push(match capabilities.color_mode {
+                    ColorMode::TrueColor => 0,
+                    ColorMode::Indexed256 => 1,
+                    ColorMode::Indexed16 => 2,
+                    ColorMode::Monochrome => 3,
+                });
+                out.push(match capabilities.graphics_protocol {
+                    None => 0,
+                    Some(GraphicsProtocol::Sixel) => 1,
+                    Some(GraphicsProtocol::Kitty) => 2,
+                });
+                out.push(u8::from(capabilities.keyboard_enhancement));
+                let (terminal, version) = capabilities.terminal_id.unwrap_or((u32::MAX, 0));
+                out.extend_from_slice(&terminal.to_le_bytes());
+                out.extend_from_slice(&version.to_le_bytes());
+            }
+            ClientMessage::Resize(size) => {
+                out.push(RESIZE);
+                put_window_size(&mut out, size);
+            }
+            ClientMessage::Key { code, kind } => {
+                out.push(KEY);
+                out.extend_from_slice(&code.to_le_bytes());
+                put_key_kind(&mut out, *kind);
+            }
+            ClientMessage::Keysym { keysym, kind } => {
+                out.push(KEYSYM);
+                out.extend_from_slice(&keysym.to_le_bytes());
+                put_key_kind(&mut out, *kind);
+            }
+            ClientMessage::Mouse(event) => {
+                out.push(MOUSE);
+                let (kind, button) = match event.kind {
+                    MouseEventKind::Down(button) => (0, button),
+                    MouseEventKind::Up(button) => (1, button),
+                    MouseEventKind::Drag(button) => (2, button),
+                    MouseEventKind::Moved => (3, MouseButton::Left),
+                    MouseEventKind::ScrollDown => (4, MouseButton::Left),
+                    MouseEventKind::ScrollUp => (5, MouseButton::Left),
+                    MouseEventKind::ScrollLeft => (6, MouseButton::Left),
+                    MouseEventKind::ScrollRight => (7, MouseButton::Left),
+                };
+                out.push(kind);
+                out.push(match button {
+                    MouseButton::Left => 0,
+                    MouseButton::Right => 1,
+                    MouseButton::Middle => 2,
+                });
+                out.extend_from_slice(&event.column.to_le_bytes());
+                out.extend_from_slice(&event.row.to_le_bytes());
+                out.push(event.modifiers.bits());
+            }
+            ClientMessage::Paste(text) => {
+                out.push(PASTE);
+                put_bytes(&mut out, text.as_bytes());
+            }
+        }
+        let len = (out.len() - 4) as u32;
+        out[..4].copy_from_slice(&len.to_le_bytes());
+        out
+    }
+
+    /// Decode the first message of `buf`
+    ///
+    /// Returns the message and the number of bytes it occupied, or `None` if `buf` does not
+    /// contain a complete message yet.
+    pub(super) fn decode(buf: &[u8]) -> Result<Option<(ClientMessage, usize)>, ProtocolError> {
+        let Some(len) = message_len(buf)? else {
+            return Ok(None);
+        };
+        let Some(payload) = buf.get(4..4 + len) else {
+            return Ok(None);
+        };
+        let message = ClientMessage::decode_payload(payload).ok_or(ProtocolError::Malformed)??;
+        Ok(Some((message, 4 + len)))
+    }
+
+    /// Decode a message without its length prefix
+    pub(super) fn decode_payload(payload: &[u8]) -> Option<Result<ClientMessage, ProtocolError>> {
+        let mut reader = Reader(payload);
+        let message = match reader.u8()? {
+            HELLO => ClientMessage::Hello {
+                version: reader.u8()?,
+                session: reader.string()?,
+                size: reader.window_size()?,
+                capabilities: TerminalCapabilities {
+                    color_mode: match reader.u8()? {
+                        0 => ColorMode::TrueColor,
+                        1 => ColorMode::Indexed256,
+                        2 => ColorMode::Indexed16,
+                        3 => ColorMode::Monochrome,
+                        _ => return None,
+                    },
+                    graphics_protocol: match reader.u8()? {
+                        0 => None,
+                        1 => Some(GraphicsProtocol::Sixel),
+                        2 => Some(GraphicsProtocol::Kitty),
+                        _ => return None,
+                    },
+                    keyboard_enhancement: reader.u8()? != 0,
+                    terminal_id: match (reader.u32()?, reader.u32()?) {
+                        (u32::MAX, _) => None,
+                        id => Some(id),
+                    },
+                },
+            },
+            RESIZE => ClientMessage::Resize(reader.window_size()?),
+            KEY => ClientMessage::Key {
+                code: reader.u32()?,
+                kind: reader.key_kind()?,
+            },
+            KEYSYM => ClientMessage::Keysym {
+                keysym: reader.u32()?,
+                kind: reader.key_kind()?,
+            },
+            MOUSE => {
+                let kind = reader.u8()?;
+                let button = match reader.u8()? {
+                    0 => MouseButton::Left,
+                    1 => MouseButton::Right,
+                    2 => MouseButton::Middle,
+                    _ => return None,
+                };
+                ClientMessage::Mouse(MouseEvent {
+                    kind: match kind {
+                        0 => MouseEventKind::Down(button),
+                        1 => MouseEventKind::Up(button),
+                        2 => MouseEventKind::Drag(button),
+                        3 => MouseEventKind::Moved,
+                        4 => MouseEventKind::ScrollDown,
+                        5 => MouseEventKind::ScrollUp,
+                        6 => MouseEventKind::ScrollLeft,
+                        7 => MouseEventKind::ScrollRight,
+                        _ => return None,
+                    },
+                    column: reader.u16()?,
+                    row: reader.u16()?,
+                    modifiers: KeyModifiers::from_bits_truncate(reader.u8()?),
+                })
+            }
+            PASTE => ClientMessage::Paste(reader.string()?),
+            ty => return Some(Err(ProtocolError::UnknownMessage(ty))),
+        };
+        if !reader.0.is_empty() {
+            return None;
+        }
+        Some(Ok(message))
+    }
+}
+
+/// Length of the first message of `buf`, if its prefix is complete
+pub(super) fn message_len(buf: &[u8]) -> Result<Option<usize>, ProtocolError> {
+    let Some(prefix) = buf.get(..4) else {
+        return Ok(None);
+    };
+    let len = u32::from_le_bytes(prefix.try_into().unwrap()) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(ProtocolError::TooLarge(len));
+    }
+    Ok(Some(len))
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn put_window_size(out: &mut Vec<u8>, size: &WindowSize) {
+    for value in [
+        size.columns_rows.width,
+        size.columns_rows.height,
+        size.pixels.width,
+        size.pixels.height,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn put_key_kind(out: &mut Vec<u8>, kind: KeyEventKind) {
+    out.push(match kind {
+        KeyEventKind::Press => 0,
+        KeyEventKind::Repeat => 1,
+        KeyEventKind::Release => 2,
+    });
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).ok()
+    }
+
+    fn key_kind(&mut self) -> Option<KeyEventKind> {
+        Some(match self.u8()? {
+            0 => KeyEventKind::Press,
+            1 => KeyEventKind::Repeat,
+            2 => KeyEventKind::Release,
+            _ => return None,
+        })
+    }
+
+    fn window_size(&mut self) -> Option<WindowSize> {
+        Some(WindowSize {
+            columns_rows: Size::new(self.u16()?, self.u16()?),
+            pixels: Size::new(self.u16()?, self.u16()?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let size = WindowSize {
+            columns_rows: Size::new(80, 24),
+            pixels: Size::new(800, 480),
+        };
+        let messages = [
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                session: "main".into(),
+                size,
+                capabilities: TerminalCapabilities {
+                    color_mode: ColorMode::Indexed16,
+                    graphics_protocol: Some(GraphicsProtocol::Kitty),
+                    terminal_id: Some((1, 7000)),
+                    keyboard_enhancement: true,
+                },
+            },
+            ClientMessage::Resize(size),
+            ClientMessage::Key {
+                code: 38,
+                kind: KeyEventKind::Release,
+            },
+            ClientMessage::Keysym {
+                keysym: 0xe9,
+                kind: KeyEventKind::Repeat,
+            },
+            ClientMessage::Mouse(MouseEvent {
+                kind: MouseEventKind::Drag(MouseButton::Middle),
+                column: 3,
+                row: 4,
+                modifiers: KeyModifiers::SHIFT | KeyModifiers::ALT,
+            }),
+            ClientMessage::Paste("pasted\ntext".into()),
+        ];
+
+        let buf = messages
+            .iter()
+            .flat_map(ClientMessage::encode)
+            .collect::<Vec<_>>();
+        let mut rest = &buf[..];
+        for message in messages {
+            let (decoded, len) = ClientMessage::decode(rest).unwrap().unwrap();
+            assert_eq!(decoded, message);
+            rest = &rest[len..];
+        }
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn incomplete_and_invalid() {
+        let buf = ClientMessage::Paste("text".into()).encode();
+        assert!(matches!(ClientMessage::decode(&buf[..3]), Ok(None)));
+        assert!(matches!(ClientMessage::decode(&buf[..buf.len() - 1]), Ok(None)));
+
+        assert!(matches!(
+            ClientMessage::decode(&[1, 0, 0, 0, 42]),
+            Err(ProtocolError::UnknownMessage(42))
+        ));
+        assert!(matches!(
+            ClientMessage::decode(&[2, 0, 0, 0, KEY, 0]),
+            Err(ProtocolError::Malformed)
+        ));
+        assert!(matches!(
+            ClientMessage::decode(&[0xff, 0xff, 0xff, 0xff]),
+            Err(ProtocolError::TooLarge(_))
+        ));
+    }
+}
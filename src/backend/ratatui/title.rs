@@ -0,0 +1,57 @@
+//! Window title and bell of the host terminal
+
+use crate::wayland::{
+    compositor,
+    shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData},
+};
+
+/// Longest title forwarded to the terminal, in characters
+pub const MAX_TITLE_LENGTH: usize = 256;
+
+/// Rings the bell of the terminal
+pub(crate) const BEL: &[u8] = b"\x07";
+/// Saves the window title of the terminal on its title stack (`CSI 22 ; 0 t`)
+pub(crate) const PUSH_TITLE: &[u8] = b"\x1b[22;0t";
+/// Restores the window title saved by [`PUSH_TITLE`] (`CSI 23 ; 0 t`)
+pub(crate) const POP_TITLE: &[u8] = b"\x1b[23;0t";
+
+/// Encode an OSC 0 sequence setting the icon name and window title (`OSC 0 ; Pt ST`)
+///
+/// Control characters are dropped, so clients cannot inject escape sequences through their title,
+/// and the title is truncated to [`MAX_TITLE_LENGTH`] characters.
+pub(crate) fn osc0(title: &str) -> Vec<u8> {
+    let mut out = b"\x1b]0;".to_vec();
+    let title = title
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_TITLE_LENGTH)
+        .collect::<String>();
+    out.extend_from_slice(title.as_bytes());
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+/// Returns the title to show in the terminal for `toplevel`
+///
+/// This is the title of the toplevel or, if it has none, its app id.
+pub fn toplevel_title(toplevel: &ToplevelSurface) -> Option<String> {
+    compositor::with_states(toplevel.wl_surface(), |states| {
+        let attributes = states.data_map.get::<XdgToplevelSurfaceData>()?.lock().unwrap();
+        attributes.title.clone().or_else(|| attributes.app_id.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc0_sequence() {
+        assert_eq!(osc0("vim - main.rs"), b"\x1b]0;vim - main.rs\x1b\\");
+        assert_eq!(
+            osc0("evil\x1b]52;c;Zm9v\x07title"),
+            b"\x1b]0;evil]52;c;Zm9vtitle\x1b\\"
+        );
+        assert_eq!(osc0(&"a".repeat(1000)).len(), MAX_TITLE_LENGTH + 6);
+    }
+}
@@ -1,3 +1,9 @@
+//! Color types and colorimetric descriptions used by renderers
+//!
+//! Besides [`Color32F`], this module contains a [`ColorDescription`] type describing the
+//! color space of some content, e.g. a client buffer using the
+//! [color management protocol](crate::wayland::color_management).
+
 use std::ops::Mul;
 
 /// A four-component color representing pre-multiplied RGBA color values
@@ -75,3 +81,476 @@ impl Mul<f32> for Color32F {
         Self::new(self.r() * rhs, self.g() * rhs, self.b() * rhs, self.a() * rhs)
     }
 }
+
+/// CIE 1931 xy chromaticity coordinates of a set of primaries and a white point
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Chromaticities {
+    /// Red primary
+    pub red: (f64, f64),
+    /// Green primary
+    pub green: (f64, f64),
+    /// Blue primary
+    pub blue: (f64, f64),
+    /// White point
+    pub white: (f64, f64),
+}
+
+const D65: (f64, f64) = (0.3127, 0.3290);
+const ILLUMINANT_C: (f64, f64) = (0.310, 0.316);
+
+/// Well-known sets of primaries
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NamedPrimaries {
+    /// Primaries of sRGB and BT.709
+    Srgb,
+    /// Primaries of BT.470 System M
+    PalM,
+    /// Primaries of BT.601 625-line systems
+    Pal,
+    /// Primaries of BT.601 525-line systems / SMPTE 170M
+    Ntsc,
+    /// Generic film using illuminant C
+    GenericFilm,
+    /// Primaries of BT.2020 and BT.2100
+    Bt2020,
+    /// CIE 1931 XYZ
+    Cie1931Xyz,
+    /// DCI-P3 using the DCI white point
+    DciP3,
+    /// Display P3 using the D65 white point
+    DisplayP3,
+    /// Adobe RGB (1998)
+    AdobeRgb,
+}
+
+impl NamedPrimaries {
+    /// Chromaticities of the primaries
+    pub fn chromaticities(&self) -> Chromaticities {
+        let (red, green, blue, white) = match self {
+            NamedPrimaries::Srgb => ((0.64, 0.33), (0.30, 0.60), (0.15, 0.06), D65),
+            NamedPrimaries::PalM => ((0.67, 0.33), (0.21, 0.71), (0.14, 0.08), ILLUMINANT_C),
+            NamedPrimaries::Pal => ((0.64, 0.33), (0.29, 0.60), (0.15, 0.06), D65),
+            NamedPrimaries::Ntsc => ((0.630, 0.340), (0.310, 0.595), (0.155, 0.070), D65),
+            NamedPrimaries::GenericFilm => ((0.681, 0.319), (0.243, 0.692), (0.145, 0.049), ILLUMINANT_C),
+            NamedPrimaries::Bt2020 => ((0.708, 0.292), (0.170, 0.797), (0.131, 0.046), D65),
+            NamedPrimaries::Cie1931Xyz => ((1.0, 0.0), (0.0, 1.0), (0.0, 0.0), (1.0 / 3.0, 1.0 / 3.0)),
+            NamedPrimaries::DciP3 => ((0.680, 0.320), (0.265, 0.690), (0.150, 0.060), (0.314, 0.351)),
+            NamedPrimaries::DisplayP3 => ((0.680, 0.320), (0.265, 0.690), (0.150, 0.060), D65),
+            NamedPrimaries::AdobeRgb => ((0.64, 0.33), (0.21, 0.71), (0.15, 0.06), D65),
+        };
+        Chromaticities {
+            red,
+            green,
+            blue,
+            white,
+        }
+    }
+}
+
+/// Primaries and white point of a color space
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Primaries {
+    /// Well-known primaries
+    Named(NamedPrimaries),
+    /// Arbitrary primaries
+    Custom(Chromaticities),
+}
+
+impl Primaries {
+    /// Chromaticities of the primaries
+    pub fn chromaticities(&self) -> Chromaticities {
+        match self {
+            Primaries::Named(named) => named.chromaticities(),
+            Primaries::Custom(chromaticities) => *chromaticities,
+        }
+    }
+}
+
+impl From<NamedPrimaries> for Primaries {
+    #[inline]
+    fn from(named: NamedPrimaries) -> Self {
+        Primaries::Named(named)
+    }
+}
+
+impl Chromaticities {
+    /// Row-major matrix converting linear RGB using these primaries to CIE 1931 XYZ
+    pub fn to_xyz(&self) -> [[f64; 3]; 3] {
+        let xyz = |(x, y): (f64, f64)| [x / y, 1.0, (1.0 - x - y) / y];
+        let [r, g, b] = [xyz(self.red), xyz(self.green), xyz(self.blue)];
+        let primaries = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+
+        // scale the primaries, so that RGB(1, 1, 1) maps to the white point
+        let scale = mat_mul_vec(&mat_invert(&primaries), xyz(self.white));
+        primaries.map(|row| [row[0] * scale[0], row[1] * scale[1], row[2] * scale[2]])
+    }
+
+    /// Row-major matrix converting linear RGB using these primaries to linear RGB using `target`
+    ///
+    /// Differing white points are adapted using the Bradford transform.
+    pub fn conversion_matrix(&self, target: &Chromaticities) -> [[f64; 3]; 3] {
+        let to_xyz = self.to_xyz();
+        let from_xyz = mat_invert(&target.to_xyz());
+        if self.white == target.white {
+            return mat_mul(&from_xyz, &to_xyz);
+        }
+
+        const BRADFORD: [[f64; 3]; 3] = [
+            [0.8951, 0.2664, -0.1614],
+            [-0.7502, 1.7135, 0.0367],
+            [0.0389, -0.0685, 1.0296],
+        ];
+        let xyz = |(x, y): (f64, f64)| [x / y, 1.0, (1.0 - x - y) / y];
+        let src = mat_mul_vec(&BRADFORD, xyz(self.white));
+        let dst = mat_mul_vec(&BRADFORD, xyz(target.white));
+        let scale = [
+            [dst[0] / src[0], 0.0, 0.0],
+            [0.0, dst[1] / src[1], 0.0],
+            [0.0, 0.0, dst[2] / src[2]],
+        ];
+        let adaptation = mat_mul(&mat_invert(&BRADFORD), &mat_mul(&scale, &BRADFORD));
+
+        mat_mul(&from_xyz, &mat_mul(&adaptation, &to_xyz))
+    }
+}
+
+fn mat_mul(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn mat_mul_vec(a: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| (0..3).map(|k| a[i][k] * v[k]).sum())
+}
+
+fn mat_invert(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum::<f64>();
+    // the adjugate is the transpose of the cofactor matrix
+    std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / det))
+}
+
+/// Transfer characteristic of a color space
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TransferFunction {
+    /// BT.1886 display transfer characteristic
+    Bt1886,
+    /// Pure power curve with an exponent of 2.2
+    Gamma22,
+    /// Pure power curve with an exponent of 2.8
+    Gamma28,
+    /// SMPTE ST 240
+    St240,
+    /// Linear transfer characteristic, not clamped to `[0.0, 1.0]`
+    Linear,
+    /// Logarithmic transfer characteristic with a 100:1 range
+    Log100,
+    /// Logarithmic transfer characteristic with a 316.22777:1 range
+    Log316,
+    /// IEC 61966-2-4 (xvYCC)
+    Xvycc,
+    /// The piece-wise sRGB transfer characteristic (IEC 61966-2-1)
+    Srgb,
+    /// The piece-wise sRGB transfer characteristic, extended to negative values and values above 1.0
+    ExtSrgb,
+    /// SMPTE ST 2084 (perceptual quantizer)
+    Pq,
+    /// SMPTE ST 428-1
+    St428,
+    /// Hybrid log-gamma (ARIB STD-B67)
+    Hlg,
+    /// Pure power curve with the given exponent
+    Power(f64),
+}
+
+const PQ_M1: f64 = 0.1593017578125;
+const PQ_M2: f64 = 78.84375;
+const PQ_C1: f64 = 0.8359375;
+const PQ_C2: f64 = 18.8515625;
+const PQ_C3: f64 = 18.6875;
+
+const HLG_A: f64 = 0.17883277;
+const HLG_B: f64 = 0.28466892;
+const HLG_C: f64 = 0.55991073;
+
+impl TransferFunction {
+    /// Decode an encoded value to linear light
+    ///
+    /// The result is normalized to the nominal range of the transfer function,
+    /// see [`ColorDescription::unit_luminance`]. For [`TransferFunction::Hlg`] this
+    /// is the inverse OETF, no OOTF is applied.
+    pub fn eotf(&self, value: f64) -> f64 {
+        let sign = value.signum();
+        let v = value.abs();
+        match *self {
+            TransferFunction::Linear => value,
+            TransferFunction::Srgb | TransferFunction::ExtSrgb => {
+                if v <= 0.04045 {
+                    value / 12.92
+                } else {
+                    sign * ((v + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            TransferFunction::Gamma22 => sign * v.powf(2.2),
+            TransferFunction::Gamma28 => sign * v.powf(2.8),
+            TransferFunction::Bt1886 => sign * v.powf(2.4),
+            TransferFunction::St428 => sign * v.powf(2.6),
+            TransferFunction::Power(exp) => sign * v.powf(exp),
+            TransferFunction::Xvycc | TransferFunction::St240 => {
+                let (alpha, beta, slope) = self.bt709_params();
+                if v < beta * slope {
+                    value / slope
+                } else {
+                    sign * ((v + alpha - 1.0) / alpha).powf(1.0 / 0.45)
+                }
+            }
+            TransferFunction::Log100 | TransferFunction::Log316 => {
+                if value <= 0.0 {
+                    0.0
+                } else {
+                    10f64.powf((value - 1.0) * self.log_decades())
+                }
+            }
+            TransferFunction::Pq => {
+                let p = value.max(0.0).powf(1.0 / PQ_M2);
+                ((p - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * p)).powf(1.0 / PQ_M1)
+            }
+            TransferFunction::Hlg => {
+                let v = value.max(0.0);
+                if v <= 0.5 {
+                    v * v / 3.0
+                } else {
+                    (((v - HLG_C) / HLG_A).exp() + HLG_B) / 12.0
+                }
+            }
+        }
+    }
+
+    /// Encode a linear value, the inverse of [`TransferFunction::eotf`]
+    pub fn inverse_eotf(&self, value: f64) -> f64 {
+        let sign = value.signum();
+        let v = value.abs();
+        match *self {
+            TransferFunction::Linear => value,
+            TransferFunction::Srgb | TransferFunction::ExtSrgb => {
+                if v <= 0.0031308 {
+                    value * 12.92
+                } else {
+                    sign * (1.055 * v.powf(1.0 / 2.4) - 0.055)
+                }
+            }
+            TransferFunction::Gamma22 => sign * v.powf(1.0 / 2.2),
+            TransferFunction::Gamma28 => sign * v.powf(1.0 / 2.8),
+            TransferFunction::Bt1886 => sign * v.powf(1.0 / 2.4),
+            TransferFunction::St428 => sign * v.powf(1.0 / 2.6),
+            TransferFunction::Power(exp) => sign * v.powf(1.0 / exp),
+            TransferFunction::Xvycc | TransferFunction::St240 => {
+                let (alpha, beta, slope) = self.bt709_params();
+                if v < beta {
+                    value * slope
+                } else {
+                    sign * (alpha * v.powf(0.45) - (alpha - 1.0))
+                }
+            }
+            TransferFunction::Log100 | TransferFunction::Log316 => {
+                let decades = self.log_decades();
+                if value < 10f64.powf(-decades) {
+                    0.0
+                } else {
+                    1.0 + value.log10() / decades
+                }
+            }
+            TransferFunction::Pq => {
+                let y = value.max(0.0).powf(PQ_M1);
+                ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+            }
+            TransferFunction::Hlg => {
+                let v = value.max(0.0);
+                if v <= 1.0 / 12.0 {
+                    (3.0 * v).sqrt()
+                } else {
+                    HLG_A * (12.0 * v - HLG_B).ln() + HLG_C
+                }
+            }
+        }
+    }
+
+    // (alpha, beta, slope) of the BT.709-style curves
+    fn bt709_params(&self) -> (f64, f64, f64) {
+        match self {
+            TransferFunction::St240 => (1.1115, 0.0228, 4.0),
+            _ => (1.099, 0.018, 4.5),
+        }
+    }
+
+    fn log_decades(&self) -> f64 {
+        match self {
+            TransferFunction::Log316 => 2.5,
+            _ => 2.0,
+        }
+    }
+}
+
+/// Luminance range and reference white of a color space, in cd/m²
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Luminances {
+    /// Minimum luminance
+    pub min: f64,
+    /// Maximum luminance
+    pub max: f64,
+    /// Luminance of the reference white
+    pub reference: f64,
+}
+
+impl Luminances {
+    /// Default luminances for a given transfer function
+    ///
+    /// These match the defaults of the wayland color management protocol.
+    pub fn for_transfer_function(tf: TransferFunction) -> Self {
+        match tf {
+            TransferFunction::Pq => Luminances {
+                min: 0.005,
+                max: 10000.0,
+                reference: 203.0,
+            },
+            TransferFunction::Hlg => Luminances {
+                min: 0.005,
+                max: 1000.0,
+                reference: 203.0,
+            },
+            _ => Luminances {
+                min: 0.2,
+                max: 80.0,
+                reference: 80.0,
+            },
+        }
+    }
+}
+
+/// Parametric description of a color space
+///
+/// Elements without a description are expected to be in sRGB, see [`ColorDescription::srgb`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColorDescription {
+    /// Primaries of the color space
+    pub primaries: Primaries,
+    /// Transfer characteristic of the color space
+    pub transfer_function: TransferFunction,
+    /// Luminance range and reference white of the color space
+    pub luminances: Luminances,
+    /// Primaries of the mastering display, if known
+    pub target_primaries: Option<Chromaticities>,
+    /// Minimum and maximum luminance of the mastering display in cd/m², if known
+    pub target_luminance: Option<(f64, f64)>,
+    /// Maximum content light level in cd/m², if known
+    pub max_cll: Option<f64>,
+    /// Maximum frame-average light level in cd/m², if known
+    pub max_fall: Option<f64>,
+}
+
+impl ColorDescription {
+    /// Create a new description using the default luminances for the transfer function
+    pub fn new(primaries: impl Into<Primaries>, transfer_function: TransferFunction) -> Self {
+        ColorDescription {
+            primaries: primaries.into(),
+            transfer_function,
+            luminances: Luminances::for_transfer_function(transfer_function),
+            target_primaries: None,
+            target_luminance: None,
+            max_cll: None,
+            max_fall: None,
+        }
+    }
+
+    /// The sRGB color space
+    pub fn srgb() -> Self {
+        Self::new(NamedPrimaries::Srgb, TransferFunction::Srgb)
+    }
+
+    /// The BT.2100 color space using the perceptual quantizer, commonly used for HDR10 content
+    pub fn bt2100_pq() -> Self {
+        Self::new(NamedPrimaries::Bt2020, TransferFunction::Pq)
+    }
+
+    /// The BT.2100 color space using hybrid log-gamma
+    pub fn bt2100_hlg() -> Self {
+        Self::new(NamedPrimaries::Bt2020, TransferFunction::Hlg)
+    }
+
+    /// Linear sRGB with extended range, as used by Windows' scRGB
+    pub fn windows_scrgb() -> Self {
+        ColorDescription {
+            // 1.0 maps to 80 cd/m², but values up to 125.0 (10000 cd/m²) are allowed.
+            // The reference white is unknown, BT.2408 suggests 203 cd/m².
+            luminances: Luminances {
+                min: 0.0,
+                max: 80.0,
+                reference: 203.0,
+            },
+            ..Self::new(NamedPrimaries::Srgb, TransferFunction::Linear)
+        }
+    }
+
+    /// Luminance in cd/m² of a linear value of `1.0`, as returned by [`TransferFunction::eotf`]
+    ///
+    /// This is 10000 cd/m² for [`TransferFunction::Pq`] and the maximum luminance otherwise.
+    pub fn unit_luminance(&self) -> f64 {
+        match self.transfer_function {
+            TransferFunction::Pq => 10000.0,
+            _ => self.luminances.max,
+        }
+    }
+}
+
+impl Default for ColorDescription {
+    #[inline]
+    fn default() -> Self {
+        Self::srgb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NamedPrimaries, TransferFunction};
+
+    #[test]
+    fn srgb_to_bt2020() {
+        let srgb = NamedPrimaries::Srgb.chromaticities();
+        let bt2020 = NamedPrimaries::Bt2020.chromaticities();
+
+        let identity = srgb.conversion_matrix(&srgb);
+        let matrix = srgb.conversion_matrix(&bt2020);
+        let expected = [
+            [0.6274, 0.3293, 0.0433],
+            [0.0691, 0.9195, 0.0114],
+            [0.0164, 0.0880, 0.8956],
+        ];
+        for i in 0..3 {
+            for j in 0..3 {
+                assert!((identity[i][j] - if i == j { 1.0 } else { 0.0 }).abs() < 1e-9);
+                assert!((matrix[i][j] - expected[i][j]).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn transfer_function_roundtrip() {
+        for tf in [
+            TransferFunction::Srgb,
+            TransferFunction::Gamma22,
+            TransferFunction::St240,
+            TransferFunction::Log100,
+            TransferFunction::Pq,
+            TransferFunction::Hlg,
+            TransferFunction::Power(1.8),
+        ] {
+            for value in [0.1, 0.25, 0.5, 0.75, 1.0] {
+                let roundtrip = tf.eotf(tf.inverse_eotf(value));
+                assert!((roundtrip - value).abs() < 1e-6, "{tf:?}: {value} != {roundtrip}");
+            }
+        }
+    }
+}
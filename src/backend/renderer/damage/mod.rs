@@ -13,6 +13,8 @@
 //! - Current geometry for elements entering the output
 //! - Current and last known geometry for moved elements (includes z-index changes)
 //! - Last known geometry for elements no longer present
+//! - Current geometry for elements sampling the content beneath them, if that content is damaged
+//!   (see [`Element::backdrop_region`](crate::backend::renderer::element::Element::backdrop_region))
 //!
 //! Elements fully occluded by opaque regions as defined by elements higher in the stack are skipped.
 //! The actual action taken by the damage tracker can be inspected from the returned [`RenderElementStates`].
@@ -23,6 +25,9 @@
 //! See the [`renderer::element`](crate::backend::renderer::element) module for more information
 //! about how to use [`RenderElement`].
 //!
+//! To debug frames rendering incorrectly, they can be recorded and rendered again offline with
+//! the [`snapshot`] module.
+//!
 //! # How to use it
 //!
 //! ```no_run
@@ -118,6 +123,7 @@ use super::{
 use super::{Renderer, Texture};
 
 mod shaper;
+pub mod snapshot;
 
 use shaper::DamageShaper;
 
@@ -426,6 +432,7 @@ impl OutputDamageTracker {
                     element_damage,
                 );
 
+                frame.set_color_description(element.color_description());
                 element.draw(
                     &mut frame,
                     element.src(),
@@ -434,6 +441,7 @@ impl OutputDamageTracker {
                     &element_opaque_regions,
                 )?;
             }
+            frame.set_color_description(None);
 
             // return the element damage so that we can re-use the allocation
             std::mem::swap(&mut self.element_damage, &mut element_damage);
@@ -525,6 +533,8 @@ impl OutputDamageTracker {
         let mut element_damage = std::mem::take(&mut self.element_damage);
 
         let mut element_visible_area_workhouse = std::mem::take(&mut self.element_visible_area_workhouse);
+        // backdrop regions and geometry of elements sampling the content beneath them
+        let mut backdrops = Vec::new();
         for element in elements.iter() {
             let element_id = element.id();
             let element_loc = element.geometry(output_scale).loc;
@@ -587,6 +597,11 @@ impl OutputDamageTracker {
                 .push(element_opaque_regions_start_index..element_opaque_regions_end_index);
             render_elements.push(element);
 
+            if let Some(mut region) = element.backdrop_region(output_scale) {
+                region.loc += element_loc;
+                backdrops.push((region, element_output_geometry));
+            }
+
             if let Some(state) = element_render_states.states.get_mut(element_id) {
                 if matches!(state.presentation_state, RenderElementPresentationState::Skipped) {
                     *state = RenderElementState::rendered(element_visible_area);
@@ -685,6 +700,8 @@ impl OutputDamageTracker {
             self.damage.push(output_geo);
         }
 
+        damage_backdrops(&mut self.damage, &backdrops);
+
         // That is all completely new damage, which we need to store for subsequent renders
         let mut new_damage = self.damage.clone();
         new_damage.shrink_to_fit();
@@ -696,6 +713,7 @@ impl OutputDamageTracker {
             self.last_state.old_damage.truncate(age);
             self.damage
                 .extend(self.last_state.old_damage.iter().take(age - 1).flatten().copied());
+            damage_backdrops(&mut self.damage, &backdrops);
         } else {
             trace!(
                 "no old damage available, re-render everything. age: {} old_damage len: {}",
@@ -786,3 +804,28 @@ impl OutputDamageTracker {
         element_render_states
     }
 }
+
+/// Damages the whole geometry of elements sampling their backdrop, if the backdrop is damaged
+///
+/// Pixels of these elements depend on their surroundings, so redrawing parts of them would sample
+/// their own previous output. Damaging an element may damage the backdrop of another one, so this
+/// is repeated until no more elements get damaged.
+fn damage_backdrops(
+    damage: &mut Vec<Rectangle<i32, Physical>>,
+    backdrops: &[(Rectangle<i32, Physical>, Rectangle<i32, Physical>)],
+) {
+    let mut damaged = vec![false; backdrops.len()];
+    loop {
+        let mut changed = false;
+        for ((region, geometry), damaged) in backdrops.iter().zip(damaged.iter_mut()) {
+            if !*damaged && damage.iter().any(|rect| rect.overlaps(*region)) {
+                damage.push(*geometry);
+                *damaged = true;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
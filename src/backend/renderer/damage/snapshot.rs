@@ -0,0 +1,1031 @@
+//! Recording and replay of rendered frames for debugging
+//!
+//! The [`SnapshotRecorder`] wraps [`OutputDamageTracker::render_output`] and records everything
+//! about the passed elements that is needed to render the frame again: their ids, geometry, src,
+//! transform, alpha, opaque regions, damage and contents. The resulting [`Snapshot`] can be saved
+//! to a file and rendered again offline with any [`Renderer`] by a [`SnapshotReplayer`], which
+//! reproduces the damage tracking of the original frames.
+//!
+//! The contents of an element are captured by drawing it alone into an offscreen buffer of the
+//! size of its geometry, so a snapshot does not depend on the renderer it was recorded with.
+//! Elements are only captured again once they are damaged or their src, transform, size or alpha
+//! changes. Elements sampling the content beneath them (see
+//! [`Element::backdrop_region`]) are captured over a transparent background.
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::{
+//! #         damage::{
+//! #             snapshot::{Snapshot, SnapshotRecorder, SnapshotReplayer},
+//! #             OutputDamageTracker,
+//! #         },
+//! #         element::solid::SolidColorRenderElement,
+//! #         ExportMem, ImportMem, Offscreen, Texture,
+//! #     },
+//! #     utils::Transform,
+//! # };
+//! # fn render<R, B>(
+//! #     renderer: &mut R,
+//! #     framebuffer: &mut R::Framebuffer<'_>,
+//! #     elements: &[SolidColorRenderElement],
+//! # ) where
+//! #     R: Offscreen<B> + ExportMem + ImportMem,
+//! #     R::TextureId: Texture + 'static,
+//! # {
+//! let mut damage_tracker = OutputDamageTracker::new((800, 600), 1.0, Transform::Normal);
+//! let mut recorder = SnapshotRecorder::new();
+//!
+//! // Render through the recorder instead of the damage tracker
+//! recorder
+//!     .render_output::<B, _, _>(&mut damage_tracker, renderer, framebuffer, 0, elements, [0.0, 0.0, 0.0, 1.0])
+//!     .expect("failed to render the output");
+//! recorder.snapshot().save("frames.snapshot").expect("failed to save the snapshot");
+//!
+//! // Later, possibly with another renderer, render the recorded frames again
+//! let snapshot = Snapshot::load("frames.snapshot").expect("failed to load the snapshot");
+//! let mut replayer = SnapshotReplayer::new(snapshot);
+//! while let Some(result) = replayer.render_next(renderer, framebuffer, 0) {
+//!     result.expect("failed to replay the frame");
+//! }
+//! # }
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{Element, Id, Kind, RenderElement},
+            utils::{CommitCounter, DamageBag, DamageSet, OpaqueRegions},
+            Color32F, ExportMem, Frame, ImportMem, Offscreen, Renderer, Texture,
+        },
+    },
+    utils::{Buffer, Physical, Rectangle, Scale, Size, Transform},
+};
+
+use super::{Error, OutputDamageTracker, RenderOutputResult};
+
+const MAGIC: [u8; 8] = *b"SMTHSNAP";
+const VERSION: u32 = 1;
+
+/// Number of damage states kept for every replayed element
+const DAMAGE_LIMIT: usize = 4;
+
+const TRANSFORMS: [Transform; 8] = [
+    Transform::Normal,
+    Transform::_90,
+    Transform::_180,
+    Transform::_270,
+    Transform::Flipped,
+    Transform::Flipped90,
+    Transform::Flipped180,
+    Transform::Flipped270,
+];
+
+/// Errors thrown when saving or loading a [`Snapshot`]
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    /// Reading or writing the snapshot failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The data is not a snapshot
+    #[error("The data is not a render element snapshot")]
+    InvalidMagic,
+    /// The snapshot was written by an incompatible version
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u32),
+    /// The snapshot is malformed
+    #[error("Malformed snapshot: {0}")]
+    Malformed(&'static str),
+}
+
+/// Recorded frames of an output
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// Captured contents of the elements, referenced by [`ElementSnapshot::texture`]
+    pub textures: Vec<TextureSnapshot>,
+    /// Rendered frames in order
+    pub frames: Vec<FrameSnapshot>,
+}
+
+/// Captured contents of an element
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureSnapshot {
+    /// Size of the contents
+    pub size: Size<i32, Buffer>,
+    /// Premultiplied pixels in [`Fourcc::Abgr8888`], row by row without padding
+    pub data: Vec<u8>,
+}
+
+/// A frame rendered by [`OutputDamageTracker::render_output`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameSnapshot {
+    /// Size of the output
+    pub size: Size<i32, Physical>,
+    /// Scale of the output
+    pub scale: Scale<f64>,
+    /// Transform of the output
+    pub transform: Transform,
+    /// Age of the buffer the frame was rendered to
+    pub age: usize,
+    /// Color the output was cleared with
+    pub clear_color: Color32F,
+    /// Elements in front-to-back order
+    pub elements: Vec<ElementSnapshot>,
+    /// Damage of the rendering operation, `None` if rendering was skipped
+    pub damage: Option<Vec<Rectangle<i32, Physical>>>,
+}
+
+/// State of an element in a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementSnapshot {
+    /// Id of the element, stable across the frames of a [`Snapshot`]
+    pub id: u64,
+    /// Kind of the element
+    pub kind: Kind,
+    /// Geometry relative to the output
+    pub geometry: Rectangle<i32, Physical>,
+    /// Src of the underlying buffer
+    pub src: Rectangle<f64, Buffer>,
+    /// Transform of the underlying buffer
+    pub transform: Transform,
+    /// Alpha of the element, already applied to the captured contents
+    pub alpha: f32,
+    /// Opaque regions relative to the element
+    pub opaque_regions: Vec<Rectangle<i32, Physical>>,
+    /// Damage since the previous frame relative to the element
+    pub damage: Vec<Rectangle<i32, Physical>>,
+    /// Index of the captured contents in [`Snapshot::textures`], `None` for empty elements
+    pub texture: Option<usize>,
+}
+
+impl Snapshot {
+    /// Saves the snapshot to a file at `path`
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Loads a snapshot from a file at `path`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the snapshot to `writer`
+    pub fn write_to(&self, writer: impl Write) -> Result<(), SnapshotError> {
+        let mut enc = Encoder(writer);
+        enc.0.write_all(&MAGIC)?;
+        enc.u32(VERSION)?;
+
+        enc.len(self.textures.len())?;
+        for texture in &self.textures {
+            enc.i32(texture.size.w)?;
+            enc.i32(texture.size.h)?;
+            enc.len(texture.data.len())?;
+            enc.0.write_all(&texture.data)?;
+        }
+
+        enc.len(self.frames.len())?;
+        for frame in &self.frames {
+            enc.i32(frame.size.w)?;
+            enc.i32(frame.size.h)?;
+            enc.f64(frame.scale.x)?;
+            enc.f64(frame.scale.y)?;
+            enc.u8(frame.transform as u8)?;
+            enc.len(frame.age)?;
+            for component in frame.clear_color.components() {
+                enc.f32(component)?;
+            }
+            match &frame.damage {
+                Some(damage) => {
+                    enc.u8(1)?;
+                    enc.rects(damage)?;
+                }
+                None => enc.u8(0)?,
+            }
+
+            enc.len(frame.elements.len())?;
+            for element in &frame.elements {
+                enc.u64(element.id)?;
+                enc.u8(match element.kind {
+                    Kind::Cursor => 0,
+                    Kind::Unspecified => 1,
+                })?;
+                enc.rect(element.geometry)?;
+                for value in [
+                    element.src.loc.x,
+                    element.src.loc.y,
+                    element.src.size.w,
+                    element.src.size.h,
+                ] {
+                    enc.f64(value)?;
+                }
+                enc.u8(element.transform as u8)?;
+                enc.f32(element.alpha)?;
+                enc.rects(&element.opaque_regions)?;
+                enc.rects(&element.damage)?;
+                match element.texture {
+                    Some(texture) => {
+                        enc.u8(1)?;
+                        enc.len(texture)?;
+                    }
+                    None => enc.u8(0)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot from `reader`
+    pub fn read_from(reader: impl Read) -> Result<Self, SnapshotError> {
+        let mut dec = Decoder(reader);
+        let mut magic = [0u8; 8];
+        dec.0.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        let version = dec.u32()?;
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut snapshot = Snapshot::default();
+        for _ in 0..dec.len()? {
+            let size = Size::from((dec.i32()?, dec.i32()?));
+            let len = dec.len()?;
+            if size.w < 0 || size.h < 0 || len != size.w as usize * size.h as usize * 4 {
+                return Err(SnapshotError::Malformed("texture size does not match its data"));
+            }
+            let mut data = Vec::new();
+            (&mut dec.0).take(len as u64).read_to_end(&mut data)?;
+            if data.len() != len {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            snapshot.textures.push(TextureSnapshot { size, data });
+        }
+
+        for _ in 0..dec.len()? {
+            let size = Size::from((dec.i32()?, dec.i32()?));
+            let scale = Scale::from((dec.f64()?, dec.f64()?));
+            let transform = dec.transform()?;
+            let age = dec.len()?;
+            let clear_color = Color32F::new(dec.f32()?, dec.f32()?, dec.f32()?, dec.f32()?);
+            let damage = match dec.u8()? {
+                0 => None,
+                1 => Some(dec.rects()?),
+                _ => return Err(SnapshotError::Malformed("invalid frame damage")),
+            };
+
+            let mut elements = Vec::new();
+            for _ in 0..dec.len()? {
+                let id = dec.u64()?;
+                let kind = match dec.u8()? {
+                    0 => Kind::Cursor,
+                    1 => Kind::Unspecified,
+                    _ => return Err(SnapshotError::Malformed("invalid element kind")),
+                };
+                let geometry = dec.rect()?;
+                let src = Rectangle::new((dec.f64()?, dec.f64()?).into(), (dec.f64()?, dec.f64()?).into());
+                let transform = dec.transform()?;
+                let alpha = dec.f32()?;
+                let opaque_regions = dec.rects()?;
+                let element_damage = dec.rects()?;
+                let texture = match dec.u8()? {
+                    0 => None,
+                    1 => {
+                        let texture = dec.len()?;
+                        if texture >= snapshot.textures.len() {
+                            return Err(SnapshotError::Malformed("invalid texture index"));
+                        }
+                        Some(texture)
+                    }
+                    _ => return Err(SnapshotError::Malformed("invalid element texture")),
+                };
+                elements.push(ElementSnapshot {
+                    id,
+                    kind,
+                    geometry,
+                    src,
+                    transform,
+                    alpha,
+                    opaque_regions,
+                    damage: element_damage,
+                    texture,
+                });
+            }
+
+            snapshot.frames.push(FrameSnapshot {
+                size,
+                scale,
+                transform,
+                age,
+                clear_color,
+                elements,
+                damage,
+            });
+        }
+        Ok(snapshot)
+    }
+}
+
+struct Encoder<W>(W);
+
+impl<W: Write> Encoder<W> {
+    fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.0.write_all(&[value])
+    }
+
+    fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn len(&mut self, value: usize) -> io::Result<()> {
+        self.u64(value as u64)
+    }
+
+    fn i32(&mut self, value: i32) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn f64(&mut self, value: f64) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn rect(&mut self, rect: Rectangle<i32, Physical>) -> io::Result<()> {
+        for value in [rect.loc.x, rect.loc.y, rect.size.w, rect.size.h] {
+            self.i32(value)?;
+        }
+        Ok(())
+    }
+
+    fn rects(&mut self, rects: &[Rectangle<i32, Physical>]) -> io::Result<()> {
+        self.len(rects.len())?;
+        rects.iter().try_for_each(|rect| self.rect(*rect))
+    }
+}
+
+struct Decoder<R>(R);
+
+impl<R: Read> Decoder<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    fn len(&mut self) -> Result<usize, SnapshotError> {
+        usize::try_from(self.u64()?).map_err(|_| SnapshotError::Malformed("length out of range"))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        self.bytes().map(i32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        self.bytes().map(f64::from_le_bytes)
+    }
+
+    fn transform(&mut self) -> Result<Transform, SnapshotError> {
+        TRANSFORMS
+            .get(self.u8()? as usize)
+            .copied()
+            .ok_or(SnapshotError::Malformed("invalid transform"))
+    }
+
+    fn rect(&mut self) -> io::Result<Rectangle<i32, Physical>> {
+        Ok(Rectangle::new(
+            (self.i32()?, self.i32()?).into(),
+            (self.i32()?, self.i32()?).into(),
+        ))
+    }
+
+    fn rects(&mut self) -> Result<Vec<Rectangle<i32, Physical>>, SnapshotError> {
+        let len = self.len()?;
+        // do not trust the length for the allocation, the data might be truncated
+        let mut rects = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            rects.push(self.rect()?);
+        }
+        Ok(rects)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CaptureKey {
+    commit: CommitCounter,
+    src: Rectangle<f64, Buffer>,
+    transform: Transform,
+    size: Size<i32, Physical>,
+    alpha: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RecordedElement {
+    id: u64,
+    key: CaptureKey,
+    texture: Option<usize>,
+}
+
+/// Records the frames rendered by an [`OutputDamageTracker`] into a [`Snapshot`]
+#[derive(Debug, Default)]
+pub struct SnapshotRecorder {
+    snapshot: Snapshot,
+    elements: HashMap<Id, RecordedElement>,
+    next_id: u64,
+}
+
+impl SnapshotRecorder {
+    /// Creates a new recorder with an empty [`Snapshot`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render the output with `damage_tracker` and record the frame
+    ///
+    /// See [`OutputDamageTracker::render_output`] for the arguments. New contents of the elements
+    /// are captured into offscreen buffers of type `B` before the output is rendered.
+    pub fn render_output<'a, B, E, R>(
+        &mut self,
+        damage_tracker: &'a mut OutputDamageTracker,
+        renderer: &mut R,
+        framebuffer: &mut R::Framebuffer<'_>,
+        age: usize,
+        elements: &[E],
+        clear_color: impl Into<Color32F>,
+    ) -> Result<RenderOutputResult<'a>, Error<R::Error>>
+    where
+        E: RenderElement<R>,
+        R: Renderer + Offscreen<B> + ExportMem,
+        R::TextureId: Texture,
+    {
+        let clear_color = clear_color.into();
+        let (size, scale, transform) =
+            std::convert::TryInto::<(Size<i32, Physical>, Scale<f64>, Transform)>::try_into(
+                damage_tracker.mode(),
+            )?;
+
+        let mut recorded = HashMap::with_capacity(elements.len());
+        let mut frame_elements = Vec::with_capacity(elements.len());
+        for element in elements {
+            let geometry = element.geometry(scale);
+            let key = CaptureKey {
+                commit: element.current_commit(),
+                src: element.src(),
+                transform: element.transform(),
+                size: geometry.size,
+                alpha: element.alpha(),
+            };
+
+            let previous = self.elements.get(element.id()).copied();
+            let damage = match previous {
+                Some(previous) => element
+                    .damage_since(scale, Some(previous.key.commit))
+                    .into_iter()
+                    .collect(),
+                None => vec![Rectangle::from_size(geometry.size)],
+            };
+
+            // elements can be rendered multiple times in a frame
+            let current = recorded.get(element.id()).copied().or(previous);
+            let (id, texture) = match current {
+                Some(current) if current.key == key => (current.id, current.texture),
+                _ => {
+                    let id = current.map(|current| current.id).unwrap_or_else(|| {
+                        self.next_id += 1;
+                        self.next_id
+                    });
+                    (id, self.capture::<B, E, R>(renderer, element, key)?)
+                }
+            };
+
+            recorded.insert(element.id().clone(), RecordedElement { id, key, texture });
+            frame_elements.push(ElementSnapshot {
+                id,
+                kind: element.kind(),
+                geometry,
+                src: key.src,
+                transform: key.transform,
+                alpha: key.alpha,
+                opaque_regions: element.opaque_regions(scale).into_iter().collect(),
+                damage,
+                texture,
+            });
+        }
+        self.elements = recorded;
+
+        let result = damage_tracker.render_output(renderer, framebuffer, age, elements, clear_color)?;
+        self.snapshot.frames.push(FrameSnapshot {
+            size,
+            scale,
+            transform,
+            age,
+            clear_color,
+            elements: frame_elements,
+            damage: result.damage.cloned(),
+        });
+        Ok(result)
+    }
+
+    fn capture<B, E, R>(
+        &mut self,
+        renderer: &mut R,
+        element: &E,
+        key: CaptureKey,
+    ) -> Result<Option<usize>, Error<R::Error>>
+    where
+        E: RenderElement<R>,
+        R: Renderer + Offscreen<B> + ExportMem,
+    {
+        if key.size.is_empty() {
+            return Ok(None);
+        }
+
+        let size = Size::<i32, Buffer>::from((key.size.w, key.size.h));
+        let mut buffer = renderer
+            .create_buffer(Fourcc::Abgr8888, size)
+            .map_err(Error::Rendering)?;
+        let mut framebuffer = renderer.bind(&mut buffer).map_err(Error::Rendering)?;
+
+        let dst = Rectangle::from_size(key.size);
+        let mut frame = renderer
+            .render(&mut framebuffer, key.size, Transform::Normal)
+            .map_err(Error::Rendering)?;
+        frame
+            .clear(Color32F::TRANSPARENT, &[dst])
+            .map_err(Error::Rendering)?;
+        element
+            .draw(&mut frame, key.src, dst, &[dst], &[])
+            .map_err(Error::Rendering)?;
+        let sync = frame.finish().map_err(Error::Rendering)?;
+        renderer.wait(&sync).map_err(Error::Rendering)?;
+
+        let mapping = renderer
+            .copy_framebuffer(&framebuffer, Rectangle::from_size(size), Fourcc::Abgr8888)
+            .map_err(Error::Rendering)?;
+        // All renderers return the rows from top to bottom, but some pad them
+        let data = renderer.map_texture(&mapping).map_err(Error::Rendering)?;
+        let row = size.w as usize * 4;
+        let stride = data.len() / size.h as usize;
+        let data = data
+            .chunks(stride)
+            .take(size.h as usize)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect();
+
+        self.snapshot.textures.push(TextureSnapshot { size, data });
+        Ok(Some(self.snapshot.textures.len() - 1))
+    }
+
+    /// Returns the frames recorded so far
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Consumes the recorder, returning the recorded frames
+    pub fn into_snapshot(self) -> Snapshot {
+        self.snapshot
+    }
+}
+
+#[derive(Debug)]
+struct ReplayedElement {
+    id: Id,
+    damage: DamageBag<i32, Physical>,
+}
+
+/// Renders the frames of a [`Snapshot`] again
+#[derive(Debug)]
+pub struct SnapshotReplayer<T> {
+    snapshot: Snapshot,
+    next_frame: usize,
+    damage_tracker: Option<OutputDamageTracker>,
+    elements: HashMap<u64, ReplayedElement>,
+    textures: HashMap<usize, T>,
+}
+
+impl<T: Texture + 'static> SnapshotReplayer<T> {
+    /// Creates a new replayer, starting with the first frame of `snapshot`
+    pub fn new(snapshot: Snapshot) -> Self {
+        SnapshotReplayer {
+            snapshot,
+            next_frame: 0,
+            damage_tracker: None,
+            elements: HashMap::new(),
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Returns the replayed snapshot
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Returns the index of the frame rendered by the next call to [`render_next`](Self::render_next)
+    pub fn next_frame(&self) -> usize {
+        self.next_frame
+    }
+
+    /// Renders the next frame of the snapshot into `framebuffer`
+    ///
+    /// The frames are rendered with an [`OutputDamageTracker`] of the recorded output mode, so
+    /// passing the recorded [`FrameSnapshot::age`] reproduces the damage of the original frame.
+    /// Returns `None` once all frames have been rendered.
+    pub fn render_next<R>(
+        &mut self,
+        renderer: &mut R,
+        framebuffer: &mut R::Framebuffer<'_>,
+        age: usize,
+    ) -> Option<Result<RenderOutputResult<'_>, Error<R::Error>>>
+    where
+        R: Renderer<TextureId = T> + ImportMem,
+    {
+        let frame = self.snapshot.frames.get(self.next_frame)?;
+        self.next_frame += 1;
+
+        // the output mode may change between frames
+        let mode = (frame.size, frame.scale, frame.transform);
+        let current_mode = self.damage_tracker.as_ref().and_then(|damage_tracker| {
+            std::convert::TryInto::<(Size<i32, Physical>, Scale<f64>, Transform)>::try_into(
+                damage_tracker.mode(),
+            )
+            .ok()
+        });
+        if current_mode != Some(mode) {
+            self.damage_tracker = None;
+        }
+        let damage_tracker = self
+            .damage_tracker
+            .get_or_insert_with(|| OutputDamageTracker::new(frame.size, frame.scale, frame.transform));
+
+        let ids = frame
+            .elements
+            .iter()
+            .map(|element| element.id)
+            .collect::<HashSet<_>>();
+        self.elements.retain(|id, _| ids.contains(id));
+        for element in &frame.elements {
+            match self.elements.get_mut(&element.id) {
+                Some(replayed) => {
+                    if !element.damage.is_empty() {
+                        replayed.damage.add(element.damage.iter().copied());
+                    }
+                }
+                // new elements are damaged entirely by the damage tracker
+                None => {
+                    self.elements.insert(
+                        element.id,
+                        ReplayedElement {
+                            id: Id::new(),
+                            damage: DamageBag::new(DAMAGE_LIMIT),
+                        },
+                    );
+                }
+            }
+        }
+
+        let used = frame
+            .elements
+            .iter()
+            .filter_map(|element| element.texture)
+            .collect::<HashSet<_>>();
+        self.textures.retain(|index, _| used.contains(index));
+        for &index in &used {
+            if self.textures.contains_key(&index) {
+                continue;
+            }
+            let texture = &self.snapshot.textures[index];
+            match renderer.import_memory(&texture.data, Fourcc::Abgr8888, texture.size, false) {
+                Ok(imported) => {
+                    self.textures.insert(index, imported);
+                }
+                Err(err) => return Some(Err(Error::Rendering(err))),
+            }
+        }
+
+        let elements = frame
+            .elements
+            .iter()
+            .map(|element| {
+                let replayed = &self.elements[&element.id];
+                ReplayElement {
+                    id: &replayed.id,
+                    damage: &replayed.damage,
+                    element,
+                    texture: element.texture.map(|index| &self.textures[&index]),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Some(damage_tracker.render_output(renderer, framebuffer, age, &elements, frame.clear_color))
+    }
+}
+
+/// An element of a [`FrameSnapshot`], drawing its captured contents
+struct ReplayElement<'a, T> {
+    id: &'a Id,
+    damage: &'a DamageBag<i32, Physical>,
+    element: &'a ElementSnapshot,
+    texture: Option<&'a T>,
+}
+
+impl<T> Element for ReplayElement<'_, T> {
+    fn id(&self) -> &Id {
+        self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.damage.current_commit()
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        self.element.src
+    }
+
+    fn transform(&self) -> Transform {
+        self.element.transform
+    }
+
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.element.geometry
+    }
+
+    fn damage_since(&self, _scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        self.damage
+            .damage_since(commit)
+            .unwrap_or_else(|| DamageSet::from_slice(&[Rectangle::from_size(self.element.geometry.size)]))
+    }
+
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        OpaqueRegions::from_slice(&self.element.opaque_regions)
+    }
+
+    fn alpha(&self) -> f32 {
+        self.element.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.element.kind
+    }
+}
+
+impl<R: Renderer> RenderElement<R> for ReplayElement<'_, R::TextureId> {
+    fn draw(
+        &self,
+        frame: &mut R::Frame<'_, '_>,
+        _src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), R::Error> {
+        let Some(texture) = self.texture else {
+            return Ok(());
+        };
+        // the alpha and transform of the element are part of the captured contents
+        frame.render_texture_from_to(
+            texture,
+            Rectangle::from_size(texture.size().to_f64()),
+            dst,
+            damage,
+            opaque_regions,
+            Transform::Normal,
+            1.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ElementSnapshot, FrameSnapshot, Snapshot, SnapshotError, TextureSnapshot};
+    use crate::{
+        backend::renderer::{element::Kind, Color32F},
+        utils::{Rectangle, Size, Transform},
+    };
+
+    fn snapshot() -> Snapshot {
+        let element = ElementSnapshot {
+            id: 1,
+            kind: Kind::Unspecified,
+            geometry: Rectangle::new((10, 20).into(), (2, 1).into()),
+            src: Rectangle::new((0.5, 0.0).into(), (1.5, 1.0).into()),
+            transform: Transform::Flipped90,
+            alpha: 0.5,
+            opaque_regions: vec![Rectangle::from_size((1, 1).into())],
+            damage: vec![Rectangle::new((1, 0).into(), (1, 1).into())],
+            texture: Some(0),
+        };
+        Snapshot {
+            textures: vec![TextureSnapshot {
+                size: Size::from((2, 1)),
+                data: vec![255, 0, 0, 255, 0, 0, 128, 128],
+            }],
+            frames: vec![
+                FrameSnapshot {
+                    size: (64, 32).into(),
+                    scale: 1.5.into(),
+                    transform: Transform::_270,
+                    age: 2,
+                    clear_color: Color32F::new(0.1, 0.2, 0.3, 1.0),
+                    elements: vec![
+                        element.clone(),
+                        ElementSnapshot {
+                            id: 2,
+                            kind: Kind::Cursor,
+                            texture: None,
+                            ..element
+                        },
+                    ],
+                    damage: Some(vec![Rectangle::from_size((64, 32).into())]),
+                },
+                FrameSnapshot {
+                    size: (64, 32).into(),
+                    scale: 1.5.into(),
+                    transform: Transform::_270,
+                    age: 1,
+                    clear_color: Color32F::TRANSPARENT,
+                    elements: Vec::new(),
+                    damage: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let snapshot = snapshot();
+        let mut data = Vec::new();
+        snapshot.write_to(&mut data).unwrap();
+        assert_eq!(Snapshot::read_from(&data[..]).unwrap(), snapshot);
+
+        assert!(matches!(
+            Snapshot::read_from(&data[..data.len() - 1]),
+            Err(SnapshotError::Io(_))
+        ));
+        data[0] = 0;
+        assert!(matches!(
+            Snapshot::read_from(&data[..]),
+            Err(SnapshotError::InvalidMagic)
+        ));
+
+        let mut snapshot = snapshot;
+        snapshot.frames[0].elements[0].texture = Some(1);
+        let mut data = Vec::new();
+        snapshot.write_to(&mut data).unwrap();
+        assert!(matches!(
+            Snapshot::read_from(&data[..]),
+            Err(SnapshotError::Malformed("invalid texture index"))
+        ));
+    }
+
+    #[cfg(feature = "renderer_gl")]
+    #[test]
+    fn replay() {
+        use super::{SnapshotRecorder, SnapshotReplayer};
+        use crate::{
+            backend::{
+                allocator::Fourcc,
+                egl::{native::EGLSurfacelessDisplay, EGLContext, EGLDisplay},
+                renderer::{
+                    damage::OutputDamageTracker,
+                    element::{solid::SolidColorRenderElement, Id},
+                    gles::{GlesRenderer, GlesTexture},
+                    utils::CommitCounter,
+                    Bind, ExportMem, Offscreen, Renderer,
+                },
+            },
+            utils::Buffer,
+        };
+
+        fn read<R: ExportMem>(renderer: &mut R, framebuffer: &R::Framebuffer<'_>) -> Vec<u8> {
+            let mapping = renderer
+                .copy_framebuffer(
+                    framebuffer,
+                    Rectangle::from_size((64, 64).into()),
+                    Fourcc::Abgr8888,
+                )
+                .unwrap();
+            renderer.map_texture(&mapping).unwrap().to_vec()
+        }
+
+        // skipped if egl is not available
+        let Some(mut renderer) = unsafe { EGLDisplay::new(EGLSurfacelessDisplay) }
+            .ok()
+            .and_then(|display| EGLContext::new(&display).ok())
+            .and_then(|context| unsafe { GlesRenderer::new(context) }.ok())
+        else {
+            return;
+        };
+
+        let size = Size::<i32, Buffer>::from((64, 64));
+        let (background, square, overlay) = (Id::new(), Id::new(), Id::new());
+        let mut overlay_commit = CommitCounter::default();
+        let mut frames = Vec::new();
+        let mut recorder = SnapshotRecorder::new();
+        let mut damage_tracker = OutputDamageTracker::new((64, 64), 1.0, Transform::Normal);
+        let mut buffer: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, size).unwrap();
+        for (square_loc, overlay_color) in [
+            ((8, 8), Color32F::new(0.0, 0.5, 0.0, 0.5)),
+            ((30, 10), Color32F::new(0.0, 0.0, 0.25, 0.5)),
+            ((30, 10), Color32F::new(0.0, 0.0, 0.25, 0.5)),
+        ] {
+            if frames.last().is_some_and(|(_, color)| *color != overlay_color) {
+                overlay_commit.increment();
+            }
+            let elements = [
+                SolidColorRenderElement::new(
+                    overlay.clone(),
+                    Rectangle::new((20, 20).into(), (20, 20).into()),
+                    overlay_commit,
+                    overlay_color,
+                    Kind::Unspecified,
+                ),
+                SolidColorRenderElement::new(
+                    square.clone(),
+                    Rectangle::new(square_loc.into(), (16, 16).into()),
+                    CommitCounter::default(),
+                    Color32F::new(1.0, 0.0, 0.0, 1.0),
+                    Kind::Unspecified,
+                ),
+                SolidColorRenderElement::new(
+                    background.clone(),
+                    Rectangle::from_size((64, 64).into()),
+                    CommitCounter::default(),
+                    Color32F::new(0.0, 0.0, 1.0, 1.0),
+                    Kind::Unspecified,
+                ),
+            ];
+
+            let mut framebuffer = renderer.bind(&mut buffer).unwrap();
+            let result = recorder
+                .render_output::<GlesTexture, _, _>(
+                    &mut damage_tracker,
+                    &mut renderer,
+                    &mut framebuffer,
+                    1,
+                    &elements,
+                    Color32F::TRANSPARENT,
+                )
+                .unwrap();
+            renderer.wait(&result.sync).unwrap();
+            frames.push((read(&mut renderer, &framebuffer), overlay_color));
+        }
+
+        let mut data = Vec::new();
+        recorder.snapshot().write_to(&mut data).unwrap();
+        let snapshot = Snapshot::read_from(&data[..]).unwrap();
+        assert_eq!(&snapshot, recorder.snapshot());
+        assert_eq!(snapshot.textures.len(), 4);
+        assert_eq!(snapshot.frames[2].damage, None);
+
+        let mut replayer = SnapshotReplayer::<GlesTexture>::new(snapshot.clone());
+        let mut buffer: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, size).unwrap();
+        for (index, (expected, _)) in frames.iter().enumerate() {
+            let mut framebuffer = renderer.bind(&mut buffer).unwrap();
+            let result = replayer
+                .render_next(&mut renderer, &mut framebuffer, 1)
+                .unwrap()
+                .unwrap();
+            renderer.wait(&result.sync).unwrap();
+            assert_eq!(result.damage.cloned(), snapshot.frames[index].damage);
+
+            let pixels = read(&mut renderer, &framebuffer);
+            // the contents are quantized once more when captured
+            let max_diff = pixels.iter().zip(expected).map(|(a, b)| a.abs_diff(*b)).max();
+            assert!(max_diff <= Some(1), "frame {} differs by {:?}", index, max_diff);
+        }
+        let mut framebuffer = renderer.bind(&mut buffer).unwrap();
+        assert!(replayer.render_next(&mut renderer, &mut framebuffer, 1).is_none());
+    }
+}
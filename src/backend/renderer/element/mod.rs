@@ -32,6 +32,7 @@ use crate::{
 #[cfg(feature = "wayland_frontend")]
 use super::utils::Buffer;
 use super::{
+    color::ColorDescription,
     utils::{CommitCounter, DamageSet, OpaqueRegions},
     Renderer,
 };
@@ -378,6 +379,30 @@ pub trait Element {
     fn kind(&self) -> Kind {
         Kind::default()
     }
+    /// Returns the [`ColorDescription`] of the contents of this element, if known
+    ///
+    /// Elements without a description are expected to be in sRGB.
+    fn color_description(&self) -> Option<ColorDescription> {
+        None
+    }
+    /// Returns `true` if the element may be presented with tearing
+    ///
+    /// Backends may use this to present the element without waiting for the next vblank,
+    /// e.g. when it is scanned out directly.
+    fn allows_tearing(&self) -> bool {
+        false
+    }
+    /// Returns the region of the content beneath this element it samples from, relative to the element
+    ///
+    /// Elements deriving their contents from what was rendered below them, like a blur, have to be
+    /// redrawn entirely whenever anything in this region is damaged, which the
+    /// [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker) takes care of.
+    /// The region may extend beyond the geometry of the element.
+    ///
+    /// Such elements must not report opaque regions, as the content beneath them has to be drawn.
+    fn backdrop_region(&self, _scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        None
+    }
 }
 
 /// A single render element
@@ -460,6 +485,18 @@ where
     fn kind(&self) -> Kind {
         (*self).kind()
     }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        (*self).color_description()
+    }
+
+    fn allows_tearing(&self) -> bool {
+        (*self).allows_tearing()
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        (*self).backdrop_region(scale)
+    }
 }
 
 impl<R, E> RenderElement<R> for &E
@@ -765,6 +802,45 @@ macro_rules! render_elements_internal {
                 Self::_GenericCatcher(_) => unreachable!(),
             }
         }
+
+        fn color_description(&self) -> Option<$crate::backend::renderer::color::ColorDescription> {
+            match self {
+                $(
+                    #[allow(unused_doc_comments)]
+                    $(
+                        #[$meta]
+                    )*
+                    Self::$body(x) => $crate::render_elements_internal!(@call color_description; x)
+                ),*,
+                Self::_GenericCatcher(_) => unreachable!(),
+            }
+        }
+
+        fn allows_tearing(&self) -> bool {
+            match self {
+                $(
+                    #[allow(unused_doc_comments)]
+                    $(
+                        #[$meta]
+                    )*
+                    Self::$body(x) => $crate::render_elements_internal!(@call allows_tearing; x)
+                ),*,
+                Self::_GenericCatcher(_) => unreachable!(),
+            }
+        }
+
+        fn backdrop_region(&self, scale: $crate::utils::Scale<f64>) -> Option<$crate::utils::Rectangle<i32, $crate::utils::Physical>> {
+            match self {
+                $(
+                    #[allow(unused_doc_comments)]
+                    $(
+                        #[$meta]
+                    )*
+                    Self::$body(x) => $crate::render_elements_internal!(@call backdrop_region; x, scale)
+                ),*,
+                Self::_GenericCatcher(_) => unreachable!(),
+            }
+        }
     };
     (@draw <$renderer:ty>; $($(#[$meta:meta])* $body:ident=$field:ty $(as <$other_renderer:ty>)?),* $(,)?) => {
         fn draw(
@@ -1395,6 +1471,18 @@ where
     fn kind(&self) -> Kind {
         self.0.kind()
     }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        self.0.color_description()
+    }
+
+    fn allows_tearing(&self) -> bool {
+        self.0.allows_tearing()
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        self.0.backdrop_region(scale)
+    }
 }
 
 impl<R, C> RenderElement<R> for Wrap<C>
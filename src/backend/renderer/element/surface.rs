@@ -69,6 +69,7 @@ use wayland_server::protocol::wl_surface;
 
 use crate::{
     backend::renderer::{
+        color::ColorDescription,
         utils::{
             Buffer, DamageSet, DamageSnapshot, OpaqueRegions, RendererSurfaceState,
             RendererSurfaceStateUserData, SurfaceView,
@@ -78,7 +79,9 @@ use crate::{
     utils::{Buffer as BufferCoords, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
     wayland::{
         alpha_modifier::AlphaModifierSurfaceCachedState,
+        color_management::ColorManagementSurfaceCachedState,
         compositor::{self, SurfaceData, TraversalAction},
+        tearing_control::TearingControlSurfaceCachedState,
     },
 };
 
@@ -168,6 +171,8 @@ pub struct WaylandSurfaceRenderElement<R: Renderer> {
     location: Point<f64, Physical>,
     alpha: f32,
     kind: Kind,
+    color_description: Option<ColorDescription>,
+    allows_tearing: bool,
 
     view: SurfaceView,
     buffer: Buffer,
@@ -208,6 +213,20 @@ impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
         let mut alpha_modifier_state = states.cached_state.get::<AlphaModifierSurfaceCachedState>();
         let alpha_multiplier = alpha_modifier_state.current().multiplier_f32().unwrap_or(1.0);
 
+        // ICC based descriptions can't be handled by renderers
+        let mut color_management_state = states.cached_state.get::<ColorManagementSurfaceCachedState>();
+        let color_description = color_management_state
+            .current()
+            .image_description()
+            .and_then(|description| description.color_description())
+            .copied();
+
+        let allows_tearing = states
+            .cached_state
+            .get::<TearingControlSurfaceCachedState>()
+            .current()
+            .is_async();
+
         let Some(data_ref) = states.data_map.get::<RendererSurfaceStateUserData>() else {
             return Ok(None);
         };
@@ -217,16 +236,21 @@ impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
             location,
             alpha * alpha_multiplier,
             kind,
+            color_description,
+            allows_tearing,
             &data_ref.lock().unwrap(),
         ))
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn from_state(
         renderer: &mut R,
         id: Id,
         location: Point<f64, Physical>,
         alpha: f32,
         kind: Kind,
+        color_description: Option<ColorDescription>,
+        allows_tearing: bool,
         data: &RendererSurfaceState,
     ) -> Option<Self>
     where
@@ -245,6 +269,8 @@ impl<R: Renderer + ImportAll> WaylandSurfaceRenderElement<R> {
             location,
             alpha,
             kind,
+            color_description,
+            allows_tearing,
             view: data.view()?,
             buffer,
             buffer_scale: data.buffer_scale(),
@@ -366,6 +392,14 @@ impl<R: Renderer + ImportAll> Element for WaylandSurfaceRenderElement<R> {
     fn kind(&self) -> Kind {
         self.kind
     }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        self.color_description
+    }
+
+    fn allows_tearing(&self) -> bool {
+        self.allows_tearing
+    }
 }
 
 impl<R> RenderElement<R> for WaylandSurfaceRenderElement<R>
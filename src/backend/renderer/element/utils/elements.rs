@@ -2,6 +2,7 @@
 
 use crate::{
     backend::renderer::{
+        color::ColorDescription,
         element::{AsRenderElements, Element, Id, Kind, RenderElement, UnderlyingStorage},
         utils::{DamageSet, OpaqueRegions},
         Renderer,
@@ -90,6 +91,20 @@ impl<E: Element> Element for RescaleRenderElement<E> {
     fn kind(&self) -> Kind {
         self.element.kind()
     }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        self.element.color_description()
+    }
+
+    fn allows_tearing(&self) -> bool {
+        self.element.allows_tearing()
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        self.element
+            .backdrop_region(scale)
+            .map(|rect| rect.to_f64().upscale(self.scale).to_i32_up())
+    }
 }
 
 impl<R: Renderer, E: RenderElement<R>> RenderElement<R> for RescaleRenderElement<E> {
@@ -274,6 +289,22 @@ impl<E: Element> Element for CropRenderElement<E> {
     fn kind(&self) -> Kind {
         self.element.kind()
     }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        self.element.color_description()
+    }
+
+    fn allows_tearing(&self) -> bool {
+        self.element.allows_tearing()
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        let element_crop_rect = self.element_crop_rect(scale)?;
+        self.element.backdrop_region(scale).map(|mut rect| {
+            rect.loc -= element_crop_rect.loc;
+            rect
+        })
+    }
 }
 
 impl<R: Renderer, E: RenderElement<R>> RenderElement<R> for CropRenderElement<E> {
@@ -376,6 +407,18 @@ impl<E: Element> Element for RelocateRenderElement<E> {
     fn kind(&self) -> Kind {
         self.element.kind()
     }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        self.element.color_description()
+    }
+
+    fn allows_tearing(&self) -> bool {
+        self.element.allows_tearing()
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        self.element.backdrop_region(scale)
+    }
 }
 
 impl<R: Renderer, E: RenderElement<R>> RenderElement<R> for RelocateRenderElement<E> {
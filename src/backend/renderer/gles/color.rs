@@ -0,0 +1,178 @@
+//! Optional color transformation pipeline of the [`GlesRenderer`]
+//!
+//! When enabled via [`GlesRenderer::set_output_color_description`], frames are rendered into a
+//! linear fp16 offscreen buffer using the primaries of the output. Content is decoded into that
+//! space according to the [`ColorDescription`] set via
+//! [`Frame::set_color_description`](crate::backend::renderer::Frame::set_color_description),
+//! and the buffer is encoded to the transfer function of the output when the frame is finished.
+//!
+//! Linear values in the offscreen buffer are relative to the reference white of the output,
+//! the reference white of all content is mapped to it.
+
+use std::sync::mpsc::Sender;
+
+use super::{
+    ffi,
+    shaders::{self, texture_program},
+    CleanupResource, GlesError, GlesTexProgram, GlesTexture, Uniform, UniformName, UniformType, UniformValue,
+};
+use crate::{
+    backend::renderer::{
+        color::{ColorDescription, TransferFunction},
+        Color32F,
+    },
+    utils::{Physical, Rectangle},
+};
+
+// Transfer function values of the `tf` uniform of the texture shader
+const TF_LINEAR: f32 = 0.0;
+const TF_SRGB: f32 = 1.0;
+const TF_POWER: f32 = 2.0;
+const TF_PQ: f32 = 3.0;
+const TF_HLG: f32 = 4.0;
+const TF_LOG: f32 = 5.0;
+const TF_BT709: f32 = 6.0;
+
+/// Texture programs decoding into and encoding from the blending space
+#[derive(Debug, Clone)]
+pub(super) struct ColorPrograms {
+    pub(super) decode: GlesTexProgram,
+    pub(super) encode: GlesTexProgram,
+}
+
+impl ColorPrograms {
+    pub(super) unsafe fn compile(
+        gl: &ffi::Gles2,
+        destruction_callback_sender: Sender<CleanupResource>,
+    ) -> Result<ColorPrograms, GlesError> {
+        let uniforms = [
+            UniformName::new("tf", UniformType::_1f),
+            UniformName::new("tf_params", UniformType::_2f),
+            UniformName::new("color_matrix", UniformType::Matrix3x3),
+        ];
+        let with_define = |define: &str| {
+            shaders::FRAGMENT_SHADER.replace("//_DEFINES_", &format!("#define {define}\n//_DEFINES_"))
+        };
+
+        Ok(ColorPrograms {
+            decode: texture_program(
+                gl,
+                &with_define("COLOR_DECODE"),
+                &uniforms,
+                destruction_callback_sender.clone(),
+            )?,
+            encode: texture_program(
+                gl,
+                &with_define("COLOR_ENCODE"),
+                &uniforms,
+                destruction_callback_sender,
+            )?,
+        })
+    }
+}
+
+/// Linear fp16 offscreen buffer used for blending
+#[derive(Debug)]
+pub(super) struct ColorBuffer {
+    pub(super) texture: GlesTexture,
+    pub(super) fbo: ffi::types::GLuint,
+    pub(super) destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl Drop for ColorBuffer {
+    fn drop(&mut self) {
+        let _ = self
+            .destruction_callback_sender
+            .send(CleanupResource::FramebufferObject(self.fbo));
+    }
+}
+
+/// State of the color pipeline during a frame
+#[derive(Debug)]
+pub(super) struct ColorPass {
+    pub(super) output: ColorDescription,
+    pub(super) input: ColorDescription,
+    pub(super) buffer: GlesTexture,
+    pub(super) fbo: ffi::types::GLuint,
+    pub(super) programs: ColorPrograms,
+    /// Damage of the offscreen buffer in frame coordinates, `None` if fully damaged
+    pub(super) damage: Option<Vec<Rectangle<i32, Physical>>>,
+}
+
+fn transfer_function_uniforms(tf: TransferFunction) -> (f32, (f32, f32)) {
+    match tf {
+        TransferFunction::Linear => (TF_LINEAR, (0.0, 0.0)),
+        TransferFunction::Srgb | TransferFunction::ExtSrgb => (TF_SRGB, (0.0, 0.0)),
+        TransferFunction::Gamma22 => (TF_POWER, (2.2, 0.0)),
+        TransferFunction::Gamma28 => (TF_POWER, (2.8, 0.0)),
+        TransferFunction::Bt1886 => (TF_POWER, (2.4, 0.0)),
+        TransferFunction::St428 => (TF_POWER, (2.6, 0.0)),
+        TransferFunction::Power(exp) => (TF_POWER, (exp as f32, 0.0)),
+        TransferFunction::Pq => (TF_PQ, (0.0, 0.0)),
+        TransferFunction::Hlg => (TF_HLG, (0.0, 0.0)),
+        TransferFunction::Log100 => (TF_LOG, (2.0, 0.0)),
+        TransferFunction::Log316 => (TF_LOG, (2.5, 0.0)),
+        TransferFunction::Xvycc => (TF_BT709, (1.099, 0.018)),
+        TransferFunction::St240 => (TF_BT709, (1.1115, 0.0228)),
+    }
+}
+
+fn uniforms(tf: TransferFunction, matrix: [[f64; 3]; 3]) -> [Uniform<'static>; 3] {
+    let (tf, params) = transfer_function_uniforms(tf);
+    // GLES 2 requires column-major matrices
+    let matrix = std::array::from_fn(|i| matrix[i % 3][i / 3] as f32);
+    [
+        Uniform::new("tf", tf),
+        Uniform::new("tf_params", params),
+        Uniform::new(
+            "color_matrix",
+            UniformValue::Matrix3x3 {
+                matrices: vec![matrix],
+                transpose: false,
+            },
+        ),
+    ]
+}
+
+/// Matrix converting linear content using `input` into the blending space of `output`
+fn decode_matrix(input: &ColorDescription, output: &ColorDescription) -> [[f64; 3]; 3] {
+    let scale = input.unit_luminance() / input.luminances.reference;
+    input
+        .primaries
+        .chromaticities()
+        .conversion_matrix(&output.primaries.chromaticities())
+        .map(|row| row.map(|v| v * scale))
+}
+
+impl ColorPass {
+    /// Uniforms of the decode program for the current input
+    pub(super) fn decode_uniforms(&self) -> [Uniform<'static>; 3] {
+        uniforms(
+            self.input.transfer_function,
+            decode_matrix(&self.input, &self.output),
+        )
+    }
+
+    /// Uniforms of the encode program
+    pub(super) fn encode_uniforms(&self) -> [Uniform<'static>; 3] {
+        let scale = self.output.luminances.reference / self.output.unit_luminance();
+        uniforms(
+            self.output.transfer_function,
+            [[scale, 0.0, 0.0], [0.0, scale, 0.0], [0.0, 0.0, scale]],
+        )
+    }
+
+    /// Convert a solid color of the current input into the blending space
+    pub(super) fn decode_color(&self, color: Color32F) -> Color32F {
+        let alpha = color.a() as f64;
+        if alpha <= 0.0 {
+            return color;
+        }
+
+        let tf = self.input.transfer_function;
+        let rgb = [color.r(), color.g(), color.b()].map(|c| tf.eotf(c as f64 / alpha));
+        let matrix = decode_matrix(&self.input, &self.output);
+        let [r, g, b] = matrix.map(|row| ((0..3).map(|i| row[i] * rgb[i]).sum::<f64>() * alpha) as f32);
+        Color32F::new(r, g, b, color.a())
+    }
+}
@@ -0,0 +1,629 @@
+//! Effects of the [`GlesRenderer`] sampling the content already rendered in a frame
+//!
+//! The region of the framebuffer an effect depends on is copied into an offscreen texture,
+//! which is then processed and drawn back. This is used by the
+//! [`BlurElement`](super::element::BlurElement) and the
+//! [`RoundedCornerElement`](super::element::RoundedCornerElement).
+
+use std::sync::mpsc::Sender;
+
+use cgmath::{Matrix3, SquareMatrix};
+
+use super::{
+    build_texture_mat, ffi, format::fourcc_to_gl_formats, shaders, shaders::texture_program, version,
+    CleanupResource, DebugFlags, GlesError, GlesFrame, GlesRenderer, GlesTexProgram, GlesTexture, Uniform,
+    UniformName, UniformType,
+};
+use crate::{
+    backend::{
+        allocator::{format::has_alpha, Fourcc},
+        renderer::{Texture, TextureFilter},
+    },
+    utils::{Buffer as BufferCoord, Physical, Point, Rectangle, Size, Transform},
+};
+
+/// Texture programs used by the effects
+#[derive(Debug, Clone)]
+pub(super) struct EffectPrograms {
+    blur_down: GlesTexProgram,
+    blur_up: GlesTexProgram,
+    rounded_corner: GlesTexProgram,
+}
+
+impl EffectPrograms {
+    pub(super) unsafe fn compile(
+        gl: &ffi::Gles2,
+        destruction_callback_sender: Sender<CleanupResource>,
+    ) -> Result<EffectPrograms, GlesError> {
+        let blur_uniforms = [
+            UniformName::new("half_pixel", UniformType::_2f),
+            UniformName::new("offset", UniformType::_1f),
+        ];
+        let rounded_corner_uniforms = [
+            UniformName::new("tex_size", UniformType::_2f),
+            UniformName::new("origin", UniformType::_2f),
+            UniformName::new("center", UniformType::_2f),
+            UniformName::new("radius", UniformType::_1f),
+            UniformName::new("mask", UniformType::_1f),
+        ];
+
+        Ok(EffectPrograms {
+            blur_down: texture_program(
+                gl,
+                shaders::FRAGMENT_SHADER_BLUR_DOWN,
+                &blur_uniforms,
+                destruction_callback_sender.clone(),
+            )?,
+            blur_up: texture_program(
+                gl,
+                shaders::FRAGMENT_SHADER_BLUR_UP,
+                &blur_uniforms,
+                destruction_callback_sender.clone(),
+            )?,
+            rounded_corner: texture_program(
+                gl,
+                shaders::FRAGMENT_SHADER_ROUNDED_CORNER,
+                &rounded_corner_uniforms,
+                destruction_callback_sender,
+            )?,
+        })
+    }
+}
+
+/// Offscreen texture with a framebuffer effects render into
+#[derive(Debug)]
+struct EffectBuffer {
+    texture: GlesTexture,
+    fbo: ffi::types::GLuint,
+    destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl Drop for EffectBuffer {
+    fn drop(&mut self) {
+        let _ = self
+            .destruction_callback_sender
+            .send(CleanupResource::FramebufferObject(self.fbo));
+    }
+}
+
+impl EffectBuffer {
+    /// Creates a new buffer, the context of the renderer has to be current
+    unsafe fn new(
+        renderer: &GlesRenderer,
+        format: Fourcc,
+        size: Size<i32, BufferCoord>,
+    ) -> Result<Self, GlesError> {
+        let (internal, format_, layout) =
+            fourcc_to_gl_formats(format).ok_or(GlesError::UnsupportedPixelFormat(format))?;
+        let gl = &renderer.gl;
+
+        let mut previous_fbo = 0;
+        gl.GetIntegerv(ffi::FRAMEBUFFER_BINDING, &mut previous_fbo);
+
+        let mut tex = 0;
+        gl.GenTextures(1, &mut tex);
+        gl.BindTexture(ffi::TEXTURE_2D, tex);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+        gl.TexImage2D(
+            ffi::TEXTURE_2D,
+            0,
+            internal as i32,
+            size.w,
+            size.h,
+            0,
+            format_,
+            layout,
+            std::ptr::null(),
+        );
+        gl.BindTexture(ffi::TEXTURE_2D, 0);
+        let texture = GlesTexture::from_raw(renderer, Some(internal), !has_alpha(format), tex, size);
+
+        let mut fbo = 0;
+        gl.GenFramebuffers(1, &mut fbo as *mut _);
+        gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+        gl.FramebufferTexture2D(ffi::FRAMEBUFFER, ffi::COLOR_ATTACHMENT0, ffi::TEXTURE_2D, tex, 0);
+        let status = gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+        gl.BindFramebuffer(ffi::FRAMEBUFFER, previous_fbo as u32);
+
+        if status != ffi::FRAMEBUFFER_COMPLETE {
+            gl.DeleteFramebuffers(1, &mut fbo as *mut _);
+            return Err(GlesError::FramebufferBindingError);
+        }
+
+        Ok(EffectBuffer {
+            texture,
+            fbo,
+            destruction_callback_sender: renderer.gles_cleanup().sender.clone(),
+        })
+    }
+}
+
+/// Offscreen buffers of the effects, kept between frames
+#[derive(Debug, Default)]
+pub(super) struct EffectBuffers {
+    /// Saved backgrounds of rounded corners, by nesting depth
+    corners: Vec<Option<EffectBuffer>>,
+    /// Levels of the blur, starting at the size of the sampled region
+    blur: Vec<Option<EffectBuffer>>,
+}
+
+/// Returns the buffer in `slot`, re-creating it if it does not match `format` and `size`
+unsafe fn effect_buffer<'a>(
+    renderer: &GlesRenderer,
+    slot: &'a mut Option<EffectBuffer>,
+    format: Fourcc,
+    size: Size<i32, BufferCoord>,
+) -> Result<&'a EffectBuffer, GlesError> {
+    let matches = slot
+        .as_ref()
+        .is_some_and(|buffer| buffer.texture.size() == size && buffer.texture.format() == Some(format));
+    if !matches {
+        *slot = None;
+        *slot = Some(EffectBuffer::new(renderer, format, size)?);
+    }
+    Ok(slot.as_ref().unwrap())
+}
+
+/// Number of pixels the blur samples around a pixel
+pub(super) fn blur_radius(passes: u32, offset: f32) -> i32 {
+    // every pass doubles the distance sampled by the following ones
+    ((offset * 2.5 + 3.0) * 2f32.powi(passes as i32)).ceil() as i32
+}
+
+/// Projection rendering into an offscreen texture of `size`, without any transformation
+fn texture_projection(size: Size<i32, BufferCoord>) -> Matrix3<f32> {
+    Matrix3::new(
+        2.0 / size.w as f32,
+        0.0,
+        0.0,
+        0.0,
+        2.0 / size.h as f32,
+        0.0,
+        -1.0,
+        -1.0,
+        1.0,
+    )
+}
+
+/// Backgrounds of the corners of a region saved by [`GlesFrame::save_rounded_corners`]
+#[derive(Debug)]
+#[must_use = "the corners have to be restored with `GlesFrame::restore_rounded_corners`"]
+pub struct RoundedCorners {
+    depth: usize,
+    dst: Rectangle<i32, Physical>,
+    radius: i32,
+    damage: Vec<Rectangle<i32, Physical>>,
+}
+
+impl GlesFrame<'_, '_> {
+    /// Converts a rectangle of the frame into the coordinate space of the framebuffer
+    fn framebuffer_rect(&self, rect: Rectangle<i32, Physical>) -> Rectangle<i32, Physical> {
+        self.transform.transform_rect_in(rect, &self.size)
+    }
+
+    fn effect_format(&self) -> Fourcc {
+        if self.color_pass.is_some() {
+            Fourcc::Abgr16161616f
+        } else {
+            Fourcc::Abgr8888
+        }
+    }
+
+    fn effect_programs(&mut self) -> Result<EffectPrograms, GlesError> {
+        if self.renderer.effect_programs.is_none() {
+            let programs = unsafe {
+                EffectPrograms::compile(&self.renderer.gl, self.renderer.gles_cleanup().sender.clone())?
+            };
+            self.renderer.effect_programs = Some(programs);
+        }
+        Ok(self.renderer.effect_programs.clone().unwrap())
+    }
+
+    /// Copies `src` of the framebuffer into `buffer` at `at`
+    fn copy_framebuffer_into(
+        &mut self,
+        buffer: ffi::types::GLuint,
+        src: Rectangle<i32, Physical>,
+        at: Point<i32, Physical>,
+    ) -> Result<(), GlesError> {
+        // glBlitFramebuffer is sadly only available for GLES 3.0 and higher
+        if self.renderer.gl_version < version::GLES_3_0 {
+            return Err(GlesError::GLVersionNotSupported(version::GLES_3_0));
+        }
+
+        let gl = &self.renderer.gl;
+        unsafe {
+            let mut framebuffer = 0;
+            gl.GetIntegerv(ffi::DRAW_FRAMEBUFFER_BINDING, &mut framebuffer);
+            gl.BindFramebuffer(ffi::DRAW_FRAMEBUFFER, buffer);
+            // the buffer may be larger than the scissor box of the frame
+            gl.Disable(ffi::SCISSOR_TEST);
+            gl.BlitFramebuffer(
+                src.loc.x,
+                src.loc.y,
+                src.loc.x + src.size.w,
+                src.loc.y + src.size.h,
+                at.x,
+                at.y,
+                at.x + src.size.w,
+                at.y + src.size.h,
+                ffi::COLOR_BUFFER_BIT,
+                ffi::NEAREST,
+            );
+            gl.Enable(ffi::SCISSOR_TEST);
+            gl.BindFramebuffer(ffi::DRAW_FRAMEBUFFER, framebuffer as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Renders `src` of `texture` into the whole `buffer` of `size` with `program`
+    fn render_offscreen(
+        &mut self,
+        buffer: ffi::types::GLuint,
+        size: Size<i32, BufferCoord>,
+        texture: &GlesTexture,
+        src: Rectangle<i32, BufferCoord>,
+        program: &GlesTexProgram,
+        additional_uniforms: &[Uniform<'_>],
+    ) -> Result<(), GlesError> {
+        let projection = std::mem::replace(&mut self.current_projection, texture_projection(size));
+        let tex_matrix = build_texture_mat(
+            src.to_f64(),
+            Rectangle::from_size((size.w, size.h).into()),
+            texture.size(),
+            Transform::Normal,
+        );
+
+        let mut framebuffer = 0;
+        let mut viewport = [0; 4];
+        unsafe {
+            self.renderer
+                .gl
+                .GetIntegerv(ffi::FRAMEBUFFER_BINDING, &mut framebuffer);
+            self.renderer.gl.GetIntegerv(ffi::VIEWPORT, viewport.as_mut_ptr());
+            self.renderer.gl.BindFramebuffer(ffi::FRAMEBUFFER, buffer);
+            self.renderer.gl.Viewport(0, 0, size.w, size.h);
+        }
+        let res = self.render_texture_internal(
+            texture,
+            tex_matrix,
+            Matrix3::identity(),
+            Some([0.0, 0.0, size.w as f32, size.h as f32]),
+            1.0,
+            Some(program),
+            additional_uniforms,
+        );
+
+        self.current_projection = projection;
+        unsafe {
+            self.renderer
+                .gl
+                .BindFramebuffer(ffi::FRAMEBUFFER, framebuffer as u32);
+            self.renderer
+                .gl
+                .Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+        res
+    }
+
+    /// Blurs the content already rendered beneath `dst` using a dual kawase blur.
+    ///
+    /// Only the `damage` relative to `dst` is redrawn, but the blur samples the framebuffer up to
+    /// a radius of [`BlurElement::radius`](super::element::BlurElement::radius) around it, which
+    /// has to be up to date. `passes` controls the number of times the content is downsampled
+    /// and `offset` the distance of the samples of each pass, both increase the strength of the blur.
+    ///
+    /// The content outside of the current output is treated as if it had the color of its closest
+    /// pixel on the output.
+    ///
+    /// Requires GLES 3.0.
+    #[profiling::function]
+    pub fn render_blur(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        passes: u32,
+        offset: f32,
+        alpha: f32,
+    ) -> Result<(), GlesError> {
+        let passes = passes.max(1) as usize;
+        let radius = blur_radius(passes as u32, offset);
+        let Some(sampled) = Rectangle::new(
+            dst.loc - Point::from((radius, radius)),
+            dst.size + Size::from((radius * 2, radius * 2)),
+        )
+        .intersection(Rectangle::from_size(self.size)) else {
+            return Ok(());
+        };
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        let programs = self.effect_programs()?;
+        let format = self.effect_format();
+        let fb_sampled = self.framebuffer_rect(sampled);
+        let fb_dst = self.framebuffer_rect(dst);
+
+        // size of every level of the blur, each one half the size of the previous one
+        let sizes = (0..=passes)
+            .map(|level| {
+                Size::<i32, BufferCoord>::from((
+                    (fb_sampled.size.w >> level).max(1),
+                    (fb_sampled.size.h >> level).max(1),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let mut buffers = std::mem::take(&mut self.renderer.effect_buffers.blur);
+        if buffers.len() < sizes.len() {
+            buffers.resize_with(sizes.len(), || None);
+        }
+        let res = (|| {
+            let mut levels = Vec::with_capacity(sizes.len());
+            for (slot, size) in buffers.iter_mut().zip(&sizes) {
+                let buffer = unsafe { effect_buffer(self.renderer, slot, format, *size)? };
+                levels.push((buffer.fbo, buffer.texture.clone()));
+            }
+            self.copy_framebuffer_into(levels[0].0, fb_sampled, Point::default())?;
+
+            // intermediate passes render into the levels, without blending or debug tint
+            let filters = (self.renderer.min_filter, self.renderer.max_filter);
+            let debug_flags = std::mem::replace(&mut self.renderer.debug_flags, DebugFlags::empty());
+            self.renderer.min_filter = TextureFilter::Linear;
+            self.renderer.max_filter = TextureFilter::Linear;
+            unsafe { self.renderer.gl.Disable(ffi::BLEND) };
+
+            let uniforms = |size: Size<i32, BufferCoord>| {
+                [
+                    Uniform::new("half_pixel", (0.5 / size.w as f32, 0.5 / size.h as f32)),
+                    Uniform::new("offset", offset),
+                ]
+            };
+            let passes_res = (|| {
+                for level in 0..passes {
+                    self.render_offscreen(
+                        levels[level + 1].0,
+                        sizes[level + 1],
+                        &levels[level].1,
+                        Rectangle::from_size(sizes[level]),
+                        &programs.blur_down,
+                        &uniforms(sizes[level]),
+                    )?;
+                }
+                for level in (1..passes).rev() {
+                    self.render_offscreen(
+                        levels[level].0,
+                        sizes[level],
+                        &levels[level + 1].1,
+                        Rectangle::from_size(sizes[level + 1]),
+                        &programs.blur_up,
+                        &uniforms(sizes[level + 1]),
+                    )?;
+                }
+                Ok::<_, GlesError>(())
+            })();
+
+            unsafe {
+                self.renderer.gl.Enable(ffi::BLEND);
+                self.renderer.gl.BlendFunc(ffi::ONE, ffi::ONE_MINUS_SRC_ALPHA);
+            }
+            self.renderer.debug_flags = debug_flags;
+            passes_res?;
+
+            // the last upsampling pass draws into the frame, the first level has half the size
+            // of the sampled region
+            let src = Rectangle::<f64, BufferCoord>::new(
+                (
+                    (fb_dst.loc.x - fb_sampled.loc.x) as f64 / 2.0,
+                    (fb_dst.loc.y - fb_sampled.loc.y) as f64 / 2.0,
+                )
+                    .into(),
+                (fb_dst.size.w as f64 / 2.0, fb_dst.size.h as f64 / 2.0).into(),
+            );
+            let res = self.render_texture_from_to(
+                &levels[1].1,
+                src,
+                dst,
+                damage,
+                &[],
+                self.transform.invert(),
+                alpha,
+                Some(&programs.blur_up),
+                &uniforms(sizes[1]),
+            );
+            (self.renderer.min_filter, self.renderer.max_filter) = filters;
+            res
+        })();
+        self.renderer.effect_buffers.blur = buffers;
+        res
+    }
+
+    /// Saves the background of the corners of `dst` to clip them to a rounded rectangle
+    ///
+    /// The content drawn into the corners afterwards is clipped to a circle of `radius`, including
+    /// anti-aliasing, by [`GlesFrame::restore_rounded_corners`]. Only the `damage` relative to
+    /// `dst` is restored. Clips may be nested, but have to be restored in reverse order.
+    ///
+    /// Requires GLES 3.0.
+    #[profiling::function]
+    pub fn save_rounded_corners(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        radius: i32,
+        damage: &[Rectangle<i32, Physical>],
+    ) -> Result<RoundedCorners, GlesError> {
+        let radius = radius.min(dst.size.w / 2).min(dst.size.h / 2).max(0);
+        let damage = if radius > 0 {
+            // damage is relative to `dst`, keep it in frame coordinates like the corners
+            corners(dst, radius)
+                .iter()
+                .flat_map(|corner| {
+                    damage.iter().filter_map(|rect| {
+                        Rectangle::new(rect.loc + dst.loc, rect.size).intersection(*corner)
+                    })
+                })
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        let depth = self.corner_depth;
+        self.corner_depth += 1;
+        let corners = RoundedCorners {
+            depth,
+            dst,
+            radius,
+            damage,
+        };
+        if corners.damage.is_empty() {
+            return Ok(corners);
+        }
+
+        let format = self.effect_format();
+        let mut buffers = std::mem::take(&mut self.renderer.effect_buffers.corners);
+        if buffers.len() <= depth {
+            buffers.resize_with(depth + 1, || None);
+        }
+        let res = (|| {
+            // all corners are stored next to each other, they are squares in any orientation
+            let size = Size::from((radius * 4, radius));
+            let buffer = unsafe { effect_buffer(self.renderer, &mut buffers[depth], format, size)? }.fbo;
+            for (i, corner) in corners_of(&corners).into_iter().enumerate() {
+                let src = self.framebuffer_rect(corner);
+                self.copy_framebuffer_into(buffer, src, Point::from((i as i32 * radius, 0)))?;
+            }
+            Ok(())
+        })();
+        self.renderer.effect_buffers.corners = buffers;
+        res.map(|_| corners)
+    }
+
+    /// Clips the content drawn since [`GlesFrame::save_rounded_corners`] to the rounded rectangle
+    #[profiling::function]
+    pub fn restore_rounded_corners(&mut self, corners: RoundedCorners) -> Result<(), GlesError> {
+        self.corner_depth = corners.depth;
+        if corners.damage.is_empty() {
+            return Ok(());
+        }
+
+        let programs = self.effect_programs()?;
+        let Some(texture) = self.renderer.effect_buffers.corners[corners.depth]
+            .as_ref()
+            .map(|buffer| buffer.texture.clone())
+        else {
+            return Ok(());
+        };
+        let radius = corners.radius;
+        let filters = (self.renderer.min_filter, self.renderer.max_filter);
+        let debug_flags = std::mem::replace(&mut self.renderer.debug_flags, DebugFlags::empty());
+        self.renderer.min_filter = TextureFilter::Nearest;
+        self.renderer.max_filter = TextureFilter::Nearest;
+
+        let res = (|| {
+            for (i, corner) in corners_of(&corners).into_iter().enumerate() {
+                let damage = corners
+                    .damage
+                    .iter()
+                    .filter_map(|rect| rect.intersection(corner))
+                    .map(|mut rect| {
+                        rect.loc -= corner.loc;
+                        rect
+                    })
+                    .collect::<Vec<_>>();
+                if damage.is_empty() {
+                    continue;
+                }
+
+                // the pixel at the outer corner of the clip, to find its orientation in the buffer
+                let outer = Point::from((
+                    if corner.loc.x == corners.dst.loc.x {
+                        corner.loc.x
+                    } else {
+                        corner.loc.x + radius - 1
+                    },
+                    if corner.loc.y == corners.dst.loc.y {
+                        corner.loc.y
+                    } else {
+                        corner.loc.y + radius - 1
+                    },
+                ));
+                let fb_corner = self.framebuffer_rect(corner);
+                let fb_outer = self.framebuffer_rect(Rectangle::new(outer, (1, 1).into()));
+                let center = (
+                    if fb_outer.loc.x == fb_corner.loc.x {
+                        radius
+                    } else {
+                        0
+                    } as f32,
+                    if fb_outer.loc.y == fb_corner.loc.y {
+                        radius
+                    } else {
+                        0
+                    } as f32,
+                );
+                let origin = i as i32 * radius;
+                let uniforms = |mask: f32| {
+                    [
+                        Uniform::new("tex_size", (radius as f32 * 4.0, radius as f32)),
+                        Uniform::new("origin", (origin as f32, 0.0)),
+                        Uniform::new("center", center),
+                        Uniform::new("radius", radius as f32),
+                        Uniform::new("mask", mask),
+                    ]
+                };
+                let src = Rectangle::new((origin, 0).into(), (radius, radius).into()).to_f64();
+
+                // scale the clipped content by its coverage, then add the background outside of it
+                unsafe { self.renderer.gl.BlendFunc(ffi::ZERO, ffi::SRC_COLOR) };
+                self.render_texture_from_to(
+                    &texture,
+                    src,
+                    corner,
+                    &damage,
+                    &[],
+                    self.transform.invert(),
+                    1.0,
+                    Some(&programs.rounded_corner),
+                    &uniforms(1.0),
+                )?;
+                unsafe { self.renderer.gl.BlendFunc(ffi::ONE, ffi::ONE) };
+                self.render_texture_from_to(
+                    &texture,
+                    src,
+                    corner,
+                    &damage,
+                    &[],
+                    self.transform.invert(),
+                    1.0,
+                    Some(&programs.rounded_corner),
+                    &uniforms(0.0),
+                )?;
+            }
+            Ok(())
+        })();
+
+        unsafe { self.renderer.gl.BlendFunc(ffi::ONE, ffi::ONE_MINUS_SRC_ALPHA) };
+        self.renderer.debug_flags = debug_flags;
+        (self.renderer.min_filter, self.renderer.max_filter) = filters;
+        res
+    }
+}
+
+/// The square corners of `dst` with a side length of `radius`
+pub(super) fn corners(dst: Rectangle<i32, Physical>, radius: i32) -> [Rectangle<i32, Physical>; 4] {
+    let size = Size::from((radius, radius));
+    let right = dst.loc.x + dst.size.w - radius;
+    let bottom = dst.loc.y + dst.size.h - radius;
+    [
+        Rectangle::new(dst.loc, size),
+        Rectangle::new((right, dst.loc.y).into(), size),
+        Rectangle::new((dst.loc.x, bottom).into(), size),
+        Rectangle::new((right, bottom).into(), size),
+    ]
+}
+
+fn corners_of(corners: &RoundedCorners) -> [Rectangle<i32, Physical>; 4] {
+    self::corners(corners.dst, corners.radius)
+}
@@ -2,13 +2,17 @@
 
 use crate::{
     backend::renderer::{
+        color::ColorDescription,
         element::{texture::TextureRenderElement, Element, Id, Kind, RenderElement, UnderlyingStorage},
         utils::{CommitCounter, DamageSet, OpaqueRegions},
     },
     utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Transform},
 };
 
-use super::{GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, GlesTexProgram, GlesTexture, Uniform};
+use super::{
+    effect::{blur_radius, corners},
+    GlesError, GlesFrame, GlesPixelProgram, GlesRenderer, GlesTexProgram, GlesTexture, Uniform,
+};
 
 /// Render element for drawing with a gles2 pixel shader
 #[derive(Debug, Clone)]
@@ -223,3 +227,228 @@ impl RenderElement<GlesRenderer> for TextureShaderElement {
         )
     }
 }
+
+/// Render element blurring the content beneath it
+///
+/// The element samples the content already rendered below it using a dual kawase blur, see
+/// [`GlesFrame::render_blur`]. The region it samples is reported via [`Element::backdrop_region`],
+/// so the [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker) redraws
+/// the element entirely whenever the content beneath it is damaged.
+///
+/// Combine it with a [`RoundedCornerElement`] to blur the background of rounded windows.
+#[derive(Debug, Clone)]
+pub struct BlurElement {
+    id: Id,
+    commit_counter: CommitCounter,
+    area: Rectangle<i32, Logical>,
+    passes: u32,
+    offset: f32,
+    alpha: f32,
+    kind: Kind,
+}
+
+impl BlurElement {
+    /// Create a new [`BlurElement`] blurring the content beneath `area`
+    ///
+    /// `passes` is the number of times the content is downsampled, at least 1, and `offset` the
+    /// distance of the samples of each pass. Both increase the strength of the blur.
+    pub fn new(area: Rectangle<i32, Logical>, passes: u32, offset: f32, alpha: f32, kind: Kind) -> Self {
+        BlurElement {
+            id: Id::new(),
+            commit_counter: CommitCounter::default(),
+            area,
+            passes: passes.max(1),
+            offset,
+            alpha,
+            kind,
+        }
+    }
+
+    /// Move or resize the blurred area
+    pub fn resize(&mut self, area: Rectangle<i32, Logical>) {
+        if self.area != area {
+            self.area = area;
+            self.commit_counter.increment();
+        }
+    }
+
+    /// Change the strength of the blur, see [`BlurElement::new`]
+    pub fn set_strength(&mut self, passes: u32, offset: f32) {
+        let passes = passes.max(1);
+        if self.passes != passes || self.offset != offset {
+            self.passes = passes;
+            self.offset = offset;
+            self.commit_counter.increment();
+        }
+    }
+
+    /// Distance in physical pixels around the element the blur samples from
+    pub fn radius(&self) -> i32 {
+        blur_radius(self.passes, self.offset)
+    }
+}
+
+impl Element for BlurElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit_counter
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.area.size.to_f64().to_buffer(1.0, Transform::Normal))
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.area.to_physical_precise_round(scale)
+    }
+
+    fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        let radius = self.radius();
+        let size = self.geometry(scale).size;
+        Some(Rectangle::new(
+            (-radius, -radius).into(),
+            (size.w + radius * 2, size.h + radius * 2).into(),
+        ))
+    }
+}
+
+impl RenderElement<GlesRenderer> for BlurElement {
+    #[profiling::function]
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        _src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        frame.render_blur(dst, damage, self.passes, self.offset, self.alpha)
+    }
+
+    #[inline]
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage<'_>> {
+        None
+    }
+}
+
+/// Render element clipping another element to a rectangle with rounded corners
+///
+/// The corners are anti-aliased, see [`GlesFrame::save_rounded_corners`]. The corners are
+/// removed from the opaque regions of the element, so the content beneath them is drawn.
+///
+/// The element shares the [`Id`] and commits of the wrapped element, changing the radius
+/// of an element is not tracked as damage.
+#[derive(Debug)]
+pub struct RoundedCornerElement<E> {
+    element: E,
+    radius: i32,
+}
+
+impl<E: Element> RoundedCornerElement<E> {
+    /// Clip `element` to rounded corners with a `radius` in physical pixels
+    ///
+    /// The radius is limited to half the size of the element.
+    pub fn new(element: E, radius: i32) -> Self {
+        RoundedCornerElement { element, radius }
+    }
+
+    /// Returns the wrapped element
+    pub fn element(&self) -> &E {
+        &self.element
+    }
+
+    /// Radius of the corners in physical pixels
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+
+    fn corners(&self, scale: Scale<f64>) -> [Rectangle<i32, Physical>; 4] {
+        let size = self.element.geometry(scale).size;
+        let radius = self.radius.min(size.w / 2).min(size.h / 2).max(0);
+        corners(Rectangle::from_size(size), radius)
+    }
+}
+
+impl<E: Element> Element for RoundedCornerElement<E> {
+    fn id(&self) -> &Id {
+        self.element.id()
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.element.current_commit()
+    }
+
+    fn location(&self, scale: Scale<f64>) -> Point<i32, Physical> {
+        self.element.location(scale)
+    }
+
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        self.element.src()
+    }
+
+    fn transform(&self) -> Transform {
+        self.element.transform()
+    }
+
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> {
+        self.element.geometry(scale)
+    }
+
+    fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        self.element.damage_since(scale, commit)
+    }
+
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        Rectangle::subtract_rects_many(self.element.opaque_regions(scale), self.corners(scale))
+            .into_iter()
+            .collect()
+    }
+
+    fn alpha(&self) -> f32 {
+        self.element.alpha()
+    }
+
+    fn kind(&self) -> Kind {
+        self.element.kind()
+    }
+
+    fn color_description(&self) -> Option<ColorDescription> {
+        self.element.color_description()
+    }
+
+    fn backdrop_region(&self, scale: Scale<f64>) -> Option<Rectangle<i32, Physical>> {
+        self.element.backdrop_region(scale)
+    }
+}
+
+impl<E: RenderElement<GlesRenderer>> RenderElement<GlesRenderer> for RoundedCornerElement<E> {
+    #[profiling::function]
+    fn draw(
+        &self,
+        frame: &mut GlesFrame<'_, '_>,
+        src: Rectangle<f64, Buffer>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        let corners = frame.save_rounded_corners(dst, self.radius, damage)?;
+        self.element.draw(frame, src, dst, damage, opaque_regions)?;
+        frame.restore_rounded_corners(corners)
+    }
+
+    #[inline]
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage<'_>> {
+        None
+    }
+}
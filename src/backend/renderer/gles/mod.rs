@@ -19,6 +19,8 @@ use std::{
 };
 use tracing::{debug, error, info, info_span, instrument, span, span::EnteredSpan, trace, warn, Level};
 
+mod color;
+mod effect;
 pub mod element;
 mod error;
 pub mod format;
@@ -27,17 +29,23 @@ mod texture;
 mod uniform;
 mod version;
 
+pub use effect::RoundedCorners;
 pub use error::*;
 use format::*;
 pub use shaders::*;
 pub use texture::*;
 pub use uniform::*;
 
-use self::version::GlVersion;
+use self::{
+    color::{ColorBuffer, ColorPass, ColorPrograms},
+    effect::{EffectBuffers, EffectPrograms},
+    version::GlVersion,
+};
 
 use super::{
-    sync::SyncPoint, Bind, Blit, BlitFrame, Color32F, ContextId, DebugFlags, ExportMem, Frame, ImportDma,
-    ImportMem, Offscreen, Renderer, RendererSuper, Texture, TextureFilter, TextureMapping,
+    color::ColorDescription, sync::SyncPoint, Bind, Blit, BlitFrame, Color32F, ContextId, DebugFlags,
+    ExportMem, Frame, ImportDma, ImportMem, Offscreen, Renderer, RendererSuper, Texture, TextureFilter,
+    TextureMapping,
 };
 use crate::{
     backend::{
@@ -52,7 +60,7 @@ use crate::{
             EGLContext, EGLSurface, MakeCurrentError,
         },
     },
-    utils::{Buffer as BufferCoord, Physical, Rectangle, Size, Transform},
+    utils::{Buffer as BufferCoord, Physical, Point, Rectangle, Size, Transform},
 };
 
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
@@ -292,6 +300,9 @@ pub enum Capability {
     ExportFence,
     /// GlesRenderer supports GL debug
     Debug,
+    /// GlesRenderer supports blending in a linear fp16 buffer,
+    /// see [`GlesRenderer::set_output_color_description`]
+    ColorTransformations,
 }
 
 /// GL resources need to be destroyed with a context active on the current thread,
@@ -368,6 +379,7 @@ pub struct GlesRenderer {
     min_filter: TextureFilter,
     max_filter: TextureFilter,
     debug_flags: DebugFlags,
+    output_color_description: Option<ColorDescription>,
 
     // internals
     egl: EGLContext,
@@ -383,6 +395,8 @@ pub struct GlesRenderer {
     // shaders
     tex_program: GlesTexProgram,
     solid_program: GlesSolidProgram,
+    color_programs: Option<ColorPrograms>,
+    effect_programs: Option<EffectPrograms>,
 
     // caches
     buffers: Vec<GlesBuffer>,
@@ -391,6 +405,8 @@ pub struct GlesRenderer {
     vertices: Vec<f32>,
     non_opaque_damage: Vec<Rectangle<i32, Physical>>,
     opaque_damage: Vec<Rectangle<i32, Physical>>,
+    color_buffer: Option<ColorBuffer>,
+    effect_buffers: EffectBuffers,
 
     // markers
     _not_send: PhantomData<*mut ()>,
@@ -414,6 +430,9 @@ pub struct GlesFrame<'frame, 'buffer> {
     transform: Transform,
     size: Size<i32, Physical>,
     tex_program_override: Option<(GlesTexProgram, Vec<Uniform<'static>>)>,
+    color_pass: Option<ColorPass>,
+    /// Number of rounded corner clips currently saved
+    corner_depth: usize,
     finished: AtomicBool,
 
     span: EnteredSpan,
@@ -427,6 +446,7 @@ impl fmt::Debug for GlesFrame<'_, '_> {
             .field("current_projection", &self.current_projection)
             .field("transform", &self.transform)
             .field("tex_program_override", &self.tex_program_override)
+            .field("color_pass", &self.color_pass)
             .field("size", &self.size)
             .field("finished", &self.finished)
             .finish_non_exhaustive()
@@ -448,6 +468,7 @@ impl fmt::Debug for GlesRenderer {
             .field("vbos", &self.vbos)
             .field("min_filter", &self.min_filter)
             .field("max_filter", &self.max_filter)
+            .field("output_color_description", &self.output_color_description)
             .finish()
     }
 }
@@ -536,6 +557,16 @@ impl GlesRenderer {
             debug!("GL Debug is supported");
         }
 
+        // required to render into fp16 buffers
+        if gl_version >= version::GLES_3_0
+            && exts
+                .iter()
+                .any(|ext| ext == "GL_EXT_color_buffer_half_float" || ext == "GL_EXT_color_buffer_float")
+        {
+            capabilities.push(Capability::ColorTransformations);
+            debug!("Color transformations are supported");
+        }
+
         Ok(capabilities)
     }
 
@@ -599,6 +630,10 @@ impl GlesRenderer {
                 Capability::Renderbuffer => GlesError::GLExtensionNotSupported(&["GL_OES_rgb8_rgba8"]),
                 Capability::ExportFence => GlesError::GLExtensionNotSupported(&["GL_OES_EGL_sync"]),
                 Capability::Debug => GlesError::GLExtensionNotSupported(&["GL_KHR_debug"]),
+                Capability::ColorTransformations => GlesError::GLExtensionNotSupported(&[
+                    "GL_EXT_color_buffer_half_float",
+                    "GL_EXT_color_buffer_float",
+                ]),
             };
             return Err(err);
         };
@@ -709,6 +744,8 @@ impl GlesRenderer {
 
             tex_program,
             solid_program,
+            color_programs: None,
+            effect_programs: None,
             vbos,
             min_filter: TextureFilter::Linear,
             max_filter: TextureFilter::Linear,
@@ -718,8 +755,11 @@ impl GlesRenderer {
             vertices: Vec::with_capacity(6 * 16),
             non_opaque_damage: Vec::with_capacity(16),
             opaque_damage: Vec::with_capacity(16),
+            color_buffer: None,
+            effect_buffers: EffectBuffers::default(),
 
             debug_flags: DebugFlags::empty(),
+            output_color_description: None,
             _not_send: PhantomData,
             span,
             gl_debug_span,
@@ -797,6 +837,92 @@ impl GlesRenderer {
     pub fn capabilities(&self) -> &[Capability] {
         &self.capabilities
     }
+
+    /// Set the [`ColorDescription`] of the targets of subsequent frames, enabling the color pipeline
+    ///
+    /// With a description set, frames are blended in a linear fp16 buffer using the primaries of the
+    /// description. Textures are decoded to linear light according to the description set via
+    /// [`Frame::set_color_description`] and the buffer is encoded to the transfer function of the
+    /// output when the frame is finished. `None` disables the pipeline, which is the default.
+    ///
+    /// As the renderer is usually shared between outputs, this has to be set before rendering each output.
+    ///
+    /// Requires [`Capability::ColorTransformations`].
+    ///
+    /// *Note*: Custom texture programs, including [`GlesFrame::override_default_tex_program`], and pixel
+    /// shaders are not decoded and have to output linear values. Blits bypass the pipeline.
+    /// Only a single fp16 buffer is kept, so content outside of the regions drawn during a frame
+    /// is undefined before being drawn again.
+    pub fn set_output_color_description(
+        &mut self,
+        description: Option<ColorDescription>,
+    ) -> Result<(), GlesError> {
+        if description.is_none() {
+            self.output_color_description = None;
+            self.color_buffer = None;
+            return Ok(());
+        }
+
+        if !self.capabilities.contains(&Capability::ColorTransformations) {
+            return Err(GlesError::GLExtensionNotSupported(&[
+                "GL_EXT_color_buffer_half_float",
+                "GL_EXT_color_buffer_float",
+            ]));
+        }
+        if self.color_programs.is_none() {
+            let programs = unsafe {
+                self.egl.make_current()?;
+                ColorPrograms::compile(&self.gl, self.gles_cleanup().sender.clone())?
+            };
+            self.color_programs = Some(programs);
+        }
+
+        self.output_color_description = description;
+        Ok(())
+    }
+
+    /// Returns the [`ColorDescription`] set via [`GlesRenderer::set_output_color_description`]
+    pub fn output_color_description(&self) -> Option<&ColorDescription> {
+        self.output_color_description.as_ref()
+    }
+
+    fn color_buffer(&mut self, size: Size<i32, BufferCoord>) -> Result<&ColorBuffer, GlesError> {
+        if self
+            .color_buffer
+            .as_ref()
+            .is_some_and(|buffer| buffer.texture.size() == size)
+        {
+            return Ok(self.color_buffer.as_ref().unwrap());
+        }
+
+        self.color_buffer = None;
+        let texture = Offscreen::<GlesTexture>::create_buffer(self, Fourcc::Abgr16161616f, size)?;
+        let mut fbo = 0;
+        unsafe {
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            let status = self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+
+            if status != ffi::FRAMEBUFFER_COMPLETE {
+                self.gl.DeleteFramebuffers(1, &mut fbo as *mut _);
+                return Err(GlesError::FramebufferBindingError);
+            }
+        }
+
+        Ok(self.color_buffer.insert(ColorBuffer {
+            texture,
+            fbo,
+            destruction_callback_sender: self.gles_cleanup().sender.clone(),
+        }))
+    }
 }
 
 #[cfg(feature = "wayland_frontend")]
@@ -1687,9 +1813,7 @@ impl<'buffer> BlitFrame<GlesTarget<'buffer>> for GlesFrame<'_, 'buffer> {
         filter: TextureFilter,
     ) -> Result<(), Self::Error> {
         let res = self.renderer.blit(self.target, to, src, dst, filter);
-        self.target
-            .0
-            .make_current(&self.renderer.gl, &self.renderer.egl)?;
+        self.make_current()?;
         res
     }
 
@@ -1701,9 +1825,7 @@ impl<'buffer> BlitFrame<GlesTarget<'buffer>> for GlesFrame<'_, 'buffer> {
         filter: TextureFilter,
     ) -> Result<(), Self::Error> {
         let res = self.renderer.blit(from, self.target, src, dst, filter);
-        self.target
-            .0
-            .make_current(&self.renderer.gl, &self.renderer.egl)?;
+        self.make_current()?;
         res
     }
 }
@@ -2037,11 +2159,15 @@ impl GlesFrame<'_, '_> {
     /// or check the source code of the version of Smithay you are using to ensure
     /// your changes don't interfere with the renderer's behavior.
     /// Doing otherwise can lead to rendering errors while using other functions of this renderer.
+    ///
+    /// When the color pipeline is enabled, the offscreen buffer of the pipeline is bound instead of the target
+    /// and considered damaged entirely.
     #[instrument(level = "trace", parent = &self.span, skip_all)]
     pub fn with_context<F, R>(&mut self, func: F) -> Result<R, GlesError>
     where
         F: FnOnce(&ffi::Gles2) -> R,
     {
+        self.damage_color_buffer_fully();
         Ok(func(&self.renderer.gl))
     }
 }
@@ -2092,9 +2218,31 @@ impl Renderer for GlesRenderer {
     where
         'buffer: 'frame,
     {
+        // the color buffer has to be created before binding the target,
+        // as creating it might change the current surface
+        let color_pass = match self.output_color_description {
+            Some(output) => {
+                let programs = self.color_programs.clone().unwrap();
+                let buffer = self.color_buffer(output_size.to_logical(1).to_buffer(1, Transform::Normal))?;
+                Some(ColorPass {
+                    output,
+                    input: ColorDescription::srgb(),
+                    buffer: buffer.texture.clone(),
+                    fbo: buffer.fbo,
+                    programs,
+                    damage: Some(Vec::new()),
+                })
+            }
+            None => None,
+        };
+
         target.0.make_current(&self.gl, &self.egl)?;
 
         unsafe {
+            if let Some(pass) = color_pass.as_ref() {
+                self.gl.BindFramebuffer(ffi::FRAMEBUFFER, pass.fbo);
+            }
+
             self.gl.Viewport(0, 0, output_size.w, output_size.h);
 
             self.gl.Scissor(0, 0, output_size.w, output_size.h);
@@ -2140,6 +2288,8 @@ impl Renderer for GlesRenderer {
             transform,
             size: output_size,
             tex_program_override: None,
+            color_pass,
+            corner_depth: 0,
             finished: AtomicBool::new(false),
 
             span,
@@ -2331,6 +2481,12 @@ impl Frame for GlesFrame<'_, '_> {
         )
     }
 
+    fn set_color_description(&mut self, description: Option<ColorDescription>) {
+        if let Some(pass) = self.color_pass.as_mut() {
+            pass.input = description.unwrap_or_default();
+        }
+    }
+
     fn transformation(&self) -> Transform {
         self.transform
     }
@@ -2349,12 +2505,16 @@ impl Frame for GlesFrame<'_, '_> {
 impl GlesFrame<'_, '_> {
     #[profiling::function]
     fn finish_internal(&mut self) -> Result<SyncPoint, GlesError> {
-        let _guard = self.span.enter();
-
         if self.finished.swap(true, Ordering::SeqCst) {
             return Ok(SyncPoint::signaled());
         }
 
+        if let Some(pass) = self.color_pass.take() {
+            self.encode_color_buffer(pass)?;
+        }
+
+        let _guard = self.span.enter();
+
         unsafe {
             self.renderer.gl.Disable(ffi::SCISSOR_TEST);
             self.renderer.gl.Disable(ffi::BLEND);
@@ -2384,6 +2544,71 @@ impl GlesFrame<'_, '_> {
         Ok(SyncPoint::signaled())
     }
 
+    fn make_current(&mut self) -> Result<(), GlesError> {
+        self.target
+            .0
+            .make_current(&self.renderer.gl, &self.renderer.egl)?;
+        if let Some(pass) = self.color_pass.as_ref() {
+            unsafe { self.renderer.gl.BindFramebuffer(ffi::FRAMEBUFFER, pass.fbo) };
+        }
+        Ok(())
+    }
+
+    fn damage_color_buffer(&mut self, offset: Point<i32, Physical>, damage: &[Rectangle<i32, Physical>]) {
+        if let Some(ColorPass {
+            damage: Some(buffer_damage),
+            ..
+        }) = self.color_pass.as_mut()
+        {
+            buffer_damage.extend(
+                damage
+                    .iter()
+                    .map(|rect| Rectangle::new(rect.loc + offset, rect.size)),
+            );
+        }
+    }
+
+    fn damage_color_buffer_fully(&mut self) {
+        if let Some(pass) = self.color_pass.as_mut() {
+            pass.damage = None;
+        }
+    }
+
+    // Encodes the damaged regions of the color buffer into the actual target
+    fn encode_color_buffer(&mut self, pass: ColorPass) -> Result<(), GlesError> {
+        self.target
+            .0
+            .make_current(&self.renderer.gl, &self.renderer.egl)?;
+
+        let damage = pass
+            .damage
+            .clone()
+            .unwrap_or_else(|| vec![Rectangle::from_size(self.size)]);
+        let uniforms = pass.encode_uniforms();
+        // the buffer was already tinted while rendering the elements
+        let debug_flags = mem::replace(&mut self.renderer.debug_flags, DebugFlags::empty());
+        unsafe {
+            self.renderer.gl.Disable(ffi::BLEND);
+        }
+
+        // The buffer has the same layout as the target, so the inverse of the
+        // output transformation maps it onto the target pixel by pixel.
+        let res = self.render_texture_from_to(
+            &pass.buffer,
+            Rectangle::from_size(pass.buffer.size()).to_f64(),
+            Rectangle::from_size(self.size),
+            &damage,
+            &[],
+            self.transform.invert(),
+            1.0,
+            Some(&pass.programs.encode),
+            &uniforms,
+        );
+
+        self.renderer.debug_flags = debug_flags;
+        res
+    }
+
     /// Overrides the default texture shader used, if none is specified.
     ///
     /// This affects calls to [`Frame::render_texture_at`] or [`Frame::render_texture_from_to`] as well as
@@ -2416,6 +2641,12 @@ impl GlesFrame<'_, '_> {
             return Ok(());
         }
 
+        self.damage_color_buffer(dest.loc, damage);
+        let color = self
+            .color_pass
+            .as_ref()
+            .map_or(color, |pass| pass.decode_color(color));
+
         let mut mat = Matrix3::<f32>::identity();
         mat = self.current_projection * mat;
 
@@ -2552,6 +2783,8 @@ impl GlesFrame<'_, '_> {
             return Ok(());
         }
 
+        self.damage_color_buffer(dest.loc, damage);
+
         let mut tex_mat = build_texture_mat(src, dest, tex_size, transform);
         if texture.0.y_inverted {
             tex_mat = Matrix3::new(1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0) * tex_mat;
@@ -2575,7 +2808,7 @@ impl GlesFrame<'_, '_> {
                 ]
             });
 
-            renderer.render_texture(
+            renderer.render_texture_internal(
                 texture,
                 tex_mat,
                 mat,
@@ -2664,10 +2897,35 @@ impl GlesFrame<'_, '_> {
     /// Additionally the matrix can be used to crop the texture.
     ///
     /// Optionally allows a custom texture program and matching additional uniforms to be passed in.
+    ///
+    /// When the color pipeline is enabled, the offscreen buffer of the pipeline is considered damaged entirely.
     #[instrument(level = "trace", skip(self, instances), parent = &self.span)]
     #[profiling::function]
     #[allow(clippy::too_many_arguments)]
     pub fn render_texture(
+        &mut self,
+        tex: &GlesTexture,
+        tex_matrix: Matrix3<f32>,
+        matrix: Matrix3<f32>,
+        instances: Option<impl IntoIterator<Item = ffi::types::GLfloat>>,
+        alpha: f32,
+        program: Option<&GlesTexProgram>,
+        additional_uniforms: &[Uniform<'_>],
+    ) -> Result<(), GlesError> {
+        self.damage_color_buffer_fully();
+        self.render_texture_internal(
+            tex,
+            tex_matrix,
+            matrix,
+            instances,
+            alpha,
+            program,
+            additional_uniforms,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_texture_internal(
         &mut self,
         tex: &GlesTexture,
         tex_matrix: Matrix3<f32>,
@@ -2724,10 +2982,21 @@ impl GlesFrame<'_, '_> {
         } else {
             ffi::TEXTURE_2D
         };
-        let (tex_program, additional_uniforms) = program
+        // content drawn with the default program gets decoded into the blending space
+        let color_uniforms;
+        let (tex_program, additional_uniforms) = match program
             .map(|p| (p, additional_uniforms))
             .or_else(|| self.tex_program_override.as_ref().map(|(p, a)| (p, &**a)))
-            .unwrap_or((&self.renderer.tex_program, &[]));
+        {
+            Some(program) => program,
+            None => match self.color_pass.as_ref() {
+                Some(pass) => {
+                    color_uniforms = pass.decode_uniforms();
+                    (&pass.programs.decode, &color_uniforms[..])
+                }
+                None => (&self.renderer.tex_program, &[][..]),
+            },
+        };
         let program_variant = tex_program.variant_for_format(
             if !tex.0.is_external { tex.0.format } else { None },
             tex.0.has_alpha,
@@ -2848,6 +3117,7 @@ impl GlesFrame<'_, '_> {
     ) -> Result<(), GlesError> {
         let fallback_damage = &[Rectangle::from_size(dest.size)];
         let damage = damage.unwrap_or(fallback_damage);
+        self.damage_color_buffer(dest.loc, damage);
 
         // prepare the vertices
         self.renderer.vertices.clear();
@@ -3065,10 +3335,230 @@ fn build_texture_mat(
 
 #[cfg(test)]
 mod tests {
-    use super::build_texture_mat;
-    use crate::utils::{Buffer, Physical, Rectangle, Size, Transform};
+    use super::{
+        build_texture_mat,
+        element::{BlurElement, RoundedCornerElement},
+        GlesRenderer, GlesTexture,
+    };
+    use crate::{
+        backend::{
+            allocator::Fourcc,
+            egl::{native::EGLSurfacelessDisplay, EGLContext, EGLDisplay},
+            renderer::{
+                damage::OutputDamageTracker,
+                element::{solid::SolidColorRenderElement, Id, Kind},
+                test::conformance,
+                utils::CommitCounter,
+                Bind, ExportMem, Offscreen,
+            },
+        },
+        utils::{Buffer, Logical, Physical, Point, Rectangle, Size, Transform},
+    };
     use cgmath::Vector3;
 
+    /// Renderer on the default device of mesa, llvmpipe if there is no gpu
+    ///
+    /// Returns `None` if egl is not available, the rendering tests are skipped in that case
+    /// unless `gles` is listed in [`conformance::REQUIRE_DEVICES_VAR`].
+    fn renderer() -> Option<GlesRenderer> {
+        let renderer = unsafe { EGLDisplay::new(EGLSurfacelessDisplay) }
+            .ok()
+            .and_then(|display| EGLContext::new(&display).ok())
+            .and_then(|context| unsafe { GlesRenderer::new(context) }.ok());
+        conformance::require_device("gles", renderer)
+    }
+
+    #[test]
+    fn conformance() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        if let Err(err) = conformance::check_offscreen::<_, GlesTexture>(&mut renderer, Fourcc::Abgr8888) {
+            panic!("{}", err);
+        }
+    }
+
+    crate::backend::renderer::element::render_elements! {
+        EffectRenderElement<=GlesRenderer>;
+        Solid=SolidColorRenderElement,
+        Blur=BlurElement,
+        Rounded=RoundedCornerElement<SolidColorRenderElement>,
+    }
+
+    const TRANSFORMS: [Transform; 8] = [
+        Transform::Normal,
+        Transform::_90,
+        Transform::_180,
+        Transform::_270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+
+    fn solid(id: &Id, geometry: (i32, i32, i32, i32), color: [f32; 4]) -> EffectRenderElement {
+        let (x, y, w, h) = geometry;
+        EffectRenderElement::Solid(SolidColorRenderElement::new(
+            id.clone(),
+            Rectangle::new((x, y).into(), (w, h).into()),
+            CommitCounter::default(),
+            color,
+            Kind::Unspecified,
+        ))
+    }
+
+    /// Renders `elements` into `texture` and returns the damage and the pixels of the output,
+    /// indexed by their position on the transformed output
+    fn render_effects(
+        renderer: &mut GlesRenderer,
+        damage_tracker: &mut OutputDamageTracker,
+        texture: &mut GlesTexture,
+        age: usize,
+        elements: &[EffectRenderElement],
+    ) -> (Vec<Rectangle<i32, Physical>>, impl Fn(i32, i32) -> [u8; 4]) {
+        let (size, _, transform): (Size<i32, Physical>, _, Transform) =
+            damage_tracker.mode().try_into().unwrap();
+        // the damage tracker renders with the inverse of the output transform
+        let transform = transform.invert();
+        let output_size = transform.transform_size(size);
+        let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+        let mut target = renderer.bind(texture).unwrap();
+        let damage = damage_tracker
+            .render_output(renderer, &mut target, age, elements, [0.0, 0.0, 0.0, 1.0])
+            .unwrap()
+            .damage
+            .cloned()
+            .unwrap_or_default();
+
+        let mapping = renderer
+            .copy_framebuffer(&target, Rectangle::from_size(buffer_size), Fourcc::Abgr8888)
+            .unwrap();
+        let data = renderer.map_texture(&mapping).unwrap().to_vec();
+        let pixel = move |x: i32, y: i32| {
+            let pixel =
+                transform.transform_rect_in(Rectangle::new((x, y).into(), (1, 1).into()), &output_size);
+            let offset = ((pixel.loc.y * size.w + pixel.loc.x) * 4) as usize;
+            data[offset..offset + 4].try_into().unwrap()
+        };
+        (damage, pixel)
+    }
+
+    #[test]
+    fn blur_element() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        let (background, mover) = (Id::new(), Id::new());
+        let blur = BlurElement::new(
+            Rectangle::<i32, Logical>::new((16, 8).into(), (32, 16).into()),
+            2,
+            1.0,
+            1.0,
+            Kind::Unspecified,
+        );
+        let scene = |mover_loc: (i32, i32)| {
+            vec![
+                EffectRenderElement::Blur(blur.clone()),
+                solid(&mover, (mover_loc.0, mover_loc.1, 4, 4), [1.0, 1.0, 1.0, 1.0]),
+                solid(&background, (0, 0, 32, 32), [1.0, 0.0, 0.0, 1.0]),
+                solid(&background, (32, 0, 32, 32), [0.0, 0.0, 1.0, 1.0]),
+            ]
+        };
+
+        for transform in TRANSFORMS {
+            let size = transform.transform_size(Size::<i32, Physical>::from((64, 32)));
+            let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+            let mut incremental: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, buffer_size).unwrap();
+            let mut full: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, buffer_size).unwrap();
+            let mut damage_tracker = OutputDamageTracker::new(size, 1.0, transform);
+
+            let _ = render_effects(
+                &mut renderer,
+                &mut damage_tracker,
+                &mut incremental,
+                0,
+                &scene((2, 2)),
+            );
+            // the mover is outside of the blur, but within the region it samples
+            let (damage, pixel) = render_effects(
+                &mut renderer,
+                &mut damage_tracker,
+                &mut incremental,
+                1,
+                &scene((4, 4)),
+            );
+            let blurred = Rectangle::<i32, Physical>::new((16, 8).into(), (32, 16).into());
+            for (x, y) in (0..32).flat_map(|x| (0..16).map(move |y| (x, y))) {
+                let point = Point::from((blurred.loc.x + x, blurred.loc.y + y));
+                assert!(
+                    damage.iter().any(|rect| rect.contains(point)),
+                    "{:?}: blur not damaged at {:?}",
+                    transform,
+                    point
+                );
+            }
+
+            let mut damage_tracker = OutputDamageTracker::new(size, 1.0, transform);
+            let (_, expected) =
+                render_effects(&mut renderer, &mut damage_tracker, &mut full, 0, &scene((4, 4)));
+            for (x, y) in (0..64).flat_map(|x| (0..32).map(move |y| (x, y))) {
+                assert_eq!(pixel(x, y), expected(x, y), "{:?}: pixel {},{}", transform, x, y);
+            }
+
+            // sharp outside of the blur, mixed across the edge of the colors within it
+            assert_eq!(pixel(8, 28), [255, 0, 0, 255], "{:?}", transform);
+            assert_eq!(pixel(56, 28), [0, 0, 255, 255], "{:?}", transform);
+            let [r, _, b, _] = pixel(32, 16);
+            assert!(r > 64 && b > 64, "{:?}: {} {}", transform, r, b);
+            let [r, _, b, _] = pixel(17, 16);
+            assert!(r > b, "{:?}: {} {}", transform, r, b);
+            let [r, _, b, _] = pixel(46, 16);
+            assert!(b > r, "{:?}: {} {}", transform, r, b);
+        }
+    }
+
+    #[test]
+    fn rounded_corner_element() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        let (background, window) = (Id::new(), Id::new());
+        let rounded = |radius| {
+            let EffectRenderElement::Solid(element) = solid(&window, (8, 8, 32, 32), [1.0, 1.0, 1.0, 1.0])
+            else {
+                unreachable!()
+            };
+            EffectRenderElement::Rounded(RoundedCornerElement::new(element, radius))
+        };
+        let elements = [
+            rounded(8),
+            solid(&background, (0, 0, 48, 48), [1.0, 0.0, 0.0, 1.0]),
+        ];
+
+        for transform in TRANSFORMS {
+            let size = Size::<i32, Physical>::from((48, 48));
+            let mut texture: GlesTexture = renderer.create_buffer(Fourcc::Abgr8888, (48, 48).into()).unwrap();
+            let mut damage_tracker = OutputDamageTracker::new(size, 1.0, transform);
+            let (_, pixel) = render_effects(&mut renderer, &mut damage_tracker, &mut texture, 0, &elements);
+
+            // the background is drawn beneath the corners, which are no longer opaque
+            assert_eq!(pixel(8, 8), [255, 0, 0, 255], "{:?}", transform);
+            assert_eq!(pixel(24, 24), [255, 255, 255, 255], "{:?}", transform);
+            assert_eq!(pixel(24, 8), [255, 255, 255, 255], "{:?}", transform);
+            assert_eq!(pixel(8, 24), [255, 255, 255, 255], "{:?}", transform);
+
+            let mut anti_aliased = false;
+            for (x, y) in (0..8).flat_map(|x| (0..8).map(move |y| (x, y))) {
+                let top_left = pixel(8 + x, 8 + y);
+                assert_eq!(top_left, pixel(39 - x, 8 + y), "{:?}: {},{}", transform, x, y);
+                assert_eq!(top_left, pixel(8 + x, 39 - y), "{:?}: {},{}", transform, x, y);
+                assert_eq!(top_left, pixel(39 - x, 39 - y), "{:?}: {},{}", transform, x, y);
+                anti_aliased |= top_left[1] > 0 && top_left[1] < 255;
+            }
+            assert!(anti_aliased, "{:?}", transform);
+        }
+    }
+
     #[test]
     fn texture_normal_double_size() {
         let src: Rectangle<f64, Buffer> = Rectangle::from_size((1000f64, 500f64).into());
@@ -0,0 +1,48 @@
+#version 100
+
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision mediump float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+// half the size of a texel of `tex`
+uniform vec2 half_pixel;
+uniform float offset;
+
+// downsampling pass of the dual kawase blur
+void main() {
+    vec4 sum = texture2D(tex, v_coords) * 4.0;
+    sum += texture2D(tex, v_coords - half_pixel * offset);
+    sum += texture2D(tex, v_coords + half_pixel * offset);
+    sum += texture2D(tex, v_coords + vec2(half_pixel.x, -half_pixel.y) * offset);
+    sum += texture2D(tex, v_coords - vec2(half_pixel.x, -half_pixel.y) * offset);
+    vec4 color = sum / 8.0;
+
+#if defined(NO_ALPHA)
+    color = vec4(color.rgb, 1.0) * alpha;
+#else
+    color = color * alpha;
+#endif
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        color = vec4(0.0, 0.2, 0.0, 0.2) + color * 0.8;
+#endif
+
+    gl_FragColor = color;
+}
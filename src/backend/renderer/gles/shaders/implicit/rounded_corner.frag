@@ -0,0 +1,54 @@
+#version 100
+
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+#if defined(GL_FRAGMENT_PRECISION_HIGH)
+precision highp float;
+#else
+precision mediump float;
+#endif
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+// size of `tex` in pixels
+uniform vec2 tex_size;
+// origin of the corner in `tex`, in pixels
+uniform vec2 origin;
+// center of the rounded corner relative to `origin`, in pixels
+uniform vec2 center;
+uniform float radius;
+// 1.0 to output the coverage of the clip, 0.0 to output the saved background outside of it
+uniform float mask;
+
+// restores the background of a corner clipped by a rounded corner
+void main() {
+    vec2 position = v_coords * tex_size - origin;
+    float coverage = clamp(radius - distance(position, center) + 0.5, 0.0, 1.0);
+
+    vec4 color;
+    if (mask == 1.0) {
+        color = vec4(coverage);
+    } else {
+        color = texture2D(tex, v_coords);
+#if defined(NO_ALPHA)
+        color = vec4(color.rgb, 1.0);
+#endif
+        color = color * (1.0 - coverage);
+    }
+
+    gl_FragColor = color;
+}
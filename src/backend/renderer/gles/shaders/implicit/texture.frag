@@ -6,7 +6,11 @@
 #extension GL_OES_EGL_image_external : require
 #endif
 
+#if (defined(COLOR_DECODE) || defined(COLOR_ENCODE)) && defined(GL_FRAGMENT_PRECISION_HIGH)
+precision highp float;
+#else
 precision mediump float;
+#endif
 #if defined(EXTERNAL)
 uniform samplerExternalOES tex;
 #else
@@ -20,9 +24,99 @@ varying vec2 v_coords;
 uniform float tint;
 #endif
 
+#if defined(COLOR_DECODE) || defined(COLOR_ENCODE)
+// transfer function, see `gles::color::TF_*`
+uniform float tf;
+uniform vec2 tf_params;
+uniform mat3 color_matrix;
+
+const float PQ_M1 = 0.1593017578125;
+const float PQ_M2 = 78.84375;
+const float PQ_C1 = 0.8359375;
+const float PQ_C2 = 18.8515625;
+const float PQ_C3 = 18.6875;
+
+const float HLG_A = 0.17883277;
+const float HLG_B = 0.28466892;
+const float HLG_C = 0.55991073;
+
+vec3 bt709_slope(vec2 params) {
+    // params are (alpha, beta), the slope of the linear segment follows from them
+    return vec3((params.x * pow(params.y, 0.45) - (params.x - 1.0)) / params.y);
+}
+#endif
+
+#if defined(COLOR_DECODE)
+vec3 eotf(vec3 c) {
+    vec3 v = abs(c);
+    if (tf == 1.0) {
+        vec3 lo = c / 12.92;
+        vec3 hi = sign(c) * pow((v + 0.055) / 1.055, vec3(2.4));
+        return mix(hi, lo, step(v, vec3(0.04045)));
+    } else if (tf == 2.0) {
+        return sign(c) * pow(v, vec3(tf_params.x));
+    } else if (tf == 3.0) {
+        vec3 p = pow(max(c, vec3(0.0)), vec3(1.0 / PQ_M2));
+        return pow(max(p - PQ_C1, vec3(0.0)) / (PQ_C2 - PQ_C3 * p), vec3(1.0 / PQ_M1));
+    } else if (tf == 4.0) {
+        vec3 e = max(c, vec3(0.0));
+        vec3 lo = e * e / 3.0;
+        vec3 hi = (exp((e - HLG_C) / HLG_A) + HLG_B) / 12.0;
+        return mix(hi, lo, step(e, vec3(0.5)));
+    } else if (tf == 5.0) {
+        vec3 l = pow(vec3(10.0), (c - 1.0) * tf_params.x);
+        return l * (1.0 - step(c, vec3(0.0)));
+    } else if (tf == 6.0) {
+        vec3 slope = bt709_slope(tf_params);
+        vec3 lo = c / slope;
+        vec3 hi = sign(c) * pow((v + tf_params.x - 1.0) / tf_params.x, vec3(1.0 / 0.45));
+        return mix(hi, lo, step(v, tf_params.y * slope));
+    }
+    return c;
+}
+#endif
+
+#if defined(COLOR_ENCODE)
+vec3 inverse_eotf(vec3 c) {
+    vec3 v = abs(c);
+    if (tf == 1.0) {
+        vec3 lo = c * 12.92;
+        vec3 hi = sign(c) * (1.055 * pow(v, vec3(1.0 / 2.4)) - 0.055);
+        return mix(hi, lo, step(v, vec3(0.0031308)));
+    } else if (tf == 2.0) {
+        return sign(c) * pow(v, vec3(1.0 / tf_params.x));
+    } else if (tf == 3.0) {
+        vec3 y = pow(max(c, vec3(0.0)), vec3(PQ_M1));
+        return pow((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y), vec3(PQ_M2));
+    } else if (tf == 4.0) {
+        vec3 e = max(c, vec3(0.0));
+        vec3 lo = sqrt(3.0 * e);
+        vec3 hi = HLG_A * log(max(12.0 * e - HLG_B, vec3(1e-6))) + HLG_C;
+        return mix(hi, lo, step(e, vec3(1.0 / 12.0)));
+    } else if (tf == 5.0) {
+        vec3 l = 1.0 + log(max(c, vec3(1e-10))) / (log(10.0) * tf_params.x);
+        return l * step(vec3(pow(10.0, -tf_params.x)), c);
+    } else if (tf == 6.0) {
+        vec3 lo = c * bt709_slope(tf_params);
+        vec3 hi = sign(c) * (tf_params.x * pow(v, vec3(0.45)) - (tf_params.x - 1.0));
+        return mix(hi, lo, step(v, vec3(tf_params.y)));
+    }
+    return c;
+}
+#endif
+
 void main() {
     vec4 color = texture2D(tex, v_coords);
 
+#if defined(COLOR_DECODE)
+#if defined(NO_ALPHA)
+    color.rgb = color_matrix * eotf(color.rgb);
+#else
+    if (color.a > 0.0)
+        color.rgb = color_matrix * eotf(color.rgb / color.a) * color.a;
+#endif
+#endif
+
 #if defined(NO_ALPHA)
     color = vec4(color.rgb, 1.0) * alpha;
 #else
@@ -34,5 +128,10 @@ void main() {
         color = vec4(0.0, 0.2, 0.0, 0.2) + color * 0.8;
 #endif
 
+#if defined(COLOR_ENCODE)
+    if (color.a > 0.0)
+        color.rgb = inverse_eotf(color_matrix * (color.rgb / color.a)) * color.a;
+#endif
+
     gl_FragColor = color;
-}
\ No newline at end of file
+}
@@ -214,7 +214,9 @@ impl Texture for GlesMapping {
 }
 impl TextureMapping for GlesMapping {
     fn flipped(&self) -> bool {
-        true
+        // the projection renders the first row to the start of the framebuffer, which is
+        // read back first
+        false
     }
     fn format(&self) -> Fourcc {
         Texture::format(self).expect("Should never happen")
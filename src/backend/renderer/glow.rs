@@ -14,6 +14,7 @@ use crate::{
         allocator::{dmabuf::Dmabuf, format::FormatSet, Format, Fourcc},
         egl::EGLContext,
         renderer::{
+            color::ColorDescription,
             element::UnderlyingStorage,
             gles::{element::*, *},
             sync, Bind, Blit, BlitFrame, Color32F, DebugFlags, ExportMem, ImportDma, ImportMem, Offscreen,
@@ -304,6 +305,10 @@ impl Frame for GlowFrame<'_, '_> {
         )
     }
 
+    fn set_color_description(&mut self, description: Option<ColorDescription>) {
+        self.frame.as_mut().unwrap().set_color_description(description)
+    }
+
     fn transformation(&self) -> Transform {
         self.frame.as_ref().unwrap().transformation()
     }
@@ -571,6 +576,48 @@ impl RenderElement<GlowRenderer> for PixelShaderElement {
     }
 }
 
+impl RenderElement<GlowRenderer> for BlurElement {
+    #[profiling::function]
+    fn draw(
+        &self,
+        frame: &mut GlowFrame<'_, '_>,
+        src: Rectangle<f64, BufferCoord>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        RenderElement::<GlesRenderer>::draw(self, frame.borrow_mut(), src, dst, damage, opaque_regions)
+    }
+
+    fn underlying_storage(&self, renderer: &mut GlowRenderer) -> Option<UnderlyingStorage<'_>> {
+        RenderElement::<GlesRenderer>::underlying_storage(self, renderer.borrow_mut())
+    }
+}
+
+impl<E: RenderElement<GlowRenderer>> RenderElement<GlowRenderer> for RoundedCornerElement<E> {
+    #[profiling::function]
+    fn draw(
+        &self,
+        frame: &mut GlowFrame<'_, '_>,
+        src: Rectangle<f64, BufferCoord>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        let corners = BorrowMut::<GlesFrame<'_, '_>>::borrow_mut(frame).save_rounded_corners(
+            dst,
+            self.radius(),
+            damage,
+        )?;
+        self.element().draw(frame, src, dst, damage, opaque_regions)?;
+        BorrowMut::<GlesFrame<'_, '_>>::borrow_mut(frame).restore_rounded_corners(corners)
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlowRenderer) -> Option<UnderlyingStorage<'_>> {
+        None
+    }
+}
+
 impl RenderElement<GlowRenderer> for TextureShaderElement {
     #[profiling::function]
     fn draw(
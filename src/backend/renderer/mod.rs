@@ -36,8 +36,13 @@ pub mod pixman;
 #[cfg(feature = "backend_ratatui")]
 pub mod ratatui;
 
+#[cfg(feature = "renderer_wgpu")]
+pub mod wgpu;
 
-mod color;
+#[cfg(feature = "renderer_vulkan")]
+pub mod vulkan;
+
+pub mod color;
 pub use color::Color32F;
 
 use crate::backend::allocator::{dmabuf::Dmabuf, Format, Fourcc};
@@ -343,6 +348,14 @@ pub trait Frame {
         alpha: f32,
     ) -> Result<(), Self::Error>;
 
+    /// Set the [`ColorDescription`](color::ColorDescription) of the content drawn by subsequent operations
+    ///
+    /// `None` resets the description to the default of sRGB.
+    /// Renderers without support for color transformations ignore the description.
+    fn set_color_description(&mut self, description: Option<color::ColorDescription>) {
+        let _ = description;
+    }
+
     /// Output transformation that is applied to this frame
     fn transformation(&self) -> Transform;
 
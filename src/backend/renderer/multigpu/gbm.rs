@@ -385,3 +385,59 @@ where
             .map_err(MultigpuError::Render)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rustix::fs::{Mode, OFlags};
+
+    use super::GbmGlesBackend;
+    use crate::{
+        backend::{
+            allocator::{gbm::GbmDevice, Fourcc},
+            drm::{DrmDeviceFd, DrmNode, NodeType},
+            renderer::{
+                gles::{GlesRenderer, GlesTexture},
+                multigpu::GpuManager,
+                test::conformance,
+            },
+        },
+        utils::DeviceFd,
+    };
+
+    type Backend = GbmGlesBackend<GlesRenderer, DrmDeviceFd>;
+
+    /// Manager of the first render node with a working renderer
+    ///
+    /// Returns `None` if there is no such node, the rendering tests are skipped in that case
+    /// unless `multi` is listed in [`conformance::REQUIRE_DEVICES_VAR`].
+    fn gpus() -> Option<(GpuManager<Backend>, DrmNode)> {
+        let gpus = std::fs::read_dir("/dev/dri").ok().and_then(|entries| {
+            entries.flatten().find_map(|entry| {
+                let node = DrmNode::from_path(entry.path()).ok()?;
+                if node.ty() != NodeType::Render {
+                    return None;
+                }
+                let fd =
+                    rustix::fs::open(entry.path(), OFlags::RDWR | OFlags::CLOEXEC, Mode::empty()).ok()?;
+                let gbm = GbmDevice::new(DrmDeviceFd::new(DeviceFd::from(fd))).ok()?;
+                let mut backend = Backend::default();
+                backend.add_node(node, gbm).ok()?;
+                let mut gpus = GpuManager::new(backend).ok()?;
+                gpus.single_renderer(&node).ok()?;
+                Some((gpus, node))
+            })
+        });
+        conformance::require_device("multi", gpus)
+    }
+
+    #[test]
+    fn conformance() {
+        let Some((mut gpus, node)) = gpus() else {
+            return;
+        };
+        let mut renderer = gpus.single_renderer(&node).unwrap();
+        if let Err(err) = conformance::check_offscreen::<_, GlesTexture>(&mut renderer, Fourcc::Abgr8888) {
+            panic!("{}", err);
+        }
+    }
+}
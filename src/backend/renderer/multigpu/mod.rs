@@ -48,8 +48,9 @@ use std::{
 };
 
 use super::{
-    sync::SyncPoint, Bind, Blit, BlitFrame, Color32F, ContextId, DebugFlags, ExportMem, Frame, ImportDma,
-    ImportMem, Offscreen, Renderer, RendererSuper, Texture, TextureFilter, TextureMapping,
+    color::ColorDescription, sync::SyncPoint, Bind, Blit, BlitFrame, Color32F, ContextId, DebugFlags,
+    ExportMem, Frame, ImportDma, ImportMem, Offscreen, Renderer, RendererSuper, Texture, TextureFilter,
+    TextureMapping,
 };
 #[cfg(feature = "wayland_frontend")]
 use super::{ImportDmaWl, ImportMemWl};
@@ -1889,6 +1890,10 @@ where
         }
     }
 
+    fn set_color_description(&mut self, description: Option<ColorDescription>) {
+        self.frame.as_mut().unwrap().set_color_description(description)
+    }
+
     fn transformation(&self) -> Transform {
         self.frame.as_ref().unwrap().transformation()
     }
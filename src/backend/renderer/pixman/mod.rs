@@ -38,8 +38,10 @@ use super::{
 };
 
 mod error;
+mod tiling;
 
 pub use error::*;
+use tiling::{Composite, RawImage, SolidCache, TileJob, TilePool, TileSource};
 
 const SUPPORTED_FORMATS: &[DrmFourcc] = &[
     #[cfg(target_endian = "little")]
@@ -295,7 +297,7 @@ impl PixmanFrame<'_, '_> {
             PixmanTargetInternal::Image(b) => b,
         };
 
-        let solid = pixman::Solid::new(color.components()).map_err(|_| PixmanError::Unsupported)?;
+        let solid = self.renderer.solids.get(color.components())?;
 
         let mut clip_region =
             pixman::Region32::init_rect(0, 0, self.output_size.w as u32, self.output_size.h as u32);
@@ -323,15 +325,31 @@ impl PixmanFrame<'_, '_> {
 
         target_image.set_clip_region32(Some(&clip_region))?;
 
-        target_image.composite32(
+        let composite = Composite {
             op,
-            &solid,
-            None,
-            (0, 0),
-            (0, 0),
-            (0, 0),
-            (target_image.width() as i32, target_image.height() as i32),
-        );
+            src_loc: (0, 0),
+            dst_loc: (0, 0),
+            size: (target_image.width() as i32, target_image.height() as i32),
+        };
+        let job = TileJob {
+            target: RawImage::new(target_image),
+            source: TileSource::Solid(color.components()),
+            composite,
+            clip: clip_region.rectangles().into(),
+        };
+        if let Some(bands) = self.renderer.tiles.bands(&job) {
+            // SAFETY: the target is borrowed until all bands are composited
+            unsafe {
+                self.renderer.tiles.composite(job, bands, |region| {
+                    target_image.set_clip_region32(Some(&region))?;
+                    composite.run(target_image, solid, None);
+                    Ok(())
+                })?;
+            }
+            target_image.set_clip_region32(Some(&clip_region))?;
+        } else {
+            composite.run(target_image, solid, None);
+        }
 
         if debug.contains(DebugFlags::TINT) {
             target_image.composite32(
@@ -596,27 +614,55 @@ impl Frame for PixmanFrame<'_, '_> {
                 .map(has_alpha)
                 .unwrap_or(true);
 
-            let op = if has_alpha {
+            // the alpha of the mask needs blending as well
+            let op = if has_alpha || alpha != 1f32 {
                 Operation::Over
             } else {
                 Operation::Src
             };
 
             let mask = if alpha != 1f32 {
-                Some(pixman::Solid::new([0f32, 0f32, 0f32, alpha]).map_err(|_| PixmanError::Unsupported)?)
+                Some(&**self.renderer.solids.get([0f32, 0f32, 0f32, alpha])?)
             } else {
                 None
             };
 
-            target_image.composite32(
+            let composite = Composite {
                 op,
-                src_image,
-                mask.as_deref(),
-                (src_x, src_y),
-                (0, 0),
-                (dest_x, dest_y),
-                (width, height),
-            );
+                src_loc: (src_x, src_y),
+                dst_loc: (dest_x, dest_y),
+                size: (width, height),
+            };
+            let job = TileJob {
+                target: RawImage::new(target_image),
+                source: TileSource::Image {
+                    image: RawImage::new(src_image),
+                    transform,
+                    filter,
+                    alpha,
+                    // shm buffers are read within the access of this thread to their pool
+                    #[cfg(feature = "wayland_frontend")]
+                    pool: src_image_accessor
+                        .buffer
+                        .as_ref()
+                        .and_then(|_| shm::PoolAccess::current()),
+                },
+                composite,
+                clip: clip_region.rectangles().into(),
+            };
+            if let Some(bands) = self.renderer.tiles.bands(&job) {
+                // SAFETY: the target and the source are borrowed until all bands are composited
+                unsafe {
+                    self.renderer.tiles.composite(job, bands, |region| {
+                        target_image.set_clip_region32(Some(&region))?;
+                        composite.run(target_image, src_image, mask);
+                        Ok(())
+                    })?;
+                }
+                target_image.set_clip_region32(Some(&clip_region))?;
+            } else {
+                composite.run(target_image, src_image, mask);
+            }
 
             src_image.clear_transform()?;
 
@@ -694,10 +740,12 @@ pub struct PixmanRenderer {
     upscale_filter: TextureFilter,
     debug_flags: DebugFlags,
     tint: pixman::Solid<'static>,
+    tiles: TilePool,
 
     // caches
     buffers: Vec<PixmanImage>,
     dmabuf_cache: Vec<PixmanImage>,
+    solids: SolidCache,
 }
 
 impl PixmanRenderer {
@@ -709,11 +757,29 @@ impl PixmanRenderer {
             upscale_filter: TextureFilter::Linear,
             debug_flags: DebugFlags::empty(),
             tint,
+            tiles: TilePool::new(1),
 
             buffers: Default::default(),
             dmabuf_cache: Default::default(),
+            solids: Default::default(),
         })
     }
+
+    /// Sets the number of threads used for compositing
+    ///
+    /// With more than one thread, draw calls covering large damaged regions are split into
+    /// horizontal tiles, which are composited in parallel. Defaults to a single thread,
+    /// compositing everything on the thread using the renderer.
+    pub fn set_threads(&mut self, threads: usize) {
+        if threads.max(1) != self.tiles.threads() {
+            self.tiles = TilePool::new(threads);
+        }
+    }
+
+    /// Returns the number of threads used for compositing
+    pub fn threads(&self) -> usize {
+        self.tiles.threads()
+    }
 }
 
 impl PixmanRenderer {
@@ -1278,3 +1344,154 @@ impl Bind<Image<'static, 'static>> for PixmanRenderer {
         Some(RENDER_BUFFER_FORMATS.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use drm_fourcc::DrmFourcc;
+    use pixman::Image;
+
+    use super::PixmanRenderer;
+    use crate::{
+        backend::renderer::{
+            test::conformance, Bind, Color32F, ExportMem, Frame, ImportMem, Offscreen, Renderer,
+        },
+        utils::{Physical, Rectangle, Size, Transform},
+    };
+
+    #[test]
+    fn conformance() {
+        let mut renderer = PixmanRenderer::new().unwrap();
+        if let Err(err) =
+            conformance::check_offscreen::<_, Image<'static, 'static>>(&mut renderer, DrmFourcc::Argb8888)
+        {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn threads() {
+        let size = Size::<i32, Physical>::from((1024, 768));
+        let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+        let pixels = (0..64u32 * 64)
+            .flat_map(|i| [(i % 64 * 4) as u8, (i / 64 * 4) as u8, 128, 255])
+            .collect::<Vec<_>>();
+
+        let render = |threads| {
+            let mut renderer = PixmanRenderer::new().unwrap();
+            renderer.set_threads(threads);
+            assert_eq!(renderer.threads(), threads);
+            let texture = renderer
+                .import_memory(&pixels, DrmFourcc::Abgr8888, (64, 64).into(), false)
+                .unwrap();
+            let mut image: Image<'static, 'static> =
+                renderer.create_buffer(DrmFourcc::Argb8888, buffer_size).unwrap();
+            let mut target = renderer.bind(&mut image).unwrap();
+
+            let mut frame = renderer.render(&mut target, size, Transform::_90).unwrap();
+            frame
+                .clear(
+                    Color32F::new(0.1, 0.2, 0.3, 1.0),
+                    &[Rectangle::from_size((768, 1024).into())],
+                )
+                .unwrap();
+            frame
+                .draw_solid(
+                    Rectangle::new((100, 50).into(), (500, 800).into()),
+                    &[Rectangle::from_size((500, 800).into())],
+                    Color32F::new(0.5, 0.0, 0.0, 0.5),
+                )
+                .unwrap();
+            frame
+                .render_texture_from_to(
+                    &texture,
+                    Rectangle::from_size((64.0, 64.0).into()),
+                    Rectangle::new((20, 30).into(), (700, 900).into()),
+                    &[
+                        Rectangle::from_size((700, 500).into()),
+                        Rectangle::new((300, 400).into(), (400, 500).into()),
+                    ],
+                    &[],
+                    Transform::Flipped,
+                    0.8,
+                )
+                .unwrap();
+            // damage reaching beyond dst, with a gap in between, must stay clipped to dst
+            frame
+                .render_texture_from_to(
+                    &texture,
+                    Rectangle::from_size((64.0, 64.0).into()),
+                    Rectangle::new((50, 100).into(), (600, 600).into()),
+                    &[
+                        Rectangle::new((-50, -50).into(), (700, 250).into()),
+                        Rectangle::new((-50, 350).into(), (700, 400).into()),
+                    ],
+                    &[],
+                    Transform::Normal,
+                    1.0,
+                )
+                .unwrap();
+            let sync = frame.finish().unwrap();
+            renderer.wait(&sync).unwrap();
+
+            let mapping = renderer
+                .copy_framebuffer(&target, Rectangle::from_size(buffer_size), DrmFourcc::Argb8888)
+                .unwrap();
+            renderer.map_texture(&mapping).unwrap().to_vec()
+        };
+
+        let single = render(1);
+        assert!(
+            render(4) == single,
+            "tiled rendering differs from rendering on a single thread"
+        );
+    }
+
+    #[cfg(feature = "wayland_frontend")]
+    #[test]
+    fn threads_shm() {
+        use crate::{backend::renderer::ImportMemWl, wayland::shm::test::TestBuffer};
+        use wayland_server::protocol::wl_shm;
+
+        let size = Size::<i32, Physical>::from((1024, 1024));
+        let buffer_size = size.to_logical(1).to_buffer(1, Transform::Normal);
+        let pixels = (0..64u32 * 64)
+            .flat_map(|i| [(i % 64 * 4) as u8, (i / 64 * 4) as u8, 128, 255])
+            .collect::<Vec<_>>();
+        let buffer = TestBuffer::new(&pixels, 0, 64, 64, 64 * 4, wl_shm::Format::Argb8888);
+
+        let render = |threads| {
+            let mut renderer = PixmanRenderer::new().unwrap();
+            renderer.set_threads(threads);
+            let texture = renderer.import_shm_buffer(&buffer.buffer, None, &[]).unwrap();
+            let mut image: Image<'static, 'static> =
+                renderer.create_buffer(DrmFourcc::Argb8888, buffer_size).unwrap();
+            let mut target = renderer.bind(&mut image).unwrap();
+
+            let mut frame = renderer.render(&mut target, size, Transform::Normal).unwrap();
+            frame
+                .render_texture_from_to(
+                    &texture,
+                    Rectangle::from_size((64.0, 64.0).into()),
+                    Rectangle::from_size(size),
+                    &[Rectangle::from_size(size)],
+                    &[],
+                    Transform::Normal,
+                    0.7,
+                )
+                .unwrap();
+            let sync = frame.finish().unwrap();
+            renderer.wait(&sync).unwrap();
+
+            let mapping = renderer
+                .copy_framebuffer(&target, Rectangle::from_size(buffer_size), DrmFourcc::Argb8888)
+                .unwrap();
+            renderer.map_texture(&mapping).unwrap().to_vec()
+        };
+
+        let single = render(1);
+        assert!(
+            render(4) == single,
+            "tiled rendering of a shm buffer differs from rendering on a single thread"
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Compositing of large regions on multiple threads
+//!
+//! The clip region of a draw call is split into horizontal bands, which are composited in
+//! parallel on separate views of the same images. Bands don't overlap, so every pixel of the
+//! target is written by a single thread only.
+
+use std::{
+    cell::RefCell,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use pixman::{Box32, Filter, FormatCode, Image, ImageRef, Operation, Region32, Repeat, Solid};
+use tracing::warn;
+
+use super::PixmanError;
+#[cfg(feature = "wayland_frontend")]
+use crate::wayland::shm::PoolAccess;
+
+/// Minimum number of pixels composited by a single band
+const MIN_BAND_AREA: i64 = 128 * 1024;
+/// Number of solid images kept by a [`SolidCache`]
+const SOLID_CACHE_SIZE: usize = 16;
+/// Number of views kept by a [`ViewCache`]
+const VIEW_CACHE_SIZE: usize = 16;
+
+thread_local! {
+    /// Solid images of the bands composited on this thread
+    static SOLIDS: RefCell<SolidCache> = RefCell::new(SolidCache::default());
+    /// Views of the source images of the bands composited on this thread
+    static VIEWS: RefCell<ViewCache> = RefCell::new(ViewCache::default());
+}
+
+/// Recently used solid images, to not allocate new ones for every draw call
+#[derive(Debug, Default)]
+pub(super) struct SolidCache(Vec<([u32; 4], Solid<'static>)>);
+
+impl SolidCache {
+    /// Returns a solid image of `color`
+    pub fn get(&mut self, color: [f32; 4]) -> Result<&Solid<'static>, PixmanError> {
+        let key = color.map(f32::to_bits);
+        if let Some(index) = self.0.iter().position(|(cached, _)| *cached == key) {
+            let entry = self.0.remove(index);
+            self.0.push(entry);
+        } else {
+            let solid = Solid::new(color).map_err(|_| PixmanError::Unsupported)?;
+            if self.0.len() >= SOLID_CACHE_SIZE {
+                self.0.remove(0);
+            }
+            self.0.push((key, solid));
+        }
+        Ok(&self.0.last().expect("just inserted").1)
+    }
+}
+
+/// Recently used views of images, to not create new ones for every band
+///
+/// Views don't own or access their memory until they are composited, so keeping them around
+/// after it was freed is fine, as long as they are only used for memory that is valid again.
+#[derive(Debug, Default)]
+struct ViewCache(Vec<(RawImage, Image<'static, 'static>)>);
+
+impl ViewCache {
+    /// Returns a view of `image`
+    ///
+    /// # Safety
+    ///
+    /// The memory of the image has to be valid as long as the view is used
+    unsafe fn get(&mut self, image: &RawImage) -> Result<&mut Image<'static, 'static>, PixmanError> {
+        if let Some(index) = self.0.iter().position(|(cached, _)| cached.same_memory(image)) {
+            let entry = self.0.remove(index);
+            self.0.push(entry);
+        } else {
+            let view = image.view()?;
+            if self.0.len() >= VIEW_CACHE_SIZE {
+                self.0.remove(0);
+            }
+            self.0.push((*image, view));
+        }
+        Ok(&mut self.0.last_mut().expect("just inserted").1)
+    }
+}
+
+/// Memory of an image, to create views of it on other threads
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RawImage {
+    format: FormatCode,
+    width: usize,
+    height: usize,
+    data: usize,
+    stride: usize,
+}
+
+impl RawImage {
+    pub fn new(image: &Image<'_, '_>) -> Self {
+        Self {
+            format: image.format(),
+            width: image.width(),
+            height: image.height(),
+            data: unsafe { image.data() } as usize,
+            stride: image.stride(),
+        }
+    }
+
+    /// Whether views of both images are interchangeable
+    fn same_memory(&self, other: &RawImage) -> bool {
+        mem::discriminant(&self.format) == mem::discriminant(&other.format)
+            && (self.width, self.height, self.data, self.stride)
+                == (other.width, other.height, other.data, other.stride)
+    }
+
+    /// Creates a new image for the memory
+    ///
+    /// # Safety
+    ///
+    /// The memory of the image has to be valid as long as the view is used
+    unsafe fn view(&self) -> Result<Image<'static, 'static>, PixmanError> {
+        Image::from_raw_mut(
+            self.format,
+            self.width,
+            self.height,
+            self.data as *mut u32,
+            self.stride,
+            false,
+        )
+        .map_err(|_| PixmanError::ImportFailed)
+    }
+}
+
+/// Arguments of a composite operation, besides the images
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Composite {
+    pub op: Operation,
+    pub src_loc: (i32, i32),
+    pub dst_loc: (i32, i32),
+    pub size: (i32, i32),
+}
+
+impl Composite {
+    /// Composites `src` into `target`, clipped to its current clip region
+    pub fn run(&self, target: &mut Image<'_, '_>, src: &ImageRef, mask: Option<&ImageRef>) {
+        target.composite32(self.op, src, mask, self.src_loc, (0, 0), self.dst_loc, self.size);
+    }
+}
+
+/// Source of a composite operation, to recreate it on other threads
+#[derive(Clone)]
+pub(super) enum TileSource {
+    /// An image, composited with a solid `alpha` mask
+    Image {
+        image: RawImage,
+        transform: Option<pixman::Transform>,
+        filter: Filter,
+        alpha: f32,
+        /// Access of the compositing thread to the shm pool holding the image
+        #[cfg(feature = "wayland_frontend")]
+        pool: Option<PoolAccess>,
+    },
+    /// A solid color
+    Solid([f32; 4]),
+}
+
+/// A composite operation to split into bands
+#[derive(Clone)]
+pub(super) struct TileJob {
+    pub target: RawImage,
+    pub source: TileSource,
+    pub composite: Composite,
+    /// Rectangles of the clip region of the draw call
+    pub clip: Arc<[Box32]>,
+}
+
+impl TileJob {
+    /// Clip region of the band in the target
+    fn region(&self, band: Box32) -> Region32 {
+        Region32::init_rects(&self.clip).intersect_rect(
+            band.x1,
+            band.y1,
+            (band.x2 - band.x1).max(0) as u32,
+            (band.y2 - band.y1).max(0) as u32,
+        )
+    }
+
+    /// Composites a band of the target on a worker thread
+    ///
+    /// # Safety
+    ///
+    /// The memory of the target and an image source has to be valid
+    unsafe fn run(self, band: Box32) -> Result<(), PixmanError> {
+        // the view is not kept around, the memory of the target may be freed after the job
+        let mut target = self.target.view()?;
+        target.set_clip_region32(Some(&self.region(band)))?;
+        SOLIDS.with_borrow_mut(|solids| -> Result<(), PixmanError> {
+            match self.source {
+                TileSource::Image {
+                    image,
+                    transform,
+                    filter,
+                    alpha,
+                    #[cfg(feature = "wayland_frontend")]
+                    pool,
+                } => VIEWS.with_borrow_mut(|views| -> Result<(), PixmanError> {
+                    let src = views.get(&image)?;
+                    match transform {
+                        Some(transform) => src.set_transform(transform)?,
+                        None => src.clear_transform()?,
+                    }
+                    src.set_filter(filter, &[])?;
+                    src.set_repeat(Repeat::None);
+
+                    let mask = if alpha != 1f32 {
+                        Some(&**solids.get([0f32, 0f32, 0f32, alpha])?)
+                    } else {
+                        None
+                    };
+                    #[cfg(feature = "wayland_frontend")]
+                    if let Some(pool) = pool {
+                        // SAFETY: the compositing thread accesses the pool until all bands are done
+                        unsafe { pool.join(|| self.composite.run(&mut target, src, mask)) };
+                        return Ok(());
+                    }
+                    self.composite.run(&mut target, src, mask);
+                    Ok(())
+                })?,
+                TileSource::Solid(color) => {
+                    self.composite.run(&mut target, solids.get(color)?, None);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads compositing bands of draw calls
+pub(super) struct TilePool {
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for TilePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TilePool")
+            .field("threads", &self.threads())
+            .finish_non_exhaustive()
+    }
+}
+
+impl TilePool {
+    /// Creates a pool compositing on `threads` threads, including the calling one
+    pub fn new(threads: usize) -> Self {
+        if threads <= 1 {
+            return TilePool {
+                sender: None,
+                workers: Vec::new(),
+            };
+        }
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (1..threads)
+            .filter_map(|index| {
+                let receiver = receiver.clone();
+                thread::Builder::new()
+                    .name(format!("pixman-tiles-{}", index))
+                    .spawn(move || loop {
+                        let job = receiver.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .map_err(|err| warn!(?err, "Failed to spawn pixman worker thread"))
+                    .ok()
+            })
+            .collect();
+
+        TilePool {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Number of threads compositing, including the calling one
+    pub fn threads(&self) -> usize {
+        self.workers.len() + 1
+    }
+
+    /// Splits the clip region of `job` into horizontal bands, if it is worth compositing in parallel
+    pub fn bands(&self, job: &TileJob) -> Option<Vec<Box32>> {
+        if self.workers.is_empty() {
+            return None;
+        }
+
+        let (mut area, mut x1, mut y1, mut x2, mut y2) = (0i64, i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+        for rect in job.clip.iter() {
+            if rect.x1 >= rect.x2 || rect.y1 >= rect.y2 {
+                continue;
+            }
+            area += (rect.x2 - rect.x1) as i64 * (rect.y2 - rect.y1) as i64;
+            x1 = x1.min(rect.x1);
+            y1 = y1.min(rect.y1);
+            x2 = x2.max(rect.x2);
+            y2 = y2.max(rect.y2);
+        }
+
+        if area == 0 {
+            return None;
+        }
+        let rows = y2 - y1;
+        let count = (area / MIN_BAND_AREA).min(self.threads() as i64).min(rows as i64) as i32;
+        if count < 2 {
+            return None;
+        }
+
+        Some(
+            (0..count)
+                .map(|index| Box32 {
+                    x1,
+                    y1: y1 + rows * index / count,
+                    x2,
+                    y2: y1 + rows * (index + 1) / count,
+                })
+                .collect(),
+        )
+    }
+
+    /// Composites all but the first band of `job` on the worker threads and the first one with
+    /// `local`, blocking until every band is done
+    ///
+    /// # Safety
+    ///
+    /// The memory of the target and an image source has to be valid until this returns
+    pub unsafe fn composite(
+        &self,
+        job: TileJob,
+        bands: Vec<Box32>,
+        local: impl FnOnce(Region32) -> Result<(), PixmanError>,
+    ) -> Result<(), PixmanError> {
+        let mut bands = bands.into_iter();
+        let Some(first) = bands.next() else {
+            return Ok(());
+        };
+
+        let (done, results) = mpsc::channel();
+        // created before the first band is sent, so no band outlives this call, even on a panic
+        let mut pending = PendingBands { results, count: 0 };
+        for band in bands {
+            let (job, done) = (job.clone(), done.clone());
+            let work: Job = Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe { job.run(band) }));
+                let _ = done.send(result);
+            });
+            pending.count += 1;
+            if let Err(mpsc::SendError(work)) = self.sender.as_ref().expect("pool has workers").send(work) {
+                work();
+            }
+        }
+        drop(done);
+
+        let mut result = local(job.region(first));
+        let mut panicked = None;
+        for band in &mut pending {
+            match band {
+                Ok(band) => result = result.and(band),
+                Err(panic) => {
+                    panicked.get_or_insert(panic);
+                }
+            }
+        }
+        if let Some(panic) = panicked {
+            panic::resume_unwind(panic);
+        }
+        result
+    }
+}
+
+/// Bands composited on the worker threads, waited for when dropped
+///
+/// The workers write to the target until they report their band, so it may only be freed or
+/// reused once all of them did, also when unwinding from a panic.
+struct PendingBands {
+    results: mpsc::Receiver<thread::Result<Result<(), PixmanError>>>,
+    count: usize,
+}
+
+impl Iterator for PendingBands {
+    type Item = thread::Result<Result<(), PixmanError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count == 0 {
+            return None;
+        }
+        self.count -= 1;
+        // fails only if every job was dropped, in which case none of them is running anymore
+        self.results.recv().ok()
+    }
+}
+
+impl Drop for PendingBands {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+impl Drop for TilePool {
+    fn drop(&mut self) {
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::{mpsc, Arc, Mutex},
+        time::Duration,
+    };
+
+    use pixman::{Box32, FormatCode, Image, Operation};
+
+    use super::{Composite, RawImage, TileJob, TilePool, TileSource};
+
+    #[test]
+    fn bands_finish_before_panic() {
+        let pool = TilePool::new(4);
+        let (width, height) = (2048, 2048);
+        let target = Image::new(FormatCode::A8R8G8B8, width, height, true).unwrap();
+        let job = TileJob {
+            target: RawImage::new(&target),
+            source: TileSource::Solid([1.0, 0.0, 0.0, 1.0]),
+            composite: Composite {
+                op: Operation::Src,
+                src_loc: (0, 0),
+                dst_loc: (0, 0),
+                size: (width as i32, height as i32),
+            },
+            clip: Arc::from([Box32 {
+                x1: 0,
+                y1: 0,
+                x2: width as i32,
+                y2: height as i32,
+            }]),
+        };
+        let bands = pool.bands(&job).unwrap();
+        let first = bands[0];
+
+        // keep the workers busy until the panic reached the caller, or for a while if it waits
+        let (release, released) = mpsc::channel::<()>();
+        let released = Arc::new(Mutex::new(released));
+        for _ in 1..pool.threads() {
+            let released = released.clone();
+            let sender = pool.sender.as_ref().unwrap();
+            sender
+                .send(Box::new(move || {
+                    let _ = released.lock().unwrap().recv_timeout(Duration::from_millis(100));
+                }))
+                .unwrap();
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+            pool.composite(job, bands, |_| panic!("compositing the local band failed"))
+        }));
+        assert!(result.is_err());
+
+        let pixels = unsafe { std::slice::from_raw_parts(target.data() as *const u32, width * height) };
+        for (y, row) in pixels.chunks(width).enumerate() {
+            let expected = if (y as i32) < first.y2 { 0 } else { 0xffff0000 };
+            assert!(
+                row.iter().all(|pixel| *pixel == expected),
+                "row {} not composited",
+                y
+            );
+        }
+        drop(release);
+    }
+}
@@ -0,0 +1,390 @@
+//! Conversion of pixel formats into the premultiplied `Argb8888` used by [`RatatuiTexture`](super::RatatuiTexture)
+//!
+//! All single-plane rgb formats advertised via `wl_shm` are supported, as well as packed yuv
+//! formats, which are converted using BT.601 limited range coefficients. Palette based and
+//! multi-planar formats are not supported.
+
+use crate::backend::allocator::Fourcc;
+
+/// Position and width in bits of a channel within a little-endian pixel
+type Channel = Option<(u32, u32)>;
+
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// Unsigned normalized channels
+    Packed {
+        bytes: usize,
+        r: Channel,
+        g: Channel,
+        b: Channel,
+        a: Channel,
+    },
+    /// Half float channels, given as the index of the 16-bit word
+    HalfFloat {
+        r: usize,
+        g: usize,
+        b: usize,
+        a: Option<usize>,
+    },
+    /// 4:2:2 subsampled yuv, given as byte offsets within a macro pixel of two pixels
+    Yuv422 {
+        y0: usize,
+        u: usize,
+        y1: usize,
+        v: usize,
+    },
+    /// Yuv without subsampling, given as byte offsets
+    Yuv444 {
+        bytes: usize,
+        y: usize,
+        u: usize,
+        v: usize,
+        a: Option<usize>,
+    },
+}
+
+const fn packed(bytes: usize, r: Channel, g: Channel, b: Channel, a: Channel) -> Layout {
+    Layout::Packed { bytes, r, g, b, a }
+}
+
+fn layout(format: Fourcc) -> Option<Layout> {
+    use Fourcc::*;
+
+    let layout = match format {
+        Rgb332 => packed(1, Some((5, 3)), Some((2, 3)), Some((0, 2)), None),
+        Bgr233 => packed(1, Some((0, 3)), Some((3, 3)), Some((6, 2)), None),
+
+        Argb4444 => packed(2, Some((8, 4)), Some((4, 4)), Some((0, 4)), Some((12, 4))),
+        Xrgb4444 => packed(2, Some((8, 4)), Some((4, 4)), Some((0, 4)), None),
+        Abgr4444 => packed(2, Some((0, 4)), Some((4, 4)), Some((8, 4)), Some((12, 4))),
+        Xbgr4444 => packed(2, Some((0, 4)), Some((4, 4)), Some((8, 4)), None),
+        Rgba4444 => packed(2, Some((12, 4)), Some((8, 4)), Some((4, 4)), Some((0, 4))),
+        Rgbx4444 => packed(2, Some((12, 4)), Some((8, 4)), Some((4, 4)), None),
+        Bgra4444 => packed(2, Some((4, 4)), Some((8, 4)), Some((12, 4)), Some((0, 4))),
+        Bgrx4444 => packed(2, Some((4, 4)), Some((8, 4)), Some((12, 4)), None),
+
+        Argb1555 => packed(2, Some((10, 5)), Some((5, 5)), Some((0, 5)), Some((15, 1))),
+        Xrgb1555 => packed(2, Some((10, 5)), Some((5, 5)), Some((0, 5)), None),
+        Abgr1555 => packed(2, Some((0, 5)), Some((5, 5)), Some((10, 5)), Some((15, 1))),
+        Xbgr1555 => packed(2, Some((0, 5)), Some((5, 5)), Some((10, 5)), None),
+        Rgba5551 => packed(2, Some((11, 5)), Some((6, 5)), Some((1, 5)), Some((0, 1))),
+        Rgbx5551 => packed(2, Some((11, 5)), Some((6, 5)), Some((1, 5)), None),
+        Bgra5551 => packed(2, Some((1, 5)), Some((6, 5)), Some((11, 5)), Some((0, 1))),
+        Bgrx5551 => packed(2, Some((1, 5)), Some((6, 5)), Some((11, 5)), None),
+
+        Rgb565 => packed(2, Some((11, 5)), Some((5, 6)), Some((0, 5)), None),
+        Bgr565 => packed(2, Some((0, 5)), Some((5, 6)), Some((11, 5)), None),
+
+        Rgb888 => packed(3, Some((16, 8)), Some((8, 8)), Some((0, 8)), None),
+        Bgr888 => packed(3, Some((0, 8)), Some((8, 8)), Some((16, 8)), None),
+
+        Argb8888 => packed(4, Some((16, 8)), Some((8, 8)), Some((0, 8)), Some((24, 8))),
+        Xrgb8888 => packed(4, Some((16, 8)), Some((8, 8)), Some((0, 8)), None),
+        Abgr8888 => packed(4, Some((0, 8)), Some((8, 8)), Some((16, 8)), Some((24, 8))),
+        Xbgr8888 => packed(4, Some((0, 8)), Some((8, 8)), Some((16, 8)), None),
+        Rgba8888 => packed(4, Some((24, 8)), Some((16, 8)), Some((8, 8)), Some((0, 8))),
+        Rgbx8888 => packed(4, Some((24, 8)), Some((16, 8)), Some((8, 8)), None),
+        Bgra8888 => packed(4, Some((8, 8)), Some((16, 8)), Some((24, 8)), Some((0, 8))),
+        Bgrx8888 => packed(4, Some((8, 8)), Some((16, 8)), Some((24, 8)), None),
+
+        Argb2101010 => packed(4, Some((20, 10)), Some((10, 10)), Some((0, 10)), Some((30, 2))),
+        Xrgb2101010 => packed(4, Some((20, 10)), Some((10, 10)), Some((0, 10)), None),
+        Abgr2101010 => packed(4, Some((0, 10)), Some((10, 10)), Some((20, 10)), Some((30, 2))),
+        Xbgr2101010 => packed(4, Some((0, 10)), Some((10, 10)), Some((20, 10)), None),
+        Rgba1010102 => packed(4, Some((22, 10)), Some((12, 10)), Some((2, 10)), Some((0, 2))),
+        Rgbx1010102 => packed(4, Some((22, 10)), Some((12, 10)), Some((2, 10)), None),
+        Bgra1010102 => packed(4, Some((2, 10)), Some((12, 10)), Some((22, 10)), Some((0, 2))),
+        Bgrx1010102 => packed(4, Some((2, 10)), Some((12, 10)), Some((22, 10)), None),
+
+        R8 => packed(1, Some((0, 8)), None, None, None),
+        R16 => packed(2, Some((0, 16)), None, None, None),
+        Rg88 => packed(2, Some((8, 8)), Some((0, 8)), None, None),
+        Gr88 => packed(2, Some((0, 8)), Some((8, 8)), None, None),
+        Rg1616 => packed(4, Some((16, 16)), Some((0, 16)), None, None),
+        Gr1616 => packed(4, Some((0, 16)), Some((16, 16)), None, None),
+
+        Argb16161616f => Layout::HalfFloat {
+            r: 2,
+            g: 1,
+            b: 0,
+            a: Some(3),
+        },
+        Xrgb16161616f => Layout::HalfFloat {
+            r: 2,
+            g: 1,
+            b: 0,
+            a: None,
+        },
+        Abgr16161616f => Layout::HalfFloat {
+            r: 0,
+            g: 1,
+            b: 2,
+            a: Some(3),
+        },
+        Xbgr16161616f => Layout::HalfFloat {
+            r: 0,
+            g: 1,
+            b: 2,
+            a: None,
+        },
+
+        Yuyv => Layout::Yuv422 {
+            y0: 0,
+            u: 1,
+            y1: 2,
+            v: 3,
+        },
+        Yvyu => Layout::Yuv422 {
+            y0: 0,
+            v: 1,
+            y1: 2,
+            u: 3,
+        },
+        Uyvy => Layout::Yuv422 {
+            u: 0,
+            y0: 1,
+            v: 2,
+            y1: 3,
+        },
+        Vyuy => Layout::Yuv422 {
+            v: 0,
+            y0: 1,
+            u: 2,
+            y1: 3,
+        },
+        Ayuv => Layout::Yuv444 {
+            bytes: 4,
+            v: 0,
+            u: 1,
+            y: 2,
+            a: Some(3),
+        },
+        Xyuv8888 => Layout::Yuv444 {
+            bytes: 4,
+            v: 0,
+            u: 1,
+            y: 2,
+            a: None,
+        },
+        Vuy888 => Layout::Yuv444 {
+            bytes: 3,
+            y: 0,
+            u: 1,
+            v: 2,
+            a: None,
+        },
+
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Formats supported by [`convert_row`]
+pub(super) const SUPPORTED_FORMATS: &[Fourcc] = &[
+    Fourcc::Argb8888,
+    Fourcc::Xrgb8888,
+    Fourcc::Abgr8888,
+    Fourcc::Xbgr8888,
+    Fourcc::Rgba8888,
+    Fourcc::Rgbx8888,
+    Fourcc::Bgra8888,
+    Fourcc::Bgrx8888,
+    Fourcc::Rgb888,
+    Fourcc::Bgr888,
+    Fourcc::Rgb565,
+    Fourcc::Bgr565,
+    Fourcc::Argb2101010,
+    Fourcc::Xrgb2101010,
+    Fourcc::Abgr2101010,
+    Fourcc::Xbgr2101010,
+    Fourcc::Rgba1010102,
+    Fourcc::Rgbx1010102,
+    Fourcc::Bgra1010102,
+    Fourcc::Bgrx1010102,
+    Fourcc::Argb16161616f,
+    Fourcc::Xrgb16161616f,
+    Fourcc::Abgr16161616f,
+    Fourcc::Xbgr16161616f,
+    Fourcc::Argb4444,
+    Fourcc::Xrgb4444,
+    Fourcc::Abgr4444,
+    Fourcc::Xbgr4444,
+    Fourcc::Rgba4444,
+    Fourcc::Rgbx4444,
+    Fourcc::Bgra4444,
+    Fourcc::Bgrx4444,
+    Fourcc::Argb1555,
+    Fourcc::Xrgb1555,
+    Fourcc::Abgr1555,
+    Fourcc::Xbgr1555,
+    Fourcc::Rgba5551,
+    Fourcc::Rgbx5551,
+    Fourcc::Bgra5551,
+    Fourcc::Bgrx5551,
+    Fourcc::Rgb332,
+    Fourcc::Bgr233,
+    Fourcc::R8,
+    Fourcc::R16,
+    Fourcc::Rg88,
+    Fourcc::Gr88,
+    Fourcc::Rg1616,
+    Fourcc::Gr1616,
+    Fourcc::Yuyv,
+    Fourcc::Yvyu,
+    Fourcc::Uyvy,
+    Fourcc::Vyuy,
+    Fourcc::Ayuv,
+    Fourcc::Xyuv8888,
+    Fourcc::Vuy888,
+];
+
+/// Minimum number of bytes per row of an image `width` pixels wide in `format`
+pub(super) fn min_stride(format: Fourcc, width: usize) -> Option<usize> {
+    let stride = match layout(format)? {
+        Layout::Packed { bytes, .. } => bytes * width,
+        Layout::HalfFloat { .. } => 8 * width,
+        Layout::Yuv422 { .. } => 4 * width.div_ceil(2),
+        Layout::Yuv444 { bytes, .. } => bytes * width,
+    };
+    Some(stride)
+}
+
+/// Convert a row of `width` pixels in `format` to premultiplied `Argb8888`
+///
+/// Returns `None` if the format is not supported or `src` is too short.
+pub(super) fn convert_row(format: Fourcc, src: &[u8], dst: &mut [u32]) -> Option<()> {
+    let layout = layout(format)?;
+    if src.len() < min_stride(format, dst.len())? {
+        return None;
+    }
+
+    match layout {
+        Layout::Packed { bytes, r, g, b, a } => {
+            for (pixel, chunk) in dst.iter_mut().zip(src.chunks_exact(bytes)) {
+                let word = chunk
+                    .iter()
+                    .rev()
+                    .fold(0u64, |word, byte| (word << 8) | u64::from(*byte));
+                let channel = |channel: Channel, default: u8| match channel {
+                    Some((shift, bits)) => {
+                        let max = (1u64 << bits) - 1;
+                        (((word >> shift) & max) * 255 / max) as u8
+                    }
+                    None => default,
+                };
+                *pixel = argb(channel(a, 255), channel(r, 0), channel(g, 0), channel(b, 0));
+            }
+        }
+        Layout::HalfFloat { r, g, b, a } => {
+            for (pixel, chunk) in dst.iter_mut().zip(src.chunks_exact(8)) {
+                let channel = |idx: usize| {
+                    let half = u16::from_le_bytes([chunk[idx * 2], chunk[idx * 2 + 1]]);
+                    (half_to_f32(half).clamp(0.0, 1.0) * 255.0).round() as u8
+                };
+                *pixel = argb(a.map_or(255, channel), channel(r), channel(g), channel(b));
+            }
+        }
+        Layout::Yuv422 { y0, u, y1, v } => {
+            for (pixels, chunk) in dst.chunks_mut(2).zip(src.chunks_exact(4)) {
+                pixels[0] = yuv_to_argb(chunk[y0], chunk[u], chunk[v], 255);
+                if let Some(pixel) = pixels.get_mut(1) {
+                    *pixel = yuv_to_argb(chunk[y1], chunk[u], chunk[v], 255);
+                }
+            }
+        }
+        Layout::Yuv444 { bytes, y, u, v, a } => {
+            for (pixel, chunk) in dst.iter_mut().zip(src.chunks_exact(bytes)) {
+                *pixel = yuv_to_argb(chunk[y], chunk[u], chunk[v], a.map_or(255, |a| chunk[a]));
+            }
+        }
+    }
+    Some(())
+}
+
+#[inline]
+fn argb(a: u8, r: u8, g: u8, b: u8) -> u32 {
+    (u32::from(a) << 24) | (u32::from(r) << 16) | (u32::from(g) << 8) | u32::from(b)
+}
+
+fn yuv_to_argb(y: u8, u: u8, v: u8, a: u8) -> u32 {
+    let y = 1.164 * (f32::from(y) - 16.0);
+    let u = f32::from(u) - 128.0;
+    let v = f32::from(v) - 128.0;
+    // yuv content is not premultiplied
+    let alpha = f32::from(a) / 255.0;
+    let channel = |c: f32| (c.clamp(0.0, 255.0) * alpha).round() as u8;
+    argb(
+        a,
+        channel(y + 1.596 * v),
+        channel(y - 0.392 * u - 0.813 * v),
+        channel(y + 2.017 * u),
+    )
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f32::from(half & 0x3ff);
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_pixel(format: Fourcc, src: &[u8]) -> u32 {
+        let mut dst = [0];
+        convert_row(format, src, &mut dst).unwrap();
+        dst[0]
+    }
+
+    #[test]
+    fn rgb_formats() {
+        let argb = 0x80402010u32.to_le_bytes();
+        assert_eq!(convert_pixel(Fourcc::Argb8888, &argb), 0x80402010);
+        assert_eq!(convert_pixel(Fourcc::Xrgb8888, &argb), 0xff402010);
+        assert_eq!(convert_pixel(Fourcc::Abgr8888, &argb), 0x80102040);
+        assert_eq!(convert_pixel(Fourcc::Rgba8888, &argb), 0x10804020);
+        assert_eq!(convert_pixel(Fourcc::Rgb888, &[0x10, 0x20, 0x40]), 0xff402010);
+        assert_eq!(convert_pixel(Fourcc::Bgr888, &[0x10, 0x20, 0x40]), 0xff102040);
+        assert_eq!(
+            convert_pixel(Fourcc::Rgb565, &0xf800u16.to_le_bytes()),
+            0xffff0000
+        );
+        assert_eq!(
+            convert_pixel(Fourcc::Bgr565, &0x07e0u16.to_le_bytes()),
+            0xff00ff00
+        );
+        assert_eq!(
+            convert_pixel(Fourcc::Argb2101010, &0xc00003ffu32.to_le_bytes()),
+            0xff0000ff
+        );
+        assert_eq!(convert_pixel(Fourcc::R8, &[0x80]), 0xff800000);
+    }
+
+    #[test]
+    fn half_float_formats() {
+        // 1.0, 0.5, 0.0, 1.0
+        let pixel = [0x00, 0x3c, 0x00, 0x38, 0x00, 0x00, 0x00, 0x3c];
+        assert_eq!(convert_pixel(Fourcc::Abgr16161616f, &pixel), 0xffff8000);
+    }
+
+    #[test]
+    fn yuv_formats() {
+        let mut dst = [0; 2];
+        convert_row(Fourcc::Yuyv, &[235, 128, 16, 128], &mut dst).unwrap();
+        assert_eq!(dst, [0xffffffff, 0xff000000]);
+        assert!(convert_row(Fourcc::Nv12, &[0; 4], &mut dst).is_none());
+    }
+
+    #[test]
+    fn short_rows() {
+        let mut dst = [0; 2];
+        assert!(convert_row(Fourcc::Argb8888, &[0; 7], &mut dst).is_none());
+    }
+}
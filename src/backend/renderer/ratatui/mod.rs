@@ -0,0 +1,1220 @@
+// TODO
+#![allow(missing_docs)]
+#![cfg_attr(docsrs, doc(cfg(feature = "ratatui_backend")))]
+
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crossterm::ExecutableCommand;
+use ratatui::backend::WindowSize;
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::Terminal;
+
+use crate::backend::allocator::dmabuf::{
+    Dmabuf, DmabufMappingFailed, DmabufMappingMode, DmabufSyncFailed, DmabufSyncFlags,
+};
+use crate::backend::allocator::{format::FormatSet, Buffer, Format, Fourcc, Modifier};
+use crate::backend::ratatui::{
+    damage_to_cells, osc0, osc22, osc52, quantize_area, quantize_cells, Clipboard, ColorMode,
+    GraphicsEncoder, GraphicsProtocol, Image, BEL, MAX_CLIPBOARD_SIZE, POP_TITLE, PUSH_TITLE,
+};
+use crate::backend::renderer::sync::Interrupted;
+use crate::backend::renderer::{
+    sync, Color32F, ContextId, DebugFlags, Frame, ImportDma, ImportDmaWl, ImportMem, ImportMemWl,
+    InnerContextId, Renderer, RendererSuper, Texture, TextureFilter,
+};
+use crate::input::pointer::CursorIcon;
+use crate::utils::{Buffer as BufferCoord, Physical, Point, Rectangle, Size, Transform};
+
+#[cfg(all(
+    feature = "wayland_frontend",
+    feature = "backend_egl",
+    feature = "use_system_lib"
+))]
+use crate::backend::{egl::display::EGLBufferReader, renderer::ImportEgl};
+use crate::wayland::shm::{shm_format_to_fourcc, with_buffer_contents};
+
+mod format;
+mod terminal;
+
+use terminal::TerminalBackend;
+
+/// A renderer for the ratatui backend
+///
+/// By default the output is approximated using half-block cells. If a [`GraphicsProtocol`] is set,
+/// the renderer draws into a pixel buffer instead, which is presented to the terminal as images.
+///
+/// Cells are rendered using 24-bit colours and reduced to the configured [`ColorMode`] on presentation.
+///
+/// The renderer either draws to the terminal of the process or to a remote terminal,
+/// see [`RatatuiListener`](crate::backend::ratatui::remote::RatatuiListener).
+#[derive(Debug)]
+pub struct RatatuiRenderer {
+    terminal: Terminal<TerminalBackend>,
+    graphics: Option<Graphics>,
+    /// Cells last presented, with their colours reduced to the [`ColorMode`]
+    presented: Option<ratatui::buffer::Buffer>,
+    /// Window title last set, the original title is restored on drop if set
+    title: Option<String>,
+    /// Shape of the mouse pointer last set, reset to the default on drop if set
+    mouse_shape: Option<CursorIcon>,
+    color_mode: ColorMode,
+    dithering: bool,
+    upscale_filter: TextureFilter,
+    downscale_filter: TextureFilter,
+    debug_flags: DebugFlags,
+}
+
+#[derive(Debug)]
+struct Graphics {
+    encoder: GraphicsEncoder,
+    /// Size of a single terminal cell in pixels
+    cell_size: Size<i32, Physical>,
+}
+
+impl Default for RatatuiRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RatatuiRenderer {
+    /// Create a new ratatui renderer
+    pub fn new() -> Self {
+        // enables raw mode and the alternate screen, and installs a panic hook restoring them
+        let _ = ratatui::init();
+        let terminal = Terminal::new(TerminalBackend::stdout()).unwrap();
+        std::io::stdout()
+            .execute(crossterm::event::EnableMouseCapture)
+            .unwrap()
+            .execute(crossterm::event::EnableBracketedPaste)
+            .unwrap()
+            .execute(crossterm::event::PushKeyboardEnhancementFlags(
+                crossterm::event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                    | crossterm::event::KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES,
+            ))
+            .unwrap();
+        Self {
+            terminal,
+            graphics: None,
+            presented: None,
+            title: None,
+            mouse_shape: None,
+            color_mode: ColorMode::default(),
+            dithering: false,
+            upscale_filter: TextureFilter::Linear,
+            downscale_filter: TextureFilter::Linear,
+            debug_flags: DebugFlags::empty(),
+        }
+    }
+
+    /// Create a renderer drawing to a remote terminal through `writer`
+    ///
+    /// The terminal modes are expected to be set up by the remote side.
+    /// `size` has to be kept up to date with the size reported by the terminal.
+    pub(crate) fn remote(writer: Box<dyn Write + Send>, size: Arc<Mutex<WindowSize>>) -> io::Result<Self> {
+        let mut terminal = Terminal::new(TerminalBackend::remote(writer, size))?;
+        terminal.clear()?;
+        Ok(Self {
+            terminal,
+            graphics: None,
+            presented: None,
+            title: None,
+            mouse_shape: None,
+            color_mode: ColorMode::default(),
+            dithering: false,
+            upscale_filter: TextureFilter::Linear,
+            downscale_filter: TextureFilter::Linear,
+            debug_flags: DebugFlags::empty(),
+        })
+    }
+
+    pub fn terminal_size(&self) -> ratatui::layout::Size {
+        self.terminal.size().unwrap()
+    }
+
+    /// Return the window size, in cells or in pixels if a [`GraphicsProtocol`] is used
+    pub fn window_size(&self) -> Size<i32, Physical> {
+        let size = self.terminal_size();
+        match self.graphics.as_ref() {
+            Some(graphics) => {
+                let cell_size = self.terminal.backend().cell_size().unwrap_or(graphics.cell_size);
+                Size::new(
+                    i32::from(size.width) * cell_size.w,
+                    i32::from(size.height) * cell_size.h,
+                )
+            }
+            None => Size::new(size.width.into(), i32::from(size.height) * 2),
+        }
+    }
+
+    /// Returns the size of a terminal cell in pixels of the [`window_size`](RatatuiRenderer::window_size)
+    ///
+    /// Without a [`GraphicsProtocol`] every cell covers two pixels stacked on top of each other.
+    pub fn cell_size(&self) -> Size<i32, Physical> {
+        match self.graphics.as_ref() {
+            Some(graphics) => self.terminal.backend().cell_size().unwrap_or(graphics.cell_size),
+            None => Size::new(1, 2),
+        }
+    }
+
+    /// Returns the [`GraphicsProtocol`] used to present frames, if any
+    pub fn graphics_protocol(&self) -> Option<GraphicsProtocol> {
+        self.graphics.as_ref().map(|graphics| graphics.encoder.protocol())
+    }
+
+    /// Set the [`GraphicsProtocol`] used to present frames
+    ///
+    /// `None` switches back to presenting frames using half-block cells.
+    /// Existing framebuffers are replaced on the next call to [`Renderer::render`],
+    /// as the [`window_size`](RatatuiRenderer::window_size) changes.
+    ///
+    /// Fails with [`RatatuiError::UnknownCellSize`], if the terminal does not report its size in pixels.
+    pub fn set_graphics_protocol(&mut self, protocol: Option<GraphicsProtocol>) -> Result<(), RatatuiError> {
+        if self.graphics_protocol() == protocol {
+            return Ok(());
+        }
+
+        self.clear_graphics()?;
+        self.graphics = match protocol {
+            Some(protocol) => {
+                let cell_size = self
+                    .terminal
+                    .backend()
+                    .cell_size()
+                    .ok_or(RatatuiError::UnknownCellSize)?;
+                Some(Graphics {
+                    encoder: GraphicsEncoder::new(protocol),
+                    cell_size,
+                })
+            }
+            None => None,
+        };
+        // the contents of the screen are unknown to ratatui now
+        self.terminal.clear()?;
+        self.presented = None;
+        if self.graphics.is_some() {
+            self.terminal.hide_cursor()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the [`ColorMode`] used to present cells
+    pub fn color_mode(&self) -> ColorMode {
+        self.color_mode
+    }
+
+    /// Set the [`ColorMode`] used to present cells
+    ///
+    /// Frames presented using a [`GraphicsProtocol`] are not affected.
+    pub fn set_color_mode(&mut self, color_mode: ColorMode) {
+        self.color_mode = color_mode;
+        self.presented = None;
+    }
+
+    /// Returns whether ordered dithering is applied when reducing colours
+    pub fn dithering(&self) -> bool {
+        self.dithering
+    }
+
+    /// Enable or disable ordered dithering when reducing colours to the [`ColorMode`]
+    pub fn set_dithering(&mut self, dithering: bool) {
+        self.dithering = dithering;
+        self.presented = None;
+    }
+
+    fn clear_graphics(&mut self) -> Result<(), RatatuiError> {
+        if let Some(graphics) = self.graphics.as_mut() {
+            let mut out = Vec::new();
+            graphics.encoder.clear(&mut out);
+            let backend = self.terminal.backend_mut();
+            backend.write_all(&out)?;
+            Write::flush(backend)?;
+        }
+        Ok(())
+    }
+
+    pub fn swap_buffers(&mut self, mut fb: RatatuiFramebuffer) -> Result<RatatuiFramebuffer, RatatuiError> {
+        if !fb.is_compatible_with(self) {
+            // window resized
+            return Ok(self.new_framebuffer());
+        }
+        match &mut fb.inner {
+            FramebufferInner::Cells(buffer) => self.present_cells(buffer, None)?,
+            FramebufferInner::Pixels(buffer) => {
+                buffer.damage = vec![Rectangle::from_size(buffer.size)];
+                self.present(buffer)?;
+            }
+        }
+        Ok(fb)
+    }
+
+    /// Present `fb`, only updating the parts of the terminal covered by `damage`
+    ///
+    /// `damage` is given in pixels of the framebuffer, like the damage returned by
+    /// [`OutputDamageTracker::render_output`](crate::backend::renderer::damage::OutputDamageTracker::render_output).
+    /// Nothing is sent to the terminal if `damage` is empty.
+    pub fn swap_buffers_with_damage(
+        &mut self,
+        mut fb: RatatuiFramebuffer,
+        damage: &[Rectangle<i32, Physical>],
+    ) -> Result<RatatuiFramebuffer, RatatuiError> {
+        if !fb.is_compatible_with(self) {
+            // window resized
+            return Ok(self.new_framebuffer());
+        }
+        if damage.is_empty() {
+            return Ok(fb);
+        }
+        let (size, cell_size) = (fb.inner.pixel_size(), self.cell_size());
+        match &mut fb.inner {
+            FramebufferInner::Cells(buffer) => {
+                let cells = damage_to_cells(damage, size, cell_size);
+                self.present_cells(buffer, Some(&cells))?;
+            }
+            FramebufferInner::Pixels(buffer) => {
+                buffer.damage = damage.to_vec();
+                self.present(buffer)?;
+            }
+        }
+        Ok(fb)
+    }
+
+    pub fn new_framebuffer(&self) -> RatatuiFramebuffer {
+        let inner = if self.graphics.is_some() {
+            let size = self.window_size();
+            FramebufferInner::Pixels(PixelBuffer {
+                pixels: vec![0; (size.w * size.h) as usize],
+                size,
+                damage: vec![Rectangle::from_size(size)],
+            })
+        } else {
+            let size = self.terminal_size();
+            FramebufferInner::Cells(ratatui::buffer::Buffer::empty(Rect::new(
+                0,
+                0,
+                size.width,
+                size.height,
+            )))
+        };
+        RatatuiFramebuffer { inner }
+    }
+
+    /// Draw `buffer` to the terminal, reducing its colours to the [`ColorMode`]
+    ///
+    /// If `damage` is given, only the colours of the cells within it are reduced again,
+    /// the other cells are expected to be unchanged since they were last presented.
+    fn present_cells(
+        &mut self,
+        buffer: &ratatui::buffer::Buffer,
+        damage: Option<&[Rect]>,
+    ) -> Result<(), RatatuiError> {
+        let (color_mode, dithering) = (self.color_mode, self.dithering);
+        let presented = match (self.presented.as_mut(), damage) {
+            (Some(presented), Some(damage)) if presented.area == buffer.area => {
+                if damage.is_empty() {
+                    return Ok(());
+                }
+                for area in damage {
+                    let area = area.intersection(buffer.area);
+                    for position in area.positions() {
+                        presented[position].clone_from(&buffer[position]);
+                    }
+                    quantize_area(presented, area, color_mode, dithering);
+                }
+                presented
+            }
+            _ => {
+                let mut presented = buffer.clone();
+                quantize_cells(&mut presented.content, buffer.area.width, color_mode, dithering);
+                self.presented.insert(presented)
+            }
+        };
+
+        // ratatui only writes the cells that differ from the previous frame
+        self.terminal.draw(|frame| {
+            frame.buffer_mut().content.clone_from(&presented.content);
+        })?;
+        Ok(())
+    }
+
+    /// Send the damaged parts of `buffer` to the terminal
+    fn present(&mut self, buffer: &mut PixelBuffer) -> Result<(), RatatuiError> {
+        let Some(graphics) = self.graphics.as_mut() else {
+            return Ok(());
+        };
+        if buffer.damage.is_empty() {
+            return Ok(());
+        }
+        if let Some(cell_size) = self.terminal.backend().cell_size() {
+            graphics.cell_size = cell_size;
+        }
+
+        const START_BUFFERING: &[u8] = b"\x1b[?2026h";
+        const STOP_BUFFERING: &[u8] = b"\x1b[?2026l";
+        let mut out = START_BUFFERING.to_vec();
+        graphics.encoder.encode(
+            &mut out,
+            Image {
+                pixels: &buffer.pixels,
+                size: buffer.size,
+            },
+            graphics.cell_size,
+            &buffer.damage,
+        );
+        out.extend_from_slice(STOP_BUFFERING);
+        buffer.damage.clear();
+
+        let backend = self.terminal.backend_mut();
+        backend.write_all(&out)?;
+        Write::flush(backend)?;
+        Ok(())
+    }
+
+    /// Copy `data` to the clipboard of the terminal using OSC 52
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`], if `data` is larger than [`MAX_CLIPBOARD_SIZE`].
+    /// Terminals without OSC 52 support ignore the sequence.
+    pub fn set_clipboard(&mut self, clipboard: Clipboard, data: &[u8]) -> io::Result<()> {
+        if data.len() > MAX_CLIPBOARD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "selection too large for the terminal clipboard",
+            ));
+        }
+        let backend = self.terminal.backend_mut();
+        backend.write_all(&osc52(clipboard, data))?;
+        Write::flush(backend)
+    }
+
+    /// Set the title of the terminal window using OSC 0
+    ///
+    /// Control characters are removed from `title`, which is also truncated to
+    /// [`MAX_TITLE_LENGTH`](crate::backend::ratatui::MAX_TITLE_LENGTH) characters.
+    /// The previous title is saved and restored once the renderer is dropped,
+    /// if the terminal supports it. Nothing is sent, if the title did not change.
+    pub fn set_title(&mut self, title: &str) -> io::Result<()> {
+        if self.title.as_deref() == Some(title) {
+            return Ok(());
+        }
+        let backend = self.terminal.backend_mut();
+        if self.title.is_none() {
+            backend.write_all(PUSH_TITLE)?;
+        }
+        backend.write_all(&osc0(title))?;
+        Write::flush(backend)?;
+        self.title = Some(title.to_owned());
+        Ok(())
+    }
+
+    /// Set the shape of the mouse pointer of the terminal using OSC 22
+    ///
+    /// Terminals without OSC 22 support ignore the sequence.
+    /// Nothing is sent, if the shape did not change.
+    pub fn set_mouse_shape(&mut self, shape: CursorIcon) -> io::Result<()> {
+        if self.mouse_shape == Some(shape) {
+            return Ok(());
+        }
+        let backend = self.terminal.backend_mut();
+        backend.write_all(&osc22(shape))?;
+        Write::flush(backend)?;
+        self.mouse_shape = Some(shape);
+        Ok(())
+    }
+
+    /// Ring the bell of the terminal
+    pub fn bell(&mut self) -> io::Result<()> {
+        let backend = self.terminal.backend_mut();
+        backend.write_all(BEL)?;
+        Write::flush(backend)
+    }
+
+    /// Undo changes to the title and mouse pointer of the terminal
+    fn restore_window(&mut self) -> io::Result<()> {
+        let backend = self.terminal.backend_mut();
+        if self.title.take().is_some() {
+            backend.write_all(POP_TITLE)?;
+        }
+        if self
+            .mouse_shape
+            .take()
+            .is_some_and(|shape| shape != CursorIcon::Default)
+        {
+            backend.write_all(&osc22(CursorIcon::Default))?;
+        }
+        Write::flush(backend)
+    }
+}
+
+impl Drop for RatatuiRenderer {
+    fn drop(&mut self) {
+        let _ = self.clear_graphics();
+        let _ = self.restore_window();
+        if self.terminal.backend().is_remote() {
+            // the remote side restores its terminal on its own
+            return;
+        }
+        let _ = std::io::stdout().execute(crossterm::event::DisableMouseCapture);
+        let _ = std::io::stdout().execute(crossterm::event::DisableBracketedPaste);
+        ratatui::restore();
+    }
+}
+
+#[derive(Debug)]
+pub struct RatatuiFramebuffer {
+    inner: FramebufferInner,
+}
+
+#[derive(Debug)]
+enum FramebufferInner {
+    Cells(ratatui::buffer::Buffer),
+    Pixels(PixelBuffer),
+}
+
+/// Pixels in `Xrgb8888` presented using a [`GraphicsProtocol`]
+#[derive(Debug)]
+struct PixelBuffer {
+    pixels: Vec<u32>,
+    size: Size<i32, Physical>,
+    /// Damage since the buffer was last presented
+    damage: Vec<Rectangle<i32, Physical>>,
+}
+
+impl RatatuiFramebuffer {
+    fn is_compatible_with(&self, renderer: &RatatuiRenderer) -> bool {
+        match &self.inner {
+            FramebufferInner::Cells(buffer) => {
+                renderer.graphics.is_none() && buffer.area.as_size() == renderer.terminal_size()
+            }
+            FramebufferInner::Pixels(buffer) => {
+                renderer.graphics.is_some() && buffer.size == renderer.window_size()
+            }
+        }
+    }
+
+    fn size(&self) -> Size<i32, Physical> {
+        match &self.inner {
+            FramebufferInner::Cells(buffer) => Size::new(buffer.area.width.into(), buffer.area.height.into()),
+            FramebufferInner::Pixels(buffer) => buffer.size,
+        }
+    }
+}
+
+impl Texture for RatatuiFramebuffer {
+    fn width(&self) -> u32 {
+        self.size().w as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.size().h as u32
+    }
+
+    fn format(&self) -> Option<Fourcc> {
+        Some(Fourcc::Argb8888)
+    }
+}
+
+/// Symbol of cells split into two pixels, the background colour covers the upper half
+/// of the cell and the foreground colour the lower half
+const LOWER_HALF_BLOCK: &str = "\u{2584}";
+
+impl FramebufferInner {
+    /// Size of the framebuffer in pixels, every cell covers two pixels
+    fn pixel_size(&self) -> Size<i32, Physical> {
+        match self {
+            FramebufferInner::Cells(buffer) => {
+                Size::new(buffer.area.width.into(), i32::from(buffer.area.height) * 2)
+            }
+            FramebufferInner::Pixels(buffer) => buffer.size,
+        }
+    }
+
+    /// Blend a premultiplied `color` onto the pixel at `(x, y)`
+    #[inline]
+    fn blend(&mut self, x: i32, y: i32, color: [f32; 4]) {
+        match self {
+            FramebufferInner::Cells(buffer) => {
+                let Some(cell) = buffer.cell_mut((x as u16, (y / 2) as u16)) else {
+                    return;
+                };
+                if cell.symbol() != LOWER_HALF_BLOCK {
+                    cell.fg = cell.bg;
+                    cell.set_symbol(LOWER_HALF_BLOCK);
+                }
+                let target = if y % 2 == 0 { &mut cell.bg } else { &mut cell.fg };
+                *target = blend_color(*target, color);
+            }
+            FramebufferInner::Pixels(buffer) => buffer.blend((y * buffer.size.w + x) as usize, color),
+        }
+    }
+
+    fn add_damage(&mut self, rect: Rectangle<i32, Physical>) {
+        if let FramebufferInner::Pixels(buffer) = self {
+            buffer.damage.push(rect);
+        }
+    }
+}
+
+/// Blend a premultiplied `color` onto `dst`, colours other than rgb are treated as black
+fn blend_color(dst: Color, color: [f32; 4]) -> Color {
+    let (r, g, b) = match dst {
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => (0, 0, 0),
+    };
+    let one_minus_alpha = 1f32 - color[3];
+    let channel = |src: f32, dst: u8| {
+        ((src + dst as f32 / 255f32 * one_minus_alpha) * 255f32)
+            .round()
+            .clamp(0f32, 255f32) as u8
+    };
+    Color::Rgb(channel(color[0], r), channel(color[1], g), channel(color[2], b))
+}
+
+impl ImportMemWl for RatatuiRenderer {
+    fn import_shm_buffer(
+        &mut self,
+        buffer: &wayland_server::protocol::wl_buffer::WlBuffer,
+        _surface: Option<&crate::wayland::compositor::SurfaceData>,
+        _damage: &[Rectangle<i32, BufferCoord>],
+    ) -> Result<Self::TextureId, Self::Error> {
+        with_buffer_contents(buffer, |ptr, len, data| -> Result<Self::TextureId, Self::Error> {
+            let format = shm_format_to_fourcc(data.format)
+                .ok_or(RatatuiError::UnsupportedWlPixelFormat(data.format))?;
+            let (Ok(offset), Ok(stride)) = (usize::try_from(data.offset), usize::try_from(data.stride))
+            else {
+                return Err(RatatuiError::BufferTooSmall);
+            };
+            if offset > len {
+                return Err(RatatuiError::BufferTooSmall);
+            }
+
+            // SAFETY: the pool is valid for `len` bytes while the callback is running
+            let pool = unsafe { std::slice::from_raw_parts(ptr, len) };
+            let size = Size::new(data.width, data.height);
+            let texture = RatatuiTexture::from_memory(&pool[offset..], format, size, stride, false)?;
+            Ok(texture.into())
+        })?
+    }
+}
+
+impl ImportDmaWl for RatatuiRenderer {}
+
+impl ImportDma for RatatuiRenderer {
+    fn dmabuf_formats(&self) -> FormatSet {
+        format::SUPPORTED_FORMATS
+            .iter()
+            .map(|code| Format {
+                code: *code,
+                modifier: Modifier::Linear,
+            })
+            .collect()
+    }
+
+    fn import_dmabuf(
+        &mut self,
+        dmabuf: &Dmabuf,
+        _damage: Option<&[Rectangle<i32, BufferCoord>]>,
+    ) -> Result<Self::TextureId, Self::Error> {
+        let format = dmabuf.format();
+        if dmabuf.num_planes() != 1 || dmabuf.has_modifier() {
+            return Err(RatatuiError::UnsupportedDmabufFormat(format));
+        }
+        let stride = dmabuf.strides().next().unwrap_or_default() as usize;
+
+        dmabuf.sync_plane(0, DmabufSyncFlags::START | DmabufSyncFlags::READ)?;
+        let texture = dmabuf
+            .map_plane(0, DmabufMappingMode::READ)
+            .map_err(RatatuiError::from)
+            .and_then(|map| {
+                // SAFETY: the mapping is valid for `length` bytes until it is dropped
+                let data = unsafe { std::slice::from_raw_parts(map.ptr() as *const u8, map.length()) };
+                RatatuiTexture::from_memory(data, format.code, dmabuf.size(), stride, dmabuf.y_inverted())
+            });
+        dmabuf.sync_plane(0, DmabufSyncFlags::END | DmabufSyncFlags::READ)?;
+
+        Ok(texture?.into())
+    }
+}
+
+/// Buffers managed by EGL cannot be accessed without an EGL context, so importing them is not supported
+#[cfg(all(
+    feature = "wayland_frontend",
+    feature = "backend_egl",
+    feature = "use_system_lib"
+))]
+impl ImportEgl for RatatuiRenderer {
+    fn bind_wl_display(
+        &mut self,
+        _display: &wayland_server::DisplayHandle,
+    ) -> Result<(), crate::backend::egl::Error> {
+        Err(crate::backend::egl::Error::DisplayNotSupported)
+    }
+
+    fn unbind_wl_display(&mut self) {}
+
+    fn egl_reader(&self) -> Option<&EGLBufferReader> {
+        None
+    }
+
+    fn import_egl_buffer(
+        &mut self,
+        _buffer: &wayland_server::protocol::wl_buffer::WlBuffer,
+        _surface: Option<&crate::wayland::compositor::SurfaceData>,
+        _damage: &[Rectangle<i32, BufferCoord>],
+    ) -> Result<Self::TextureId, Self::Error> {
+        Err(RatatuiError::UnsupportedBufferType)
+    }
+}
+
+/// A widget that displays the compositor scene
+#[derive(Debug)]
+pub struct CompositorWidget;
+
+/// The state of the `CompositorWidget`
+#[derive(Debug)]
+pub struct CompositorWidgetState;
+
+/// A texture for the ratatui renderer
+///
+/// Pixels are stored as premultiplied `Argb8888`, independent of the format they were imported from.
+#[derive(Debug, Clone)]
+pub struct RatatuiTexture {
+    pixels: Vec<u32>,
+    size: Size<i32, BufferCoord>,
+    /// Format of the imported data
+    format: Fourcc,
+    flipped: bool,
+}
+
+/// TODO: doc
+#[derive(Clone, Debug)]
+pub struct RatatuiTextureHandle(Arc<Mutex<RatatuiTexture>>);
+
+impl From<RatatuiTexture> for RatatuiTextureHandle {
+    fn from(value: RatatuiTexture) -> Self {
+        Self(Arc::new(Mutex::new(value)))
+    }
+}
+
+/// TODO: docs
+#[derive(thiserror::Error, Debug)]
+pub enum RatatuiError {
+    /// TODO: docs
+    #[error("Texture width or height {}x{} cannot be represented as u16", .0.0, .0.1)]
+    TextureTooBig((i32, i32)),
+    /// TODO: docs
+    #[error("Texture size {}x{} does not match terminal size {}x{}", actual.width, actual.height, expected.width, expected.height)]
+    InvalidTextureSize {
+        /// TODO: docs
+        actual: ratatui::layout::Size,
+        /// TODO: docs
+        expected: ratatui::layout::Size,
+    },
+    /// TODO: docs
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedWlPixelFormat(wayland_server::protocol::wl_shm::Format),
+    /// The pixel format cannot be converted
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedPixelFormat(Fourcc),
+    /// The dmabuf has multiple planes or uses a non-linear modifier
+    #[error("Unsupported dmabuf format: {0:?}")]
+    UnsupportedDmabufFormat(Format),
+    /// The type of the buffer cannot be imported
+    #[error("Unsupported buffer type")]
+    UnsupportedBufferType,
+    /// The buffer holds less data than required for its size and format
+    #[error("Buffer is too small for its size and format")]
+    BufferTooSmall,
+    /// The region to update is not contained in the texture
+    #[error("Region {0:?} is outside of the texture")]
+    InvalidRegion(Rectangle<i32, BufferCoord>),
+    /// TODO: docs
+    #[error("Buffer access error: {0:?}")]
+    BufferAccessError(#[from] crate::wayland::shm::BufferAccessError),
+    /// Mapping the dmabuf failed
+    #[error("Failed to map dmabuf: {0}")]
+    DmabufMapping(#[from] DmabufMappingFailed),
+    /// Synchronizing access to the dmabuf failed
+    #[error("Failed to synchronize dmabuf access: {0}")]
+    DmabufSync(#[from] DmabufSyncFailed),
+    /// TODO: docs
+    #[error("IO error: {0:?}")]
+    IoError(#[from] std::io::Error),
+    /// The terminal does not report the size of its cells in pixels
+    #[error("Terminal cell size in pixels is unknown")]
+    UnknownCellSize,
+}
+
+impl RatatuiTexture {
+    fn from_memory(
+        data: &[u8],
+        format: Fourcc,
+        size: Size<i32, BufferCoord>,
+        stride: usize,
+        flipped: bool,
+    ) -> Result<Self, RatatuiError> {
+        let (Ok(w), Ok(h)) = (u16::try_from(size.w), u16::try_from(size.h)) else {
+            return Err(RatatuiError::TextureTooBig((size.w, size.h)));
+        };
+        let mut texture = RatatuiTexture {
+            pixels: vec![0; usize::from(w) * usize::from(h)],
+            size,
+            format,
+            flipped,
+        };
+        texture.write(data, stride, Rectangle::from_size(size))?;
+        Ok(texture)
+    }
+
+    /// Convert rows of `data` in the format of the texture into the pixels covered by `region`
+    fn write(
+        &mut self,
+        data: &[u8],
+        stride: usize,
+        region: Rectangle<i32, BufferCoord>,
+    ) -> Result<(), RatatuiError> {
+        if !Rectangle::from_size(self.size).contains_rect(region) {
+            return Err(RatatuiError::InvalidRegion(region));
+        }
+        let width = region.size.w as usize;
+        let min_stride = format::min_stride(self.format, width)
+            .ok_or(RatatuiError::UnsupportedPixelFormat(self.format))?;
+        if stride < min_stride {
+            return Err(RatatuiError::BufferTooSmall);
+        }
+
+        for row in 0..region.size.h {
+            let mut y = region.loc.y + row;
+            if self.flipped {
+                y = self.size.h - 1 - y;
+            }
+            let start = (y * self.size.w + region.loc.x) as usize;
+            let src = data
+                .get(row as usize * stride..)
+                .ok_or(RatatuiError::BufferTooSmall)?;
+            format::convert_row(self.format, src, &mut self.pixels[start..start + width])
+                .ok_or(RatatuiError::BufferTooSmall)?;
+        }
+        Ok(())
+    }
+
+    /// Premultiplied colour of the texture at `point` using `filter`
+    fn sample(&self, point: Point<f64, BufferCoord>, filter: TextureFilter) -> [f32; 4] {
+        if self.pixels.is_empty() {
+            return [0f32; 4];
+        }
+        let texel = |x: i32, y: i32| {
+            let x = x.clamp(0, self.size.w - 1);
+            let y = y.clamp(0, self.size.h - 1);
+            let pixel = self.pixels[(y * self.size.w + x) as usize];
+            [16, 8, 0, 24].map(|shift| ((pixel >> shift) & 0xff) as f32 / 255f32)
+        };
+
+        match filter {
+            TextureFilter::Nearest => texel(point.x.floor() as i32, point.y.floor() as i32),
+            TextureFilter::Linear => {
+                // texel centers are at half pixel offsets
+                let (x, y) = (point.x - 0.5, point.y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = ((x - x0) as f32, (y - y0) as f32);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                let [top_left, top_right, bottom_left, bottom_right] = [
+                    texel(x0, y0),
+                    texel(x0 + 1, y0),
+                    texel(x0, y0 + 1),
+                    texel(x0 + 1, y0 + 1),
+                ];
+                std::array::from_fn(|i| {
+                    let top = top_left[i] * (1f32 - fx) + top_right[i] * fx;
+                    let bottom = bottom_left[i] * (1f32 - fx) + bottom_right[i] * fx;
+                    top * (1f32 - fy) + bottom * fy
+                })
+            }
+        }
+    }
+}
+
+impl Texture for RatatuiTextureHandle {
+    fn width(&self) -> u32 {
+        self.0.lock().unwrap().size.w as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.0.lock().unwrap().size.h as u32
+    }
+
+    fn format(&self) -> Option<Fourcc> {
+        Some(self.0.lock().unwrap().format)
+    }
+}
+
+/// A frame for the ratatui renderer
+#[derive(Debug)]
+pub struct RatatuiFrame<'frame, 'buffer> {
+    renderer: &'frame mut RatatuiRenderer,
+    framebuffer: &'frame mut <RatatuiRenderer as RendererSuper>::Framebuffer<'buffer>,
+    transform: Transform,
+    /// Size of the output before applying `transform`
+    output_size: Size<i32, Physical>,
+    /// Size of the output after applying `transform`
+    size: Size<i32, Physical>,
+    /// Pixels drawn by this frame, only the cells covering them are presented
+    damage: Vec<Rectangle<i32, Physical>>,
+}
+
+impl<'frame> RatatuiFrame<'frame, '_> {
+    fn new(
+        renderer: &'frame mut RatatuiRenderer,
+        framebuffer: &'frame mut RatatuiFramebuffer,
+        output_size: Size<i32, Physical>,
+        transform: Transform,
+    ) -> Self {
+        if !framebuffer.is_compatible_with(renderer) {
+            tracing::warn!(
+                "window resized? fb {:?}, window {:?}; creating new framebuffer",
+                framebuffer.size(),
+                renderer.window_size()
+            );
+            *framebuffer = renderer.new_framebuffer();
+        }
+
+        Self {
+            renderer,
+            framebuffer,
+            transform,
+            output_size,
+            size: transform.transform_size(output_size),
+            damage: Vec::new(),
+        }
+    }
+
+    /// Blend the premultiplied colour returned by `shader` onto every pixel covered by `rects`
+    ///
+    /// `rects` are in output coordinates, `shader` is called with the center of each pixel
+    /// in output coordinates.
+    fn draw(
+        &mut self,
+        rects: impl IntoIterator<Item = Rectangle<i32, Physical>>,
+        mut shader: impl FnMut(Point<f64, Physical>) -> [f32; 4],
+    ) {
+        let framebuffer = &mut self.framebuffer.inner;
+        let bounds = Rectangle::from_size(framebuffer.pixel_size());
+        let inverse = self.transform.invert();
+        let output_size = self.output_size.to_f64();
+
+        for rect in rects {
+            let Some(rect) = self
+                .transform
+                .transform_rect_in(rect, &self.size)
+                .intersection(bounds)
+            else {
+                continue;
+            };
+
+            for y in rect.loc.y..rect.loc.y + rect.size.h {
+                for x in rect.loc.x..rect.loc.x + rect.size.w {
+                    let center = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                    let color = shader(inverse.transform_point_in(center, &output_size));
+                    framebuffer.blend(x, y, color);
+                }
+            }
+            framebuffer.add_damage(rect);
+            self.damage.push(rect);
+        }
+    }
+}
+
+/// Inverse of `transform` when applied using [`Transform::transform_point_in`]
+///
+/// Unlike with [`Transform::invert`], every flipped transformation is its own inverse.
+fn inverse(transform: Transform) -> Transform {
+    match transform {
+        Transform::Flipped90 | Transform::Flipped270 => transform,
+        transform => transform.invert(),
+    }
+}
+
+impl PixelBuffer {
+    /// Blend a premultiplied `color` onto the pixel at `idx`
+    #[inline]
+    fn blend(&mut self, idx: usize, color: [f32; 4]) {
+        let dst = self.pixels[idx];
+        let one_minus_alpha = 1f32 - color[3];
+        let channel = |shift: u32, src: f32| {
+            let dst = ((dst >> shift) & 0xff) as f32 / 255f32;
+            (((src + dst * one_minus_alpha) * 255f32)
+                .round()
+                .clamp(0f32, 255f32) as u32)
+                << shift
+        };
+        self.pixels[idx] = channel(16, color[0]) | channel(8, color[1]) | channel(0, color[2]);
+    }
+}
+
+impl Drop for RatatuiFrame<'_, '_> {
+    fn drop(&mut self) {
+        let (size, cell_size) = (self.framebuffer.inner.pixel_size(), self.renderer.cell_size());
+        match &mut self.framebuffer.inner {
+            FramebufferInner::Cells(buffer) => {
+                let cells = damage_to_cells(&self.damage, size, cell_size);
+                if let Err(err) = self.renderer.present_cells(buffer, Some(&cells)) {
+                    tracing::warn!(?err, "Failed to present frame");
+                }
+            }
+            FramebufferInner::Pixels(buffer) => {
+                if let Err(err) = self.renderer.present(buffer) {
+                    tracing::warn!(?err, "Failed to present frame");
+                }
+            }
+        }
+    }
+}
+
+impl Frame for RatatuiFrame<'_, '_> {
+    type Error = RatatuiError;
+    type TextureId = RatatuiTextureHandle;
+
+    fn context_id(&self) -> ContextId<Self::TextureId> {
+        ContextId(Arc::new(InnerContextId(0)), PhantomData)
+    }
+
+    fn clear(&mut self, color: Color32F, at: &[Rectangle<i32, Physical>]) -> Result<(), Self::Error> {
+        let color = [color.r(), color.g(), color.b(), 1f32];
+        self.draw(at.iter().copied(), |_| color);
+        Ok(())
+    }
+
+    fn draw_solid(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: Color32F,
+    ) -> Result<(), Self::Error> {
+        let color = [color.r(), color.g(), color.b(), color.a()];
+        let rects = damage
+            .iter()
+            .filter_map(|rect| Rectangle::new(rect.loc + dst.loc, rect.size).intersection(dst));
+        self.draw(rects, |_| color);
+        Ok(())
+    }
+
+    fn render_texture_from_to(
+        &mut self,
+        texture: &Self::TextureId,
+        src: Rectangle<f64, BufferCoord>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+        src_transform: Transform,
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        if dst.is_empty() {
+            return Ok(());
+        }
+        let texture = texture.0.lock().unwrap();
+
+        // size of the source after applying the transform, matching the orientation of `dst`
+        let src_size = src_transform.transform_size(src.size);
+        let scale_x = src_size.w / dst.size.w as f64;
+        let scale_y = src_size.h / dst.size.h as f64;
+        let filter = if scale_x < 1f64 || scale_y < 1f64 {
+            self.renderer.upscale_filter
+        } else {
+            self.renderer.downscale_filter
+        };
+        let src_inverse = inverse(src_transform);
+        let dst_loc = dst.loc.to_f64();
+
+        let rects = damage
+            .iter()
+            .filter_map(|rect| Rectangle::new(rect.loc + dst.loc, rect.size).intersection(dst));
+        self.draw(rects, |point| {
+            let point = point - dst_loc;
+            let point = Point::<f64, BufferCoord>::new(point.x * scale_x, point.y * scale_y);
+            let point = src.loc + src_inverse.transform_point_in(point, &src_size);
+            texture.sample(point, filter).map(|c| c * alpha)
+        });
+        Ok(())
+    }
+
+    fn transformation(&self) -> Transform {
+        self.transform
+    }
+
+    fn wait(&mut self, sync: &sync::SyncPoint) -> Result<(), Self::Error> {
+        while let Err(Interrupted) = sync.wait() {}
+        Ok(())
+    }
+
+    fn finish(self) -> Result<sync::SyncPoint, Self::Error> {
+        // rendering happens on the cpu, so the frame is done once it is presented
+        Ok(sync::SyncPoint::default())
+    }
+}
+
+impl RendererSuper for RatatuiRenderer {
+    type Error = RatatuiError;
+    type TextureId = RatatuiTextureHandle;
+    type Framebuffer<'buffer> = RatatuiFramebuffer;
+    type Frame<'frame, 'buffer>
+        = RatatuiFrame<'frame, 'buffer>
+    where
+        'buffer: 'frame,
+        Self: 'frame;
+}
+
+impl Renderer for RatatuiRenderer {
+    fn context_id(&self) -> ContextId<Self::TextureId> {
+        ContextId(Arc::new(InnerContextId(0)), PhantomData)
+    }
+
+    fn downscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.downscale_filter = filter;
+        Ok(())
+    }
+
+    fn upscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.upscale_filter = filter;
+        Ok(())
+    }
+
+    fn set_debug_flags(&mut self, flags: DebugFlags) {
+        self.debug_flags = flags;
+    }
+
+    fn debug_flags(&self) -> DebugFlags {
+        self.debug_flags
+    }
+
+    fn render<'frame, 'buffer>(
+        &'frame mut self,
+        framebuffer: &'frame mut Self::Framebuffer<'buffer>,
+        output_size: Size<i32, Physical>,
+        dst_transform: Transform,
+    ) -> Result<Self::Frame<'frame, 'buffer>, Self::Error>
+    where
+        'buffer: 'frame,
+    {
+        Ok(RatatuiFrame::new(self, framebuffer, output_size, dst_transform))
+    }
+
+    fn wait(&mut self, sync: &sync::SyncPoint) -> Result<(), Self::Error> {
+        while let Err(Interrupted) = sync.wait() {}
+        Ok(())
+    }
+}
+
+impl ImportMem for RatatuiRenderer {
+    fn import_memory(
+        &mut self,
+        data: &[u8],
+        format: Fourcc,
+        size: Size<i32, BufferCoord>,
+        flipped: bool,
+    ) -> Result<Self::TextureId, Self::Error> {
+        let stride = format::min_stride(format, size.w.max(0) as usize)
+            .ok_or(RatatuiError::UnsupportedPixelFormat(format))?;
+        let texture = RatatuiTexture::from_memory(data, format, size, stride, flipped)?;
+        Ok(texture.into())
+    }
+
+    fn update_memory(
+        &mut self,
+        texture: &Self::TextureId,
+        data: &[u8],
+        region: Rectangle<i32, BufferCoord>,
+    ) -> Result<(), Self::Error> {
+        let mut texture = texture.0.lock().unwrap();
+        let stride = format::min_stride(texture.format, region.size.w.max(0) as usize)
+            .ok_or(RatatuiError::UnsupportedPixelFormat(texture.format))?;
+        texture.write(data, stride, region)
+    }
+
+    fn mem_formats(&self) -> Box<dyn Iterator<Item = Fourcc>> {
+        Box::new(format::SUPPORTED_FORMATS.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use ratatui::{backend::WindowSize, layout::Size as CellSize, style::Color};
+
+    use super::{FramebufferInner, RatatuiFramebuffer, RatatuiRenderer, LOWER_HALF_BLOCK};
+    use crate::backend::{
+        ratatui::GraphicsProtocol,
+        renderer::{test::conformance, Renderer, TextureFilter},
+    };
+
+    /// Renderer drawing to a discarded remote terminal of `columns` times `rows` cells of `cell_size` pixels
+    fn renderer(columns: u16, rows: u16, cell_size: (u16, u16)) -> RatatuiRenderer {
+        let size = WindowSize {
+            columns_rows: CellSize::new(columns, rows),
+            pixels: CellSize::new(columns * cell_size.0, rows * cell_size.1),
+        };
+        let mut renderer = RatatuiRenderer::remote(Box::new(io::sink()), Arc::new(Mutex::new(size))).unwrap();
+        renderer.upscale_filter(TextureFilter::Nearest).unwrap();
+        renderer.downscale_filter(TextureFilter::Nearest).unwrap();
+        renderer
+    }
+
+    /// Reads back the 24-bit colours of `framebuffer` as opaque rgba
+    fn read_framebuffer(framebuffer: &RatatuiFramebuffer) -> image::RgbaImage {
+        let size = framebuffer.inner.pixel_size();
+        let rgb = |color: Color| match color {
+            Color::Rgb(r, g, b) => image::Rgba([r, g, b, 255]),
+            _ => image::Rgba([0, 0, 0, 255]),
+        };
+        image::RgbaImage::from_fn(size.w as u32, size.h as u32, |x, y| match &framebuffer.inner {
+            FramebufferInner::Cells(buffer) => {
+                let cell = &buffer[(x as u16, (y / 2) as u16)];
+                if y % 2 == 1 && cell.symbol() == LOWER_HALF_BLOCK {
+                    rgb(cell.fg)
+                } else {
+                    rgb(cell.bg)
+                }
+            }
+            FramebufferInner::Pixels(buffer) => {
+                let [b, g, r, _] = buffer.pixels[(y * size.w as u32 + x) as usize].to_le_bytes();
+                image::Rgba([r, g, b, 255])
+            }
+        })
+    }
+
+    fn check(renderer: &mut RatatuiRenderer, scene: &conformance::Scene) {
+        let mut framebuffer = renderer.new_framebuffer();
+        assert_eq!(framebuffer.size().w, scene.size.w);
+        if let Err(err) = scene
+            .render(renderer, &mut framebuffer)
+            .and_then(|_| scene.check_image(&read_framebuffer(&framebuffer), &conformance::reference_dir()))
+        {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn conformance_cells() {
+        for scene in conformance::scenes() {
+            // every cell covers two pixels
+            let mut renderer = renderer(scene.size.w as u16, scene.size.h as u16 / 2, (8, 16));
+            check(&mut renderer, &scene);
+        }
+    }
+
+    #[test]
+    fn conformance_pixels() {
+        for scene in conformance::scenes() {
+            let mut renderer = renderer(scene.size.w as u16, scene.size.h as u16, (1, 1));
+            renderer
+                .set_graphics_protocol(Some(GraphicsProtocol::Kitty))
+                .unwrap();
+            check(&mut renderer, &scene);
+        }
+    }
+}
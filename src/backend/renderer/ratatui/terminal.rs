@@ -0,0 +1,147 @@
+//! Terminal the renderer draws to, either the one of the process or a remote terminal
+
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use ratatui::{
+    backend::{Backend, ClearType, CrosstermBackend, WindowSize},
+    buffer::Cell,
+    layout::{Position, Size},
+};
+
+use crate::utils::{Physical, Size as PhysicalSize};
+
+/// A [`Backend`] writing to stdout or to the connection of a remote terminal
+pub(super) struct TerminalBackend {
+    backend: CrosstermBackend<Box<dyn Write + Send>>,
+    /// Size last reported by a remote terminal, `None` for the terminal of the process
+    remote_size: Option<Arc<Mutex<WindowSize>>>,
+}
+
+impl fmt::Debug for TerminalBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TerminalBackend")
+            .field("remote_size", &self.remote_size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TerminalBackend {
+    /// Backend for the terminal attached to stdout
+    pub(super) fn stdout() -> TerminalBackend {
+        TerminalBackend {
+            backend: CrosstermBackend::new(Box::new(io::stdout())),
+            remote_size: None,
+        }
+    }
+
+    /// Backend for a remote terminal, which reports its size through `size`
+    pub(super) fn remote(writer: Box<dyn Write + Send>, size: Arc<Mutex<WindowSize>>) -> TerminalBackend {
+        TerminalBackend {
+            backend: CrosstermBackend::new(writer),
+            remote_size: Some(size),
+        }
+    }
+
+    pub(super) fn is_remote(&self) -> bool {
+        self.remote_size.is_some()
+    }
+
+    fn current_window_size(&self) -> io::Result<WindowSize> {
+        match &self.remote_size {
+            Some(size) => Ok(*size.lock().unwrap()),
+            None => {
+                let size = crossterm::terminal::window_size()?;
+                Ok(WindowSize {
+                    columns_rows: Size::new(size.columns, size.rows),
+                    pixels: Size::new(size.width, size.height),
+                })
+            }
+        }
+    }
+
+    /// Size of a single terminal cell in pixels, if known
+    pub(super) fn cell_size(&self) -> Option<PhysicalSize<i32, Physical>> {
+        let WindowSize { columns_rows, pixels } = self.current_window_size().ok()?;
+        if columns_rows.width == 0
+            || columns_rows.height == 0
+            || pixels.width < columns_rows.width
+            || pixels.height < columns_rows.height
+        {
+            return None;
+        }
+        Some(PhysicalSize::new(
+            i32::from(pixels.width / columns_rows.width),
+            i32::from(pixels.height / columns_rows.height),
+        ))
+    }
+}
+
+impl Write for TerminalBackend {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.backend.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Write::flush(&mut self.backend)
+    }
+}
+
+impl Backend for TerminalBackend {
+    fn draw<'a, I>(&mut self, content: I) -> io::Result<()>
+    where
+        I: Iterator<Item = (u16, u16, &'a Cell)>,
+    {
+        self.backend.draw(content)
+    }
+
+    fn append_lines(&mut self, n: u16) -> io::Result<()> {
+        self.backend.append_lines(n)
+    }
+
+    fn hide_cursor(&mut self) -> io::Result<()> {
+        self.backend.hide_cursor()
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        self.backend.show_cursor()
+    }
+
+    fn get_cursor_position(&mut self) -> io::Result<Position> {
+        if self.is_remote() {
+            // answering requires reading from the remote terminal, only used by inline viewports
+            return Err(io::Error::from(io::ErrorKind::Unsupported));
+        }
+        self.backend.get_cursor_position()
+    }
+
+    fn set_cursor_position<P: Into<Position>>(&mut self, position: P) -> io::Result<()> {
+        self.backend.set_cursor_position(position)
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.backend.clear()
+    }
+
+    fn clear_region(&mut self, clear_type: ClearType) -> io::Result<()> {
+        self.backend.clear_region(clear_type)
+    }
+
+    fn size(&self) -> io::Result<Size> {
+        match &self.remote_size {
+            Some(size) => Ok(size.lock().unwrap().columns_rows),
+            None => self.backend.size(),
+        }
+    }
+
+    fn window_size(&mut self) -> io::Result<WindowSize> {
+        self.current_window_size()
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Backend::flush(&mut self.backend)
+    }
+}
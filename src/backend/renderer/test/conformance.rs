@@ -0,0 +1,654 @@
+//! Conformance tests shared by all renderers
+//!
+//! Every [`Scene`] returned by [`scenes`] is a scripted list of frames, which is rendered through
+//! an [`OutputDamageTracker`] and compared against a reference image. The scenes cover clipping,
+//! blending, texture crops and scaling, buffer and output transformations and damage tracking.
+//!
+//! [`check_offscreen`] runs all scenes on a renderer with offscreen buffers and reads the results
+//! back with [`ExportMem`], so the suite also runs without a gpu, e.g. on pixman or on llvmpipe and
+//! lavapipe. Renderers not supporting [`ExportMem`] render the scenes using [`Scene::render`] and
+//! pass the result to [`Scene::check_image`] instead, or just make sure rendering does not fail like
+//! the [`DummyRenderer`](super::DummyRenderer).
+//!
+//! Renderers needing a device skip the suite using [`require_device`] if there is none, unless
+//! they are listed in [`REQUIRE_DEVICES_VAR`].
+//!
+//! The reference images are stored as png in `src/backend/renderer/test/reference`.
+//! To create missing references or update the existing ones, run the tests of a renderer with the
+//! `SMITHAY_UPDATE_REFERENCES` environment variable set. Images not matching their reference are
+//! written to `smithay-conformance` in the temporary directory for inspection.
+//!
+//! Textures are sampled with [`TextureFilter::Nearest`] and only scaled up by integer factors,
+//! so the results do not depend on the precision of filtering. Channels may still differ by
+//! [`TOLERANCE`] from the reference to allow for different rounding of blended colors.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::{self, OutputDamageTracker},
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                Kind,
+            },
+            Color32F, ExportMem, ImportMem, Offscreen, Renderer, Texture, TextureFilter, TextureMapping,
+        },
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Size, Transform},
+};
+
+/// Largest difference of a channel to the reference, which is still accepted
+pub const TOLERANCE: u8 = 2;
+
+/// Environment variable to set for updating the reference images instead of comparing against them
+pub const UPDATE_REFERENCES_VAR: &str = "SMITHAY_UPDATE_REFERENCES";
+
+/// Environment variable listing the renderers, separated by commas, whose tests fail instead of being
+/// skipped if there is no device, e.g. `gles,wgpu`
+pub const REQUIRE_DEVICES_VAR: &str = "SMITHAY_REQUIRE_DEVICES";
+
+/// Color the output is cleared with before rendering the elements of a scene
+pub const CLEAR_COLOR: Color32F = Color32F::new(0.2, 0.2, 0.2, 1.0);
+
+/// Directory of the reference images of [`scenes`]
+pub fn reference_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src/backend/renderer/test/reference")
+}
+
+/// Returns `renderer`, or `None` to skip the tests of a renderer without a device
+///
+/// Panics if `name` is listed in [`REQUIRE_DEVICES_VAR`], so the tests are not skipped silently
+/// where they are expected to run.
+pub fn require_device<R>(name: &str, renderer: Option<R>) -> Option<R> {
+    if renderer.is_none() {
+        let required = std::env::var(REQUIRE_DEVICES_VAR)
+            .is_ok_and(|names| names.split(',').any(|required| required.trim() == name));
+        if required {
+            panic!("No device found for the {name} renderer, but it is listed in {REQUIRE_DEVICES_VAR}");
+        }
+        tracing::warn!("No device found for the {} renderer, skipping the tests", name);
+    }
+    renderer
+}
+
+/// Error returned by the conformance tests
+#[derive(Debug, thiserror::Error)]
+pub enum ConformanceError<E: std::error::Error> {
+    /// The renderer returned an error
+    #[error("The renderer failed: {0}")]
+    Renderer(#[source] E),
+    /// Reading or writing an image failed
+    #[error("Failed to access an image: {0}")]
+    Image(#[from] image::ImageError),
+    /// There is no reference image for the scene
+    #[error("Missing reference image {0}, set {UPDATE_REFERENCES_VAR} to create it")]
+    MissingReference(PathBuf),
+    /// The rendered image differs from the reference
+    #[error(
+        "Scene {scene} differs from the reference in {pixels} pixels, the first at {first:?} is {actual:?} instead of {expected:?} (see {path})",
+        path = .actual_path.display()
+    )]
+    Mismatch {
+        /// Name of the scene
+        scene: &'static str,
+        /// Number of differing pixels
+        pixels: usize,
+        /// Location of the first differing pixel
+        first: Point<u32, Buffer>,
+        /// Expected color of the first differing pixel as rgba
+        expected: [u8; 4],
+        /// Actual color of the first differing pixel as rgba
+        actual: [u8; 4],
+        /// Path the rendered image was written to
+        actual_path: PathBuf,
+    },
+}
+
+impl<E: std::error::Error> From<damage::Error<E>> for ConformanceError<E> {
+    #[inline]
+    fn from(err: damage::Error<E>) -> Self {
+        match err {
+            damage::Error::Rendering(err) => ConformanceError::Renderer(err),
+            damage::Error::OutputNoMode(_) => unreachable!("the damage tracker of a scene has a mode"),
+        }
+    }
+}
+
+/// Element of a [`Scene`]
+#[derive(Debug, Clone)]
+pub enum SceneElement {
+    /// A solid color
+    Solid {
+        /// The buffer of the element, the same buffer in multiple frames refers to the same element
+        buffer: SolidColorBuffer,
+        /// Location of the element on the output
+        location: Point<i32, Physical>,
+    },
+    /// A texture imported from memory
+    Memory {
+        /// The buffer of the element, the same buffer in multiple frames refers to the same element
+        buffer: MemoryRenderBuffer,
+        /// Location of the element on the output
+        location: Point<i32, Physical>,
+        /// Alpha multiplier of the element
+        alpha: f32,
+        /// Region of the buffer to show, the whole buffer if `None`
+        src: Option<Rectangle<f64, Logical>>,
+        /// Size of the element, the size of `src` if `None`
+        size: Option<Size<i32, Logical>>,
+    },
+}
+
+impl SceneElement {
+    /// Creates a solid color element of `size` at `location`
+    pub fn solid(
+        location: impl Into<Point<i32, Physical>>,
+        size: impl Into<Size<i32, Logical>>,
+        color: impl Into<Color32F>,
+    ) -> Self {
+        SceneElement::Solid {
+            buffer: SolidColorBuffer::new(size, color),
+            location: location.into(),
+        }
+    }
+
+    /// Creates an element showing the whole `buffer` at `location`
+    pub fn memory(buffer: &MemoryRenderBuffer, location: impl Into<Point<i32, Physical>>) -> Self {
+        SceneElement::Memory {
+            buffer: buffer.clone(),
+            location: location.into(),
+            alpha: 1.0,
+            src: None,
+            size: None,
+        }
+    }
+
+    /// Moves the element to `location`, keeping its identity
+    pub fn moved(&self, location: impl Into<Point<i32, Physical>>) -> Self {
+        let mut element = self.clone();
+        match &mut element {
+            SceneElement::Solid { location: loc, .. } | SceneElement::Memory { location: loc, .. } => {
+                *loc = location.into()
+            }
+        }
+        element
+    }
+
+    fn render_element<R>(&self, renderer: &mut R) -> Result<ConformanceRenderElement<R>, R::Error>
+    where
+        R: Renderer + ImportMem,
+        R::TextureId: Send + Clone + 'static,
+    {
+        Ok(match self {
+            SceneElement::Solid { buffer, location } => ConformanceRenderElement::Solid(
+                SolidColorRenderElement::from_buffer(buffer, *location, 1.0, 1.0, Kind::Unspecified),
+            ),
+            SceneElement::Memory {
+                buffer,
+                location,
+                alpha,
+                src,
+                size,
+            } => ConformanceRenderElement::Memory(MemoryRenderBufferRenderElement::from_buffer(
+                renderer,
+                location.to_f64(),
+                buffer,
+                Some(*alpha),
+                *src,
+                *size,
+                Kind::Unspecified,
+            )?),
+        })
+    }
+}
+
+crate::backend::renderer::element::render_elements! {
+    ConformanceRenderElement<R> where
+        R: ImportMem;
+    Solid=SolidColorRenderElement,
+    Memory=MemoryRenderBufferRenderElement<R>,
+}
+
+/// A scripted list of frames rendered to an output
+#[derive(Debug, Clone)]
+pub struct Scene {
+    /// Name of the scene and its reference image
+    pub name: &'static str,
+    /// Size of the framebuffer
+    pub size: Size<i32, Buffer>,
+    /// Transformation of the output
+    pub transform: Transform,
+    /// Elements of every frame, from top to bottom
+    ///
+    /// All frames are rendered to the same framebuffer, after the first frame only the damage
+    /// is redrawn. The reference image shows the result of the last frame.
+    pub frames: Vec<Vec<SceneElement>>,
+}
+
+impl Scene {
+    /// Renders all frames of the scene to `framebuffer`
+    pub fn render<R>(
+        &self,
+        renderer: &mut R,
+        framebuffer: &mut R::Framebuffer<'_>,
+    ) -> Result<(), ConformanceError<R::Error>>
+    where
+        R: Renderer + ImportMem,
+        R::TextureId: Send + Clone + 'static,
+    {
+        let mode_size = Size::<i32, Physical>::from((self.size.w, self.size.h));
+        let mut damage_tracker = OutputDamageTracker::new(mode_size, 1.0, self.transform);
+        for (age, frame) in self.frames.iter().enumerate() {
+            let elements = frame
+                .iter()
+                .map(|element| element.render_element(renderer))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(ConformanceError::Renderer)?;
+            let result =
+                damage_tracker.render_output(renderer, framebuffer, age.min(1), &elements, CLEAR_COLOR)?;
+            renderer.wait(&result.sync).map_err(ConformanceError::Renderer)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the scene to `framebuffer` and compares the result against the reference image in `reference_dir`
+    ///
+    /// If [`UPDATE_REFERENCES_VAR`] is set, the reference image is replaced with the result instead.
+    pub fn check<R>(
+        &self,
+        renderer: &mut R,
+        framebuffer: &mut R::Framebuffer<'_>,
+        reference_dir: &Path,
+    ) -> Result<(), ConformanceError<R::Error>>
+    where
+        R: Renderer + ImportMem + ExportMem,
+        R::TextureId: Send + Clone + 'static,
+    {
+        self.render(renderer, framebuffer)?;
+        let actual =
+            read_framebuffer(renderer, framebuffer, self.size).map_err(ConformanceError::Renderer)?;
+        self.check_image(&actual, reference_dir)
+    }
+
+    /// Compares `actual`, the rgba result of rendering the scene, against the reference image in `reference_dir`
+    ///
+    /// If [`UPDATE_REFERENCES_VAR`] is set, the reference image is replaced with `actual` instead.
+    pub fn check_image<E: std::error::Error>(
+        &self,
+        actual: &image::RgbaImage,
+        reference_dir: &Path,
+    ) -> Result<(), ConformanceError<E>> {
+        let path = reference_dir.join(format!("{}.png", self.name));
+        if std::env::var_os(UPDATE_REFERENCES_VAR).is_some() {
+            std::fs::create_dir_all(reference_dir).map_err(image::ImageError::IoError)?;
+            actual.save(&path)?;
+            return Ok(());
+        }
+        if !path.exists() {
+            return Err(ConformanceError::MissingReference(path));
+        }
+        let expected = image::open(&path)?.to_rgba8();
+
+        let mismatch = compare(&expected, actual);
+        if let Some((first, pixels)) = mismatch {
+            let dir = std::env::temp_dir().join("smithay-conformance");
+            std::fs::create_dir_all(&dir).map_err(image::ImageError::IoError)?;
+            let actual_path = dir.join(format!("{}.png", self.name));
+            actual.save(&actual_path)?;
+            return Err(ConformanceError::Mismatch {
+                scene: self.name,
+                pixels,
+                first,
+                expected: pixel(&expected, first),
+                actual: pixel(actual, first),
+                actual_path,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn pixel(image: &image::RgbaImage, loc: Point<u32, Buffer>) -> [u8; 4] {
+    image
+        .get_pixel_checked(loc.x, loc.y)
+        .map(|pixel| pixel.0)
+        .unwrap_or_default()
+}
+
+/// Returns the first pixel differing by more than [`TOLERANCE`] and the number of differing pixels
+fn compare(expected: &image::RgbaImage, actual: &image::RgbaImage) -> Option<(Point<u32, Buffer>, usize)> {
+    if expected.dimensions() != actual.dimensions() {
+        let (w, h) = expected.dimensions().max(actual.dimensions());
+        return Some((Point::from((0, 0)), (w * h) as usize));
+    }
+
+    let mut differing = expected
+        .enumerate_pixels()
+        .zip(actual.pixels())
+        .filter(|((_, _, expected), actual)| {
+            expected
+                .0
+                .iter()
+                .zip(actual.0)
+                .any(|(expected, actual)| expected.abs_diff(actual) > TOLERANCE)
+        })
+        .map(|((x, y, _), _)| Point::from((x, y)));
+    let first = differing.next()?;
+    Some((first, differing.count() + 1))
+}
+
+/// Reads back `framebuffer` as rgba
+fn read_framebuffer<R: ExportMem>(
+    renderer: &mut R,
+    framebuffer: &R::Framebuffer<'_>,
+    size: Size<i32, Buffer>,
+) -> Result<image::RgbaImage, R::Error> {
+    // read in the format of the framebuffer if possible, some renderers do not convert
+    let format = framebuffer
+        .format()
+        .filter(|format| {
+            matches!(
+                format,
+                Fourcc::Argb8888 | Fourcc::Xrgb8888 | Fourcc::Abgr8888 | Fourcc::Xbgr8888
+            )
+        })
+        .unwrap_or(Fourcc::Abgr8888);
+    let mapping = renderer.copy_framebuffer(framebuffer, Rectangle::from_size(size), format)?;
+    let flipped = mapping.flipped();
+    let data = renderer.map_texture(&mapping)?;
+
+    let (width, height) = (size.w as u32, size.h as u32);
+    let stride = data.len() / (height as usize).max(1);
+    Ok(image::RgbaImage::from_fn(width, height, |x, y| {
+        let row = if flipped { height - 1 - y } else { y };
+        let offset = row as usize * stride + x as usize * 4;
+        let [c0, c1, c2, c3] = data[offset..offset + 4].try_into().unwrap();
+        // little endian byte order of the drm formats
+        image::Rgba(match format {
+            Fourcc::Argb8888 => [c2, c1, c0, c3],
+            Fourcc::Xrgb8888 => [c2, c1, c0, 255],
+            Fourcc::Xbgr8888 => [c0, c1, c2, 255],
+            _ => [c0, c1, c2, c3],
+        })
+    }))
+}
+
+/// Renders all [`scenes`] to offscreen buffers of `format` and compares them against the references
+///
+/// The texture filters of the renderer are set to [`TextureFilter::Nearest`].
+pub fn check_offscreen<R, T>(renderer: &mut R, format: Fourcc) -> Result<(), ConformanceError<R::Error>>
+where
+    R: Renderer + ImportMem + ExportMem + Offscreen<T>,
+    R::TextureId: Send + Clone + 'static,
+{
+    renderer
+        .upscale_filter(TextureFilter::Nearest)
+        .map_err(ConformanceError::Renderer)?;
+    renderer
+        .downscale_filter(TextureFilter::Nearest)
+        .map_err(ConformanceError::Renderer)?;
+
+    let reference_dir = reference_dir();
+    for scene in scenes() {
+        let mut buffer = renderer
+            .create_buffer(format, scene.size)
+            .map_err(ConformanceError::Renderer)?;
+        let mut framebuffer = renderer.bind(&mut buffer).map_err(ConformanceError::Renderer)?;
+        scene.check(renderer, &mut framebuffer, &reference_dir)?;
+    }
+    Ok(())
+}
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const YELLOW: [u8; 4] = [255, 255, 0, 255];
+const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+const CYAN: [u8; 4] = [0, 255, 255, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+fn color([r, g, b, a]: [u8; 4]) -> Color32F {
+    Color32F::new(
+        r as f32 / 255.0,
+        g as f32 / 255.0,
+        b as f32 / 255.0,
+        a as f32 / 255.0,
+    )
+}
+
+/// Creates a buffer of `size` from premultiplied rgba `pixels`, row by row
+fn buffer(format: Fourcc, size: (i32, i32), pixels: &[[u8; 4]], transform: Transform) -> MemoryRenderBuffer {
+    assert_eq!(pixels.len(), (size.0 * size.1) as usize);
+    let data = pixels
+        .iter()
+        .flat_map(|&[r, g, b, a]| match format {
+            Fourcc::Argb8888 => [b, g, r, a],
+            // the alpha channel has to be ignored
+            Fourcc::Xrgb8888 => [b, g, r, 0],
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+    MemoryRenderBuffer::from_slice(&data, format, size, 1, transform, None)
+}
+
+/// Returns the scenes of the conformance suite
+pub fn scenes() -> Vec<Scene> {
+    vec![
+        solid_blend(),
+        texture_alpha(),
+        texture_crop_scale(),
+        buffer_transforms(),
+        output_transform(Transform::_90),
+        output_transform(Transform::Flipped270),
+        damage_incremental(),
+    ]
+}
+
+/// Opaque and translucent solid colors, partially outside of the output
+fn solid_blend() -> Scene {
+    Scene {
+        name: "solid_blend",
+        size: (16, 16).into(),
+        transform: Transform::Normal,
+        frames: vec![vec![
+            SceneElement::solid((2, 2), (8, 8), color([0, 128, 0, 128])),
+            SceneElement::solid((-4, -4), (8, 8), color(RED)),
+            SceneElement::solid((12, 4), (6, 6), color(BLUE)),
+            SceneElement::solid((4, 12), (4, 8), color([0, 0, 0, 0])),
+        ]],
+    }
+}
+
+/// Textures with an alpha channel, ignored alpha channels and alpha multipliers
+fn texture_alpha() -> Scene {
+    let translucent = [128, 128, 128, 128];
+    let quadrants = [
+        RED,
+        RED,
+        GREEN,
+        GREEN,
+        RED,
+        RED,
+        GREEN,
+        GREEN,
+        BLUE,
+        BLUE,
+        translucent,
+        translucent,
+        BLUE,
+        BLUE,
+        translucent,
+        translucent,
+    ];
+    let argb = buffer(Fourcc::Argb8888, (4, 4), &quadrants, Transform::Normal);
+    let xrgb = buffer(
+        Fourcc::Xrgb8888,
+        (4, 4),
+        &[[0, 128, 128, 255]; 16],
+        Transform::Normal,
+    );
+    let element = |buffer: &MemoryRenderBuffer, location: (i32, i32), alpha: f32| SceneElement::Memory {
+        buffer: buffer.clone(),
+        location: location.into(),
+        alpha,
+        src: None,
+        size: None,
+    };
+
+    Scene {
+        name: "texture_alpha",
+        size: (16, 16).into(),
+        transform: Transform::Normal,
+        frames: vec![vec![
+            element(&argb, (1, 1), 1.0),
+            element(&argb, (9, 1), 0.5),
+            element(&xrgb, (1, 9), 1.0),
+            element(&xrgb, (9, 9), 0.5),
+        ]],
+    }
+}
+
+/// Cropped textures and textures scaled up by integer factors
+fn texture_crop_scale() -> Scene {
+    let pixels = [
+        RED, GREEN, BLUE, WHITE, //
+        YELLOW, MAGENTA, CYAN, BLACK, //
+        BLUE, WHITE, RED, GREEN, //
+        CYAN, BLACK, YELLOW, MAGENTA,
+    ];
+    let texture = buffer(Fourcc::Argb8888, (4, 4), &pixels, Transform::Normal);
+    let element = |location: (i32, i32), src: Option<((f64, f64), (f64, f64))>, size: Option<(i32, i32)>| {
+        SceneElement::Memory {
+            buffer: texture.clone(),
+            location: location.into(),
+            alpha: 1.0,
+            src: src.map(|(loc, size)| Rectangle::new(loc.into(), size.into())),
+            size: size.map(Size::from),
+        }
+    };
+
+    Scene {
+        name: "texture_crop_scale",
+        size: (24, 16).into(),
+        transform: Transform::Normal,
+        frames: vec![vec![
+            element((1, 1), Some(((1.0, 1.0), (2.0, 2.0))), None),
+            element((4, 1), Some(((0.0, 2.0), (4.0, 2.0))), None),
+            element((1, 5), Some(((0.0, 0.0), (4.0, 4.0))), Some((8, 8))),
+            element((11, 1), Some(((1.0, 1.0), (2.0, 3.0))), Some((6, 9))),
+            element((18, 11), Some(((3.0, 0.0), (1.0, 4.0))), Some((5, 4))),
+        ]],
+    }
+}
+
+/// A texture shown with every buffer transformation
+fn buffer_transforms() -> Scene {
+    let pixels = [RED, GREEN, BLUE, YELLOW, MAGENTA, CYAN];
+    let transforms = [
+        Transform::Normal,
+        Transform::_90,
+        Transform::_180,
+        Transform::_270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+    let elements = transforms
+        .into_iter()
+        .enumerate()
+        .map(|(i, transform)| {
+            let buffer = buffer(Fourcc::Argb8888, (3, 2), &pixels, transform);
+            let location = ((i as i32 % 4) * 5 + 1, (i as i32 / 4) * 5 + 1);
+            SceneElement::memory(&buffer, location)
+        })
+        .collect();
+
+    Scene {
+        name: "buffer_transforms",
+        size: (20, 10).into(),
+        transform: Transform::Normal,
+        frames: vec![elements],
+    }
+}
+
+/// Elements on a transformed output
+fn output_transform(transform: Transform) -> Scene {
+    let texture = buffer(
+        Fourcc::Argb8888,
+        (3, 2),
+        &[RED, GREEN, BLUE, YELLOW, MAGENTA, CYAN],
+        Transform::Normal,
+    );
+
+    Scene {
+        name: match transform {
+            Transform::_90 => "output_90",
+            Transform::Flipped270 => "output_flipped_270",
+            _ => unimplemented!(),
+        },
+        size: (12, 20).into(),
+        transform,
+        // the output is 20x12 after the transformation
+        frames: vec![vec![
+            SceneElement::memory(&texture, (8, 4)),
+            SceneElement::solid((0, 0), (4, 2), color(WHITE)),
+            SceneElement::solid((16, 9), (4, 3), color(YELLOW)),
+        ]],
+    }
+}
+
+/// Elements moved, removed and added between frames, only the damage is redrawn
+fn damage_incremental() -> Scene {
+    let texture = buffer(
+        Fourcc::Argb8888,
+        (2, 2),
+        &[RED, GREEN, BLUE, WHITE],
+        Transform::Normal,
+    );
+    let moving = SceneElement::solid((1, 1), (6, 6), color(RED));
+    let removed = SceneElement::memory(&texture, (10, 10));
+    let translucent = SceneElement::solid((8, 2), (4, 4), color([0, 0, 128, 128]));
+    let added = SceneElement::solid((1, 10), (4, 4), color(GREEN));
+
+    Scene {
+        name: "damage_incremental",
+        size: (16, 16).into(),
+        transform: Transform::Normal,
+        frames: vec![
+            vec![translucent.clone(), moving.clone(), removed.clone()],
+            vec![translucent.clone(), moving.moved((5, 5)), added.clone()],
+            vec![translucent, moving.moved((5, 5)), added.moved((2, 11))],
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, scenes};
+    use crate::backend::renderer::test::{DummyFramebuffer, DummyRenderer};
+
+    #[test]
+    fn dummy_renderer_renders_all_scenes() {
+        let mut renderer = DummyRenderer;
+        for scene in scenes() {
+            scene.render(&mut renderer, &mut DummyFramebuffer).unwrap();
+        }
+    }
+
+    #[test]
+    fn compare_with_tolerance() {
+        let expected = image::RgbaImage::from_pixel(2, 2, image::Rgba([10, 20, 30, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 1, image::Rgba([12, 18, 30, 255]));
+        assert_eq!(compare(&expected, &actual), None);
+
+        actual.put_pixel(1, 0, image::Rgba([10, 20, 33, 255]));
+        actual.put_pixel(1, 1, image::Rgba([10, 20, 30, 0]));
+        assert_eq!(compare(&expected, &actual), Some(((1, 0).into(), 2)));
+    }
+}
@@ -30,6 +30,8 @@ use std::sync::LazyLock;
 
 use super::{Color32F, ContextId};
 
+pub mod conformance;
+
 /// All [`DummyRenderer`] instances share the same static [`ContextId`].
 static CONTEXT_ID: LazyLock<ContextId<DummyTexture>> = LazyLock::new(ContextId::new);
 
@@ -107,10 +109,13 @@ impl ImportMem for DummyRenderer {
         &mut self,
         _data: &[u8],
         _format: Fourcc,
-        _size: Size<i32, Buffer>,
+        size: Size<i32, Buffer>,
         _flipped: bool,
     ) -> Result<Self::TextureId, Self::Error> {
-        unimplemented!()
+        Ok(DummyTexture {
+            width: size.w as u32,
+            height: size.h as u32,
+        })
     }
 
     fn update_memory(
@@ -119,7 +124,7 @@ impl ImportMem for DummyRenderer {
         _data: &[u8],
         _region: Rectangle<i32, Buffer>,
     ) -> Result<(), Self::Error> {
-        unimplemented!()
+        Ok(())
     }
 
     fn mem_formats(&self) -> Box<dyn Iterator<Item = Fourcc>> {
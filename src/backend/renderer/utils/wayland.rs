@@ -2,8 +2,8 @@
 use crate::wayland::drm_syncobj::{DrmSyncPoint, DrmSyncobjCachedState};
 use crate::{
     backend::renderer::{
-        buffer_dimensions, buffer_has_alpha, element::RenderElement, ContextId, ErasedContextId, ImportAll,
-        Renderer, Texture,
+        buffer_dimensions, buffer_has_alpha, element::RenderElement, ContextId, ErasedContextId, Frame,
+        ImportAll, Renderer, Texture,
     },
     utils::{Buffer as BufferCoord, Coordinate, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
     wayland::{
@@ -677,8 +677,10 @@ where
             continue;
         }
 
+        frame.set_color_description(element.color_description());
         element.draw(frame, element.src(), element_geometry, &element_damage, &[])?;
     }
+    frame.set_color_description(None);
 
     Ok(Some(render_damage))
 }
@@ -0,0 +1,196 @@
+//! Import of dmabufs as vulkan images
+
+use std::{
+    ffi::CStr,
+    os::unix::io::{AsFd, AsRawFd, IntoRawFd},
+};
+
+use ash::{ext, khr, vk};
+use tracing::warn;
+
+use crate::backend::{
+    allocator::{dmabuf::Dmabuf, format::FormatSet, Buffer, Format as DrmFormat},
+    vulkan::PhysicalDevice,
+};
+
+use super::{
+    format::{fourcc_to_vk, SUPPORTED_FORMATS},
+    VulkanError, VulkanRenderer, VulkanTexture,
+};
+
+/// Extensions required to import dmabufs
+pub(super) const DMABUF_EXTENSIONS: &[&CStr] = &[
+    khr::external_memory_fd::NAME,
+    ext::external_memory_dma_buf::NAME,
+    ext::image_drm_format_modifier::NAME,
+    ext::queue_family_foreign::NAME,
+];
+
+/// Returns the formats of dmabufs that can be sampled from and rendered to on `phd`
+pub(super) fn dmabuf_formats(phd: &PhysicalDevice) -> (FormatSet, FormatSet) {
+    let mut texture_formats = Vec::new();
+    let mut render_formats = Vec::new();
+    for &code in SUPPORTED_FORMATS {
+        let Some((vk_format, _)) = fourcc_to_vk(code) else {
+            continue;
+        };
+        let properties = match phd.get_format_modifier_properties(vk_format) {
+            Ok(properties) => properties,
+            Err(err) => {
+                warn!("Failed to query modifiers of {:?}: {}", code, err);
+                continue;
+            }
+        };
+        // disjoint planes are not supported
+        for properties in properties
+            .into_iter()
+            .filter(|properties| properties.drm_format_modifier_plane_count == 1)
+        {
+            let format = DrmFormat {
+                code,
+                modifier: properties.drm_format_modifier.into(),
+            };
+            let features = properties.drm_format_modifier_tiling_features;
+            if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+                texture_formats.push(format);
+            }
+            if features.contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND) {
+                render_formats.push(format);
+            }
+        }
+    }
+    (
+        texture_formats.into_iter().collect(),
+        render_formats.into_iter().collect(),
+    )
+}
+
+impl VulkanRenderer {
+    /// Imports `dmabuf` as vulkan image
+    ///
+    /// Textures imported as `render_target` can additionally be rendered to.
+    pub(super) fn import_dmabuf_texture(
+        &self,
+        dmabuf: &Dmabuf,
+        render_target: bool,
+    ) -> Result<VulkanTexture, VulkanError> {
+        let external_memory_fd = self
+            .device
+            .external_memory_fd
+            .as_ref()
+            .ok_or(VulkanError::DmabufImportUnsupported)?;
+        let format = dmabuf.format();
+        let supported = if render_target {
+            &self.dmabuf_render_formats
+        } else {
+            &self.dmabuf_texture_formats
+        };
+        if !supported.contains(&format) {
+            return match fourcc_to_vk(format.code) {
+                Some(_) => Err(VulkanError::UnsupportedModifier(format.modifier)),
+                None => Err(VulkanError::UnsupportedPixelFormat(format.code)),
+            };
+        }
+        let (vk_format, _) =
+            fourcc_to_vk(format.code).ok_or(VulkanError::UnsupportedPixelFormat(format.code))?;
+
+        let mut usage = vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC;
+        if render_target {
+            usage |= vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        }
+
+        let device = &self.device.handle;
+        let size = dmabuf.size();
+        let plane_layouts = dmabuf
+            .offsets()
+            .zip(dmabuf.strides())
+            .map(|(offset, stride)| vk::SubresourceLayout {
+                offset: offset as u64,
+                size: 0,
+                row_pitch: stride as u64,
+                array_pitch: 0,
+                depth_pitch: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut external_memory_info = vk::ExternalMemoryImageCreateInfo::default()
+            .handle_types(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+        let mut modifier_info = vk::ImageDrmFormatModifierExplicitCreateInfoEXT::default()
+            .drm_format_modifier(format.modifier.into())
+            .plane_layouts(&plane_layouts);
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D {
+                width: size.w as u32,
+                height: size.h as u32,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .push_next(&mut external_memory_info)
+            .push_next(&mut modifier_info);
+
+        let image = unsafe { device.create_image(&image_info, None) }?;
+        let image = scopeguard::guard(image, |image| unsafe { device.destroy_image(image, None) });
+
+        // Vulkan takes ownership of the fd on a successful import, so pass a duplicate
+        let fd = dmabuf
+            .handles()
+            .next()
+            .expect("dmabufs have at least one plane")
+            .as_fd()
+            .try_clone_to_owned()
+            .map_err(VulkanError::DuplicateFd)?;
+        let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+        unsafe {
+            external_memory_fd.get_memory_fd_properties(
+                vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+                fd.as_raw_fd(),
+                &mut fd_properties,
+            )
+        }?;
+
+        let requirements = unsafe { device.get_image_memory_requirements(*image) };
+        let type_filter = requirements.memory_type_bits & fd_properties.memory_type_bits;
+        let memory_type_index = self
+            .device
+            .find_memory_type(type_filter, vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .or_else(|| {
+                self.device
+                    .find_memory_type(type_filter, vk::MemoryPropertyFlags::empty())
+            })
+            .ok_or(VulkanError::NoMemoryType)?;
+
+        let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+            .handle_type(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT)
+            .fd(fd.as_raw_fd());
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(*image);
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+        let memory = unsafe { device.allocate_memory(&allocate_info, None) }?;
+        let _ = fd.into_raw_fd();
+        let memory = scopeguard::guard(memory, |memory| unsafe { device.free_memory(memory, None) });
+
+        unsafe { device.bind_image_memory(*image, *memory, 0) }?;
+
+        let image = scopeguard::ScopeGuard::into_inner(image);
+        let memory = scopeguard::ScopeGuard::into_inner(memory);
+        VulkanTexture::from_image(
+            &self.device,
+            image,
+            memory,
+            format.code,
+            size,
+            usage,
+            Some(dmabuf.clone()),
+        )
+    }
+}
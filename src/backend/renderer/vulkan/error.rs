@@ -0,0 +1,86 @@
+use drm_fourcc::{DrmFourcc, DrmModifier};
+use thiserror::Error;
+
+use ash::vk;
+
+use crate::{
+    backend::SwapBuffersError,
+    utils::{Buffer, Rectangle, Size},
+};
+
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::wl_shm;
+
+/// Error returned during rendering using vulkan
+#[derive(Debug, Error)]
+pub enum VulkanError {
+    /// The physical device does not support vulkan 1.1
+    #[error("The physical device does not support vulkan 1.1")]
+    UnsupportedVersion,
+    /// The physical device has no queue family supporting graphics operations
+    #[error("The physical device has no graphics queue")]
+    NoGraphicsQueue,
+    /// The given buffer has an unsupported pixel format
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedPixelFormat(DrmFourcc),
+    /// The given buffer has an unsupported modifier
+    #[error("Unsupported modifier: {0:?}")]
+    UnsupportedModifier(DrmModifier),
+    /// The given wl buffer has an unsupported pixel format
+    #[error("Unsupported wl_shm format: {0:?}")]
+    #[cfg(feature = "wayland_frontend")]
+    UnsupportedWlPixelFormat(wl_shm::Format),
+    /// The given buffer is incomplete
+    #[error("Incomplete buffer {expected} < {actual}")]
+    IncompleteBuffer {
+        /// Expected len of the buffer
+        expected: usize,
+        /// Actual len of the buffer
+        actual: usize,
+    },
+    /// The given wl buffer was not accessible
+    #[error("Error accessing the buffer ({0:?})")]
+    #[cfg(feature = "wayland_frontend")]
+    BufferAccessError(#[from] crate::wayland::shm::BufferAccessError),
+    /// The image cannot be used for the requested operation
+    ///
+    /// Memory imports can not be rendered to, dmabufs imported as textures can not be updated.
+    #[error("The image was not created with the required usage {0:?}")]
+    MissingImageUsage(vk::ImageUsageFlags),
+    /// Images can not be empty
+    #[error("Invalid image size {0:?}")]
+    InvalidSize(Size<i32, Buffer>),
+    /// The requested region is not contained in the texture
+    #[error("The region {0:?} is out of bounds of the texture")]
+    RegionOutOfBounds(Rectangle<i32, Buffer>),
+    /// The device lacks the extensions to import dmabufs
+    #[error("Dmabuf import is not supported by the device")]
+    DmabufImportUnsupported,
+    /// Duplicating the file descriptor of a dmabuf failed
+    #[error("Failed to duplicate the dmabuf file descriptor: {0}")]
+    DuplicateFd(#[source] std::io::Error),
+    /// No memory type of the device fits the requirements of a resource
+    #[error("No suitable memory type available")]
+    NoMemoryType,
+    /// A vulkan call failed
+    #[error("Vulkan call failed: {0}")]
+    Vk(#[from] vk::Result),
+    /// Blocking for a synchronization primitive failed
+    #[error("Blocking for a synchronization primitive got interrupted")]
+    SyncInterrupted,
+}
+
+impl From<VulkanError> for SwapBuffersError {
+    #[inline]
+    fn from(value: VulkanError) -> Self {
+        match value {
+            x @ VulkanError::SyncInterrupted
+            | x @ VulkanError::Vk(
+                vk::Result::TIMEOUT
+                | vk::Result::ERROR_OUT_OF_HOST_MEMORY
+                | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+            ) => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            x => SwapBuffersError::ContextLost(Box::new(x)),
+        }
+    }
+}
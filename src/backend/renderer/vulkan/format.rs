@@ -0,0 +1,55 @@
+//! Conversions between fourcc and vulkan formats
+//!
+//! Unlike the [allocator](crate::backend::allocator::vulkan::format), the renderer uses `UNORM` formats,
+//! so blending happens on the encoded values like in the gles renderer.
+
+use ash::vk;
+use drm_fourcc::DrmFourcc;
+
+/// Formats supported for memory imports, offscreen buffers and dmabufs, if the device supports them
+pub(super) const SUPPORTED_FORMATS: &[DrmFourcc] = &[
+    DrmFourcc::Argb8888,
+    DrmFourcc::Xrgb8888,
+    DrmFourcc::Abgr8888,
+    DrmFourcc::Xbgr8888,
+    #[cfg(target_endian = "little")]
+    DrmFourcc::Argb2101010,
+    #[cfg(target_endian = "little")]
+    DrmFourcc::Xrgb2101010,
+    #[cfg(target_endian = "little")]
+    DrmFourcc::Abgr2101010,
+    #[cfg(target_endian = "little")]
+    DrmFourcc::Xbgr2101010,
+    DrmFourcc::Abgr16161616f,
+    DrmFourcc::Xbgr16161616f,
+];
+
+/// Returns the vulkan format matching the memory layout of `fourcc` and whether the alpha channel is used
+pub(super) const fn fourcc_to_vk(fourcc: DrmFourcc) -> Option<(vk::Format, bool)> {
+    Some(match fourcc {
+        DrmFourcc::Argb8888 => (vk::Format::B8G8R8A8_UNORM, true),
+        DrmFourcc::Xrgb8888 => (vk::Format::B8G8R8A8_UNORM, false),
+        DrmFourcc::Abgr8888 => (vk::Format::R8G8B8A8_UNORM, true),
+        DrmFourcc::Xbgr8888 => (vk::Format::R8G8B8A8_UNORM, false),
+        // PACK32 formats are stored as u32 in host endian
+        #[cfg(target_endian = "little")]
+        DrmFourcc::Argb2101010 => (vk::Format::A2R10G10B10_UNORM_PACK32, true),
+        #[cfg(target_endian = "little")]
+        DrmFourcc::Xrgb2101010 => (vk::Format::A2R10G10B10_UNORM_PACK32, false),
+        #[cfg(target_endian = "little")]
+        DrmFourcc::Abgr2101010 => (vk::Format::A2B10G10R10_UNORM_PACK32, true),
+        #[cfg(target_endian = "little")]
+        DrmFourcc::Xbgr2101010 => (vk::Format::A2B10G10R10_UNORM_PACK32, false),
+        DrmFourcc::Abgr16161616f => (vk::Format::R16G16B16A16_SFLOAT, true),
+        DrmFourcc::Xbgr16161616f => (vk::Format::R16G16B16A16_SFLOAT, false),
+        _ => return None,
+    })
+}
+
+/// Bytes per pixel of the supported formats
+pub(super) const fn bytes_per_pixel(format: vk::Format) -> u32 {
+    match format {
+        vk::Format::R16G16B16A16_SFLOAT => 8,
+        _ => 4,
+    }
+}
@@ -0,0 +1,2300 @@
+//! Implementation of the rendering traits using Vulkan
+//!
+//! The [`VulkanRenderer`] creates its own logical device on a [`PhysicalDevice`] of the
+//! [vulkan backend](crate::backend::vulkan). It requires Vulkan 1.1 and a queue family supporting
+//! graphics operations, so it also works with software implementations like lavapipe.
+//! Blending and transformations are the same as in the gles renderer: colors and textures are
+//! premultiplied and blended on their encoded values.
+//!
+//! Importing and binding dmabufs requires the following device extensions:
+//! - `VK_KHR_external_memory_fd`
+//! - `VK_EXT_external_memory_dma_buf`
+//! - `VK_EXT_image_drm_format_modifier`
+//! - `VK_EXT_queue_family_foreign`
+//!
+//! With `VK_KHR_external_semaphore_fd` the renderer synchronizes explicitly: the [`SyncPoint`]s of
+//! finished frames can be exported as sync_file, and sync points exported by others, like the acquire
+//! points of drm_syncobj timelines, are waited for on the gpu instead of blocking.
+//!
+//! All of these extensions are enabled if the device supports them.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Cursor,
+    marker::PhantomData,
+    os::unix::io::OwnedFd,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use ash::{khr, vk};
+use cgmath::{Matrix3, SquareMatrix, Vector2};
+use drm_fourcc::DrmFourcc;
+use tracing::warn;
+
+use crate::{
+    backend::{
+        allocator::{
+            dmabuf::{Dmabuf, WeakDmabuf},
+            format::FormatSet,
+        },
+        vulkan::{version::Version, PhysicalDevice},
+    },
+    utils::{Buffer as BufferCoords, Physical, Rectangle, Size, Transform},
+};
+
+#[cfg(feature = "wayland_frontend")]
+use crate::{
+    backend::renderer::{ImportDmaWl, ImportMemWl},
+    wayland::{compositor::SurfaceData, shm},
+};
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::wl_buffer;
+
+use super::{
+    sync::SyncPoint, Bind, Color32F, ContextId, DebugFlags, ExportMem, Frame, ImportDma, ImportMem,
+    Offscreen, Renderer, RendererSuper, Texture, TextureFilter, TextureMapping,
+};
+
+mod dmabuf;
+mod error;
+mod format;
+mod sync;
+
+pub use error::*;
+pub use sync::VulkanFence;
+
+use dmabuf::{dmabuf_formats, DMABUF_EXTENSIONS};
+use format::{bytes_per_pixel, fourcc_to_vk, SUPPORTED_FORMATS};
+use sync::FenceInner;
+
+const FLAG_NO_ALPHA: u32 = 1;
+const FLAG_TINT: u32 = 2;
+
+/// Push constants of a single draw, matches `PushConstants` in the shaders
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PushConstants {
+    /// Rows of the affine matrix mapping positions to clip space
+    transform: [[f32; 4]; 2],
+    /// Rows of the affine matrix mapping positions to texture coordinates
+    tex_transform: [[f32; 4]; 2],
+    /// Translation in xy and size in zw of the quad
+    rect: [f32; 4],
+    color: [f32; 4],
+    alpha: f32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+/// The first two rows of the affine `matrix`
+fn affine_rows(matrix: Matrix3<f32>) -> [[f32; 4]; 2] {
+    // cgmath matrices are column major
+    [
+        [matrix.x.x, matrix.y.x, matrix.z.x, 0.0],
+        [matrix.x.y, matrix.y.y, matrix.z.y, 0.0],
+    ]
+}
+
+/// The logical device, destroyed once the renderer and all of its resources are dropped
+pub(super) struct DeviceInner {
+    handle: ash::Device,
+    external_memory_fd: Option<khr::external_memory_fd::Device>,
+    external_semaphore_fd: Option<khr::external_semaphore_fd::Device>,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    // keeps the instance alive
+    phd: PhysicalDevice,
+}
+
+impl fmt::Debug for DeviceInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceInner")
+            .field("handle", &self.handle.handle())
+            .field("phd", &self.phd.name())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DeviceInner {
+    fn find_memory_type(&self, type_filter: u32, properties: vk::MemoryPropertyFlags) -> Option<u32> {
+        (0..self.memory_properties.memory_type_count).find(|&i| {
+            (type_filter & (1 << i)) != 0
+                && self.memory_properties.memory_types[i as usize]
+                    .property_flags
+                    .contains(properties)
+        })
+    }
+}
+
+impl Drop for DeviceInner {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.handle.device_wait_idle();
+            self.handle.destroy_device(None);
+        }
+    }
+}
+
+struct TextureInner {
+    device: Arc<DeviceInner>,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    format: vk::Format,
+    fourcc: DrmFourcc,
+    has_alpha: bool,
+    size: Size<i32, BufferCoords>,
+    usage: vk::ImageUsageFlags,
+    /// Layout of the image between submissions
+    layout: Mutex<vk::ImageLayout>,
+    /// The dmabuf the memory of the image was imported from
+    ///
+    /// These images are owned by the foreign queue family between submissions.
+    dmabuf: Option<Dmabuf>,
+}
+
+impl fmt::Debug for TextureInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextureInner")
+            .field("image", &self.image)
+            .field("format", &self.fourcc)
+            .field("size", &self.size)
+            .field("usage", &self.usage)
+            .field("dmabuf", &self.dmabuf)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for TextureInner {
+    fn drop(&mut self) {
+        let device = &self.device.handle;
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// A handle to a vulkan image
+#[derive(Debug, Clone)]
+pub struct VulkanTexture {
+    inner: Arc<TextureInner>,
+    flipped: bool,
+}
+
+impl VulkanTexture {
+    /// Wraps `image`, which is bound to `memory`, taking ownership of both
+    fn from_image(
+        device: &Arc<DeviceInner>,
+        image: vk::Image,
+        memory: vk::DeviceMemory,
+        fourcc: DrmFourcc,
+        size: Size<i32, BufferCoords>,
+        usage: vk::ImageUsageFlags,
+        dmabuf: Option<Dmabuf>,
+    ) -> Result<VulkanTexture, VulkanError> {
+        let (format, has_alpha) = fourcc_to_vk(fourcc).ok_or(VulkanError::UnsupportedPixelFormat(fourcc))?;
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(COLOR_RANGE);
+        let view = match unsafe { device.handle.create_image_view(&view_info, None) } {
+            Ok(view) => view,
+            Err(err) => {
+                unsafe {
+                    device.handle.destroy_image(image, None);
+                    device.handle.free_memory(memory, None);
+                }
+                return Err(err.into());
+            }
+        };
+
+        Ok(VulkanTexture {
+            inner: Arc::new(TextureInner {
+                device: device.clone(),
+                image,
+                memory,
+                view,
+                format,
+                fourcc,
+                has_alpha,
+                size,
+                usage,
+                layout: Mutex::new(vk::ImageLayout::UNDEFINED),
+                dmabuf,
+            }),
+            flipped: false,
+        })
+    }
+
+    /// Get the underlying vulkan image
+    pub fn image(&self) -> vk::Image {
+        self.inner.image
+    }
+
+    /// Get the usage the underlying image was created with
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.inner.usage
+    }
+}
+
+impl Texture for VulkanTexture {
+    fn width(&self) -> u32 {
+        self.inner.size.w as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.inner.size.h as u32
+    }
+
+    fn format(&self) -> Option<DrmFourcc> {
+        Some(self.inner.fourcc)
+    }
+}
+
+/// A framebuffer of a [`VulkanRenderer`]
+#[derive(Debug)]
+pub struct VulkanTarget<'a> {
+    texture: VulkanTexture,
+    _buffer: PhantomData<&'a mut ()>,
+}
+
+impl VulkanTarget<'_> {
+    /// Get the underlying vulkan image
+    pub fn image(&self) -> vk::Image {
+        self.texture.image()
+    }
+}
+
+impl Texture for VulkanTarget<'_> {
+    fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    fn format(&self) -> Option<DrmFourcc> {
+        self.texture.format()
+    }
+}
+
+const COLOR_RANGE: vk::ImageSubresourceRange = vk::ImageSubresourceRange {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    base_mip_level: 0,
+    level_count: 1,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+const COLOR_LAYERS: vk::ImageSubresourceLayers = vk::ImageSubresourceLayers {
+    aspect_mask: vk::ImageAspectFlags::COLOR,
+    mip_level: 0,
+    base_array_layer: 0,
+    layer_count: 1,
+};
+
+/// A buffer in host visible memory, used to upload and read back images
+#[derive(Debug)]
+struct HostBuffer {
+    device: Arc<DeviceInner>,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: u64,
+}
+
+impl HostBuffer {
+    fn new(device: &Arc<DeviceInner>, size: u64, usage: vk::BufferUsageFlags) -> Result<Self, VulkanError> {
+        let handle = &device.handle;
+        let buffer_info = vk::BufferCreateInfo::default()
+            .size(size)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { handle.create_buffer(&buffer_info, None) }?;
+        let buffer = scopeguard::guard(buffer, |buffer| unsafe { handle.destroy_buffer(buffer, None) });
+
+        let requirements = unsafe { handle.get_buffer_memory_requirements(*buffer) };
+        let coherent = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let memory_type_index = device
+            .find_memory_type(
+                requirements.memory_type_bits,
+                coherent | vk::MemoryPropertyFlags::HOST_CACHED,
+            )
+            .or_else(|| device.find_memory_type(requirements.memory_type_bits, coherent))
+            .ok_or(VulkanError::NoMemoryType)?;
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        let memory = unsafe { handle.allocate_memory(&allocate_info, None) }?;
+        let memory = scopeguard::guard(memory, |memory| unsafe { handle.free_memory(memory, None) });
+        unsafe { handle.bind_buffer_memory(*buffer, *memory, 0) }?;
+
+        Ok(HostBuffer {
+            device: device.clone(),
+            buffer: scopeguard::ScopeGuard::into_inner(buffer),
+            memory: scopeguard::ScopeGuard::into_inner(memory),
+            size,
+        })
+    }
+
+    /// Calls `f` with the mapped contents of the buffer
+    fn with_mapped<T>(&self, f: impl FnOnce(&mut [u8]) -> T) -> Result<T, VulkanError> {
+        let handle = &self.device.handle;
+        let ptr = unsafe { handle.map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty()) }?;
+        // SAFETY: the memory is host visible, coherent and mapped until the end of this function
+        let data = unsafe { std::slice::from_raw_parts_mut(ptr as *mut u8, self.size as usize) };
+        let result = f(data);
+        unsafe { handle.unmap_memory(self.memory) };
+        Ok(result)
+    }
+}
+
+impl Drop for HostBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.handle.destroy_buffer(self.buffer, None);
+            self.device.handle.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Resources of a submission to the queue, freed once it finished executing
+#[derive(Debug)]
+struct Submission {
+    fence: Arc<FenceInner>,
+    command_buffer: vk::CommandBuffer,
+    framebuffer: vk::Framebuffer,
+    descriptor_pool: vk::DescriptorPool,
+    semaphores: Vec<vk::Semaphore>,
+    buffers: Vec<Arc<HostBuffer>>,
+    textures: Vec<VulkanTexture>,
+}
+
+/// Render pass and pipelines for one target format
+#[derive(Debug)]
+struct RenderSetup {
+    render_pass: vk::RenderPass,
+    /// Replaces the contents of the target
+    clear: vk::Pipeline,
+    solid: vk::Pipeline,
+    texture: vk::Pipeline,
+}
+
+#[derive(Debug, Clone)]
+enum DrawKind {
+    Clear,
+    Solid,
+    Texture(VulkanTexture, vk::Sampler),
+}
+
+#[derive(Debug)]
+struct Draw {
+    kind: DrawKind,
+    constants: PushConstants,
+}
+
+/// A frame of the [`VulkanRenderer`]
+///
+/// Draw calls are recorded and submitted as a single render pass when the frame is finished.
+pub struct VulkanFrame<'frame, 'buffer> {
+    renderer: &'frame mut VulkanRenderer,
+    target: &'frame mut VulkanTarget<'buffer>,
+    projection: Matrix3<f32>,
+    transform: Transform,
+    draws: Vec<Draw>,
+    finished: bool,
+}
+
+impl fmt::Debug for VulkanFrame<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VulkanFrame")
+            .field("renderer", &self.renderer)
+            .field("target", &self.target)
+            .field("transform", &self.transform)
+            .field("draws", &self.draws)
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VulkanFrame<'_, '_> {
+    fn push_draws(
+        &mut self,
+        kind: DrawKind,
+        constants: PushConstants,
+        rects: impl IntoIterator<Item = Rectangle<i32, Physical>>,
+    ) {
+        for rect in rects {
+            if rect.is_empty() {
+                continue;
+            }
+            let constants = PushConstants {
+                rect: [
+                    rect.loc.x as f32,
+                    rect.loc.y as f32,
+                    rect.size.w as f32,
+                    rect.size.h as f32,
+                ],
+                ..constants
+            };
+            self.draws.push(Draw {
+                kind: kind.clone(),
+                constants,
+            });
+        }
+    }
+
+    #[profiling::function]
+    fn finish_internal(&mut self) -> Result<SyncPoint, VulkanError> {
+        if std::mem::replace(&mut self.finished, true) || self.draws.is_empty() {
+            return Ok(SyncPoint::signaled());
+        }
+
+        let draws = std::mem::take(&mut self.draws);
+        self.renderer.submit_frame(&self.target.texture, draws)
+    }
+}
+
+/// Clamps `damage`, which is relative to a destination of `dest_size`, to the destination
+fn constrained_damage(
+    dest_size: Size<i32, Physical>,
+    damage: &[Rectangle<i32, Physical>],
+) -> impl Iterator<Item = Rectangle<i32, Physical>> + '_ {
+    damage.iter().map(move |rect| {
+        let rect_constrained_loc = rect.loc.constrain(Rectangle::from_size(dest_size));
+        let rect_clamped_size = rect
+            .size
+            .clamp((0, 0), (dest_size.to_point() - rect_constrained_loc).to_size());
+        Rectangle::new(rect_constrained_loc, rect_clamped_size)
+    })
+}
+
+impl Frame for VulkanFrame<'_, '_> {
+    type Error = VulkanError;
+    type TextureId = VulkanTexture;
+
+    fn context_id(&self) -> ContextId<VulkanTexture> {
+        self.renderer.context_id.clone()
+    }
+
+    #[profiling::function]
+    fn clear(&mut self, color: Color32F, at: &[Rectangle<i32, Physical>]) -> Result<(), Self::Error> {
+        let constants = PushConstants {
+            transform: affine_rows(self.projection),
+            color: color.components(),
+            ..bytemuck::Zeroable::zeroed()
+        };
+        self.push_draws(DrawKind::Clear, constants, at.iter().copied());
+        Ok(())
+    }
+
+    #[profiling::function]
+    fn draw_solid(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: Color32F,
+    ) -> Result<(), Self::Error> {
+        let constants = PushConstants {
+            transform: affine_rows(self.projection),
+            color: color.components(),
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let rects =
+            constrained_damage(dst.size, damage).map(|rect| Rectangle::new(dst.loc + rect.loc, rect.size));
+        self.push_draws(DrawKind::Solid, constants, rects);
+        Ok(())
+    }
+
+    #[profiling::function]
+    fn render_texture_from_to(
+        &mut self,
+        texture: &VulkanTexture,
+        src: Rectangle<f64, BufferCoords>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+        src_transform: Transform,
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let tex_size = texture.size();
+        if src.size.is_empty() || tex_size.is_empty() || dst.size.is_empty() {
+            return Ok(());
+        }
+        if !texture.inner.usage.contains(vk::ImageUsageFlags::SAMPLED) {
+            return Err(VulkanError::MissingImageUsage(vk::ImageUsageFlags::SAMPLED));
+        }
+
+        let matrix =
+            self.projection * Matrix3::from_translation(Vector2::new(dst.loc.x as f32, dst.loc.y as f32));
+        let mut tex_matrix = build_texture_mat(src, dst, tex_size, src_transform);
+        if texture.flipped {
+            tex_matrix = Matrix3::from_translation(Vector2::new(0.0, 1.0))
+                * Matrix3::from_nonuniform_scale(1.0, -1.0)
+                * tex_matrix;
+        }
+
+        let mut flags = 0;
+        if !texture.inner.has_alpha {
+            flags |= FLAG_NO_ALPHA;
+        }
+        if self.renderer.debug_flags.contains(DebugFlags::TINT) {
+            flags |= FLAG_TINT;
+        }
+
+        let constants = PushConstants {
+            transform: affine_rows(matrix),
+            tex_transform: affine_rows(tex_matrix),
+            alpha,
+            flags,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let sampler = self.renderer.sampler();
+        self.push_draws(
+            DrawKind::Texture(texture.clone(), sampler),
+            constants,
+            constrained_damage(dst.size, damage),
+        );
+        Ok(())
+    }
+
+    fn transformation(&self) -> Transform {
+        self.transform
+    }
+
+    fn wait(&mut self, sync: &SyncPoint) -> Result<(), Self::Error> {
+        self.renderer.wait_for(sync)
+    }
+
+    #[profiling::function]
+    fn finish(mut self) -> Result<SyncPoint, Self::Error> {
+        self.finish_internal()
+    }
+}
+
+impl Drop for VulkanFrame<'_, '_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish_internal() {
+            warn!("Ignored error finishing VulkanFrame on drop: {}", err);
+        }
+    }
+}
+
+/// A renderer using Vulkan
+#[derive(Debug)]
+pub struct VulkanRenderer {
+    device: Arc<DeviceInner>,
+    queue: vk::Queue,
+    queue_family_index: u32,
+    command_pool: vk::CommandPool,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    pipeline_layout: vk::PipelineLayout,
+    vertex_shader: vk::ShaderModule,
+    solid_shader: vk::ShaderModule,
+    texture_shader: vk::ShaderModule,
+    render_setups: HashMap<vk::Format, RenderSetup>,
+
+    /// Samplers for every combination of downscale and upscale filter
+    samplers: [vk::Sampler; 4],
+    downscale_filter: TextureFilter,
+    upscale_filter: TextureFilter,
+    debug_flags: DebugFlags,
+
+    mem_formats: Vec<DrmFourcc>,
+    render_formats: Vec<DrmFourcc>,
+    dmabuf_texture_formats: FormatSet,
+    dmabuf_render_formats: FormatSet,
+
+    /// Submissions, which did not finish executing yet
+    submissions: Vec<Submission>,
+    /// Semaphores the next submission waits for
+    pending_waits: Vec<vk::Semaphore>,
+    context_id: ContextId<VulkanTexture>,
+
+    // caches
+    buffers: HashMap<WeakDmabuf, VulkanTexture>,
+    dmabuf_cache: HashMap<WeakDmabuf, VulkanTexture>,
+}
+
+impl VulkanRenderer {
+    /// Create a new vulkan renderer with a new logical device on `phd`
+    pub fn new(phd: &PhysicalDevice) -> Result<VulkanRenderer, VulkanError> {
+        let version = phd.api_version().min(phd.instance().api_version());
+        if version < Version::VERSION_1_1 {
+            return Err(VulkanError::UnsupportedVersion);
+        }
+
+        let instance = phd.instance().handle();
+        let queue_family_index = unsafe { instance.get_physical_device_queue_family_properties(phd.handle()) }
+            .iter()
+            .position(|properties| properties.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .ok_or(VulkanError::NoGraphicsQueue)? as u32;
+
+        // VK_EXT_image_drm_format_modifier depends on VK_KHR_image_format_list, which is core since 1.2
+        let has_image_format_list = phd.has_device_extension(khr::image_format_list::NAME);
+        let dmabuf_supported = DMABUF_EXTENSIONS
+            .iter()
+            .all(|extension| phd.has_device_extension(extension))
+            && (has_image_format_list || version >= Version::VERSION_1_2);
+        let sync_supported = phd.has_device_extension(khr::external_semaphore_fd::NAME);
+
+        let mut extensions = Vec::new();
+        if dmabuf_supported {
+            extensions.extend(DMABUF_EXTENSIONS.iter().map(|extension| extension.as_ptr()));
+            if has_image_format_list {
+                extensions.push(khr::image_format_list::NAME.as_ptr());
+            }
+        }
+        if sync_supported {
+            extensions.push(khr::external_semaphore_fd::NAME.as_ptr());
+        }
+
+        let queue_create_info = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(queue_family_index)
+            .queue_priorities(&[1.0])];
+        let create_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_create_info)
+            .enabled_extension_names(&extensions);
+        let handle = unsafe { instance.create_device(phd.handle(), &create_info, None) }?;
+
+        let device = Arc::new(DeviceInner {
+            external_memory_fd: dmabuf_supported
+                .then(|| khr::external_memory_fd::Device::new(instance, &handle)),
+            external_semaphore_fd: sync_supported
+                .then(|| khr::external_semaphore_fd::Device::new(instance, &handle)),
+            memory_properties: unsafe { instance.get_physical_device_memory_properties(phd.handle()) },
+            handle,
+            phd: phd.clone(),
+        });
+
+        let format_features = |fourcc: DrmFourcc| {
+            let (format, _) = fourcc_to_vk(fourcc).expect("supported formats are convertible");
+            unsafe { instance.get_physical_device_format_properties(phd.handle(), format) }
+                .optimal_tiling_features
+        };
+        let mem_features = vk::FormatFeatureFlags::SAMPLED_IMAGE
+            | vk::FormatFeatureFlags::TRANSFER_SRC
+            | vk::FormatFeatureFlags::TRANSFER_DST;
+        let mem_formats = SUPPORTED_FORMATS
+            .iter()
+            .copied()
+            .filter(|&fourcc| format_features(fourcc).contains(mem_features))
+            .collect();
+        let render_formats = SUPPORTED_FORMATS
+            .iter()
+            .copied()
+            .filter(|&fourcc| {
+                format_features(fourcc)
+                    .contains(mem_features | vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND)
+            })
+            .collect();
+        let (dmabuf_texture_formats, dmabuf_render_formats) = if dmabuf_supported {
+            dmabuf_formats(phd)
+        } else {
+            Default::default()
+        };
+
+        let queue = unsafe { device.handle.get_device_queue(queue_family_index, 0) };
+
+        // Destroying null handles is a no-op, so a partially initialized renderer can be dropped
+        let mut renderer = VulkanRenderer {
+            device,
+            queue,
+            queue_family_index,
+            command_pool: vk::CommandPool::null(),
+            descriptor_set_layout: vk::DescriptorSetLayout::null(),
+            pipeline_layout: vk::PipelineLayout::null(),
+            vertex_shader: vk::ShaderModule::null(),
+            solid_shader: vk::ShaderModule::null(),
+            texture_shader: vk::ShaderModule::null(),
+            render_setups: HashMap::new(),
+
+            samplers: [vk::Sampler::null(); 4],
+            downscale_filter: TextureFilter::Linear,
+            upscale_filter: TextureFilter::Linear,
+            debug_flags: DebugFlags::empty(),
+
+            mem_formats,
+            render_formats,
+            dmabuf_texture_formats,
+            dmabuf_render_formats,
+
+            submissions: Vec::new(),
+            pending_waits: Vec::new(),
+            context_id: ContextId::new(),
+
+            buffers: HashMap::new(),
+            dmabuf_cache: HashMap::new(),
+        };
+        renderer.init()?;
+
+        Ok(renderer)
+    }
+
+    fn init(&mut self) -> Result<(), VulkanError> {
+        let device = &self.device.handle;
+
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(self.queue_family_index);
+        self.command_pool = unsafe { device.create_command_pool(&pool_info, None) }?;
+
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        self.descriptor_set_layout = unsafe { device.create_descriptor_set_layout(&layout_info, None) }?;
+
+        let set_layouts = [self.descriptor_set_layout];
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: size_of::<PushConstants>() as u32,
+        }];
+        let pipeline_layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+        self.pipeline_layout = unsafe { device.create_pipeline_layout(&pipeline_layout_info, None) }?;
+
+        let shader = |spv: &[u8]| -> Result<vk::ShaderModule, VulkanError> {
+            let code = ash::util::read_spv(&mut Cursor::new(spv)).expect("shaders are valid spir-v");
+            let info = vk::ShaderModuleCreateInfo::default().code(&code);
+            Ok(unsafe { device.create_shader_module(&info, None) }?)
+        };
+        self.vertex_shader = shader(include_bytes!("shaders/quad.vert.spv"))?;
+        self.solid_shader = shader(include_bytes!("shaders/solid.frag.spv"))?;
+        self.texture_shader = shader(include_bytes!("shaders/texture.frag.spv"))?;
+
+        let filter = |filter| match filter {
+            TextureFilter::Linear => vk::Filter::LINEAR,
+            TextureFilter::Nearest => vk::Filter::NEAREST,
+        };
+        for downscale in [TextureFilter::Linear, TextureFilter::Nearest] {
+            for upscale in [TextureFilter::Linear, TextureFilter::Nearest] {
+                let info = vk::SamplerCreateInfo::default()
+                    .min_filter(filter(downscale))
+                    .mag_filter(filter(upscale))
+                    .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+                    .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                    .max_lod(0.25);
+                self.samplers[sampler_index(downscale, upscale)] =
+                    unsafe { device.create_sampler(&info, None) }?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the physical device the renderer was created on
+    pub fn physical_device(&self) -> &PhysicalDevice {
+        &self.device.phd
+    }
+
+    /// Get the logical device of the renderer
+    pub fn device(&self) -> &ash::Device {
+        &self.device.handle
+    }
+
+    /// Get the index of the queue family the renderer submits to
+    pub fn queue_family_index(&self) -> u32 {
+        self.queue_family_index
+    }
+
+    /// Returns whether sync points of frames can be exported as sync_file
+    pub fn supports_sync_file(&self) -> bool {
+        self.device.external_semaphore_fd.is_some()
+    }
+
+    fn sampler(&self) -> vk::Sampler {
+        self.samplers[sampler_index(self.downscale_filter, self.upscale_filter)]
+    }
+
+    fn create_render_setup(&self, format: vk::Format) -> Result<RenderSetup, VulkanError> {
+        let device = &self.device.handle;
+
+        let attachments = [vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+        let color_attachments = [vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        }];
+        let subpasses = [vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachments)];
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachments)
+            .subpasses(&subpasses);
+        let render_pass = unsafe { device.create_render_pass(&render_pass_info, None) }?;
+        let render_pass = scopeguard::guard(render_pass, |render_pass| unsafe {
+            device.destroy_render_pass(render_pass, None)
+        });
+
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo::default();
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP);
+        let viewport = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+        let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .line_width(1.0);
+        let multisample = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+        // premultiplied alpha, like glBlendFunc(GL_ONE, GL_ONE_MINUS_SRC_ALPHA) in the gles renderer
+        let blend_attachment = |blend: bool| {
+            [vk::PipelineColorBlendAttachmentState::default()
+                .blend_enable(blend)
+                .src_color_blend_factor(vk::BlendFactor::ONE)
+                .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .color_blend_op(vk::BlendOp::ADD)
+                .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+                .alpha_blend_op(vk::BlendOp::ADD)
+                .color_write_mask(vk::ColorComponentFlags::RGBA)]
+        };
+        let opaque_attachments = blend_attachment(false);
+        let blend_attachments = blend_attachment(true);
+        let opaque = vk::PipelineColorBlendStateCreateInfo::default().attachments(&opaque_attachments);
+        let blend = vk::PipelineColorBlendStateCreateInfo::default().attachments(&blend_attachments);
+
+        let stages = |fragment: vk::ShaderModule| {
+            [
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(self.vertex_shader)
+                    .name(c"main"),
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(fragment)
+                    .name(c"main"),
+            ]
+        };
+        let solid_stages = stages(self.solid_shader);
+        let texture_stages = stages(self.texture_shader);
+
+        let base_info = vk::GraphicsPipelineCreateInfo::default()
+            .vertex_input_state(&vertex_input)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport)
+            .rasterization_state(&rasterization)
+            .multisample_state(&multisample)
+            .dynamic_state(&dynamic)
+            .layout(self.pipeline_layout)
+            .render_pass(*render_pass)
+            .subpass(0);
+        let infos = [
+            base_info.stages(&solid_stages).color_blend_state(&opaque),
+            base_info.stages(&solid_stages).color_blend_state(&blend),
+            base_info.stages(&texture_stages).color_blend_state(&blend),
+        ];
+        let pipelines = unsafe { device.create_graphics_pipelines(vk::PipelineCache::null(), &infos, None) }
+            .map_err(|(pipelines, err)| {
+                for pipeline in pipelines {
+                    unsafe { device.destroy_pipeline(pipeline, None) };
+                }
+                err
+            })?;
+
+        Ok(RenderSetup {
+            render_pass: scopeguard::ScopeGuard::into_inner(render_pass),
+            clear: pipelines[0],
+            solid: pipelines[1],
+            texture: pipelines[2],
+        })
+    }
+
+    /// Creates a device local image
+    fn create_texture(
+        &self,
+        fourcc: DrmFourcc,
+        size: Size<i32, BufferCoords>,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<VulkanTexture, VulkanError> {
+        let (format, _) = fourcc_to_vk(fourcc).ok_or(VulkanError::UnsupportedPixelFormat(fourcc))?;
+        if size.w <= 0 || size.h <= 0 {
+            return Err(VulkanError::InvalidSize(size));
+        }
+        let device = &self.device.handle;
+
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(format)
+            .extent(vk::Extent3D {
+                width: size.w as u32,
+                height: size.h as u32,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = unsafe { device.create_image(&image_info, None) }?;
+        let image = scopeguard::guard(image, |image| unsafe { device.destroy_image(image, None) });
+
+        let requirements = unsafe { device.get_image_memory_requirements(*image) };
+        let memory_type_index = self
+            .device
+            .find_memory_type(
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+            .or_else(|| {
+                self.device
+                    .find_memory_type(requirements.memory_type_bits, vk::MemoryPropertyFlags::empty())
+            })
+            .ok_or(VulkanError::NoMemoryType)?;
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        let memory = unsafe { device.allocate_memory(&allocate_info, None) }?;
+        let memory = scopeguard::guard(memory, |memory| unsafe { device.free_memory(memory, None) });
+        unsafe { device.bind_image_memory(*image, *memory, 0) }?;
+
+        VulkanTexture::from_image(
+            &self.device,
+            scopeguard::ScopeGuard::into_inner(image),
+            scopeguard::ScopeGuard::into_inner(memory),
+            fourcc,
+            size,
+            usage,
+            None,
+        )
+    }
+
+    /// Allocates a command buffer and starts recording it
+    fn begin_submission(&mut self) -> Result<Submission, VulkanError> {
+        self.cleanup_submissions();
+
+        let device = &self.device.handle;
+        let fence = FenceInner::new(&self.device)?;
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let command_buffer = unsafe { device.allocate_command_buffers(&allocate_info) }?[0];
+        let submission = Submission {
+            fence,
+            command_buffer,
+            framebuffer: vk::Framebuffer::null(),
+            descriptor_pool: vk::DescriptorPool::null(),
+            semaphores: Vec::new(),
+            buffers: Vec::new(),
+            textures: Vec::new(),
+        };
+
+        let begin_info =
+            vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        if let Err(err) = unsafe { device.begin_command_buffer(command_buffer, &begin_info) } {
+            self.free_submission(submission);
+            return Err(err.into());
+        }
+        Ok(submission)
+    }
+
+    /// Submits the recorded commands, waiting for all pending sync points
+    ///
+    /// Returns the fence of the submission and, if `export` is set and supported, a sync_file
+    /// signaled together with the fence.
+    fn submit(
+        &mut self,
+        mut submission: Submission,
+        export: bool,
+    ) -> Result<(Arc<FenceInner>, Option<OwnedFd>), VulkanError> {
+        let device = &self.device.handle;
+        let waits = std::mem::take(&mut self.pending_waits);
+        let wait_stages = vec![vk::PipelineStageFlags::ALL_COMMANDS; waits.len()];
+        submission.semaphores.extend(&waits);
+
+        let signal = if export {
+            match self.create_exportable_semaphore() {
+                Ok(semaphore) => semaphore,
+                Err(err) => {
+                    self.free_submission(submission);
+                    return Err(err);
+                }
+            }
+        } else {
+            None
+        };
+        submission.semaphores.extend(signal);
+
+        let command_buffers = [submission.command_buffer];
+        let signal_semaphores = signal.as_slice();
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(&waits)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(signal_semaphores);
+        let result = unsafe { device.end_command_buffer(submission.command_buffer) }.and_then(|_| unsafe {
+            device.queue_submit(self.queue, &[submit_info], submission.fence.handle())
+        });
+        if let Err(err) = result {
+            self.free_submission(submission);
+            return Err(err.into());
+        }
+
+        let sync_file = signal.and_then(|semaphore| self.export_sync_file(semaphore));
+        let fence = submission.fence.clone();
+        self.submissions.push(submission);
+        Ok((fence, sync_file))
+    }
+
+    fn free_submission(&self, submission: Submission) {
+        let device = &self.device.handle;
+        unsafe {
+            device.free_command_buffers(self.command_pool, &[submission.command_buffer]);
+            device.destroy_framebuffer(submission.framebuffer, None);
+            device.destroy_descriptor_pool(submission.descriptor_pool, None);
+            for semaphore in submission.semaphores {
+                device.destroy_semaphore(semaphore, None);
+            }
+        }
+    }
+
+    /// Frees the resources of finished submissions
+    fn cleanup_submissions(&mut self) {
+        let (finished, pending) = std::mem::take(&mut self.submissions)
+            .into_iter()
+            .partition::<Vec<_>, _>(|submission| submission.fence.is_signaled());
+        self.submissions = pending;
+        for submission in finished {
+            self.free_submission(submission);
+        }
+    }
+
+    /// Records a transition of `texture` into `layout`
+    ///
+    /// Images of dmabufs are acquired from the foreign queue family.
+    fn acquire(&self, command_buffer: vk::CommandBuffer, texture: &VulkanTexture, layout: vk::ImageLayout) {
+        let mut current = texture.inner.layout.lock().unwrap();
+        let (old_layout, src_queue, dst_queue) = if texture.inner.dmabuf.is_some() {
+            (
+                vk::ImageLayout::GENERAL,
+                vk::QUEUE_FAMILY_FOREIGN_EXT,
+                self.queue_family_index,
+            )
+        } else if *current == layout {
+            return;
+        } else {
+            (*current, vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED)
+        };
+        self.image_barrier(
+            command_buffer,
+            texture.inner.image,
+            old_layout,
+            layout,
+            src_queue,
+            dst_queue,
+        );
+        *current = layout;
+    }
+
+    /// Records the release of `texture` to the foreign queue family, if it is the image of a dmabuf
+    fn release(&self, command_buffer: vk::CommandBuffer, texture: &VulkanTexture) {
+        if texture.inner.dmabuf.is_none() {
+            return;
+        }
+        let mut current = texture.inner.layout.lock().unwrap();
+        self.image_barrier(
+            command_buffer,
+            texture.inner.image,
+            *current,
+            vk::ImageLayout::GENERAL,
+            self.queue_family_index,
+            vk::QUEUE_FAMILY_FOREIGN_EXT,
+        );
+        *current = vk::ImageLayout::GENERAL;
+    }
+
+    fn image_barrier(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        src_queue: u32,
+        dst_queue: u32,
+    ) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+            .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(src_queue)
+            .dst_queue_family_index(dst_queue)
+            .image(image)
+            .subresource_range(COLOR_RANGE);
+        unsafe {
+            self.device.handle.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            )
+        };
+    }
+
+    #[profiling::function]
+    fn submit_frame(&mut self, target: &VulkanTexture, draws: Vec<Draw>) -> Result<SyncPoint, VulkanError> {
+        let mut submission = self.begin_submission()?;
+        if let Err(err) = self.record_frame(&mut submission, target, &draws) {
+            self.free_submission(submission);
+            return Err(err);
+        }
+        let (fence, sync_file) = self.submit(submission, true)?;
+        Ok(SyncPoint::from(VulkanFence {
+            inner: fence,
+            sync_file,
+        }))
+    }
+
+    fn record_frame(
+        &self,
+        submission: &mut Submission,
+        target: &VulkanTexture,
+        draws: &[Draw],
+    ) -> Result<(), VulkanError> {
+        let device = &self.device.handle;
+        let command_buffer = submission.command_buffer;
+        let setup = &self.render_setups[&target.inner.format];
+
+        // every texture needs its own descriptor set, images and samplers are bound together
+        let mut bindings: Vec<(VulkanTexture, vk::Sampler)> = Vec::new();
+        for draw in draws {
+            if let DrawKind::Texture(texture, sampler) = &draw.kind {
+                if !bindings
+                    .iter()
+                    .any(|(t, s)| Arc::ptr_eq(&t.inner, &texture.inner) && s == sampler)
+                {
+                    bindings.push((texture.clone(), *sampler));
+                }
+            }
+        }
+
+        let mut descriptor_sets = Vec::new();
+        if !bindings.is_empty() {
+            let count = bindings.len() as u32;
+            let pool_sizes = [
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::SAMPLED_IMAGE,
+                    descriptor_count: count,
+                },
+                vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::SAMPLER,
+                    descriptor_count: count,
+                },
+            ];
+            let pool_info = vk::DescriptorPoolCreateInfo::default()
+                .max_sets(count)
+                .pool_sizes(&pool_sizes);
+            submission.descriptor_pool = unsafe { device.create_descriptor_pool(&pool_info, None) }?;
+
+            let set_layouts = vec![self.descriptor_set_layout; bindings.len()];
+            let allocate_info = vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(submission.descriptor_pool)
+                .set_layouts(&set_layouts);
+            descriptor_sets = unsafe { device.allocate_descriptor_sets(&allocate_info) }?;
+
+            let image_infos = bindings
+                .iter()
+                .map(|(texture, sampler)| {
+                    (
+                        [vk::DescriptorImageInfo::default()
+                            .image_view(texture.inner.view)
+                            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)],
+                        [vk::DescriptorImageInfo::default().sampler(*sampler)],
+                    )
+                })
+                .collect::<Vec<_>>();
+            let writes = descriptor_sets
+                .iter()
+                .zip(&image_infos)
+                .flat_map(|(set, (image, sampler))| {
+                    [
+                        vk::WriteDescriptorSet::default()
+                            .dst_set(*set)
+                            .dst_binding(0)
+                            .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                            .image_info(image),
+                        vk::WriteDescriptorSet::default()
+                            .dst_set(*set)
+                            .dst_binding(1)
+                            .descriptor_type(vk::DescriptorType::SAMPLER)
+                            .image_info(sampler),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+
+        let mut textures: Vec<VulkanTexture> = Vec::new();
+        for (texture, _) in &bindings {
+            if !textures.iter().any(|t| Arc::ptr_eq(&t.inner, &texture.inner)) {
+                textures.push(texture.clone());
+            }
+        }
+
+        let size = target.size();
+        let extent = vk::Extent2D {
+            width: size.w as u32,
+            height: size.h as u32,
+        };
+        let attachments = [target.inner.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(setup.render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        submission.framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None) }?;
+
+        for texture in &textures {
+            self.acquire(command_buffer, texture, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        }
+        self.acquire(command_buffer, target, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let render_area = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(setup.render_pass)
+            .framebuffer(submission.framebuffer)
+            .render_area(render_area);
+        unsafe {
+            device.cmd_begin_render_pass(command_buffer, &begin_info, vk::SubpassContents::INLINE);
+            device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: extent.width as f32,
+                    height: extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device.cmd_set_scissor(command_buffer, 0, &[render_area]);
+
+            let mut bound = vk::Pipeline::null();
+            for draw in draws {
+                let pipeline = match draw.kind {
+                    DrawKind::Clear => setup.clear,
+                    DrawKind::Solid => setup.solid,
+                    DrawKind::Texture(..) => setup.texture,
+                };
+                if pipeline != bound {
+                    device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                    bound = pipeline;
+                }
+                if let DrawKind::Texture(texture, sampler) = &draw.kind {
+                    let index = bindings
+                        .iter()
+                        .position(|(t, s)| Arc::ptr_eq(&t.inner, &texture.inner) && s == sampler)
+                        .expect("bindings contain all textures");
+                    device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        self.pipeline_layout,
+                        0,
+                        &[descriptor_sets[index]],
+                        &[],
+                    );
+                }
+                device.cmd_push_constants(
+                    command_buffer,
+                    self.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                    0,
+                    bytemuck::bytes_of(&draw.constants),
+                );
+                device.cmd_draw(command_buffer, 4, 1, 0, 0);
+            }
+            device.cmd_end_render_pass(command_buffer);
+        }
+
+        for texture in &textures {
+            self.release(command_buffer, texture);
+        }
+        self.release(command_buffer, target);
+
+        textures.push(target.clone());
+        submission.textures = textures;
+        Ok(())
+    }
+
+    /// Copies `region` of `data`, laid out with `stride` bytes per row, into the same region of `texture`
+    #[profiling::function]
+    fn upload(
+        &mut self,
+        texture: &VulkanTexture,
+        data: &[u8],
+        stride: usize,
+        region: Rectangle<i32, BufferCoords>,
+    ) -> Result<(), VulkanError> {
+        let bpp = bytes_per_pixel(texture.inner.format) as usize;
+        let row_len = region.size.w as usize * bpp;
+        let staging = HostBuffer::new(
+            &self.device,
+            (row_len * region.size.h as usize) as u64,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+        )?;
+        staging.with_mapped(|mapped| {
+            for (row, dst) in mapped.chunks_exact_mut(row_len).enumerate() {
+                let offset = (region.loc.y as usize + row) * stride + region.loc.x as usize * bpp;
+                dst.copy_from_slice(&data[offset..offset + row_len]);
+            }
+        })?;
+
+        let mut submission = self.begin_submission()?;
+        let command_buffer = submission.command_buffer;
+        self.acquire(command_buffer, texture, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        let copy = vk::BufferImageCopy::default()
+            .image_subresource(COLOR_LAYERS)
+            .image_offset(vk::Offset3D {
+                x: region.loc.x,
+                y: region.loc.y,
+                z: 0,
+            })
+            .image_extent(vk::Extent3D {
+                width: region.size.w as u32,
+                height: region.size.h as u32,
+                depth: 1,
+            });
+        unsafe {
+            self.device.handle.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer,
+                texture.inner.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[copy],
+            )
+        };
+        self.acquire(command_buffer, texture, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        submission.buffers.push(Arc::new(staging));
+        submission.textures.push(texture.clone());
+        self.submit(submission, false)?;
+        Ok(())
+    }
+
+    #[profiling::function]
+    fn copy(
+        &mut self,
+        texture: &VulkanTexture,
+        region: Rectangle<i32, BufferCoords>,
+        format: DrmFourcc,
+    ) -> Result<VulkanMapping, VulkanError> {
+        if !texture.inner.usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            return Err(VulkanError::MissingImageUsage(vk::ImageUsageFlags::TRANSFER_SRC));
+        }
+        if region.is_empty() || !Rectangle::from_size(texture.size()).contains_rect(region) {
+            return Err(VulkanError::RegionOutOfBounds(region));
+        }
+        if fourcc_to_vk(format).map(|(format, _)| format) != Some(texture.inner.format) {
+            return Err(VulkanError::UnsupportedPixelFormat(format));
+        }
+
+        let bpp = bytes_per_pixel(texture.inner.format) as u64;
+        let buffer = Arc::new(HostBuffer::new(
+            &self.device,
+            region.size.w as u64 * region.size.h as u64 * bpp,
+            vk::BufferUsageFlags::TRANSFER_DST,
+        )?);
+
+        let mut submission = self.begin_submission()?;
+        let command_buffer = submission.command_buffer;
+        self.acquire(command_buffer, texture, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        let copy = vk::BufferImageCopy::default()
+            .image_subresource(COLOR_LAYERS)
+            .image_offset(vk::Offset3D {
+                x: region.loc.x,
+                y: region.loc.y,
+                z: 0,
+            })
+            .image_extent(vk::Extent3D {
+                width: region.size.w as u32,
+                height: region.size.h as u32,
+                depth: 1,
+            });
+        let host_barrier = vk::MemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ);
+        unsafe {
+            let device = &self.device.handle;
+            device.cmd_copy_image_to_buffer(
+                command_buffer,
+                texture.inner.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer.buffer,
+                &[copy],
+            );
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[host_barrier],
+                &[],
+                &[],
+            );
+        }
+        self.release(command_buffer, texture);
+
+        submission.buffers.push(buffer.clone());
+        submission.textures.push(texture.clone());
+        let (fence, _) = self.submit(submission, false)?;
+
+        Ok(VulkanMapping {
+            buffer,
+            fence,
+            size: region.size,
+            format,
+            data: OnceLock::new(),
+        })
+    }
+
+    fn cleanup(&mut self) {
+        self.dmabuf_cache.retain(|dmabuf, _| !dmabuf.is_gone());
+        self.buffers.retain(|dmabuf, _| !dmabuf.is_gone());
+        self.cleanup_submissions();
+    }
+}
+
+impl Drop for VulkanRenderer {
+    fn drop(&mut self) {
+        let device = &self.device.handle;
+        unsafe {
+            let _ = device.device_wait_idle();
+        }
+        for submission in std::mem::take(&mut self.submissions) {
+            self.free_submission(submission);
+        }
+        unsafe {
+            for semaphore in self.pending_waits.drain(..) {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for (_, setup) in self.render_setups.drain() {
+                device.destroy_pipeline(setup.clear, None);
+                device.destroy_pipeline(setup.solid, None);
+                device.destroy_pipeline(setup.texture, None);
+                device.destroy_render_pass(setup.render_pass, None);
+            }
+            for sampler in self.samplers {
+                device.destroy_sampler(sampler, None);
+            }
+            device.destroy_shader_module(self.vertex_shader, None);
+            device.destroy_shader_module(self.solid_shader, None);
+            device.destroy_shader_module(self.texture_shader, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
+
+fn sampler_index(downscale: TextureFilter, upscale: TextureFilter) -> usize {
+    let index = |filter| match filter {
+        TextureFilter::Linear => 0,
+        TextureFilter::Nearest => 1,
+    };
+    index(downscale) * 2 + index(upscale)
+}
+
+/// Maps physical coordinates of an output of `output_size` to normalized device coordinates
+fn projection(mut output_size: Size<i32, Physical>, transform: Transform) -> Matrix3<f32> {
+    // Handle the width/height swap when the output is rotated by 90°/270°.
+    if let Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 = transform {
+        std::mem::swap(&mut output_size.w, &mut output_size.h);
+    }
+
+    // Same projection as the gles renderer, including the flip for the coordinate system of OpenGL:
+    // the first row of the target is at the bottom of the clip space in OpenGL and at the top in
+    // Vulkan, but both store it first in memory.
+    let mut renderer = Matrix3::<f32>::identity();
+    let x = 2.0 / (output_size.w as f32);
+    let y = 2.0 / (output_size.h as f32);
+
+    // Scale
+    renderer[0][0] = x;
+    renderer[1][1] = -y;
+
+    // Translation
+    renderer[2][0] = -1.0;
+    renderer[2][1] = 1.0;
+
+    let flip180 = Matrix3::new(1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0);
+    flip180 * transform.matrix() * renderer
+}
+
+impl RendererSuper for VulkanRenderer {
+    type Error = VulkanError;
+    type TextureId = VulkanTexture;
+    type Framebuffer<'buffer> = VulkanTarget<'buffer>;
+    type Frame<'frame, 'buffer>
+        = VulkanFrame<'frame, 'buffer>
+    where
+        'buffer: 'frame,
+        Self: 'frame;
+}
+
+impl Renderer for VulkanRenderer {
+    fn context_id(&self) -> ContextId<VulkanTexture> {
+        self.context_id.clone()
+    }
+
+    fn downscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.downscale_filter = filter;
+        Ok(())
+    }
+
+    fn upscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.upscale_filter = filter;
+        Ok(())
+    }
+
+    fn set_debug_flags(&mut self, flags: DebugFlags) {
+        self.debug_flags = flags;
+    }
+
+    fn debug_flags(&self) -> DebugFlags {
+        self.debug_flags
+    }
+
+    #[profiling::function]
+    fn render<'frame, 'buffer>(
+        &'frame mut self,
+        target: &'frame mut VulkanTarget<'buffer>,
+        output_size: Size<i32, Physical>,
+        transform: Transform,
+    ) -> Result<VulkanFrame<'frame, 'buffer>, Self::Error>
+    where
+        'buffer: 'frame,
+    {
+        self.cleanup_submissions();
+
+        let format = target.texture.inner.format;
+        if !self.render_setups.contains_key(&format) {
+            let setup = self.create_render_setup(format)?;
+            self.render_setups.insert(format, setup);
+        }
+
+        let projection = projection(output_size, transform);
+
+        Ok(VulkanFrame {
+            renderer: self,
+            target,
+            projection,
+            transform,
+            draws: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn wait(&mut self, sync: &SyncPoint) -> Result<(), Self::Error> {
+        self.wait_for(sync)
+    }
+
+    fn cleanup_texture_cache(&mut self) -> Result<(), Self::Error> {
+        self.cleanup();
+        Ok(())
+    }
+}
+
+impl ImportMem for VulkanRenderer {
+    #[profiling::function]
+    fn import_memory(
+        &mut self,
+        data: &[u8],
+        format: DrmFourcc,
+        size: Size<i32, BufferCoords>,
+        flipped: bool,
+    ) -> Result<VulkanTexture, VulkanError> {
+        if !self.mem_formats.contains(&format) {
+            return Err(VulkanError::UnsupportedPixelFormat(format));
+        }
+        let (vk_format, _) = fourcc_to_vk(format).ok_or(VulkanError::UnsupportedPixelFormat(format))?;
+        let stride = size.w as usize * bytes_per_pixel(vk_format) as usize;
+        let expected_len = stride * size.h as usize;
+        if data.len() < expected_len {
+            return Err(VulkanError::IncompleteBuffer {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+
+        let mut texture = self.create_texture(
+            format,
+            size,
+            vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::TRANSFER_SRC,
+        )?;
+        self.upload(&texture, data, stride, Rectangle::from_size(size))?;
+        texture.flipped = flipped;
+
+        Ok(texture)
+    }
+
+    #[profiling::function]
+    fn update_memory(
+        &mut self,
+        texture: &VulkanTexture,
+        data: &[u8],
+        region: Rectangle<i32, BufferCoords>,
+    ) -> Result<(), VulkanError> {
+        if !texture.inner.usage.contains(vk::ImageUsageFlags::TRANSFER_DST) || texture.inner.dmabuf.is_some()
+        {
+            return Err(VulkanError::MissingImageUsage(vk::ImageUsageFlags::TRANSFER_DST));
+        }
+        if !Rectangle::from_size(texture.size()).contains_rect(region) {
+            return Err(VulkanError::RegionOutOfBounds(region));
+        }
+        let stride = texture.width() as usize * bytes_per_pixel(texture.inner.format) as usize;
+        let expected_len = stride * texture.height() as usize;
+        if data.len() < expected_len {
+            return Err(VulkanError::IncompleteBuffer {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+
+        if region.is_empty() {
+            return Ok(());
+        }
+        self.upload(texture, data, stride, region)
+    }
+
+    fn mem_formats(&self) -> Box<dyn Iterator<Item = DrmFourcc>> {
+        Box::new(self.mem_formats.clone().into_iter())
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportMemWl for VulkanRenderer {
+    #[profiling::function]
+    fn import_shm_buffer(
+        &mut self,
+        buffer: &wl_buffer::WlBuffer,
+        surface: Option<&SurfaceData>,
+        damage: &[Rectangle<i32, BufferCoords>],
+    ) -> Result<VulkanTexture, VulkanError> {
+        type CacheMap = HashMap<ContextId<VulkanTexture>, VulkanTexture>;
+
+        let mut surface_lock = surface.as_ref().map(|surface_data| {
+            surface_data
+                .data_map
+                .get_or_insert_threadsafe(|| Arc::new(Mutex::new(CacheMap::new())))
+                .lock()
+                .unwrap()
+        });
+
+        shm::with_buffer_contents(buffer, |ptr, len, data| {
+            let format = shm::shm_format_to_fourcc(data.format)
+                .filter(|format| self.mem_formats.contains(format))
+                .ok_or(VulkanError::UnsupportedWlPixelFormat(data.format))?;
+            let size = Size::<i32, BufferCoords>::from((data.width, data.height));
+
+            let offset = data.offset as usize;
+            let contents_len = data.stride as usize * data.height as usize;
+            if len < offset + contents_len {
+                return Err(VulkanError::IncompleteBuffer {
+                    expected: offset + contents_len,
+                    actual: len,
+                });
+            }
+            // SAFETY: the pool is mapped for the duration of the closure and the buffer was checked
+            // to lie within it
+            let contents = unsafe { std::slice::from_raw_parts(ptr.add(offset), contents_len) };
+
+            let id = self.context_id();
+            let cached = surface_lock
+                .as_ref()
+                .and_then(|cache| cache.get(&id).cloned())
+                .filter(|texture| texture.size() == size && texture.inner.fourcc == format);
+
+            let texture = match cached {
+                Some(texture) => {
+                    for region in damage
+                        .iter()
+                        .filter_map(|rect| rect.intersection(Rectangle::from_size(size)))
+                    {
+                        self.upload(&texture, contents, data.stride as usize, region)?;
+                    }
+                    texture
+                }
+                None => {
+                    let texture = self.create_texture(
+                        format,
+                        size,
+                        vk::ImageUsageFlags::SAMPLED
+                            | vk::ImageUsageFlags::TRANSFER_DST
+                            | vk::ImageUsageFlags::TRANSFER_SRC,
+                    )?;
+                    self.upload(
+                        &texture,
+                        contents,
+                        data.stride as usize,
+                        Rectangle::from_size(size),
+                    )?;
+                    if let Some(cache) = surface_lock.as_mut() {
+                        cache.insert(id, texture.clone());
+                    }
+                    texture
+                }
+            };
+
+            Ok(texture)
+        })?
+    }
+}
+
+impl ImportDma for VulkanRenderer {
+    #[profiling::function]
+    fn import_dmabuf(
+        &mut self,
+        dmabuf: &Dmabuf,
+        _damage: Option<&[Rectangle<i32, BufferCoords>]>,
+    ) -> Result<VulkanTexture, VulkanError> {
+        if let Some(texture) = self.dmabuf_cache.get(&dmabuf.weak()) {
+            return Ok(texture.clone());
+        }
+
+        let texture = self.import_dmabuf_texture(dmabuf, false)?;
+        self.dmabuf_cache.insert(dmabuf.weak(), texture.clone());
+        Ok(texture)
+    }
+
+    fn dmabuf_formats(&self) -> FormatSet {
+        self.dmabuf_texture_formats.clone()
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportDmaWl for VulkanRenderer {}
+
+impl Bind<Dmabuf> for VulkanRenderer {
+    #[profiling::function]
+    fn bind<'a>(&mut self, target: &'a mut Dmabuf) -> Result<VulkanTarget<'a>, VulkanError> {
+        let texture = match self.buffers.get(&target.weak()) {
+            Some(texture) => texture.clone(),
+            None => {
+                let texture = self.import_dmabuf_texture(target, true)?;
+                self.buffers.insert(target.weak(), texture.clone());
+                texture
+            }
+        };
+
+        Ok(VulkanTarget {
+            texture,
+            _buffer: PhantomData,
+        })
+    }
+
+    fn supported_formats(&self) -> Option<FormatSet> {
+        self.device
+            .external_memory_fd
+            .is_some()
+            .then(|| self.dmabuf_render_formats.clone())
+    }
+}
+
+impl Offscreen<VulkanTexture> for VulkanRenderer {
+    #[profiling::function]
+    fn create_buffer(
+        &mut self,
+        format: DrmFourcc,
+        size: Size<i32, BufferCoords>,
+    ) -> Result<VulkanTexture, VulkanError> {
+        if !self.render_formats.contains(&format) {
+            return Err(VulkanError::UnsupportedPixelFormat(format));
+        }
+        self.create_texture(
+            format,
+            size,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST,
+        )
+    }
+}
+
+impl Bind<VulkanTexture> for VulkanRenderer {
+    fn bind<'a>(&mut self, target: &'a mut VulkanTexture) -> Result<VulkanTarget<'a>, VulkanError> {
+        if !target.inner.usage.contains(vk::ImageUsageFlags::COLOR_ATTACHMENT) {
+            return Err(VulkanError::MissingImageUsage(
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ));
+        }
+        Ok(VulkanTarget {
+            texture: target.clone(),
+            _buffer: PhantomData,
+        })
+    }
+}
+
+/// A texture read back from the gpu
+#[derive(Debug)]
+pub struct VulkanMapping {
+    buffer: Arc<HostBuffer>,
+    fence: Arc<FenceInner>,
+    size: Size<i32, BufferCoords>,
+    format: DrmFourcc,
+    /// Contents of `buffer`, once the copy finished
+    data: OnceLock<Vec<u8>>,
+}
+
+impl Texture for VulkanMapping {
+    fn width(&self) -> u32 {
+        self.size.w as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.size.h as u32
+    }
+
+    fn format(&self) -> Option<DrmFourcc> {
+        Some(self.format)
+    }
+}
+
+impl TextureMapping for VulkanMapping {
+    fn flipped(&self) -> bool {
+        false
+    }
+}
+
+impl ExportMem for VulkanRenderer {
+    type TextureMapping = VulkanMapping;
+
+    fn copy_framebuffer(
+        &mut self,
+        target: &VulkanTarget<'_>,
+        region: Rectangle<i32, BufferCoords>,
+        format: DrmFourcc,
+    ) -> Result<VulkanMapping, VulkanError> {
+        self.copy(&target.texture, region, format)
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &VulkanTexture,
+        region: Rectangle<i32, BufferCoords>,
+        format: DrmFourcc,
+    ) -> Result<VulkanMapping, VulkanError> {
+        self.copy(texture, region, format)
+    }
+
+    fn can_read_texture(&mut self, texture: &VulkanTexture) -> Result<bool, VulkanError> {
+        Ok(texture.inner.usage.contains(vk::ImageUsageFlags::TRANSFER_SRC))
+    }
+
+    #[profiling::function]
+    fn map_texture<'a>(&mut self, texture_mapping: &'a VulkanMapping) -> Result<&'a [u8], VulkanError> {
+        if let Some(data) = texture_mapping.data.get() {
+            return Ok(data);
+        }
+
+        texture_mapping.fence.wait()?;
+        let data = texture_mapping.buffer.with_mapped(|data| data.to_vec())?;
+
+        Ok(texture_mapping.data.get_or_init(|| data))
+    }
+}
+
+/// Builds the matrix mapping positions in `dest` to texture coordinates of `src`,
+/// see the function of the same name in the gles renderer
+fn build_texture_mat(
+    src: Rectangle<f64, BufferCoords>,
+    dest: Rectangle<i32, Physical>,
+    texture: Size<i32, BufferCoords>,
+    transform: Transform,
+) -> Matrix3<f32> {
+    let dst_src_size = transform.transform_size(src.size);
+    let scale = dst_src_size.to_f64() / dest.size.to_f64();
+
+    let mut tex_mat = Matrix3::<f32>::identity();
+
+    // first bring the damage into src scale
+    tex_mat = Matrix3::from_nonuniform_scale(scale.x as f32, scale.y as f32) * tex_mat;
+
+    // then compensate for the texture transform
+    let transform_mat = transform.matrix();
+    let translation = match transform {
+        Transform::Normal => Matrix3::identity(),
+        Transform::_90 => Matrix3::from_translation(Vector2::new(0f32, dst_src_size.w as f32)),
+        Transform::_180 => {
+            Matrix3::from_translation(Vector2::new(dst_src_size.w as f32, dst_src_size.h as f32))
+        }
+        Transform::_270 => Matrix3::from_translation(Vector2::new(dst_src_size.h as f32, 0f32)),
+        Transform::Flipped => Matrix3::from_translation(Vector2::new(dst_src_size.w as f32, 0f32)),
+        Transform::Flipped90 => Matrix3::identity(),
+        Transform::Flipped180 => Matrix3::from_translation(Vector2::new(0f32, dst_src_size.h as f32)),
+        Transform::Flipped270 => {
+            Matrix3::from_translation(Vector2::new(dst_src_size.h as f32, dst_src_size.w as f32))
+        }
+    };
+    tex_mat = transform_mat * tex_mat;
+    tex_mat = translation * tex_mat;
+
+    // now we can add the src crop loc, the size already done implicit by the src size
+    tex_mat = Matrix3::from_translation(Vector2::new(src.loc.x as f32, src.loc.y as f32)) * tex_mat;
+
+    // at last we have to normalize the values for UV space
+    tex_mat = Matrix3::from_nonuniform_scale(
+        (1.0f64 / texture.w as f64) as f32,
+        (1.0f64 / texture.h as f64) as f32,
+    ) * tex_mat;
+
+    tex_mat
+}
+
+#[cfg(test)]
+mod tests {
+    use ash::vk;
+    use cgmath::Vector3;
+    use drm_fourcc::DrmFourcc;
+
+    use super::{projection, VulkanRenderer, VulkanTexture};
+    use crate::{
+        backend::{
+            renderer::{
+                test::conformance, Bind, Color32F, DebugFlags, ExportMem, Frame, ImportMem, Offscreen,
+                Renderer, Texture, TextureFilter,
+            },
+            vulkan::{version::Version, Instance, PhysicalDevice},
+        },
+        utils::{Buffer, Physical, Rectangle, Size, Transform},
+    };
+
+    /// Renderer on a software implementation like lavapipe, or any other device if there is none
+    ///
+    /// Returns `None` if there is no vulkan device at all, the rendering tests are skipped in that case
+    /// unless `vulkan` is listed in [`conformance::REQUIRE_DEVICES_VAR`].
+    fn renderer() -> Option<VulkanRenderer> {
+        let renderer = Instance::new(Version::VERSION_1_2, None)
+            .ok()
+            .and_then(|instance| {
+                let mut devices = PhysicalDevice::enumerate(&instance).ok()?.collect::<Vec<_>>();
+                devices.sort_by_key(|phd| phd.ty() != vk::PhysicalDeviceType::CPU);
+                devices.iter().find_map(|phd| VulkanRenderer::new(phd).ok())
+            });
+        conformance::require_device("vulkan", renderer)
+    }
+
+    fn render(
+        renderer: &mut VulkanRenderer,
+        buffer: &mut VulkanTexture,
+        draw: impl FnOnce(&mut super::VulkanFrame<'_, '_>),
+    ) {
+        let size = buffer.size();
+        let mut target = renderer.bind(buffer).unwrap();
+        let mut frame = renderer
+            .render(&mut target, (size.w, size.h).into(), Transform::Normal)
+            .unwrap();
+        draw(&mut frame);
+        let sync = frame.finish().unwrap();
+        renderer.wait(&sync).unwrap();
+    }
+
+    /// Pixels of `texture` as `[b, g, r, a]`
+    fn pixels(renderer: &mut VulkanRenderer, texture: &VulkanTexture) -> Vec<[u8; 4]> {
+        let mapping = renderer
+            .copy_texture(texture, Rectangle::from_size(texture.size()), DrmFourcc::Argb8888)
+            .unwrap();
+        let data = renderer.map_texture(&mapping).unwrap();
+        data.chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    const RED: [u8; 4] = [0, 0, 255, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [255, 0, 0, 255];
+
+    #[test]
+    fn projection_normal_and_rotated() {
+        let size: Size<i32, Physical> = (128, 64).into();
+
+        // the first row of the target is at the top of the clip space
+        let normal = projection(size, Transform::Normal);
+        assert_eq!(
+            normal * Vector3::new(0f32, 0f32, 1f32),
+            Vector3::new(-1f32, -1f32, 1f32)
+        );
+        assert_eq!(
+            normal * Vector3::new(128f32, 64f32, 1f32),
+            Vector3::new(1f32, 1f32, 1f32)
+        );
+
+        // the frame of a rotated output has the swapped size
+        let rotated = projection(size, Transform::_90);
+        assert_eq!(
+            rotated * Vector3::new(0f32, 0f32, 1f32),
+            Vector3::new(1f32, -1f32, 1f32)
+        );
+        assert_eq!(
+            rotated * Vector3::new(64f32, 128f32, 1f32),
+            Vector3::new(-1f32, 1f32, 1f32)
+        );
+    }
+
+    #[test]
+    fn clear_and_draw_solid() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        let mut buffer = renderer
+            .create_buffer(DrmFourcc::Argb8888, (4, 4).into())
+            .unwrap();
+
+        render(&mut renderer, &mut buffer, |frame| {
+            frame
+                .clear(
+                    Color32F::new(1.0, 0.0, 0.0, 1.0),
+                    &[Rectangle::from_size((4, 4).into())],
+                )
+                .unwrap();
+            frame
+                .clear(
+                    Color32F::new(0.0, 0.0, 1.0, 1.0),
+                    &[Rectangle::new((2, 0).into(), (2, 2).into())],
+                )
+                .unwrap();
+            // damage is relative to the destination and limited to it
+            frame
+                .draw_solid(
+                    Rectangle::new((0, 3).into(), (2, 1).into()),
+                    &[Rectangle::new((1, 0).into(), (4, 4).into())],
+                    Color32F::new(0.0, 1.0, 0.0, 1.0),
+                )
+                .unwrap();
+        });
+
+        let pixels = pixels(&mut renderer, &buffer);
+        assert_eq!(pixels[0], RED);
+        assert_eq!(pixels[2], BLUE);
+        assert_eq!(pixels[4 + 3], BLUE);
+        assert_eq!(pixels[2 * 4 + 3], RED);
+        assert_eq!(pixels[3 * 4], RED);
+        assert_eq!(pixels[3 * 4 + 1], GREEN);
+        assert_eq!(pixels[3 * 4 + 2], RED);
+    }
+
+    #[test]
+    fn render_texture_transformed() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        renderer.upscale_filter(TextureFilter::Nearest).unwrap();
+        renderer.downscale_filter(TextureFilter::Nearest).unwrap();
+
+        let texture = renderer
+            .import_memory(&[RED, GREEN].concat(), DrmFourcc::Argb8888, (2, 1).into(), false)
+            .unwrap();
+        let mut buffer = renderer
+            .create_buffer(DrmFourcc::Argb8888, (4, 1).into())
+            .unwrap();
+        let src: Rectangle<f64, Buffer> = Rectangle::from_size((2.0, 1.0).into());
+
+        render(&mut renderer, &mut buffer, |frame| {
+            let damage = [Rectangle::from_size((2, 1).into())];
+            frame
+                .render_texture_from_to(
+                    &texture,
+                    src,
+                    Rectangle::from_size((2, 1).into()),
+                    &damage,
+                    &[],
+                    Transform::Normal,
+                    1.0,
+                )
+                .unwrap();
+            frame
+                .render_texture_from_to(
+                    &texture,
+                    src,
+                    Rectangle::new((2, 0).into(), (2, 1).into()),
+                    &damage,
+                    &[],
+                    Transform::Flipped,
+                    1.0,
+                )
+                .unwrap();
+        });
+
+        assert_eq!(pixels(&mut renderer, &buffer), [RED, GREEN, GREEN, RED]);
+    }
+
+    #[test]
+    fn alpha_and_tint() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        // the alpha channel of xrgb textures is ignored
+        let opaque = renderer
+            .import_memory(&[255, 255, 255, 0], DrmFourcc::Xrgb8888, (1, 1).into(), false)
+            .unwrap();
+        let translucent = renderer
+            .import_memory(&[0, 0, 128, 128], DrmFourcc::Argb8888, (1, 1).into(), false)
+            .unwrap();
+        let mut buffer = renderer
+            .create_buffer(DrmFourcc::Argb8888, (3, 1).into())
+            .unwrap();
+
+        render(&mut renderer, &mut buffer, |frame| {
+            frame
+                .clear(
+                    Color32F::new(0.0, 0.0, 1.0, 1.0),
+                    &[Rectangle::from_size((3, 1).into())],
+                )
+                .unwrap();
+            // premultiplied half transparent red over blue
+            frame
+                .render_texture_at(
+                    &translucent,
+                    (2, 0).into(),
+                    1,
+                    1.0,
+                    Transform::Normal,
+                    &[Rectangle::from_size((1, 1).into())],
+                    &[],
+                    1.0,
+                )
+                .unwrap();
+        });
+        renderer.set_debug_flags(DebugFlags::TINT);
+        render(&mut renderer, &mut buffer, |frame| {
+            frame
+                .clear(
+                    Color32F::new(0.0, 0.0, 0.0, 1.0),
+                    &[Rectangle::from_size((2, 1).into())],
+                )
+                .unwrap();
+            frame
+                .render_texture_at(
+                    &opaque,
+                    (0, 0).into(),
+                    1,
+                    1.0,
+                    Transform::Normal,
+                    &[Rectangle::from_size((1, 1).into())],
+                    &[],
+                    1.0,
+                )
+                .unwrap();
+        });
+
+        let pixels = pixels(&mut renderer, &buffer);
+        let assert_close = |pixel: [u8; 4], expected: [u8; 4]| {
+            for (channel, expected) in pixel.into_iter().zip(expected) {
+                assert!(channel.abs_diff(expected) <= 1, "{:?} != {:?}", pixel, expected);
+            }
+        };
+        // 0.8 * white + the green tint
+        assert_close(pixels[0], [204, 255, 204, 255]);
+        assert_eq!(pixels[1], [0, 0, 0, 255]);
+        assert_close(pixels[2], [127, 0, 128, 255]);
+    }
+
+    #[test]
+    fn update_and_export_memory() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        let texture = renderer
+            .import_memory(&[RED; 4].concat(), DrmFourcc::Argb8888, (2, 2).into(), false)
+            .unwrap();
+        renderer
+            .update_memory(
+                &texture,
+                &[RED, RED, RED, BLUE].concat(),
+                Rectangle::new((1, 1).into(), (1, 1).into()),
+            )
+            .unwrap();
+        assert!(renderer.can_read_texture(&texture).unwrap());
+        assert_eq!(pixels(&mut renderer, &texture), [RED, RED, RED, BLUE]);
+
+        let mapping = renderer
+            .copy_texture(
+                &texture,
+                Rectangle::new((1, 0).into(), (1, 2).into()),
+                DrmFourcc::Argb8888,
+            )
+            .unwrap();
+        assert_eq!(renderer.map_texture(&mapping).unwrap(), [RED, BLUE].concat());
+
+        assert!(renderer
+            .copy_texture(&texture, Rectangle::from_size((3, 3).into()), DrmFourcc::Argb8888)
+            .is_err());
+        assert!(renderer
+            .copy_texture(&texture, Rectangle::from_size((2, 2).into()), DrmFourcc::Abgr8888)
+            .is_err());
+    }
+
+    #[cfg(feature = "wayland_frontend")]
+    #[test]
+    fn import_shm_with_offset() {
+        use crate::{
+            backend::renderer::ImportMemWl, reexports::wayland_server::protocol::wl_shm,
+            wayland::shm::test::TestBuffer,
+        };
+
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        // the buffer starts after a row of another buffer and ends at the end of the pool
+        let pool = [[BLUE; 2].concat(), [RED, RED, RED, BLUE].concat()].concat();
+        let buffer = TestBuffer::new(&pool, 8, 2, 2, 8, wl_shm::Format::Argb8888);
+        let texture = renderer.import_shm_buffer(&buffer.buffer, None, &[]).unwrap();
+        assert_eq!(pixels(&mut renderer, &texture), [RED, RED, RED, BLUE]);
+
+        let buffer = TestBuffer::new(&pool, 12, 2, 2, 8, wl_shm::Format::Argb8888);
+        assert!(renderer.import_shm_buffer(&buffer.buffer, None, &[]).is_err());
+    }
+
+    #[test]
+    fn conformance() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        if let Err(err) = conformance::check_offscreen::<_, VulkanTexture>(&mut renderer, DrmFourcc::Argb8888)
+        {
+            panic!("{}", err);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+#version 450
+
+// Vertex shader of all pipelines of the vulkan renderer, mirrors the programs of the gles renderer.
+// The compiled shaders are checked in, regenerate them with `naga quad.vert quad.vert.spv` and
+// likewise for the fragment shaders after changing any of them.
+
+// Must match `PushConstants` in mod.rs
+layout(push_constant) uniform PushConstants {
+    // rows of the affine matrices mapping positions to clip space and to texture coordinates
+    vec4 transform[2];
+    vec4 tex_transform[2];
+    // translation in xy and size in zw of the quad
+    vec4 rect;
+    vec4 color;
+    float alpha;
+    uint flags;
+} pc;
+
+layout(location = 0) out vec2 v_tex_coords;
+
+void main() {
+    // corners of the unit quad in triangle strip order
+    vec2 corner = vec2(float(gl_VertexIndex & 1), float(gl_VertexIndex >> 1));
+    vec3 position = vec3(pc.rect.xy + corner * pc.rect.zw, 1.0);
+
+    v_tex_coords = vec2(dot(pc.tex_transform[0].xyz, position), dot(pc.tex_transform[1].xyz, position));
+    gl_Position = vec4(dot(pc.transform[0].xyz, position), dot(pc.transform[1].xyz, position), 0.0, 1.0);
+}
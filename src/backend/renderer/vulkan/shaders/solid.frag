@@ -0,0 +1,17 @@
+#version 450
+
+// Must match `PushConstants` in mod.rs
+layout(push_constant) uniform PushConstants {
+    vec4 transform[2];
+    vec4 tex_transform[2];
+    vec4 rect;
+    vec4 color;
+    float alpha;
+    uint flags;
+} pc;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = pc.color;
+}
@@ -0,0 +1,35 @@
+#version 450
+
+const uint FLAG_NO_ALPHA = 1u;
+const uint FLAG_TINT = 2u;
+
+// Must match `PushConstants` in mod.rs
+layout(push_constant) uniform PushConstants {
+    vec4 transform[2];
+    vec4 tex_transform[2];
+    vec4 rect;
+    vec4 color;
+    float alpha;
+    uint flags;
+} pc;
+
+layout(set = 0, binding = 0) uniform texture2D tex;
+layout(set = 0, binding = 1) uniform sampler tex_sampler;
+
+layout(location = 0) in vec2 v_tex_coords;
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    vec4 color = texture(sampler2D(tex, tex_sampler), v_tex_coords);
+
+    if ((pc.flags & FLAG_NO_ALPHA) != 0u) {
+        color = vec4(color.rgb, 1.0);
+    }
+    color = color * pc.alpha;
+
+    if ((pc.flags & FLAG_TINT) != 0u) {
+        color = vec4(0.0, 0.2, 0.0, 0.2) + color * 0.8;
+    }
+
+    out_color = color;
+}
@@ -0,0 +1,172 @@
+//! Synchronization of the vulkan renderer with other devices and processes
+
+use std::{
+    fmt,
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd},
+    sync::Arc,
+};
+
+use ash::vk;
+
+use crate::backend::renderer::sync::{Fence, Interrupted, SyncPoint};
+
+use super::{DeviceInner, VulkanError, VulkanRenderer};
+
+/// A vulkan fence, destroyed once neither the renderer nor any [`SyncPoint`] refers to it
+pub(super) struct FenceInner {
+    device: Arc<DeviceInner>,
+    fence: vk::Fence,
+}
+
+impl fmt::Debug for FenceInner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FenceInner").field("fence", &self.fence).finish()
+    }
+}
+
+impl FenceInner {
+    pub(super) fn new(device: &Arc<DeviceInner>) -> Result<Arc<FenceInner>, VulkanError> {
+        let fence = unsafe { device.handle.create_fence(&vk::FenceCreateInfo::default(), None) }?;
+        Ok(Arc::new(FenceInner {
+            device: device.clone(),
+            fence,
+        }))
+    }
+
+    pub(super) fn handle(&self) -> vk::Fence {
+        self.fence
+    }
+
+    pub(super) fn is_signaled(&self) -> bool {
+        unsafe { self.device.handle.get_fence_status(self.fence) }.unwrap_or(false)
+    }
+
+    pub(super) fn wait(&self) -> Result<(), VulkanError> {
+        unsafe { self.device.handle.wait_for_fences(&[self.fence], true, u64::MAX) }?;
+        Ok(())
+    }
+}
+
+impl Drop for FenceInner {
+    fn drop(&mut self) {
+        unsafe { self.device.handle.destroy_fence(self.fence, None) };
+    }
+}
+
+/// Fence signaled once a submission of a [`VulkanRenderer`] finished executing
+///
+/// If the device supports `VK_KHR_external_semaphore_fd`, the fence can be exported as sync_file,
+/// e.g. to be used as in-fence of a drm plane.
+#[derive(Debug)]
+pub struct VulkanFence {
+    pub(super) inner: Arc<FenceInner>,
+    pub(super) sync_file: Option<OwnedFd>,
+}
+
+impl Fence for VulkanFence {
+    fn is_signaled(&self) -> bool {
+        self.inner.is_signaled()
+    }
+
+    fn wait(&self) -> Result<(), Interrupted> {
+        self.inner.wait().map_err(|err| {
+            tracing::warn!(?err, "Waiting for fence was interrupted");
+            Interrupted
+        })
+    }
+
+    fn is_exportable(&self) -> bool {
+        self.sync_file.is_some()
+    }
+
+    fn export(&self) -> Option<OwnedFd> {
+        self.sync_file.as_ref().and_then(|fd| fd.try_clone().ok())
+    }
+}
+
+impl VulkanRenderer {
+    /// Makes the next submission wait for `sync`
+    ///
+    /// Sync points that can be exported as sync_file, like those of other renderers or drm_syncobj
+    /// timelines, are waited for on the gpu. Other sync points block until they are reached.
+    pub(super) fn wait_for(&mut self, sync: &SyncPoint) -> Result<(), VulkanError> {
+        if sync.is_reached() {
+            return Ok(());
+        }
+
+        // submissions of this renderer are executed in order anyway
+        if let Some(fence) = sync.get::<VulkanFence>() {
+            if Arc::ptr_eq(&fence.inner.device, &self.device) {
+                return Ok(());
+            }
+        }
+
+        if self.device.external_semaphore_fd.is_some() {
+            if let Some(sync_file) = sync.export() {
+                match self.import_sync_file(sync_file) {
+                    Ok(semaphore) => {
+                        self.pending_waits.push(semaphore);
+                        return Ok(());
+                    }
+                    Err(err) => tracing::debug!(?err, "Failed to import sync_file, blocking instead"),
+                }
+            }
+        }
+
+        sync.wait().map_err(|_| VulkanError::SyncInterrupted)
+    }
+
+    /// Creates a semaphore with a temporary payload imported from `sync_file`
+    fn import_sync_file(&self, sync_file: OwnedFd) -> Result<vk::Semaphore, VulkanError> {
+        let external_semaphore_fd = self
+            .device
+            .external_semaphore_fd
+            .as_ref()
+            .ok_or(VulkanError::Vk(vk::Result::ERROR_EXTENSION_NOT_PRESENT))?;
+        let device = &self.device.handle;
+
+        let semaphore = unsafe { device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None) }?;
+        let import_info = vk::ImportSemaphoreFdInfoKHR::default()
+            .semaphore(semaphore)
+            .flags(vk::SemaphoreImportFlags::TEMPORARY)
+            .handle_type(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD)
+            .fd(sync_file.as_raw_fd());
+        if let Err(err) = unsafe { external_semaphore_fd.import_semaphore_fd(&import_info) } {
+            unsafe { device.destroy_semaphore(semaphore, None) };
+            return Err(err.into());
+        }
+        // Vulkan took ownership of the fd
+        let _ = sync_file.into_raw_fd();
+
+        Ok(semaphore)
+    }
+
+    /// Creates a semaphore, which can be exported as sync_file once signaled
+    pub(super) fn create_exportable_semaphore(&self) -> Result<Option<vk::Semaphore>, VulkanError> {
+        if self.device.external_semaphore_fd.is_none() {
+            return Ok(None);
+        }
+        let mut export_info = vk::ExportSemaphoreCreateInfo::default()
+            .handle_types(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD);
+        let create_info = vk::SemaphoreCreateInfo::default().push_next(&mut export_info);
+        let semaphore = unsafe { self.device.handle.create_semaphore(&create_info, None) }?;
+        Ok(Some(semaphore))
+    }
+
+    /// Exports the payload of a semaphore signaled by a submission as sync_file
+    pub(super) fn export_sync_file(&self, semaphore: vk::Semaphore) -> Option<OwnedFd> {
+        let external_semaphore_fd = self.device.external_semaphore_fd.as_ref()?;
+        let info = vk::SemaphoreGetFdInfoKHR::default()
+            .semaphore(semaphore)
+            .handle_type(vk::ExternalSemaphoreHandleTypeFlags::SYNC_FD);
+        match unsafe { external_semaphore_fd.get_semaphore_fd(&info) } {
+            // -1 is returned if the semaphore was already signaled
+            Ok(fd) if fd >= 0 => Some(unsafe { OwnedFd::from_raw_fd(fd) }),
+            Ok(_) => None,
+            Err(err) => {
+                tracing::warn!(?err, "Failed to export sync_file");
+                None
+            }
+        }
+    }
+}
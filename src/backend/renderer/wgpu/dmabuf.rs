@@ -0,0 +1,301 @@
+//! Import of dmabufs through the vulkan backend of wgpu
+
+use std::{
+    ffi::CStr,
+    fmt,
+    os::unix::io::{AsFd, AsRawFd, IntoRawFd},
+};
+
+use ash::{ext, khr, vk};
+use wgpu_hal as hal;
+
+use crate::backend::allocator::{dmabuf::Dmabuf, format::FormatSet, Buffer, Format as DrmFormat};
+
+use super::{
+    format::{fourcc_to_wgpu, wgpu_to_vk, SUPPORTED_FORMATS},
+    WgpuError, WgpuRenderer, WgpuTexture,
+};
+
+const REQUIRED_EXTENSIONS: &[&CStr] = &[
+    khr::external_memory_fd::NAME,
+    ext::external_memory_dma_buf::NAME,
+    ext::image_drm_format_modifier::NAME,
+];
+
+/// State required to import dmabufs, only available for devices using the vulkan backend
+pub(super) struct VulkanData {
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    external_memory_fd: khr::external_memory_fd::Device,
+    /// Formats that can be sampled from
+    pub(super) texture_formats: FormatSet,
+    /// Formats that can be rendered to
+    pub(super) render_formats: FormatSet,
+}
+
+impl fmt::Debug for VulkanData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VulkanData")
+            .field("texture_formats", &self.texture_formats)
+            .field("render_formats", &self.render_formats)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VulkanData {
+    /// Queries the supported formats of `device`, returns `None` if it cannot import dmabufs
+    pub(super) fn new(instance: &wgpu::Instance, device: &wgpu::Device) -> Option<VulkanData> {
+        // SAFETY: the raw handles are only used while `instance` and `device` are alive
+        let hal_instance = unsafe { instance.as_hal::<hal::api::Vulkan>() }?;
+        let hal_device = unsafe { device.as_hal::<hal::api::Vulkan>() }?;
+
+        let enabled = hal_device.enabled_device_extensions();
+        if !REQUIRED_EXTENSIONS
+            .iter()
+            .all(|required| enabled.contains(required))
+        {
+            return None;
+        }
+
+        let ash_instance = hal_instance.shared_instance().raw_instance();
+        let physical_device = hal_device.raw_physical_device();
+        let memory_properties =
+            unsafe { ash_instance.get_physical_device_memory_properties(physical_device) };
+        let external_memory_fd = khr::external_memory_fd::Device::new(ash_instance, hal_device.raw_device());
+
+        let mut texture_formats = Vec::new();
+        let mut render_formats = Vec::new();
+        for &code in SUPPORTED_FORMATS {
+            let Some(vk_format) = fourcc_to_wgpu(code).and_then(|(format, _)| wgpu_to_vk(format)) else {
+                continue;
+            };
+            for properties in unsafe { format_modifier_properties(ash_instance, physical_device, vk_format) }
+            {
+                let format = DrmFormat {
+                    code,
+                    modifier: properties.drm_format_modifier.into(),
+                };
+                let features = properties.drm_format_modifier_tiling_features;
+                if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+                    texture_formats.push(format);
+                }
+                if features.contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND) {
+                    render_formats.push(format);
+                }
+            }
+        }
+
+        Some(VulkanData {
+            memory_properties,
+            external_memory_fd,
+            texture_formats: texture_formats.into_iter().collect(),
+            render_formats: render_formats.into_iter().collect(),
+        })
+    }
+
+    fn find_memory_type(&self, type_filter: u32, properties: vk::MemoryPropertyFlags) -> Option<u32> {
+        (0..self.memory_properties.memory_type_count).find(|&i| {
+            (type_filter & (1 << i)) != 0
+                && self.memory_properties.memory_types[i as usize]
+                    .property_flags
+                    .contains(properties)
+        })
+    }
+}
+
+unsafe fn format_modifier_properties(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+) -> Vec<vk::DrmFormatModifierPropertiesEXT> {
+    let mut list = vk::DrmFormatModifierPropertiesListEXT::default();
+    let mut format_properties = vk::FormatProperties2::default().push_next(&mut list);
+    instance.get_physical_device_format_properties2(physical_device, format, &mut format_properties);
+    let count = list.drm_format_modifier_count as usize;
+
+    let mut data = Vec::with_capacity(count);
+    let mut list = vk::DrmFormatModifierPropertiesListEXT {
+        p_drm_format_modifier_properties: data.as_mut_ptr(),
+        drm_format_modifier_count: count as u32,
+        ..Default::default()
+    };
+    let mut format_properties = vk::FormatProperties2::default().push_next(&mut list);
+    instance.get_physical_device_format_properties2(physical_device, format, &mut format_properties);
+    // SAFETY: vulkan initialized the reported number of elements
+    data.set_len(list.drm_format_modifier_count as usize);
+    data
+}
+
+impl WgpuRenderer {
+    /// Imports `dmabuf` as vulkan image and wraps it as wgpu texture
+    ///
+    /// Textures imported as `render_target` can additionally be rendered to.
+    pub(super) fn import_dmabuf_texture(
+        &self,
+        dmabuf: &Dmabuf,
+        render_target: bool,
+    ) -> Result<WgpuTexture, WgpuError> {
+        let vulkan = self.vulkan.as_ref().ok_or(WgpuError::DmabufImportUnsupported)?;
+        let format = dmabuf.format();
+        let supported = if render_target {
+            &vulkan.render_formats
+        } else {
+            &vulkan.texture_formats
+        };
+        if !supported.contains(&format) {
+            return match fourcc_to_wgpu(format.code) {
+                Some(_) => Err(WgpuError::UnsupportedModifier(format.modifier)),
+                None => Err(WgpuError::UnsupportedPixelFormat(format.code)),
+            };
+        }
+        let (wgpu_format, has_alpha) =
+            fourcc_to_wgpu(format.code).ok_or(WgpuError::UnsupportedPixelFormat(format.code))?;
+        let vk_format = wgpu_to_vk(wgpu_format).ok_or(WgpuError::UnsupportedPixelFormat(format.code))?;
+
+        let (mut vk_usage, mut hal_usage, mut usage) = (
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+            wgpu::TextureUses::RESOURCE | wgpu::TextureUses::COPY_SRC,
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        );
+        if render_target {
+            vk_usage |= vk::ImageUsageFlags::COLOR_ATTACHMENT;
+            hal_usage |= wgpu::TextureUses::COLOR_TARGET;
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        // SAFETY: the raw device outlives the image, which is destroyed by the drop callback of the texture
+        let hal_device =
+            unsafe { self.device.as_hal::<hal::api::Vulkan>() }.ok_or(WgpuError::DmabufImportUnsupported)?;
+        let ash_device = hal_device.raw_device();
+
+        let size = dmabuf.size();
+        let plane_layouts = dmabuf
+            .offsets()
+            .zip(dmabuf.strides())
+            .map(|(offset, stride)| vk::SubresourceLayout {
+                offset: offset as u64,
+                size: 0,
+                row_pitch: stride as u64,
+                array_pitch: 0,
+                depth_pitch: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut external_memory_info = vk::ExternalMemoryImageCreateInfo::default()
+            .handle_types(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT);
+        let mut modifier_info = vk::ImageDrmFormatModifierExplicitCreateInfoEXT::default()
+            .drm_format_modifier(format.modifier.into())
+            .plane_layouts(&plane_layouts);
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D {
+                width: size.w as u32,
+                height: size.h as u32,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::DRM_FORMAT_MODIFIER_EXT)
+            .usage(vk_usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .push_next(&mut external_memory_info)
+            .push_next(&mut modifier_info);
+
+        let image = unsafe { ash_device.create_image(&image_info, None) }.map_err(WgpuError::DmabufImport)?;
+        let destroy_image =
+            scopeguard::guard(image, |image| unsafe { ash_device.destroy_image(image, None) });
+
+        // Vulkan takes ownership of the fd on a successful import, so pass a duplicate
+        let fd = dmabuf
+            .handles()
+            .next()
+            .expect("dmabufs have at least one plane")
+            .as_fd()
+            .try_clone_to_owned()
+            .map_err(WgpuError::DuplicateFd)?;
+        let mut fd_properties = vk::MemoryFdPropertiesKHR::default();
+        unsafe {
+            vulkan.external_memory_fd.get_memory_fd_properties(
+                vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT,
+                fd.as_raw_fd(),
+                &mut fd_properties,
+            )
+        }
+        .map_err(WgpuError::DmabufImport)?;
+
+        let requirements = unsafe { ash_device.get_image_memory_requirements(image) };
+        let type_filter = requirements.memory_type_bits & fd_properties.memory_type_bits;
+        let memory_type_index = vulkan
+            .find_memory_type(type_filter, vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .or_else(|| vulkan.find_memory_type(type_filter, vk::MemoryPropertyFlags::empty()))
+            .ok_or(WgpuError::DmabufImport(vk::Result::ERROR_INVALID_EXTERNAL_HANDLE))?;
+
+        let mut import_info = vk::ImportMemoryFdInfoKHR::default()
+            .handle_type(vk::ExternalMemoryHandleTypeFlags::DMA_BUF_EXT)
+            .fd(fd.as_raw_fd());
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default().image(image);
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut import_info)
+            .push_next(&mut dedicated_info);
+        let memory =
+            unsafe { ash_device.allocate_memory(&allocate_info, None) }.map_err(WgpuError::DmabufImport)?;
+        let _ = fd.into_raw_fd();
+        let free_memory = scopeguard::guard(memory, |memory| unsafe { ash_device.free_memory(memory, None) });
+
+        unsafe { ash_device.bind_image_memory(image, memory, 0) }.map_err(WgpuError::DmabufImport)?;
+
+        let extent = wgpu::Extent3d {
+            width: size.w as u32,
+            height: size.h as u32,
+            depth_or_array_layers: 1,
+        };
+        let image = scopeguard::ScopeGuard::into_inner(destroy_image);
+        let memory = scopeguard::ScopeGuard::into_inner(free_memory);
+        let drop_device = ash_device.clone();
+        let drop_callback = Box::new(move || unsafe {
+            drop_device.destroy_image(image, None);
+            drop_device.free_memory(memory, None);
+        });
+
+        let texture = unsafe {
+            let hal_texture = hal_device.texture_from_raw(
+                image,
+                &hal::TextureDescriptor {
+                    label: Some("smithay_wgpu_dmabuf"),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu_format,
+                    usage: hal_usage,
+                    memory_flags: hal::MemoryFlags::empty(),
+                    view_formats: vec![wgpu_format],
+                },
+                Some(drop_callback),
+            );
+            self.device.create_texture_from_hal::<hal::api::Vulkan>(
+                hal_texture,
+                &wgpu::TextureDescriptor {
+                    label: Some("smithay_wgpu_dmabuf"),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu_format,
+                    usage,
+                    view_formats: &[],
+                },
+            )
+        };
+
+        Ok(WgpuTexture::from_parts(
+            texture,
+            Some(format.code),
+            has_alpha,
+            false,
+        ))
+    }
+}
@@ -0,0 +1,76 @@
+use drm_fourcc::{DrmFourcc, DrmModifier};
+use thiserror::Error;
+
+use crate::{
+    backend::SwapBuffersError,
+    utils::{Buffer, Rectangle},
+};
+
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::wl_shm;
+
+/// Error returned during rendering using wgpu
+#[derive(Debug, Error)]
+pub enum WgpuError {
+    /// The given buffer has an unsupported pixel format
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedPixelFormat(DrmFourcc),
+    /// The given buffer has an unsupported modifier
+    #[error("Unsupported modifier: {0:?}")]
+    UnsupportedModifier(DrmModifier),
+    /// The given wl buffer has an unsupported pixel format
+    #[error("Unsupported wl_shm format: {0:?}")]
+    #[cfg(feature = "wayland_frontend")]
+    UnsupportedWlPixelFormat(wl_shm::Format),
+    /// The given buffer is incomplete
+    #[error("Incomplete buffer {expected} < {actual}")]
+    IncompleteBuffer {
+        /// Expected len of the buffer
+        expected: usize,
+        /// Actual len of the buffer
+        actual: usize,
+    },
+    /// The given wl buffer was not accessible
+    #[error("Error accessing the buffer ({0:?})")]
+    #[cfg(feature = "wayland_frontend")]
+    BufferAccessError(#[from] crate::wayland::shm::BufferAccessError),
+    /// The texture cannot be used for the requested operation
+    ///
+    /// Textures need to be created with `RENDER_ATTACHMENT` usage to be bound
+    /// and with `COPY_SRC` usage to be read back.
+    #[error("The texture was not created with the required usage {0:?}")]
+    MissingTextureUsage(wgpu::TextureUsages),
+    /// The requested region is not contained in the texture
+    #[error("The region {0:?} is out of bounds of the texture")]
+    RegionOutOfBounds(Rectangle<i32, Buffer>),
+    /// The wgpu device does not use the vulkan backend or lacks the extensions to import dmabufs
+    #[error("Dmabuf import is not supported by the wgpu device")]
+    DmabufImportUnsupported,
+    /// A vulkan call required to import a dmabuf failed
+    #[error("Importing the dmabuf failed: {0}")]
+    DmabufImport(#[source] ash::vk::Result),
+    /// Duplicating the file descriptor of a dmabuf failed
+    #[error("Failed to duplicate the dmabuf file descriptor: {0}")]
+    DuplicateFd(#[source] std::io::Error),
+    /// Reading back a buffer from the gpu failed
+    #[error("Mapping the buffer failed: {0}")]
+    Map(#[from] wgpu::BufferAsyncError),
+    /// Waiting for the device failed
+    #[error("Polling the device failed: {0}")]
+    Poll(#[from] wgpu::PollError),
+    /// Blocking for a synchronization primitive failed
+    #[error("Blocking for a synchronization primitive got interrupted")]
+    SyncInterrupted,
+}
+
+impl From<WgpuError> for SwapBuffersError {
+    #[inline]
+    fn from(value: WgpuError) -> Self {
+        match value {
+            x @ WgpuError::SyncInterrupted | x @ WgpuError::Poll(_) => {
+                SwapBuffersError::TemporaryFailure(Box::new(x))
+            }
+            x => SwapBuffersError::ContextLost(Box::new(x)),
+        }
+    }
+}
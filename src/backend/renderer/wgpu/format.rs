@@ -0,0 +1,60 @@
+//! Conversions between fourcc and wgpu texture formats
+
+use drm_fourcc::DrmFourcc;
+
+/// Formats supported for memory imports, offscreen buffers and dmabufs
+pub(super) const SUPPORTED_FORMATS: &[DrmFourcc] = &[
+    DrmFourcc::Argb8888,
+    DrmFourcc::Xrgb8888,
+    DrmFourcc::Abgr8888,
+    DrmFourcc::Xbgr8888,
+    DrmFourcc::Abgr2101010,
+    DrmFourcc::Xbgr2101010,
+    DrmFourcc::Abgr16161616f,
+    DrmFourcc::Xbgr16161616f,
+];
+
+/// Returns the wgpu format matching the memory layout of `fourcc` and whether the alpha channel is used
+pub(super) const fn fourcc_to_wgpu(fourcc: DrmFourcc) -> Option<(wgpu::TextureFormat, bool)> {
+    Some(match fourcc {
+        DrmFourcc::Argb8888 => (wgpu::TextureFormat::Bgra8Unorm, true),
+        DrmFourcc::Xrgb8888 => (wgpu::TextureFormat::Bgra8Unorm, false),
+        DrmFourcc::Abgr8888 => (wgpu::TextureFormat::Rgba8Unorm, true),
+        DrmFourcc::Xbgr8888 => (wgpu::TextureFormat::Rgba8Unorm, false),
+        DrmFourcc::Abgr2101010 => (wgpu::TextureFormat::Rgb10a2Unorm, true),
+        DrmFourcc::Xbgr2101010 => (wgpu::TextureFormat::Rgb10a2Unorm, false),
+        DrmFourcc::Abgr16161616f => (wgpu::TextureFormat::Rgba16Float, true),
+        DrmFourcc::Xbgr16161616f => (wgpu::TextureFormat::Rgba16Float, false),
+        _ => return None,
+    })
+}
+
+/// Returns the fourcc with alpha channel matching the memory layout of `format`
+pub(super) const fn wgpu_to_fourcc(format: wgpu::TextureFormat) -> Option<DrmFourcc> {
+    Some(match format {
+        wgpu::TextureFormat::Bgra8Unorm => DrmFourcc::Argb8888,
+        wgpu::TextureFormat::Rgba8Unorm => DrmFourcc::Abgr8888,
+        wgpu::TextureFormat::Rgb10a2Unorm => DrmFourcc::Abgr2101010,
+        wgpu::TextureFormat::Rgba16Float => DrmFourcc::Abgr16161616f,
+        _ => return None,
+    })
+}
+
+/// Returns the vulkan format wgpu uses for `format`
+pub(super) const fn wgpu_to_vk(format: wgpu::TextureFormat) -> Option<ash::vk::Format> {
+    Some(match format {
+        wgpu::TextureFormat::Bgra8Unorm => ash::vk::Format::B8G8R8A8_UNORM,
+        wgpu::TextureFormat::Rgba8Unorm => ash::vk::Format::R8G8B8A8_UNORM,
+        wgpu::TextureFormat::Rgb10a2Unorm => ash::vk::Format::A2B10G10R10_UNORM_PACK32,
+        wgpu::TextureFormat::Rgba16Float => ash::vk::Format::R16G16B16A16_SFLOAT,
+        _ => return None,
+    })
+}
+
+/// Bytes per pixel of the supported formats
+pub(super) const fn bytes_per_pixel(format: wgpu::TextureFormat) -> u32 {
+    match format {
+        wgpu::TextureFormat::Rgba16Float => 8,
+        _ => 4,
+    }
+}
@@ -0,0 +1,1497 @@
+//! Implementation of the rendering traits using wgpu
+//!
+//! The [`WgpuRenderer`] draws with a [`wgpu::Device`] created by the compositor, so it works with
+//! any adapter wgpu supports, including software adapters like llvmpipe, which can be requested
+//! through [`wgpu::RequestAdapterOptions::force_fallback_adapter`].
+//!
+//! Importing and binding dmabufs requires the device to use the vulkan backend with the
+//! `VK_EXT_image_drm_format_modifier` and `VK_EXT_external_memory_dma_buf` extensions enabled.
+//! Memory imports, offscreen textures and read-back work on every backend.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    marker::PhantomData,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use cgmath::{Matrix3, SquareMatrix, Vector2};
+use drm_fourcc::DrmFourcc;
+use tracing::warn;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    backend::allocator::{
+        dmabuf::{Dmabuf, WeakDmabuf},
+        format::FormatSet,
+    },
+    utils::{Buffer as BufferCoords, Physical, Rectangle, Size, Transform},
+};
+
+#[cfg(feature = "wayland_frontend")]
+use crate::{
+    backend::renderer::{ImportDmaWl, ImportMemWl},
+    wayland::{compositor::SurfaceData, shm},
+};
+#[cfg(feature = "wayland_frontend")]
+use std::sync::Mutex;
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::wl_buffer;
+
+use super::{
+    sync::{Fence, Interrupted, SyncPoint},
+    Bind, Color32F, ContextId, DebugFlags, ExportMem, Frame, ImportDma, ImportMem, Offscreen, Renderer,
+    RendererSuper, Texture, TextureFilter, TextureMapping,
+};
+
+mod dmabuf;
+mod error;
+mod format;
+
+pub use error::*;
+
+use dmabuf::VulkanData;
+use format::{bytes_per_pixel, fourcc_to_wgpu, wgpu_to_fourcc, SUPPORTED_FORMATS};
+
+const FLAG_NO_ALPHA: u32 = 1;
+const FLAG_TINT: u32 = 2;
+
+/// Uniforms of a single draw, matches `DrawUniforms` in `shader.wgsl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawUniforms {
+    matrix: [[f32; 4]; 3],
+    tex_matrix: [[f32; 4]; 3],
+    color: [f32; 4],
+    alpha: f32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+/// Columns of `matrix` padded to the uniform layout of `mat3x3<f32>`
+fn mat3_uniform(matrix: Matrix3<f32>) -> [[f32; 4]; 3] {
+    [
+        [matrix.x.x, matrix.x.y, matrix.x.z, 0.0],
+        [matrix.y.x, matrix.y.y, matrix.y.z, 0.0],
+        [matrix.z.x, matrix.z.y, matrix.z.z, 0.0],
+    ]
+}
+
+/// A handle to a wgpu texture
+#[derive(Debug, Clone)]
+pub struct WgpuTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    format: Option<DrmFourcc>,
+    has_alpha: bool,
+    flipped: bool,
+}
+
+impl WgpuTexture {
+    /// Wraps an existing wgpu texture
+    ///
+    /// The texture needs `TEXTURE_BINDING` usage to be rendered, `RENDER_ATTACHMENT` usage to be bound
+    /// as framebuffer and `COPY_SRC` usage to be read back.
+    pub fn new(texture: wgpu::Texture) -> WgpuTexture {
+        let format = wgpu_to_fourcc(texture.format());
+        WgpuTexture::from_parts(texture, format, true, false)
+    }
+
+    fn from_parts(texture: wgpu::Texture, format: Option<DrmFourcc>, has_alpha: bool, flipped: bool) -> Self {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        WgpuTexture {
+            texture,
+            view,
+            format,
+            has_alpha,
+            flipped,
+        }
+    }
+
+    /// Get a reference to the underlying wgpu texture
+    pub fn wgpu_texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+}
+
+impl Texture for WgpuTexture {
+    fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    fn format(&self) -> Option<DrmFourcc> {
+        self.format
+    }
+}
+
+/// A framebuffer of a [`WgpuRenderer`]
+#[derive(Debug)]
+pub struct WgpuTarget<'a> {
+    texture: WgpuTexture,
+    _buffer: PhantomData<&'a mut ()>,
+}
+
+impl WgpuTarget<'_> {
+    /// Get a reference to the underlying wgpu texture
+    pub fn wgpu_texture(&self) -> &wgpu::Texture {
+        &self.texture.texture
+    }
+}
+
+impl Texture for WgpuTarget<'_> {
+    fn width(&self) -> u32 {
+        self.texture.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.texture.height()
+    }
+
+    fn format(&self) -> Option<DrmFourcc> {
+        self.texture.format()
+    }
+}
+
+#[derive(Debug)]
+enum DrawKind {
+    /// Replaces the contents of the target
+    Clear,
+    Solid,
+    Texture(WgpuTexture),
+}
+
+#[derive(Debug)]
+struct Draw {
+    kind: DrawKind,
+    uniform_offset: u32,
+    instances: Range<u32>,
+}
+
+/// Render pipelines for one target format
+#[derive(Debug)]
+struct Pipelines {
+    clear: wgpu::RenderPipeline,
+    solid: wgpu::RenderPipeline,
+    texture: wgpu::RenderPipeline,
+}
+
+/// Fence signaled once a submission to the queue finished executing
+#[derive(Debug)]
+struct WgpuFence {
+    device: Arc<wgpu::Device>,
+    submission: wgpu::SubmissionIndex,
+    signaled: Arc<AtomicBool>,
+}
+
+impl Fence for WgpuFence {
+    fn is_signaled(&self) -> bool {
+        if !self.signaled.load(Ordering::Acquire) {
+            // runs the callback registered in `WgpuFrame::finish_internal` if the work is done
+            let _ = self.device.poll(wgpu::PollType::Poll);
+        }
+        self.signaled.load(Ordering::Acquire)
+    }
+
+    fn wait(&self) -> Result<(), Interrupted> {
+        self.device
+            .poll(wgpu::PollType::Wait {
+                submission_index: Some(self.submission.clone()),
+                timeout: None,
+            })
+            .map(|_| ())
+            .map_err(|_| Interrupted)
+    }
+
+    fn is_exportable(&self) -> bool {
+        false
+    }
+
+    fn export(&self) -> Option<std::os::unix::io::OwnedFd> {
+        None
+    }
+}
+
+/// A frame of the [`WgpuRenderer`]
+///
+/// Draw calls are recorded and submitted as a single render pass when the frame is finished.
+pub struct WgpuFrame<'frame, 'buffer> {
+    renderer: &'frame mut WgpuRenderer,
+    target: &'frame mut WgpuTarget<'buffer>,
+    projection: Matrix3<f32>,
+    transform: Transform,
+    draws: Vec<Draw>,
+    uniforms: Vec<u8>,
+    instances: Vec<[f32; 4]>,
+    finished: bool,
+}
+
+impl fmt::Debug for WgpuFrame<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WgpuFrame")
+            .field("renderer", &self.renderer)
+            .field("target", &self.target)
+            .field("transform", &self.transform)
+            .field("draws", &self.draws)
+            .field("finished", &self.finished)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WgpuFrame<'_, '_> {
+    fn push_draw(
+        &mut self,
+        kind: DrawKind,
+        uniforms: DrawUniforms,
+        instances: impl IntoIterator<Item = [f32; 4]>,
+    ) {
+        let start = self.instances.len() as u32;
+        self.instances.extend(instances);
+        let end = self.instances.len() as u32;
+        if start == end {
+            return;
+        }
+
+        let uniform_offset = self.uniforms.len() as u32;
+        self.uniforms.extend_from_slice(bytemuck::bytes_of(&uniforms));
+        let aligned_len = self
+            .uniforms
+            .len()
+            .next_multiple_of(self.renderer.uniform_alignment);
+        self.uniforms.resize(aligned_len, 0);
+
+        self.draws.push(Draw {
+            kind,
+            uniform_offset,
+            instances: start..end,
+        });
+    }
+
+    #[profiling::function]
+    fn finish_internal(&mut self) -> Result<SyncPoint, WgpuError> {
+        if std::mem::replace(&mut self.finished, true) || self.draws.is_empty() {
+            return Ok(SyncPoint::signaled());
+        }
+
+        let renderer = &*self.renderer;
+        let device = &renderer.device;
+        let target = &self.target.texture;
+        let pipelines = &renderer.pipelines[&target.texture.format()];
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("smithay_wgpu_uniforms"),
+            contents: &self.uniforms,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("smithay_wgpu_instances"),
+            contents: bytemuck::cast_slice(&self.instances),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let uniform_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("smithay_wgpu_uniforms"),
+            layout: &renderer.uniform_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &uniform_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(size_of::<DrawUniforms>() as u64),
+                }),
+            }],
+        });
+        let texture_groups = self
+            .draws
+            .iter()
+            .map(|draw| match &draw.kind {
+                DrawKind::Texture(texture) => Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("smithay_wgpu_texture"),
+                    layout: &renderer.texture_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&texture.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&renderer.sampler),
+                        },
+                    ],
+                })),
+                DrawKind::Clear | DrawKind::Solid => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("smithay_wgpu_frame"),
+        });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("smithay_wgpu_frame"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    depth_slice: None,
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_vertex_buffer(0, instance_buffer.slice(..));
+            for (draw, texture_group) in self.draws.iter().zip(&texture_groups) {
+                pass.set_pipeline(match draw.kind {
+                    DrawKind::Clear => &pipelines.clear,
+                    DrawKind::Solid => &pipelines.solid,
+                    DrawKind::Texture(_) => &pipelines.texture,
+                });
+                pass.set_bind_group(0, &uniform_group, &[draw.uniform_offset]);
+                if let Some(texture_group) = texture_group {
+                    pass.set_bind_group(1, texture_group, &[]);
+                }
+                pass.draw(0..4, draw.instances.clone());
+            }
+        }
+
+        let submission = renderer.queue.submit(Some(encoder.finish()));
+        let signaled = Arc::new(AtomicBool::new(false));
+        renderer.queue.on_submitted_work_done({
+            let signaled = signaled.clone();
+            move || signaled.store(true, Ordering::Release)
+        });
+
+        Ok(SyncPoint::from(WgpuFence {
+            device: renderer.device.clone(),
+            submission,
+            signaled,
+        }))
+    }
+}
+
+/// Clamps `damage`, which is relative to a destination of `dest_size`, to the destination
+fn constrained_damage(
+    dest_size: Size<i32, Physical>,
+    damage: &[Rectangle<i32, Physical>],
+) -> impl Iterator<Item = Rectangle<i32, Physical>> + '_ {
+    damage.iter().map(move |rect| {
+        let rect_constrained_loc = rect.loc.constrain(Rectangle::from_size(dest_size));
+        let rect_clamped_size = rect
+            .size
+            .clamp((0, 0), (dest_size.to_point() - rect_constrained_loc).to_size());
+        Rectangle::new(rect_constrained_loc, rect_clamped_size)
+    })
+}
+
+fn instance(rect: Rectangle<i32, Physical>) -> [f32; 4] {
+    [
+        rect.loc.x as f32,
+        rect.loc.y as f32,
+        rect.size.w as f32,
+        rect.size.h as f32,
+    ]
+}
+
+impl Frame for WgpuFrame<'_, '_> {
+    type Error = WgpuError;
+    type TextureId = WgpuTexture;
+
+    fn context_id(&self) -> ContextId<WgpuTexture> {
+        self.renderer.context_id.clone()
+    }
+
+    #[profiling::function]
+    fn clear(&mut self, color: Color32F, at: &[Rectangle<i32, Physical>]) -> Result<(), Self::Error> {
+        let uniforms = DrawUniforms {
+            matrix: mat3_uniform(self.projection),
+            color: color.components(),
+            ..bytemuck::Zeroable::zeroed()
+        };
+        self.push_draw(DrawKind::Clear, uniforms, at.iter().copied().map(instance));
+        Ok(())
+    }
+
+    #[profiling::function]
+    fn draw_solid(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: Color32F,
+    ) -> Result<(), Self::Error> {
+        let uniforms = DrawUniforms {
+            matrix: mat3_uniform(self.projection),
+            color: color.components(),
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let instances = constrained_damage(dst.size, damage)
+            .map(|rect| instance(Rectangle::new(dst.loc + rect.loc, rect.size)));
+        self.push_draw(DrawKind::Solid, uniforms, instances);
+        Ok(())
+    }
+
+    #[profiling::function]
+    fn render_texture_from_to(
+        &mut self,
+        texture: &WgpuTexture,
+        src: Rectangle<f64, BufferCoords>,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        _opaque_regions: &[Rectangle<i32, Physical>],
+        src_transform: Transform,
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let tex_size = texture.size();
+        if src.size.is_empty() || tex_size.is_empty() || dst.size.is_empty() {
+            return Ok(());
+        }
+
+        let matrix =
+            self.projection * Matrix3::from_translation(Vector2::new(dst.loc.x as f32, dst.loc.y as f32));
+        let mut tex_matrix = build_texture_mat(src, dst, tex_size, src_transform);
+        if texture.flipped {
+            tex_matrix = Matrix3::from_translation(Vector2::new(0.0, 1.0))
+                * Matrix3::from_nonuniform_scale(1.0, -1.0)
+                * tex_matrix;
+        }
+
+        let mut flags = 0;
+        if !texture.has_alpha {
+            flags |= FLAG_NO_ALPHA;
+        }
+        if self.renderer.debug_flags.contains(DebugFlags::TINT) {
+            flags |= FLAG_TINT;
+        }
+
+        let uniforms = DrawUniforms {
+            matrix: mat3_uniform(matrix),
+            tex_matrix: mat3_uniform(tex_matrix),
+            alpha,
+            flags,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        let instances = constrained_damage(dst.size, damage).map(instance);
+        self.push_draw(DrawKind::Texture(texture.clone()), uniforms, instances);
+        Ok(())
+    }
+
+    fn transformation(&self) -> Transform {
+        self.transform
+    }
+
+    fn wait(&mut self, sync: &SyncPoint) -> Result<(), Self::Error> {
+        sync.wait().map_err(|_| WgpuError::SyncInterrupted)
+    }
+
+    #[profiling::function]
+    fn finish(mut self) -> Result<SyncPoint, Self::Error> {
+        self.finish_internal()
+    }
+}
+
+impl Drop for WgpuFrame<'_, '_> {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish_internal() {
+            warn!("Ignored error finishing WgpuFrame on drop: {}", err);
+        }
+    }
+}
+
+/// A renderer using wgpu
+#[derive(Debug)]
+pub struct WgpuRenderer {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    shader: wgpu::ShaderModule,
+    uniform_layout: wgpu::BindGroupLayout,
+    texture_layout: wgpu::BindGroupLayout,
+    solid_pipeline_layout: wgpu::PipelineLayout,
+    texture_pipeline_layout: wgpu::PipelineLayout,
+    pipelines: HashMap<wgpu::TextureFormat, Pipelines>,
+    uniform_alignment: usize,
+
+    sampler: wgpu::Sampler,
+    downscale_filter: TextureFilter,
+    upscale_filter: TextureFilter,
+    debug_flags: DebugFlags,
+
+    vulkan: Option<VulkanData>,
+    context_id: ContextId<WgpuTexture>,
+
+    // caches
+    buffers: HashMap<WeakDmabuf, WgpuTexture>,
+    dmabuf_cache: HashMap<WeakDmabuf, WgpuTexture>,
+}
+
+impl WgpuRenderer {
+    /// Create a new wgpu renderer from an existing device and queue
+    ///
+    /// `instance` has to be the instance `device` was created from.
+    pub fn new(instance: &wgpu::Instance, device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("smithay_wgpu_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+        });
+
+        let uniform_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("smithay_wgpu_uniforms"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(size_of::<DrawUniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("smithay_wgpu_texture"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let solid_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("smithay_wgpu_solid"),
+            bind_group_layouts: &[&uniform_layout],
+            push_constant_ranges: &[],
+        });
+        let texture_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("smithay_wgpu_texture"),
+            bind_group_layouts: &[&uniform_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        let uniform_alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let sampler = create_sampler(&device, TextureFilter::Linear, TextureFilter::Linear);
+        let vulkan = VulkanData::new(instance, &device);
+
+        WgpuRenderer {
+            device,
+            queue,
+            shader,
+            uniform_layout,
+            texture_layout,
+            solid_pipeline_layout,
+            texture_pipeline_layout,
+            pipelines: HashMap::new(),
+            uniform_alignment,
+
+            sampler,
+            downscale_filter: TextureFilter::Linear,
+            upscale_filter: TextureFilter::Linear,
+            debug_flags: DebugFlags::empty(),
+
+            vulkan,
+            context_id: ContextId::new(),
+
+            buffers: HashMap::new(),
+            dmabuf_cache: HashMap::new(),
+        }
+    }
+
+    /// Get the wgpu device
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    /// Get the wgpu queue
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    fn create_pipelines(&self, format: wgpu::TextureFormat) -> Pipelines {
+        let pipeline = |label: &str,
+                        layout: &wgpu::PipelineLayout,
+                        entry_point: &str,
+                        blend: Option<wgpu::BlendState>| {
+            self.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(layout),
+                    vertex: wgpu::VertexState {
+                        module: &self.shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[wgpu::VertexBufferLayout {
+                            array_stride: size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &wgpu::vertex_attr_array![0 => Float32x4],
+                        }],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &self.shader,
+                        entry_point: Some(entry_point),
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleStrip,
+                        ..Default::default()
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                    cache: None,
+                })
+        };
+
+        let blend = Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING);
+        Pipelines {
+            clear: pipeline(
+                "smithay_wgpu_clear",
+                &self.solid_pipeline_layout,
+                "fs_solid",
+                None,
+            ),
+            solid: pipeline(
+                "smithay_wgpu_solid",
+                &self.solid_pipeline_layout,
+                "fs_solid",
+                blend,
+            ),
+            texture: pipeline(
+                "smithay_wgpu_texture",
+                &self.texture_pipeline_layout,
+                "fs_texture",
+                blend,
+            ),
+        }
+    }
+
+    /// Copies `data`, laid out with `stride` bytes per row, into `region` of `texture`
+    fn upload(
+        &self,
+        texture: &wgpu::Texture,
+        data: &[u8],
+        stride: u32,
+        region: Rectangle<i32, BufferCoords>,
+    ) {
+        let bpp = bytes_per_pixel(texture.format()) as u64;
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.loc.x as u32,
+                    y: region.loc.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::TexelCopyBufferLayout {
+                offset: region.loc.y as u64 * stride as u64 + region.loc.x as u64 * bpp,
+                bytes_per_row: Some(stride),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: region.size.w as u32,
+                height: region.size.h as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    #[profiling::function]
+    fn copy(
+        &mut self,
+        texture: &WgpuTexture,
+        region: Rectangle<i32, BufferCoords>,
+        format: DrmFourcc,
+    ) -> Result<WgpuMapping, WgpuError> {
+        if !texture.texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(WgpuError::MissingTextureUsage(wgpu::TextureUsages::COPY_SRC));
+        }
+        if region.is_empty() || !Rectangle::from_size(texture.size()).contains_rect(region) {
+            return Err(WgpuError::RegionOutOfBounds(region));
+        }
+        let wgpu_format = texture.texture.format();
+        if fourcc_to_wgpu(format).map(|(format, _)| format) != Some(wgpu_format) {
+            return Err(WgpuError::UnsupportedPixelFormat(format));
+        }
+
+        let bytes_per_row = (region.size.w as u32 * bytes_per_pixel(wgpu_format))
+            .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("smithay_wgpu_mapping"),
+            size: bytes_per_row as u64 * region.size.h as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("smithay_wgpu_copy"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.loc.x as u32,
+                    y: region.loc.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(region.size.h as u32),
+                },
+            },
+            wgpu::Extent3d {
+                width: region.size.w as u32,
+                height: region.size.h as u32,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        Ok(WgpuMapping {
+            buffer,
+            size: region.size,
+            format,
+            bytes_per_row,
+            data: OnceLock::new(),
+        })
+    }
+
+    fn cleanup(&mut self) {
+        self.dmabuf_cache.retain(|dmabuf, _| !dmabuf.is_gone());
+        self.buffers.retain(|dmabuf, _| !dmabuf.is_gone());
+    }
+}
+
+/// Maps physical coordinates of an output of `output_size` to normalized device coordinates
+fn projection(mut output_size: Size<i32, Physical>, transform: Transform) -> Matrix3<f32> {
+    // Handle the width/height swap when the output is rotated by 90°/270°.
+    if let Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 = transform {
+        std::mem::swap(&mut output_size.w, &mut output_size.h);
+    }
+
+    // Same projection as the gles renderer, but the first row of the target is at the top
+    // in wgpu, so the flip for the coordinate system of OpenGL is not needed.
+    let mut renderer = Matrix3::<f32>::identity();
+    let x = 2.0 / (output_size.w as f32);
+    let y = 2.0 / (output_size.h as f32);
+
+    // Scale
+    renderer[0][0] = x;
+    renderer[1][1] = -y;
+
+    // Translation
+    renderer[2][0] = -1.0;
+    renderer[2][1] = 1.0;
+
+    transform.matrix() * renderer
+}
+
+fn create_sampler(device: &wgpu::Device, upscale: TextureFilter, downscale: TextureFilter) -> wgpu::Sampler {
+    let filter_mode = |filter| match filter {
+        TextureFilter::Linear => wgpu::FilterMode::Linear,
+        TextureFilter::Nearest => wgpu::FilterMode::Nearest,
+    };
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("smithay_wgpu_sampler"),
+        mag_filter: filter_mode(upscale),
+        min_filter: filter_mode(downscale),
+        ..Default::default()
+    })
+}
+
+impl RendererSuper for WgpuRenderer {
+    type Error = WgpuError;
+    type TextureId = WgpuTexture;
+    type Framebuffer<'buffer> = WgpuTarget<'buffer>;
+    type Frame<'frame, 'buffer>
+        = WgpuFrame<'frame, 'buffer>
+    where
+        'buffer: 'frame,
+        Self: 'frame;
+}
+
+impl Renderer for WgpuRenderer {
+    fn context_id(&self) -> ContextId<WgpuTexture> {
+        self.context_id.clone()
+    }
+
+    fn downscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.downscale_filter = filter;
+        self.sampler = create_sampler(&self.device, self.upscale_filter, self.downscale_filter);
+        Ok(())
+    }
+
+    fn upscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.upscale_filter = filter;
+        self.sampler = create_sampler(&self.device, self.upscale_filter, self.downscale_filter);
+        Ok(())
+    }
+
+    fn set_debug_flags(&mut self, flags: DebugFlags) {
+        self.debug_flags = flags;
+    }
+
+    fn debug_flags(&self) -> DebugFlags {
+        self.debug_flags
+    }
+
+    #[profiling::function]
+    fn render<'frame, 'buffer>(
+        &'frame mut self,
+        target: &'frame mut WgpuTarget<'buffer>,
+        output_size: Size<i32, Physical>,
+        transform: Transform,
+    ) -> Result<WgpuFrame<'frame, 'buffer>, Self::Error>
+    where
+        'buffer: 'frame,
+    {
+        let format = target.texture.texture.format();
+        if !self.pipelines.contains_key(&format) {
+            let pipelines = self.create_pipelines(format);
+            self.pipelines.insert(format, pipelines);
+        }
+
+        let projection = projection(output_size, transform);
+
+        Ok(WgpuFrame {
+            renderer: self,
+            target,
+            projection,
+            transform,
+            draws: Vec::new(),
+            uniforms: Vec::new(),
+            instances: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn wait(&mut self, sync: &SyncPoint) -> Result<(), Self::Error> {
+        sync.wait().map_err(|_| WgpuError::SyncInterrupted)
+    }
+
+    fn cleanup_texture_cache(&mut self) -> Result<(), Self::Error> {
+        self.cleanup();
+        self.device.poll(wgpu::PollType::Poll)?;
+        Ok(())
+    }
+}
+
+impl ImportMem for WgpuRenderer {
+    #[profiling::function]
+    fn import_memory(
+        &mut self,
+        data: &[u8],
+        format: DrmFourcc,
+        size: Size<i32, BufferCoords>,
+        flipped: bool,
+    ) -> Result<WgpuTexture, WgpuError> {
+        let (wgpu_format, has_alpha) =
+            fourcc_to_wgpu(format).ok_or(WgpuError::UnsupportedPixelFormat(format))?;
+        let stride = size.w as u32 * bytes_per_pixel(wgpu_format);
+        let expected_len = stride as usize * size.h as usize;
+        if data.len() < expected_len {
+            return Err(WgpuError::IncompleteBuffer {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("smithay_wgpu_memory"),
+            size: wgpu::Extent3d {
+                width: size.w as u32,
+                height: size.h as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu_format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        self.upload(&texture, data, stride, Rectangle::from_size(size));
+
+        Ok(WgpuTexture::from_parts(texture, Some(format), has_alpha, flipped))
+    }
+
+    #[profiling::function]
+    fn update_memory(
+        &mut self,
+        texture: &WgpuTexture,
+        data: &[u8],
+        region: Rectangle<i32, BufferCoords>,
+    ) -> Result<(), WgpuError> {
+        if !texture.texture.usage().contains(wgpu::TextureUsages::COPY_DST) {
+            return Err(WgpuError::MissingTextureUsage(wgpu::TextureUsages::COPY_DST));
+        }
+        if !Rectangle::from_size(texture.size()).contains_rect(region) {
+            return Err(WgpuError::RegionOutOfBounds(region));
+        }
+        let stride = texture.width() * bytes_per_pixel(texture.texture.format());
+        let expected_len = stride as usize * texture.height() as usize;
+        if data.len() < expected_len {
+            return Err(WgpuError::IncompleteBuffer {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+
+        self.upload(&texture.texture, data, stride, region);
+        Ok(())
+    }
+
+    fn mem_formats(&self) -> Box<dyn Iterator<Item = DrmFourcc>> {
+        Box::new(SUPPORTED_FORMATS.iter().copied())
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportMemWl for WgpuRenderer {
+    #[profiling::function]
+    fn import_shm_buffer(
+        &mut self,
+        buffer: &wl_buffer::WlBuffer,
+        surface: Option<&SurfaceData>,
+        damage: &[Rectangle<i32, BufferCoords>],
+    ) -> Result<WgpuTexture, WgpuError> {
+        type CacheMap = HashMap<ContextId<WgpuTexture>, WgpuTexture>;
+
+        let mut surface_lock = surface.as_ref().map(|surface_data| {
+            surface_data
+                .data_map
+                .get_or_insert_threadsafe(|| Arc::new(Mutex::new(CacheMap::new())))
+                .lock()
+                .unwrap()
+        });
+
+        shm::with_buffer_contents(buffer, |ptr, len, data| {
+            let format = shm::shm_format_to_fourcc(data.format)
+                .ok_or(WgpuError::UnsupportedWlPixelFormat(data.format))?;
+            let size = Size::<i32, BufferCoords>::from((data.width, data.height));
+
+            let offset = data.offset as usize;
+            let contents_len = data.stride as usize * data.height as usize;
+            if len < offset + contents_len {
+                return Err(WgpuError::IncompleteBuffer {
+                    expected: offset + contents_len,
+                    actual: len,
+                });
+            }
+            // SAFETY: the pool is mapped for the duration of the closure and the buffer was checked
+            // to lie within it
+            let contents = unsafe { std::slice::from_raw_parts(ptr.add(offset), contents_len) };
+
+            let id = self.context_id();
+            let cached = surface_lock
+                .as_ref()
+                .and_then(|cache| cache.get(&id).cloned())
+                .filter(|texture| texture.size() == size && texture.format == Some(format));
+
+            let texture = match cached {
+                Some(texture) => {
+                    for region in damage
+                        .iter()
+                        .filter_map(|rect| rect.intersection(Rectangle::from_size(size)))
+                    {
+                        self.upload(&texture.texture, contents, data.stride as u32, region);
+                    }
+                    texture
+                }
+                None => {
+                    let (wgpu_format, has_alpha) =
+                        fourcc_to_wgpu(format).ok_or(WgpuError::UnsupportedWlPixelFormat(data.format))?;
+                    let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("smithay_wgpu_shm"),
+                        size: wgpu::Extent3d {
+                            width: size.w as u32,
+                            height: size.h as u32,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu_format,
+                        usage: wgpu::TextureUsages::TEXTURE_BINDING
+                            | wgpu::TextureUsages::COPY_DST
+                            | wgpu::TextureUsages::COPY_SRC,
+                        view_formats: &[],
+                    });
+                    self.upload(&texture, contents, data.stride as u32, Rectangle::from_size(size));
+                    let texture = WgpuTexture::from_parts(texture, Some(format), has_alpha, false);
+                    if let Some(cache) = surface_lock.as_mut() {
+                        cache.insert(id, texture.clone());
+                    }
+                    texture
+                }
+            };
+
+            Ok(texture)
+        })?
+    }
+}
+
+impl ImportDma for WgpuRenderer {
+    #[profiling::function]
+    fn import_dmabuf(
+        &mut self,
+        dmabuf: &Dmabuf,
+        _damage: Option<&[Rectangle<i32, BufferCoords>]>,
+    ) -> Result<WgpuTexture, WgpuError> {
+        if let Some(texture) = self.dmabuf_cache.get(&dmabuf.weak()) {
+            return Ok(texture.clone());
+        }
+
+        let texture = self.import_dmabuf_texture(dmabuf, false)?;
+        self.dmabuf_cache.insert(dmabuf.weak(), texture.clone());
+        Ok(texture)
+    }
+
+    fn dmabuf_formats(&self) -> FormatSet {
+        self.vulkan
+            .as_ref()
+            .map(|vulkan| vulkan.texture_formats.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportDmaWl for WgpuRenderer {}
+
+impl Bind<Dmabuf> for WgpuRenderer {
+    #[profiling::function]
+    fn bind<'a>(&mut self, target: &'a mut Dmabuf) -> Result<WgpuTarget<'a>, WgpuError> {
+        let texture = match self.buffers.get(&target.weak()) {
+            Some(texture) => texture.clone(),
+            None => {
+                let texture = self.import_dmabuf_texture(target, true)?;
+                self.buffers.insert(target.weak(), texture.clone());
+                texture
+            }
+        };
+
+        Ok(WgpuTarget {
+            texture,
+            _buffer: PhantomData,
+        })
+    }
+
+    fn supported_formats(&self) -> Option<FormatSet> {
+        self.vulkan.as_ref().map(|vulkan| vulkan.render_formats.clone())
+    }
+}
+
+impl Offscreen<WgpuTexture> for WgpuRenderer {
+    #[profiling::function]
+    fn create_buffer(
+        &mut self,
+        format: DrmFourcc,
+        size: Size<i32, BufferCoords>,
+    ) -> Result<WgpuTexture, WgpuError> {
+        let (wgpu_format, has_alpha) =
+            fourcc_to_wgpu(format).ok_or(WgpuError::UnsupportedPixelFormat(format))?;
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("smithay_wgpu_offscreen"),
+            size: wgpu::Extent3d {
+                width: size.w as u32,
+                height: size.h as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        Ok(WgpuTexture::from_parts(texture, Some(format), has_alpha, false))
+    }
+}
+
+impl Bind<WgpuTexture> for WgpuRenderer {
+    fn bind<'a>(&mut self, target: &'a mut WgpuTexture) -> Result<WgpuTarget<'a>, WgpuError> {
+        if !target
+            .texture
+            .usage()
+            .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        {
+            return Err(WgpuError::MissingTextureUsage(
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            ));
+        }
+        Ok(WgpuTarget {
+            texture: target.clone(),
+            _buffer: PhantomData,
+        })
+    }
+}
+
+/// A texture read back from the gpu
+#[derive(Debug)]
+pub struct WgpuMapping {
+    buffer: wgpu::Buffer,
+    size: Size<i32, BufferCoords>,
+    format: DrmFourcc,
+    /// Stride of `buffer`, which is padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`]
+    bytes_per_row: u32,
+    /// Contents of `buffer` without padding, once mapped
+    data: OnceLock<Vec<u8>>,
+}
+
+impl Texture for WgpuMapping {
+    fn width(&self) -> u32 {
+        self.size.w as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.size.h as u32
+    }
+
+    fn format(&self) -> Option<DrmFourcc> {
+        Some(self.format)
+    }
+}
+
+impl TextureMapping for WgpuMapping {
+    fn flipped(&self) -> bool {
+        false
+    }
+}
+
+impl ExportMem for WgpuRenderer {
+    type TextureMapping = WgpuMapping;
+
+    fn copy_framebuffer(
+        &mut self,
+        target: &WgpuTarget<'_>,
+        region: Rectangle<i32, BufferCoords>,
+        format: DrmFourcc,
+    ) -> Result<WgpuMapping, WgpuError> {
+        self.copy(&target.texture, region, format)
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &WgpuTexture,
+        region: Rectangle<i32, BufferCoords>,
+        format: DrmFourcc,
+    ) -> Result<WgpuMapping, WgpuError> {
+        self.copy(texture, region, format)
+    }
+
+    fn can_read_texture(&mut self, texture: &WgpuTexture) -> Result<bool, WgpuError> {
+        Ok(texture.texture.usage().contains(wgpu::TextureUsages::COPY_SRC))
+    }
+
+    #[profiling::function]
+    fn map_texture<'a>(&mut self, texture_mapping: &'a WgpuMapping) -> Result<&'a [u8], WgpuError> {
+        if let Some(data) = texture_mapping.data.get() {
+            return Ok(data);
+        }
+
+        let slice = texture_mapping.buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.device.poll(wgpu::PollType::wait_indefinitely())?;
+        rx.recv().map_err(|_| WgpuError::SyncInterrupted)??;
+
+        let row_len = texture_mapping.width() as usize
+            * bytes_per_pixel(fourcc_to_wgpu(texture_mapping.format).expect("checked on copy").0) as usize;
+        let data = slice
+            .get_mapped_range()
+            .chunks(texture_mapping.bytes_per_row as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect::<Vec<_>>();
+        texture_mapping.buffer.unmap();
+
+        Ok(texture_mapping.data.get_or_init(|| data))
+    }
+}
+
+/// Builds the matrix mapping positions in `dest` to texture coordinates of `src`,
+/// see the function of the same name in the gles renderer
+fn build_texture_mat(
+    src: Rectangle<f64, BufferCoords>,
+    dest: Rectangle<i32, Physical>,
+    texture: Size<i32, BufferCoords>,
+    transform: Transform,
+) -> Matrix3<f32> {
+    let dst_src_size = transform.transform_size(src.size);
+    let scale = dst_src_size.to_f64() / dest.size.to_f64();
+
+    let mut tex_mat = Matrix3::<f32>::identity();
+
+    // first bring the damage into src scale
+    tex_mat = Matrix3::from_nonuniform_scale(scale.x as f32, scale.y as f32) * tex_mat;
+
+    // then compensate for the texture transform
+    let transform_mat = transform.matrix();
+    let translation = match transform {
+        Transform::Normal => Matrix3::identity(),
+        Transform::_90 => Matrix3::from_translation(Vector2::new(0f32, dst_src_size.w as f32)),
+        Transform::_180 => {
+            Matrix3::from_translation(Vector2::new(dst_src_size.w as f32, dst_src_size.h as f32))
+        }
+        Transform::_270 => Matrix3::from_translation(Vector2::new(dst_src_size.h as f32, 0f32)),
+        Transform::Flipped => Matrix3::from_translation(Vector2::new(dst_src_size.w as f32, 0f32)),
+        Transform::Flipped90 => Matrix3::identity(),
+        Transform::Flipped180 => Matrix3::from_translation(Vector2::new(0f32, dst_src_size.h as f32)),
+        Transform::Flipped270 => {
+            Matrix3::from_translation(Vector2::new(dst_src_size.h as f32, dst_src_size.w as f32))
+        }
+    };
+    tex_mat = transform_mat * tex_mat;
+    tex_mat = translation * tex_mat;
+
+    // now we can add the src crop loc, the size already done implicit by the src size
+    tex_mat = Matrix3::from_translation(Vector2::new(src.loc.x as f32, src.loc.y as f32)) * tex_mat;
+
+    // at last we have to normalize the values for UV space
+    tex_mat = Matrix3::from_nonuniform_scale(
+        (1.0f64 / texture.w as f64) as f32,
+        (1.0f64 / texture.h as f64) as f32,
+    ) * tex_mat;
+
+    tex_mat
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::Vector3;
+    use drm_fourcc::DrmFourcc;
+
+    use super::{projection, WgpuRenderer, WgpuTexture};
+    use crate::{
+        backend::renderer::{
+            test::conformance, Bind, Color32F, DebugFlags, ExportMem, Frame, ImportMem, Offscreen, Renderer,
+            Texture,
+        },
+        utils::{Physical, Rectangle, Size, Transform},
+    };
+
+    /// Renderer on a software adapter, or any other adapter if there is none
+    ///
+    /// Returns `None` if wgpu finds no adapter at all, the rendering tests are skipped in that case
+    /// unless `wgpu` is listed in [`conformance::REQUIRE_DEVICES_VAR`].
+    fn renderer() -> Option<WgpuRenderer> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            force_fallback_adapter: true,
+            ..Default::default()
+        }))
+        .or_else(|_| pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())));
+        let renderer = adapter.ok().and_then(|adapter| {
+            let (device, queue) =
+                pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()?;
+            Some(WgpuRenderer::new(&instance, Arc::new(device), Arc::new(queue)))
+        });
+        conformance::require_device("wgpu", renderer)
+    }
+
+    fn render(
+        renderer: &mut WgpuRenderer,
+        buffer: &mut WgpuTexture,
+        draw: impl FnOnce(&mut super::WgpuFrame<'_, '_>),
+    ) {
+        let size = buffer.size();
+        let mut target = renderer.bind(buffer).unwrap();
+        let mut frame = renderer
+            .render(&mut target, (size.w, size.h).into(), Transform::Normal)
+            .unwrap();
+        draw(&mut frame);
+        let sync = frame.finish().unwrap();
+        renderer.wait(&sync).unwrap();
+    }
+
+    /// Pixels of `texture` as `[b, g, r, a]`
+    fn pixels(renderer: &mut WgpuRenderer, texture: &WgpuTexture) -> Vec<[u8; 4]> {
+        let mapping = renderer
+            .copy_texture(texture, Rectangle::from_size(texture.size()), DrmFourcc::Argb8888)
+            .unwrap();
+        let data = renderer.map_texture(&mapping).unwrap();
+        data.chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    const RED: [u8; 4] = [0, 0, 255, 255];
+    const BLUE: [u8; 4] = [255, 0, 0, 255];
+
+    #[test]
+    fn projection_normal_and_rotated() {
+        let size: Size<i32, Physical> = (128, 64).into();
+
+        let normal = projection(size, Transform::Normal);
+        assert_eq!(
+            normal * Vector3::new(0f32, 0f32, 1f32),
+            Vector3::new(-1f32, 1f32, 1f32)
+        );
+        assert_eq!(
+            normal * Vector3::new(128f32, 64f32, 1f32),
+            Vector3::new(1f32, -1f32, 1f32)
+        );
+
+        // the frame of a rotated output has the swapped size, its origin ends up at the top right
+        let rotated = projection(size, Transform::_90);
+        assert_eq!(
+            rotated * Vector3::new(0f32, 0f32, 1f32),
+            Vector3::new(1f32, 1f32, 1f32)
+        );
+        assert_eq!(
+            rotated * Vector3::new(64f32, 128f32, 1f32),
+            Vector3::new(-1f32, -1f32, 1f32)
+        );
+    }
+
+    #[test]
+    fn alpha_and_tint() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        // the alpha channel of xrgb textures is ignored
+        let opaque = renderer
+            .import_memory(&[255, 255, 255, 0], DrmFourcc::Xrgb8888, (1, 1).into(), false)
+            .unwrap();
+        let mut buffer = renderer
+            .create_buffer(DrmFourcc::Argb8888, (2, 1).into())
+            .unwrap();
+
+        renderer.set_debug_flags(DebugFlags::TINT);
+        render(&mut renderer, &mut buffer, |frame| {
+            frame
+                .clear(
+                    Color32F::new(0.0, 0.0, 0.0, 1.0),
+                    &[Rectangle::from_size((2, 1).into())],
+                )
+                .unwrap();
+            frame
+                .render_texture_at(
+                    &opaque,
+                    (0, 0).into(),
+                    1,
+                    1.0,
+                    Transform::Normal,
+                    &[Rectangle::from_size((1, 1).into())],
+                    &[],
+                    1.0,
+                )
+                .unwrap();
+        });
+
+        let pixels = pixels(&mut renderer, &buffer);
+        // 0.8 * white + the green tint
+        for (channel, expected) in pixels[0].into_iter().zip([204u8, 255, 204, 255]) {
+            assert!(channel.abs_diff(expected) <= 1, "{:?}", pixels[0]);
+        }
+        assert_eq!(pixels[1], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn update_and_export_memory() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        let texture = renderer
+            .import_memory(&[RED; 4].concat(), DrmFourcc::Argb8888, (2, 2).into(), false)
+            .unwrap();
+        renderer
+            .update_memory(
+                &texture,
+                &[RED, RED, RED, BLUE].concat(),
+                Rectangle::new((1, 1).into(), (1, 1).into()),
+            )
+            .unwrap();
+        assert!(renderer.can_read_texture(&texture).unwrap());
+        assert_eq!(pixels(&mut renderer, &texture), [RED, RED, RED, BLUE]);
+
+        let mapping = renderer
+            .copy_texture(
+                &texture,
+                Rectangle::new((1, 0).into(), (1, 2).into()),
+                DrmFourcc::Argb8888,
+            )
+            .unwrap();
+        assert_eq!(renderer.map_texture(&mapping).unwrap(), [RED, BLUE].concat());
+
+        assert!(renderer
+            .copy_texture(&texture, Rectangle::from_size((3, 3).into()), DrmFourcc::Argb8888)
+            .is_err());
+        assert!(renderer
+            .copy_texture(&texture, Rectangle::from_size((2, 2).into()), DrmFourcc::Abgr8888)
+            .is_err());
+    }
+
+    #[cfg(feature = "wayland_frontend")]
+    #[test]
+    fn import_shm_with_offset() {
+        use crate::{
+            backend::renderer::ImportMemWl, reexports::wayland_server::protocol::wl_shm,
+            wayland::shm::test::TestBuffer,
+        };
+
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        // the buffer starts after a row of another buffer and ends at the end of the pool
+        let pool = [[BLUE; 2].concat(), [RED, RED, RED, BLUE].concat()].concat();
+        let buffer = TestBuffer::new(&pool, 8, 2, 2, 8, wl_shm::Format::Argb8888);
+        let texture = renderer.import_shm_buffer(&buffer.buffer, None, &[]).unwrap();
+        assert_eq!(pixels(&mut renderer, &texture), [RED, RED, RED, BLUE]);
+
+        let buffer = TestBuffer::new(&pool, 12, 2, 2, 8, wl_shm::Format::Argb8888);
+        assert!(renderer.import_shm_buffer(&buffer.buffer, None, &[]).is_err());
+    }
+
+    #[test]
+    fn conformance() {
+        let Some(mut renderer) = renderer() else {
+            return;
+        };
+        if let Err(err) = conformance::check_offscreen::<_, WgpuTexture>(&mut renderer, DrmFourcc::Argb8888) {
+            panic!("{}", err);
+        }
+    }
+}
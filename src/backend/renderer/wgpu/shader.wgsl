@@ -0,0 +1,65 @@
+// Mirrors the texture and solid programs of the gles renderer, see `DrawUniforms` in mod.rs.
+
+struct DrawUniforms {
+    matrix: mat3x3<f32>,
+    tex_matrix: mat3x3<f32>,
+    color: vec4<f32>,
+    alpha: f32,
+    flags: u32,
+};
+
+const FLAG_NO_ALPHA: u32 = 1u;
+const FLAG_TINT: u32 = 2u;
+
+@group(0) @binding(0)
+var<uniform> draw: DrawUniforms;
+
+@group(1) @binding(0)
+var tex: texture_2d<f32>;
+@group(1) @binding(1)
+var tex_sampler: sampler;
+
+struct VertexInput {
+    @builtin(vertex_index) index: u32,
+    // translation in xy and scale in zw of the unit quad
+    @location(0) vert_position: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    // corners of the unit quad in triangle strip order
+    let vert = vec2<f32>(f32(in.index & 1u), f32(in.index >> 1u));
+    let position = vec3<f32>(vert * in.vert_position.zw + in.vert_position.xy, 1.0);
+
+    var out: VertexOutput;
+    out.tex_coords = (draw.tex_matrix * position).xy;
+    out.position = vec4<f32>((draw.matrix * position).xy, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_solid(in: VertexOutput) -> @location(0) vec4<f32> {
+    return draw.color;
+}
+
+@fragment
+fn fs_texture(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(tex, tex_sampler, in.tex_coords);
+
+    if (draw.flags & FLAG_NO_ALPHA) != 0u {
+        color = vec4<f32>(color.rgb, 1.0) * draw.alpha;
+    } else {
+        color = color * draw.alpha;
+    }
+
+    if (draw.flags & FLAG_TINT) != 0u {
+        color = vec4<f32>(0.0, 0.2, 0.0, 0.2) + color * 0.8;
+    }
+
+    return color;
+}
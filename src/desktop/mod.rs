@@ -28,6 +28,12 @@
 //! Elements get a position and stacking order through mapping. Outputs become views of a part of the [`Space`]
 //! and can be rendered via [`render_output`](crate::desktop::space::render_output).
 //!
+//! ### [`Workspaces`]
+//!
+//! Workspaces manage multiple [`Space`]s, of which one is shown on a set of [`Output`](crate::output::Output)s at a time.
+//! Switching the active workspace moves all outputs to its [`Space`].
+//! Workspaces can optionally be advertised to clients via the [ext-workspace protocol](crate::wayland::workspace).
+//!
 //! ### Layer Shell
 //!
 //! A [`LayerSurface`] represents a surface as provided by e.g. the layer-shell protocol.
@@ -51,6 +57,8 @@
 
 pub mod space;
 pub use self::space::Space;
+pub mod workspaces;
+pub use self::workspaces::{Workspace, Workspaces};
 
 #[cfg(feature = "wayland_frontend")]
 pub use self::wayland::{
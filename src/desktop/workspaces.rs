@@ -0,0 +1,336 @@
+//! Helper to manage multiple [`Space`]s as switchable workspaces
+//!
+//! [`Workspaces`] holds a list of [`Workspace`]s, each wrapping its own [`Space`], of which
+//! exactly one is active at a time. Outputs mapped through [`Workspaces::map_output`] are always
+//! mapped into the [`Space`] of the active workspace and move along when switching workspaces.
+//!
+//! For per-output workspaces, use one [`Workspaces`] instance per output.
+//!
+//! With the `wayland_frontend` feature, workspaces can be advertised to clients via the
+//! [ext-workspace protocol](crate::wayland::workspace) by assigning a
+//! [`WorkspaceGroupHandle`] and [`WorkspaceHandle`]s. The states of the handles and the outputs
+//! of the group are kept up-to-date, but you still need to call
+//! [`WorkspaceManagerState::done`](crate::wayland::workspace::WorkspaceManagerState::done)
+//! after any changes.
+
+#[cfg(feature = "wayland_frontend")]
+use crate::wayland::workspace::{WorkspaceGroupHandle, WorkspaceHandle, WorkspaceState};
+use crate::{
+    desktop::space::{Space, SpaceElement},
+    output::Output,
+    utils::{Logical, Point},
+};
+
+/// A single workspace of [`Workspaces`]
+#[derive(Debug)]
+pub struct Workspace<E: SpaceElement> {
+    space: Space<E>,
+    name: String,
+    #[cfg(feature = "wayland_frontend")]
+    handle: Option<WorkspaceHandle>,
+}
+
+impl<E: SpaceElement> Workspace<E> {
+    /// Returns the name of the workspace
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Set the name of the workspace
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+        #[cfg(feature = "wayland_frontend")]
+        if let Some(handle) = self.handle.as_ref() {
+            handle.set_name(self.name.clone());
+        }
+    }
+
+    /// Returns the [`Space`] of the workspace
+    pub fn space(&self) -> &Space<E> {
+        &self.space
+    }
+
+    /// Returns the [`Space`] of the workspace mutably
+    pub fn space_mut(&mut self) -> &mut Space<E> {
+        &mut self.space
+    }
+
+    /// Returns the [`WorkspaceHandle`] advertising this workspace, if any
+    #[cfg(feature = "wayland_frontend")]
+    pub fn handle(&self) -> Option<&WorkspaceHandle> {
+        self.handle.as_ref()
+    }
+}
+
+/// A list of [`Space`]s, of which one is shown on a set of outputs at a time
+#[derive(Debug)]
+pub struct Workspaces<E: SpaceElement> {
+    workspaces: Vec<Workspace<E>>,
+    active: usize,
+    outputs: Vec<(Output, Point<i32, Logical>)>,
+    #[cfg(feature = "wayland_frontend")]
+    group: Option<WorkspaceGroupHandle>,
+}
+
+impl<E: SpaceElement + PartialEq> Workspaces<E> {
+    /// Create a new set of workspaces with a single, active workspace
+    pub fn new(name: impl Into<String>) -> Self {
+        Workspaces {
+            workspaces: vec![Workspace {
+                space: Space::default(),
+                name: name.into(),
+                #[cfg(feature = "wayland_frontend")]
+                handle: None,
+            }],
+            active: 0,
+            outputs: Vec::new(),
+            #[cfg(feature = "wayland_frontend")]
+            group: None,
+        }
+    }
+
+    /// Append a new workspace, returning its index
+    pub fn add_workspace(&mut self, name: impl Into<String>) -> usize {
+        self.workspaces.push(Workspace {
+            space: Space::default(),
+            name: name.into(),
+            #[cfg(feature = "wayland_frontend")]
+            handle: None,
+        });
+        self.workspaces.len() - 1
+    }
+
+    /// Remove the workspace at the given index
+    ///
+    /// The last remaining workspace cannot be removed. If the active workspace is removed,
+    /// the previous workspace (or the next one, if there is no previous) becomes active.
+    ///
+    /// Elements of the removed workspace are not moved, but the workspace is returned
+    /// to allow re-mapping them.
+    pub fn remove_workspace(&mut self, idx: usize) -> Option<Workspace<E>> {
+        if idx >= self.workspaces.len() || self.workspaces.len() == 1 {
+            return None;
+        }
+
+        if idx == self.active {
+            self.activate(idx.checked_sub(1).unwrap_or(1));
+        }
+        if idx < self.active {
+            self.active -= 1;
+        }
+
+        Some(self.workspaces.remove(idx))
+    }
+
+    /// Returns the number of workspaces
+    pub fn len(&self) -> usize {
+        self.workspaces.len()
+    }
+
+    /// Returns `true` if there are no workspaces
+    ///
+    /// This is never the case, but provided for consistency with [`Workspaces::len`].
+    pub fn is_empty(&self) -> bool {
+        self.workspaces.is_empty()
+    }
+
+    /// Iterate over all workspaces
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Workspace<E>> {
+        self.workspaces.iter()
+    }
+
+    /// Iterate mutably over all workspaces
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = &mut Workspace<E>> {
+        self.workspaces.iter_mut()
+    }
+
+    /// Returns the workspace at the given index
+    pub fn get(&self, idx: usize) -> Option<&Workspace<E>> {
+        self.workspaces.get(idx)
+    }
+
+    /// Returns the workspace at the given index mutably
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut Workspace<E>> {
+        self.workspaces.get_mut(idx)
+    }
+
+    /// Returns the index of the active workspace
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    /// Returns the active workspace
+    pub fn active(&self) -> &Workspace<E> {
+        &self.workspaces[self.active]
+    }
+
+    /// Returns the active workspace mutably
+    pub fn active_mut(&mut self) -> &mut Workspace<E> {
+        &mut self.workspaces[self.active]
+    }
+
+    /// Activate the workspace at the given index
+    ///
+    /// All outputs are moved from the [`Space`] of the previously active workspace
+    /// to the [`Space`] of the newly active workspace.
+    ///
+    /// Returns `false` if the index is out of bounds.
+    pub fn activate(&mut self, idx: usize) -> bool {
+        if idx >= self.workspaces.len() {
+            return false;
+        }
+        if idx == self.active {
+            return true;
+        }
+
+        let old = self.active;
+        for (output, location) in &self.outputs {
+            self.workspaces[old].space.unmap_output(output);
+            self.workspaces[idx].space.map_output(output, *location);
+        }
+        self.active = idx;
+
+        #[cfg(feature = "wayland_frontend")]
+        {
+            self.update_handle_state(old);
+            self.update_handle_state(idx);
+        }
+
+        true
+    }
+
+    /// Map an [`Output`] into the active workspace
+    ///
+    /// The location is kept when switching workspaces.
+    pub fn map_output<P: Into<Point<i32, Logical>>>(&mut self, output: &Output, location: P) {
+        let location = location.into();
+        match self.outputs.iter_mut().find(|(o, _)| o == output) {
+            Some((_, loc)) => *loc = location,
+            None => {
+                self.outputs.push((output.clone(), location));
+                #[cfg(feature = "wayland_frontend")]
+                if let Some(group) = self.group.as_ref() {
+                    group.add_output(output);
+                }
+            }
+        }
+        self.workspaces[self.active].space.map_output(output, location);
+    }
+
+    /// Unmap an [`Output`] from the workspaces
+    pub fn unmap_output(&mut self, output: &Output) {
+        let Some(pos) = self.outputs.iter().position(|(o, _)| o == output) else {
+            return;
+        };
+
+        self.outputs.remove(pos);
+        self.workspaces[self.active].space.unmap_output(output);
+        #[cfg(feature = "wayland_frontend")]
+        if let Some(group) = self.group.as_ref() {
+            group.remove_output(output);
+        }
+    }
+
+    /// Iterate over all mapped [`Output`]s
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.outputs.iter().map(|(o, _)| o)
+    }
+
+    /// Returns the index of the workspace the element is mapped in
+    pub fn workspace_for_element(&self, element: &E) -> Option<usize> {
+        self.workspaces
+            .iter()
+            .position(|workspace| workspace.space.elements().any(|e| e == element))
+    }
+
+    /// Move an element to another workspace, keeping its location
+    ///
+    /// Returns `false` if the element is not mapped or the index is out of bounds.
+    pub fn move_element(&mut self, element: &E, idx: usize, activate: bool) -> bool
+    where
+        E: Clone,
+    {
+        if idx >= self.workspaces.len() {
+            return false;
+        }
+        let Some(current) = self.workspace_for_element(element) else {
+            return false;
+        };
+
+        let space = &mut self.workspaces[current].space;
+        let location = space.element_location(element).unwrap_or_default();
+        space.unmap_elem(element);
+        self.workspaces[idx]
+            .space
+            .map_element(element.clone(), location, activate);
+
+        true
+    }
+
+    /// Refresh all workspaces
+    ///
+    /// See [`Space::refresh`].
+    pub fn refresh(&mut self) {
+        for workspace in &mut self.workspaces {
+            workspace.space.refresh();
+        }
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl<E: SpaceElement + PartialEq> Workspaces<E> {
+    /// Set the [`WorkspaceGroupHandle`] advertising these workspaces
+    ///
+    /// All mapped outputs are added to the group. Workspaces with an assigned handle
+    /// should be assigned to the group via
+    /// [`WorkspaceManagerState::set_workspace_group`](crate::wayland::workspace::WorkspaceManagerState::set_workspace_group).
+    pub fn set_group_handle(&mut self, group: Option<WorkspaceGroupHandle>) {
+        if let Some(old) = self.group.as_ref() {
+            for (output, _) in &self.outputs {
+                old.remove_output(output);
+            }
+        }
+        if let Some(group) = group.as_ref() {
+            for (output, _) in &self.outputs {
+                group.add_output(output);
+            }
+        }
+        self.group = group;
+    }
+
+    /// Returns the [`WorkspaceGroupHandle`] advertising these workspaces, if any
+    pub fn group_handle(&self) -> Option<&WorkspaceGroupHandle> {
+        self.group.as_ref()
+    }
+
+    /// Set the [`WorkspaceHandle`] advertising the workspace at the given index
+    ///
+    /// The active state of the handle is updated to match the workspace.
+    pub fn set_workspace_handle(&mut self, idx: usize, handle: Option<WorkspaceHandle>) {
+        let Some(workspace) = self.workspaces.get_mut(idx) else {
+            return;
+        };
+        if let Some(handle) = handle.as_ref() {
+            handle.set_name(workspace.name.clone());
+        }
+        workspace.handle = handle;
+        self.update_handle_state(idx);
+    }
+
+    /// Returns the index of the workspace advertised by the given [`WorkspaceHandle`]
+    pub fn workspace_for_handle(&self, handle: &WorkspaceHandle) -> Option<usize> {
+        self.workspaces
+            .iter()
+            .position(|workspace| workspace.handle.as_ref() == Some(handle))
+    }
+
+    fn update_handle_state(&self, idx: usize) {
+        let Some(handle) = self.workspaces.get(idx).and_then(|w| w.handle.as_ref()) else {
+            return;
+        };
+
+        let mut state = handle.state();
+        state.set(WorkspaceState::Active, idx == self.active);
+        handle.set_state(state);
+    }
+}
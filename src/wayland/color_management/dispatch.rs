@@ -0,0 +1,891 @@
+use std::{
+    os::unix::io::{AsFd, OwnedFd},
+    sync::atomic::Ordering,
+};
+
+use tracing::warn;
+use wayland_protocols::wp::color_management::v1::server::{
+    wp_color_management_output_v1::{self, WpColorManagementOutputV1},
+    wp_color_management_surface_feedback_v1::{self, WpColorManagementSurfaceFeedbackV1},
+    wp_color_management_surface_v1::{self, WpColorManagementSurfaceV1},
+    wp_color_manager_v1::{self, WpColorManagerV1},
+    wp_image_description_creator_icc_v1::{self, WpImageDescriptionCreatorIccV1},
+    wp_image_description_creator_params_v1::{self, WpImageDescriptionCreatorParamsV1},
+    wp_image_description_info_v1::{self, WpImageDescriptionInfoV1},
+    wp_image_description_v1::{self, WpImageDescriptionV1},
+};
+use wayland_server::{
+    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+
+use super::{
+    ColorManagementFeedbackData, ColorManagementHandler, ColorManagementOutputData, ColorManagementState,
+    ColorManagementSurfaceAttached, ColorManagementSurfaceCachedState, ColorManagementSurfaceData, Feature,
+    IccCreatorData, ImageDescription, ImageDescriptionContents, ImageDescriptionData, ParametricCreatorData,
+};
+use crate::{
+    backend::renderer::color::{
+        Chromaticities, ColorDescription, Luminances, NamedPrimaries, Primaries, TransferFunction,
+    },
+    output::Output,
+    utils::SealedFile,
+    wayland::compositor,
+};
+
+/// Upper limit for ICC profiles sent by clients
+const MAX_ICC_SIZE: u32 = 32 * 1024 * 1024;
+
+impl<D> GlobalDispatch<WpColorManagerV1, (), D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn bind(
+        state: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<WpColorManagerV1>,
+        _: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        let capabilities = &state.color_management_state().capabilities;
+        for intent in &capabilities.render_intents {
+            manager.supported_intent(*intent);
+        }
+        for feature in &capabilities.features {
+            manager.supported_feature(*feature);
+        }
+        for tf in capabilities
+            .transfer_functions
+            .iter()
+            .copied()
+            .filter_map(tf_to_wire)
+        {
+            manager.supported_tf_named(tf);
+        }
+        for primaries in &capabilities.primaries {
+            manager.supported_primaries_named(primaries_to_wire(*primaries));
+        }
+        manager.done();
+    }
+}
+
+impl<D> Dispatch<WpColorManagerV1, (), D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        manager: &WpColorManagerV1,
+        request: wp_color_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let capabilities = &state.color_management_state().capabilities;
+        match request {
+            wp_color_manager_v1::Request::GetOutput { id, output } => {
+                let output = Output::from_resource(&output)
+                    .map(|output| output.downgrade())
+                    .unwrap_or_default();
+                let instance = data_init.init(id, ColorManagementOutputData { output });
+                state.color_management_state().outputs.push(instance);
+            }
+            wp_color_manager_v1::Request::GetSurface { id, surface } => {
+                let already_attached = compositor::with_states(&surface, |states| {
+                    let attached = states
+                        .data_map
+                        .get_or_insert_threadsafe(ColorManagementSurfaceAttached::default);
+                    attached.0.swap(true, Ordering::SeqCst)
+                });
+
+                if already_attached {
+                    manager.post_error(
+                        wp_color_manager_v1::Error::SurfaceExists,
+                        "wl_surface already has a color management surface object attached",
+                    );
+                    return;
+                }
+
+                data_init.init(
+                    id,
+                    ColorManagementSurfaceData {
+                        surface: surface.downgrade(),
+                    },
+                );
+            }
+            wp_color_manager_v1::Request::GetSurfaceFeedback { id, surface } => {
+                let instance = data_init.init(
+                    id,
+                    ColorManagementFeedbackData {
+                        surface: surface.downgrade(),
+                    },
+                );
+                state.color_management_state().feedbacks.push(instance);
+            }
+            wp_color_manager_v1::Request::CreateIccCreator { obj } => {
+                if !capabilities.features.contains(&Feature::IccV2V4) {
+                    manager.post_error(
+                        wp_color_manager_v1::Error::UnsupportedFeature,
+                        "ICC profiles are not supported",
+                    );
+                    return;
+                }
+                data_init.init(obj, IccCreatorData::default());
+            }
+            wp_color_manager_v1::Request::CreateParametricCreator { obj } => {
+                if !capabilities.features.contains(&Feature::Parametric) {
+                    manager.post_error(
+                        wp_color_manager_v1::Error::UnsupportedFeature,
+                        "parametric image descriptions are not supported",
+                    );
+                    return;
+                }
+                data_init.init(obj, ParametricCreatorData::default());
+            }
+            wp_color_manager_v1::Request::CreateWindowsScrgb { image_description } => {
+                if !capabilities.features.contains(&Feature::WindowsScrgb) {
+                    manager.post_error(
+                        wp_color_manager_v1::Error::UnsupportedFeature,
+                        "windows_scrgb is not supported",
+                    );
+                    return;
+                }
+                let description = ImageDescription::parametric(ColorDescription::windows_scrgb());
+                init_image_description(data_init, image_description, description, false);
+            }
+            wp_color_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpColorManagementOutputV1, ColorManagementOutputData, D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        _: &WpColorManagementOutputV1,
+        request: wp_color_management_output_v1::Request,
+        data: &ColorManagementOutputData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_color_management_output_v1::Request::GetImageDescription { image_description } => {
+                match data.output.upgrade() {
+                    Some(output) => {
+                        let description = state.color_management_state().output_image_description(&output);
+                        init_image_description(data_init, image_description, description, true);
+                    }
+                    None => {
+                        let instance = data_init.init(
+                            image_description,
+                            ImageDescriptionData {
+                                description: None,
+                                allow_information: false,
+                            },
+                        );
+                        instance.failed(
+                            wp_image_description_v1::Cause::NoOutput,
+                            "output was removed".into(),
+                        );
+                    }
+                }
+            }
+            wp_color_management_output_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        object: &WpColorManagementOutputV1,
+        _data: &ColorManagementOutputData,
+    ) {
+        state
+            .color_management_state()
+            .outputs
+            .retain(|instance| instance != object);
+    }
+}
+
+impl<D> Dispatch<WpColorManagementSurfaceV1, ColorManagementSurfaceData, D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        obj: &WpColorManagementSurfaceV1,
+        request: wp_color_management_surface_v1::Request,
+        data: &ColorManagementSurfaceData,
+        _dh: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        let Ok(surface) = data.surface.upgrade() else {
+            if !matches!(request, wp_color_management_surface_v1::Request::Destroy) {
+                obj.post_error(
+                    wp_color_management_surface_v1::Error::Inert,
+                    "wl_surface was destroyed",
+                );
+            }
+            return;
+        };
+
+        match request {
+            wp_color_management_surface_v1::Request::SetImageDescription {
+                image_description,
+                render_intent,
+            } => {
+                let render_intent = match render_intent {
+                    WEnum::Value(intent)
+                        if state
+                            .color_management_state()
+                            .capabilities
+                            .render_intents
+                            .contains(&intent) =>
+                    {
+                        intent
+                    }
+                    _ => {
+                        obj.post_error(
+                            wp_color_management_surface_v1::Error::RenderIntent,
+                            "unsupported render intent",
+                        );
+                        return;
+                    }
+                };
+
+                let Some(description) = image_description
+                    .data::<ImageDescriptionData>()
+                    .and_then(|data| data.description.clone())
+                else {
+                    obj.post_error(
+                        wp_color_management_surface_v1::Error::ImageDescription,
+                        "image description is not ready",
+                    );
+                    return;
+                };
+
+                compositor::with_states(&surface, |states| {
+                    states
+                        .cached_state
+                        .get::<ColorManagementSurfaceCachedState>()
+                        .pending()
+                        .description = Some((description, render_intent));
+                });
+            }
+            wp_color_management_surface_v1::Request::UnsetImageDescription => {
+                compositor::with_states(&surface, |states| {
+                    states
+                        .cached_state
+                        .get::<ColorManagementSurfaceCachedState>()
+                        .pending()
+                        .description = None;
+                });
+            }
+            wp_color_management_surface_v1::Request::Destroy => {
+                compositor::with_states(&surface, |states| {
+                    if let Some(attached) = states.data_map.get::<ColorManagementSurfaceAttached>() {
+                        attached.0.store(false, Ordering::SeqCst);
+                    }
+
+                    states
+                        .cached_state
+                        .get::<ColorManagementSurfaceCachedState>()
+                        .pending()
+                        .description = None;
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpColorManagementSurfaceFeedbackV1, ColorManagementFeedbackData, D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        obj: &WpColorManagementSurfaceFeedbackV1,
+        request: wp_color_management_surface_feedback_v1::Request,
+        data: &ColorManagementFeedbackData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let surface = data.surface.upgrade();
+
+        match request {
+            wp_color_management_surface_feedback_v1::Request::GetPreferred { image_description } => {
+                let Ok(surface) = surface else {
+                    obj.post_error(
+                        wp_color_management_surface_feedback_v1::Error::Inert,
+                        "wl_surface was destroyed",
+                    );
+                    return;
+                };
+
+                let description = state
+                    .color_management_state()
+                    .preferred_image_description(&surface);
+                init_image_description(data_init, image_description, description, true);
+            }
+            wp_color_management_surface_feedback_v1::Request::GetPreferredParametric {
+                image_description,
+            } => {
+                let Ok(surface) = surface else {
+                    obj.post_error(
+                        wp_color_management_surface_feedback_v1::Error::Inert,
+                        "wl_surface was destroyed",
+                    );
+                    return;
+                };
+
+                let color_management_state = state.color_management_state();
+                if !color_management_state
+                    .capabilities
+                    .features
+                    .contains(&Feature::Parametric)
+                {
+                    obj.post_error(
+                        wp_color_management_surface_feedback_v1::Error::UnsupportedFeature,
+                        "parametric image descriptions are not supported",
+                    );
+                    return;
+                }
+
+                let mut description = color_management_state.preferred_image_description(&surface);
+                if description.color_description().is_none() {
+                    // fall back to sRGB, if the compositor prefers an ICC profile
+                    description = color_management_state.default_image_description().clone();
+                    if description.color_description().is_none() {
+                        description = ImageDescription::parametric(ColorDescription::srgb());
+                    }
+                }
+                init_image_description(data_init, image_description, description, true);
+            }
+            wp_color_management_surface_feedback_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        object: &WpColorManagementSurfaceFeedbackV1,
+        _data: &ColorManagementFeedbackData,
+    ) {
+        state
+            .color_management_state()
+            .feedbacks
+            .retain(|instance| instance != object);
+    }
+}
+
+impl<D> Dispatch<WpImageDescriptionCreatorIccV1, IccCreatorData, D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        _state: &mut D,
+        _: &Client,
+        obj: &WpImageDescriptionCreatorIccV1,
+        request: wp_image_description_creator_icc_v1::Request,
+        data: &IccCreatorData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_image_description_creator_icc_v1::Request::SetIccFile {
+                icc_profile,
+                offset,
+                length,
+            } => {
+                let mut icc = data.icc.lock().unwrap();
+                if icc.is_some() {
+                    obj.post_error(
+                        wp_image_description_creator_icc_v1::Error::AlreadySet,
+                        "ICC file was already set",
+                    );
+                    return;
+                }
+
+                if length == 0 || length > MAX_ICC_SIZE {
+                    obj.post_error(
+                        wp_image_description_creator_icc_v1::Error::BadSize,
+                        format!("invalid ICC file size {}", length),
+                    );
+                    return;
+                }
+
+                match read_icc_file(icc_profile, offset, length) {
+                    Ok(data) => *icc = Some(data.into()),
+                    Err(IccReadError::BadFd) => obj.post_error(
+                        wp_image_description_creator_icc_v1::Error::BadFd,
+                        "ICC file descriptor is not readable and seekable",
+                    ),
+                    Err(IccReadError::OutOfFile) => obj.post_error(
+                        wp_image_description_creator_icc_v1::Error::OutOfFile,
+                        "offset and length exceed the ICC file",
+                    ),
+                }
+            }
+            wp_image_description_creator_icc_v1::Request::Create { image_description } => {
+                let Some(icc) = data.icc.lock().unwrap().take() else {
+                    obj.post_error(
+                        wp_image_description_creator_icc_v1::Error::IncompleteSet,
+                        "ICC file was not set",
+                    );
+                    return;
+                };
+
+                let description = ImageDescription::new(ImageDescriptionContents::Icc(icc));
+                init_image_description(data_init, image_description, description, false);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Parameters set on a [`WpImageDescriptionCreatorParamsV1`]
+#[derive(Debug, Default)]
+pub(super) struct PendingParameters {
+    transfer_function: Option<TransferFunction>,
+    primaries: Option<Primaries>,
+    luminances: Option<Luminances>,
+    target_primaries: Option<Chromaticities>,
+    target_luminance: Option<(f64, f64)>,
+    max_cll: Option<f64>,
+    max_fall: Option<f64>,
+}
+
+impl<D> Dispatch<WpImageDescriptionCreatorParamsV1, ParametricCreatorData, D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        obj: &WpImageDescriptionCreatorParamsV1,
+        request: wp_image_description_creator_params_v1::Request,
+        data: &ParametricCreatorData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        use wp_image_description_creator_params_v1::{Error, Request};
+
+        let capabilities = &state.color_management_state().capabilities;
+        let mut params = data.params.lock().unwrap();
+
+        macro_rules! set_once {
+            ($field:ident, $value:expr, $name:literal) => {
+                if params.$field.is_some() {
+                    obj.post_error(Error::AlreadySet, concat!($name, " was already set"));
+                    return;
+                }
+                params.$field = Some($value);
+            };
+        }
+        macro_rules! require_feature {
+            ($feature:expr, $name:literal) => {
+                if !capabilities.features.contains(&$feature) {
+                    obj.post_error(
+                        Error::UnsupportedFeature,
+                        concat!($name, " is not supported"),
+                    );
+                    return;
+                }
+            };
+        }
+
+        match request {
+            Request::SetTfNamed { tf } => {
+                let Some(tf) = tf
+                    .into_result()
+                    .ok()
+                    .map(tf_from_wire)
+                    .filter(|tf| capabilities.transfer_functions.contains(tf))
+                else {
+                    obj.post_error(Error::InvalidTf, "unsupported transfer function");
+                    return;
+                };
+                set_once!(transfer_function, tf, "transfer function");
+            }
+            Request::SetTfPower { eexp } => {
+                require_feature!(Feature::SetTfPower, "set_tf_power");
+                if !(10000..=100000).contains(&eexp) {
+                    obj.post_error(Error::InvalidTf, format!("invalid exponent {}", eexp));
+                    return;
+                }
+                set_once!(
+                    transfer_function,
+                    TransferFunction::Power(eexp as f64 / 10000.),
+                    "transfer function"
+                );
+            }
+            Request::SetPrimariesNamed { primaries } => {
+                let Some(primaries) = primaries
+                    .into_result()
+                    .ok()
+                    .map(primaries_from_wire)
+                    .filter(|primaries| capabilities.primaries.contains(primaries))
+                else {
+                    obj.post_error(Error::InvalidPrimariesNamed, "unsupported primaries");
+                    return;
+                };
+                set_once!(primaries, Primaries::Named(primaries), "primaries");
+            }
+            Request::SetPrimaries {
+                r_x,
+                r_y,
+                g_x,
+                g_y,
+                b_x,
+                b_y,
+                w_x,
+                w_y,
+            } => {
+                require_feature!(Feature::SetPrimaries, "set_primaries");
+                let chromaticities = chromaticities_from_wire([r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y]);
+                set_once!(primaries, Primaries::Custom(chromaticities), "primaries");
+            }
+            Request::SetLuminances {
+                min_lum,
+                max_lum,
+                reference_lum,
+            } => {
+                require_feature!(Feature::SetLuminances, "set_luminances");
+                let min = min_lum as f64 / 10000.;
+                let max = max_lum as f64;
+                let reference = reference_lum as f64;
+                if max <= min || reference <= min {
+                    obj.post_error(Error::InvalidLuminance, "invalid luminances");
+                    return;
+                }
+                set_once!(luminances, Luminances { min, max, reference }, "luminances");
+            }
+            Request::SetMasteringDisplayPrimaries {
+                r_x,
+                r_y,
+                g_x,
+                g_y,
+                b_x,
+                b_y,
+                w_x,
+                w_y,
+            } => {
+                require_feature!(
+                    Feature::SetMasteringDisplayPrimaries,
+                    "set_mastering_display_primaries"
+                );
+                let chromaticities = chromaticities_from_wire([r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y]);
+                set_once!(target_primaries, chromaticities, "mastering display primaries");
+            }
+            Request::SetMasteringLuminance { min_lum, max_lum } => {
+                require_feature!(Feature::SetMasteringDisplayPrimaries, "set_mastering_luminance");
+                let min = min_lum as f64 / 10000.;
+                let max = max_lum as f64;
+                if max <= min {
+                    obj.post_error(Error::InvalidLuminance, "invalid mastering luminance");
+                    return;
+                }
+                set_once!(target_luminance, (min, max), "mastering luminance");
+            }
+            Request::SetMaxCll { max_cll } => {
+                set_once!(max_cll, max_cll as f64, "max_cll");
+            }
+            Request::SetMaxFall { max_fall } => {
+                set_once!(max_fall, max_fall as f64, "max_fall");
+            }
+            Request::Create { image_description } => {
+                let (Some(transfer_function), Some(primaries)) = (params.transfer_function, params.primaries)
+                else {
+                    obj.post_error(
+                        Error::IncompleteSet,
+                        "transfer function and primaries are required",
+                    );
+                    return;
+                };
+
+                let description = ColorDescription {
+                    primaries,
+                    transfer_function,
+                    luminances: params
+                        .luminances
+                        .unwrap_or_else(|| Luminances::for_transfer_function(transfer_function)),
+                    target_primaries: params.target_primaries,
+                    target_luminance: params.target_luminance,
+                    max_cll: params.max_cll,
+                    max_fall: params.max_fall,
+                };
+                init_image_description(
+                    data_init,
+                    image_description,
+                    ImageDescription::parametric(description),
+                    false,
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpImageDescriptionV1, ImageDescriptionData, D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        _state: &mut D,
+        _: &Client,
+        obj: &WpImageDescriptionV1,
+        request: wp_image_description_v1::Request,
+        data: &ImageDescriptionData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_image_description_v1::Request::GetInformation { information } => {
+                let Some(description) = data.description.as_ref() else {
+                    obj.post_error(
+                        wp_image_description_v1::Error::NotReady,
+                        "image description is not ready",
+                    );
+                    return;
+                };
+                if !data.allow_information {
+                    obj.post_error(
+                        wp_image_description_v1::Error::NoInformation,
+                        "get_information is not allowed for this image description",
+                    );
+                    return;
+                }
+
+                let info = data_init.init(information, ());
+                send_information(&info, description);
+            }
+            wp_image_description_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpImageDescriptionInfoV1, (), D> for ColorManagementState
+where
+    D: ColorManagementHandler,
+{
+    fn request(
+        _state: &mut D,
+        _: &Client,
+        _: &WpImageDescriptionInfoV1,
+        _request: wp_image_description_info_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        // wp_image_description_info_v1 has no requests
+        unreachable!()
+    }
+}
+
+fn init_image_description<D>(
+    data_init: &mut DataInit<'_, D>,
+    id: New<WpImageDescriptionV1>,
+    description: ImageDescription,
+    allow_information: bool,
+) where
+    D: Dispatch<WpImageDescriptionV1, ImageDescriptionData> + 'static,
+{
+    let identity = description.identity();
+    let instance = data_init.init(
+        id,
+        ImageDescriptionData {
+            description: Some(description),
+            allow_information,
+        },
+    );
+    instance.ready(identity);
+}
+
+fn send_information(info: &WpImageDescriptionInfoV1, description: &ImageDescription) {
+    match description.contents() {
+        ImageDescriptionContents::Icc(icc) => match SealedFile::with_data(c"smithay-icc-profile", icc) {
+            Ok(file) => info.icc_file(file.as_fd(), file.size() as u32),
+            Err(err) => warn!(?err, "Failed to create ICC profile file"),
+        },
+        ImageDescriptionContents::Parametric(description) => {
+            let primaries = description.primaries.chromaticities();
+            send_chromaticities(&primaries, |r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y| {
+                info.primaries(r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y)
+            });
+            if let Primaries::Named(named) = description.primaries {
+                info.primaries_named(primaries_to_wire(named));
+            }
+
+            match description.transfer_function {
+                TransferFunction::Power(exp) => info.tf_power((exp * 10000.).round() as u32),
+                tf => {
+                    if let Some(tf) = tf_to_wire(tf) {
+                        info.tf_named(tf);
+                    }
+                }
+            }
+
+            let luminances = description.luminances;
+            info.luminances(
+                (luminances.min * 10000.).round() as u32,
+                luminances.max.round() as u32,
+                luminances.reference.round() as u32,
+            );
+
+            let target_primaries = description.target_primaries.unwrap_or(primaries);
+            send_chromaticities(&target_primaries, |r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y| {
+                info.target_primaries(r_x, r_y, g_x, g_y, b_x, b_y, w_x, w_y)
+            });
+            let (min, max) = description
+                .target_luminance
+                .unwrap_or((luminances.min, luminances.max));
+            info.target_luminance((min * 10000.).round() as u32, max.round() as u32);
+
+            if let Some(max_cll) = description.max_cll {
+                info.target_max_cll(max_cll.round() as u32);
+            }
+            if let Some(max_fall) = description.max_fall {
+                info.target_max_fall(max_fall.round() as u32);
+            }
+        }
+    }
+    info.done();
+}
+
+fn send_chromaticities(
+    chromaticities: &Chromaticities,
+    send: impl FnOnce(i32, i32, i32, i32, i32, i32, i32, i32),
+) {
+    let to_wire = |v: f64| (v * 1_000_000.).round() as i32;
+    send(
+        to_wire(chromaticities.red.0),
+        to_wire(chromaticities.red.1),
+        to_wire(chromaticities.green.0),
+        to_wire(chromaticities.green.1),
+        to_wire(chromaticities.blue.0),
+        to_wire(chromaticities.blue.1),
+        to_wire(chromaticities.white.0),
+        to_wire(chromaticities.white.1),
+    )
+}
+
+fn chromaticities_from_wire(values: [i32; 8]) -> Chromaticities {
+    let from_wire = |v: i32| v as f64 / 1_000_000.;
+    Chromaticities {
+        red: (from_wire(values[0]), from_wire(values[1])),
+        green: (from_wire(values[2]), from_wire(values[3])),
+        blue: (from_wire(values[4]), from_wire(values[5])),
+        white: (from_wire(values[6]), from_wire(values[7])),
+    }
+}
+
+fn primaries_to_wire(primaries: NamedPrimaries) -> wp_color_manager_v1::Primaries {
+    use wp_color_manager_v1::Primaries as Wire;
+    match primaries {
+        NamedPrimaries::Srgb => Wire::Srgb,
+        NamedPrimaries::PalM => Wire::PalM,
+        NamedPrimaries::Pal => Wire::Pal,
+        NamedPrimaries::Ntsc => Wire::Ntsc,
+        NamedPrimaries::GenericFilm => Wire::GenericFilm,
+        NamedPrimaries::Bt2020 => Wire::Bt2020,
+        NamedPrimaries::Cie1931Xyz => Wire::Cie1931Xyz,
+        NamedPrimaries::DciP3 => Wire::DciP3,
+        NamedPrimaries::DisplayP3 => Wire::DisplayP3,
+        NamedPrimaries::AdobeRgb => Wire::AdobeRgb,
+    }
+}
+
+fn primaries_from_wire(primaries: wp_color_manager_v1::Primaries) -> NamedPrimaries {
+    use wp_color_manager_v1::Primaries as Wire;
+    match primaries {
+        Wire::Srgb => NamedPrimaries::Srgb,
+        Wire::PalM => NamedPrimaries::PalM,
+        Wire::Pal => NamedPrimaries::Pal,
+        Wire::Ntsc => NamedPrimaries::Ntsc,
+        Wire::GenericFilm => NamedPrimaries::GenericFilm,
+        Wire::Bt2020 => NamedPrimaries::Bt2020,
+        Wire::Cie1931Xyz => NamedPrimaries::Cie1931Xyz,
+        Wire::DciP3 => NamedPrimaries::DciP3,
+        Wire::DisplayP3 => NamedPrimaries::DisplayP3,
+        Wire::AdobeRgb => NamedPrimaries::AdobeRgb,
+        _ => unreachable!(),
+    }
+}
+
+fn tf_to_wire(tf: TransferFunction) -> Option<wp_color_manager_v1::TransferFunction> {
+    use wp_color_manager_v1::TransferFunction as Wire;
+    Some(match tf {
+        TransferFunction::Bt1886 => Wire::Bt1886,
+        TransferFunction::Gamma22 => Wire::Gamma22,
+        TransferFunction::Gamma28 => Wire::Gamma28,
+        TransferFunction::St240 => Wire::St240,
+        TransferFunction::Linear => Wire::ExtLinear,
+        TransferFunction::Log100 => Wire::Log100,
+        TransferFunction::Log316 => Wire::Log316,
+        TransferFunction::Xvycc => Wire::Xvycc,
+        TransferFunction::Srgb => Wire::Srgb,
+        TransferFunction::ExtSrgb => Wire::ExtSrgb,
+        TransferFunction::Pq => Wire::St2084Pq,
+        TransferFunction::St428 => Wire::St428,
+        TransferFunction::Hlg => Wire::Hlg,
+        TransferFunction::Power(_) => return None,
+    })
+}
+
+fn tf_from_wire(tf: wp_color_manager_v1::TransferFunction) -> TransferFunction {
+    use wp_color_manager_v1::TransferFunction as Wire;
+    match tf {
+        Wire::Bt1886 => TransferFunction::Bt1886,
+        Wire::Gamma22 => TransferFunction::Gamma22,
+        Wire::Gamma28 => TransferFunction::Gamma28,
+        Wire::St240 => TransferFunction::St240,
+        Wire::ExtLinear => TransferFunction::Linear,
+        Wire::Log100 => TransferFunction::Log100,
+        Wire::Log316 => TransferFunction::Log316,
+        Wire::Xvycc => TransferFunction::Xvycc,
+        // the compound power 2.4 curve is what the sRGB transfer function describes
+        Wire::Srgb | Wire::CompoundPower24 => TransferFunction::Srgb,
+        Wire::ExtSrgb => TransferFunction::ExtSrgb,
+        Wire::St2084Pq => TransferFunction::Pq,
+        Wire::St428 => TransferFunction::St428,
+        Wire::Hlg => TransferFunction::Hlg,
+        _ => unreachable!(),
+    }
+}
+
+enum IccReadError {
+    BadFd,
+    OutOfFile,
+}
+
+fn read_icc_file(fd: OwnedFd, offset: u32, length: u32) -> Result<Vec<u8>, IccReadError> {
+    let mut bytes = vec![0u8; length as usize];
+
+    let mut read = 0;
+    while read < bytes.len() {
+        match rustix::io::pread(&fd, &mut bytes[read..], offset as u64 + read as u64) {
+            Ok(0) => return Err(IccReadError::OutOfFile),
+            Ok(n) => read += n,
+            Err(rustix::io::Errno::INTR) => continue,
+            Err(err) => {
+                warn!(?err, "Failed to read ICC file");
+                return Err(IccReadError::BadFd);
+            }
+        }
+    }
+
+    Ok(bytes)
+}
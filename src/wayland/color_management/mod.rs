@@ -0,0 +1,573 @@
+//! Implementation of `wp_color_management_v1` protocol
+//!
+//! This protocol allows clients to describe the color space of their surfaces using
+//! image descriptions, and to query the preferred image description of surfaces and outputs.
+//!
+//! The compositor advertises the supported render intents, features, transfer functions and
+//! primaries through [`ColorManagementCapabilities`]. Image descriptions set by clients are stored
+//! double-buffered in the [`ColorManagementSurfaceCachedState`] of the surface.
+//!
+//! [`WaylandSurfaceRenderElement`][`crate::backend::renderer::element::surface::WaylandSurfaceRenderElement`]
+//! exposes parametric image descriptions as
+//! [`Element::color_description`](crate::backend::renderer::element::Element::color_description)
+//! automatically.
+//!
+//! ### Example
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! #
+//! use wayland_server::{protocol::wl_surface::WlSurface, DisplayHandle};
+//! use smithay::{
+//!     delegate_color_management, delegate_compositor,
+//!     backend::renderer::color::ColorDescription,
+//!     wayland::compositor::{self, CompositorState, CompositorClientState, CompositorHandler},
+//!     wayland::color_management::{
+//!         ColorManagementCapabilities, ColorManagementHandler, ColorManagementState,
+//!         ColorManagementSurfaceCachedState, ImageDescription,
+//!     },
+//! };
+//!
+//! pub struct State {
+//!     compositor_state: CompositorState,
+//!     color_management_state: ColorManagementState,
+//! };
+//! struct ClientState { compositor_state: CompositorClientState }
+//! impl wayland_server::backend::ClientData for ClientState {}
+//!
+//! delegate_color_management!(State);
+//! delegate_compositor!(State);
+//!
+//! impl ColorManagementHandler for State {
+//!     fn color_management_state(&mut self) -> &mut ColorManagementState {
+//!         &mut self.color_management_state
+//!     }
+//! }
+//!
+//! impl CompositorHandler for State {
+//!    fn compositor_state(&mut self) -> &mut CompositorState {
+//!        &mut self.compositor_state
+//!    }
+//!
+//!    fn client_compositor_state<'a>(&self, client: &'a wayland_server::Client) -> &'a CompositorClientState {
+//!        &client.get_data::<ClientState>().unwrap().compositor_state
+//!    }
+//!
+//!    fn commit(&mut self, surface: &WlSurface) {
+//!        compositor::with_states(&surface, |states| {
+//!            let mut color_state = states.cached_state.get::<ColorManagementSurfaceCachedState>();
+//!            dbg!(color_state.current().image_description());
+//!        });
+//!    }
+//! }
+//!
+//! let mut display = wayland_server::Display::<State>::new().unwrap();
+//!
+//! let compositor_state = CompositorState::new::<State>(&display.handle());
+//! let mut color_management_state = ColorManagementState::new::<State>(
+//!     &display.handle(),
+//!     ColorManagementCapabilities::default(),
+//! );
+//!
+//! // Let clients know about the color space of an output
+//! # let output: smithay::output::Output = todo!();
+//! color_management_state.set_output_image_description(
+//!     &output,
+//!     ImageDescription::parametric(ColorDescription::bt2100_pq()),
+//! );
+//!
+//! let state = State {
+//!     compositor_state,
+//!     color_management_state,
+//! };
+//! ```
+
+use std::sync::{
+    atomic::{self, AtomicBool, AtomicU32},
+    Arc, Mutex,
+};
+
+use wayland_protocols::wp::color_management::v1::server::{
+    wp_color_management_output_v1::WpColorManagementOutputV1,
+    wp_color_management_surface_feedback_v1::WpColorManagementSurfaceFeedbackV1,
+    wp_color_management_surface_v1::WpColorManagementSurfaceV1,
+    wp_color_manager_v1::{self, WpColorManagerV1},
+    wp_image_description_creator_icc_v1::WpImageDescriptionCreatorIccV1,
+    wp_image_description_creator_params_v1::WpImageDescriptionCreatorParamsV1,
+    wp_image_description_info_v1::WpImageDescriptionInfoV1,
+    wp_image_description_v1::WpImageDescriptionV1,
+};
+use wayland_server::{
+    backend::GlobalId, protocol::wl_surface::WlSurface, Dispatch, DisplayHandle, GlobalDispatch, Resource,
+    Weak,
+};
+
+use super::compositor::{self, Cacheable};
+use crate::{
+    backend::renderer::color::{ColorDescription, NamedPrimaries, TransferFunction},
+    output::{Output, WeakOutput},
+};
+
+mod dispatch;
+
+pub use wp_color_manager_v1::{Feature, RenderIntent};
+
+/// Handler for the color management protocol
+pub trait ColorManagementHandler:
+    GlobalDispatch<WpColorManagerV1, ()>
+    + Dispatch<WpColorManagerV1, ()>
+    + Dispatch<WpColorManagementOutputV1, ColorManagementOutputData>
+    + Dispatch<WpColorManagementSurfaceV1, ColorManagementSurfaceData>
+    + Dispatch<WpColorManagementSurfaceFeedbackV1, ColorManagementFeedbackData>
+    + Dispatch<WpImageDescriptionCreatorIccV1, IccCreatorData>
+    + Dispatch<WpImageDescriptionCreatorParamsV1, ParametricCreatorData>
+    + Dispatch<WpImageDescriptionV1, ImageDescriptionData>
+    + Dispatch<WpImageDescriptionInfoV1, ()>
+    + 'static
+{
+    /// [ColorManagementState] getter
+    fn color_management_state(&mut self) -> &mut ColorManagementState;
+}
+
+/// Render intents, features, transfer functions and primaries supported by the compositor
+#[derive(Debug, Clone)]
+pub struct ColorManagementCapabilities {
+    /// Supported render intents
+    ///
+    /// [`RenderIntent::Perceptual`] is required by the protocol.
+    pub render_intents: Vec<RenderIntent>,
+    /// Supported features
+    pub features: Vec<Feature>,
+    /// Supported named transfer functions
+    ///
+    /// [`TransferFunction::Power`] is controlled by [`Feature::SetTfPower`] instead.
+    pub transfer_functions: Vec<TransferFunction>,
+    /// Supported named primaries
+    pub primaries: Vec<NamedPrimaries>,
+}
+
+impl Default for ColorManagementCapabilities {
+    fn default() -> Self {
+        ColorManagementCapabilities {
+            render_intents: vec![RenderIntent::Perceptual],
+            features: vec![
+                Feature::Parametric,
+                Feature::SetPrimaries,
+                Feature::SetLuminances,
+                Feature::SetMasteringDisplayPrimaries,
+            ],
+            transfer_functions: vec![
+                TransferFunction::Srgb,
+                TransferFunction::Gamma22,
+                TransferFunction::Linear,
+                TransferFunction::Pq,
+                TransferFunction::Hlg,
+            ],
+            primaries: vec![
+                NamedPrimaries::Srgb,
+                NamedPrimaries::Bt2020,
+                NamedPrimaries::DisplayP3,
+            ],
+        }
+    }
+}
+
+/// Contents of an [`ImageDescription`]
+// always stored behind an `Arc` in `ImageDescription`
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum ImageDescriptionContents {
+    /// Parametric description
+    Parametric(ColorDescription),
+    /// ICC profile
+    Icc(Arc<[u8]>),
+}
+
+#[derive(Debug)]
+struct ImageDescriptionInner {
+    identity: u32,
+    contents: ImageDescriptionContents,
+}
+
+/// Description of the color space of some content
+///
+/// Descriptions compare equal, if they were created from the same call to
+/// [`ImageDescription::new`] or the same client request.
+#[derive(Debug, Clone)]
+pub struct ImageDescription {
+    inner: Arc<ImageDescriptionInner>,
+}
+
+impl PartialEq for ImageDescription {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+static IDENTITY: AtomicU32 = AtomicU32::new(1);
+
+impl ImageDescription {
+    /// Create a new image description
+    pub fn new(contents: ImageDescriptionContents) -> Self {
+        let mut identity = IDENTITY.fetch_add(1, atomic::Ordering::Relaxed);
+        // the identity has to be non-zero
+        if identity == 0 {
+            identity = IDENTITY.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+
+        ImageDescription {
+            inner: Arc::new(ImageDescriptionInner { identity, contents }),
+        }
+    }
+
+    /// Create a new parametric image description
+    pub fn parametric(description: ColorDescription) -> Self {
+        Self::new(ImageDescriptionContents::Parametric(description))
+    }
+
+    /// Identity of the image description as sent to clients
+    pub fn identity(&self) -> u32 {
+        self.inner.identity
+    }
+
+    /// Contents of the image description
+    pub fn contents(&self) -> &ImageDescriptionContents {
+        &self.inner.contents
+    }
+
+    /// The parametric description, if this is not an ICC based description
+    pub fn color_description(&self) -> Option<&ColorDescription> {
+        match &self.inner.contents {
+            ImageDescriptionContents::Parametric(description) => Some(description),
+            ImageDescriptionContents::Icc(_) => None,
+        }
+    }
+
+    /// The ICC profile, if this is an ICC based description
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        match &self.inner.contents {
+            ImageDescriptionContents::Parametric(_) => None,
+            ImageDescriptionContents::Icc(icc) => Some(icc),
+        }
+    }
+}
+
+/// Color management state of a surface
+///
+/// ```no_run
+/// use smithay::wayland::compositor;
+/// use smithay::wayland::color_management::ColorManagementSurfaceCachedState;
+///
+/// # let wl_surface = todo!();
+/// compositor::with_states(&wl_surface, |states| {
+///     let mut color_state = states.cached_state.get::<ColorManagementSurfaceCachedState>();
+///     dbg!(color_state.current().image_description());
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ColorManagementSurfaceCachedState {
+    description: Option<(ImageDescription, RenderIntent)>,
+}
+
+impl ColorManagementSurfaceCachedState {
+    /// Image description of the surface, if set by the client
+    pub fn image_description(&self) -> Option<&ImageDescription> {
+        self.description.as_ref().map(|(description, _)| description)
+    }
+
+    /// Render intent requested by the client, if an image description is set
+    pub fn render_intent(&self) -> Option<RenderIntent> {
+        self.description.as_ref().map(|(_, intent)| *intent)
+    }
+}
+
+impl Cacheable for ColorManagementSurfaceCachedState {
+    fn commit(&mut self, _dh: &DisplayHandle) -> Self {
+        self.clone()
+    }
+
+    fn merge_into(self, into: &mut Self, _dh: &DisplayHandle) {
+        *into = self;
+    }
+}
+
+#[derive(Debug, Default)]
+struct ColorManagementSurfaceAttached(AtomicBool);
+
+#[derive(Debug, Default)]
+struct PreferredImageDescription(Mutex<Option<ImageDescription>>);
+
+/// User data of [WpColorManagementOutputV1] objects
+#[derive(Debug)]
+pub struct ColorManagementOutputData {
+    output: WeakOutput,
+}
+
+/// User data of [WpColorManagementSurfaceV1] objects
+#[derive(Debug)]
+pub struct ColorManagementSurfaceData {
+    surface: Weak<WlSurface>,
+}
+
+/// User data of [WpColorManagementSurfaceFeedbackV1] objects
+#[derive(Debug)]
+pub struct ColorManagementFeedbackData {
+    surface: Weak<WlSurface>,
+}
+
+/// User data of [WpImageDescriptionCreatorIccV1] objects
+#[derive(Debug, Default)]
+pub struct IccCreatorData {
+    icc: Mutex<Option<Arc<[u8]>>>,
+}
+
+/// User data of [WpImageDescriptionCreatorParamsV1] objects
+#[derive(Debug, Default)]
+pub struct ParametricCreatorData {
+    params: Mutex<dispatch::PendingParameters>,
+}
+
+/// User data of [WpImageDescriptionV1] objects
+#[derive(Debug)]
+pub struct ImageDescriptionData {
+    description: Option<ImageDescription>,
+    allow_information: bool,
+}
+
+impl ImageDescriptionData {
+    /// The image description, if it was created successfully
+    pub fn image_description(&self) -> Option<&ImageDescription> {
+        self.description.as_ref()
+    }
+}
+
+/// State of the [WpColorManagerV1] global
+#[derive(Debug)]
+pub struct ColorManagementState {
+    global: GlobalId,
+    capabilities: ColorManagementCapabilities,
+    default_description: ImageDescription,
+    output_descriptions: Vec<(WeakOutput, ImageDescription)>,
+    outputs: Vec<WpColorManagementOutputV1>,
+    feedbacks: Vec<WpColorManagementSurfaceFeedbackV1>,
+}
+
+impl ColorManagementState {
+    /// Register new [WpColorManagerV1] global
+    pub fn new<D: ColorManagementHandler>(
+        display: &DisplayHandle,
+        capabilities: ColorManagementCapabilities,
+    ) -> Self {
+        let global = display.create_global::<D, WpColorManagerV1, _>(1, ());
+
+        ColorManagementState {
+            global,
+            capabilities,
+            default_description: ImageDescription::parametric(ColorDescription::srgb()),
+            output_descriptions: Vec::new(),
+            outputs: Vec::new(),
+            feedbacks: Vec::new(),
+        }
+    }
+
+    /// Returns the [WpColorManagerV1] global id
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Returns the capabilities advertised to clients
+    pub fn capabilities(&self) -> &ColorManagementCapabilities {
+        &self.capabilities
+    }
+
+    /// Returns the image description used for outputs and surfaces without a specific one
+    ///
+    /// Defaults to sRGB.
+    pub fn default_image_description(&self) -> &ImageDescription {
+        &self.default_description
+    }
+
+    /// Set the image description used for outputs and surfaces without a specific one
+    pub fn set_default_image_description(&mut self, description: ImageDescription) {
+        if self.default_description == description {
+            return;
+        }
+        self.default_description = description;
+
+        for output in &self.outputs {
+            let data = output.data::<ColorManagementOutputData>().unwrap();
+            let specific = data.output.upgrade().is_some_and(|output| {
+                self.output_descriptions
+                    .iter()
+                    .any(|(o, _)| o.upgrade().as_ref() == Some(&output))
+            });
+            if !specific {
+                output.image_description_changed();
+            }
+        }
+        for feedback in &self.feedbacks {
+            let data = feedback.data::<ColorManagementFeedbackData>().unwrap();
+            let Ok(surface) = data.surface.upgrade() else {
+                continue;
+            };
+            let specific = compositor::with_states(&surface, |states| {
+                states
+                    .data_map
+                    .get::<PreferredImageDescription>()
+                    .is_some_and(|preferred| preferred.0.lock().unwrap().is_some())
+            });
+            if !specific {
+                feedback.preferred_changed(self.default_description.identity());
+            }
+        }
+    }
+
+    /// Returns the image description of an output
+    pub fn output_image_description(&self, output: &Output) -> ImageDescription {
+        self.output_descriptions
+            .iter()
+            .find(|(o, _)| o.upgrade().as_ref() == Some(output))
+            .map(|(_, description)| description.clone())
+            .unwrap_or_else(|| self.default_description.clone())
+    }
+
+    /// Set the image description of an output
+    ///
+    /// This should describe the color space the compositor is outputting in.
+    pub fn set_output_image_description(&mut self, output: &Output, description: ImageDescription) {
+        self.output_descriptions.retain(|(o, _)| o.is_alive());
+        match self
+            .output_descriptions
+            .iter_mut()
+            .find(|(o, _)| o.upgrade().as_ref() == Some(output))
+        {
+            Some((_, current)) if *current == description => return,
+            Some((_, current)) => *current = description,
+            None => self.output_descriptions.push((output.downgrade(), description)),
+        }
+
+        for instance in &self.outputs {
+            let data = instance.data::<ColorManagementOutputData>().unwrap();
+            if data.output.upgrade().as_ref() == Some(output) {
+                instance.image_description_changed();
+            }
+        }
+    }
+
+    /// Returns the preferred image description of a surface
+    pub fn preferred_image_description(&self, surface: &WlSurface) -> ImageDescription {
+        compositor::with_states(surface, |states| {
+            states
+                .data_map
+                .get::<PreferredImageDescription>()
+                .and_then(|preferred| preferred.0.lock().unwrap().clone())
+        })
+        .unwrap_or_else(|| self.default_description.clone())
+    }
+
+    /// Set the preferred image description of a surface
+    ///
+    /// This is usually the image description of the output the surface is primarily shown on.
+    /// If `None` the [default image description](Self::default_image_description) is used.
+    pub fn set_preferred_image_description(
+        &mut self,
+        surface: &WlSurface,
+        description: Option<ImageDescription>,
+    ) {
+        let old = self.preferred_image_description(surface);
+        compositor::with_states(surface, |states| {
+            let preferred = states
+                .data_map
+                .get_or_insert_threadsafe(PreferredImageDescription::default);
+            *preferred.0.lock().unwrap() = description;
+        });
+        let new = self.preferred_image_description(surface);
+        if old == new {
+            return;
+        }
+
+        for feedback in &self.feedbacks {
+            let data = feedback.data::<ColorManagementFeedbackData>().unwrap();
+            if data.surface.upgrade().ok().as_ref() == Some(surface) {
+                feedback.preferred_changed(new.identity());
+            }
+        }
+    }
+}
+
+/// Macro to delegate implementation of the color management protocol
+#[macro_export]
+macro_rules! delegate_color_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        type __WpColorManagerV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_color_manager_v1::WpColorManagerV1;
+        type __WpColorManagementOutputV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_color_management_output_v1::WpColorManagementOutputV1;
+        type __WpColorManagementSurfaceV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_color_management_surface_v1::WpColorManagementSurfaceV1;
+        type __WpColorManagementSurfaceFeedbackV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_color_management_surface_feedback_v1::WpColorManagementSurfaceFeedbackV1;
+        type __WpImageDescriptionCreatorIccV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_image_description_creator_icc_v1::WpImageDescriptionCreatorIccV1;
+        type __WpImageDescriptionCreatorParamsV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_image_description_creator_params_v1::WpImageDescriptionCreatorParamsV1;
+        type __WpImageDescriptionV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_image_description_v1::WpImageDescriptionV1;
+        type __WpImageDescriptionInfoV1 =
+            $crate::reexports::wayland_protocols::wp::color_management::v1::server::wp_image_description_info_v1::WpImageDescriptionInfoV1;
+
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpColorManagerV1: ()
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpColorManagerV1: ()
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpColorManagementOutputV1: $crate::wayland::color_management::ColorManagementOutputData
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpColorManagementSurfaceV1: $crate::wayland::color_management::ColorManagementSurfaceData
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpColorManagementSurfaceFeedbackV1: $crate::wayland::color_management::ColorManagementFeedbackData
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpImageDescriptionCreatorIccV1: $crate::wayland::color_management::IccCreatorData
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpImageDescriptionCreatorParamsV1: $crate::wayland::color_management::ParametricCreatorData
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpImageDescriptionV1: $crate::wayland::color_management::ImageDescriptionData
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty:
+            [
+                __WpImageDescriptionInfoV1: ()
+            ] => $crate::wayland::color_management::ColorManagementState
+        );
+    };
+}
@@ -94,7 +94,7 @@ impl Drop for ForeignToplevelHandleInner {
 }
 
 /// Weak version of [ForeignToplevelHandle]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ForeignToplevelWeakHandle {
     inner: std::sync::Weak<(Mutex<ForeignToplevelHandleInner>, UserDataMap)>,
 }
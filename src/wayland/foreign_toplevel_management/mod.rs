@@ -0,0 +1,876 @@
+//! Foreign toplevel management
+//!
+//! This module implements the `wlr-foreign-toplevel-management-unstable-v1` protocol.
+//!
+//! Other than the read-only [foreign toplevel list](crate::wayland::foreign_toplevel_list),
+//! this protocol allows privileged clients, like taskbars and docks, to request state changes
+//! of toplevels, e.g. to minimize, maximize, activate or close them.
+//!
+//! The compositor creates a [`ForeignToplevelManagementHandle`] for each toplevel and keeps it
+//! up-to-date, either manually, or using [`ForeignToplevelManagementHandle::update_from_toplevel`]
+//! and [`ForeignToplevelManagementHandle::update_from_x11`].
+//! Requests of clients are forwarded to the [`ForeignToplevelManagementHandler`].
+//!
+//! ```no_run
+//! use smithay::wayland::foreign_toplevel_management::{
+//!     ForeignToplevelManagementHandle, ForeignToplevelManagementHandler, ForeignToplevelManagementState,
+//! };
+//!
+//! pub struct State {
+//!     foreign_toplevel_management: ForeignToplevelManagementState,
+//! }
+//!
+//! smithay::delegate_foreign_toplevel_management!(State);
+//!
+//! impl ForeignToplevelManagementHandler for State {
+//!     fn foreign_toplevel_management_state(&mut self) -> &mut ForeignToplevelManagementState {
+//!         &mut self.foreign_toplevel_management
+//!     }
+//!
+//!     fn close(&mut self, handle: ForeignToplevelManagementHandle) {
+//!         // close the window associated with the handle
+//!     }
+//! }
+//!
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! # let display_handle = display.handle();
+//! let mut state = State {
+//!     foreign_toplevel_management: ForeignToplevelManagementState::new::<State>(&display_handle),
+//! };
+//!
+//! let handle = state
+//!     .foreign_toplevel_management
+//!     .new_toplevel::<State>("Window Title", "com.example");
+//!
+//! // Handle can be used to update the title, app_id, state and outputs
+//! handle.send_title("Window title has changed");
+//! handle.send_done();
+//!
+//! // Once the window is gone, close the handle
+//! state.foreign_toplevel_management.remove_toplevel(&handle);
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols_wlr::foreign_toplevel::v1::server::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    protocol::{wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, Weak,
+};
+
+use crate::{
+    output::Output,
+    utils::{user_data::UserDataMap, Logical, Rectangle},
+    wayland::{
+        compositor,
+        shell::xdg::{ToplevelSurface, XdgToplevelSurfaceData},
+    },
+};
+
+#[cfg(feature = "xwayland")]
+use crate::xwayland::X11Surface;
+
+pub use zwlr_foreign_toplevel_handle_v1::State as ToplevelState;
+
+const VERSION: u32 = 3;
+
+/// Handler for the foreign toplevel management protocol
+///
+/// All requests have default implementations ignoring them.
+/// Requests for handles, that have already been closed, are not forwarded.
+pub trait ForeignToplevelManagementHandler:
+    GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelManagerGlobalData>
+    + Dispatch<ZwlrForeignToplevelManagerV1, ()>
+    + Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelManagementHandle>
+    + 'static
+{
+    /// [ForeignToplevelManagementState] getter
+    fn foreign_toplevel_management_state(&mut self) -> &mut ForeignToplevelManagementState;
+
+    /// A client requested the toplevel to be maximized
+    fn set_maximized(&mut self, handle: ForeignToplevelManagementHandle) {
+        let _ = handle;
+    }
+
+    /// A client requested the toplevel to be unmaximized
+    fn unset_maximized(&mut self, handle: ForeignToplevelManagementHandle) {
+        let _ = handle;
+    }
+
+    /// A client requested the toplevel to be minimized
+    fn set_minimized(&mut self, handle: ForeignToplevelManagementHandle) {
+        let _ = handle;
+    }
+
+    /// A client requested the toplevel to be unminimized
+    fn unset_minimized(&mut self, handle: ForeignToplevelManagementHandle) {
+        let _ = handle;
+    }
+
+    /// A client requested the toplevel to be fullscreened
+    ///
+    /// If `output` is `None`, the compositor should choose an output.
+    fn set_fullscreen(&mut self, handle: ForeignToplevelManagementHandle, output: Option<Output>) {
+        let _ = (handle, output);
+    }
+
+    /// A client requested the toplevel to leave fullscreen
+    fn unset_fullscreen(&mut self, handle: ForeignToplevelManagementHandle) {
+        let _ = handle;
+    }
+
+    /// A client requested the toplevel to be activated on the given seat
+    fn activate(&mut self, handle: ForeignToplevelManagementHandle, seat: WlSeat) {
+        let _ = (handle, seat);
+    }
+
+    /// A client requested the toplevel to be closed
+    fn close(&mut self, handle: ForeignToplevelManagementHandle) {
+        let _ = handle;
+    }
+
+    /// A client set the rectangle representing the toplevel relative to the given surface
+    ///
+    /// This is commonly used as a target for minimize animations.
+    /// An empty rectangle unsets the previously set rectangle.
+    fn set_rectangle(
+        &mut self,
+        handle: ForeignToplevelManagementHandle,
+        surface: WlSurface,
+        rectangle: Rectangle<i32, Logical>,
+    ) {
+        let _ = (handle, surface, rectangle);
+    }
+}
+
+#[derive(Debug)]
+struct ForeignToplevelManagementHandleInner {
+    title: String,
+    app_id: String,
+    states: Vec<ToplevelState>,
+    outputs: Vec<Output>,
+    parent: Option<ForeignToplevelManagementWeakHandle>,
+    // Each ZwlrForeignToplevelHandleV1 contains the handle in it's user data,
+    // so this ref has to be weak
+    instances: Vec<Weak<ZwlrForeignToplevelHandleV1>>,
+    closed: bool,
+}
+
+impl ForeignToplevelManagementHandleInner {
+    fn send_closed(&mut self) {
+        if self.closed {
+            return;
+        }
+
+        self.closed = true;
+        // drain to prevent any events from being sent to closed handles
+        for toplevel in self.instances.drain(..) {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                toplevel.closed();
+            }
+        }
+    }
+}
+
+impl Drop for ForeignToplevelManagementHandleInner {
+    fn drop(&mut self) {
+        self.send_closed()
+    }
+}
+
+/// Weak version of [ForeignToplevelManagementHandle]
+#[derive(Debug, Clone, Default)]
+pub struct ForeignToplevelManagementWeakHandle {
+    inner: std::sync::Weak<(Mutex<ForeignToplevelManagementHandleInner>, UserDataMap)>,
+}
+
+impl ForeignToplevelManagementWeakHandle {
+    /// Upgrade weak [ForeignToplevelManagementWeakHandle] to strong [ForeignToplevelManagementHandle]
+    pub fn upgrade(&self) -> Option<ForeignToplevelManagementHandle> {
+        Some(ForeignToplevelManagementHandle {
+            inner: self.inner.upgrade()?,
+        })
+    }
+}
+
+/// Handle of a toplevel, used to update its properties after initial handle creation
+#[derive(Debug, Clone)]
+pub struct ForeignToplevelManagementHandle {
+    inner: Arc<(Mutex<ForeignToplevelManagementHandleInner>, UserDataMap)>,
+}
+
+impl PartialEq for ForeignToplevelManagementHandle {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl ForeignToplevelManagementHandle {
+    fn new(title: String, app_id: String) -> Self {
+        Self {
+            inner: Arc::new((
+                Mutex::new(ForeignToplevelManagementHandleInner {
+                    title,
+                    app_id,
+                    states: Vec::new(),
+                    outputs: Vec::new(),
+                    parent: None,
+                    instances: Vec::new(),
+                    closed: false,
+                }),
+                UserDataMap::new(),
+            )),
+        }
+    }
+
+    /// Downgrade strong [ForeignToplevelManagementHandle] to weak [ForeignToplevelManagementWeakHandle]
+    pub fn downgrade(&self) -> ForeignToplevelManagementWeakHandle {
+        ForeignToplevelManagementWeakHandle {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Attempt to retrieve [ForeignToplevelManagementHandle] from an existing resource
+    pub fn from_resource(resource: &ZwlrForeignToplevelHandleV1) -> Option<Self> {
+        resource.data::<Self>().cloned()
+    }
+
+    /// Retrieve [`ZwlrForeignToplevelHandleV1`]
+    /// instances for this handle.
+    pub fn resources(&self) -> Vec<ZwlrForeignToplevelHandleV1> {
+        let inner = self.inner.0.lock().unwrap();
+        inner
+            .instances
+            .iter()
+            .filter_map(|weak| weak.upgrade().ok())
+            .collect()
+    }
+
+    /// Retrieve [`ZwlrForeignToplevelHandleV1`]
+    /// instances for this handle of a given [`Client`].
+    pub fn resources_for_client(&self, client: &Client) -> Vec<ZwlrForeignToplevelHandleV1> {
+        self.resources()
+            .into_iter()
+            .filter(|handle| handle.client().as_ref().is_some_and(|c| c == client))
+            .collect()
+    }
+
+    /// Access the [UserDataMap] associated with this [ForeignToplevelManagementHandle]
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.inner.1
+    }
+
+    /// The title of the toplevel has changed.
+    ///
+    /// [Self::send_done] has to be called to finalize the update
+    pub fn send_title(&self, title: &str) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.title == title {
+            return;
+        }
+
+        inner.title = title.to_string();
+
+        for toplevel in inner.instances.iter() {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                toplevel.title(title.to_string());
+            }
+        }
+    }
+
+    /// The app_id of the toplevel has changed.
+    ///
+    /// [Self::send_done] has to be called to finalize the update
+    pub fn send_app_id(&self, app_id: &str) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.app_id == app_id {
+            return;
+        }
+
+        inner.app_id = app_id.to_string();
+
+        for toplevel in inner.instances.iter() {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                toplevel.app_id(app_id.to_string());
+            }
+        }
+    }
+
+    /// The states of the toplevel have changed.
+    ///
+    /// [Self::send_done] has to be called to finalize the update
+    pub fn send_states(&self, states: &[ToplevelState]) {
+        let mut inner = self.inner.0.lock().unwrap();
+
+        let mut new_states = Vec::with_capacity(states.len());
+        for state in states {
+            if !new_states.contains(state) {
+                new_states.push(*state);
+            }
+        }
+        if inner.states == new_states {
+            return;
+        }
+
+        inner.states = new_states;
+
+        for toplevel in inner.instances.iter() {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                toplevel.state(encode_states(&inner.states, toplevel.version()));
+            }
+        }
+    }
+
+    /// The toplevel has entered an output.
+    ///
+    /// [Self::send_done] has to be called to finalize the update
+    pub fn send_output_enter(&self, output: &Output) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.outputs.contains(output) {
+            return;
+        }
+
+        inner.outputs.push(output.clone());
+
+        for toplevel in inner.instances.iter() {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                let Some(client) = toplevel.client() else {
+                    continue;
+                };
+                for wl_output in output.client_outputs(&client) {
+                    toplevel.output_enter(&wl_output);
+                }
+            }
+        }
+    }
+
+    /// The toplevel has left an output.
+    ///
+    /// [Self::send_done] has to be called to finalize the update
+    pub fn send_output_leave(&self, output: &Output) {
+        let mut inner = self.inner.0.lock().unwrap();
+        let Some(pos) = inner.outputs.iter().position(|o| o == output) else {
+            return;
+        };
+
+        inner.outputs.remove(pos);
+
+        for toplevel in inner.instances.iter() {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                let Some(client) = toplevel.client() else {
+                    continue;
+                };
+                for wl_output in output.client_outputs(&client) {
+                    toplevel.output_leave(&wl_output);
+                }
+            }
+        }
+    }
+
+    /// The parent of the toplevel has changed.
+    ///
+    /// [Self::send_done] has to be called to finalize the update
+    pub fn send_parent(&self, parent: Option<&ForeignToplevelManagementHandle>) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.parent.as_ref().and_then(|p| p.upgrade()).as_ref() == parent {
+            return;
+        }
+
+        inner.parent = parent.map(|p| p.downgrade());
+        let instances = inner.instances.clone();
+        // looking up the resources of the parent requires its lock
+        std::mem::drop(inner);
+
+        for toplevel in instances {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                send_parent(&toplevel, parent);
+            }
+        }
+    }
+
+    /// This event is should be sent after all changes in the toplevel state have been sent.
+    pub fn send_done(&self) {
+        let inner = self.inner.0.lock().unwrap();
+        for toplevel in inner.instances.iter() {
+            if let Ok(toplevel) = toplevel.upgrade() {
+                toplevel.done();
+            }
+        }
+    }
+
+    /// The toplevel has been closed
+    pub fn send_closed(&self) {
+        self.inner.0.lock().unwrap().send_closed();
+    }
+
+    /// Update title, app_id and states from a [`ToplevelSurface`]
+    ///
+    /// Maximized, fullscreen and activated states are taken from the current state of the toplevel,
+    /// while the minimized state, which xdg-shell has no notion of, is kept as is.
+    ///
+    /// Sends [`done`](Self::send_done) if anything changed.
+    pub fn update_from_toplevel(&self, toplevel: &ToplevelSurface) {
+        use crate::reexports::wayland_protocols::xdg::shell::server::xdg_toplevel;
+
+        let (title, app_id) = compositor::with_states(toplevel.wl_surface(), |states| {
+            let attributes = states
+                .data_map
+                .get::<XdgToplevelSurfaceData>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            (
+                attributes.title.clone().unwrap_or_default(),
+                attributes.app_id.clone().unwrap_or_default(),
+            )
+        });
+        let current = toplevel.current_state();
+
+        let mut states = Vec::new();
+        if current.states.contains(xdg_toplevel::State::Maximized) {
+            states.push(ToplevelState::Maximized);
+        }
+        if self.states().contains(&ToplevelState::Minimized) {
+            states.push(ToplevelState::Minimized);
+        }
+        if current.states.contains(xdg_toplevel::State::Activated) {
+            states.push(ToplevelState::Activated);
+        }
+        if current.states.contains(xdg_toplevel::State::Fullscreen) {
+            states.push(ToplevelState::Fullscreen);
+        }
+
+        self.update(&title, &app_id, &states);
+    }
+
+    /// Update title, app_id and states from a [`X11Surface`]
+    ///
+    /// The class of the window is used as the app_id.
+    ///
+    /// Sends [`done`](Self::send_done) if anything changed.
+    #[cfg(feature = "xwayland")]
+    pub fn update_from_x11(&self, surface: &X11Surface) {
+        let mut states = Vec::new();
+        if surface.is_maximized() {
+            states.push(ToplevelState::Maximized);
+        }
+        if surface.is_minimized() {
+            states.push(ToplevelState::Minimized);
+        }
+        if surface.is_activated() {
+            states.push(ToplevelState::Activated);
+        }
+        if surface.is_fullscreen() {
+            states.push(ToplevelState::Fullscreen);
+        }
+
+        self.update(&surface.title(), &surface.class(), &states);
+    }
+
+    fn update(&self, title: &str, app_id: &str, states: &[ToplevelState]) {
+        let changed = {
+            let inner = self.inner.0.lock().unwrap();
+            inner.title != title || inner.app_id != app_id || inner.states != states
+        };
+        if !changed {
+            return;
+        }
+
+        self.send_title(title);
+        self.send_app_id(app_id);
+        self.send_states(states);
+        self.send_done();
+    }
+
+    /// The title of the toplevel
+    pub fn title(&self) -> String {
+        self.inner.0.lock().unwrap().title.clone()
+    }
+
+    /// The app id of the toplevel
+    pub fn app_id(&self) -> String {
+        self.inner.0.lock().unwrap().app_id.clone()
+    }
+
+    /// The states of the toplevel
+    pub fn states(&self) -> Vec<ToplevelState> {
+        self.inner.0.lock().unwrap().states.clone()
+    }
+
+    /// The outputs the toplevel is currently visible on
+    pub fn outputs(&self) -> Vec<Output> {
+        self.inner.0.lock().unwrap().outputs.clone()
+    }
+
+    /// The parent of the toplevel
+    pub fn parent(&self) -> Option<ForeignToplevelManagementHandle> {
+        self.inner.0.lock().unwrap().parent.as_ref()?.upgrade()
+    }
+
+    /// The toplevel has been closed
+    pub fn is_closed(&self) -> bool {
+        self.inner.0.lock().unwrap().closed
+    }
+
+    fn init_new_instance(&self, toplevel: ZwlrForeignToplevelHandleV1) {
+        debug_assert!(
+            !self.is_closed(),
+            "No handles should ever be created for closed toplevel"
+        );
+
+        toplevel.title(self.title());
+        toplevel.app_id(self.app_id());
+        toplevel.state(encode_states(&self.states(), toplevel.version()));
+        if let Some(client) = toplevel.client() {
+            for output in self.outputs() {
+                for wl_output in output.client_outputs(&client) {
+                    toplevel.output_enter(&wl_output);
+                }
+            }
+        }
+        if let Some(parent) = self.parent() {
+            send_parent(&toplevel, Some(&parent));
+        }
+        toplevel.done();
+
+        self.inner.0.lock().unwrap().instances.push(toplevel.downgrade());
+    }
+
+    fn remove_instance(&self, instance: &ZwlrForeignToplevelHandleV1) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if let Some(pos) = inner.instances.iter().position(|i| i == instance) {
+            inner.instances.remove(pos);
+        }
+    }
+}
+
+fn encode_states(states: &[ToplevelState], version: u32) -> Vec<u8> {
+    states
+        .iter()
+        // the fullscreen state was added together with the set_fullscreen request
+        .filter(|state| {
+            **state != ToplevelState::Fullscreen
+                || version >= zwlr_foreign_toplevel_handle_v1::REQ_SET_FULLSCREEN_SINCE
+        })
+        .flat_map(|state| (*state as u32).to_ne_bytes())
+        .collect()
+}
+
+fn send_parent(toplevel: &ZwlrForeignToplevelHandleV1, parent: Option<&ForeignToplevelManagementHandle>) {
+    if toplevel.version() < zwlr_foreign_toplevel_handle_v1::EVT_PARENT_SINCE {
+        return;
+    }
+
+    match parent {
+        Some(parent) => {
+            let Some(client) = toplevel.client() else {
+                return;
+            };
+            // Only announce the parent, once the client knows about it
+            if let Some(parent) = parent.resources_for_client(&client).first() {
+                toplevel.parent(Some(parent));
+            }
+        }
+        None => toplevel.parent(None),
+    }
+}
+
+/// State of the [ZwlrForeignToplevelManagerV1] global
+#[derive(Debug)]
+pub struct ForeignToplevelManagementState {
+    global: GlobalId,
+    toplevels: Vec<ForeignToplevelManagementWeakHandle>,
+    managers: Vec<ZwlrForeignToplevelManagerV1>,
+    dh: DisplayHandle,
+}
+
+impl ForeignToplevelManagementState {
+    /// Register new [ZwlrForeignToplevelManagerV1] global
+    pub fn new<D: ForeignToplevelManagementHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register new [ZwlrForeignToplevelManagerV1] global with filter
+    ///
+    /// As this protocol allows clients to control any toplevel, it is
+    /// recommended to restrict the global to privileged clients.
+    pub fn new_with_filter<D: ForeignToplevelManagementHandler>(
+        dh: &DisplayHandle,
+        can_view: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ZwlrForeignToplevelManagerV1, _>(
+            VERSION,
+            ForeignToplevelManagerGlobalData {
+                filter: Box::new(can_view),
+            },
+        );
+
+        Self {
+            global,
+            toplevels: Vec::new(),
+            managers: Vec::new(),
+            dh: dh.clone(),
+        }
+    }
+
+    /// [ZwlrForeignToplevelManagerV1] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Create a handle for a new toplevel and announce it to all clients
+    pub fn new_toplevel<D: ForeignToplevelManagementHandler>(
+        &mut self,
+        title: impl Into<String>,
+        app_id: impl Into<String>,
+    ) -> ForeignToplevelManagementHandle {
+        let handle = ForeignToplevelManagementHandle::new(title.into(), app_id.into());
+
+        for manager in &self.managers {
+            let Ok(client) = self.dh.get_client(manager.id()) else {
+                continue;
+            };
+
+            let Ok(toplevel) = client.create_resource::<ZwlrForeignToplevelHandleV1, _, D>(
+                &self.dh,
+                manager.version(),
+                handle.clone(),
+            ) else {
+                continue;
+            };
+
+            manager.toplevel(&toplevel);
+            handle.init_new_instance(toplevel);
+        }
+
+        self.toplevels.push(handle.downgrade());
+
+        handle
+    }
+
+    /// Remove the toplevel, and send closed event if needed
+    ///
+    /// Alternatively, you can just call [ForeignToplevelManagementHandle::send_closed] and the handle will be
+    /// lazely cleaned up, either by [Self::cleanup_closed_handles], or during next global bind
+    pub fn remove_toplevel(&mut self, handle: &ForeignToplevelManagementHandle) {
+        handle.send_closed();
+
+        if let Some(pos) = self
+            .toplevels
+            .iter()
+            .filter_map(|h| h.upgrade())
+            .position(|h| &h == handle)
+        {
+            self.toplevels.remove(pos);
+        }
+    }
+
+    /// Auto cleanup closed handles
+    ///
+    /// This is not needed if you already manually remove each handle with [Self::remove_toplevel]
+    pub fn cleanup_closed_handles(&mut self) {
+        self.toplevels.retain(|handle| {
+            let Some(handle) = handle.upgrade() else {
+                return false;
+            };
+            !handle.is_closed()
+        });
+    }
+
+    /// Notify the state about a newly bound [`WlOutput`]
+    ///
+    /// This sends `output_enter` for all toplevels visible on the output,
+    /// should be called from [`OutputHandler::output_bound`](crate::wayland::output::OutputHandler::output_bound).
+    pub fn output_bound(&mut self, output: &Output, wl_output: &WlOutput) {
+        let Some(client) = wl_output.client() else {
+            return;
+        };
+
+        for handle in self.toplevels.iter().filter_map(|h| h.upgrade()) {
+            if handle.is_closed() || !handle.outputs().contains(output) {
+                continue;
+            }
+            for toplevel in handle.resources_for_client(&client) {
+                toplevel.output_enter(wl_output);
+                toplevel.done();
+            }
+        }
+    }
+}
+
+/// Glabal data of [ZwlrForeignToplevelManagerV1]
+pub struct ForeignToplevelManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for ForeignToplevelManagerGlobalData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ForeignToplevelManagerGlobalData")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: ForeignToplevelManagementHandler>
+    GlobalDispatch<ZwlrForeignToplevelManagerV1, ForeignToplevelManagerGlobalData, D>
+    for ForeignToplevelManagementState
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        client: &Client,
+        resource: New<ZwlrForeignToplevelManagerV1>,
+        _global_data: &ForeignToplevelManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        let state = state.foreign_toplevel_management_state();
+
+        state.toplevels.retain(|handle| {
+            let Some(handle) = handle.upgrade() else {
+                // Cleanup dead handles
+                return false;
+            };
+
+            if handle.is_closed() {
+                // Cleanup closed handles
+                return false;
+            }
+
+            if let Ok(toplevel) = client.create_resource::<ZwlrForeignToplevelHandleV1, _, D>(
+                dh,
+                manager.version(),
+                handle.clone(),
+            ) {
+                manager.toplevel(&toplevel);
+                handle.init_new_instance(toplevel);
+            }
+
+            true
+        });
+
+        state.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &ForeignToplevelManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: ForeignToplevelManagementHandler> Dispatch<ZwlrForeignToplevelManagerV1, (), D>
+    for ForeignToplevelManagementState
+{
+    fn request(
+        state: &mut D,
+        client: &Client,
+        manager: &ZwlrForeignToplevelManagerV1,
+        request: zwlr_foreign_toplevel_manager_v1::Request,
+        data: &(),
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_foreign_toplevel_manager_v1::Request::Stop => {
+                Self::destroyed(state, client.id(), manager, data);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrForeignToplevelManagerV1, _data: &()) {
+        state
+            .foreign_toplevel_management_state()
+            .managers
+            .retain(|i| i != resource);
+    }
+}
+
+impl<D: ForeignToplevelManagementHandler>
+    Dispatch<ZwlrForeignToplevelHandleV1, ForeignToplevelManagementHandle, D>
+    for ForeignToplevelManagementState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrForeignToplevelHandleV1,
+        request: zwlr_foreign_toplevel_handle_v1::Request,
+        handle: &ForeignToplevelManagementHandle,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Request::SetRectangle { width, height, .. } = &request {
+            if *width < 0 || *height < 0 {
+                resource.post_error(
+                    zwlr_foreign_toplevel_handle_v1::Error::InvalidRectangle,
+                    "width and height must be positive or zero",
+                );
+                return;
+            }
+        }
+
+        // Requests for closed toplevels are ignored
+        if handle.is_closed() {
+            return;
+        }
+
+        let handle = handle.clone();
+        match request {
+            zwlr_foreign_toplevel_handle_v1::Request::SetMaximized => state.set_maximized(handle),
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMaximized => state.unset_maximized(handle),
+            zwlr_foreign_toplevel_handle_v1::Request::SetMinimized => state.set_minimized(handle),
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMinimized => state.unset_minimized(handle),
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { seat } => state.activate(handle, seat),
+            zwlr_foreign_toplevel_handle_v1::Request::Close => state.close(handle),
+            zwlr_foreign_toplevel_handle_v1::Request::SetRectangle {
+                surface,
+                x,
+                y,
+                width,
+                height,
+            } => state.set_rectangle(
+                handle,
+                surface,
+                Rectangle::new((x, y).into(), (width, height).into()),
+            ),
+            zwlr_foreign_toplevel_handle_v1::Request::SetFullscreen { output } => {
+                let output = output.as_ref().and_then(Output::from_resource);
+                state.set_fullscreen(handle, output);
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetFullscreen => state.unset_fullscreen(handle),
+            zwlr_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrForeignToplevelHandleV1,
+        handle: &ForeignToplevelManagementHandle,
+    ) {
+        handle.remove_instance(resource);
+    }
+}
+
+/// Macro to delegate implementation of the wlr foreign toplevel management to [ForeignToplevelManagementState].
+///
+/// You must also implement [ForeignToplevelManagementHandler] to use this.
+#[macro_export]
+macro_rules! delegate_foreign_toplevel_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: $crate::wayland::foreign_toplevel_management::ForeignToplevelManagerGlobalData
+        ] => $crate::wayland::foreign_toplevel_management::ForeignToplevelManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::wayland::foreign_toplevel_management::ForeignToplevelManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1: $crate::wayland::foreign_toplevel_management::ForeignToplevelManagementHandle
+        ] => $crate::wayland::foreign_toplevel_management::ForeignToplevelManagementState);
+    };
+}
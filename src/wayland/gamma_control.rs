@@ -0,0 +1,356 @@
+//! Utilities for handling the wlr-gamma-control protocol
+//!
+//! This protocol allows privileged clients like `gammastep` or `wlsunset` to set the gamma
+//! tables of outputs. Only one client can control the gamma of an output at a time. Once its
+//! gamma control object is destroyed (or the client disconnects), the original gamma tables
+//! are restored through [`GammaControlHandler::reset_gamma`].
+//!
+//! On the drm backend the gamma tables can be applied using
+//! [`DrmSurface::set_gamma`](crate::backend::drm::DrmSurface::set_gamma) and restored using
+//! [`DrmSurface::reset_gamma`](crate::backend::drm::DrmSurface::reset_gamma).
+//!
+//! ## Initialization
+//!
+//! ```
+//! use smithay::delegate_gamma_control;
+//! use smithay::output::Output;
+//! use smithay::wayland::gamma_control::{GammaControlHandler, GammaControlState};
+//!
+//! # struct State { gamma_control_state: GammaControlState }
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! # let display_handle = display.handle();
+//! let gamma_control_state = GammaControlState::new::<State>(&display_handle);
+//!
+//! impl GammaControlHandler for State {
+//!     fn gamma_control_state(&mut self) -> &mut GammaControlState {
+//!         &mut self.gamma_control_state
+//!     }
+//!
+//!     fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+//!         // query the size of the gamma ramps of the output from the backend
+//!         # None
+//!     }
+//!
+//!     fn set_gamma(&mut self, output: &Output, red: &[u16], green: &[u16], blue: &[u16]) -> bool {
+//!         // apply the gamma ramps to the output
+//!         true
+//!     }
+//!
+//!     fn reset_gamma(&mut self, output: &Output) {
+//!         // restore the original gamma ramps of the output
+//!     }
+//! }
+//!
+//! delegate_gamma_control!(State);
+//! ```
+
+use std::{
+    os::unix::io::OwnedFd,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tracing::warn;
+use wayland_protocols_wlr::gamma_control::v1::server::{
+    zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+
+use crate::output::{Output, WeakOutput};
+
+/// Handler for the wlr-gamma-control protocol
+pub trait GammaControlHandler:
+    GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData>
+    + Dispatch<ZwlrGammaControlManagerV1, ()>
+    + Dispatch<ZwlrGammaControlV1, GammaControlData>
+    + 'static
+{
+    /// [`GammaControlState`] getter
+    fn gamma_control_state(&mut self) -> &mut GammaControlState;
+
+    /// Returns the number of elements of each gamma ramp of the output
+    ///
+    /// Returning `None` indicates the output does not support setting gamma tables.
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+
+    /// A client wants to set new gamma ramps for the output
+    ///
+    /// Every ramp has exactly the number of elements returned by [`GammaControlHandler::gamma_size`].
+    /// Returning `false` indicates setting the gamma ramps failed, which invalidates the gamma control
+    /// of the client and restores the original gamma ramps.
+    fn set_gamma(&mut self, output: &Output, red: &[u16], green: &[u16], blue: &[u16]) -> bool;
+
+    /// The gamma ramps of the output should be restored to their original value
+    ///
+    /// Only called for gamma controls that previously set gamma ramps successfully.
+    fn reset_gamma(&mut self, output: &Output);
+}
+
+/// State of the wlr-gamma-control protocol
+#[derive(Debug)]
+pub struct GammaControlState {
+    global: GlobalId,
+    controls: Vec<(WeakOutput, ZwlrGammaControlV1)>,
+}
+
+impl GammaControlState {
+    /// Register a new [`ZwlrGammaControlManagerV1`] global
+    pub fn new<D: GammaControlHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register a new [`ZwlrGammaControlManagerV1`] global with a filter
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D: GammaControlHandler>(
+        dh: &DisplayHandle,
+        filter: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ZwlrGammaControlManagerV1, _>(
+            1,
+            GammaControlGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        Self {
+            global,
+            controls: Vec::new(),
+        }
+    }
+
+    /// [`ZwlrGammaControlManagerV1`] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Returns whether a client currently controls the gamma of the output
+    pub fn has_control(&self, output: &Output) -> bool {
+        self.controls.iter().any(|(o, _)| o == output)
+    }
+
+    /// Invalidate the gamma control of the output, if any
+    ///
+    /// This should be called if the output is removed or the compositor wants to take back control
+    /// of the gamma tables. The original gamma ramps are *not* restored by this function.
+    pub fn invalidate_control(&mut self, output: &Output) {
+        self.controls.retain(|(o, control)| {
+            if o == output {
+                invalidate(control);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+fn invalidate(control: &ZwlrGammaControlV1) {
+    if let Some(data) = control.data::<GammaControlData>() {
+        data.valid.store(false, Ordering::SeqCst);
+    }
+    control.failed();
+}
+
+/// Global data of the [`ZwlrGammaControlManagerV1`] global
+pub struct GammaControlGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for GammaControlGlobalData {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GammaControlGlobalData").finish_non_exhaustive()
+    }
+}
+
+/// User data of [`ZwlrGammaControlV1`] resources
+#[derive(Debug)]
+pub struct GammaControlData {
+    output: WeakOutput,
+    gamma_size: u32,
+    valid: AtomicBool,
+    // whether this control changed the gamma ramp of its output
+    gamma_set: AtomicBool,
+}
+
+impl<D: GammaControlHandler> GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlGlobalData, D>
+    for GammaControlState
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _global_data: &GammaControlGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GammaControlGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: GammaControlHandler> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlState {
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let gamma_size = output.as_ref().and_then(|output| {
+                    if state.gamma_control_state().has_control(output) {
+                        None
+                    } else {
+                        state.gamma_size(output).filter(|size| *size > 0)
+                    }
+                });
+
+                let control = data_init.init(
+                    id,
+                    GammaControlData {
+                        output: output.as_ref().map(Output::downgrade).unwrap_or_default(),
+                        gamma_size: gamma_size.unwrap_or(0),
+                        valid: AtomicBool::new(gamma_size.is_some()),
+                        gamma_set: AtomicBool::new(false),
+                    },
+                );
+
+                match (output, gamma_size) {
+                    (Some(output), Some(gamma_size)) => {
+                        control.gamma_size(gamma_size);
+                        state
+                            .gamma_control_state()
+                            .controls
+                            .push((output.downgrade(), control));
+                    }
+                    _ => control.failed(),
+                }
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D: GammaControlHandler> Dispatch<ZwlrGammaControlV1, GammaControlData, D> for GammaControlState {
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        control: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &GammaControlData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                if !data.valid.load(Ordering::SeqCst) {
+                    return;
+                }
+                let Some(output) = data.output.upgrade() else {
+                    return;
+                };
+
+                let Some(ramp) = read_gamma_ramp(fd, data.gamma_size as usize) else {
+                    control.post_error(
+                        zwlr_gamma_control_v1::Error::InvalidGamma,
+                        "gamma table does not match the gamma size",
+                    );
+                    return;
+                };
+
+                let size = data.gamma_size as usize;
+                let (red, rest) = ramp.split_at(size);
+                let (green, blue) = rest.split_at(size);
+                if state.set_gamma(&output, red, green, blue) {
+                    data.gamma_set.store(true, Ordering::SeqCst);
+                } else {
+                    state.gamma_control_state().controls.retain(|(_, c)| c != control);
+                    invalidate(control);
+                    if data.gamma_set.swap(false, Ordering::SeqCst) {
+                        state.reset_gamma(&output);
+                    }
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrGammaControlV1, data: &GammaControlData) {
+        if !data.valid.load(Ordering::SeqCst) {
+            return;
+        }
+
+        state
+            .gamma_control_state()
+            .controls
+            .retain(|(_, control)| control != resource);
+        if !data.gamma_set.load(Ordering::SeqCst) {
+            return;
+        }
+        if let Some(output) = data.output.upgrade() {
+            state.reset_gamma(&output);
+        }
+    }
+}
+
+fn read_gamma_ramp(fd: OwnedFd, size: usize) -> Option<Vec<u16>> {
+    let mut bytes = vec![0u8; size * 3 * std::mem::size_of::<u16>()];
+    // read one more byte to detect tables larger than advertised
+    bytes.push(0);
+
+    let mut read = 0;
+    while read < bytes.len() {
+        match rustix::io::pread(&fd, &mut bytes[read..], read as u64) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(rustix::io::Errno::INTR) => continue,
+            Err(err) => {
+                warn!(?err, "Failed to read gamma table");
+                return None;
+            }
+        }
+    }
+
+    if read != size * 3 * std::mem::size_of::<u16>() {
+        return None;
+    }
+
+    Some(
+        bytes[..read]
+            .chunks_exact(2)
+            .map(|chunk| u16::from_ne_bytes([chunk[0], chunk[1]]))
+            .collect(),
+    )
+}
+
+/// Macro to delegate implementation of the wlr-gamma-control protocol to [`GammaControlState`].
+///
+/// You must also implement [`GammaControlHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_gamma_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: $crate::wayland::gamma_control::GammaControlGlobalData
+        ] => $crate::wayland::gamma_control::GammaControlState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::wayland::gamma_control::GammaControlState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: $crate::wayland::gamma_control::GammaControlData
+        ] => $crate::wayland::gamma_control::GammaControlState);
+    };
+}
@@ -0,0 +1,450 @@
+//! Utilities for handling the `ext-image-capture-source-v1` protocol
+//!
+//! Image capture sources are opaque handles describing *what* a client wants to capture,
+//! they are consumed by other protocols like
+//! [`ext-image-copy-capture`](crate::wayland::image_copy_capture).
+//!
+//! This module provides two source managers, one allowing clients to create sources
+//! from a [`wl_output`](wayland_server::protocol::wl_output::WlOutput) and one allowing
+//! them to create sources from a toplevel handle obtained through the
+//! [`foreign_toplevel_list`](crate::wayland::foreign_toplevel_list) protocol.
+//!
+//! ## How to use it
+//!
+//! ```
+//! use smithay::delegate_image_capture_source;
+//! use smithay::delegate_output_capture_source;
+//! use smithay::delegate_toplevel_capture_source;
+//! use smithay::wayland::image_capture_source::{
+//!     ImageCaptureSourceHandler, OutputCaptureSourceHandler, OutputCaptureSourceState,
+//!     ToplevelCaptureSourceHandler, ToplevelCaptureSourceState,
+//! };
+//!
+//! # struct State {
+//! #     output_source_state: OutputCaptureSourceState,
+//! #     toplevel_source_state: ToplevelCaptureSourceState,
+//! # }
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! // Create the source manager globals
+//! let output_source_state = OutputCaptureSourceState::new::<State>(&display.handle());
+//! let toplevel_source_state = ToplevelCaptureSourceState::new::<State>(&display.handle());
+//!
+//! // Insert them into your state and implement the handlers
+//! impl ImageCaptureSourceHandler for State {}
+//!
+//! impl OutputCaptureSourceHandler for State {
+//!     fn output_capture_source_state(&mut self) -> &mut OutputCaptureSourceState {
+//!         &mut self.output_source_state
+//!     }
+//! }
+//!
+//! impl ToplevelCaptureSourceHandler for State {
+//!     fn toplevel_capture_source_state(&mut self) -> &mut ToplevelCaptureSourceState {
+//!         &mut self.toplevel_source_state
+//!     }
+//! }
+//!
+//! delegate_image_capture_source!(State);
+//! delegate_output_capture_source!(State);
+//! delegate_toplevel_capture_source!(State);
+//! ```
+
+use std::sync::Arc;
+
+use wayland_protocols::ext::image_capture_source::v1::server::{
+    ext_foreign_toplevel_image_capture_source_manager_v1::{
+        self, ExtForeignToplevelImageCaptureSourceManagerV1,
+    },
+    ext_image_capture_source_v1::{self, ExtImageCaptureSourceV1},
+    ext_output_image_capture_source_manager_v1::{self, ExtOutputImageCaptureSourceManagerV1},
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+
+use crate::{
+    output::{Output, WeakOutput},
+    utils::user_data::UserDataMap,
+    wayland::foreign_toplevel_list::{ForeignToplevelHandle, ForeignToplevelWeakHandle},
+};
+
+const MANAGER_VERSION: u32 = 1;
+
+/// What an [`ImageCaptureSource`] refers to
+#[derive(Debug, Clone)]
+pub enum ImageCaptureSourceKind {
+    /// The source captures an output
+    Output(WeakOutput),
+    /// The source captures a toplevel announced via `ext-foreign-toplevel-list`
+    Toplevel(ForeignToplevelWeakHandle),
+}
+
+#[derive(Debug)]
+struct ImageCaptureSourceInner {
+    kind: ImageCaptureSourceKind,
+    user_data: UserDataMap,
+}
+
+/// An image capture source
+///
+/// Sources are created by clients and are immutable, they can be compared to find out
+/// if two sessions are capturing the same source object.
+#[derive(Debug, Clone)]
+pub struct ImageCaptureSource {
+    inner: Arc<ImageCaptureSourceInner>,
+}
+
+impl PartialEq for ImageCaptureSource {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for ImageCaptureSource {}
+
+impl ImageCaptureSource {
+    fn new(kind: ImageCaptureSourceKind) -> Self {
+        ImageCaptureSource {
+            inner: Arc::new(ImageCaptureSourceInner {
+                kind,
+                user_data: UserDataMap::new(),
+            }),
+        }
+    }
+
+    /// Attempt to retrieve the [`ImageCaptureSource`] from an existing resource
+    pub fn from_resource(resource: &ExtImageCaptureSourceV1) -> Option<Self> {
+        resource.data::<Self>().cloned()
+    }
+
+    /// What this source refers to
+    pub fn kind(&self) -> &ImageCaptureSourceKind {
+        &self.inner.kind
+    }
+
+    /// The output captured by this source, if it is an output source and the output is still alive
+    pub fn output(&self) -> Option<Output> {
+        match &self.inner.kind {
+            ImageCaptureSourceKind::Output(output) => output.upgrade(),
+            _ => None,
+        }
+    }
+
+    /// The toplevel captured by this source, if it is a toplevel source and the toplevel is still alive
+    pub fn toplevel(&self) -> Option<ForeignToplevelHandle> {
+        match &self.inner.kind {
+            ImageCaptureSourceKind::Toplevel(toplevel) => {
+                toplevel.upgrade().filter(|toplevel| !toplevel.is_closed())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the captured output or toplevel still exists
+    pub fn is_alive(&self) -> bool {
+        match &self.inner.kind {
+            ImageCaptureSourceKind::Output(output) => output.is_alive(),
+            ImageCaptureSourceKind::Toplevel(_) => self.toplevel().is_some(),
+        }
+    }
+
+    /// Access the [`UserDataMap`] associated with this source
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.inner.user_data
+    }
+}
+
+/// Handler for the `ext-image-capture-source-v1` protocol
+pub trait ImageCaptureSourceHandler: Dispatch<ExtImageCaptureSourceV1, ImageCaptureSource> + 'static {
+    /// A client destroyed an image capture source
+    ///
+    /// Sessions created from this source are not affected.
+    fn source_destroyed(&mut self, source: ImageCaptureSource) {
+        let _ = source;
+    }
+}
+
+/// Global data of the capture source managers
+pub struct CaptureSourceGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for CaptureSourceGlobalData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptureSourceGlobalData").finish_non_exhaustive()
+    }
+}
+
+/// State of the [`ExtOutputImageCaptureSourceManagerV1`] global
+#[derive(Debug)]
+pub struct OutputCaptureSourceState {
+    global: GlobalId,
+}
+
+impl OutputCaptureSourceState {
+    /// Register a new [`ExtOutputImageCaptureSourceManagerV1`] global
+    pub fn new<D: OutputCaptureSourceHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register a new [`ExtOutputImageCaptureSourceManagerV1`] global with a client filter
+    pub fn new_with_filter<D: OutputCaptureSourceHandler>(
+        dh: &DisplayHandle,
+        filter: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ExtOutputImageCaptureSourceManagerV1, _>(
+            MANAGER_VERSION,
+            CaptureSourceGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+        Self { global }
+    }
+
+    /// [`ExtOutputImageCaptureSourceManagerV1`] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// Handler for the output capture source manager
+pub trait OutputCaptureSourceHandler:
+    ImageCaptureSourceHandler
+    + GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, CaptureSourceGlobalData>
+    + Dispatch<ExtOutputImageCaptureSourceManagerV1, ()>
+{
+    /// [`OutputCaptureSourceState`] getter
+    fn output_capture_source_state(&mut self) -> &mut OutputCaptureSourceState;
+
+    /// A new source capturing `output` was created
+    fn output_source_created(&mut self, source: ImageCaptureSource, output: &Output) {
+        let _ = (source, output);
+    }
+}
+
+/// State of the [`ExtForeignToplevelImageCaptureSourceManagerV1`] global
+#[derive(Debug)]
+pub struct ToplevelCaptureSourceState {
+    global: GlobalId,
+}
+
+impl ToplevelCaptureSourceState {
+    /// Register a new [`ExtForeignToplevelImageCaptureSourceManagerV1`] global
+    pub fn new<D: ToplevelCaptureSourceHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register a new [`ExtForeignToplevelImageCaptureSourceManagerV1`] global with a client filter
+    pub fn new_with_filter<D: ToplevelCaptureSourceHandler>(
+        dh: &DisplayHandle,
+        filter: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ExtForeignToplevelImageCaptureSourceManagerV1, _>(
+            MANAGER_VERSION,
+            CaptureSourceGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+        Self { global }
+    }
+
+    /// [`ExtForeignToplevelImageCaptureSourceManagerV1`] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// Handler for the foreign toplevel capture source manager
+pub trait ToplevelCaptureSourceHandler:
+    ImageCaptureSourceHandler
+    + GlobalDispatch<ExtForeignToplevelImageCaptureSourceManagerV1, CaptureSourceGlobalData>
+    + Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, ()>
+{
+    /// [`ToplevelCaptureSourceState`] getter
+    fn toplevel_capture_source_state(&mut self) -> &mut ToplevelCaptureSourceState;
+
+    /// A new source capturing `toplevel` was created
+    fn toplevel_source_created(&mut self, source: ImageCaptureSource, toplevel: &ForeignToplevelHandle) {
+        let _ = (source, toplevel);
+    }
+}
+
+impl<D: ImageCaptureSourceHandler> Dispatch<ExtImageCaptureSourceV1, ImageCaptureSource, D>
+    for ImageCaptureSource
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ExtImageCaptureSourceV1,
+        request: ext_image_capture_source_v1::Request,
+        _data: &ImageCaptureSource,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_capture_source_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        _resource: &ExtImageCaptureSourceV1,
+        data: &ImageCaptureSource,
+    ) {
+        state.source_destroyed(data.clone());
+    }
+}
+
+impl<D: OutputCaptureSourceHandler>
+    GlobalDispatch<ExtOutputImageCaptureSourceManagerV1, CaptureSourceGlobalData, D>
+    for OutputCaptureSourceState
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtOutputImageCaptureSourceManagerV1>,
+        _global_data: &CaptureSourceGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &CaptureSourceGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: OutputCaptureSourceHandler> Dispatch<ExtOutputImageCaptureSourceManagerV1, (), D>
+    for OutputCaptureSourceState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ExtOutputImageCaptureSourceManagerV1,
+        request: ext_output_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_output_image_capture_source_manager_v1::Request::CreateSource { source, output } => {
+                // An inert output results in a source that can never be captured,
+                // sessions created from it will be stopped immediately.
+                let output = Output::from_resource(&output);
+                let capture_source = ImageCaptureSource::new(ImageCaptureSourceKind::Output(
+                    output.as_ref().map(Output::downgrade).unwrap_or_default(),
+                ));
+                data_init.init(source, capture_source.clone());
+
+                if let Some(output) = output {
+                    state.output_source_created(capture_source, &output);
+                }
+            }
+            ext_output_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D: ToplevelCaptureSourceHandler>
+    GlobalDispatch<ExtForeignToplevelImageCaptureSourceManagerV1, CaptureSourceGlobalData, D>
+    for ToplevelCaptureSourceState
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtForeignToplevelImageCaptureSourceManagerV1>,
+        _global_data: &CaptureSourceGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &CaptureSourceGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: ToplevelCaptureSourceHandler> Dispatch<ExtForeignToplevelImageCaptureSourceManagerV1, (), D>
+    for ToplevelCaptureSourceState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ExtForeignToplevelImageCaptureSourceManagerV1,
+        request: ext_foreign_toplevel_image_capture_source_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::CreateSource {
+                source,
+                toplevel_handle,
+            } => {
+                let toplevel = ForeignToplevelHandle::from_resource(&toplevel_handle);
+                let capture_source = ImageCaptureSource::new(ImageCaptureSourceKind::Toplevel(
+                    toplevel
+                        .as_ref()
+                        .map(ForeignToplevelHandle::downgrade)
+                        .unwrap_or_default(),
+                ));
+                data_init.init(source, capture_source.clone());
+
+                if let Some(toplevel) = toplevel.filter(|toplevel| !toplevel.is_closed()) {
+                    state.toplevel_source_created(capture_source, &toplevel);
+                }
+            }
+            ext_foreign_toplevel_image_capture_source_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Macro to delegate implementation of the `ext_image_capture_source_v1` interface to [`ImageCaptureSource`].
+///
+/// You must also implement [`ImageCaptureSourceHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_image_capture_source {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_image_capture_source_v1::ExtImageCaptureSourceV1: $crate::wayland::image_capture_source::ImageCaptureSource
+        ] => $crate::wayland::image_capture_source::ImageCaptureSource);
+    };
+}
+
+/// Macro to delegate implementation of the output capture source manager to [`OutputCaptureSourceState`].
+///
+/// You must also implement [`OutputCaptureSourceHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_output_capture_source {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1: $crate::wayland::image_capture_source::CaptureSourceGlobalData
+        ] => $crate::wayland::image_capture_source::OutputCaptureSourceState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_output_image_capture_source_manager_v1::ExtOutputImageCaptureSourceManagerV1: ()
+        ] => $crate::wayland::image_capture_source::OutputCaptureSourceState);
+    };
+}
+
+/// Macro to delegate implementation of the foreign toplevel capture source manager to [`ToplevelCaptureSourceState`].
+///
+/// You must also implement [`ToplevelCaptureSourceHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_toplevel_capture_source {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1: $crate::wayland::image_capture_source::CaptureSourceGlobalData
+        ] => $crate::wayland::image_capture_source::ToplevelCaptureSourceState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_capture_source::v1::server::ext_foreign_toplevel_image_capture_source_manager_v1::ExtForeignToplevelImageCaptureSourceManagerV1: ()
+        ] => $crate::wayland::image_capture_source::ToplevelCaptureSourceState);
+    };
+}
@@ -0,0 +1,984 @@
+//! Utilities for handling the `ext-image-copy-capture-v1` protocol
+//!
+//! This protocol allows clients to copy the contents of an
+//! [`ImageCaptureSource`] into a client provided buffer, which is the
+//! basis of screenshots and screen recording.
+//!
+//! The lifecycle is as follows:
+//!
+//! - A client creates a capture session for a source. The compositor is asked for the
+//!   [`BufferConstraints`] of the source via [`ImageCopyCaptureHandler::capture_constraints`]
+//!   and is handed a [`Session`] through [`ImageCopyCaptureHandler::new_session`].
+//! - The client creates frames, attaches buffers and requests a capture. Every captured frame is
+//!   handed to the compositor via [`ImageCopyCaptureHandler::frame`].
+//! - The compositor fills the buffer, for example using [`render_frame`], and completes the frame
+//!   with [`Frame::success`] or [`Frame::fail`].
+//!
+//! Dropping a [`Session`] stops the session, dropping a [`Frame`] without completing it
+//! fails the frame.
+//!
+//! ## How to use it
+//!
+//! ```
+//! use smithay::delegate_image_copy_capture;
+//! use smithay::wayland::image_capture_source::ImageCaptureSource;
+//! use smithay::wayland::image_copy_capture::{
+//!     BufferConstraints, Frame, ImageCopyCaptureHandler, ImageCopyCaptureState, Session, SessionRef,
+//! };
+//!
+//! # struct State {
+//! #     image_copy_capture_state: ImageCopyCaptureState,
+//! #     sessions: Vec<Session>,
+//! #     pending_frames: Vec<(SessionRef, Frame)>,
+//! # }
+//! # impl smithay::wayland::image_capture_source::ImageCaptureSourceHandler for State {}
+//! # smithay::delegate_image_capture_source!(State);
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! // Create the global
+//! let image_copy_capture_state = ImageCopyCaptureState::new::<State>(&display.handle());
+//!
+//! // Insert it into your state and implement the handler
+//! impl ImageCopyCaptureHandler for State {
+//!     fn image_copy_capture_state(&mut self) -> &mut ImageCopyCaptureState {
+//!         &mut self.image_copy_capture_state
+//!     }
+//!
+//!     fn capture_constraints(&mut self, source: &ImageCaptureSource) -> Option<BufferConstraints> {
+//!         // Return the buffer size and formats you are able to render the source to.
+//!         # None
+//!     }
+//!
+//!     fn new_session(&mut self, session: Session) {
+//!         self.sessions.push(session);
+//!     }
+//!
+//!     fn frame(&mut self, session: &SessionRef, frame: Frame) {
+//!         // Render the source into `frame.buffer()` on the next repaint.
+//!         self.pending_frames.push((session.clone(), frame));
+//!     }
+//! }
+//! delegate_image_copy_capture!(State);
+//! ```
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wayland_protocols::ext::{
+    image_capture_source::v1::server::ext_image_capture_source_v1::ExtImageCaptureSourceV1,
+    image_copy_capture::v1::server::{
+        ext_image_copy_capture_cursor_session_v1::{self, ExtImageCopyCaptureCursorSessionV1},
+        ext_image_copy_capture_frame_v1::{self, ExtImageCopyCaptureFrameV1, FailureReason},
+        ext_image_copy_capture_manager_v1::{self, ExtImageCopyCaptureManagerV1, Options},
+        ext_image_copy_capture_session_v1::{self, ExtImageCopyCaptureSessionV1},
+    },
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    protocol::{wl_buffer::WlBuffer, wl_pointer::WlPointer, wl_shm},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum, Weak,
+};
+
+use crate::{
+    backend::allocator::{format::FormatSet, Buffer as _, Fourcc},
+    utils::{user_data::UserDataMap, Buffer as BufferCoords, Point, Rectangle, Size, Transform},
+    wayland::{dmabuf::get_dmabuf, image_capture_source::ImageCaptureSource, shm::with_buffer_contents},
+};
+
+mod render;
+
+pub use render::{render_frame, CaptureRenderError};
+
+const MANAGER_VERSION: u32 = 1;
+
+/// Constraints for dmabuf buffers of a capture session
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmabufConstraints {
+    /// Device that buffers should be allocated on
+    pub node: libc::dev_t,
+    /// Supported formats and modifiers
+    pub formats: FormatSet,
+}
+
+/// Buffer constraints of a capture session
+///
+/// Buffers attached by clients have to match these constraints, otherwise
+/// the capture fails with [`FailureReason::BufferConstraints`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferConstraints {
+    /// Required size of the buffer
+    pub size: Size<i32, BufferCoords>,
+    /// Supported shm formats, empty if shm is not supported
+    pub shm: Vec<wl_shm::Format>,
+    /// Dmabuf constraints, `None` if dmabufs are not supported
+    pub dma: Option<DmabufConstraints>,
+}
+
+impl BufferConstraints {
+    fn send_to(&self, session: &ExtImageCopyCaptureSessionV1) {
+        session.buffer_size(self.size.w as u32, self.size.h as u32);
+        for format in &self.shm {
+            session.shm_format(*format);
+        }
+        if let Some(dma) = self.dma.as_ref() {
+            session.dmabuf_device(dma.node.to_ne_bytes().to_vec());
+            let mut codes: Vec<Fourcc> = Vec::new();
+            for format in dma.formats.iter() {
+                if !codes.contains(&format.code) {
+                    codes.push(format.code);
+                }
+            }
+            for code in codes {
+                let modifiers = dma
+                    .formats
+                    .iter()
+                    .filter(|format| format.code == code)
+                    .flat_map(|format| u64::from(format.modifier).to_ne_bytes())
+                    .collect::<Vec<u8>>();
+                session.dmabuf_format(code as u32, modifiers);
+            }
+        }
+        session.done();
+    }
+
+    fn verify(&self, buffer: &WlBuffer) -> bool {
+        if let Ok(dmabuf) = get_dmabuf(buffer) {
+            return self
+                .dma
+                .as_ref()
+                .is_some_and(|dma| dmabuf.size() == self.size && dma.formats.contains(&dmabuf.format()));
+        }
+
+        with_buffer_contents(buffer, |_, _, data| {
+            Size::<i32, BufferCoords>::from((data.width, data.height)) == self.size
+                && self.shm.contains(&data.format)
+        })
+        .unwrap_or(false)
+    }
+}
+
+#[derive(Debug)]
+struct SessionInner {
+    source: ImageCaptureSource,
+    paint_cursors: bool,
+    pointer: Option<WlPointer>,
+    constraints: Option<BufferConstraints>,
+    resource: Option<Weak<ExtImageCopyCaptureSessionV1>>,
+    has_frame: bool,
+    stopped: bool,
+}
+
+#[derive(Debug)]
+struct SessionShared {
+    inner: Mutex<SessionInner>,
+    user_data: UserDataMap,
+}
+
+/// Reference to a capture session
+///
+/// Unlike a [`Session`], this reference does not keep the session running.
+#[derive(Debug, Clone)]
+pub struct SessionRef {
+    shared: Arc<SessionShared>,
+}
+
+impl PartialEq for SessionRef {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+impl Eq for SessionRef {}
+
+impl SessionRef {
+    /// The source captured by this session
+    pub fn source(&self) -> ImageCaptureSource {
+        self.shared.inner.lock().unwrap().source.clone()
+    }
+
+    /// Whether the client asked for cursors to be painted onto captured frames
+    pub fn draw_cursor(&self) -> bool {
+        self.shared.inner.lock().unwrap().paint_cursors
+    }
+
+    /// The pointer whose cursor is captured, if this session was created through a cursor session
+    pub fn cursor_pointer(&self) -> Option<WlPointer> {
+        self.shared.inner.lock().unwrap().pointer.clone()
+    }
+
+    /// Current buffer constraints of the session
+    pub fn current_constraints(&self) -> Option<BufferConstraints> {
+        self.shared.inner.lock().unwrap().constraints.clone()
+    }
+
+    /// Returns `true` if the session has been stopped
+    pub fn is_stopped(&self) -> bool {
+        self.shared.inner.lock().unwrap().stopped
+    }
+
+    /// Access the [`UserDataMap`] associated with this session
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.shared.user_data
+    }
+}
+
+/// A running capture session
+///
+/// Dropping the session stops it, no further frames will be captured.
+#[derive(Debug)]
+pub struct Session {
+    session: SessionRef,
+}
+
+impl std::ops::Deref for Session {
+    type Target = SessionRef;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.session
+    }
+}
+
+impl PartialEq<SessionRef> for Session {
+    #[inline]
+    fn eq(&self, other: &SessionRef) -> bool {
+        self.session == *other
+    }
+}
+
+impl Session {
+    /// Update the buffer constraints, e.g. after the source was resized.
+    ///
+    /// The client will re-allocate its buffers, frames already handed out using
+    /// buffers that do not match the new constraints should be failed
+    /// with [`FailureReason::BufferConstraints`].
+    pub fn update_constraints(&self, constraints: BufferConstraints) {
+        let mut inner = self.session.shared.inner.lock().unwrap();
+        if inner.stopped || inner.constraints.as_ref() == Some(&constraints) {
+            return;
+        }
+
+        if let Some(resource) = inner.resource.as_ref().and_then(|r| r.upgrade().ok()) {
+            constraints.send_to(&resource);
+        }
+        inner.constraints = Some(constraints);
+    }
+
+    /// Stop the session
+    ///
+    /// This is also done automatically, when the session is dropped.
+    pub fn stop(self) {}
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let mut inner = self.session.shared.inner.lock().unwrap();
+        if inner.stopped {
+            return;
+        }
+
+        inner.stopped = true;
+        if let Some(resource) = inner.resource.as_ref().and_then(|r| r.upgrade().ok()) {
+            resource.stopped();
+        }
+    }
+}
+
+/// User data of the `ext_image_copy_capture_session_v1` resource
+#[derive(Debug)]
+pub struct SessionData {
+    session: SessionRef,
+}
+
+#[derive(Debug, Default)]
+struct FrameInner {
+    buffer: Option<WlBuffer>,
+    damage: Vec<Rectangle<i32, BufferCoords>>,
+    capture_requested: bool,
+    completed: bool,
+}
+
+/// User data of the `ext_image_copy_capture_frame_v1` resource
+#[derive(Debug)]
+pub struct FrameData {
+    session: SessionRef,
+    inner: Mutex<FrameInner>,
+}
+
+/// Reference to a frame, that was aborted by the client
+#[derive(Debug, Clone)]
+pub struct FrameRef {
+    frame: ExtImageCopyCaptureFrameV1,
+}
+
+impl PartialEq for FrameRef {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.frame == other.frame
+    }
+}
+
+impl PartialEq<FrameRef> for Frame {
+    #[inline]
+    fn eq(&self, other: &FrameRef) -> bool {
+        self.frame == other.frame
+    }
+}
+
+impl FrameRef {
+    /// The session this frame belonged to
+    pub fn session(&self) -> Option<SessionRef> {
+        self.frame.data::<FrameData>().map(|data| data.session.clone())
+    }
+}
+
+/// A frame waiting to be captured
+///
+/// Has to be completed using [`Frame::success`] or [`Frame::fail`].
+/// Dropping the frame without completing it will fail the frame
+/// with [`FailureReason::Unknown`].
+#[derive(Debug)]
+pub struct Frame {
+    frame: ExtImageCopyCaptureFrameV1,
+    buffer: WlBuffer,
+}
+
+impl Frame {
+    fn data(&self) -> &FrameData {
+        self.frame.data::<FrameData>().unwrap()
+    }
+
+    /// Buffer to copy the captured contents into
+    pub fn buffer(&self) -> WlBuffer {
+        self.buffer.clone()
+    }
+
+    /// Damage of the buffer, as reported by the client, since the buffer was last used
+    ///
+    /// Regions not damaged by the client still contain the contents of the last
+    /// capture into this buffer.
+    pub fn buffer_damage(&self) -> Vec<Rectangle<i32, BufferCoords>> {
+        self.data().inner.lock().unwrap().damage.clone()
+    }
+
+    /// The session this frame belongs to
+    pub fn session(&self) -> SessionRef {
+        self.data().session.clone()
+    }
+
+    /// Returns `true` if the client already destroyed the frame.
+    ///
+    /// The frame should be dropped without doing any work.
+    pub fn is_aborted(&self) -> bool {
+        !self.frame.is_alive()
+    }
+
+    /// Signal that the buffer has been filled successfully
+    ///
+    /// - `transform` of the buffer contents
+    /// - `damage` of the contents since the previous frame of the session, `None` for the full buffer
+    /// - `presentation_time` is the time the contents were presented, measured in `CLOCK_MONOTONIC`
+    pub fn success(
+        self,
+        transform: impl Into<Transform>,
+        damage: impl Into<Option<Vec<Rectangle<i32, BufferCoords>>>>,
+        presentation_time: impl Into<Duration>,
+    ) {
+        if !self.complete() {
+            return;
+        }
+
+        self.frame.transform(transform.into().into());
+        let damage = damage.into();
+        match damage {
+            Some(damage) => {
+                for rect in damage {
+                    self.frame
+                        .damage(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+                }
+            }
+            None => {
+                let size = self.session().current_constraints().map(|c| c.size);
+                if let Some(size) = size {
+                    self.frame.damage(0, 0, size.w, size.h);
+                }
+            }
+        }
+
+        let time = presentation_time.into();
+        let tv_sec = time.as_secs();
+        self.frame
+            .presentation_time((tv_sec >> 32) as u32, tv_sec as u32, time.subsec_nanos());
+        self.frame.ready();
+    }
+
+    /// Signal that the capture failed
+    pub fn fail(self, reason: FailureReason) {
+        if self.complete() {
+            self.frame.failed(reason);
+        }
+    }
+
+    // Returns `true` if the frame has to be completed by the caller
+    fn complete(&self) -> bool {
+        let Some(data) = self.frame.data::<FrameData>() else {
+            return false;
+        };
+        let mut inner = data.inner.lock().unwrap();
+        let needs_completion = !inner.completed && self.frame.is_alive();
+        inner.completed = true;
+        needs_completion
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        if self.complete() {
+            self.frame.failed(FailureReason::Unknown);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CursorSessionInner {
+    position: Option<Point<i32, BufferCoords>>,
+    hotspot: Point<i32, BufferCoords>,
+    session_created: bool,
+    stopped: bool,
+}
+
+/// User data of the `ext_image_copy_capture_cursor_session_v1` resource
+#[derive(Debug)]
+pub struct CursorSessionData {
+    source: ImageCaptureSource,
+    pointer: WlPointer,
+    inner: Arc<Mutex<CursorSessionInner>>,
+}
+
+/// A cursor capture session
+///
+/// Cursor sessions allow clients to capture the cursor image of a pointer separately from the source.
+/// The compositor is responsible for keeping position and hotspot up to date.
+///
+/// Dropping the cursor session makes any capture session created from it stop immediately.
+#[derive(Debug)]
+pub struct CursorSession {
+    resource: ExtImageCopyCaptureCursorSessionV1,
+}
+
+impl CursorSession {
+    fn data(&self) -> &CursorSessionData {
+        self.resource.data::<CursorSessionData>().unwrap()
+    }
+
+    /// The source the cursor is captured relative to
+    pub fn source(&self) -> ImageCaptureSource {
+        self.data().source.clone()
+    }
+
+    /// The pointer whose cursor is captured
+    pub fn pointer(&self) -> WlPointer {
+        self.data().pointer.clone()
+    }
+
+    /// Update the cursor position relative to the source
+    ///
+    /// `None` signals that the cursor left the source.
+    pub fn set_position(&self, position: Option<Point<i32, BufferCoords>>) {
+        let mut inner = self.data().inner.lock().unwrap();
+        if inner.position == position {
+            return;
+        }
+
+        match (inner.position, position) {
+            (None, Some(position)) => {
+                self.resource.enter();
+                self.resource.hotspot(inner.hotspot.x, inner.hotspot.y);
+                self.resource.position(position.x, position.y);
+            }
+            (Some(_), Some(position)) => self.resource.position(position.x, position.y),
+            (Some(_), None) => self.resource.leave(),
+            (None, None) => {}
+        }
+        inner.position = position;
+    }
+
+    /// Update the hotspot of the cursor image
+    pub fn set_hotspot(&self, hotspot: Point<i32, BufferCoords>) {
+        let mut inner = self.data().inner.lock().unwrap();
+        if inner.hotspot == hotspot {
+            return;
+        }
+
+        inner.hotspot = hotspot;
+        if inner.position.is_some() {
+            self.resource.hotspot(hotspot.x, hotspot.y);
+        }
+    }
+}
+
+impl Drop for CursorSession {
+    fn drop(&mut self) {
+        if let Some(data) = self.resource.data::<CursorSessionData>() {
+            data.inner.lock().unwrap().stopped = true;
+        }
+    }
+}
+
+/// Handler for the `ext-image-copy-capture-v1` protocol
+pub trait ImageCopyCaptureHandler:
+    GlobalDispatch<ExtImageCopyCaptureManagerV1, ImageCopyCaptureGlobalData>
+    + Dispatch<ExtImageCopyCaptureManagerV1, ()>
+    + Dispatch<ExtImageCopyCaptureSessionV1, SessionData>
+    + Dispatch<ExtImageCopyCaptureFrameV1, FrameData>
+    + Dispatch<ExtImageCopyCaptureCursorSessionV1, CursorSessionData>
+    + 'static
+{
+    /// [`ImageCopyCaptureState`] getter
+    fn image_copy_capture_state(&mut self) -> &mut ImageCopyCaptureState;
+
+    /// Buffer constraints for capturing the given source
+    ///
+    /// Returning `None` will create a stopped session.
+    fn capture_constraints(&mut self, source: &ImageCaptureSource) -> Option<BufferConstraints>;
+
+    /// Buffer constraints for capturing the cursor of a pointer
+    ///
+    /// Returning `None` will create a stopped session.
+    fn cursor_capture_constraints(&mut self, source: &ImageCaptureSource) -> Option<BufferConstraints> {
+        let _ = source;
+        None
+    }
+
+    /// A new capture session was created
+    ///
+    /// The session is stopped once it is dropped.
+    fn new_session(&mut self, session: Session);
+
+    /// A new cursor session was created
+    ///
+    /// Capture sessions created from the cursor session are passed to [`Self::new_session`],
+    /// [`SessionRef::cursor_pointer`] can be used to distinguish them.
+    fn new_cursor_session(&mut self, session: CursorSession) {
+        let _ = session;
+    }
+
+    /// A client requested a frame to be captured
+    fn frame(&mut self, session: &SessionRef, frame: Frame);
+
+    /// A client destroyed a frame before it was completed
+    fn frame_aborted(&mut self, frame: FrameRef) {
+        let _ = frame;
+    }
+
+    /// A client destroyed a session
+    fn session_destroyed(&mut self, session: SessionRef) {
+        let _ = session;
+    }
+}
+
+/// Global data of the [`ExtImageCopyCaptureManagerV1`] global
+pub struct ImageCopyCaptureGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for ImageCopyCaptureGlobalData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageCopyCaptureGlobalData")
+            .finish_non_exhaustive()
+    }
+}
+
+/// State of the [`ExtImageCopyCaptureManagerV1`] global
+#[derive(Debug)]
+pub struct ImageCopyCaptureState {
+    global: GlobalId,
+}
+
+impl ImageCopyCaptureState {
+    /// Register a new [`ExtImageCopyCaptureManagerV1`] global
+    pub fn new<D: ImageCopyCaptureHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register a new [`ExtImageCopyCaptureManagerV1`] global with a client filter
+    pub fn new_with_filter<D: ImageCopyCaptureHandler>(
+        dh: &DisplayHandle,
+        filter: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ExtImageCopyCaptureManagerV1, _>(
+            MANAGER_VERSION,
+            ImageCopyCaptureGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+        Self { global }
+    }
+
+    /// [`ExtImageCopyCaptureManagerV1`] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+fn init_session<D: ImageCopyCaptureHandler>(
+    state: &mut D,
+    resource: New<ExtImageCopyCaptureSessionV1>,
+    source: ImageCaptureSource,
+    paint_cursors: bool,
+    pointer: Option<WlPointer>,
+    stopped: bool,
+    data_init: &mut DataInit<'_, D>,
+) {
+    let constraints = if stopped || !source.is_alive() {
+        None
+    } else if pointer.is_some() {
+        state.cursor_capture_constraints(&source)
+    } else {
+        state.capture_constraints(&source)
+    };
+
+    let session = SessionRef {
+        shared: Arc::new(SessionShared {
+            inner: Mutex::new(SessionInner {
+                source,
+                paint_cursors,
+                pointer,
+                constraints: constraints.clone(),
+                resource: None,
+                has_frame: false,
+                stopped: constraints.is_none(),
+            }),
+            user_data: UserDataMap::new(),
+        }),
+    };
+    let resource = data_init.init(
+        resource,
+        SessionData {
+            session: session.clone(),
+        },
+    );
+    session.shared.inner.lock().unwrap().resource = Some(resource.downgrade());
+
+    match constraints {
+        Some(constraints) => {
+            constraints.send_to(&resource);
+            state.new_session(Session { session });
+        }
+        None => resource.stopped(),
+    }
+}
+
+impl<D: ImageCopyCaptureHandler> GlobalDispatch<ExtImageCopyCaptureManagerV1, ImageCopyCaptureGlobalData, D>
+    for ImageCopyCaptureState
+{
+    fn bind(
+        _state: &mut D,
+        _dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtImageCopyCaptureManagerV1>,
+        _global_data: &ImageCopyCaptureGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &ImageCopyCaptureGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+fn source_from_resource(source: &ExtImageCaptureSourceV1) -> Option<ImageCaptureSource> {
+    ImageCaptureSource::from_resource(source)
+}
+
+impl<D: ImageCopyCaptureHandler> Dispatch<ExtImageCopyCaptureManagerV1, (), D> for ImageCopyCaptureState {
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ExtImageCopyCaptureManagerV1,
+        request: ext_image_copy_capture_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_manager_v1::Request::CreateSession {
+                session,
+                source,
+                options,
+            } => {
+                let paint_cursors = match options {
+                    WEnum::Value(options) => options.contains(Options::PaintCursors),
+                    WEnum::Unknown(_) => {
+                        manager.post_error(
+                            ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                            "unknown capture option",
+                        );
+                        return;
+                    }
+                };
+
+                let Some(source) = source_from_resource(&source) else {
+                    manager.post_error(
+                        ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                        "unknown capture source",
+                    );
+                    return;
+                };
+
+                init_session(state, session, source, paint_cursors, None, false, data_init);
+            }
+            ext_image_copy_capture_manager_v1::Request::CreatePointerCursorSession {
+                session,
+                source,
+                pointer,
+            } => {
+                let Some(source) = source_from_resource(&source) else {
+                    manager.post_error(
+                        ext_image_copy_capture_manager_v1::Error::InvalidOption,
+                        "unknown capture source",
+                    );
+                    return;
+                };
+
+                let resource = data_init.init(
+                    session,
+                    CursorSessionData {
+                        source,
+                        pointer,
+                        inner: Arc::new(Mutex::new(CursorSessionInner {
+                            position: None,
+                            hotspot: Point::default(),
+                            session_created: false,
+                            stopped: false,
+                        })),
+                    },
+                );
+                state.new_cursor_session(CursorSession { resource });
+            }
+            ext_image_copy_capture_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D: ImageCopyCaptureHandler> Dispatch<ExtImageCopyCaptureCursorSessionV1, CursorSessionData, D>
+    for ImageCopyCaptureState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ExtImageCopyCaptureCursorSessionV1,
+        request: ext_image_copy_capture_cursor_session_v1::Request,
+        data: &CursorSessionData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_cursor_session_v1::Request::GetCaptureSession { session } => {
+                let stopped = {
+                    let mut inner = data.inner.lock().unwrap();
+                    if inner.session_created {
+                        resource.post_error(
+                            ext_image_copy_capture_cursor_session_v1::Error::DuplicateSession,
+                            "get_capture_session sent twice",
+                        );
+                        return;
+                    }
+                    inner.session_created = true;
+                    inner.stopped
+                };
+
+                init_session(
+                    state,
+                    session,
+                    data.source.clone(),
+                    false,
+                    Some(data.pointer.clone()),
+                    stopped,
+                    data_init,
+                );
+            }
+            ext_image_copy_capture_cursor_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D: ImageCopyCaptureHandler> Dispatch<ExtImageCopyCaptureSessionV1, SessionData, D>
+    for ImageCopyCaptureState
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        resource: &ExtImageCopyCaptureSessionV1,
+        request: ext_image_copy_capture_session_v1::Request,
+        data: &SessionData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_session_v1::Request::CreateFrame { frame } => {
+                let mut inner = data.session.shared.inner.lock().unwrap();
+                if inner.has_frame {
+                    resource.post_error(
+                        ext_image_copy_capture_session_v1::Error::DuplicateFrame,
+                        "session already has a frame",
+                    );
+                    return;
+                }
+                inner.has_frame = true;
+
+                data_init.init(
+                    frame,
+                    FrameData {
+                        session: data.session.clone(),
+                        inner: Mutex::new(FrameInner::default()),
+                    },
+                );
+            }
+            ext_image_copy_capture_session_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        _resource: &ExtImageCopyCaptureSessionV1,
+        data: &SessionData,
+    ) {
+        state.session_destroyed(data.session.clone());
+    }
+}
+
+impl<D: ImageCopyCaptureHandler> Dispatch<ExtImageCopyCaptureFrameV1, FrameData, D>
+    for ImageCopyCaptureState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ExtImageCopyCaptureFrameV1,
+        request: ext_image_copy_capture_frame_v1::Request,
+        data: &FrameData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_image_copy_capture_frame_v1::Request::AttachBuffer { buffer } => {
+                let mut inner = data.inner.lock().unwrap();
+                if inner.capture_requested {
+                    resource.post_error(
+                        ext_image_copy_capture_frame_v1::Error::AlreadyCaptured,
+                        "attach_buffer sent after capture",
+                    );
+                    return;
+                }
+                inner.buffer = Some(buffer);
+            }
+            ext_image_copy_capture_frame_v1::Request::DamageBuffer { x, y, width, height } => {
+                let mut inner = data.inner.lock().unwrap();
+                if inner.capture_requested {
+                    resource.post_error(
+                        ext_image_copy_capture_frame_v1::Error::AlreadyCaptured,
+                        "damage_buffer sent after capture",
+                    );
+                    return;
+                }
+                if x < 0 || y < 0 || width <= 0 || height <= 0 {
+                    resource.post_error(
+                        ext_image_copy_capture_frame_v1::Error::InvalidBufferDamage,
+                        "invalid buffer damage",
+                    );
+                    return;
+                }
+                inner
+                    .damage
+                    .push(Rectangle::new((x, y).into(), (width, height).into()));
+            }
+            ext_image_copy_capture_frame_v1::Request::Capture => {
+                let buffer = {
+                    let mut inner = data.inner.lock().unwrap();
+                    if inner.capture_requested {
+                        resource.post_error(
+                            ext_image_copy_capture_frame_v1::Error::AlreadyCaptured,
+                            "capture sent twice",
+                        );
+                        return;
+                    }
+                    let Some(buffer) = inner.buffer.clone() else {
+                        resource.post_error(
+                            ext_image_copy_capture_frame_v1::Error::NoBuffer,
+                            "capture sent without attach_buffer",
+                        );
+                        return;
+                    };
+                    inner.capture_requested = true;
+                    buffer
+                };
+
+                let (stopped, constraints) = {
+                    let session = data.session.shared.inner.lock().unwrap();
+                    (session.stopped, session.constraints.clone())
+                };
+
+                let frame = Frame {
+                    frame: resource.clone(),
+                    buffer,
+                };
+                if stopped {
+                    frame.fail(FailureReason::Stopped);
+                    return;
+                }
+                if !constraints.is_some_and(|constraints| constraints.verify(&frame.buffer)) {
+                    frame.fail(FailureReason::BufferConstraints);
+                    return;
+                }
+
+                state.frame(&data.session, frame);
+            }
+            ext_image_copy_capture_frame_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ExtImageCopyCaptureFrameV1, data: &FrameData) {
+        data.session.shared.inner.lock().unwrap().has_frame = false;
+
+        let inner = data.inner.lock().unwrap();
+        let aborted = inner.capture_requested && !inner.completed;
+        std::mem::drop(inner);
+        if aborted {
+            state.frame_aborted(FrameRef {
+                frame: resource.clone(),
+            });
+        }
+    }
+}
+
+/// Macro to delegate implementation of the `ext-image-copy-capture-v1` protocol to [`ImageCopyCaptureState`].
+///
+/// You must also implement [`ImageCopyCaptureHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_image_copy_capture {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1: $crate::wayland::image_copy_capture::ImageCopyCaptureGlobalData
+        ] => $crate::wayland::image_copy_capture::ImageCopyCaptureState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_manager_v1::ExtImageCopyCaptureManagerV1: ()
+        ] => $crate::wayland::image_copy_capture::ImageCopyCaptureState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_session_v1::ExtImageCopyCaptureSessionV1: $crate::wayland::image_copy_capture::SessionData
+        ] => $crate::wayland::image_copy_capture::ImageCopyCaptureState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_frame_v1::ExtImageCopyCaptureFrameV1: $crate::wayland::image_copy_capture::FrameData
+        ] => $crate::wayland::image_copy_capture::ImageCopyCaptureState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::image_copy_capture::v1::server::ext_image_copy_capture_cursor_session_v1::ExtImageCopyCaptureCursorSessionV1: $crate::wayland::image_copy_capture::CursorSessionData
+        ] => $crate::wayland::image_copy_capture::ImageCopyCaptureState);
+    };
+}
@@ -0,0 +1,162 @@
+//! Helper for filling capture frames with any [`Renderer`]
+
+use std::time::Duration;
+
+use tracing::trace;
+
+use super::Frame;
+use crate::{
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        renderer::{
+            damage::{Error as DamageError, OutputDamageTracker},
+            element::RenderElement,
+            Bind, Color32F, ExportMem, Offscreen, Renderer, Texture, TextureMapping,
+        },
+    },
+    output::OutputNoMode,
+    utils::{Buffer as BufferCoords, Physical, Rectangle, Scale, Size, Transform},
+    wayland::{
+        dmabuf::get_dmabuf,
+        shm::{shm_format_to_fourcc, with_buffer_contents, with_buffer_contents_mut, BufferAccessError},
+    },
+};
+
+/// Errors thrown by [`render_frame`]
+#[derive(Debug, thiserror::Error)]
+pub enum CaptureRenderError<E: std::error::Error> {
+    /// The provided [`Renderer`] returned an error
+    #[error(transparent)]
+    Rendering(E),
+    /// The damage tracker has no mode set
+    #[error(transparent)]
+    OutputNoMode(#[from] OutputNoMode),
+    /// The buffer is neither a shm buffer nor a dmabuf
+    #[error("Unsupported buffer type")]
+    UnsupportedBuffer,
+    /// The shm buffer could not be accessed
+    #[error(transparent)]
+    BufferAccess(#[from] BufferAccessError),
+}
+
+impl<E: std::error::Error> From<DamageError<E>> for CaptureRenderError<E> {
+    #[inline]
+    fn from(err: DamageError<E>) -> Self {
+        match err {
+            DamageError::Rendering(err) => CaptureRenderError::Rendering(err),
+            DamageError::OutputNoMode(err) => CaptureRenderError::OutputNoMode(err),
+        }
+    }
+}
+
+/// Render `elements` into the buffer of a capture [`Frame`] and complete it
+///
+/// `damage_tracker` has to be unique to the capture session the frame belongs to and is used to
+/// compute the damage reported to the client. Its mode determines the size, scale and transform
+/// used for rendering, the size should match the buffer constraints of the session.
+///
+/// Dmabufs are rendered to directly using [`Bind<Dmabuf>`], shm buffers are rendered into an
+/// offscreen buffer of type `T`, which is then copied into the shm buffer using [`ExportMem`].
+///
+/// If there is no damage since the last frame of the session, the frame is returned and should
+/// be retried once the contents change. On error the frame is failed.
+pub fn render_frame<R, T, E>(
+    renderer: &mut R,
+    frame: Frame,
+    damage_tracker: &mut OutputDamageTracker,
+    elements: &[E],
+    clear_color: impl Into<Color32F>,
+    presentation_time: impl Into<Duration>,
+) -> Result<Option<Frame>, CaptureRenderError<R::Error>>
+where
+    R: Renderer + Bind<Dmabuf> + Offscreen<T> + ExportMem,
+    R::TextureId: Texture,
+    E: RenderElement<R>,
+{
+    let (size, scale, transform): (Size<i32, Physical>, Scale<f64>, Transform) =
+        damage_tracker.mode().try_into()?;
+
+    // A fresh buffer always needs the full contents, so the damage reported to the client
+    // is relative to the previous frame of the session, while the buffer itself is always
+    // rendered completely.
+    //
+    // The damage is tracked in the transformed output space, the buffer however holds the
+    // untransformed contents, which is what the client expects the damage to be relative to.
+    let output_size = transform.transform_size(size).to_logical(1);
+    let damage = match damage_tracker.damage_output(1, elements)? {
+        (Some(damage), _) => damage
+            .iter()
+            .map(|rect| rect.to_logical(1).to_buffer(1, transform, &output_size))
+            .collect::<Vec<Rectangle<i32, BufferCoords>>>(),
+        (None, _) => {
+            trace!("no damage, delaying frame");
+            return Ok(Some(frame));
+        }
+    };
+
+    let clear_color = clear_color.into();
+    let mut full_damage_tracker = OutputDamageTracker::new(size, scale, transform);
+    let buffer = frame.buffer();
+
+    if let Ok(dmabuf) = get_dmabuf(&buffer) {
+        let mut dmabuf = dmabuf.clone();
+        let mut framebuffer = renderer
+            .bind(&mut dmabuf)
+            .map_err(CaptureRenderError::Rendering)?;
+        let res = full_damage_tracker.render_output(renderer, &mut framebuffer, 0, elements, clear_color)?;
+        let sync = res.sync;
+        renderer.wait(&sync).map_err(CaptureRenderError::Rendering)?;
+    } else {
+        let (format, buffer_size) = with_buffer_contents(&buffer, |_, _, data| {
+            (
+                data.format,
+                Size::<i32, BufferCoords>::from((data.width, data.height)),
+            )
+        })?;
+        let fourcc = shm_format_to_fourcc(format).ok_or(CaptureRenderError::UnsupportedBuffer)?;
+
+        let mut offscreen = renderer
+            .create_buffer(fourcc, buffer_size)
+            .map_err(CaptureRenderError::Rendering)?;
+        let mut framebuffer = renderer
+            .bind(&mut offscreen)
+            .map_err(CaptureRenderError::Rendering)?;
+        let res = full_damage_tracker.render_output(renderer, &mut framebuffer, 0, elements, clear_color)?;
+        let sync = res.sync;
+        renderer.wait(&sync).map_err(CaptureRenderError::Rendering)?;
+
+        let mapping = renderer
+            .copy_framebuffer(&framebuffer, Rectangle::from_size(buffer_size), fourcc)
+            .map_err(CaptureRenderError::Rendering)?;
+        let flipped = mapping.flipped();
+        let pixels = renderer
+            .map_texture(&mapping)
+            .map_err(CaptureRenderError::Rendering)?;
+
+        with_buffer_contents_mut(&buffer, |ptr, len, data| {
+            let src_stride = pixels.len() / data.height as usize;
+            let row_len = src_stride.min(data.stride as usize);
+            for row in 0..data.height as usize {
+                let src_row = if flipped {
+                    data.height as usize - 1 - row
+                } else {
+                    row
+                };
+                let src = &pixels[src_row * src_stride..src_row * src_stride + row_len];
+                let dst_offset = data.offset as usize + row * data.stride as usize;
+                if dst_offset + row_len > len {
+                    break;
+                }
+                // SAFETY: the destination range was checked to be within the pool above
+                unsafe {
+                    std::ptr::copy_nonoverlapping(src.as_ptr(), ptr.add(dst_offset), row_len);
+                }
+            }
+        })?;
+    }
+
+    // the buffer holds the contents like the framebuffer of the output, the client applies
+    // the transform of the output itself
+    frame.success(transform, damage, presentation_time);
+    Ok(None)
+}
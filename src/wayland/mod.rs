@@ -49,6 +49,7 @@
 
 pub mod alpha_modifier;
 pub mod buffer;
+pub mod color_management;
 pub mod commit_timing;
 pub mod compositor;
 pub mod content_type;
@@ -60,9 +61,13 @@ pub mod drm_lease;
 pub mod drm_syncobj;
 pub mod fifo;
 pub mod foreign_toplevel_list;
+pub mod foreign_toplevel_management;
 pub mod fractional_scale;
+pub mod gamma_control;
 pub mod idle_inhibit;
 pub mod idle_notify;
+pub mod image_capture_source;
+pub mod image_copy_capture;
 pub mod input_method;
 pub mod keyboard_shortcuts_inhibit;
 pub mod output;
@@ -79,9 +84,12 @@ pub mod shm;
 pub mod single_pixel_buffer;
 pub mod socket;
 pub mod tablet_manager;
+pub mod tearing_control;
 pub mod text_input;
 pub mod viewporter;
 pub mod virtual_keyboard;
+pub mod virtual_pointer;
+pub mod workspace;
 pub mod xdg_activation;
 pub mod xdg_foreign;
 pub mod xdg_system_bell;
@@ -0,0 +1,975 @@
+//! wlr-output-management protocol
+//!
+//! This protocol allows clients like `wlr-randr` or `kanshi` to inspect and configure the
+//! outputs of the compositor.
+//!
+//! Every [`Output`] added to the [`OutputManagementState`] is advertised as a head, mirroring its
+//! modes, current mode, position, transform and scale, as well as whether the compositor considers
+//! it enabled. Since [`Output`] does not notify about state changes, the compositor needs to call
+//! [`OutputManagementState::update`] after changing any output, which sends the differences to
+//! all clients.
+//!
+//! Configurations created by clients are validated for protocol errors and then handed to the
+//! [`OutputManagementHandler`] to be tested or applied. Configurations created against outdated
+//! state are cancelled automatically.
+//!
+//! ```
+//! use smithay::delegate_output_management;
+//! use smithay::output::{Output, PhysicalProperties, Subpixel};
+//! use smithay::wayland::output::management::{
+//!     OutputConfiguration, OutputManagementHandler, OutputManagementState,
+//! };
+//!
+//! # struct State { output_management_state: OutputManagementState }
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! # let display_handle = display.handle();
+//! let mut output_management_state = OutputManagementState::new::<State>(&display_handle);
+//!
+//! // advertise an output
+//! # let output = Output::new("output-0".into(), PhysicalProperties {
+//! #     size: (0, 0).into(), subpixel: Subpixel::Unknown, make: "".into(), model: "".into(),
+//! # });
+//! output_management_state.add_head::<State>(&output);
+//!
+//! // after changing the state of the output, send the changes to clients
+//! output_management_state.update::<State>();
+//!
+//! impl OutputManagementHandler for State {
+//!     fn output_management_state(&mut self) -> &mut OutputManagementState {
+//!         &mut self.output_management_state
+//!     }
+//!
+//!     fn test_configuration(&mut self, config: Vec<(Output, OutputConfiguration)>) -> bool {
+//!         // check whether the backend could apply the configuration
+//!         true
+//!     }
+//!
+//!     fn apply_configuration(&mut self, config: Vec<(Output, OutputConfiguration)>) -> bool {
+//!         // apply the configuration to the backend and update the outputs accordingly
+//!         true
+//!     }
+//! }
+//!
+//! delegate_output_management!(State);
+//! ```
+
+use std::sync::Mutex;
+
+use wayland_protocols_wlr::output_management::v1::server::{
+    zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, AdaptiveSyncState, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+
+use crate::{
+    output::{Mode, Output, WeakOutput},
+    utils::{Logical, Physical, Point, Size, Transform},
+};
+
+const MANAGER_VERSION: u32 = 4;
+
+/// Handler for the wlr-output-management protocol
+pub trait OutputManagementHandler:
+    GlobalDispatch<ZwlrOutputManagerV1, OutputManagementGlobalData>
+    + Dispatch<ZwlrOutputManagerV1, ()>
+    + Dispatch<ZwlrOutputHeadV1, OutputHeadData>
+    + Dispatch<ZwlrOutputModeV1, OutputModeData>
+    + Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>
+    + Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData>
+    + 'static
+{
+    /// [`OutputManagementState`] getter
+    fn output_management_state(&mut self) -> &mut OutputManagementState;
+
+    /// A client wants to know whether a configuration could be applied
+    ///
+    /// The configuration contains every head advertised to the client. Return `true`
+    /// if applying the configuration would likely succeed.
+    fn test_configuration(&mut self, config: Vec<(Output, OutputConfiguration)>) -> bool;
+
+    /// A client wants to apply a configuration
+    ///
+    /// The configuration contains every head advertised to the client. If applying the
+    /// configuration succeeds, the compositor is expected to have updated the affected
+    /// [`Output`]s and their enabled state before returning `true`.
+    /// [`OutputManagementState::update`] is called automatically afterwards.
+    ///
+    /// If applying fails, any partial changes should be reverted before returning `false`.
+    fn apply_configuration(&mut self, config: Vec<(Output, OutputConfiguration)>) -> bool;
+}
+
+/// Requested configuration of a single head
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputConfiguration {
+    /// The head should be enabled
+    Enabled {
+        /// The requested mode, if the client requested a change
+        mode: Option<ModeConfiguration>,
+        /// The requested position, if the client requested a change
+        position: Option<Point<i32, Logical>>,
+        /// The requested transform, if the client requested a change
+        transform: Option<Transform>,
+        /// The requested scale, if the client requested a change
+        scale: Option<f64>,
+        /// The requested adaptive sync state, if the client requested a change
+        adaptive_sync: Option<bool>,
+    },
+    /// The head should be disabled
+    Disabled,
+}
+
+/// Requested mode of a head
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeConfiguration {
+    /// One of the modes advertised for the output
+    Mode(Mode),
+    /// A custom mode
+    Custom {
+        /// Size of the mode
+        size: Size<i32, Physical>,
+        /// Refresh rate in millihertz, if the client requested one
+        refresh: Option<i32>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HeadSnapshot {
+    description: String,
+    modes: Vec<Mode>,
+    preferred_mode: Option<Mode>,
+    enabled: bool,
+    current_mode: Option<Mode>,
+    position: Point<i32, Logical>,
+    transform: Transform,
+    scale: f64,
+    adaptive_sync: Option<bool>,
+}
+
+#[derive(Debug)]
+struct HeadInstance {
+    manager: ZwlrOutputManagerV1,
+    head: ZwlrOutputHeadV1,
+    modes: Vec<(Mode, ZwlrOutputModeV1)>,
+}
+
+#[derive(Debug)]
+struct Head {
+    output: Output,
+    enabled: bool,
+    adaptive_sync: Option<bool>,
+    last: HeadSnapshot,
+    instances: Vec<HeadInstance>,
+}
+
+impl Head {
+    fn snapshot(&self) -> HeadSnapshot {
+        HeadSnapshot {
+            description: self.output.description(),
+            modes: self.output.modes(),
+            preferred_mode: self.output.preferred_mode(),
+            enabled: self.enabled,
+            current_mode: self.output.current_mode(),
+            position: self.output.current_location(),
+            transform: self.output.current_transform(),
+            scale: self.output.current_scale().fractional_scale(),
+            adaptive_sync: self.adaptive_sync,
+        }
+    }
+}
+
+impl HeadInstance {
+    fn send_mode<D: OutputManagementHandler>(
+        &mut self,
+        dh: &DisplayHandle,
+        client: &Client,
+        output: &Output,
+        mode: Mode,
+        preferred: bool,
+    ) {
+        let version = self.head.version().min(ZwlrOutputModeV1::interface().version);
+        let Ok(resource) = client.create_resource::<ZwlrOutputModeV1, _, D>(
+            dh,
+            version,
+            OutputModeData {
+                output: output.downgrade(),
+                mode,
+            },
+        ) else {
+            return;
+        };
+
+        self.head.mode(&resource);
+        resource.size(mode.size.w, mode.size.h);
+        if mode.refresh > 0 {
+            resource.refresh(mode.refresh);
+        }
+        if preferred {
+            resource.preferred();
+        }
+        self.modes.push((mode, resource));
+    }
+
+    fn send_current(&self, snapshot: &HeadSnapshot) {
+        if let Some(mode) = snapshot.current_mode {
+            if let Some((_, resource)) = self.modes.iter().find(|(m, _)| *m == mode) {
+                self.head.current_mode(resource);
+            }
+        }
+        self.head.position(snapshot.position.x, snapshot.position.y);
+        self.head.transform(snapshot.transform.into());
+        self.head.scale(snapshot.scale);
+    }
+
+    fn send_adaptive_sync(&self, adaptive_sync: Option<bool>) {
+        if let Some(adaptive_sync) = adaptive_sync {
+            if self.head.version() >= zwlr_output_head_v1::EVT_ADAPTIVE_SYNC_SINCE {
+                self.head.adaptive_sync(if adaptive_sync {
+                    AdaptiveSyncState::Enabled
+                } else {
+                    AdaptiveSyncState::Disabled
+                });
+            }
+        }
+    }
+
+    fn send_all<D: OutputManagementHandler>(
+        &mut self,
+        dh: &DisplayHandle,
+        client: &Client,
+        output: &Output,
+        snapshot: &HeadSnapshot,
+    ) {
+        let physical = output.physical_properties();
+
+        self.head.name(output.name());
+        self.head.description(snapshot.description.clone());
+        if physical.size.w > 0 && physical.size.h > 0 {
+            self.head.physical_size(physical.size.w, physical.size.h);
+        }
+        for &mode in &snapshot.modes {
+            self.send_mode::<D>(dh, client, output, mode, snapshot.preferred_mode == Some(mode));
+        }
+        if self.head.version() >= zwlr_output_head_v1::EVT_MAKE_SINCE {
+            self.head.make(physical.make);
+        }
+        if self.head.version() >= zwlr_output_head_v1::EVT_MODEL_SINCE {
+            self.head.model(physical.model);
+        }
+        self.head.enabled(snapshot.enabled as i32);
+        if snapshot.enabled {
+            self.send_current(snapshot);
+        }
+        self.send_adaptive_sync(snapshot.adaptive_sync);
+    }
+
+    fn send_changes<D: OutputManagementHandler>(
+        &mut self,
+        dh: &DisplayHandle,
+        output: &Output,
+        old: &HeadSnapshot,
+        new: &HeadSnapshot,
+    ) {
+        if old.description != new.description {
+            self.head.description(new.description.clone());
+        }
+
+        if old.modes != new.modes || old.preferred_mode != new.preferred_mode {
+            // The preferred event can only be sent on creation, so modes changing their
+            // preferred state are re-created as well.
+            self.modes.retain(|(mode, resource)| {
+                let keep = new.modes.contains(mode)
+                    && (old.preferred_mode == Some(*mode)) == (new.preferred_mode == Some(*mode));
+                if !keep {
+                    resource.finished();
+                }
+                keep
+            });
+            if let Ok(client) = dh.get_client(self.head.id()) {
+                for &mode in &new.modes {
+                    if self.modes.iter().all(|(m, _)| *m != mode) {
+                        self.send_mode::<D>(dh, &client, output, mode, new.preferred_mode == Some(mode));
+                    }
+                }
+            }
+        }
+
+        if old.enabled != new.enabled {
+            self.head.enabled(new.enabled as i32);
+        }
+        if new.enabled {
+            if !old.enabled || old.modes != new.modes {
+                self.send_current(new);
+            } else {
+                if old.current_mode != new.current_mode {
+                    if let Some(mode) = new.current_mode {
+                        if let Some((_, resource)) = self.modes.iter().find(|(m, _)| *m == mode) {
+                            self.head.current_mode(resource);
+                        }
+                    }
+                }
+                if old.position != new.position {
+                    self.head.position(new.position.x, new.position.y);
+                }
+                if old.transform != new.transform {
+                    self.head.transform(new.transform.into());
+                }
+                if old.scale != new.scale {
+                    self.head.scale(new.scale);
+                }
+            }
+        }
+        if old.adaptive_sync != new.adaptive_sync {
+            self.send_adaptive_sync(new.adaptive_sync);
+        }
+    }
+
+    fn finish(&self) {
+        for (_, mode) in &self.modes {
+            mode.finished();
+        }
+        self.head.finished();
+    }
+}
+
+/// State of the wlr-output-management protocol
+#[derive(Debug)]
+pub struct OutputManagementState {
+    global: GlobalId,
+    dh: DisplayHandle,
+    serial: u32,
+    managers: Vec<ZwlrOutputManagerV1>,
+    heads: Vec<Head>,
+}
+
+impl OutputManagementState {
+    /// Register a new [`ZwlrOutputManagerV1`] global
+    pub fn new<D: OutputManagementHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register a new [`ZwlrOutputManagerV1`] global with a filter
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D: OutputManagementHandler>(
+        dh: &DisplayHandle,
+        filter: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ZwlrOutputManagerV1, _>(
+            MANAGER_VERSION,
+            OutputManagementGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        Self {
+            global,
+            dh: dh.clone(),
+            serial: 0,
+            managers: Vec::new(),
+            heads: Vec::new(),
+        }
+    }
+
+    /// [`ZwlrOutputManagerV1`] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Advertise a new head for the given output
+    ///
+    /// The head is initially considered enabled. Adding an already advertised output does nothing.
+    pub fn add_head<D: OutputManagementHandler>(&mut self, output: &Output) {
+        if self.heads.iter().any(|head| head.output == *output) {
+            return;
+        }
+
+        let mut head = Head {
+            output: output.clone(),
+            enabled: true,
+            adaptive_sync: None,
+            last: HeadSnapshot {
+                description: String::new(),
+                modes: Vec::new(),
+                preferred_mode: None,
+                enabled: true,
+                current_mode: None,
+                position: Point::default(),
+                transform: Transform::Normal,
+                scale: 1.0,
+                adaptive_sync: None,
+            },
+            instances: Vec::new(),
+        };
+        head.last = head.snapshot();
+
+        for manager in &self.managers {
+            if let Some(instance) = create_head_instance::<D>(&self.dh, manager, &head) {
+                head.instances.push(instance);
+            }
+        }
+        self.heads.push(head);
+
+        self.send_done();
+    }
+
+    /// Stop advertising the head of the given output
+    pub fn remove_head(&mut self, output: &Output) {
+        let Some(pos) = self.heads.iter().position(|head| head.output == *output) else {
+            return;
+        };
+
+        let head = self.heads.remove(pos);
+        for instance in &head.instances {
+            instance.finish();
+        }
+
+        self.send_done();
+    }
+
+    /// Returns the outputs currently advertised as heads
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.heads.iter().map(|head| &head.output)
+    }
+
+    /// Set whether the head of the given output is enabled
+    ///
+    /// Changes are sent to clients on the next call to [`OutputManagementState::update`].
+    pub fn set_head_enabled(&mut self, output: &Output, enabled: bool) {
+        if let Some(head) = self.heads.iter_mut().find(|head| head.output == *output) {
+            head.enabled = enabled;
+        }
+    }
+
+    /// Returns whether the head of the given output is enabled
+    ///
+    /// Returns `None` if the output is not advertised.
+    pub fn head_enabled(&self, output: &Output) -> Option<bool> {
+        self.heads
+            .iter()
+            .find(|head| head.output == *output)
+            .map(|head| head.enabled)
+    }
+
+    /// Set the adaptive sync state of the head of the given output
+    ///
+    /// `None` means the state is unknown and will not be advertised.
+    /// Changes are sent to clients on the next call to [`OutputManagementState::update`].
+    pub fn set_adaptive_sync(&mut self, output: &Output, adaptive_sync: Option<bool>) {
+        if let Some(head) = self.heads.iter_mut().find(|head| head.output == *output) {
+            head.adaptive_sync = adaptive_sync;
+        }
+    }
+
+    /// Send any changes of the advertised outputs to clients
+    ///
+    /// This needs to be called after changing the state of an advertised [`Output`]
+    /// or its enabled or adaptive sync state. Pending configurations of clients created
+    /// before the changes are cancelled.
+    pub fn update<D: OutputManagementHandler>(&mut self) {
+        let mut changed = false;
+
+        for head in &mut self.heads {
+            let new = head.snapshot();
+            if new == head.last {
+                continue;
+            }
+
+            for instance in &mut head.instances {
+                instance.send_changes::<D>(&self.dh, &head.output, &head.last, &new);
+            }
+            head.last = new;
+            changed = true;
+        }
+
+        if changed {
+            self.send_done();
+        }
+    }
+
+    fn send_done(&mut self) {
+        self.serial = self.serial.wrapping_add(1);
+        for manager in &self.managers {
+            manager.done(self.serial);
+        }
+    }
+}
+
+fn create_head_instance<D: OutputManagementHandler>(
+    dh: &DisplayHandle,
+    manager: &ZwlrOutputManagerV1,
+    head: &Head,
+) -> Option<HeadInstance> {
+    let client = manager.client()?;
+    let resource = client
+        .create_resource::<ZwlrOutputHeadV1, _, D>(
+            dh,
+            manager.version(),
+            OutputHeadData {
+                output: head.output.downgrade(),
+            },
+        )
+        .ok()?;
+    manager.head(&resource);
+
+    let mut instance = HeadInstance {
+        manager: manager.clone(),
+        head: resource,
+        modes: Vec::new(),
+    };
+    instance.send_all::<D>(dh, &client, &head.output, &head.last);
+
+    Some(instance)
+}
+
+/// Global data of the [`ZwlrOutputManagerV1`] global
+pub struct OutputManagementGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for OutputManagementGlobalData {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputManagementGlobalData")
+            .finish_non_exhaustive()
+    }
+}
+
+/// User data of [`ZwlrOutputHeadV1`] resources
+#[derive(Debug)]
+pub struct OutputHeadData {
+    output: WeakOutput,
+}
+
+/// User data of [`ZwlrOutputModeV1`] resources
+#[derive(Debug)]
+pub struct OutputModeData {
+    output: WeakOutput,
+    mode: Mode,
+}
+
+/// User data of [`ZwlrOutputConfigurationV1`] resources
+#[derive(Debug)]
+pub struct OutputConfigurationData {
+    serial: u32,
+    inner: Mutex<ConfigurationInner>,
+}
+
+#[derive(Debug, Default)]
+struct ConfigurationInner {
+    used: bool,
+    heads: Vec<(WeakOutput, Option<ZwlrOutputConfigurationHeadV1>)>,
+}
+
+/// User data of [`ZwlrOutputConfigurationHeadV1`] resources
+#[derive(Debug)]
+pub struct OutputConfigurationHeadData {
+    output: WeakOutput,
+    inner: Mutex<PendingHeadConfiguration>,
+}
+
+#[derive(Debug, Default)]
+struct PendingHeadConfiguration {
+    mode: Option<ModeConfiguration>,
+    position: Option<Point<i32, Logical>>,
+    transform: Option<Transform>,
+    scale: Option<f64>,
+    adaptive_sync: Option<bool>,
+}
+
+impl<D: OutputManagementHandler> GlobalDispatch<ZwlrOutputManagerV1, OutputManagementGlobalData, D>
+    for OutputManagementState
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ZwlrOutputManagerV1>,
+        _global_data: &OutputManagementGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+
+        let state = state.output_management_state();
+        for head in &mut state.heads {
+            if let Some(instance) = create_head_instance::<D>(dh, &manager, head) {
+                head.instances.push(instance);
+            }
+        }
+        manager.done(state.serial);
+
+        state.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &OutputManagementGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: OutputManagementHandler> Dispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState {
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _data: &(),
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(
+                    id,
+                    OutputConfigurationData {
+                        serial,
+                        inner: Mutex::new(ConfigurationInner::default()),
+                    },
+                );
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                let state = state.output_management_state();
+                state.managers.retain(|m| m != manager);
+                for head in &mut state.heads {
+                    head.instances.retain(|instance| {
+                        if instance.manager == *manager {
+                            instance.finish();
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputManagerV1, _data: &()) {
+        state.output_management_state().managers.retain(|m| m != resource);
+    }
+}
+
+impl<D: OutputManagementHandler> Dispatch<ZwlrOutputHeadV1, OutputHeadData, D> for OutputManagementState {
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _head: &ZwlrOutputHeadV1,
+        request: zwlr_output_head_v1::Request,
+        _data: &OutputHeadData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_head_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputHeadV1, _data: &OutputHeadData) {
+        for head in &mut state.output_management_state().heads {
+            head.instances.retain(|instance| instance.head != *resource);
+        }
+    }
+}
+
+impl<D: OutputManagementHandler> Dispatch<ZwlrOutputModeV1, OutputModeData, D> for OutputManagementState {
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _mode: &ZwlrOutputModeV1,
+        request: zwlr_output_mode_v1::Request,
+        _data: &OutputModeData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_mode_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, resource: &ZwlrOutputModeV1, data: &OutputModeData) {
+        for head in &mut state.output_management_state().heads {
+            if head.output != data.output {
+                continue;
+            }
+            for instance in &mut head.instances {
+                instance.modes.retain(|(_, mode)| mode != resource);
+            }
+        }
+    }
+}
+
+impl<D: OutputManagementHandler> Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData, D>
+    for OutputManagementState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        configuration: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &OutputConfigurationData,
+        _dh: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let output = head.data::<OutputHeadData>().unwrap().output.clone();
+                let config_head = data_init.init(
+                    id,
+                    OutputConfigurationHeadData {
+                        output: output.clone(),
+                        inner: Mutex::new(PendingHeadConfiguration::default()),
+                    },
+                );
+
+                let mut inner = data.inner.lock().unwrap();
+                if inner.used {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyUsed,
+                        "configuration has already been applied or tested",
+                    );
+                    return;
+                }
+                if inner.heads.iter().any(|(o, _)| *o == output) {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+                        "head has already been configured",
+                    );
+                    return;
+                }
+                inner.heads.push((output, Some(config_head)));
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => {
+                let output = head.data::<OutputHeadData>().unwrap().output.clone();
+
+                let mut inner = data.inner.lock().unwrap();
+                if inner.used {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyUsed,
+                        "configuration has already been applied or tested",
+                    );
+                    return;
+                }
+                if inner.heads.iter().any(|(o, _)| *o == output) {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+                        "head has already been configured",
+                    );
+                    return;
+                }
+                inner.heads.push((output, None));
+            }
+            request @ (zwlr_output_configuration_v1::Request::Apply
+            | zwlr_output_configuration_v1::Request::Test) => {
+                let apply = matches!(request, zwlr_output_configuration_v1::Request::Apply);
+
+                let heads = {
+                    let mut inner = data.inner.lock().unwrap();
+                    if inner.used {
+                        configuration.post_error(
+                            zwlr_output_configuration_v1::Error::AlreadyUsed,
+                            "configuration has already been applied or tested",
+                        );
+                        return;
+                    }
+                    inner.used = true;
+                    std::mem::take(&mut inner.heads)
+                };
+
+                let management_state = state.output_management_state();
+                if data.serial != management_state.serial {
+                    configuration.cancelled();
+                    return;
+                }
+
+                if management_state
+                    .heads
+                    .iter()
+                    .any(|head| heads.iter().all(|(o, _)| *o != head.output))
+                {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::UnconfiguredHead,
+                        "not all heads have been configured",
+                    );
+                    return;
+                }
+
+                let config = heads
+                    .into_iter()
+                    .filter_map(|(output, config_head)| {
+                        let output = output.upgrade()?;
+                        let config = match config_head {
+                            Some(config_head) => {
+                                let data = config_head.data::<OutputConfigurationHeadData>().unwrap();
+                                let pending = data.inner.lock().unwrap();
+                                OutputConfiguration::Enabled {
+                                    mode: pending.mode,
+                                    position: pending.position,
+                                    transform: pending.transform,
+                                    scale: pending.scale,
+                                    adaptive_sync: pending.adaptive_sync,
+                                }
+                            }
+                            None => OutputConfiguration::Disabled,
+                        };
+                        Some((output, config))
+                    })
+                    .collect::<Vec<_>>();
+
+                let success = if apply {
+                    state.apply_configuration(config)
+                } else {
+                    state.test_configuration(config)
+                };
+
+                if success {
+                    configuration.succeeded();
+                } else {
+                    configuration.failed();
+                }
+
+                if apply && success {
+                    state.output_management_state().update::<D>();
+                }
+            }
+            zwlr_output_configuration_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D: OutputManagementHandler> Dispatch<ZwlrOutputConfigurationHeadV1, OutputConfigurationHeadData, D>
+    for OutputManagementState
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        config_head: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &OutputConfigurationHeadData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut pending = data.inner.lock().unwrap();
+
+        macro_rules! already_set {
+            ($field:ident, $name:literal) => {
+                if pending.$field.is_some() {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        concat!($name, " has already been set"),
+                    );
+                    return;
+                }
+            };
+        }
+
+        match request {
+            zwlr_output_configuration_head_v1::Request::SetMode { mode } => {
+                already_set!(mode, "mode");
+                let mode_data = mode.data::<OutputModeData>().unwrap();
+                if mode_data.output != data.output {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidMode,
+                        "mode does not belong to the head",
+                    );
+                    return;
+                }
+                pending.mode = Some(ModeConfiguration::Mode(mode_data.mode));
+            }
+            zwlr_output_configuration_head_v1::Request::SetCustomMode {
+                width,
+                height,
+                refresh,
+            } => {
+                already_set!(mode, "mode");
+                if width <= 0 || height <= 0 || refresh < 0 {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidCustomMode,
+                        "invalid custom mode",
+                    );
+                    return;
+                }
+                pending.mode = Some(ModeConfiguration::Custom {
+                    size: (width, height).into(),
+                    refresh: (refresh > 0).then_some(refresh),
+                });
+            }
+            zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
+                already_set!(position, "position");
+                pending.position = Some((x, y).into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { transform } => {
+                already_set!(transform, "transform");
+                let WEnum::Value(transform) = transform else {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidTransform,
+                        "invalid transform",
+                    );
+                    return;
+                };
+                pending.transform = Some(transform.into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { scale } => {
+                already_set!(scale, "scale");
+                if !scale.is_finite() || scale <= 0.0 {
+                    config_head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidScale,
+                        "invalid scale",
+                    );
+                    return;
+                }
+                pending.scale = Some(scale);
+            }
+            zwlr_output_configuration_head_v1::Request::SetAdaptiveSync { state } => {
+                already_set!(adaptive_sync, "adaptive sync");
+                pending.adaptive_sync = Some(match state {
+                    WEnum::Value(AdaptiveSyncState::Enabled) => true,
+                    WEnum::Value(AdaptiveSyncState::Disabled) => false,
+                    _ => {
+                        config_head.post_error(
+                            zwlr_output_configuration_head_v1::Error::InvalidAdaptiveSyncState,
+                            "invalid adaptive sync state",
+                        );
+                        return;
+                    }
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Macro to delegate implementation of the wlr-output-management protocol to [`OutputManagementState`].
+///
+/// You must also implement [`OutputManagementHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_output_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: $crate::wayland::output::management::OutputManagementGlobalData
+        ] => $crate::wayland::output::management::OutputManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::wayland::output::management::OutputManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1: $crate::wayland::output::management::OutputHeadData
+        ] => $crate::wayland::output::management::OutputManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_mode_v1::ZwlrOutputModeV1: $crate::wayland::output::management::OutputModeData
+        ] => $crate::wayland::output::management::OutputManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: $crate::wayland::output::management::OutputConfigurationData
+        ] => $crate::wayland::output::management::OutputManagementState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: $crate::wayland::output::management::OutputConfigurationHeadData
+        ] => $crate::wayland::output::management::OutputManagementState);
+    };
+}
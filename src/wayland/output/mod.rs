@@ -71,6 +71,7 @@
 //! ```
 
 mod handlers;
+pub mod management;
 pub(crate) mod xdg;
 
 use std::mem;
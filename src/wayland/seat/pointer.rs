@@ -487,6 +487,24 @@ impl From<AxisSource> for WlAxisSource {
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown AxisSource {0:?}")]
+pub struct UnknownAxisSource(WlAxisSource);
+
+impl TryFrom<WlAxisSource> for AxisSource {
+    type Error = UnknownAxisSource;
+    #[inline]
+    fn try_from(value: WlAxisSource) -> Result<Self, Self::Error> {
+        match value {
+            WlAxisSource::Wheel => Ok(AxisSource::Wheel),
+            WlAxisSource::Finger => Ok(AxisSource::Finger),
+            WlAxisSource::Continuous => Ok(AxisSource::Continuous),
+            WlAxisSource::WheelTilt => Ok(AxisSource::WheelTilt),
+            x => Err(UnknownAxisSource(x)),
+        }
+    }
+}
+
 impl From<ButtonState> for WlButtonState {
     #[inline]
     fn from(state: ButtonState) -> WlButtonState {
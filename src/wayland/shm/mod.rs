@@ -113,6 +113,8 @@ use wayland_server::{
 
 mod handlers;
 mod pool;
+#[cfg(test)]
+pub(crate) mod test;
 
 use crate::{
     backend::allocator::format::get_bpp,
@@ -120,6 +122,8 @@ use crate::{
 };
 
 use self::pool::Pool;
+#[cfg(feature = "renderer_pixman")]
+pub(crate) use self::pool::PoolAccess;
 
 use super::buffer::BufferHandler;
 
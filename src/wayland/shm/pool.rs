@@ -7,6 +7,7 @@ use std::{
     os::unix::io::{AsFd, BorrowedFd, OwnedFd},
     ptr,
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{channel, Sender},
         LazyLock, OnceLock, RwLock,
     },
@@ -41,7 +42,8 @@ static DROP_THIS: LazyLock<Sender<InnerPool>> = LazyLock::new(|| {
     tx
 });
 
-thread_local!(static SIGBUS_GUARD: Cell<(*const MemMap, bool)> = const { Cell::new((ptr::null_mut(), false)) });
+// The flag is set when a SIGBUS was caught, it is shared with the threads joining the access
+thread_local!(static SIGBUS_GUARD: Cell<(*const MemMap, *const AtomicBool)> = const { Cell::new((ptr::null(), ptr::null())) });
 
 static OLD_SIGBUS_HANDLER: OnceLock<libc::sigaction> = OnceLock::new();
 
@@ -111,28 +113,26 @@ impl InnerPool {
         trace!(fd = ?self.fd, "Buffer access on shm pool");
 
         // Prepare the access
+        let triggered = AtomicBool::new(false);
         SIGBUS_GUARD.with(|guard| {
             let (p, _) = guard.get();
             if !p.is_null() {
                 // Recursive call of this method is not supported
                 panic!("Recursive access to a SHM pool content is not supported.");
             }
-            guard.set((&*pool_guard as *const MemMap, false))
+            guard.set((&*pool_guard as *const MemMap, &triggered as *const AtomicBool))
         });
 
         let t = f(pool_guard.ptr as *const _, pool_guard.size);
 
         // Cleanup Post-access
-        SIGBUS_GUARD.with(|guard| {
-            let (_, triggered) = guard.get();
-            guard.set((ptr::null_mut(), false));
-            if triggered {
-                debug!(fd = ?self.fd, "SIGBUS caught on access on shm pool");
-                Err(())
-            } else {
-                Ok(t)
-            }
-        })
+        SIGBUS_GUARD.with(|guard| guard.set((ptr::null(), ptr::null())));
+        if triggered.load(Ordering::SeqCst) {
+            debug!(fd = ?self.fd, "SIGBUS caught on access on shm pool");
+            Err(())
+        } else {
+            Ok(t)
+        }
     }
 
     #[instrument(level = "trace", skip_all, name = "wayland_shm")]
@@ -147,28 +147,74 @@ impl InnerPool {
         trace!(fd = ?self.fd, "Mutable buffer access on shm pool");
 
         // Prepare the access
+        let triggered = AtomicBool::new(false);
         SIGBUS_GUARD.with(|guard| {
             let (p, _) = guard.get();
             if !p.is_null() {
                 // Recursive call of this method is not supported
                 panic!("Recursive access to a SHM pool content is not supported.");
             }
-            guard.set((&*pool_guard as *const MemMap, false))
+            guard.set((&*pool_guard as *const MemMap, &triggered as *const AtomicBool))
         });
 
         let t = f(pool_guard.ptr, pool_guard.size);
 
         // Cleanup Post-access
+        SIGBUS_GUARD.with(|guard| guard.set((ptr::null(), ptr::null())));
+        if triggered.load(Ordering::SeqCst) {
+            debug!(fd = ?self.fd, "SIGBUS caught on access on shm pool");
+            Err(())
+        } else {
+            Ok(t)
+        }
+    }
+}
+
+/// An access to a pool in progress on some thread, to extend its protection to other threads
+#[cfg(feature = "renderer_pixman")]
+#[derive(Debug, Clone, Copy)]
+pub struct PoolAccess {
+    map: *const MemMap,
+    triggered: *const AtomicBool,
+}
+
+// SAFETY: The pointers are only dereferenced while the access is in progress, see `PoolAccess::join`.
+#[cfg(feature = "renderer_pixman")]
+unsafe impl Send for PoolAccess {}
+#[cfg(feature = "renderer_pixman")]
+unsafe impl Sync for PoolAccess {}
+
+#[cfg(feature = "renderer_pixman")]
+impl PoolAccess {
+    /// The access of the calling thread, if it is accessing the contents of a pool
+    pub fn current() -> Option<PoolAccess> {
+        let (map, triggered) = SIGBUS_GUARD.with(Cell::get);
+        (!map.is_null()).then_some(PoolAccess { map, triggered })
+    }
+
+    /// Calls `f` on the calling thread as part of the access
+    ///
+    /// A SIGBUS caught during `f` is reported by the thread owning the access.
+    ///
+    /// # Safety
+    ///
+    /// The access has to be in progress until this returns.
+    pub unsafe fn join<T, F: FnOnce() -> T>(&self, f: F) -> T {
         SIGBUS_GUARD.with(|guard| {
-            let (_, triggered) = guard.get();
-            guard.set((ptr::null_mut(), false));
-            if triggered {
-                debug!(fd = ?self.fd, "SIGBUS caught on access on shm pool");
-                Err(())
-            } else {
-                Ok(t)
+            if !guard.get().0.is_null() {
+                panic!("Recursive access to a SHM pool content is not supported.");
             }
-        })
+            guard.set((self.map, self.triggered))
+        });
+        // clears the guard, also when unwinding
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                SIGBUS_GUARD.with(|guard| guard.set((ptr::null(), ptr::null())));
+            }
+        }
+        let _reset = Reset;
+        f()
     }
 }
 
@@ -325,12 +371,12 @@ unsafe fn reraise_sigbus() {
 extern "C" fn sigbus_handler(_signum: libc::c_int, info: *mut libc::siginfo_t, _context: *mut libc::c_void) {
     let faulty_ptr = unsafe { siginfo_si_addr(info) } as *mut u8;
     SIGBUS_GUARD.with(|guard| {
-        let (memmap, _) = guard.get();
+        let (memmap, triggered) = guard.get();
         match unsafe { memmap.as_ref() }.map(|m| (m, m.contains(faulty_ptr))) {
             Some((m, true)) => {
                 // we are in a faulty memory pool !
                 // remember that it was faulty
-                guard.set((memmap, true));
+                unsafe { &*triggered }.store(true, Ordering::SeqCst);
                 // nullify the pool
                 if m.nullify().is_err() {
                     // something terrible occurred !
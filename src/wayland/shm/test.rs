@@ -0,0 +1,82 @@
+//! Shm buffers for tests, created without a client speaking the protocol
+
+use std::{io::Write, num::NonZeroUsize, os::unix::net::UnixStream, sync::Arc};
+
+use wayland_server::{
+    backend::{ClientData, ClientId, DisconnectReason},
+    protocol::{wl_buffer::WlBuffer, wl_shm},
+    Display,
+};
+
+use super::{BufferData, Pool, ShmBufferUserData, ShmState};
+use crate::wayland::buffer::BufferHandler;
+
+#[derive(Debug)]
+pub(crate) struct TestState;
+
+impl BufferHandler for TestState {
+    fn buffer_destroyed(&mut self, _buffer: &WlBuffer) {}
+}
+
+wayland_server::delegate_dispatch!(TestState: [WlBuffer: ShmBufferUserData] => ShmState);
+
+struct TestClient;
+
+impl ClientData for TestClient {
+    fn initialized(&self, _client_id: ClientId) {}
+    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
+}
+
+/// A shm buffer in a pool holding `pool`
+///
+/// The display and the connection of the client owning the buffer are kept alive with it.
+pub(crate) struct TestBuffer {
+    pub buffer: WlBuffer,
+    _display: Display<TestState>,
+    _client: UnixStream,
+}
+
+impl TestBuffer {
+    pub fn new(
+        pool: &[u8],
+        offset: i32,
+        width: i32,
+        height: i32,
+        stride: i32,
+        format: wl_shm::Format,
+    ) -> Self {
+        let fd = rustix::fs::memfd_create("smithay-test-shm", rustix::fs::MemfdFlags::CLOEXEC).unwrap();
+        std::fs::File::from(fd.try_clone().unwrap())
+            .write_all(pool)
+            .unwrap();
+        let pool = Pool::new(fd, NonZeroUsize::new(pool.len()).unwrap()).unwrap();
+
+        let display = Display::<TestState>::new().unwrap();
+        let (server, client) = UnixStream::pair().unwrap();
+        let mut handle = display.handle();
+        let client_object = handle.insert_client(server, Arc::new(TestClient)).unwrap();
+        let buffer = client_object
+            .create_resource::<WlBuffer, _, TestState>(
+                &handle,
+                1,
+                ShmBufferUserData {
+                    pool: Arc::new(pool),
+                    data: BufferData {
+                        offset,
+                        width,
+                        height,
+                        stride,
+                        format,
+                    },
+                    destruction_hooks: Default::default(),
+                },
+            )
+            .unwrap();
+
+        TestBuffer {
+            buffer,
+            _display: display,
+            _client: client,
+        }
+    }
+}
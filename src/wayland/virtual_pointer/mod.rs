@@ -0,0 +1,631 @@
+//! Utilities for virtual pointer support
+//!
+//! This module implements the `wlr-virtual-pointer-unstable-v1` protocol, which allows
+//! clients like remote desktop servers or automation tools to emulate a pointer device.
+//!
+//! Requests of virtual pointers are translated into [`InputEvent`]s of the
+//! [`VirtualPointerInputBackend`], which can be processed the same way as events of
+//! any other [`InputBackend`]. Each virtual pointer is exposed as a separate [`VirtualPointer`]
+//! device, announced using [`InputEvent::DeviceAdded`] and [`InputEvent::DeviceRemoved`].
+//!
+//! Absolute motion events are relative to an area chosen by the compositor. Clients may ask for
+//! it to be a specific [`Output`], which is available through [`VirtualPointer::output`].
+//! A typical compositor would map such events like this:
+//!
+//! ```no_run
+//! # use smithay::backend::input::{AbsolutePositionEvent, Event};
+//! # use smithay::utils::{Logical, Point, Rectangle};
+//! # use smithay::wayland::virtual_pointer::VirtualPointerMotionAbsoluteEvent;
+//! # let event: VirtualPointerMotionAbsoluteEvent = todo!();
+//! # let output_geometry = |_: &smithay::output::Output| -> Rectangle<i32, Logical> { todo!() };
+//! # let whole_output_space: Rectangle<i32, Logical> = todo!();
+//! let area = match event.device().output() {
+//!     Some(output) => output_geometry(&output),
+//!     None => whole_output_space,
+//! };
+//! let location: Point<f64, Logical> = event.position_transformed(area.size) + area.loc.to_f64();
+//! ```
+//!
+//! ```
+//! use smithay::delegate_virtual_pointer_manager;
+//! use smithay::backend::input::InputEvent;
+//! use smithay::wayland::virtual_pointer::{
+//!     VirtualPointerHandler, VirtualPointerInputBackend, VirtualPointerManagerState,
+//! };
+//! use smithay::reexports::wayland_server::Display;
+//!
+//! # struct State;
+//! # let mut display = Display::<State>::new().unwrap();
+//! # let display_handle = display.handle();
+//! impl VirtualPointerHandler for State {
+//!     fn virtual_pointer_event(&mut self, event: InputEvent<VirtualPointerInputBackend>) {
+//!         // process the event like the events of any other input backend
+//!     }
+//! }
+//!
+//! // Delegate virtual pointer handling for State to VirtualPointerManagerState.
+//! delegate_virtual_pointer_manager!(State);
+//!
+//! // Create the manager global and add a client filter
+//! // to avoid untrusted clients controlling the pointer
+//! VirtualPointerManagerState::new::<State, _>(&display_handle, |_client| true);
+//! ```
+
+use std::sync::Mutex;
+
+use wayland_protocols_wlr::virtual_pointer::v1::server::{
+    zwlr_virtual_pointer_manager_v1::{self, ZwlrVirtualPointerManagerV1},
+    zwlr_virtual_pointer_v1::{self, ZwlrVirtualPointerV1},
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    protocol::wl_seat::WlSeat,
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+
+use crate::{
+    backend::input::{
+        self, AbsolutePositionEvent, Axis, AxisRelativeDirection, AxisSource, ButtonState, Device,
+        DeviceCapability, InputBackend, InputEvent, PointerAxisEvent, PointerButtonEvent,
+        PointerMotionAbsoluteEvent, PointerMotionEvent, UnusedEvent,
+    },
+    output::{Output, WeakOutput},
+    utils::{Logical, Point, Size},
+};
+
+const MANAGER_VERSION: u32 = 2;
+
+/// Handler trait for virtual pointers
+pub trait VirtualPointerHandler:
+    GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualPointerManagerGlobalData>
+    + Dispatch<ZwlrVirtualPointerManagerV1, ()>
+    + Dispatch<ZwlrVirtualPointerV1, VirtualPointerUserData>
+    + 'static
+{
+    /// A virtual pointer generated an input event
+    fn virtual_pointer_event(&mut self, event: InputEvent<VirtualPointerInputBackend>);
+}
+
+/// State of wlr virtual pointer protocol
+#[derive(Debug)]
+pub struct VirtualPointerManagerState {
+    global: GlobalId,
+}
+
+/// Data associated with a VirtualPointerManager global.
+#[allow(missing_debug_implementations)]
+pub struct VirtualPointerManagerGlobalData {
+    filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+impl VirtualPointerManagerState {
+    /// Initialize a virtual pointer manager global.
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: VirtualPointerHandler,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = VirtualPointerManagerGlobalData {
+            filter: Box::new(filter),
+        };
+        let global = display.create_global::<D, ZwlrVirtualPointerManagerV1, _>(MANAGER_VERSION, data);
+
+        Self { global }
+    }
+
+    /// Get the id of ZwlrVirtualPointerManagerV1 global
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+/// User data of a virtual pointer resource
+#[derive(Debug)]
+pub struct VirtualPointerUserData {
+    seat: Option<WlSeat>,
+    output: Option<WeakOutput>,
+    axis_frame: Mutex<AxisFrame>,
+}
+
+#[derive(Debug, Default)]
+struct AxisFrame {
+    time: u32,
+    source: Option<AxisSource>,
+    amount: (Option<f64>, Option<f64>),
+    amount_v120: (Option<f64>, Option<f64>),
+}
+
+impl AxisFrame {
+    fn is_empty(&self) -> bool {
+        self.amount == (None, None) && self.amount_v120 == (None, None)
+    }
+
+    fn amount_mut(&mut self, axis: Axis) -> &mut Option<f64> {
+        match axis {
+            Axis::Horizontal => &mut self.amount.0,
+            Axis::Vertical => &mut self.amount.1,
+        }
+    }
+
+    fn amount_v120_mut(&mut self, axis: Axis) -> &mut Option<f64> {
+        match axis {
+            Axis::Horizontal => &mut self.amount_v120.0,
+            Axis::Vertical => &mut self.amount_v120.1,
+        }
+    }
+}
+
+/// Marker used to define the [`InputBackend`] types for virtual pointers.
+#[derive(Debug)]
+pub struct VirtualPointerInputBackend;
+
+/// Input device representing a single virtual pointer
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VirtualPointer {
+    pointer: ZwlrVirtualPointerV1,
+}
+
+impl VirtualPointer {
+    /// The seat the client requested the virtual pointer for
+    ///
+    /// If `None`, the compositor should choose a seat.
+    pub fn seat(&self) -> Option<WlSeat> {
+        self.data().and_then(|data| data.seat.clone())
+    }
+
+    /// The output absolute motion events should be mapped to
+    ///
+    /// If `None`, the compositor should choose an area, usually the bounding box of all outputs.
+    pub fn output(&self) -> Option<Output> {
+        self.data()
+            .and_then(|data| data.output.as_ref())
+            .and_then(|output| output.upgrade())
+    }
+
+    /// Access the underlying [`ZwlrVirtualPointerV1`] resource
+    pub fn resource(&self) -> &ZwlrVirtualPointerV1 {
+        &self.pointer
+    }
+
+    fn data(&self) -> Option<&VirtualPointerUserData> {
+        self.pointer.data::<VirtualPointerUserData>()
+    }
+}
+
+impl Device for VirtualPointer {
+    fn id(&self) -> String {
+        format!("wlr-virtual-pointer-{}", self.pointer.id().protocol_id())
+    }
+
+    fn name(&self) -> String {
+        "wlr virtual pointer".to_owned()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        capability == DeviceCapability::Pointer
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn syspath(&self) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+/// Relative motion of a virtual pointer, implementing [`PointerMotionEvent`]
+#[derive(Debug, Clone)]
+pub struct VirtualPointerMotionEvent {
+    device: VirtualPointer,
+    time: u32,
+    delta: Point<f64, Logical>,
+}
+
+impl input::Event<VirtualPointerInputBackend> for VirtualPointerMotionEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointer {
+        self.device.clone()
+    }
+}
+
+impl PointerMotionEvent<VirtualPointerInputBackend> for VirtualPointerMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.x
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.y
+    }
+
+    fn delta_x_unaccel(&self) -> f64 {
+        self.delta.x
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        self.delta.y
+    }
+}
+
+/// Absolute motion of a virtual pointer, implementing [`PointerMotionAbsoluteEvent`]
+///
+/// The position is relative to an extent chosen by the client, use
+/// [`AbsolutePositionEvent::position_transformed`] to map it onto an area.
+#[derive(Debug, Clone)]
+pub struct VirtualPointerMotionAbsoluteEvent {
+    device: VirtualPointer,
+    time: u32,
+    position: Point<u32, Logical>,
+    extent: Size<u32, Logical>,
+}
+
+impl input::Event<VirtualPointerInputBackend> for VirtualPointerMotionAbsoluteEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointer {
+        self.device.clone()
+    }
+}
+
+impl PointerMotionAbsoluteEvent<VirtualPointerInputBackend> for VirtualPointerMotionAbsoluteEvent {}
+impl AbsolutePositionEvent<VirtualPointerInputBackend> for VirtualPointerMotionAbsoluteEvent {
+    fn x(&self) -> f64 {
+        self.position.x as f64
+    }
+
+    fn y(&self) -> f64 {
+        self.position.y as f64
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.position.x as f64 * width as f64 / self.extent.w as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.position.y as f64 * height as f64 / self.extent.h as f64
+    }
+}
+
+/// Button event of a virtual pointer, implementing [`PointerButtonEvent`]
+#[derive(Debug, Clone)]
+pub struct VirtualPointerButtonEvent {
+    device: VirtualPointer,
+    time: u32,
+    button: u32,
+    state: ButtonState,
+}
+
+impl input::Event<VirtualPointerInputBackend> for VirtualPointerButtonEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointer {
+        self.device.clone()
+    }
+}
+
+impl PointerButtonEvent<VirtualPointerInputBackend> for VirtualPointerButtonEvent {
+    fn button_code(&self) -> u32 {
+        self.button
+    }
+
+    fn state(&self) -> ButtonState {
+        self.state
+    }
+}
+
+/// Axis event of a virtual pointer, implementing [`PointerAxisEvent`]
+///
+/// All axis requests between two frame requests of the client are combined into a single event.
+#[derive(Debug, Clone)]
+pub struct VirtualPointerAxisEvent {
+    device: VirtualPointer,
+    time: u32,
+    source: AxisSource,
+    amount: (Option<f64>, Option<f64>),
+    amount_v120: (Option<f64>, Option<f64>),
+}
+
+impl input::Event<VirtualPointerInputBackend> for VirtualPointerAxisEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointer {
+        self.device.clone()
+    }
+}
+
+impl PointerAxisEvent<VirtualPointerInputBackend> for VirtualPointerAxisEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.amount.0,
+            Axis::Vertical => self.amount.1,
+        }
+    }
+
+    fn amount_v120(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.amount_v120.0,
+            Axis::Vertical => self.amount_v120.1,
+        }
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source
+    }
+
+    fn relative_direction(&self, _axis: Axis) -> AxisRelativeDirection {
+        AxisRelativeDirection::Identical
+    }
+}
+
+impl InputBackend for VirtualPointerInputBackend {
+    type Device = VirtualPointer;
+    type KeyboardKeyEvent = UnusedEvent;
+    type PointerAxisEvent = VirtualPointerAxisEvent;
+    type PointerButtonEvent = VirtualPointerButtonEvent;
+    type PointerMotionEvent = VirtualPointerMotionEvent;
+    type PointerMotionAbsoluteEvent = VirtualPointerMotionAbsoluteEvent;
+
+    type GestureSwipeBeginEvent = UnusedEvent;
+    type GestureSwipeUpdateEvent = UnusedEvent;
+    type GestureSwipeEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = UnusedEvent;
+    type GesturePinchUpdateEvent = UnusedEvent;
+    type GesturePinchEndEvent = UnusedEvent;
+    type GestureHoldBeginEvent = UnusedEvent;
+    type GestureHoldEndEvent = UnusedEvent;
+
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+
+    type SwitchToggleEvent = UnusedEvent;
+
+    type SpecialEvent = UnusedEvent;
+}
+
+impl<D: VirtualPointerHandler> GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualPointerManagerGlobalData, D>
+    for VirtualPointerManagerState
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<ZwlrVirtualPointerManagerV1>,
+        _: &VirtualPointerManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &VirtualPointerManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: VirtualPointerHandler> Dispatch<ZwlrVirtualPointerManagerV1, (), D> for VirtualPointerManagerState {
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _resource: &ZwlrVirtualPointerManagerV1,
+        request: zwlr_virtual_pointer_manager_v1::Request,
+        _data: &(),
+        _handle: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (seat, output, id) = match request {
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { seat, id } => (seat, None, id),
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput { seat, output, id } => {
+                let output = output.as_ref().and_then(Output::from_resource);
+                (seat, output, id)
+            }
+            zwlr_virtual_pointer_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let pointer = data_init.init(
+            id,
+            VirtualPointerUserData {
+                seat,
+                output: output.map(|o| o.downgrade()),
+                axis_frame: Mutex::new(AxisFrame::default()),
+            },
+        );
+        state.virtual_pointer_event(InputEvent::DeviceAdded {
+            device: VirtualPointer { pointer },
+        });
+    }
+}
+
+impl<D: VirtualPointerHandler> Dispatch<ZwlrVirtualPointerV1, VirtualPointerUserData, D>
+    for VirtualPointerManagerState
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        resource: &ZwlrVirtualPointerV1,
+        request: zwlr_virtual_pointer_v1::Request,
+        data: &VirtualPointerUserData,
+        _handle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let device = VirtualPointer {
+            pointer: resource.clone(),
+        };
+
+        let axis = |axis: WEnum<_>| match axis.into_result().ok().map(Axis::try_from) {
+            Some(Ok(axis)) => Some(axis),
+            _ => {
+                resource.post_error(zwlr_virtual_pointer_v1::Error::InvalidAxis, "invalid axis");
+                None
+            }
+        };
+
+        match request {
+            zwlr_virtual_pointer_v1::Request::Motion { time, dx, dy } => {
+                state.virtual_pointer_event(InputEvent::PointerMotion {
+                    event: VirtualPointerMotionEvent {
+                        device,
+                        time,
+                        delta: (dx, dy).into(),
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute {
+                time,
+                x,
+                y,
+                x_extent,
+                y_extent,
+            } => {
+                // an empty extent can't be mapped to anything
+                if x_extent == 0 || y_extent == 0 {
+                    return;
+                }
+                state.virtual_pointer_event(InputEvent::PointerMotionAbsolute {
+                    event: VirtualPointerMotionAbsoluteEvent {
+                        device,
+                        time,
+                        position: (x.min(x_extent), y.min(y_extent)).into(),
+                        extent: (x_extent, y_extent).into(),
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::Button {
+                time,
+                button,
+                state: button_state,
+            } => {
+                // unknown button states are ignored, there is no protocol error for them
+                let Some(button_state) = button_state
+                    .into_result()
+                    .ok()
+                    .and_then(|s| ButtonState::try_from(s).ok())
+                else {
+                    return;
+                };
+                state.virtual_pointer_event(InputEvent::PointerButton {
+                    event: VirtualPointerButtonEvent {
+                        device,
+                        time,
+                        button,
+                        state: button_state,
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::Axis {
+                time,
+                axis: wl_axis,
+                value,
+            } => {
+                let Some(axis) = axis(wl_axis) else {
+                    return;
+                };
+                let mut frame = data.axis_frame.lock().unwrap();
+                frame.time = time;
+                *frame.amount_mut(axis) = Some(frame.amount_mut(axis).unwrap_or(0.0) + value);
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete {
+                time,
+                axis: wl_axis,
+                value,
+                discrete,
+            } => {
+                let Some(axis) = axis(wl_axis) else {
+                    return;
+                };
+                let mut frame = data.axis_frame.lock().unwrap();
+                frame.time = time;
+                *frame.amount_mut(axis) = Some(frame.amount_mut(axis).unwrap_or(0.0) + value);
+                *frame.amount_v120_mut(axis) =
+                    Some(frame.amount_v120_mut(axis).unwrap_or(0.0) + discrete as f64 * 120.0);
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop { time, axis: wl_axis } => {
+                let Some(axis) = axis(wl_axis) else {
+                    return;
+                };
+                let mut frame = data.axis_frame.lock().unwrap();
+                frame.time = time;
+                frame.amount_mut(axis).get_or_insert(0.0);
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource { axis_source } => {
+                match axis_source
+                    .into_result()
+                    .ok()
+                    .and_then(|s| AxisSource::try_from(s).ok())
+                {
+                    Some(source) => data.axis_frame.lock().unwrap().source = Some(source),
+                    None => resource.post_error(
+                        zwlr_virtual_pointer_v1::Error::InvalidAxisSource,
+                        "invalid axis source",
+                    ),
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::Frame => {
+                let frame = std::mem::take(&mut *data.axis_frame.lock().unwrap());
+                if frame.is_empty() {
+                    return;
+                }
+                state.virtual_pointer_event(InputEvent::PointerAxis {
+                    event: VirtualPointerAxisEvent {
+                        device,
+                        time: frame.time,
+                        // clients are not required to send a source, wheel is the most likely one
+                        source: frame.source.unwrap_or(AxisSource::Wheel),
+                        amount: frame.amount,
+                        amount_v120: frame.amount_v120,
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ZwlrVirtualPointerV1,
+        _data: &VirtualPointerUserData,
+    ) {
+        state.virtual_pointer_event(InputEvent::DeviceRemoved {
+            device: VirtualPointer {
+                pointer: resource.clone(),
+            },
+        });
+    }
+}
+
+/// Macro to delegate implementation of the wlr virtual pointer protocol to [`VirtualPointerManagerState`].
+///
+/// You must also implement [`VirtualPointerHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_virtual_pointer_manager {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1: $crate::wayland::virtual_pointer::VirtualPointerManagerGlobalData
+        ] => $crate::wayland::virtual_pointer::VirtualPointerManagerState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1: ()
+        ] => $crate::wayland::virtual_pointer::VirtualPointerManagerState);
+
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1: $crate::wayland::virtual_pointer::VirtualPointerUserData
+        ] => $crate::wayland::virtual_pointer::VirtualPointerManagerState);
+    };
+}
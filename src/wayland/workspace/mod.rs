@@ -0,0 +1,907 @@
+//! Utilities for handling the ext-workspace protocol
+//!
+//! This protocol allows clients like task bars to list the workspaces of the compositor and
+//! request to activate, deactivate, remove or move them.
+//!
+//! Workspaces are represented by [`WorkspaceHandle`]s and can be assigned to one
+//! [`WorkspaceGroupHandle`], which in turn are associated with a set of [`Output`]s.
+//! Changes to workspaces and groups are sent to clients immediately, but only take effect
+//! on the client side once [`WorkspaceManagerState::done`] is called. This allows to
+//! update multiple properties atomically.
+//!
+//! Requests of clients are collected until the client commits them and are then passed to
+//! [`WorkspaceHandler::commit_requests`]. Requests for capabilities not advertised by
+//! a workspace or group are ignored.
+//!
+//! To advertise outputs of a group to clients binding an output after the group was created,
+//! call [`WorkspaceManagerState::output_bound`] from
+//! [`OutputHandler::output_bound`](crate::wayland::output::OutputHandler::output_bound).
+//!
+//! ```
+//! use smithay::delegate_workspace;
+//! use smithay::wayland::workspace::{
+//!     GroupCapabilities, WorkspaceCapabilities, WorkspaceHandler, WorkspaceManagerState,
+//!     WorkspaceRequest, WorkspaceState,
+//! };
+//!
+//! # struct State { workspace_state: WorkspaceManagerState }
+//! # let mut display = wayland_server::Display::<State>::new().unwrap();
+//! # let display_handle = display.handle();
+//! let mut workspace_state = WorkspaceManagerState::new::<State>(&display_handle);
+//!
+//! let group = workspace_state.create_workspace_group::<State>(GroupCapabilities::empty());
+//! let workspace = workspace_state.create_workspace::<State>(
+//!     None,
+//!     "1",
+//!     WorkspaceCapabilities::Activate,
+//! );
+//! workspace_state.set_workspace_group(&workspace, Some(&group));
+//! workspace.set_state(WorkspaceState::Active);
+//! workspace_state.done();
+//!
+//! impl WorkspaceHandler for State {
+//!     fn workspace_manager_state(&mut self) -> &mut WorkspaceManagerState {
+//!         &mut self.workspace_state
+//!     }
+//!
+//!     fn commit_requests(&mut self, requests: Vec<WorkspaceRequest>) {
+//!         for request in requests {
+//!             // handle the request
+//!         }
+//!     }
+//! }
+//!
+//! delegate_workspace!(State);
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols::ext::workspace::v1::server::{
+    ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
+    ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
+    ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+};
+use wayland_server::{
+    backend::{ClientId, GlobalId},
+    protocol::wl_output::WlOutput,
+    Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource, Weak,
+};
+
+use crate::{output::Output, utils::user_data::UserDataMap};
+
+pub use ext_workspace_group_handle_v1::GroupCapabilities;
+pub use ext_workspace_handle_v1::{State as WorkspaceState, WorkspaceCapabilities};
+
+/// Handler for the ext-workspace protocol
+pub trait WorkspaceHandler:
+    GlobalDispatch<ExtWorkspaceManagerV1, WorkspaceGlobalData>
+    + Dispatch<ExtWorkspaceManagerV1, WorkspaceManagerData>
+    + Dispatch<ExtWorkspaceGroupHandleV1, WorkspaceGroupData>
+    + Dispatch<ExtWorkspaceHandleV1, WorkspaceData>
+    + 'static
+{
+    /// [`WorkspaceManagerState`] getter
+    fn workspace_manager_state(&mut self) -> &mut WorkspaceManagerState;
+
+    /// A client committed a batch of requests
+    ///
+    /// The requests should be applied in order. Any resulting changes should be followed
+    /// by a call to [`WorkspaceManagerState::done`].
+    fn commit_requests(&mut self, requests: Vec<WorkspaceRequest>);
+}
+
+/// A request of a client regarding workspaces
+#[derive(Debug, Clone)]
+pub enum WorkspaceRequest {
+    /// The workspace should be activated
+    Activate(WorkspaceHandle),
+    /// The workspace should be deactivated
+    Deactivate(WorkspaceHandle),
+    /// The workspace should be removed
+    Remove(WorkspaceHandle),
+    /// The workspace should be moved to another group
+    Assign {
+        /// The workspace to move
+        workspace: WorkspaceHandle,
+        /// The group the workspace should be moved to
+        group: WorkspaceGroupHandle,
+    },
+    /// A new workspace should be created in the group
+    CreateWorkspace {
+        /// The group to create the workspace in
+        group: WorkspaceGroupHandle,
+        /// The requested name of the new workspace
+        name: String,
+    },
+}
+
+#[derive(Debug)]
+struct WorkspaceGroupInner {
+    capabilities: GroupCapabilities,
+    outputs: Vec<Output>,
+    instances: Vec<Weak<ExtWorkspaceGroupHandleV1>>,
+    removed: bool,
+}
+
+/// Handle to a workspace group
+#[derive(Debug, Clone)]
+pub struct WorkspaceGroupHandle {
+    inner: Arc<(Mutex<WorkspaceGroupInner>, UserDataMap)>,
+}
+
+impl PartialEq for WorkspaceGroupHandle {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for WorkspaceGroupHandle {}
+
+impl WorkspaceGroupHandle {
+    /// Retrieve the [`WorkspaceGroupHandle`] from a [`ExtWorkspaceGroupHandleV1`] resource
+    pub fn from_resource(resource: &ExtWorkspaceGroupHandleV1) -> Option<Self> {
+        Some(resource.data::<WorkspaceGroupData>()?.group.clone())
+    }
+
+    /// Returns the capabilities of the group
+    pub fn capabilities(&self) -> GroupCapabilities {
+        self.inner.0.lock().unwrap().capabilities
+    }
+
+    /// Returns the outputs associated with the group
+    pub fn outputs(&self) -> Vec<Output> {
+        self.inner.0.lock().unwrap().outputs.clone()
+    }
+
+    /// Associate an output with the group
+    pub fn add_output(&self, output: &Output) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.removed || inner.outputs.contains(output) {
+            return;
+        }
+
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            let Some(client) = instance.client() else {
+                continue;
+            };
+            for wl_output in output.client_outputs(&client) {
+                instance.output_enter(&wl_output);
+            }
+        }
+        inner.outputs.push(output.clone());
+    }
+
+    /// Remove the association of an output with the group
+    pub fn remove_output(&self, output: &Output) {
+        let mut inner = self.inner.0.lock().unwrap();
+        let Some(pos) = inner.outputs.iter().position(|o| o == output) else {
+            return;
+        };
+
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            let Some(client) = instance.client() else {
+                continue;
+            };
+            for wl_output in output.client_outputs(&client) {
+                instance.output_leave(&wl_output);
+            }
+        }
+        inner.outputs.remove(pos);
+    }
+
+    /// Returns whether the group has been removed
+    pub fn is_removed(&self) -> bool {
+        self.inner.0.lock().unwrap().removed
+    }
+
+    /// Returns the user data of this group
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.inner.1
+    }
+
+    fn instance_for_manager(&self, manager: &ExtWorkspaceManagerV1) -> Option<ExtWorkspaceGroupHandleV1> {
+        self.inner
+            .0
+            .lock()
+            .unwrap()
+            .instances
+            .iter()
+            .filter_map(|i| i.upgrade().ok())
+            .find(|i| {
+                i.data::<WorkspaceGroupData>()
+                    .is_some_and(|data| data.manager == *manager)
+            })
+    }
+}
+
+#[derive(Debug)]
+struct WorkspaceInner {
+    id: Option<String>,
+    name: String,
+    coordinates: Vec<u32>,
+    state: WorkspaceState,
+    capabilities: WorkspaceCapabilities,
+    group: Option<WorkspaceGroupHandle>,
+    instances: Vec<Weak<ExtWorkspaceHandleV1>>,
+    removed: bool,
+}
+
+/// Handle to a workspace
+#[derive(Debug, Clone)]
+pub struct WorkspaceHandle {
+    inner: Arc<(Mutex<WorkspaceInner>, UserDataMap)>,
+}
+
+impl PartialEq for WorkspaceHandle {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for WorkspaceHandle {}
+
+impl WorkspaceHandle {
+    /// Retrieve the [`WorkspaceHandle`] from a [`ExtWorkspaceHandleV1`] resource
+    pub fn from_resource(resource: &ExtWorkspaceHandleV1) -> Option<Self> {
+        Some(resource.data::<WorkspaceData>()?.workspace.clone())
+    }
+
+    /// Returns the stable identifier of the workspace, if any
+    pub fn id(&self) -> Option<String> {
+        self.inner.0.lock().unwrap().id.clone()
+    }
+
+    /// Returns the name of the workspace
+    pub fn name(&self) -> String {
+        self.inner.0.lock().unwrap().name.clone()
+    }
+
+    /// Set the name of the workspace
+    pub fn set_name(&self, name: impl Into<String>) {
+        let mut inner = self.inner.0.lock().unwrap();
+        let name = name.into();
+        if inner.removed || inner.name == name {
+            return;
+        }
+
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            instance.name(name.clone());
+        }
+        inner.name = name;
+    }
+
+    /// Returns the coordinates of the workspace
+    pub fn coordinates(&self) -> Vec<u32> {
+        self.inner.0.lock().unwrap().coordinates.clone()
+    }
+
+    /// Set the coordinates of the workspace in an N-dimensional grid
+    pub fn set_coordinates(&self, coordinates: Vec<u32>) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.removed || inner.coordinates == coordinates {
+            return;
+        }
+
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            instance.coordinates(coordinates_to_bytes(&coordinates));
+        }
+        inner.coordinates = coordinates;
+    }
+
+    /// Returns the state of the workspace
+    pub fn state(&self) -> WorkspaceState {
+        self.inner.0.lock().unwrap().state
+    }
+
+    /// Set the state of the workspace
+    pub fn set_state(&self, state: WorkspaceState) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.removed || inner.state == state {
+            return;
+        }
+
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            instance.state(state);
+        }
+        inner.state = state;
+    }
+
+    /// Returns the capabilities of the workspace
+    pub fn capabilities(&self) -> WorkspaceCapabilities {
+        self.inner.0.lock().unwrap().capabilities
+    }
+
+    /// Set the capabilities of the workspace
+    pub fn set_capabilities(&self, capabilities: WorkspaceCapabilities) {
+        let mut inner = self.inner.0.lock().unwrap();
+        if inner.removed || inner.capabilities == capabilities {
+            return;
+        }
+
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            instance.capabilities(capabilities);
+        }
+        inner.capabilities = capabilities;
+    }
+
+    /// Returns the group the workspace is assigned to
+    pub fn group(&self) -> Option<WorkspaceGroupHandle> {
+        self.inner.0.lock().unwrap().group.clone()
+    }
+
+    /// Returns whether the workspace has been removed
+    pub fn is_removed(&self) -> bool {
+        self.inner.0.lock().unwrap().removed
+    }
+
+    /// Returns the user data of this workspace
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.inner.1
+    }
+
+    fn instances(&self) -> Vec<ExtWorkspaceHandleV1> {
+        self.inner
+            .0
+            .lock()
+            .unwrap()
+            .instances
+            .iter()
+            .filter_map(|i| i.upgrade().ok())
+            .collect()
+    }
+
+    fn send_group_event(&self, group: &WorkspaceGroupHandle, enter: bool) {
+        for instance in self.instances() {
+            let Some(manager) = instance
+                .data::<WorkspaceData>()
+                .and_then(|data| data.manager.upgrade().ok())
+            else {
+                continue;
+            };
+            if let Some(group_instance) = group.instance_for_manager(&manager) {
+                if enter {
+                    group_instance.workspace_enter(&instance);
+                } else {
+                    group_instance.workspace_leave(&instance);
+                }
+            }
+        }
+    }
+}
+
+fn coordinates_to_bytes(coordinates: &[u32]) -> Vec<u8> {
+    coordinates.iter().flat_map(|c| c.to_ne_bytes()).collect()
+}
+
+/// State of the ext-workspace protocol
+#[derive(Debug)]
+pub struct WorkspaceManagerState {
+    global: GlobalId,
+    dh: DisplayHandle,
+    managers: Vec<ExtWorkspaceManagerV1>,
+    groups: Vec<WorkspaceGroupHandle>,
+    workspaces: Vec<WorkspaceHandle>,
+}
+
+impl WorkspaceManagerState {
+    /// Register a new [`ExtWorkspaceManagerV1`] global
+    pub fn new<D: WorkspaceHandler>(dh: &DisplayHandle) -> Self {
+        Self::new_with_filter::<D>(dh, |_| true)
+    }
+
+    /// Register a new [`ExtWorkspaceManagerV1`] global with a filter
+    ///
+    /// Filters can be used to limit visibility of a global to certain clients.
+    pub fn new_with_filter<D: WorkspaceHandler>(
+        dh: &DisplayHandle,
+        filter: impl Fn(&Client) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let global = dh.create_global::<D, ExtWorkspaceManagerV1, _>(
+            1,
+            WorkspaceGlobalData {
+                filter: Box::new(filter),
+            },
+        );
+
+        Self {
+            global,
+            dh: dh.clone(),
+            managers: Vec::new(),
+            groups: Vec::new(),
+            workspaces: Vec::new(),
+        }
+    }
+
+    /// [`ExtWorkspaceManagerV1`] GlobalId getter
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// Returns all current workspace groups
+    pub fn workspace_groups(&self) -> impl Iterator<Item = &WorkspaceGroupHandle> {
+        self.groups.iter()
+    }
+
+    /// Returns all current workspaces
+    pub fn workspaces(&self) -> impl Iterator<Item = &WorkspaceHandle> {
+        self.workspaces.iter()
+    }
+
+    /// Create a new workspace group
+    pub fn create_workspace_group<D: WorkspaceHandler>(
+        &mut self,
+        capabilities: GroupCapabilities,
+    ) -> WorkspaceGroupHandle {
+        let group = WorkspaceGroupHandle {
+            inner: Arc::new((
+                Mutex::new(WorkspaceGroupInner {
+                    capabilities,
+                    outputs: Vec::new(),
+                    instances: Vec::new(),
+                    removed: false,
+                }),
+                UserDataMap::new(),
+            )),
+        };
+
+        for manager in &self.managers {
+            init_group_instance::<D>(&self.dh, manager, &group);
+        }
+        self.groups.push(group.clone());
+
+        group
+    }
+
+    /// Remove a workspace group
+    ///
+    /// Workspaces assigned to the group are unassigned first.
+    pub fn remove_workspace_group(&mut self, group: &WorkspaceGroupHandle) {
+        let Some(pos) = self.groups.iter().position(|g| g == group) else {
+            return;
+        };
+        self.groups.remove(pos);
+
+        for workspace in &self.workspaces {
+            if workspace.group().as_ref() == Some(group) {
+                workspace.send_group_event(group, false);
+                workspace.inner.0.lock().unwrap().group = None;
+            }
+        }
+
+        let mut inner = group.inner.0.lock().unwrap();
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            if let Some(client) = instance.client() {
+                for output in &inner.outputs {
+                    for wl_output in output.client_outputs(&client) {
+                        instance.output_leave(&wl_output);
+                    }
+                }
+            }
+            instance.removed();
+        }
+        inner.instances.clear();
+        inner.removed = true;
+    }
+
+    /// Create a new workspace
+    ///
+    /// `id` can be used to give the workspace an identifier, which stays stable across sessions.
+    pub fn create_workspace<D: WorkspaceHandler>(
+        &mut self,
+        id: Option<String>,
+        name: impl Into<String>,
+        capabilities: WorkspaceCapabilities,
+    ) -> WorkspaceHandle {
+        let workspace = WorkspaceHandle {
+            inner: Arc::new((
+                Mutex::new(WorkspaceInner {
+                    id,
+                    name: name.into(),
+                    coordinates: Vec::new(),
+                    state: WorkspaceState::empty(),
+                    capabilities,
+                    group: None,
+                    instances: Vec::new(),
+                    removed: false,
+                }),
+                UserDataMap::new(),
+            )),
+        };
+
+        for manager in &self.managers {
+            init_workspace_instance::<D>(&self.dh, manager, &workspace);
+        }
+        self.workspaces.push(workspace.clone());
+
+        workspace
+    }
+
+    /// Remove a workspace
+    pub fn remove_workspace(&mut self, workspace: &WorkspaceHandle) {
+        let Some(pos) = self.workspaces.iter().position(|w| w == workspace) else {
+            return;
+        };
+        self.workspaces.remove(pos);
+
+        if let Some(group) = workspace.group() {
+            workspace.send_group_event(&group, false);
+        }
+
+        let mut inner = workspace.inner.0.lock().unwrap();
+        for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+            instance.removed();
+        }
+        inner.instances.clear();
+        inner.group = None;
+        inner.removed = true;
+    }
+
+    /// Assign a workspace to a group, or remove it from its current group
+    pub fn set_workspace_group(&mut self, workspace: &WorkspaceHandle, group: Option<&WorkspaceGroupHandle>) {
+        if workspace.is_removed() || group.is_some_and(|g| g.is_removed()) {
+            return;
+        }
+
+        let old = workspace.group();
+        if old.as_ref() == group {
+            return;
+        }
+
+        if let Some(old) = old {
+            workspace.send_group_event(&old, false);
+        }
+        if let Some(group) = group {
+            workspace.send_group_event(group, true);
+        }
+        workspace.inner.0.lock().unwrap().group = group.cloned();
+    }
+
+    /// Notify the workspace state about a newly bound [`WlOutput`]
+    ///
+    /// This sends `output_enter` for all groups associated with the output.
+    pub fn output_bound(&mut self, output: &Output, wl_output: &WlOutput) {
+        let Some(client) = wl_output.client() else {
+            return;
+        };
+
+        for group in &self.groups {
+            let inner = group.inner.0.lock().unwrap();
+            if !inner.outputs.contains(output) {
+                continue;
+            }
+            for instance in inner.instances.iter().filter_map(|i| i.upgrade().ok()) {
+                if instance.client().as_ref() == Some(&client) {
+                    instance.output_enter(wl_output);
+                }
+            }
+        }
+
+        for manager in &self.managers {
+            if manager.client().as_ref() == Some(&client) {
+                manager.done();
+            }
+        }
+    }
+
+    /// Notify clients, that all pending changes have been sent
+    ///
+    /// This should be called after a batch of changes to workspaces or groups.
+    pub fn done(&mut self) {
+        for manager in &self.managers {
+            manager.done();
+        }
+    }
+}
+
+fn init_group_instance<D: WorkspaceHandler>(
+    dh: &DisplayHandle,
+    manager: &ExtWorkspaceManagerV1,
+    group: &WorkspaceGroupHandle,
+) {
+    let Some(client) = manager.client() else {
+        return;
+    };
+    let Ok(instance) = client.create_resource::<ExtWorkspaceGroupHandleV1, _, D>(
+        dh,
+        manager.version(),
+        WorkspaceGroupData {
+            group: group.clone(),
+            manager: manager.downgrade(),
+        },
+    ) else {
+        return;
+    };
+
+    manager.workspace_group(&instance);
+
+    let mut inner = group.inner.0.lock().unwrap();
+    instance.capabilities(inner.capabilities);
+    for output in &inner.outputs {
+        for wl_output in output.client_outputs(&client) {
+            instance.output_enter(&wl_output);
+        }
+    }
+    inner.instances.push(instance.downgrade());
+}
+
+fn init_workspace_instance<D: WorkspaceHandler>(
+    dh: &DisplayHandle,
+    manager: &ExtWorkspaceManagerV1,
+    workspace: &WorkspaceHandle,
+) {
+    let Some(client) = manager.client() else {
+        return;
+    };
+    let Ok(instance) = client.create_resource::<ExtWorkspaceHandleV1, _, D>(
+        dh,
+        manager.version(),
+        WorkspaceData {
+            workspace: workspace.clone(),
+            manager: manager.downgrade(),
+        },
+    ) else {
+        return;
+    };
+
+    manager.workspace(&instance);
+
+    let group = {
+        let mut inner = workspace.inner.0.lock().unwrap();
+        if let Some(id) = inner.id.clone() {
+            instance.id(id);
+        }
+        instance.name(inner.name.clone());
+        if !inner.coordinates.is_empty() {
+            instance.coordinates(coordinates_to_bytes(&inner.coordinates));
+        }
+        instance.state(inner.state);
+        instance.capabilities(inner.capabilities);
+        inner.instances.push(instance.downgrade());
+        inner.group.clone()
+    };
+
+    if let Some(group_instance) = group.and_then(|group| group.instance_for_manager(manager)) {
+        group_instance.workspace_enter(&instance);
+    }
+}
+
+/// Global data of the [`ExtWorkspaceManagerV1`] global
+pub struct WorkspaceGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+impl std::fmt::Debug for WorkspaceGlobalData {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkspaceGlobalData").finish_non_exhaustive()
+    }
+}
+
+/// User data of [`ExtWorkspaceManagerV1`] resources
+#[derive(Debug, Default)]
+pub struct WorkspaceManagerData {
+    requests: Mutex<Vec<WorkspaceRequest>>,
+}
+
+impl WorkspaceManagerData {
+    fn push(manager: &Weak<ExtWorkspaceManagerV1>, request: WorkspaceRequest) {
+        if let Some(data) = manager
+            .upgrade()
+            .ok()
+            .as_ref()
+            .and_then(|manager| manager.data::<WorkspaceManagerData>())
+        {
+            data.requests.lock().unwrap().push(request);
+        }
+    }
+}
+
+/// User data of [`ExtWorkspaceGroupHandleV1`] resources
+#[derive(Debug)]
+pub struct WorkspaceGroupData {
+    group: WorkspaceGroupHandle,
+    manager: Weak<ExtWorkspaceManagerV1>,
+}
+
+/// User data of [`ExtWorkspaceHandleV1`] resources
+#[derive(Debug)]
+pub struct WorkspaceData {
+    workspace: WorkspaceHandle,
+    manager: Weak<ExtWorkspaceManagerV1>,
+}
+
+impl<D: WorkspaceHandler> GlobalDispatch<ExtWorkspaceManagerV1, WorkspaceGlobalData, D>
+    for WorkspaceManagerState
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _client: &Client,
+        resource: New<ExtWorkspaceManagerV1>,
+        _global_data: &WorkspaceGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, WorkspaceManagerData::default());
+
+        let state = state.workspace_manager_state();
+        for group in &state.groups {
+            init_group_instance::<D>(dh, &manager, group);
+        }
+        for workspace in &state.workspaces {
+            init_workspace_instance::<D>(dh, &manager, workspace);
+        }
+        manager.done();
+
+        state.managers.push(manager);
+    }
+
+    fn can_view(client: Client, global_data: &WorkspaceGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D: WorkspaceHandler> Dispatch<ExtWorkspaceManagerV1, WorkspaceManagerData, D> for WorkspaceManagerState {
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ExtWorkspaceManagerV1,
+        request: ext_workspace_manager_v1::Request,
+        data: &WorkspaceManagerData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_workspace_manager_v1::Request::Commit => {
+                let requests = std::mem::take(&mut *data.requests.lock().unwrap());
+                if !requests.is_empty() {
+                    state.commit_requests(requests);
+                }
+            }
+            ext_workspace_manager_v1::Request::Stop => {
+                state.workspace_manager_state().managers.retain(|m| m != manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        resource: &ExtWorkspaceManagerV1,
+        _data: &WorkspaceManagerData,
+    ) {
+        state.workspace_manager_state().managers.retain(|m| m != resource);
+    }
+}
+
+impl<D: WorkspaceHandler> Dispatch<ExtWorkspaceGroupHandleV1, WorkspaceGroupData, D>
+    for WorkspaceManagerState
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ExtWorkspaceGroupHandleV1,
+        request: ext_workspace_group_handle_v1::Request,
+        data: &WorkspaceGroupData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_workspace_group_handle_v1::Request::CreateWorkspace { workspace } => {
+                if data.group.is_removed()
+                    || !data
+                        .group
+                        .capabilities()
+                        .contains(GroupCapabilities::CreateWorkspace)
+                {
+                    return;
+                }
+                WorkspaceManagerData::push(
+                    &data.manager,
+                    WorkspaceRequest::CreateWorkspace {
+                        group: data.group.clone(),
+                        name: workspace,
+                    },
+                );
+            }
+            ext_workspace_group_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        resource: &ExtWorkspaceGroupHandleV1,
+        data: &WorkspaceGroupData,
+    ) {
+        data.group
+            .inner
+            .0
+            .lock()
+            .unwrap()
+            .instances
+            .retain(|i| i.id() != Resource::id(resource));
+    }
+}
+
+impl<D: WorkspaceHandler> Dispatch<ExtWorkspaceHandleV1, WorkspaceData, D> for WorkspaceManagerState {
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _resource: &ExtWorkspaceHandleV1,
+        request: ext_workspace_handle_v1::Request,
+        data: &WorkspaceData,
+        _dh: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let workspace = &data.workspace;
+        if workspace.is_removed() {
+            // inert object
+            return;
+        }
+        let capabilities = workspace.capabilities();
+
+        let request = match request {
+            ext_workspace_handle_v1::Request::Activate => capabilities
+                .contains(WorkspaceCapabilities::Activate)
+                .then(|| WorkspaceRequest::Activate(workspace.clone())),
+            ext_workspace_handle_v1::Request::Deactivate => capabilities
+                .contains(WorkspaceCapabilities::Deactivate)
+                .then(|| WorkspaceRequest::Deactivate(workspace.clone())),
+            ext_workspace_handle_v1::Request::Remove => capabilities
+                .contains(WorkspaceCapabilities::Remove)
+                .then(|| WorkspaceRequest::Remove(workspace.clone())),
+            ext_workspace_handle_v1::Request::Assign { workspace_group } => {
+                WorkspaceGroupHandle::from_resource(&workspace_group)
+                    .filter(|group| {
+                        capabilities.contains(WorkspaceCapabilities::Assign) && !group.is_removed()
+                    })
+                    .map(|group| WorkspaceRequest::Assign {
+                        workspace: workspace.clone(),
+                        group,
+                    })
+            }
+            ext_workspace_handle_v1::Request::Destroy => None,
+            _ => unreachable!(),
+        };
+
+        if let Some(request) = request {
+            WorkspaceManagerData::push(&data.manager, request);
+        }
+    }
+
+    fn destroyed(_state: &mut D, _client: ClientId, resource: &ExtWorkspaceHandleV1, data: &WorkspaceData) {
+        data.workspace
+            .inner
+            .0
+            .lock()
+            .unwrap()
+            .instances
+            .retain(|i| i.id() != Resource::id(resource));
+    }
+}
+
+/// Macro to delegate implementation of the ext-workspace protocol to [`WorkspaceManagerState`].
+///
+/// You must also implement [`WorkspaceHandler`] to use this.
+#[macro_export]
+macro_rules! delegate_workspace {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        $crate::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::workspace::v1::server::ext_workspace_manager_v1::ExtWorkspaceManagerV1: $crate::wayland::workspace::WorkspaceGlobalData
+        ] => $crate::wayland::workspace::WorkspaceManagerState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::workspace::v1::server::ext_workspace_manager_v1::ExtWorkspaceManagerV1: $crate::wayland::workspace::WorkspaceManagerData
+        ] => $crate::wayland::workspace::WorkspaceManagerState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::workspace::v1::server::ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1: $crate::wayland::workspace::WorkspaceGroupData
+        ] => $crate::wayland::workspace::WorkspaceManagerState);
+        $crate::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            $crate::reexports::wayland_protocols::ext::workspace::v1::server::ext_workspace_handle_v1::ExtWorkspaceHandleV1: $crate::wayland::workspace::WorkspaceData
+        ] => $crate::wayland::workspace::WorkspaceManagerState);
+    };
+}